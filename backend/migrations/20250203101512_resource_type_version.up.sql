@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE resource_type_version (
+	id bigserial PRIMARY KEY,
+	workspace_id varchar(50) NOT NULL,
+	resource_type_name varchar(50) NOT NULL,
+	schema jsonb,
+	created_by varchar(50) NOT NULL,
+	created_at timestamptz NOT NULL DEFAULT now(),
+	FOREIGN KEY (workspace_id, resource_type_name) REFERENCES resource_type (workspace_id, name) ON DELETE CASCADE
+);
+CREATE INDEX index_resource_type_version_name_created_at ON resource_type_version (workspace_id, resource_type_name, created_at);
+
+INSERT INTO resource_type_version (workspace_id, resource_type_name, schema, created_by, created_at)
+SELECT workspace_id, name, schema, COALESCE(created_by, 'system'), COALESCE(edited_at, now()) FROM resource_type;
+
+GRANT ALL ON resource_type_version TO windmill_user;
+GRANT ALL ON resource_type_version_id_seq TO windmill_user;
+GRANT ALL ON resource_type_version TO windmill_admin;
+GRANT ALL ON resource_type_version_id_seq TO windmill_admin;
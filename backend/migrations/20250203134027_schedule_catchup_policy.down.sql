@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE schedule DROP COLUMN catchup_policy;
+DROP TYPE SCHEDULE_CATCHUP_POLICY;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+CREATE TYPE SCHEDULE_CATCHUP_POLICY AS ENUM ('skip', 'run_once', 'run_all');
+ALTER TABLE schedule ADD COLUMN catchup_policy SCHEDULE_CATCHUP_POLICY NOT NULL DEFAULT 'skip';
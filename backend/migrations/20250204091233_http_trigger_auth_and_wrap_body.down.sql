@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE http_trigger
+  DROP COLUMN static_auth_header_name,
+  DROP COLUMN static_auth_header_secret,
+  DROP COLUMN hmac_verification,
+  DROP COLUMN wrap_body;
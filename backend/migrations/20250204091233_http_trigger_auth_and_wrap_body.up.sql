@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE http_trigger
+  ADD COLUMN static_auth_header_name VARCHAR(255),
+  ADD COLUMN static_auth_header_secret TEXT,
+  ADD COLUMN hmac_verification JSONB,
+  ADD COLUMN wrap_body BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS ix_audit_workspace_timestamp_id;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+CREATE INDEX IF NOT EXISTS ix_audit_workspace_timestamp_id ON audit (workspace_id, timestamp DESC, id DESC);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE websocket_trigger
+  DROP COLUMN binary_as_base64,
+  DROP COLUMN batch,
+  DROP COLUMN messages_received,
+  DROP COLUMN jobs_pushed,
+  DROP COLUMN last_push_error,
+  DROP COLUMN last_push_error_at;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+ALTER TABLE websocket_trigger
+  ADD COLUMN binary_as_base64 BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN batch JSONB,
+  ADD COLUMN messages_received BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN jobs_pushed BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN last_push_error TEXT,
+  ADD COLUMN last_push_error_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger
+  DROP COLUMN backfill,
+  DROP COLUMN backfill_where,
+  DROP COLUMN backfill_batch_size,
+  DROP COLUMN backfill_checkpoint;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE postgres_trigger
+  ADD COLUMN backfill BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN backfill_where TEXT,
+  ADD COLUMN backfill_batch_size INTEGER,
+  ADD COLUMN backfill_checkpoint JSONB;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE audit_log_exports;
+DROP TYPE AUDIT_EXPORT_STATUS;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE TYPE AUDIT_EXPORT_STATUS AS ENUM ('running', 'success', 'failure');
+
+CREATE TABLE audit_log_exports (
+    id BIGSERIAL PRIMARY KEY,
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    export_date DATE NOT NULL,
+    status AUDIT_EXPORT_STATUS NOT NULL DEFAULT 'running',
+    object_path TEXT,
+    row_count BIGINT,
+    error TEXT,
+    triggered_by VARCHAR(255) NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    finished_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE (workspace_id, export_date)
+);
+
+GRANT ALL ON audit_log_exports TO windmill_user;
+GRANT ALL ON audit_log_exports TO windmill_admin;
+GRANT ALL ON audit_log_exports_id_seq TO windmill_user;
+GRANT ALL ON audit_log_exports_id_seq TO windmill_admin;
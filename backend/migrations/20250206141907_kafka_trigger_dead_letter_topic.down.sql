@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE kafka_trigger
+  DROP COLUMN dead_letter_topic,
+  DROP COLUMN max_in_flight;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE kafka_trigger
+  ADD COLUMN dead_letter_topic VARCHAR(255),
+  ADD COLUMN max_in_flight INTEGER;
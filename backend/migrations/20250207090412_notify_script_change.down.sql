@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS "notify_script_change" ON "script";
+DROP FUNCTION IF EXISTS "notify_script_change" ();
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE FUNCTION "notify_script_change" ()
+RETURNS TRIGGER AS $$
+DECLARE
+    script_row RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        script_row := OLD;
+    ELSE
+        script_row := NEW;
+    END IF;
+    PERFORM pg_notify(
+        'notify_script_change',
+        json_build_object('workspace_id', script_row.workspace_id, 'path', script_row.path)::text
+    );
+    RETURN NULL;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE OR REPLACE TRIGGER "notify_script_change"
+ AFTER INSERT OR DELETE OR UPDATE OF archived, deleted, content, path ON "script"
+    FOR EACH ROW
+EXECUTE FUNCTION "notify_script_change" ();
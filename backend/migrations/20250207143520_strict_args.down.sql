@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE flow DROP COLUMN strict_args;
+ALTER TABLE script DROP COLUMN strict_args;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE script ADD COLUMN strict_args BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE flow ADD COLUMN strict_args BOOLEAN NOT NULL DEFAULT false;
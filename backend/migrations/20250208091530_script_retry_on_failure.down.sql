@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS script_job_retry;
+ALTER TABLE script DROP COLUMN IF EXISTS retry_on_failure;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE script ADD COLUMN retry_on_failure JSONB;
+
+CREATE TABLE script_job_retry (
+    job_id UUID PRIMARY KEY,
+    workspace_id VARCHAR(50) NOT NULL,
+    original_job_id UUID NOT NULL,
+    attempt INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX script_job_retry_original_job_id_idx ON script_job_retry (original_job_id);
+
+GRANT ALL ON script_job_retry TO windmill_user;
+GRANT ALL ON script_job_retry TO windmill_admin;
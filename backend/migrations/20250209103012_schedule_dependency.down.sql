@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE schedule_run_log;
+ALTER TABLE schedule DROP COLUMN dependency_lookback_secs;
+ALTER TABLE schedule DROP COLUMN depends_on_schedule;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+ALTER TABLE schedule ADD COLUMN depends_on_schedule VARCHAR(255);
+ALTER TABLE schedule ADD COLUMN dependency_lookback_secs INTEGER;
+
+CREATE TABLE schedule_run_log (
+    id BIGSERIAL PRIMARY KEY,
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    schedule_path VARCHAR(255) NOT NULL,
+    job_id UUID NOT NULL,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    skipped_reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX schedule_run_log_schedule_idx ON schedule_run_log (workspace_id, schedule_path, created_at DESC);
+CREATE INDEX schedule_run_log_job_id_idx ON schedule_run_log (job_id);
+
+GRANT ALL ON schedule_run_log TO windmill_user;
+GRANT ALL ON schedule_run_log TO windmill_admin;
+GRANT ALL ON schedule_run_log_id_seq TO windmill_user;
+GRANT ALL ON schedule_run_log_id_seq TO windmill_admin;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN schedule_jitter_enabled;
+ALTER TABLE schedule DROP COLUMN jitter_seconds;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE schedule ADD COLUMN jitter_seconds INTEGER CHECK (jitter_seconds >= 0);
+ALTER TABLE workspace_settings ADD COLUMN schedule_jitter_enabled BOOLEAN NOT NULL DEFAULT true;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- schedules without a valid acl get an admin-only one: only admins and owners of the path (or of
+-- its folder) can edit them until an acl is granted through /schedules/acls. The acls already
+-- granted are kept.
+UPDATE schedule SET extra_perms = '{}'::jsonb
+WHERE extra_perms IS NULL OR jsonb_typeof(extra_perms) != 'object';
+
+UPDATE schedule SET extra_perms = (
+    SELECT COALESCE(jsonb_object_agg(key, value), '{}'::jsonb)
+    FROM jsonb_each(extra_perms)
+    WHERE jsonb_typeof(value) = 'boolean'
+) WHERE EXISTS (
+    SELECT 1 FROM jsonb_each(extra_perms) WHERE jsonb_typeof(value) != 'boolean'
+);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS "notify_workspace_job_limits_change" ON "workspace_settings";
+DROP FUNCTION IF EXISTS "notify_workspace_job_limits_change" ();
+ALTER TABLE workspace_settings DROP COLUMN default_cache_ttl;
+ALTER TABLE workspace_settings DROP COLUMN default_timeout_secs_max;
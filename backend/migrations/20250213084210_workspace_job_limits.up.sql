@@ -0,0 +1,18 @@
+-- Add up migration script here
+ALTER TABLE workspace_settings ADD COLUMN default_timeout_secs_max INTEGER CHECK (default_timeout_secs_max > 0);
+ALTER TABLE workspace_settings ADD COLUMN default_cache_ttl INTEGER CHECK (default_cache_ttl > 0);
+
+CREATE FUNCTION "notify_workspace_job_limits_change" ()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('notify_workspace_job_limits_change', NEW.workspace_id::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE TRIGGER "notify_workspace_job_limits_change"
+ AFTER UPDATE ON "workspace_settings"
+    FOR EACH ROW
+    WHEN (OLD.default_timeout_secs_max IS DISTINCT FROM NEW.default_timeout_secs_max
+        OR OLD.default_cache_ttl IS DISTINCT FROM NEW.default_cache_ttl)
+EXECUTE FUNCTION "notify_workspace_job_limits_change" ();
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE flow_test_data (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    flow_path VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    args JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_by VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, flow_path, name)
+);
+
+GRANT ALL ON flow_test_data TO windmill_user;
+GRANT ALL ON flow_test_data TO windmill_admin;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE schedule DROP COLUMN last_skipped_at;
+ALTER TABLE schedule DROP COLUMN skipped_runs_count;
+ALTER TABLE schedule DROP COLUMN on_conflict;
+ALTER TABLE schedule DROP COLUMN max_concurrent_runs;
+DROP TYPE SCHEDULE_CONFLICT_POLICY;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TYPE SCHEDULE_CONFLICT_POLICY AS ENUM ('skip', 'delay');
+ALTER TABLE schedule ADD COLUMN max_concurrent_runs INTEGER;
+ALTER TABLE schedule ADD COLUMN on_conflict SCHEDULE_CONFLICT_POLICY NOT NULL DEFAULT 'skip';
+ALTER TABLE schedule ADD COLUMN skipped_runs_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE schedule ADD COLUMN last_skipped_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE capture DROP COLUMN replay_job_ids;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE capture ADD COLUMN replay_job_ids UUID[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS input_args_hash_null_idx;
+DROP INDEX IF EXISTS input_args_hash_idx;
+ALTER TABLE input DROP COLUMN is_starred;
+ALTER TABLE input DROP COLUMN last_used_at;
+ALTER TABLE input DROP COLUMN use_count;
+ALTER TABLE input DROP COLUMN args_hash;
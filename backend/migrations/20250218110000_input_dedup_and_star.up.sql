@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE input ADD COLUMN args_hash TEXT;
+ALTER TABLE input ADD COLUMN use_count INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE input ADD COLUMN last_used_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE input ADD COLUMN is_starred BOOLEAN NOT NULL DEFAULT FALSE;
+-- set after adding the column so that existing rows are not stamped with the migration time
+ALTER TABLE input ALTER COLUMN last_used_at SET DEFAULT NOW();
+
+-- existing rows keep a NULL args_hash and last_used_at, the monitor backfills them in batches
+-- (see backfill_input_args_hash) so that the migration does not hold a lock on the whole table
+CREATE INDEX IF NOT EXISTS input_args_hash_idx ON input (workspace_id, runnable_id, runnable_type, args_hash);
+CREATE INDEX IF NOT EXISTS input_args_hash_null_idx ON input (id) WHERE args_hash IS NULL;
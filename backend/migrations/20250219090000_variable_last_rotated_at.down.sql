@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE variable DROP COLUMN IF EXISTS last_rotated_at;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE variable ADD COLUMN IF NOT EXISTS last_rotated_at TIMESTAMPTZ;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE job_upload (
+    workspace_id VARCHAR(50) NOT NULL,
+    job_id UUID NOT NULL,
+    file_key TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (job_id, file_key)
+);
+
+CREATE INDEX job_upload_workspace_created_at_idx ON job_upload (workspace_id, created_at);
+
+GRANT ALL ON job_upload TO windmill_user;
+GRANT ALL ON job_upload TO windmill_admin;
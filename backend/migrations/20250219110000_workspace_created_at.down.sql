@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS workspace_id_name_owner_idx;
+ALTER TABLE workspace DROP COLUMN IF EXISTS created_at;
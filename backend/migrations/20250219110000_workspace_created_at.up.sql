@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- existing workspaces have no known creation date and are left with a NULL created_at
+ALTER TABLE workspace ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ;
+ALTER TABLE workspace ALTER COLUMN created_at SET DEFAULT now();
+
+CREATE INDEX IF NOT EXISTS workspace_id_name_owner_idx ON workspace (id, name, owner);
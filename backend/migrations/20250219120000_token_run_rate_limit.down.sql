@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE token DROP COLUMN IF EXISTS run_rate_limit_per_minute;
+ALTER TABLE token DROP COLUMN IF EXISTS run_rate_limit_burst;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE token ADD COLUMN IF NOT EXISTS run_rate_limit_per_minute INT;
+ALTER TABLE token ADD COLUMN IF NOT EXISTS run_rate_limit_burst INT;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN IF EXISTS reuse_lock_across_paths;
+DROP INDEX IF EXISTS script_lock_import_hash_idx;
+ALTER TABLE script DROP COLUMN IF EXISTS lock_import_hash;
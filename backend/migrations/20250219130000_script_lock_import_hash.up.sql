@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE script ADD COLUMN IF NOT EXISTS lock_import_hash BIGINT;
+CREATE INDEX IF NOT EXISTS script_lock_import_hash_idx ON script (workspace_id, lock_import_hash)
+    WHERE lock_import_hash IS NOT NULL;
+ALTER TABLE workspace_settings ADD COLUMN IF NOT EXISTS reuse_lock_across_paths BOOLEAN NOT NULL DEFAULT false;
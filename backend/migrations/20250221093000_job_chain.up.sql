@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE job_chain (
+    job_id UUID PRIMARY KEY,
+    workspace_id VARCHAR(50) NOT NULL,
+    on_success_path VARCHAR(255),
+    on_failure_path VARCHAR(255),
+    depth INTEGER NOT NULL DEFAULT 0,
+    follow_up_job_id UUID,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+GRANT ALL ON job_chain TO windmill_user;
+GRANT ALL ON job_chain TO windmill_admin;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN IF EXISTS deploy_webhook_secret;
+ALTER TABLE workspace_settings DROP COLUMN IF EXISTS deploy_webhook_url;
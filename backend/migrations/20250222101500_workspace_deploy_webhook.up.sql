@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE workspace_settings ADD COLUMN deploy_webhook_url VARCHAR(1000);
+ALTER TABLE workspace_settings ADD COLUMN deploy_webhook_secret VARCHAR(255);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS "notify_workspace_job_limits_change" ON "workspace_settings";
+CREATE TRIGGER "notify_workspace_job_limits_change"
+ AFTER UPDATE ON "workspace_settings"
+    FOR EACH ROW
+    WHEN (OLD.default_timeout_secs_max IS DISTINCT FROM NEW.default_timeout_secs_max
+        OR OLD.default_cache_ttl IS DISTINCT FROM NEW.default_cache_ttl)
+EXECUTE FUNCTION "notify_workspace_job_limits_change" ();
+
+ALTER TABLE workspace_settings DROP COLUMN max_mem_limit_mb;
+ALTER TABLE queue DROP COLUMN mem_limit_mb;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE queue ADD COLUMN mem_limit_mb INTEGER CHECK (mem_limit_mb > 0);
+ALTER TABLE workspace_settings ADD COLUMN max_mem_limit_mb INTEGER CHECK (max_mem_limit_mb > 0);
+
+DROP TRIGGER IF EXISTS "notify_workspace_job_limits_change" ON "workspace_settings";
+CREATE TRIGGER "notify_workspace_job_limits_change"
+ AFTER UPDATE ON "workspace_settings"
+    FOR EACH ROW
+    WHEN (OLD.default_timeout_secs_max IS DISTINCT FROM NEW.default_timeout_secs_max
+        OR OLD.default_cache_ttl IS DISTINCT FROM NEW.default_cache_ttl
+        OR OLD.max_mem_limit_mb IS DISTINCT FROM NEW.max_mem_limit_mb)
+EXECUTE FUNCTION "notify_workspace_job_limits_change" ();
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE queue DROP COLUMN redact_args;
+ALTER TABLE script DROP COLUMN redact_args;
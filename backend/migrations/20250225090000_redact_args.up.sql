@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE script ADD COLUMN redact_args TEXT[];
+ALTER TABLE queue ADD COLUMN redact_args TEXT[];
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE saved_input (
+    id UUID PRIMARY KEY,
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    path VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    args JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_by VARCHAR(50) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (workspace_id, path, name)
+);
+
+GRANT ALL ON saved_input TO windmill_user;
+GRANT ALL ON saved_input TO windmill_admin;
@@ -0,0 +1,26 @@
+-- Add up migration script here
+CREATE TABLE capture_log (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    trigger_path VARCHAR(255) NOT NULL,
+    trigger_kind TRIGGER_KIND NOT NULL,
+    payload JSONB NOT NULL DEFAULT 'null'::jsonb,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX capture_log_workspace_trigger_path_idx ON capture_log (workspace_id, trigger_path, received_at DESC);
+
+GRANT ALL ON capture_log TO windmill_user;
+GRANT ALL ON capture_log TO windmill_admin;
+
+ALTER TABLE capture_log ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY admin_policy ON capture_log FOR ALL TO windmill_admin USING (true);
+CREATE POLICY see_folder_extra_perms_user_select ON capture_log FOR SELECT TO windmill_user
+USING (SPLIT_PART(capture_log.trigger_path, '/', 1) = 'f' AND SPLIT_PART(capture_log.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_read'), ',')::text[]));
+CREATE POLICY see_folder_extra_perms_user_delete ON capture_log FOR DELETE TO windmill_user
+USING (SPLIT_PART(capture_log.trigger_path, '/', 1) = 'f' AND SPLIT_PART(capture_log.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_write'), ',')::text[]));
+CREATE POLICY see_own ON capture_log FOR ALL TO windmill_user
+USING (SPLIT_PART(capture_log.trigger_path, '/', 1) = 'u' AND SPLIT_PART(capture_log.trigger_path, '/', 2) = current_setting('session.user'));
+CREATE POLICY see_member ON capture_log FOR ALL TO windmill_user
+USING (SPLIT_PART(capture_log.trigger_path, '/', 1) = 'g' AND SPLIT_PART(capture_log.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.groups'), ',')::text[]));
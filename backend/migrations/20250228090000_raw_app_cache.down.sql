@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE raw_app DROP COLUMN IF EXISTS data_hash;
+ALTER TABLE raw_app DROP COLUMN IF EXISTS data_gzip;
+ALTER TABLE raw_app DROP COLUMN IF EXISTS cache_max_age;
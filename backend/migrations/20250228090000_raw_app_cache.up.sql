@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE raw_app ADD COLUMN IF NOT EXISTS data_hash VARCHAR(64);
+ALTER TABLE raw_app ADD COLUMN IF NOT EXISTS data_gzip BYTEA;
+ALTER TABLE raw_app ADD COLUMN IF NOT EXISTS cache_max_age INTEGER;
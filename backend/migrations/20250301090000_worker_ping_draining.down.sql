@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE worker_ping DROP COLUMN IF EXISTS draining;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE worker_ping ADD COLUMN IF NOT EXISTS draining BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE schedule ADD COLUMN IF NOT EXISTS paused_by_bulk_operation BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE workspace_settings ADD COLUMN IF NOT EXISTS strict_tags BOOLEAN NOT NULL DEFAULT false;
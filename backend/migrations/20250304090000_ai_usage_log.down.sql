@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN IF EXISTS ai_daily_budget_usd;
+DROP TABLE IF EXISTS ai_usage_log;
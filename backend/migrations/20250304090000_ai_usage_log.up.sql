@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS ai_usage_log (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    model VARCHAR(255) NOT NULL,
+    prompt_tokens BIGINT NOT NULL,
+    completion_tokens BIGINT NOT NULL,
+    total_tokens BIGINT NOT NULL,
+    cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS ai_usage_log_workspace_created_at_idx ON ai_usage_log (workspace_id, created_at);
+
+GRANT ALL ON ai_usage_log TO windmill_user;
+GRANT ALL ON ai_usage_log TO windmill_admin;
+
+ALTER TABLE workspace_settings ADD COLUMN IF NOT EXISTS ai_daily_budget_usd DOUBLE PRECISION;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS resource_lock (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    path VARCHAR(255) NOT NULL,
+    lock_token VARCHAR(255) NOT NULL,
+    holder_job_id UUID,
+    acquired_by VARCHAR(255) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (workspace_id, path)
+);
+
+GRANT ALL ON resource_lock TO windmill_user;
+GRANT ALL ON resource_lock TO windmill_admin;
+
+ALTER TABLE resource_lock ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY admin_policy ON resource_lock FOR ALL TO windmill_admin USING (true);
+CREATE POLICY see_folder_extra_perms_user ON resource_lock FOR ALL TO windmill_user
+USING (SPLIT_PART(resource_lock.path, '/', 1) = 'f' AND SPLIT_PART(resource_lock.path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_write'), ',')::text[]));
+CREATE POLICY see_own ON resource_lock FOR ALL TO windmill_user
+USING (SPLIT_PART(resource_lock.path, '/', 1) = 'u' AND SPLIT_PART(resource_lock.path, '/', 2) = current_setting('session.user'));
+CREATE POLICY see_member ON resource_lock FOR ALL TO windmill_user
+USING (SPLIT_PART(resource_lock.path, '/', 1) = 'g' AND SPLIT_PART(resource_lock.path, '/', 2) = any(regexp_split_to_array(current_setting('session.groups'), ',')::text[]));
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS igroup_mapping_member;
+DROP TABLE IF EXISTS igroup_mapping;
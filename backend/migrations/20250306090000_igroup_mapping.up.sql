@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS igroup_mapping (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    instance_group VARCHAR(255) NOT NULL,
+    workspace_id VARCHAR(50) NOT NULL,
+    workspace_group VARCHAR(50) NOT NULL,
+    remove_on_unassign BOOLEAN NOT NULL DEFAULT false,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (instance_group, workspace_id, workspace_group),
+    FOREIGN KEY (workspace_id, workspace_group) REFERENCES group_(workspace_id, name) ON DELETE CASCADE
+);
+
+-- workspace group members added by a mapping, the only ones it can remove
+CREATE TABLE IF NOT EXISTS igroup_mapping_member (
+    mapping_id BIGINT NOT NULL REFERENCES igroup_mapping(id) ON DELETE CASCADE,
+    username VARCHAR(50) NOT NULL,
+    PRIMARY KEY (mapping_id, username)
+);
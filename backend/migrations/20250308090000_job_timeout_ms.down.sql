@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE queue DROP COLUMN timeout_ms;
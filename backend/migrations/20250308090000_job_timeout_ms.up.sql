@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE queue ADD COLUMN timeout_ms INTEGER CHECK (timeout_ms > 0);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS "notify_custom_response_headers_change" ON "workspace_settings";
+DROP FUNCTION IF EXISTS "notify_custom_response_headers_change" ();
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE FUNCTION "notify_custom_response_headers_change" ()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('notify_custom_response_headers_change', NEW.workspace_id::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE TRIGGER "notify_custom_response_headers_change"
+ AFTER UPDATE ON "workspace_settings"
+    FOR EACH ROW
+    WHEN (OLD.custom_response_headers IS DISTINCT FROM NEW.custom_response_headers)
+EXECUTE FUNCTION "notify_custom_response_headers_change" ();
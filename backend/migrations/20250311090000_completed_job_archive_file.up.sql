@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE completed_job ADD COLUMN IF NOT EXISTS archive_file VARCHAR(255);
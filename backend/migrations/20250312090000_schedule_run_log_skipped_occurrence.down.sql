@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM schedule_run_log WHERE job_id IS NULL;
+ALTER TABLE schedule_run_log ALTER COLUMN job_id SET NOT NULL;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- occurrences skipped because of the schedule they depend on are never pushed and have no job
+ALTER TABLE schedule_run_log ALTER COLUMN job_id DROP NOT NULL;
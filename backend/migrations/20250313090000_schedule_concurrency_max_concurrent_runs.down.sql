@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE schedule ADD COLUMN skip_if_running BOOLEAN;
+ALTER TABLE schedule ADD COLUMN skipped_runs_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE schedule ADD COLUMN last_skipped_at TIMESTAMPTZ;
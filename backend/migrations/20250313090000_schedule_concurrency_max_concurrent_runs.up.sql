@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- max_concurrent_runs is the only concurrency limit of schedules and schedule_run_log the only
+-- record of their skipped occurrences
+UPDATE schedule SET max_concurrent_runs = 1, on_conflict = 'skip'
+WHERE max_concurrent_runs IS NULL AND (skip_if_running IS TRUE OR no_flow_overlap);
+ALTER TABLE schedule DROP COLUMN skip_if_running;
+ALTER TABLE schedule DROP COLUMN skipped_runs_count;
+ALTER TABLE schedule DROP COLUMN last_skipped_at;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger DROP COLUMN IF EXISTS backfill_where;
+ALTER TABLE postgres_trigger ADD COLUMN IF NOT EXISTS backfill_filter JSONB;
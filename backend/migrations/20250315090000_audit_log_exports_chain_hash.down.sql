@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE audit_log_exports
+  DROP COLUMN prev_chain_hash,
+  DROP COLUMN chain_hash;
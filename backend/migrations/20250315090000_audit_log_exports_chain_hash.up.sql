@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE audit_log_exports
+  ADD COLUMN prev_chain_hash TEXT,
+  ADD COLUMN chain_hash TEXT;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE job_upload
+  DROP COLUMN created_by,
+  DROP COLUMN email;
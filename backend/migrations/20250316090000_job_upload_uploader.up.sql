@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- the object store of the workspace is loaded as the uploader to delete expired uploads, the
+-- uploads recorded before are not collected
+ALTER TABLE job_upload
+  ADD COLUMN created_by VARCHAR(255),
+  ADD COLUMN email VARCHAR(255);
@@ -9,9 +9,9 @@
 use anyhow::Context;
 use monitor::{
     load_base_url, load_otel, reload_delete_logs_periodically_setting, reload_indexer_config,
-    reload_instance_python_version_setting, reload_nuget_config_setting,
-    reload_timeout_wait_result_setting, send_current_log_file_to_object_store,
-    send_logs_to_object_store,
+    reload_instance_python_version_setting, reload_introspection_client_setting,
+    reload_nuget_config_setting, reload_run_rate_limit_setting, reload_timeout_wait_result_setting,
+    send_current_log_file_to_object_store, send_logs_to_object_store,
 };
 use rand::Rng;
 use sqlx::{postgres::PgListener, Pool, Postgres};
@@ -32,22 +32,24 @@ use windmill_common::ee::{maybe_renew_license_key_on_start, LICENSE_KEY_ID, LICE
 
 use windmill_common::{
     global_settings::{
-        BASE_URL_SETTING, BUNFIG_INSTALL_SCOPES_SETTING, CRITICAL_ALERT_MUTE_UI_SETTING,
-        CRITICAL_ERROR_CHANNELS_SETTING, CUSTOM_TAGS_SETTING, DEFAULT_TAGS_PER_WORKSPACE_SETTING,
-        DEFAULT_TAGS_WORKSPACES_SETTING, ENV_SETTINGS, EXPOSE_DEBUG_METRICS_SETTING,
-        EXPOSE_METRICS_SETTING, EXTRA_PIP_INDEX_URL_SETTING, HUB_BASE_URL_SETTING, INDEXER_SETTING,
-        INSTANCE_PYTHON_VERSION_SETTING, JOB_DEFAULT_TIMEOUT_SECS_SETTING, JWT_SECRET_SETTING,
+        AUDIT_RETENTION_PERIOD_SECS_SETTING, BASE_URL_SETTING, BUNFIG_INSTALL_SCOPES_SETTING,
+        CRITICAL_ALERT_MUTE_UI_SETTING, CRITICAL_ERROR_CHANNELS_SETTING, CUSTOM_TAGS_SETTING,
+        DEFAULT_TAGS_PER_WORKSPACE_SETTING, DEFAULT_TAGS_WORKSPACES_SETTING, ENV_SETTINGS,
+        EXPOSE_DEBUG_METRICS_SETTING, EXPOSE_METRICS_SETTING, EXTRA_PIP_INDEX_URL_SETTING,
+        HUB_BASE_URL_SETTING, INDEXER_SETTING, INSTANCE_PYTHON_VERSION_SETTING,
+        INTROSPECTION_CLIENT_SETTING, JOB_DEFAULT_TIMEOUT_SECS_SETTING, JWT_SECRET_SETTING,
         KEEP_JOB_DIR_SETTING, LICENSE_KEY_SETTING, MONITOR_LOGS_ON_OBJECT_STORE_SETTING,
         NPM_CONFIG_REGISTRY_SETTING, NUGET_CONFIG_SETTING, OAUTH_SETTING, OTEL_SETTING,
         PIP_INDEX_URL_SETTING, REQUEST_SIZE_LIMIT_SETTING,
         REQUIRE_PREEXISTING_USER_FOR_OAUTH_SETTING, RETENTION_PERIOD_SECS_SETTING,
-        SAML_METADATA_SETTING, SCIM_TOKEN_SETTING, SMTP_SETTING, TEAMS_SETTING,
-        TIMEOUT_WAIT_RESULT_SETTING,
+        RUN_RATE_LIMIT_SETTING, SAML_METADATA_SETTING, SCIM_TOKEN_SETTING, SMTP_SETTING,
+        TEAMS_SETTING, TIMEOUT_WAIT_RESULT_SETTING,
     },
     scripts::ScriptLang,
     stats_ee::schedule_stats,
     utils::{hostname, rd_string, Mode, GIT_VERSION},
-    worker::{reload_custom_tags_setting, HUB_CACHE_DIR, TMP_DIR, WORKER_GROUP},
+    worker::{reload_custom_tags_setting, HUB_CACHE_DIR, TMP_DIR, WORKER_CONFIG, WORKER_GROUP},
+    workspaces::reload_workspace_job_limits,
     DB, METRICS_ENABLED,
 };
 
@@ -79,17 +81,18 @@ use windmill_worker::{
 use crate::monitor::{
     initial_load, load_keep_job_dir, load_metrics_debug_enabled, load_require_preexisting_user,
     load_tag_per_workspace_enabled, load_tag_per_workspace_workspaces, monitor_db,
+    reload_ai_completion_url_setting, reload_audit_retention_period_setting,
     reload_base_url_setting, reload_bunfig_install_scopes_setting,
     reload_critical_alert_mute_ui_setting, reload_critical_error_channels_setting,
     reload_extra_pip_index_url_setting, reload_hub_base_url_setting,
     reload_job_default_timeout_setting, reload_jwt_secret_setting, reload_license_key,
     reload_npm_config_registry_setting, reload_pip_index_url_setting,
     reload_retention_period_setting, reload_scim_token_setting, reload_smtp_config,
-    reload_worker_config,
+    reload_version_check_url_setting, reload_worker_config,
 };
 
 #[cfg(feature = "parquet")]
-use crate::monitor::reload_s3_cache_setting;
+use crate::monitor::{reload_large_result_threshold_setting, reload_s3_cache_setting};
 
 const DEFAULT_NUM_WORKERS: usize = 1;
 const DEFAULT_PORT: u16 = 8000;
@@ -643,6 +646,9 @@ Windmill Community Edition {GIT_VERSION}
                     )
                     .await?;
                     tracing::info!("All workers exited.");
+                    if WORKER_CONFIG.read().await.drain {
+                        tracing::info!("Worker group drained, shutting down.");
+                    }
                     killpill_tx.send(())?;
                 } else {
                     rx.recv().await?;
@@ -746,9 +752,22 @@ Windmill Community Edition {GIT_VERSION}
                                                 TIMEOUT_WAIT_RESULT_SETTING => {
                                                     reload_timeout_wait_result_setting(&db).await
                                                 },
+                                                RUN_RATE_LIMIT_SETTING => {
+                                                    if let Err(e) = reload_run_rate_limit_setting(&db).await {
+                                                        tracing::error!("Error reloading run rate limit setting: {e:#}");
+                                                    }
+                                                },
+                                                INTROSPECTION_CLIENT_SETTING => {
+                                                    if let Err(e) = reload_introspection_client_setting(&db).await {
+                                                        tracing::error!("Error reloading introspection client setting: {e:#}");
+                                                    }
+                                                },
                                                 RETENTION_PERIOD_SECS_SETTING => {
                                                     reload_retention_period_setting(&db).await
                                                 },
+                                                AUDIT_RETENTION_PERIOD_SECS_SETTING => {
+                                                    reload_audit_retention_period_setting(&db).await
+                                                },
                                                 MONITOR_LOGS_ON_OBJECT_STORE_SETTING => {
                                                     reload_delete_logs_periodically_setting(&db).await
                                                 },
@@ -759,9 +778,19 @@ Windmill Community Edition {GIT_VERSION}
                                                 OBJECT_STORE_CACHE_CONFIG_SETTING if !is_agent => {
                                                     reload_s3_cache_setting(&db).await
                                                 },
+                                                #[cfg(feature = "parquet")]
+                                                windmill_common::global_settings::LARGE_RESULT_THRESHOLD_KB_SETTING if !is_agent => {
+                                                    reload_large_result_threshold_setting(&db).await
+                                                },
                                                 SCIM_TOKEN_SETTING => {
                                                     reload_scim_token_setting(&db).await
                                                 },
+                                                windmill_common::global_settings::VERSION_CHECK_URL_SETTING => {
+                                                    reload_version_check_url_setting(&db).await
+                                                },
+                                                windmill_common::global_settings::AI_COMPLETION_URL_SETTING => {
+                                                    reload_ai_completion_url_setting(&db).await
+                                                },
                                                 EXTRA_PIP_INDEX_URL_SETTING => {
                                                     reload_extra_pip_index_url_setting(&db).await
                                                 },
@@ -852,6 +881,18 @@ Windmill Community Edition {GIT_VERSION}
                                                 }
                                             }
                                         },
+                                        "notify_workspace_job_limits_change" => {
+                                            tracing::info!("Workspace job limits change detected: {}", n.payload());
+                                            if let Err(e) = reload_workspace_job_limits(&db, Some(n.payload())).await {
+                                                tracing::error!(error = %e, "Could not reload workspace job limits");
+                                            }
+                                        },
+                                        "notify_custom_response_headers_change" => {
+                                            tracing::info!("Workspace custom response headers change detected: {}", n.payload());
+                                            if let Err(e) = windmill_api::reload_custom_response_headers(&db, Some(n.payload())).await {
+                                                tracing::error!(error = %e, "Could not reload workspace custom response headers");
+                                            }
+                                        },
                                         _ => {
                                             tracing::warn!("Unknown notification received");
                                             continue;
@@ -937,7 +978,12 @@ async fn listen_pg(db: &DB) -> Option<PgListener> {
     };
 
     if let Err(e) = listener
-        .listen_all(vec!["notify_config_change", "notify_global_setting_change"])
+        .listen_all(vec![
+            "notify_config_change",
+            "notify_global_setting_change",
+            "notify_workspace_job_limits_change",
+            "notify_custom_response_headers_change",
+        ])
         .await
     {
         tracing::error!(error = %e, "Could not listen to database");
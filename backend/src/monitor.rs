@@ -5,7 +5,7 @@ use std::{
     ops::Mul,
     str::FromStr,
     sync::{
-        atomic::{AtomicU16, Ordering},
+        atomic::{AtomicBool, AtomicU16, Ordering},
         Arc,
     },
     time::Duration,
@@ -23,12 +23,15 @@ use tokio::{
 #[cfg(feature = "embedding")]
 use windmill_api::embeddings::update_embeddings_db;
 use windmill_api::{
-    jobs::TIMEOUT_WAIT_RESULT, DEFAULT_BODY_LIMIT, IS_SECURE, REQUEST_SIZE_LIMIT, SAML_METADATA,
-    SCIM_TOKEN,
+    jobs::TIMEOUT_WAIT_RESULT,
+    rate_limit::{RunRateLimit, RUN_RATE_LIMIT},
+    reload_custom_response_headers, IntrospectionClient, DEFAULT_BODY_LIMIT, INTROSPECTION_CLIENT,
+    IS_SECURE, REQUEST_SIZE_LIMIT, SAML_METADATA, SCIM_TOKEN,
 };
 
 #[cfg(feature = "enterprise")]
 use windmill_common::ee::{jobs_waiting_alerts, worker_groups_alerts};
+use windmill_common::large_results::delete_large_results;
 
 #[cfg(feature = "oauth2")]
 use windmill_common::global_settings::OAUTH_SETTING;
@@ -38,15 +41,17 @@ use windmill_common::{
     error,
     flow_status::FlowStatusModule,
     global_settings::{
-        BASE_URL_SETTING, BUNFIG_INSTALL_SCOPES_SETTING, CRITICAL_ALERT_MUTE_UI_SETTING,
-        CRITICAL_ERROR_CHANNELS_SETTING, DEFAULT_TAGS_PER_WORKSPACE_SETTING,
-        DEFAULT_TAGS_WORKSPACES_SETTING, EXPOSE_DEBUG_METRICS_SETTING, EXPOSE_METRICS_SETTING,
-        EXTRA_PIP_INDEX_URL_SETTING, HUB_BASE_URL_SETTING, INSTANCE_PYTHON_VERSION_SETTING,
+        AUDIT_RETENTION_PERIOD_SECS_SETTING, BASE_URL_SETTING, BUNFIG_INSTALL_SCOPES_SETTING,
+        CRITICAL_ALERT_MUTE_UI_SETTING, CRITICAL_ERROR_CHANNELS_SETTING,
+        DEFAULT_TAGS_PER_WORKSPACE_SETTING, DEFAULT_TAGS_WORKSPACES_SETTING,
+        EXPOSE_DEBUG_METRICS_SETTING, EXPOSE_METRICS_SETTING, EXTRA_PIP_INDEX_URL_SETTING,
+        HUB_BASE_URL_SETTING, INSTANCE_PYTHON_VERSION_SETTING, INTROSPECTION_CLIENT_SETTING,
         JOB_DEFAULT_TIMEOUT_SECS_SETTING, JWT_SECRET_SETTING, KEEP_JOB_DIR_SETTING,
         LICENSE_KEY_SETTING, MONITOR_LOGS_ON_OBJECT_STORE_SETTING, NPM_CONFIG_REGISTRY_SETTING,
         NUGET_CONFIG_SETTING, OTEL_SETTING, PIP_INDEX_URL_SETTING, REQUEST_SIZE_LIMIT_SETTING,
         REQUIRE_PREEXISTING_USER_FOR_OAUTH_SETTING, RETENTION_PERIOD_SECS_SETTING,
-        SAML_METADATA_SETTING, SCIM_TOKEN_SETTING, TIMEOUT_WAIT_RESULT_SETTING,
+        RUN_RATE_LIMIT_SETTING, SAML_METADATA_SETTING, SCIM_TOKEN_SETTING,
+        TIMEOUT_WAIT_RESULT_SETTING,
     },
     indexer::load_indexer_config,
     jobs::QueuedJob,
@@ -60,8 +65,9 @@ use windmill_common::{
         update_min_version, DEFAULT_TAGS_PER_WORKSPACE, DEFAULT_TAGS_WORKSPACES, INDEXER_CONFIG,
         SMTP_CONFIG, TMP_DIR, WORKER_CONFIG, WORKER_GROUP,
     },
-    BASE_URL, CRITICAL_ALERT_MUTE_UI_ENABLED, CRITICAL_ERROR_CHANNELS, DB, DEFAULT_HUB_BASE_URL,
-    HUB_BASE_URL, JOB_RETENTION_SECS, METRICS_DEBUG_ENABLED, METRICS_ENABLED,
+    workspaces::reload_workspace_job_limits,
+    AUDIT_RETENTION_SECS, BASE_URL, CRITICAL_ALERT_MUTE_UI_ENABLED, CRITICAL_ERROR_CHANNELS, DB,
+    DEFAULT_HUB_BASE_URL, HUB_BASE_URL, JOB_RETENTION_SECS, METRICS_DEBUG_ENABLED, METRICS_ENABLED,
     MONITOR_LOGS_ON_OBJECT_STORE, OTEL_LOGS_ENABLED, OTEL_METRICS_ENABLED, OTEL_TRACING_ENABLED,
     SERVICE_LOG_RETENTION_SECS,
 };
@@ -159,6 +165,9 @@ pub async fn initial_load(
 
     if server_mode {
         load_require_preexisting_user(db).await;
+        if let Err(e) = reload_custom_response_headers(db, None).await {
+            tracing::error!("Error loading custom response headers: {e:#}");
+        }
     }
 
     if worker_mode {
@@ -170,6 +179,10 @@ pub async fn initial_load(
         tracing::error!("Error reloading custom tags: {:?}", e)
     }
 
+    if let Err(e) = reload_workspace_job_limits(db, None).await {
+        tracing::error!("Error loading workspace job limits: {e:#}");
+    }
+
     if let Err(e) = reload_hub_base_url_setting(db, server_mode).await {
         tracing::error!("Error reloading hub base url: {:?}", e)
     }
@@ -181,15 +194,25 @@ pub async fn initial_load(
     #[cfg(feature = "parquet")]
     if !_is_agent {
         reload_s3_cache_setting(&db).await;
+        reload_large_result_threshold_setting(&db).await;
     }
 
     reload_smtp_config(&db).await;
 
     if server_mode {
         reload_retention_period_setting(&db).await;
+        reload_audit_retention_period_setting(&db).await;
         reload_request_size(&db).await;
         reload_saml_metadata_setting(&db).await;
         reload_scim_token_setting(&db).await;
+        reload_version_check_url_setting(&db).await;
+        reload_ai_completion_url_setting(&db).await;
+        if let Err(e) = reload_run_rate_limit_setting(&db).await {
+            tracing::error!("Error loading run rate limit setting: {e:#}");
+        }
+        if let Err(e) = reload_introspection_client_setting(&db).await {
+            tracing::error!("Error loading introspection client setting: {e:#}");
+        }
     }
 
     if worker_mode {
@@ -711,6 +734,21 @@ pub async fn delete_expired_items(db: &DB) -> () {
         Err(e) => tracing::error!("Error deleting cache resource {}", e.to_string()),
     }
 
+    let deleted_resource_locks = sqlx::query_scalar::<_, String>(
+        "DELETE FROM resource_lock WHERE expires_at <= now() RETURNING path",
+    )
+    .fetch_all(db)
+    .await;
+
+    match deleted_resource_locks {
+        Ok(res) => {
+            if res.len() > 0 {
+                tracing::info!("deleted {} expired resource locks {:?}", res.len(), res)
+            }
+        }
+        Err(e) => tracing::error!("Error deleting expired resource locks {}", e.to_string()),
+    }
+
     match sqlx::query_as!(
         LogFile,
         "DELETE FROM log_file WHERE log_ts <= now() - ($1::bigint::text || ' s')::interval RETURNING file_path, hostname",
@@ -730,19 +768,51 @@ pub async fn delete_expired_items(db: &DB) -> () {
         Err(e) => tracing::error!("Error deleting log file: {:?}", e),
     }
 
+    let audit_retention_secs = *AUDIT_RETENTION_SECS.read().await;
+    if audit_retention_secs > 0 {
+        match sqlx::query(
+            "DELETE FROM audit WHERE timestamp <= now() - ($1::bigint::text || ' s')::interval",
+        )
+        .bind(audit_retention_secs)
+        .execute(db)
+        .await
+        {
+            Ok(res) => {
+                if res.rows_affected() > 0 {
+                    tracing::info!(
+                        "deleted {} audit logs older than AUDIT_RETENTION_SECS {}",
+                        res.rows_affected(),
+                        audit_retention_secs
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Error deleting expired audit logs: {:?}", e),
+        }
+    }
+
     let job_retention_secs = *JOB_RETENTION_SECS.read().await;
     if job_retention_secs > 0 {
         match db.begin().await {
             Ok(mut tx) => {
-                let deleted_jobs = sqlx::query_scalar!(
-                            "DELETE FROM completed_job WHERE created_at <= now() - ($1::bigint::text || ' s')::interval  AND started_at + ((duration_ms/1000 + $1::bigint) || ' s')::interval <= now() RETURNING id",
+                let deleted_jobs = sqlx::query!(
+                            "DELETE FROM completed_job WHERE created_at <= now() - ($1::bigint::text || ' s')::interval  AND started_at + ((duration_ms/1000 + $1::bigint) || ' s')::interval <= now() \
+                            RETURNING id, result->'__windmill_large_result'->>'s3' as large_result, archive_file",
                             job_retention_secs
                         )
                         .fetch_all(&mut *tx)
                         .await;
 
+                let mut large_results = vec![];
+                let mut archive_files = vec![];
                 match deleted_jobs {
                     Ok(deleted_jobs) => {
+                        let mut deleted_ids = Vec::with_capacity(deleted_jobs.len());
+                        for job in deleted_jobs {
+                            deleted_ids.push(job.id);
+                            large_results.extend(job.large_result);
+                            archive_files.extend(job.archive_file);
+                        }
+                        let deleted_jobs = deleted_ids;
                         if deleted_jobs.len() > 0 {
                             tracing::info!(
                                 "deleted {} jobs completed JOB_RETENTION_SECS {} ago: {:?}",
@@ -801,7 +871,11 @@ pub async fn delete_expired_items(db: &DB) -> () {
                 }
 
                 match tx.commit().await {
-                    Ok(_) => (),
+                    Ok(_) => {
+                        delete_large_results(large_results).await;
+                        windmill_api::job_archive::delete_unreferenced_archives(db, archive_files)
+                            .await;
+                    }
                     Err(err) => tracing::error!("Error deleting expired jobs: {:?}", err),
                 }
             }
@@ -963,6 +1037,22 @@ pub async fn reload_retention_period_setting(db: &DB) {
         tracing::error!("Error reloading retention period: {:?}", e)
     }
 }
+
+pub async fn reload_audit_retention_period_setting(db: &DB) {
+    if let Err(e) = reload_setting(
+        db,
+        AUDIT_RETENTION_PERIOD_SECS_SETTING,
+        "AUDIT_RETENTION_SECS",
+        0,
+        AUDIT_RETENTION_SECS.clone(),
+        |x| x,
+    )
+    .await
+    {
+        tracing::error!("Error reloading audit retention period: {:?}", e)
+    }
+}
+
 pub async fn reload_delete_logs_periodically_setting(db: &DB) {
     if let Err(e) = reload_setting(
         db,
@@ -1043,6 +1133,37 @@ pub async fn reload_job_default_timeout_setting(db: &DB) {
     .await;
 }
 
+#[cfg(feature = "parquet")]
+pub async fn reload_large_result_threshold_setting(db: &DB) {
+    reload_option_setting_with_tracing(
+        db,
+        windmill_common::global_settings::LARGE_RESULT_THRESHOLD_KB_SETTING,
+        "LARGE_RESULT_THRESHOLD_KB",
+        windmill_common::large_results::LARGE_RESULT_THRESHOLD_KB.clone(),
+    )
+    .await;
+}
+
+pub async fn reload_version_check_url_setting(db: &DB) {
+    reload_option_setting_with_tracing(
+        db,
+        windmill_common::global_settings::VERSION_CHECK_URL_SETTING,
+        "VERSION_CHECK_URL",
+        windmill_api::version_check::VERSION_CHECK_URL.clone(),
+    )
+    .await;
+}
+
+pub async fn reload_ai_completion_url_setting(db: &DB) {
+    reload_option_setting_with_tracing(
+        db,
+        windmill_common::global_settings::AI_COMPLETION_URL_SETTING,
+        "AI_COMPLETION_URL",
+        windmill_api::ai::AI_COMPLETION_URL.clone(),
+    )
+    .await;
+}
+
 pub async fn reload_request_size(db: &DB) {
     if let Err(e) = reload_setting(
         db,
@@ -1181,10 +1302,13 @@ pub async fn reload_setting<T: FromStr + DeserializeOwned + Display>(
     Ok(())
 }
 
+/// Metrics of the db pool and of the tokio runtime the task is spawned on. The busy duration and
+/// blocking threads of the runtime are only available when built with `--cfg tokio_unstable`
 #[cfg(feature = "prometheus")]
 pub async fn monitor_pool(db: &DB) {
     if METRICS_ENABLED.load(Ordering::Relaxed) {
         let db = db.clone();
+        let runtime_metrics = tokio::runtime::Handle::current().metrics();
         tokio::spawn(async move {
             let active_pool_connections: prometheus::IntGauge = prometheus::register_int_gauge!(
                 "pool_connections_active",
@@ -1204,16 +1328,50 @@ pub async fn monitor_pool(db: &DB) {
             )
             .unwrap();
 
+            let inject_queue_depth: prometheus::IntGauge = prometheus::register_int_gauge!(
+                "tokio_inject_queue_depth",
+                "Number of tasks in the global queue of the tokio runtime"
+            )
+            .unwrap();
+
+            #[cfg(tokio_unstable)]
+            let worker_busy_duration_total: prometheus::Gauge = prometheus::register_gauge!(
+                "tokio_worker_busy_duration_total",
+                "Total time in seconds the workers of the tokio runtime have been busy"
+            )
+            .unwrap();
+
+            #[cfg(tokio_unstable)]
+            let blocking_threads: prometheus::IntGauge = prometheus::register_int_gauge!(
+                "tokio_blocking_threads",
+                "Number of blocking threads spawned by the tokio runtime"
+            )
+            .unwrap();
+
             max_pool_connections.set(db.options().get_max_connections() as i64);
             loop {
                 active_pool_connections.set(db.size() as i64);
                 idle_pool_connections.set(db.num_idle() as i64);
+
+                inject_queue_depth.set(runtime_metrics.global_queue_depth() as i64);
+                #[cfg(tokio_unstable)]
+                {
+                    worker_busy_duration_total.set(
+                        (0..runtime_metrics.num_workers())
+                            .map(|i| runtime_metrics.worker_total_busy_duration(i))
+                            .sum::<Duration>()
+                            .as_secs_f64(),
+                    );
+                    blocking_threads.set(runtime_metrics.num_blocking_threads() as i64);
+                }
                 tokio::time::sleep(Duration::from_secs(30)).await;
             }
         });
     }
 }
 
+static AUDIT_EXPORT_RUNNING: AtomicBool = AtomicBool::new(false);
+
 pub async fn monitor_db(
     db: &Pool<Postgres>,
     base_internal_url: &str,
@@ -1236,6 +1394,7 @@ pub async fn monitor_db(
     let expired_items_f = async {
         if server_mode && !initial_load {
             delete_expired_items(&db).await;
+            windmill_api::inputs::backfill_input_args_hash(&db).await;
         }
     };
 
@@ -1279,6 +1438,40 @@ pub async fn monitor_db(
         update_min_version(db).await;
     };
 
+    let audit_export_f = async {
+        // the export uploads a day of logs per workspace, it runs in the background so that it does
+        // not hold up the monitor and a new one only starts once the previous one is done
+        if server_mode && !initial_load && !AUDIT_EXPORT_RUNNING.swap(true, Ordering::SeqCst) {
+            let db = db.clone();
+            tokio::spawn(async move {
+                windmill_api::workspaces_export::export_audit_logs_to_object_store(&db).await;
+                AUDIT_EXPORT_RUNNING.store(false, Ordering::SeqCst);
+            });
+        }
+    };
+
+    let live_worker_tags_f = async {
+        if server_mode {
+            if let Err(e) = windmill_api::workers::refresh_live_worker_tags(db).await {
+                tracing::error!("Error refreshing the tags of live workers: {e:#}");
+            }
+        }
+    };
+
+    let job_archive_f = async {
+        #[cfg(feature = "parquet")]
+        if server_mode && !initial_load {
+            windmill_api::job_archive::archive_completed_jobs(db).await;
+        }
+    };
+
+    let expired_uploads_f = async {
+        #[cfg(feature = "parquet")]
+        if server_mode && !initial_load {
+            windmill_api::args::delete_expired_uploads(db).await;
+        }
+    };
+
     join!(
         expired_items_f,
         zombie_jobs_f,
@@ -1288,6 +1481,10 @@ pub async fn monitor_db(
         jobs_waiting_alerts_f,
         apply_autoscaling_f,
         update_min_worker_version_f,
+        audit_export_f,
+        job_archive_f,
+        expired_uploads_f,
+        live_worker_tags_f,
     );
 }
 
@@ -1423,6 +1620,14 @@ pub async fn reload_worker_config(
                     }
                 }
             }
+
+            if (*wc).drain != config.drain {
+                if config.drain {
+                    tracing::info!("Worker group set to drain, workers stop pulling jobs and exit once idle, shutting down once all have exited.");
+                } else {
+                    tracing::info!("Worker group not draining anymore, workers still alive resume pulling jobs.");
+                }
+            }
             drop(wc);
 
             let mut wc = WORKER_CONFIG.write().await;
@@ -1833,6 +2038,49 @@ pub async fn reload_critical_error_channels_setting(db: &DB) -> error::Result<()
     Ok(())
 }
 
+pub async fn reload_run_rate_limit_setting(db: &DB) -> error::Result<()> {
+    let run_rate_limit = load_value_from_global_settings(db, RUN_RATE_LIMIT_SETTING).await?;
+
+    let run_rate_limit = if let Some(q) = run_rate_limit {
+        match serde_json::from_value::<RunRateLimit>(q.clone()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                tracing::error!(
+                    "Could not parse run_rate_limit setting: {e:#}, found: {:#?}",
+                    &q
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut l = RUN_RATE_LIMIT.write().await;
+    *l = run_rate_limit;
+
+    Ok(())
+}
+
+/// Not loaded with `reload_option_setting` which logs the loaded value, the client secret must
+/// not end up in the logs
+pub async fn reload_introspection_client_setting(db: &DB) -> error::Result<()> {
+    let client = load_value_from_global_settings(db, INTROSPECTION_CLIENT_SETTING)
+        .await?
+        .and_then(|q| match serde_json::from_value::<IntrospectionClient>(q) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                tracing::error!("Could not parse introspection_client setting: {e:#}");
+                None
+            }
+        });
+
+    let mut l = INTROSPECTION_CLIENT.write().await;
+    *l = client;
+
+    Ok(())
+}
+
 async fn generate_and_save_jwt_secret(db: &DB) -> error::Result<String> {
     let secret = rd_string(32);
     sqlx::query!(
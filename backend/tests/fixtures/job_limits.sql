@@ -0,0 +1,24 @@
+-- workspaces whose job limits or custom response headers are set by a single test each, so that
+-- the settings cached in WORKSPACE_JOB_LIMITS or CUSTOM_RESPONSE_HEADERS do not leak to the tests
+-- running concurrently in test-workspace
+
+INSERT INTO workspace
+            (id,                       name,                     owner)
+     VALUES ('test-timeout-workspace', 'test-timeout-workspace', 'test-user'),
+            ('test-mem-limit-workspace', 'test-mem-limit-workspace', 'test-user'),
+            ('test-headers-workspace', 'test-headers-workspace', 'test-user');
+
+INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+	('test-timeout-workspace', 'test@windmill.dev', 'test-user', true, 'Admin'),
+	('test-mem-limit-workspace', 'test@windmill.dev', 'test-user', true, 'Admin'),
+	('test-headers-workspace', 'test@windmill.dev', 'test-user', true, 'Admin');
+
+INSERT INTO workspace_key(workspace_id, kind, key) VALUES
+	('test-timeout-workspace', 'cloud', 'test-key'),
+	('test-mem-limit-workspace', 'cloud', 'test-key'),
+	('test-headers-workspace', 'cloud', 'test-key');
+
+INSERT INTO workspace_settings (workspace_id) VALUES
+	('test-timeout-workspace'),
+	('test-mem-limit-workspace'),
+	('test-headers-workspace');
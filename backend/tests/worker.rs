@@ -452,6 +452,73 @@ mod suspend_resume {
             result
         );
     }
+
+    #[sqlx::test(fixtures("base"))]
+    async fn list_suspended(db: Pool<Postgres>) {
+        initialize_tracing().await;
+
+        let server = ApiServer::start(db.clone()).await;
+        let port = server.addr.port();
+
+        let flow =
+            RunJob::from(JobPayload::RawFlow { value: flow(), path: None, restarted_from: None })
+                .arg("n", json!(1))
+                .arg("port", json!(port))
+                .push(&db)
+                .await;
+
+        let mut completed = listen_for_completed_jobs(&db).await;
+        let queue = listen_for_queue(&db).await;
+        let db_ = db.clone();
+
+        let list = |query: &'static str| {
+            reqwest::Client::new()
+                .get(format!(
+                    "http://localhost:{port}/api/w/test-workspace/jobs/queue/list_suspended{query}"
+                ))
+                .bearer_auth("SECRET_TOKEN")
+                .send()
+        };
+
+        in_test_worker(
+            &db,
+            async move {
+                wait_until_flow_suspends(flow, queue, &db_).await;
+                /* The first job resumes itself, the flow waits after the second one. */
+                let _first = completed.next().await.unwrap();
+                let _second = completed.next().await.unwrap();
+
+                let suspended = list("")
+                    .await
+                    .unwrap()
+                    .json::<serde_json::Value>()
+                    .await
+                    .unwrap();
+                assert_eq!(suspended.as_array().unwrap().len(), 1);
+                assert_eq!(suspended[0]["id"], json!(flow));
+                assert_eq!(suspended[0]["step_id"], json!("b"));
+                assert_eq!(suspended[0]["required_events"], json!(1));
+                assert_eq!(suspended[0]["remaining_events"], json!(1));
+                assert_eq!(suspended[0]["approvers"], json!([]));
+                assert!(suspended[0]["suspended_at"].is_string());
+
+                let actionable = list("?approver_can_act=me")
+                    .await
+                    .unwrap()
+                    .json::<serde_json::Value>()
+                    .await
+                    .unwrap();
+                assert_eq!(actionable, suspended);
+
+                let invalid = list("?approver_can_act=someone").await.unwrap();
+                assert_eq!(invalid.status(), 400);
+            },
+            port,
+        )
+        .await;
+
+        server.close().await.unwrap();
+    }
 }
 
 mod retry {
@@ -1136,6 +1203,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1178,6 +1246,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                             cache_ttl: None,
                             mock: None,
                             timeout: None,
+                            step_timeout_ms: None,
                             priority: None,
                             delete_after_use: None,
                             continue_on_error: None,
@@ -1195,6 +1264,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1303,6 +1373,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1355,6 +1426,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                                 cache_ttl: None,
                                 mock: None,
                                 timeout: None,
+                                step_timeout_ms: None,
                                 priority: None,
                                 delete_after_use: None,
                                 continue_on_error: None,
@@ -1394,6 +1466,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                                 cache_ttl: None,
                                 mock: None,
                                 timeout: None,
+                                step_timeout_ms: None,
                                 priority: None,
                                 delete_after_use: None,
                                 continue_on_error: None,
@@ -1411,6 +1484,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1456,6 +1530,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1879,6 +1954,182 @@ echo "hello $msg"
     assert_eq!(job.json_result(), Some(json!("hello world")));
 }
 
+/// Removes the job limits cached for a workspace when dropped, even if the test panicked
+struct WorkspaceJobLimitsGuard(&'static str);
+
+impl WorkspaceJobLimitsGuard {
+    async fn set(db: &Pool<Postgres>, w_id: &'static str, settings: &str) -> Self {
+        sqlx::query(&format!(
+            "UPDATE workspace_settings SET {settings} WHERE workspace_id = $1"
+        ))
+        .bind(w_id)
+        .execute(db)
+        .await
+        .unwrap();
+        windmill_common::workspaces::reload_workspace_job_limits(db, Some(w_id))
+            .await
+            .unwrap();
+        Self(w_id)
+    }
+}
+
+impl Drop for WorkspaceJobLimitsGuard {
+    fn drop(&mut self) {
+        loop {
+            if let Ok(mut limits) = windmill_common::workspaces::WORKSPACE_JOB_LIMITS.try_write() {
+                limits.remove(self.0);
+                return;
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+#[sqlx::test(fixtures("base", "job_limits"))]
+async fn test_workspace_max_timeout(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let _limits = WorkspaceJobLimitsGuard::set(
+        &db,
+        "test-timeout-workspace",
+        "default_timeout_secs_max = 1",
+    )
+    .await;
+
+    let args = std::collections::HashMap::new();
+    let push = |custom_timeout: Option<i32>| {
+        windmill_queue::push(
+            &db,
+            PushIsolationLevel::IsolatedRoot(db.clone()),
+            "test-timeout-workspace",
+            JobPayload::Code(RawCode {
+                hash: None,
+                content: "sleep 10".to_string(),
+                path: None,
+                lock: None,
+                language: ScriptLang::Bash,
+                custom_concurrency_key: None,
+                concurrent_limit: None,
+                concurrency_time_window_s: None,
+                cache_ttl: None,
+                dedicated_worker: None,
+            }),
+            windmill_queue::PushArgs::from(&args),
+            "test-user",
+            "test@windmill.dev",
+            "u/test-user".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            true,
+            None,
+            custom_timeout,
+            None,
+            None,
+            None,
+        )
+    };
+
+    let (uuid, tx) = push(Some(60)).await.expect("push has to succeed");
+    tx.commit().await.unwrap();
+
+    let timeout = sqlx::query_scalar::<_, Option<i32>>("SELECT timeout FROM queue WHERE id = $1")
+        .bind(uuid)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(timeout, Some(1));
+
+    // without a custom timeout, the instance default timeout is clamped as well
+    let (default_uuid, tx) = push(None).await.expect("push has to succeed");
+    tx.commit().await.unwrap();
+    let timeout = sqlx::query_scalar::<_, Option<i32>>("SELECT timeout FROM queue WHERE id = $1")
+        .bind(default_uuid)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(timeout, Some(1));
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&uuid), port).await;
+    let job = completed_job(uuid, &db).await;
+
+    assert!(!job.success);
+    assert!(job.duration_ms < 10_000);
+}
+
+#[sqlx::test(fixtures("base", "job_limits"))]
+async fn test_job_mem_limit(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let _limits =
+        WorkspaceJobLimitsGuard::set(&db, "test-mem-limit-workspace", "max_mem_limit_mb = 256")
+            .await;
+
+    let run_preview = |mem_limit_mb: i32| {
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{port}/api/w/test-mem-limit-workspace/jobs/run/preview?mem_limit_mb={mem_limit_mb}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "language": "python3",
+                "content": r#"
+import time
+
+def main():
+    data = "a" * (300 * 1024 * 1024)
+    time.sleep(10)
+    return len(data)
+"#,
+                "args": {},
+            }))
+            .send()
+    };
+
+    let response = run_preview(1024).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let uuid = run_preview(64)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let uuid = Uuid::parse_str(&uuid).unwrap();
+
+    let mem_limit =
+        sqlx::query_scalar::<_, Option<i32>>("SELECT mem_limit_mb FROM queue WHERE id = $1")
+            .bind(uuid)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(mem_limit, Some(64));
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&uuid), port).await;
+    let job = completed_job(uuid, &db).await;
+
+    assert!(!job.success);
+    assert!(job.duration_ms < 10_000);
+    assert!(job.mem_peak.is_some_and(|mem_peak| mem_peak > 0));
+    assert!(job.json_result().unwrap()["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("memory limit exceeded (64 MB)"));
+}
+
 #[sqlx::test(fixtures("base"))]
 async fn test_python_job(db: Pool<Postgres>) {
     initialize_tracing().await;
@@ -2875,6 +3126,7 @@ async fn test_flow_lock_all(db: Pool<Postgres>) {
                     timeout: None,
                     visible_to_runner_only: None,
                     on_behalf_of_email: None,
+                    strict_args: None,
                 },
                 draft_only: None,
                 deployment_message: None,
@@ -3170,6 +3422,13 @@ async fn test_script_schedule_handlers(db: Pool<Postgres>) {
         tag: None,
         paused_until: None,
         cron_version: None,
+        catchup_policy: None,
+        depends_on_schedule: None,
+        dependency_lookback_secs: None,
+        jitter_seconds: None,
+        max_concurrent_runs: None,
+        on_conflict: None,
+        pinned_version_id: None,
     };
 
     let _ = client.create_schedule("test-workspace", &schedule).await;
@@ -3238,6 +3497,13 @@ async fn test_script_schedule_handlers(db: Pool<Postgres>) {
                 tag: None,
                 paused_until: None,
                 cron_version: None,
+                catchup_policy: None,
+                depends_on_schedule: None,
+                dependency_lookback_secs: None,
+                jitter_seconds: None,
+                max_concurrent_runs: None,
+                on_conflict: None,
+                pinned_version_id: None,
             },
         )
         .await
@@ -3321,6 +3587,13 @@ async fn test_flow_schedule_handlers(db: Pool<Postgres>) {
         tag: None,
         paused_until: None,
         cron_version: None,
+        catchup_policy: None,
+        depends_on_schedule: None,
+        dependency_lookback_secs: None,
+        jitter_seconds: None,
+        max_concurrent_runs: None,
+        on_conflict: None,
+        pinned_version_id: None,
     };
 
     let _ = client.create_schedule("test-workspace", &schedule).await;
@@ -3390,6 +3663,13 @@ async fn test_flow_schedule_handlers(db: Pool<Postgres>) {
                 tag: None,
                 paused_until: None,
                 cron_version: None,
+                catchup_policy: None,
+                depends_on_schedule: None,
+                dependency_lookback_secs: None,
+                jitter_seconds: None,
+                max_concurrent_runs: None,
+                on_conflict: None,
+                pinned_version_id: None,
             },
         )
         .await
@@ -3429,152 +3709,6516 @@ async fn test_flow_schedule_handlers(db: Pool<Postgres>) {
     .await;
 }
 
-async fn run_deployed_relative_imports(
-    db: &Pool<Postgres>,
-    script_content: String,
-    language: ScriptLang,
-) {
-    initialize_tracing().await;
-    let server = ApiServer::start(db.clone()).await;
-    let port = server.addr.port();
-    let client = windmill_api_client::create_client(
-        &format!("http://localhost:{port}"),
-        "SECRET_TOKEN".to_string(),
-    );
-
-    client
-        .create_script(
-            "test-workspace",
-            &NewScript {
-                language: NewScriptLanguage::from_str(language.as_str()).unwrap(),
-                content: script_content,
-                path: "f/system/test_import".to_string(),
-                concurrent_limit: None,
-                concurrency_time_window_s: None,
-                cache_ttl: None,
-                dedicated_worker: None,
-                description: "".to_string(),
-                draft_only: None,
-                envs: vec![],
-                is_template: None,
-                kind: None,
-                parent_hash: None,
-                lock: None,
-                summary: "".to_string(),
-                tag: None,
-                schema: std::collections::HashMap::new(),
-                ws_error_handler_muted: Some(false),
-                priority: None,
-                delete_after_use: None,
-                timeout: None,
-                restart_unless_cancelled: None,
-                deployment_message: None,
-                concurrency_key: None,
-                visible_to_runner_only: None,
-                no_main_func: None,
-                codebase: None,
-                has_preprocessor: None,
-                on_behalf_of_email: None,
-            },
-        )
+async fn run_chained_failing_script(
+    port: u16,
+    token: &str,
+    chain: &str,
+    args: serde_json::Value,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/p/f/system/failing_script?{chain}"
+        ))
+        .bearer_auth(token)
+        .json(&args)
+        .send()
         .await
-        .unwrap();
+        .unwrap()
+}
 
-    let mut completed = listen_for_completed_jobs(&db).await;
+/// Runs the chained job and its follow-up, checks that the follow-up is recorded on the chain and
+/// returns its path, args and depth in the chain
+async fn completed_follow_up(
+    db: &Pool<Postgres>,
+    port: u16,
+    job_id: Uuid,
+) -> (String, serde_json::Value, i32) {
+    let mut str = listen_for_completed_jobs(db).await;
     let db2 = db.clone();
     in_test_worker(
-        &db,
+        db,
         async move {
-            completed.next().await; // deployed script
+            str.next().await; // completed chained job
+            let follow_up =
+                tokio::time::timeout(std::time::Duration::from_millis(5000), str.next())
+                    .await
+                    .expect("follow-up job was not run within 5 s")
+                    .unwrap();
 
-            let script = query!(
-                "SELECT hash FROM script WHERE path = $1",
-                "f/system/test_import".to_string()
+            let recorded = sqlx::query_scalar::<_, Option<Uuid>>(
+                "SELECT follow_up_job_id FROM job_chain WHERE job_id = $1",
             )
+            .bind(job_id)
             .fetch_one(&db2)
             .await
             .unwrap();
-
-            let job = RunJob::from(JobPayload::ScriptHash {
-                path: "f/system/test_import".to_string(),
-                hash: ScriptHash(script.hash),
-                custom_concurrency_key: None,
-                concurrent_limit: None,
-                concurrency_time_window_s: None,
-                cache_ttl: None,
-                dedicated_worker: None,
-                language,
-                priority: None,
-                apply_preprocessor: false,
-            })
-            .push(&db2)
-            .await;
-
-            completed.next().await; // completed job
-
-            let result = completed_job(job, &db2).await.json_result().unwrap();
-
-            assert_eq!(
-                result,
-                serde_json::json!([
-                    "f/system/same_folder_script",
-                    "f/system/same_folder_script",
-                    "f/system_relative/different_folder_script",
-                    "f/system_relative/different_folder_script"
-                ])
-            );
+            assert_eq!(recorded, Some(follow_up));
         },
         port,
     )
     .await;
+
+    sqlx::query_as::<_, (Option<String>, Option<serde_json::Value>, i32)>(
+        "SELECT completed_job.script_path, completed_job.args, follow_up.depth FROM completed_job
+        JOIN job_chain ON job_chain.follow_up_job_id = completed_job.id
+        JOIN job_chain follow_up ON follow_up.job_id = completed_job.id
+        WHERE job_chain.job_id = $1",
+    )
+    .bind(job_id)
+    .fetch_one(db)
+    .await
+    .map(|(path, args, depth)| (path.unwrap(), args.unwrap(), depth))
+    .unwrap()
 }
 
-async fn run_preview_relative_imports(
-    db: &Pool<Postgres>,
-    script_content: String,
-    language: ScriptLang,
-) {
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_job_chain_on_success(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
-    let mut completed = listen_for_completed_jobs(&db).await;
-    let db2 = db.clone();
-    in_test_worker(
-        &db,
-        async move {
-            let job = RunJob::from(JobPayload::Code(RawCode {
-                hash: None,
-                content: script_content,
-                path: Some("f/system/test_import".to_string()),
-                language,
-                lock: None,
-                custom_concurrency_key: None,
-                concurrent_limit: None,
-                concurrency_time_window_s: None,
-                cache_ttl: None,
-                dedicated_worker: None,
-            }))
-            .push(&db2)
-            .await;
-
-            completed.next().await; // completed job
+    let job_id = run_chained_failing_script(
+        port,
+        "SECRET_TOKEN",
+        "on_success_path=script/f/system/schedule_recovery_handler&on_failure_path=script/f/system/schedule_error_handler",
+        json!({ "fail": false }),
+    )
+    .await
+    .error_for_status()
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    let job_id = Uuid::parse_str(&job_id).unwrap();
+
+    let (path, args, depth) = completed_follow_up(&db, port, job_id).await;
+    assert_eq!(path, "f/system/schedule_recovery_handler");
+    assert_eq!(args["previous_result"], json!("OK"));
+    assert_eq!(args["previous_job_id"], json!(job_id));
+    assert_eq!(args["previous_success"], json!(true));
+    assert_eq!(depth, 1);
+}
 
-            let result = completed_job(job, &db2).await.json_result().unwrap();
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_job_chain_on_failure(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
 
-            assert_eq!(
-                result,
-                serde_json::json!([
-                    "f/system/same_folder_script",
-                    "f/system/same_folder_script",
-                    "f/system_relative/different_folder_script",
-                    "f/system_relative/different_folder_script"
-                ])
-            );
+    let job_id = run_chained_failing_script(
+        port,
+        "SECRET_TOKEN",
+        "on_success_path=script/f/system/schedule_recovery_handler&on_failure_path=script/f/system/schedule_error_handler",
+        // the depth is stored server side, an arg cannot set it
+        json!({ "fail": true, "wm_chain_depth": 4 }),
+    )
+    .await
+    .error_for_status()
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    let job_id = Uuid::parse_str(&job_id).unwrap();
+
+    let (path, args, depth) = completed_follow_up(&db, port, job_id).await;
+    assert_eq!(path, "f/system/schedule_error_handler");
+    assert_eq!(args["previous_job_id"], json!(job_id));
+    assert_eq!(args["previous_success"], json!(false));
+    assert!(args["previous_result"]["error"].is_object());
+    assert_eq!(depth, 1);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_job_chain_depth_limit(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // jobs run the follow-ups of their chain with their job token
+    let last_job = Uuid::new_v4();
+    let inner_job = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin, job) VALUES
+            ('LAST_JOB_TOKEN', 'test@windmill.dev', 'job token', true, $1),
+            ('INNER_JOB_TOKEN', 'test@windmill.dev', 'job token', true, $2)",
+    )
+    .bind(last_job)
+    .bind(inner_job)
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO job_chain (job_id, workspace_id, depth) VALUES
+            ($1, 'test-workspace', 5), ($2, 'test-workspace', 4)",
+    )
+    .bind(last_job)
+    .bind(inner_job)
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let response = run_chained_failing_script(
+        port,
+        "LAST_JOB_TOKEN",
+        "on_success_path=script/f/system/schedule_recovery_handler",
+        json!({ "fail": false }),
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let job_id = run_chained_failing_script(
+        port,
+        "INNER_JOB_TOKEN",
+        "on_success_path=script/f/system/schedule_recovery_handler",
+        json!({ "fail": false }),
+    )
+    .await
+    .error_for_status()
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    let job_id = Uuid::parse_str(&job_id).unwrap();
+
+    let depth = sqlx::query_scalar::<_, i32>("SELECT depth FROM job_chain WHERE job_id = $1")
+        .bind(job_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(depth, 4);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_failing_script_is_retried_with_its_retry_count(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "UPDATE script SET retry_on_failure = $1
+        WHERE path = 'f/system/failing_script' AND workspace_id = 'test-workspace'",
+    )
+    .bind(json!({ "max_attempts": 1, "backoff_ms": 0, "backoff_multiplier": 1.0 }))
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let job_id = run_chained_failing_script(port, "SECRET_TOKEN", "", json!({ "fail": true }))
+        .await
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let job_id = Uuid::parse_str(&job_id).unwrap();
+
+    let mut str = listen_for_completed_jobs(&db).await;
+    let retry_id = in_test_worker(
+        &db,
+        async move {
+            str.next().await; // failed job
+            tokio::time::timeout(std::time::Duration::from_secs(5), str.next())
+                .await
+                .expect("retry was not run within 5 s")
+                .unwrap()
         },
         port,
     )
-    .await;
+    .await;
+
+    let (original_job_id, attempt) = sqlx::query_as::<_, (Uuid, i32)>(
+        "SELECT original_job_id, attempt FROM script_job_retry WHERE job_id = $1",
+    )
+    .bind(retry_id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!((original_job_id, attempt), (job_id, 1));
+
+    let (success, parent_job, args) =
+        sqlx::query_as::<_, (bool, Option<Uuid>, Option<serde_json::Value>)>(
+            "SELECT success, parent_job, args FROM completed_job WHERE id = $1",
+        )
+        .bind(retry_id)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert!(!success);
+    assert_eq!(parent_job, Some(job_id));
+    assert_eq!(args, Some(json!({ "fail": true })));
+
+    // max_attempts is 1, the failed retry is not retried again
+    let retries = sqlx::query_scalar::<_, i64>(
+        "SELECT (SELECT count(*) FROM queue WHERE parent_job IN ($1, $2))
+            + (SELECT count(*) FROM completed_job WHERE parent_job IN ($1, $2))",
+    )
+    .bind(job_id)
+    .bind(retry_id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(retries, 1);
+
+    let retry_count = |id: Uuid| async move {
+        reqwest::Client::new()
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs_u/get/{id}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap()["retry_count"]
+            .clone()
+    };
+    assert_eq!(retry_count(retry_id).await, json!(1));
+    assert_eq!(retry_count(job_id).await, serde_json::Value::Null);
+}
+
+async fn bulk_move_folder(
+    port: u16,
+    source_prefix: &str,
+    target_prefix: &str,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/folders/bulk_move"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "source_prefix": source_prefix, "target_prefix": target_prefix }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_folder_stats_match_the_folder_name_literally(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // `_` is a LIKE wildcard, the items of `axb` must not be counted in `a_b`
+    sqlx::query(
+        "INSERT INTO folder (workspace_id, name, display_name, owners) VALUES
+            ('test-workspace', 'a_b', 'a_b', '{}'), ('test-workspace', 'axb', 'axb', '{}')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable (workspace_id, path, value) VALUES
+            ('test-workspace', 'f/a_b/my_var', 'a'), ('test-workspace', 'f/axb/my_var', 'b'),
+            ('test-workspace', 'f/axb/other_var', 'c')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+            VALUES ('test-workspace', 'test-user', 'echo x', '{}', '', '', 'f/axb/my_script', 454545, 'bash', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let stats = |name: &'static str| async move {
+        reqwest::Client::new()
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/folders/{name}/stats"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap()
+    };
+
+    let a_b = stats("a_b").await;
+    assert_eq!(a_b["variables"], json!(1));
+    assert_eq!(a_b["scripts"], json!(0));
+    let axb = stats("axb").await;
+    assert_eq!(axb["variables"], json!(2));
+    assert_eq!(axb["scripts"], json!(1));
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_folder_bulk_move(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "INSERT INTO folder (workspace_id, name, display_name, owners) VALUES
+            ('test-workspace', 'system', 'system', '{}'), ('test-workspace', 'moved', 'moved', '{}')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable (workspace_id, path, value) VALUES
+            ('test-workspace', 'f/system/my_var', 'a'), ('test-workspace', 'f/moved/my_var', 'b')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO websocket_trigger
+            (workspace_id, path, url, script_path, is_flow, edited_by, email, enabled)
+            VALUES ('test-workspace', 'u/test-user/ws', 'ws://localhost', 'f/system/failing_flow',
+            true, 'test-user', 'test@windmill.dev', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let response = bulk_move_folder(port, "f/system", "f/system/sub").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let response = bulk_move_folder(port, "f/system", "f/moved").await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("variable f/moved/my_var"));
+
+    sqlx::query("DELETE FROM variable WHERE path = 'f/moved/my_var'")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let moved = bulk_move_folder(port, "f/system", "f/moved")
+        .await
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(moved["scripts"], json!(3));
+    assert_eq!(moved["flows"], json!(1));
+    assert_eq!(moved["variables"], json!(1));
+    assert_eq!(moved["triggers"], json!(1));
+    assert_eq!(moved["referencing_flows"], json!(1));
+
+    let trigger_script_path = sqlx::query_scalar::<_, String>(
+        "SELECT script_path FROM websocket_trigger WHERE path = 'u/test-user/ws'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(trigger_script_path, "f/moved/failing_flow");
+
+    let module_paths = sqlx::query_scalar::<_, String>(
+        "SELECT flow.value->'modules'->0->'value'->>'path' FROM flow
+        WHERE flow.path = 'f/moved/failing_flow'
+        UNION ALL SELECT flow_version.value->'modules'->0->'value'->>'path' FROM flow
+        JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+        WHERE flow.path = 'f/moved/failing_flow'",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        module_paths,
+        vec!["f/moved/failing_script", "f/moved/failing_script"]
+    );
+
+    let paths = sqlx::query_scalar::<_, String>(
+        "SELECT path FROM script WHERE workspace_id = 'test-workspace' AND path LIKE 'f/%'
+        UNION ALL SELECT path FROM variable WHERE workspace_id = 'test-workspace'
+        ORDER BY path",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        paths,
+        vec![
+            "f/moved/failing_script",
+            "f/moved/my_var",
+            "f/moved/schedule_error_handler",
+            "f/moved/schedule_recovery_handler",
+        ]
+    );
+}
+
+async fn introspect(port: u16, client_secret: &str, token: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("http://localhost:{port}/api/auth/introspect"))
+        .basic_auth("resource-server", Some(client_secret))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_token_introspection(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    *windmill_api::INTROSPECTION_CLIENT.write().await = Some(windmill_api::IntrospectionClient {
+        client_id: "resource-server".to_string(),
+        client_secret: "client-secret".to_string(),
+    });
+
+    let unauthorized = introspect(port, "wrong-secret", "SECRET_TOKEN").await;
+    let active = introspect(port, "client-secret", "SECRET_TOKEN")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let inactive = introspect(port, "client-secret", "not_a_token")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    *windmill_api::INTROSPECTION_CLIENT.write().await = None;
+
+    assert_eq!(unauthorized.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(active["active"], json!(true));
+    assert_eq!(active["email"], json!("test@windmill.dev"));
+    assert_eq!(inactive, json!({ "active": false }));
+}
+
+async fn effective_permission(
+    port: u16,
+    token: &str,
+    path: &str,
+    username: &str,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/acls/effective"
+        ))
+        .query(&[("kind", "variable"), ("path", path), ("username", username)])
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_effective_permission(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO group_(workspace_id, name) VALUES ('test-workspace', 'devs')")
+        .execute(&db)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO usr_to_group(workspace_id, group_, usr) VALUES ('test-workspace', 'devs', 'alice')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO folder(workspace_id, name, display_name, owners, extra_perms) VALUES
+            ('test-workspace', 'team', 'team', '{}', '{\"g/devs\": false}')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable(workspace_id, path, value) VALUES
+            ('test-workspace', 'f/team/read', 'a'),
+            ('test-workspace', 'u/test-user/private', 'b')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable(workspace_id, path, value, extra_perms) VALUES
+            ('test-workspace', 'f/team/write', 'c', '{\"u/alice\": true}')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let read = effective_permission(port, "ALICE_TOKEN", "f/team/read", "alice")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let write = effective_permission(port, "SECRET_TOKEN", "f/team/write", "alice")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let private = effective_permission(port, "ALICE_TOKEN", "u/test-user/private", "alice")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let missing = effective_permission(port, "ALICE_TOKEN", "f/team/missing", "alice")
+        .await
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let other_user = effective_permission(port, "ALICE_TOKEN", "f/team/read", "test-user").await;
+
+    assert_eq!(
+        read,
+        json!({
+            "permission": "viewer",
+            "grants": [{ "source": "folder", "grantee": "g/devs", "permission": "viewer" }]
+        })
+    );
+    assert_eq!(write["permission"], json!("writer"));
+    assert_eq!(
+        write["grants"][1],
+        json!({ "source": "item", "grantee": "u/alice", "permission": "writer" })
+    );
+    assert_eq!(private, json!({ "permission": "none", "grants": [] }));
+    assert_eq!(missing, private);
+    assert_eq!(other_user.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_create_script_from_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let run_preview = || async {
+        let uuid = client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/preview"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "language": "deno",
+                "content": "export function main() { return 42 }",
+                "args": {},
+                "tag": "custom",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        Uuid::parse_str(&uuid).unwrap()
+    };
+    let create_from_job = |job_id: Uuid, path: &'static str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/scripts/create_from_job/{job_id}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "path": path, "summary": "from preview" }))
+            .send()
+    };
+
+    let preview = run_preview().await;
+    let response = create_from_job(preview, "u/test-user/from_preview")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let hash = response.text().await.unwrap();
+
+    let (path, content, language, tag, summary) =
+        sqlx::query_as::<_, (String, String, String, Option<String>, String)>(
+            "SELECT path, content, language::text, tag, summary FROM script \
+            WHERE hash = $1 AND workspace_id = 'test-workspace'",
+        )
+        .bind(windmill_common::scripts::to_i64(&hash).unwrap())
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(path, "u/test-user/from_preview");
+    assert_eq!(content, "export function main() { return 42 }");
+    assert_eq!(language, "deno");
+    assert_eq!(tag.as_deref(), Some("custom"));
+    assert_eq!(summary, "from preview");
+
+    let purged = run_preview().await;
+    for table in ["queue", "job"] {
+        sqlx::query(&format!("UPDATE {table} SET raw_code = NULL WHERE id = $1"))
+            .bind(purged)
+            .execute(&db)
+            .await
+            .unwrap();
+    }
+    let response = create_from_job(purged, "u/test-user/purged").await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(fixtures("base", "job_limits"))]
+async fn test_workspace_custom_response_headers(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let edit_headers = |headers: serde_json::Value| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-headers-workspace/workspaces/edit_custom_response_headers"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "custom_response_headers": headers }))
+            .send()
+    };
+    let get = |path: &'static str| {
+        client
+            .get(format!("http://localhost:{port}/api{path}"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+
+    let invalid = edit_headers(json!({ "X Frame Options": "DENY" }))
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+    for forbidden in [
+        "Content-Type",
+        "Content-Length",
+        "Transfer-Encoding",
+        "Connection",
+        "Set-Cookie",
+    ] {
+        let rejected = edit_headers(json!({ forbidden: "x" })).await.unwrap();
+        assert_eq!(rejected.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    edit_headers(json!({ "X-Frame-Options": "DENY", "Content-Disposition": "inline" }))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let workspace_route = get("/w/test-headers-workspace/workspaces/get_settings")
+        .await
+        .unwrap();
+    let global_route = get("/version").await.unwrap();
+    assert_eq!(
+        workspace_route.headers().get("x-frame-options").unwrap(),
+        "DENY"
+    );
+    assert_eq!(
+        workspace_route
+            .headers()
+            .get("content-disposition")
+            .unwrap(),
+        "inline"
+    );
+    assert!(global_route.headers().get("x-frame-options").is_none());
+    // the headers set by the handler are kept
+    let tarball = get("/w/test-headers-workspace/workspaces/tarball")
+        .await
+        .unwrap();
+    assert!(tarball.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    assert_eq!(tarball.headers().get("x-frame-options").unwrap(), "DENY");
+
+    let settings = workspace_route.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        settings["custom_response_headers"],
+        json!({ "X-Frame-Options": "DENY", "Content-Disposition": "inline" })
+    );
+
+    // edited from another server, which notifies this one to reload them
+    sqlx::query(
+        "UPDATE workspace_settings SET custom_response_headers = '{\"X-Frame-Options\": \"SAMEORIGIN\"}'
+        WHERE workspace_id = 'test-headers-workspace'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    windmill_api::reload_custom_response_headers(&db, Some("test-headers-workspace"))
+        .await
+        .unwrap();
+    let workspace_route = get("/w/test-headers-workspace/workspaces/get_settings")
+        .await
+        .unwrap();
+    assert_eq!(
+        workspace_route.headers().get("x-frame-options").unwrap(),
+        "SAMEORIGIN"
+    );
+
+    edit_headers(json!(null))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let workspace_route = get("/w/test-headers-workspace/workspaces/get_settings")
+        .await
+        .unwrap();
+    assert!(workspace_route.headers().get("x-frame-options").is_none());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_redact_args(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let uuid = reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/preview?redact_args=ssn,user.email"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "language": "deno",
+            "content": r#"
+export function main(ssn: string, user: { name: string, email: string }) {
+    console.log(`ssn: ${ssn}`);
+    return user.name;
+}
+"#,
+            "args": { "ssn": "123-45-6789", "user": { "name": "alice", "email": "alice@acme.com" } },
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let uuid = Uuid::parse_str(&uuid).unwrap();
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&uuid), port).await;
+    let job = completed_job(uuid, &db).await;
+    assert_eq!(job.json_result(), Some(json!("alice")));
+
+    let args = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs_u/get_args/{uuid}"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        args,
+        json!({ "ssn": "***REDACTED***", "user": { "name": "alice", "email": "***REDACTED***" } })
+    );
+
+    let logs = sqlx::query_scalar::<_, String>("SELECT logs FROM job_logs WHERE job_id = $1")
+        .bind(uuid)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert!(logs.contains("ssn: ***REDACTED***"));
+    assert!(!logs.contains("123-45-6789"));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_redact_args_of_script(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock, redact_args)
+        VALUES ('test-workspace', 'test-user', 'token=\"$1\"\necho \"${#token}\"', '{}', '', '', 'f/system/redacted_script', 444444, 'bash', '', ARRAY['token'])",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let run = || async {
+        let uuid = reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/p/f/system/redacted_script"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "token": "s3cr3t" }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        Uuid::parse_str(&uuid).unwrap()
+    };
+    // the second run reads the paths of the script from the cache
+    let first = run().await;
+    let second = run().await;
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&second), port).await;
+    for uuid in [first, second] {
+        let job = completed_job(uuid, &db).await;
+        assert_eq!(job.json_result(), Some(json!("6")));
+        assert_eq!(
+            job.args.map(|args| serde_json::to_value(args.0).unwrap()),
+            Some(json!({ "token": "***REDACTED***" }))
+        );
+    }
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_redact_args_of_flow_steps_and_canceled_jobs(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let flow = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/preview_flow?redact_args=ssn"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "value": {
+                "modules": [{
+                    "id": "a",
+                    "value": {
+                        "type": "rawscript",
+                        "language": "deno",
+                        "content": "export function main(id: string) { return id.length }",
+                        "input_transforms": {
+                            "id": { "type": "javascript", "expr": "flow_input.ssn" },
+                        },
+                    },
+                }],
+            },
+            "args": { "ssn": "123-45-6789" },
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let flow = Uuid::parse_str(&flow).unwrap();
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&flow), port).await;
+    let job = completed_job(flow, &db).await;
+    assert_eq!(job.json_result(), Some(json!(11)));
+
+    let step_args = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT args FROM completed_job WHERE parent_job = $1",
+    )
+    .bind(flow)
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(step_args, vec![json!({ "id": "***REDACTED***" })]);
+
+    let queued = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/preview?redact_args=ssn"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "language": "deno",
+            "content": "export function main(ssn: string) { return ssn }",
+            "args": { "ssn": "123-45-6789" },
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let queued = Uuid::parse_str(&queued).unwrap();
+
+    let canceled = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/queue/cancel_selection"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!([queued]))
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<Uuid>>()
+        .await
+        .unwrap();
+    assert_eq!(canceled, vec![queued]);
+
+    let args = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT args FROM completed_job WHERE id = $1 AND canceled = true",
+    )
+    .bind(queued)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(args, json!({ "ssn": "***REDACTED***" }));
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_saved_inputs(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/inputs");
+
+    let create = |name: &str| {
+        client
+            .post(&base)
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": "f/system/failing_script",
+                "name": name,
+                "args": { "fail": true },
+            }))
+            .send()
+    };
+    let id = create("failing").await.unwrap();
+    assert_eq!(id.status(), 201);
+    let id = Uuid::parse_str(&id.text().await.unwrap()).unwrap();
+    assert_eq!(create("failing").await.unwrap().status(), 400);
+
+    let unknown_path = client
+        .post(&base)
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "path": "f/system/unknown", "name": "a", "args": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown_path.status(), 404);
+
+    client
+        .put(format!("{base}/{id}"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "name": "succeeding", "args": { "fail": false } }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let saved_inputs = client
+        .get(format!("{base}/by_path/f/system/failing_script"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(saved_inputs.as_array().unwrap().len(), 1);
+    assert_eq!(saved_inputs[0]["name"], json!("succeeding"));
+    assert_eq!(saved_inputs[0]["args"], json!({ "fail": false }));
+
+    // the stored args replace the ones of the request
+    let uuid = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/p/f/system/failing_script?saved_input_id={id}"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "fail": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let uuid = Uuid::parse_str(&uuid).unwrap();
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&uuid), port).await;
+    let job = completed_job(uuid, &db).await;
+    assert!(job.success);
+    assert_eq!(job.json_result(), Some(json!("OK")));
+
+    let other_path = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run/p/f/system/schedule_error_handler?saved_input_id={id}"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(other_path.status(), 400);
+
+    client
+        .delete(format!("{base}/{id}"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let deleted = client
+        .get(format!("{base}/{id}"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(deleted.status(), 404);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_capture_log(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+
+    sqlx::query(
+        "INSERT INTO capture_config (workspace_id, path, is_flow, trigger_kind, owner, email, last_client_ping)
+        VALUES ('test-workspace', 'u/test-user/hook', false, 'webhook', 'test-user', 'test@windmill.dev', now())",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    for i in 0..2 {
+        client
+            .post(format!("{base}/capture_u/webhook/script/u/test-user/hook"))
+            .json(&json!({ "i": i }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let history = client
+        .get(format!("{base}/capture/history/u/test-user/hook"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let history = history.as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["payload"]["i"], json!(1));
+    assert_eq!(history[0]["trigger_kind"], json!("webhook"));
+
+    let entry = client
+        .get(format!("{base}/capture/history_entry/{}", history[1]["id"]))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(entry["payload"]["i"], json!(0));
+
+    client
+        .delete(format!("{base}/capture/history/u/test-user/hook"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let history = client
+        .get(format!("{base}/capture/history/u/test-user/hook"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(history, json!([]));
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_rename_script_path(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+
+    let rename = |dry_run: bool| {
+        client
+            .post(format!(
+                "{base}/scripts/rename/f/system/failing_script?dry_run={dry_run}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "new_path": "f/system/renamed_script" }))
+            .send()
+    };
+    let flow_module_path = || {
+        let db = db.clone();
+        async move {
+            sqlx::query_scalar::<_, String>(
+                "SELECT value->'modules'->0->'value'->>'path' FROM flow
+                WHERE path = 'f/system/failing_flow' AND workspace_id = 'test-workspace'",
+            )
+            .fetch_one(&db)
+            .await
+            .unwrap()
+        }
+    };
+
+    let dry_run = rename(true)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert!(dry_run["rewritten"]
+        .as_array()
+        .unwrap()
+        .contains(&json!({ "kind": "flow", "path": "f/system/failing_flow" })));
+    assert_eq!(flow_module_path().await, "f/system/failing_script");
+
+    let renamed = rename(false)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(renamed["rewritten"], dry_run["rewritten"]);
+    assert_eq!(flow_module_path().await, "f/system/renamed_script");
+    let hash = sqlx::query_scalar::<_, i64>(
+        "SELECT hash FROM script WHERE path = 'f/system/renamed_script' AND workspace_id = 'test-workspace'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(hash, 12349);
+
+    let renamed_again = rename(false).await.unwrap();
+    assert_eq!(renamed_again.status(), 404);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_rename_requires_writer_of_references(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // f/system/failing_flow, which alice cannot write, runs a script of alice
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES ('test-workspace', 'alice', 'echo x', '{}', '', '', 'u/alice/shared', 565656, 'bash', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    for table in ["flow", "flow_version"] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET value = jsonb_set(value, '{{modules,0,value,path}}', '\"u/alice/shared\"')
+            WHERE path = 'f/system/failing_flow'"
+        ))
+        .execute(&db)
+        .await
+        .unwrap();
+    }
+
+    let rename = |token: &'static str, dry_run: bool| {
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/scripts/rename/u/alice/shared?dry_run={dry_run}"
+            ))
+            .bearer_auth(token)
+            .json(&json!({ "new_path": "u/alice/renamed" }))
+            .send()
+    };
+
+    for dry_run in [true, false] {
+        let denied = rename("ALICE_TOKEN", dry_run).await.unwrap();
+        assert_eq!(denied.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert!(!denied.text().await.unwrap().contains("failing_flow"));
+    }
+    let path = sqlx::query_scalar::<_, String>("SELECT path FROM script WHERE hash = 565656")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(path, "u/alice/shared");
+
+    let renamed = rename("SECRET_TOKEN", false)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        renamed["rewritten"],
+        json!([{ "kind": "flow", "path": "f/system/failing_flow" }])
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_rename_flow_path(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let (events_tx, mut events) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+    let webhook = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook_addr = webhook.local_addr().unwrap();
+    let app = axum::Router::new().route(
+        "/",
+        axum::routing::post(
+            move |axum::Json(event): axum::Json<serde_json::Value>| async move {
+                let _ = events_tx.send(event);
+            },
+        ),
+    );
+    tokio::spawn(async move { axum::serve(webhook, app).await.unwrap() });
+
+    sqlx::query(
+        "UPDATE workspace_settings SET deploy_webhook_url = $1 WHERE workspace_id = 'test-workspace'",
+    )
+    .bind(format!("http://{webhook_addr}/"))
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "UPDATE flow SET strict_args = true
+        WHERE path = 'f/system/failing_flow' AND workspace_id = 'test-workspace'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/flows/rename/f/system/failing_flow"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "new_path": "f/system/renamed_flow" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let (strict_args, version) = sqlx::query_as::<_, (bool, i64)>(
+        "SELECT strict_args, versions[array_upper(versions, 1)] FROM flow
+        WHERE path = 'f/system/renamed_flow' AND workspace_id = 'test-workspace'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(strict_args);
+
+    let mut received = vec![];
+    while received.len() < 2 {
+        let event = tokio::time::timeout(std::time::Duration::from_secs(10), events.recv())
+            .await
+            .expect("deploy webhook was not called")
+            .unwrap();
+        received.push((
+            event["action"].as_str().unwrap().to_string(),
+            event["path"].as_str().unwrap().to_string(),
+            event["version"].clone(),
+        ));
+    }
+    received.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        received,
+        vec![
+            (
+                "delete".to_string(),
+                "f/system/failing_flow".to_string(),
+                serde_json::Value::Null
+            ),
+            (
+                "deploy".to_string(),
+                "f/system/renamed_flow".to_string(),
+                json!(version.to_string())
+            ),
+        ]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_archive_job_result(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "echo \"secret $1\"".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .arg("msg", json!("value"))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(job.json_result(), Some(json!("secret value")));
+
+    reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/completed/{}/archive",
+            job.id
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let archived = completed_job(job.id, &db).await;
+    assert_eq!(archived.json_result(), Some(json!({ "archived": true })));
+    assert_eq!(archived.args, Some(json!({})));
+    assert!(archived.success);
+    assert_eq!(archived.duration_ms, job.duration_ms);
+    assert_eq!(archived.logs.as_deref(), Some("[archived]"));
+    assert!(!archived.deleted);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_last_failed_jobs_per_script(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let bash = |path: &str, content: &str| {
+        RunJob::from(JobPayload::Code(RawCode {
+            hash: None,
+            content: content.to_string(),
+            path: Some(path.to_string()),
+            lock: None,
+            language: ScriptLang::Bash,
+            custom_concurrency_key: None,
+            concurrent_limit: None,
+            concurrency_time_window_s: None,
+            cache_ttl: None,
+            dedicated_worker: None,
+        }))
+    };
+    bash("u/test-user/broken", "exit 1")
+        .run_until_complete(&db, port)
+        .await;
+    let last = bash("u/test-user/broken", "exit 2")
+        .run_until_complete(&db, port)
+        .await;
+    let other = bash("u/test-user/other", "exit 1")
+        .run_until_complete(&db, port)
+        .await;
+    let ok = bash("u/test-user/ok", "echo ok")
+        .run_until_complete(&db, port)
+        .await;
+    assert!(!last.success && !other.success && ok.success);
+
+    let failed = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/completed/failed_last_per_script"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let failed = failed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|j| (j["script_path"].clone(), j["id"].clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        failed,
+        vec![
+            (json!("u/test-user/broken"), json!(last.id)),
+            (json!("u/test-user/other"), json!(other.id)),
+        ]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_raw_app_etag(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/raw_apps");
+
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "u/test-user/raw",
+            "summary": "",
+            "value": "console.log('hello')",
+            "cache_max_age": 60,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let res = client
+        .get(format!("{base}/get_data/0/u/test-user/raw"))
+        .bearer_auth("SECRET_TOKEN")
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.headers()["cache-control"], "private, max-age=60");
+    let etag = res.headers()["etag"].clone();
+    assert_eq!(&res.bytes().await.unwrap()[..2], &[0x1f, 0x8b]);
+
+    let res = client
+        .get(format!("{base}/get_data/0/u/test-user/raw"))
+        .bearer_auth("SECRET_TOKEN")
+        .header("if-none-match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 304);
+
+    // apps uploaded before the hash was stored are hashed on their first load
+    sqlx::query(
+        "INSERT INTO raw_app (workspace_id, path, summary, data)
+        VALUES ('test-workspace', 'u/test-user/legacy', '', 'console.log(''hello'')')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    let res = client
+        .get(format!("{base}/get_data/0/u/test-user/legacy"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!(res.headers()["cache-control"], "no-cache");
+    assert_eq!(res.text().await.unwrap(), "console.log('hello')");
+    let backfilled = sqlx::query_scalar::<_, bool>(
+        "SELECT data_hash IS NOT NULL AND data_gzip IS NOT NULL FROM raw_app WHERE path = 'u/test-user/legacy'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(backfilled);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_drain_worker_group(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api");
+
+    sqlx::query(
+        "INSERT INTO config (name, config) VALUES ('worker__drained', '{\"worker_tags\": [\"a\"]}')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO worker_ping (worker_instance, worker, ip, worker_group, wm_version)
+        VALUES ('instance', 'wk-drained', 'ip', 'drained', 'test')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let drain = |drain: bool| {
+        client
+            .post(format!("{base}/configs/drain/drained"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "drain": drain }))
+            .send()
+    };
+    let (client, base) = (&client, &base);
+    let worker = || async move {
+        client
+            .get(format!("{base}/workers/list"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|w| w["worker"] == json!("wk-drained"))
+            .unwrap()
+    };
+
+    drain(true).await.unwrap().error_for_status().unwrap();
+    let config = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT config FROM config WHERE name = 'worker__drained'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(config, json!({ "worker_tags": ["a"], "drain": true }));
+    let w = worker().await;
+    assert_eq!(w["group_draining"], json!(true));
+    assert_eq!(w["draining"], json!(false));
+
+    drain(false).await.unwrap().error_for_status().unwrap();
+    assert_eq!(worker().await["group_draining"], json!(false));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_request_id(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let echoed = client
+        .get(format!("http://localhost:{port}/api/version"))
+        .header("x-request-id", "client-request-1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(echoed.headers()["x-request-id"], "client-request-1");
+
+    let generated = client
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/list"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap();
+    let request_id = generated.headers()["x-request-id"].to_str().unwrap();
+    assert!(Uuid::parse_str(request_id).is_ok());
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_dedup_window(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let run = |args: serde_json::Value| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/p/f/system/failing_script?dedup_window_s=60"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&args)
+            .send()
+    };
+
+    let first = run(json!({ "fail": false })).await.unwrap();
+    assert_eq!(first.status(), 201);
+    assert!(first.headers().get("x-windmill-deduplicated").is_none());
+    let first_id = first.text().await.unwrap();
+
+    let second = run(json!({ "fail": false })).await.unwrap();
+    assert_eq!(second.status(), 200);
+    assert_eq!(second.headers()["x-windmill-deduplicated"], "true");
+    assert_eq!(second.text().await.unwrap(), first_id);
+
+    let other = run(json!({ "fail": true })).await.unwrap();
+    assert_eq!(other.status(), 201);
+    assert_ne!(other.text().await.unwrap(), first_id);
+
+    let (a, b) = tokio::join!(
+        run(json!({ "fail": false, "n": 1 })),
+        run(json!({ "n": 1, "fail": false }))
+    );
+    let (a, b) = (a.unwrap(), b.unwrap());
+    let mut statuses = vec![a.status().as_u16(), b.status().as_u16()];
+    statuses.sort();
+    assert_eq!(statuses, vec![200, 201]);
+    assert_eq!(a.text().await.unwrap(), b.text().await.unwrap());
+
+    let wait_result = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/run_wait_result/p/f/system/failing_script?dedup_window_s=60"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "fail": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(wait_result.status(), 400);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_pause_schedules_by_prefix(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+
+    for (path, enabled) in [
+        ("f/system/etl_a", true),
+        ("f/system/etl_b", false),
+        ("f/system/other", true),
+    ] {
+        client
+            .post(format!("{base}/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "schedule": "0 0 0 1 1 *",
+                "timezone": "UTC",
+                "script_path": "f/system/failing_script",
+                "is_flow": false,
+                "args": {},
+                "enabled": enabled,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let bulk = |action: &'static str| {
+        client
+            .post(format!("{base}/{action}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "path_prefix": "f/system/etl_" }))
+            .send()
+    };
+    let enabled_schedules = || async {
+        sqlx::query_as::<_, (String, bool, bool)>(
+            "SELECT path, enabled, paused_by_bulk_operation FROM schedule \
+            WHERE workspace_id = 'test-workspace' ORDER BY path",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap()
+    };
+
+    let paused = bulk("pause_prefix")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(paused, json!({ "updated_count": 1, "unchanged_count": 1 }));
+    assert_eq!(
+        enabled_schedules().await,
+        vec![
+            ("f/system/etl_a".to_string(), false, true),
+            ("f/system/etl_b".to_string(), false, false),
+            ("f/system/other".to_string(), true, false),
+        ]
+    );
+
+    let resumed = bulk("resume_prefix")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(resumed, json!({ "updated_count": 1, "unchanged_count": 1 }));
+    assert_eq!(
+        enabled_schedules().await,
+        vec![
+            ("f/system/etl_a".to_string(), true, false),
+            ("f/system/etl_b".to_string(), false, false),
+            ("f/system/other".to_string(), true, false),
+        ]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_completed_job_result_json_path(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content:
+            r#"echo '{"rows": [{"name": "a", "tags": {"x.y": 1}}], "empty": null}' > result.json"#
+                .to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .run_until_complete(&db, port)
+    .await;
+
+    let get = |endpoint: &'static str, json_path: &'static str| {
+        client
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs_u/completed/{endpoint}/{}",
+                job.id
+            ))
+            .query(&[("json_path", json_path)])
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+
+    let name = get("get_result", "rows[0].name").await.unwrap();
+    assert_eq!(name.status(), 200);
+    assert_eq!(name.json::<serde_json::Value>().await.unwrap(), json!("a"));
+    let nested = get("get_result", r#"rows[0].tags["x.y"]"#).await.unwrap();
+    assert_eq!(nested.json::<serde_json::Value>().await.unwrap(), json!(1));
+    let null = get("get_result", "empty").await.unwrap();
+    assert_eq!(null.status(), 200);
+    assert_eq!(null.json::<serde_json::Value>().await.unwrap(), json!(null));
+    assert_eq!(get("get_result", "rows[1]").await.unwrap().status(), 404);
+
+    let maybe = get("get_result_maybe", "rows[0].name").await.unwrap();
+    assert_eq!(
+        maybe.json::<serde_json::Value>().await.unwrap()["result"],
+        json!("a")
+    );
+    let missing = get("get_result_maybe", "missing.field").await.unwrap();
+    assert_eq!(missing.status(), 200);
+    assert_eq!(
+        missing.json::<serde_json::Value>().await.unwrap()["result"],
+        json!(null)
+    );
+}
+
+/// Sets the instance object store and the large result threshold, the previous values are
+/// restored when the guard is dropped
+#[cfg(feature = "parquet")]
+struct LargeResultSettingsGuard {
+    object_store: Option<Arc<dyn object_store::ObjectStore>>,
+    threshold_kb: Option<usize>,
+}
+
+#[cfg(feature = "parquet")]
+impl LargeResultSettingsGuard {
+    async fn set(object_store: Arc<dyn object_store::ObjectStore>, threshold_kb: usize) -> Self {
+        use windmill_common::large_results::LARGE_RESULT_THRESHOLD_KB;
+        use windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS;
+
+        let object_store = std::mem::replace(
+            &mut *OBJECT_STORE_CACHE_SETTINGS.write().await,
+            Some(object_store),
+        );
+        let threshold_kb = std::mem::replace(
+            &mut *LARGE_RESULT_THRESHOLD_KB.write().await,
+            Some(threshold_kb),
+        );
+        Self { object_store, threshold_kb }
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl Drop for LargeResultSettingsGuard {
+    fn drop(&mut self) {
+        use windmill_common::large_results::LARGE_RESULT_THRESHOLD_KB;
+        use windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS;
+
+        loop {
+            if let Ok(mut object_store) = OBJECT_STORE_CACHE_SETTINGS.try_write() {
+                *object_store = self.object_store.take();
+                break;
+            }
+            std::thread::yield_now();
+        }
+        loop {
+            if let Ok(mut threshold_kb) = LARGE_RESULT_THRESHOLD_KB.try_write() {
+                *threshold_kb = self.threshold_kb.take();
+                break;
+            }
+            std::thread::yield_now();
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+#[sqlx::test(fixtures("base"))]
+async fn test_large_result_offloaded_to_object_store(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let os: Arc<dyn object_store::ObjectStore> = Arc::new(object_store::memory::InMemory::new());
+    let _settings = LargeResultSettingsGuard::set(os.clone(), 1024).await;
+
+    let job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content:
+            r#"printf '{"data": "%s"}' "$(head -c 10000000 /dev/zero | tr '\0' a)" > result.json"#
+                .to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .run_until_complete(&db, port)
+    .await;
+
+    let path = format!("results/test-workspace/{}.json", job.id);
+    let stored = job.json_result().unwrap();
+    assert_eq!(stored["__windmill_large_result"]["s3"], json!(path));
+    assert!(stored["__windmill_large_result"]["size"].as_u64().unwrap() > 10_000_000);
+
+    let result = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs_u/completed/get_result/{}",
+            job.id
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(result["data"].as_str().map(str::len), Some(10_000_000));
+    assert!(os
+        .head(&object_store::path::Path::from(path.as_str()))
+        .await
+        .is_ok());
+
+    reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/completed/delete/{}",
+            job.id
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert!(os
+        .head(&object_store::path::Path::from(path.as_str()))
+        .await
+        .is_err());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_strict_tags(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    {
+        let mut custom_tags = windmill_common::worker::CUSTOM_TAGS_PER_WORKSPACE
+            .write()
+            .await;
+        for tag in ["served", "unserved"] {
+            if !custom_tags.0.contains(&tag.to_string()) {
+                custom_tags.0.push(tag.to_string());
+            }
+        }
+    }
+    sqlx::query(
+        "INSERT INTO worker_ping (worker_instance, worker, ip, worker_group, wm_version, custom_tags)
+        VALUES ('instance', 'wk-served', 'ip', 'served', 'test', ARRAY['served'])",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    windmill_api::workers::refresh_live_worker_tags(&db)
+        .await
+        .unwrap();
+
+    let tags = client
+        .get(format!("http://localhost:{port}/api/workers/tags"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert!(tags.contains(&json!({ "tag": "served", "live_workers": 1 })));
+    assert!(tags.contains(&json!({ "tag": "deno", "live_workers": 0 })));
+
+    let run_preview = |tag: &'static str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/preview"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "language": "bash",
+                "content": "echo 1",
+                "args": {},
+                "tag": tag,
+            }))
+            .send()
+    };
+
+    assert!(run_preview("unserved").await.unwrap().status().is_success());
+
+    client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/workspaces/edit_strict_tags"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "strict_tags": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(
+        run_preview("unserved").await.unwrap().status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    assert!(run_preview("served").await.unwrap().status().is_success());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_ai_usage_and_daily_budget(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+
+    sqlx::query(
+        "INSERT INTO ai_usage_log
+            (workspace_id, model, prompt_tokens, completion_tokens, total_tokens, cost_usd, created_by, created_at)
+        VALUES
+            ('test-workspace', 'gpt-4o', 10, 20, 30, 0.5, 'test-user', now()),
+            ('test-workspace', 'gpt-4o', 5, 5, 10, 0.25, 'test-user', now()),
+            ('test-workspace', 'codestral', 1, 1, 2, 0.0, 'test-user', now()),
+            ('test-workspace', 'gpt-4o', 100, 100, 200, 3.0, 'test-user', now() - interval '2 days')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let usage = client
+        .get(format!("{base}/ai/usage"))
+        .query(&[("model", "gpt-4o")])
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[0]["model"], json!("gpt-4o"));
+    assert_eq!(usage[0]["total_tokens"], json!(40));
+    assert_eq!(usage[0]["total_cost_usd"], json!(0.75));
+    assert_eq!(usage[0]["request_count"], json!(2));
+    assert_eq!(usage[1]["total_tokens"], json!(200));
+
+    let complete = || {
+        client
+            .post(format!("{base}/ai/completion"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "prompt": "hello", "model": "gpt-4o", "max_tokens": 10 }))
+            .send()
+    };
+    let set_budget = |budget: f64| {
+        client
+            .post(format!("{base}/workspaces/edit_ai_daily_budget"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "ai_daily_budget_usd": budget }))
+            .send()
+    };
+
+    set_budget(0.5).await.unwrap().error_for_status().unwrap();
+    assert_eq!(
+        complete().await.unwrap().status(),
+        reqwest::StatusCode::TOO_MANY_REQUESTS
+    );
+    let proxied = client
+        .post(format!("{base}/ai/proxy/v1/chat/completions"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "model": "gpt-4o", "messages": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(proxied.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    // under budget, the request goes on to the unconfigured ai resource
+    set_budget(1.0).await.unwrap().error_for_status().unwrap();
+    let resp = complete().await.unwrap();
+    assert_ne!(resp.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp
+        .text()
+        .await
+        .unwrap()
+        .contains("AI resource not configured"));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_resource_value_is_validated_against_its_type_schema(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/resources");
+
+    sqlx::query(
+        "INSERT INTO resource_type (workspace_id, name, schema, description)
+        VALUES ('test-workspace', 'smtp_server', $1, '')",
+    )
+    .bind(json!({
+        "type": "object",
+        "properties": { "host": { "type": "string" }, "port": { "type": "integer" } },
+        "required": ["host"]
+    }))
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let create = |path: &'static str, value: serde_json::Value| {
+        client
+            .post(format!("{base}/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "path": path, "value": value, "resource_type": "smtp_server" }))
+            .send()
+    };
+    let create_invalid = create("u/test-user/invalid", json!({ "port": "25" }))
+        .await
+        .unwrap();
+    assert_eq!(create_invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+    let error = create_invalid.text().await.unwrap();
+    assert!(error.contains("smtp_server"), "{error}");
+    create(
+        "u/test-user/smtp",
+        json!({ "host": "smtp.windmill.dev", "port": 25 }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    let update_value = |value: serde_json::Value| {
+        client
+            .post(format!("{base}/update_value/u/test-user/smtp"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "value": value }))
+            .send()
+    };
+    assert_eq!(
+        update_value(json!({ "host": "smtp.windmill.dev", "port": "25" }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    update_value(json!({ "host": "mail.windmill.dev" }))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let value = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT value FROM resource WHERE path = 'u/test-user/smtp' AND workspace_id = 'test-workspace'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(value, json!({ "host": "mail.windmill.dev" }));
+
+    let validation = client
+        .post(format!("{base}/validate/smtp_server"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "value": { "port": 25 } }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(validation["valid"], false);
+    assert_eq!(validation["errors"].as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_resource_state_lock(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/resources");
+
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by)
+        VALUES ('test-workspace', 'u/test-user/state', '{\"count\": 0}', 'state', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let acquire = |ttl_s: i32, wait_ms: u64| {
+        client
+            .post(format!("{base}/lock/u/test-user/state"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "ttl_s": ttl_s, "wait_ms": wait_ms }))
+            .send()
+    };
+    let update = |count: i32, lock_token: Option<&str>| {
+        let mut req = client
+            .post(format!("{base}/update_value/u/test-user/state"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "value": { "count": count } }));
+        if let Some(lock_token) = lock_token {
+            req = req.query(&[("lock_token", lock_token)]);
+        }
+        req.send()
+    };
+
+    let lock = acquire(60, 0)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let lock_token = lock["lock_token"].as_str().unwrap().to_string();
+
+    assert_eq!(
+        acquire(60, 0).await.unwrap().status(),
+        reqwest::StatusCode::CONFLICT
+    );
+    assert_eq!(
+        update(1, None).await.unwrap().status(),
+        reqwest::StatusCode::CONFLICT
+    );
+    assert_eq!(
+        update(1, Some("not-the-token")).await.unwrap().status(),
+        reqwest::StatusCode::CONFLICT
+    );
+    update(1, Some(&lock_token))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let release = |lock_token: String| {
+        client
+            .delete(format!("{base}/lock/u/test-user/state"))
+            .query(&[("lock_token", lock_token)])
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+    assert_eq!(
+        release("not-the-token".to_string()).await.unwrap().status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+    release(lock_token)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    update(2, None).await.unwrap().error_for_status().unwrap();
+
+    // a waiting caller gets the lock once the current one expires
+    acquire(1, 0).await.unwrap().error_for_status().unwrap();
+    acquire(60, 5000).await.unwrap().error_for_status().unwrap();
+
+    assert_eq!(
+        sqlx::query_scalar::<_, serde_json::Value>(
+            "SELECT value FROM resource WHERE path = 'u/test-user/state'"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap(),
+        json!({ "count": 2 })
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_resource_lock_held_by_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    set_jwt_secret().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let url =
+        format!("http://localhost:{port}/api/w/test-workspace/resources/lock/u/test-user/state");
+
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by)
+        VALUES ('test-workspace', 'u/test-user/state', '{\"count\": 0}', 'state', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let job_id = Uuid::new_v4();
+    let job_token = windmill_worker::create_token_for_owner(
+        &db,
+        "test-workspace",
+        "u/test-user",
+        "",
+        100,
+        "",
+        &job_id,
+    )
+    .await
+    .unwrap();
+    client
+        .post(&url)
+        .bearer_auth(&job_token)
+        .json(&json!({ "ttl_s": 60 }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let holder_job_id = sqlx::query_scalar::<_, Option<Uuid>>(
+        "SELECT holder_job_id FROM resource_lock
+        WHERE workspace_id = 'test-workspace' AND path = 'u/test-user/state'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(holder_job_id, Some(job_id));
+
+    let conflict = client
+        .post(&url)
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "ttl_s": 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(conflict.status(), reqwest::StatusCode::CONFLICT);
+    assert!(conflict
+        .text()
+        .await
+        .unwrap()
+        .contains(&format!("(job {job_id})")));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_instance_group_mappings(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let api = format!("http://localhost:{port}/api");
+    let post = |url: String, body: serde_json::Value| {
+        client
+            .post(url)
+            .bearer_auth("SECRET_TOKEN")
+            .json(&body)
+            .send()
+    };
+
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer'),
+            ('test-workspace', 'bob@windmill.dev', 'bob', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    post(
+        format!("{api}/groups/create"),
+        json!({ "name": "data-eng" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    post(
+        format!("{api}/w/test-workspace/groups/create"),
+        json!({ "name": "data" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    for (url, user) in [
+        (
+            format!("{api}/groups/adduser/data-eng"),
+            json!({ "email": "alice@windmill.dev" }),
+        ),
+        (
+            format!("{api}/w/test-workspace/groups/adduser/data"),
+            json!({ "username": "bob" }),
+        ),
+    ] {
+        post(url, user).await.unwrap().error_for_status().unwrap();
+    }
+
+    post(
+        format!("{api}/groups/mappings"),
+        json!({
+            "instance_group": "data-eng",
+            "workspace_id": "test-workspace",
+            "workspace_group": "data",
+            "remove_on_unassign": true,
+        }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    let sync = || async {
+        post(format!("{api}/groups/mappings/sync"), json!({}))
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+    };
+    let members = || async {
+        sqlx::query_scalar::<_, String>(
+            "SELECT usr FROM usr_to_group WHERE workspace_id = 'test-workspace' AND group_ = 'data' ORDER BY usr",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap()
+    };
+
+    let synced = sync().await;
+    assert_eq!(synced.len(), 1);
+    assert_eq!(
+        (&synced[0]["added"], &synced[0]["removed"]),
+        (&json!(1), &json!(0))
+    );
+    assert_eq!(members().await, vec!["alice", "bob", "test-user"]);
+
+    let synced = sync().await;
+    assert_eq!(
+        (&synced[0]["added"], &synced[0]["removed"]),
+        (&json!(0), &json!(0))
+    );
+
+    // leaving the instance group removes alice right away, bob was added by hand and stays
+    post(
+        format!("{api}/groups/removeuser/data-eng"),
+        json!({ "email": "alice@windmill.dev" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    assert_eq!(members().await, vec!["bob", "test-user"]);
+
+    post(
+        format!("{api}/groups/adduser/data-eng"),
+        json!({ "email": "bob@windmill.dev" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    post(
+        format!("{api}/groups/removeuser/data-eng"),
+        json!({ "email": "bob@windmill.dev" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    assert_eq!(members().await, vec!["bob", "test-user"]);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_audit_list_filters(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO audit (workspace_id, username, operation, action_kind, resource, timestamp)
+        VALUES
+            ('test-workspace', 'alice', 'scripts.delete', 'delete', 'f/a', now() - interval '3 minutes'),
+            ('test-workspace', 'alice', 'scripts.create', 'create', 'f/b', now() - interval '2 minutes'),
+            ('test-workspace', 'test-user', 'scripts.delete', 'delete', 'f/c', now() - interval '1 minute')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let list = |params: &'static str| {
+        let client = client.clone();
+        async move {
+            client
+                .get(format!(
+                    "http://localhost:{port}/api/w/test-workspace/audit/list?{params}"
+                ))
+                .bearer_auth("SECRET_TOKEN")
+                .send()
+                .await
+                .unwrap()
+        }
+    };
+    let resources = |logs: serde_json::Value| {
+        logs.as_array()
+            .unwrap()
+            .iter()
+            .map(|log| log["resource"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let logs = list("action_kind=delete").await.json().await.unwrap();
+    assert_eq!(resources(logs), vec!["f/c", "f/a"]);
+
+    let logs = list("action_kind=Delete&email=alice@windmill.dev")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resources(logs), vec!["f/a"]);
+
+    let logs = list("username=alice").await.json().await.unwrap();
+    assert_eq!(resources(logs), vec!["f/b", "f/a"]);
+
+    let page: serde_json::Value = list("email=alice@windmill.dev&per_page=1&cursor=")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resources(page["items"].clone()), vec!["f/b"]);
+    let cursor = page["next_cursor"].as_str().unwrap().to_string();
+    let page: serde_json::Value = client
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/audit/list?email=alice@windmill.dev&per_page=1&cursor={cursor}"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(resources(page["items"].clone()), vec!["f/a"]);
+
+    assert_eq!(list("action_kind=drop").await.status(), 400);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_audit_export_takes_over_stale_running_export(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let trigger = |date: &'static str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/workspaces/trigger_audit_export"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "date": date }))
+            .send()
+    };
+
+    for (date, started_at) in [
+        ("2025-01-01", "now()"),
+        ("2025-01-02", "now() - interval '2 hours'"),
+    ] {
+        sqlx::query(&format!(
+            "INSERT INTO audit_log_exports (workspace_id, export_date, status, triggered_by, started_at)
+            VALUES ('test-workspace', $1::date, 'running', 'scheduler', {started_at})"
+        ))
+        .bind(date)
+        .execute(&db)
+        .await
+        .unwrap();
+    }
+
+    let running = trigger("2025-01-01").await.unwrap();
+    assert_eq!(running.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // the test server has no object store configured, so the retried export fails but it is
+    // no longer stuck in the running state
+    let stale = trigger("2025-01-02")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(stale["status"], "failure");
+    assert_eq!(stale["triggered_by"], "test-user");
+    assert!(stale["finished_at"].is_string());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_schedule_dependency_cycle_is_rejected(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let create = |path: &'static str, depends_on_schedule: &'static str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/schedules/create"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "schedule": "0 0 0 * * *",
+                "timezone": "UTC",
+                "script_path": "f/system/etl_step",
+                "is_flow": false,
+                "args": {},
+                "enabled": false,
+                "depends_on_schedule": depends_on_schedule,
+            }))
+            .send()
+    };
+
+    create("f/system/extract", "f/system/load")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    create("f/system/transform", "f/system/extract")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let cycle = create("f/system/load", "f/system/transform").await.unwrap();
+    assert_eq!(cycle.status(), reqwest::StatusCode::BAD_REQUEST);
+    let itself = create("f/system/load", "f/system/load").await.unwrap();
+    assert_eq!(itself.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    create("f/system/load", "f/system/other")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_schedule_waits_for_a_successful_dependency_run(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES ('test-workspace', 'test-user', 'echo loaded', '{}', '', '', 'f/system/etl_step', 424242, 'bash', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/schedules/create"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/load",
+            "schedule": "0 0 0 * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/etl_step",
+            "is_flow": false,
+            "args": {},
+            "enabled": true,
+            "depends_on_schedule": "f/system/extract",
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let queued_runs = || {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT count(*) FROM queue WHERE schedule_path = 'f/system/load'",
+        )
+        .fetch_one(&db)
+    };
+    // the blocked occurrence is recorded without being pushed
+    assert_eq!(queued_runs().await.unwrap(), 0);
+    let skipped_reason = sqlx::query_scalar::<_, String>(
+        "SELECT skipped_reason FROM schedule_run_log
+        WHERE schedule_path = 'f/system/load' AND job_id IS NULL",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        skipped_reason,
+        "upstream schedule f/system/extract has no completed run"
+    );
+    let status = client
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/schedules/dependency_status/f/system/load"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(status["blocked_reason"], json!(skipped_reason));
+
+    // a successful run of the upstream schedule pushes the next run of the dependent one
+    let upstream_run = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "echo extracted".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .push(&db)
+    .await;
+    sqlx::query("UPDATE queue SET schedule_path = 'f/system/extract' WHERE id = $1")
+        .bind(upstream_run)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&upstream_run), port).await;
+
+    tokio::time::timeout(std::time::Duration::from_secs(10), async {
+        while queued_runs().await.unwrap() == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("the dependent schedule was not pushed");
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_max_concurrent_runs_skips_the_occurrence_at_enqueue(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES ('test-workspace', 'test-user', 'echo guarded', '{}', '', '', 'f/system/guarded_script', 434343, 'bash', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/schedules/create"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/guarded",
+            "schedule": "0 0 0 * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/guarded_script",
+            "is_flow": false,
+            "args": {},
+            "enabled": false,
+            "max_concurrent_runs": 1,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let previous_run = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "sleep 1000".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .push(&db)
+    .await;
+    sqlx::query(
+        "UPDATE queue SET schedule_path = 'f/system/guarded', running = true WHERE id = $1",
+    )
+    .bind(previous_run)
+    .execute(&db)
+    .await
+    .unwrap();
+
+    client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/schedules/setenabled/f/system/guarded"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "enabled": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // the next occurrence is recorded as skipped without being pushed, the following one is
+    let (skipped_for, skipped_reason) =
+        sqlx::query_as::<_, (chrono::DateTime<chrono::Utc>, String)>(
+            "SELECT scheduled_for, skipped_reason FROM schedule_run_log
+            WHERE schedule_path = 'f/system/guarded' AND job_id IS NULL",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(skipped_reason, "max_concurrent_runs_reached");
+    let scheduled_fors = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        "SELECT scheduled_for FROM queue WHERE schedule_path = 'f/system/guarded' AND id != $1",
+    )
+    .bind(previous_run)
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        scheduled_fors,
+        vec![skipped_for + chrono::Duration::try_days(1).unwrap()]
+    );
+    assert_eq!(
+        client
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/schedules/get/f/system/guarded"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap()["last_skip_reason"],
+        "max_concurrent_runs_reached"
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_no_flow_overlap_skips_the_flow_run_when_it_starts(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/no_overlap",
+            "schedule": "0 0 0 * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/failing_flow",
+            "is_flow": true,
+            "args": {},
+            "enabled": false,
+            "no_flow_overlap": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    // no_flow_overlap is a single concurrent run
+    let schedule = client
+        .get(format!("{base}/get/f/system/no_overlap"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(schedule["max_concurrent_runs"], json!(1));
+    assert_eq!(schedule["on_conflict"], json!("skip"));
+
+    let previous_run = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "sleep 1000".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .push(&db)
+    .await;
+    let flow_run = RunJob::from(JobPayload::Flow {
+        path: "f/system/failing_flow".to_string(),
+        dedicated_worker: None,
+        apply_preprocessor: false,
+        version: None,
+    })
+    .arg("fail", json!(false))
+    .push(&db)
+    .await;
+    sqlx::query(
+        "UPDATE queue SET schedule_path = 'f/system/no_overlap',
+            running = (id = $1) WHERE id = ANY($2)",
+    )
+    .bind(previous_run)
+    .bind(vec![previous_run, flow_run])
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let listener = listen_for_completed_jobs(&db).await;
+    in_test_worker(&db, listener.find(&flow_run), port).await;
+
+    // the flow stops before its first step and the skip is recorded with its job
+    let skipped_reason = sqlx::query_scalar::<_, String>(
+        "SELECT skipped_reason FROM schedule_run_log
+        WHERE schedule_path = 'f/system/no_overlap' AND job_id = $1",
+    )
+    .bind(flow_run)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(skipped_reason, "max_concurrent_runs_reached");
+    let steps =
+        sqlx::query_scalar::<_, i64>("SELECT count(*) FROM completed_job WHERE parent_job = $1")
+            .bind(flow_run)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(steps, 0);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_resource_usage_matches_exact_paths(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/resources");
+    for path in ["u/test-user/db", "u/test-user/db_2"] {
+        http.post(format!("{base}/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "path": path, "value": {}, "resource_type": "postgresql" }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script(
+                "u/test-user/uses_db_2",
+                "def main(db = \"$res:u/test-user/db_2\"):\n    return db\n",
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+
+    let usage = |path: &'static str| {
+        http.get(format!("{base}/usage/{path}"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+    let usage_of_db = usage("u/test-user/db")
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(usage_of_db, json!({}));
+    let usage_of_db_2 = usage("u/test-user/db_2")
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        usage_of_db_2,
+        json!({ "script": { "paths": ["u/test-user/uses_db_2"], "total": 1 } })
+    );
+
+    let delete = |path: &'static str, force: bool| {
+        http.delete(format!("{base}/delete/{path}?force={force}"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+    let referenced = delete("u/test-user/db_2", false).await.unwrap();
+    assert_eq!(referenced.status(), reqwest::StatusCode::CONFLICT);
+    assert!(referenced
+        .text()
+        .await
+        .unwrap()
+        .contains("script: u/test-user/uses_db_2"));
+
+    let unreferenced = delete("u/test-user/db", false).await.unwrap();
+    assert_eq!(unreferenced.status(), reqwest::StatusCode::OK);
+    let missing = delete("u/test-user/db", false).await.unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::OK);
+    let forced = delete("u/test-user/db_2", true).await.unwrap();
+    assert_eq!(forced.status(), reqwest::StatusCode::OK);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_step_timeout_ms_is_not_rounded_to_seconds(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // rounded up to 2 seconds, the step would complete
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "input_transforms": {},
+                "type": "rawscript",
+                "language": "bash",
+                "content": "sleep 1.8",
+            },
+            "step_timeout_ms": 1200,
+        }],
+    }))
+    .unwrap();
+    let job = RunJob::from(JobPayload::RawFlow { value: flow, path: None, restarted_from: None })
+        .run_until_complete(&db, port)
+        .await;
+
+    assert!(!job.success);
+    let step = &job.flow_status.unwrap()["modules"][0];
+    assert_eq!(step["type"], json!("Failure"));
+    assert_eq!(step["reason"], json!("step_timeout"));
+    let step_job =
+        completed_job(Uuid::parse_str(step["job"].as_str().unwrap()).unwrap(), &db).await;
+    assert!(step_job.duration_ms < 1800);
+    assert_eq!(
+        step_job.json_result().unwrap()["error"]["name"],
+        json!("JobTimeout")
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_cancel_by_schedule_path_keeps_the_next_tick(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+    client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script("u/test-user/tick", "def main():\n    return 1\n", None),
+        )
+        .await
+        .unwrap();
+
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+    http.post(format!("{base}/schedules/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "u/test-user/yearly",
+            "schedule": "0 0 0 1 1 *",
+            "timezone": "UTC",
+            "script_path": "u/test-user/tick",
+            "is_flow": false,
+            "args": {},
+            "enabled": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // a run of the schedule that is due but not started yet
+    let due = http
+        .post(format!("{base}/jobs/run/p/u/test-user/tick"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let due = Uuid::parse_str(&due).unwrap();
+    sqlx::query("UPDATE queue SET schedule_path = 'u/test-user/yearly' WHERE id = $1")
+        .bind(due)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let cancelled = http
+        .post(format!("{base}/jobs/cancel_by_schedule_path"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "schedule_path": "u/test-user/yearly" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        cancelled,
+        json!({ "cancelled_count": 1, "job_ids": [due.to_string()] })
+    );
+
+    let next_ticks = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM queue WHERE workspace_id = 'test-workspace'
+            AND schedule_path = 'u/test-user/yearly' AND scheduled_for > now()",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(next_ticks, 1);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_saved_inputs_with_the_same_args_keep_distinct_names(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/inputs");
+    let runnable = "runnable_id=u/test-user/script&runnable_type=ScriptPath";
+    for name in ["first", "second", "first"] {
+        http.post(format!("{base}/create?{runnable}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "name": name, "args": { "a": 1, "b": 2 } }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let inputs = http
+        .get(format!("{base}/list?{runnable}"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    let mut names = inputs
+        .iter()
+        .map(|i| {
+            (
+                i["name"].as_str().unwrap().to_string(),
+                i["use_count"].as_i64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![("first".to_string(), 2), ("second".to_string(), 1)]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_purge_workspace_deletes_job_side_tables(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let job_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO job_chain (job_id, workspace_id) VALUES ($1, 'test-workspace')")
+        .bind(job_id)
+        .execute(&db)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO script_job_retry (job_id, workspace_id, original_job_id, attempt)
+        VALUES ($1, 'test-workspace', $1, 1)",
+    )
+    .bind(job_id)
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO job_upload (workspace_id, job_id, file_key, created_by, email)
+        VALUES ('test-workspace', $1, 'uploads/file', 'test-user', 'test@windmill.dev')",
+    )
+    .bind(job_id)
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO audit (workspace_id, username, operation, action_kind, resource)
+        VALUES ('test-workspace', 'test-user', 'scripts.create', 'create', 'u/test-user/script')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let report = reqwest::Client::new()
+        .delete(format!(
+            "http://localhost:{port}/api/w/test-workspace/workspaces/purge"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+    for table in ["job_chain", "script_job_retry", "job_upload", "audit"] {
+        assert_eq!(report["deleted_counts"][table], json!(1), "{table}");
+        let left = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT count(*) FROM {table} WHERE workspace_id = 'test-workspace'"
+        ))
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(left, 0, "{table}");
+    }
+
+    assert_eq!(report["deleted_counts"]["workspace"], json!(1));
+    let workspace_left = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM workspace WHERE id = 'test-workspace')",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(!workspace_left);
+}
+
+/// Webhook args with a multipart/form-data body made of a `label` text field and the given text
+/// files
+#[cfg(feature = "parquet")]
+async fn multipart_webhook_args(files: &[(&str, &str)]) -> windmill_api::args::WebhookArgs {
+    use axum::extract::FromRequest;
+
+    let mut body = "--boundary\r\nContent-Disposition: form-data; name=\"label\"\r\n\r\nreport\r\n"
+        .to_string();
+    for (name, content) in files {
+        body.push_str(&format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n{content}\r\n"
+        ));
+    }
+    body.push_str("--boundary--\r\n");
+    let request = axum::extract::Request::builder()
+        .header("content-type", "multipart/form-data; boundary=boundary")
+        .body(axum::body::Body::from(body))
+        .unwrap();
+    windmill_api::args::WebhookArgs {
+        multipart: Some(
+            axum::extract::Multipart::from_request(request, &())
+                .await
+                .unwrap(),
+        ),
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "parquet")]
+#[sqlx::test(fixtures("base"))]
+async fn test_job_uploads_are_limited_claimed_and_cleaned_up(db: Pool<Postgres>) {
+    use object_store::{path::Path, ObjectStore};
+    use windmill_api::args::{delete_expired_uploads_from, MultipartUpload};
+    use windmill_common::error::Error;
+
+    initialize_tracing().await;
+    let os: Arc<dyn ObjectStore> = Arc::new(object_store::memory::InMemory::new());
+    let upload =
+        |job_id: Uuid, file_size_limit: usize, request_size_limit: usize| MultipartUpload {
+            s3_client: os.clone(),
+            w_id: "test-workspace",
+            job_id: Some(job_id),
+            username: "test-user",
+            email: "test@windmill.dev",
+            file_size_limit,
+            request_size_limit,
+        };
+    let stored = |key: String| {
+        let os = os.clone();
+        async move {
+            match os.get(&Path::from(key)).await {
+                Ok(object) => Some(object.bytes().await.unwrap().to_vec()),
+                Err(_) => None,
+            }
+        }
+    };
+
+    let job_id = Uuid::new_v4();
+    let args = multipart_webhook_args(&[("doc", "hello")])
+        .await
+        .upload_multipart(&db, upload(job_id, 10, 100))
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
+    let arg =
+        |name: &str| serde_json::from_str::<serde_json::Value>(args.args[name].get()).unwrap();
+    assert_eq!(arg("label"), json!("report"));
+    assert_eq!(
+        arg("doc"),
+        json!({ "s3": format!("uploads/{job_id}/doc"), "size": 5, "content_type": "text/plain" })
+    );
+    assert_eq!(
+        stored(format!("uploads/{job_id}/doc")).await,
+        Some(b"hello".to_vec())
+    );
+
+    // a second request with the same job id cannot overwrite the files of the first one
+    let reused = multipart_webhook_args(&[("doc", "overwritten")])
+        .await
+        .upload_multipart(&db, upload(job_id, 100, 100))
+        .await;
+    assert!(matches!(reused, Err(Error::Conflict(_))));
+    assert_eq!(
+        stored(format!("uploads/{job_id}/doc")).await,
+        Some(b"hello".to_vec())
+    );
+
+    let too_large_file = Uuid::new_v4();
+    let result = multipart_webhook_args(&[("doc", "more than ten bytes")])
+        .await
+        .upload_multipart(&db, upload(too_large_file, 10, 100))
+        .await;
+    match result {
+        Err(Error::BadRequest(msg)) => assert!(msg.contains("exceeds 10 bytes"), "{msg}"),
+        _ => panic!("expected the file size limit to be enforced"),
+    }
+    assert_eq!(stored(format!("uploads/{too_large_file}/doc")).await, None);
+
+    let too_large_request = Uuid::new_v4();
+    let result = multipart_webhook_args(&[("first", "12345678"), ("second", "12345678")])
+        .await
+        .upload_multipart(&db, upload(too_large_request, 10, 12))
+        .await;
+    match result {
+        Err(Error::BadRequest(msg)) => assert!(msg.contains("exceeds 12 bytes"), "{msg}"),
+        _ => panic!("expected the request size limit to be enforced"),
+    }
+    assert_eq!(
+        stored(format!("uploads/{too_large_request}/first")).await,
+        Some(b"12345678".to_vec())
+    );
+    assert_eq!(
+        stored(format!("uploads/{too_large_request}/second")).await,
+        None
+    );
+
+    // the uploads of the first job expired without the job being pushed, the others are recent
+    sqlx::query("UPDATE job_upload SET created_at = now() - interval '2 days' WHERE job_id = $1")
+        .bind(job_id)
+        .execute(&db)
+        .await
+        .unwrap();
+    delete_expired_uploads_from(&db, "test-workspace", "test-user", Some(os.clone()))
+        .await
+        .unwrap();
+    assert_eq!(stored(format!("uploads/{job_id}/doc")).await, None);
+    assert_eq!(
+        stored(format!("uploads/{too_large_request}/first")).await,
+        Some(b"12345678".to_vec())
+    );
+    let uploads = sqlx::query_scalar::<_, String>(
+        "SELECT file_key FROM job_upload ORDER BY file_key COLLATE \"C\"",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    let mut expected = vec![
+        format!("uploads/{too_large_file}/doc"),
+        format!("uploads/{too_large_request}/first"),
+        format!("uploads/{too_large_request}/second"),
+    ];
+    expected.sort();
+    assert_eq!(uploads, expected);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_disabling_a_user_cancels_their_running_jobs(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+    client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script("u/test-user/long", "def main():\n    return 1\n", None),
+        )
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role)
+        VALUES ('test-workspace', 'other@windmill.dev', 'other-user', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+    let mut jobs = vec![];
+    for created_by in ["other-user", "test-user"] {
+        let job = http
+            .post(format!("{base}/jobs/run/p/u/test-user/long"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let job = Uuid::parse_str(&job).unwrap();
+        // as if a worker had picked the job of the user
+        sqlx::query(
+            "UPDATE queue SET created_by = $1, running = true, started_at = now() WHERE id = $2",
+        )
+        .bind(created_by)
+        .bind(job)
+        .execute(&db)
+        .await
+        .unwrap();
+        jobs.push(job);
+    }
+
+    http.post(format!("{base}/users/update/other-user"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "disabled": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let canceled = |job: Uuid| {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM queue WHERE id = $1 AND canceled = true)
+                OR EXISTS (SELECT 1 FROM completed_job WHERE id = $1 AND canceled = true)",
+        )
+        .bind(job)
+        .fetch_one(&db)
+    };
+    assert!(canceled(jobs[0]).await.unwrap());
+    assert!(!canceled(jobs[1]).await.unwrap());
+}
+
+fn new_python_script(path: &str, content: &str, parent_hash: Option<String>) -> NewScript {
+    NewScript {
+        language: NewScriptLanguage::Python3,
+        content: content.to_string(),
+        path: path.to_string(),
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+        description: "".to_string(),
+        draft_only: None,
+        envs: vec![],
+        is_template: None,
+        kind: None,
+        parent_hash,
+        lock: None,
+        summary: "".to_string(),
+        tag: None,
+        schema: std::collections::HashMap::new(),
+        ws_error_handler_muted: Some(false),
+        priority: None,
+        delete_after_use: None,
+        timeout: None,
+        restart_unless_cancelled: None,
+        deployment_message: None,
+        concurrency_key: None,
+        visible_to_runner_only: None,
+        no_main_func: None,
+        codebase: None,
+        has_preprocessor: None,
+        on_behalf_of_email: None,
+        strict_args: None,
+        retry_on_failure: None,
+        redact_args: None,
+    }
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_token_run_scope_with_path_glob(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+    for path in ["f/system/notify", "u/test-user/charge"] {
+        client
+            .create_script(
+                "test-workspace",
+                None,
+                &new_python_script(path, "def main():\n    return 1\n", None),
+            )
+            .await
+            .unwrap();
+    }
+
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api");
+    let token = http
+        .post(format!("{base}/users/tokens/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "label": "alerts only", "scopes": ["run:script/f/system/*"] }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let run = |path: &'static str| {
+        http.post(format!("{base}/w/test-workspace/jobs/run/p/{path}"))
+            .bearer_auth(&token)
+            .json(&json!({}))
+            .send()
+    };
+
+    let allowed = run("f/system/notify").await.unwrap();
+    assert_eq!(allowed.status(), reqwest::StatusCode::CREATED);
+    let job_id = allowed.text().await.unwrap();
+
+    let denied = run("u/test-user/charge").await.unwrap();
+    assert_eq!(denied.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let list = http
+        .get(format!("{base}/w/test-workspace/jobs/list"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(list.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let get = http
+        .get(format!("{base}/w/test-workspace/jobs_u/get/{job_id}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(get.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let invalid = http
+        .post(format!("{base}/users/tokens/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "label": "invalid", "scopes": ["run:script/f/*/notify"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_extend_token_with_session_token(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin, expiration) VALUES
+            ('SESSION_TOKEN', 'test@windmill.dev', 'session', true, now() + interval '1 hour'),
+            ('abc_TOKEN_1', 'test@windmill.dev', 'cli', false, now() + interval '1 hour'),
+            ('abcdTOKEN_2', 'test@windmill.dev', 'cli', false, now() + interval '1 hour')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let extend = |token: &'static str, prefix: &'static str| {
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{port}/api/users/tokens/{prefix}/extend"
+            ))
+            .bearer_auth(token)
+            .json(&json!({ "extend_by_days": 30 }))
+            .send()
+    };
+
+    // only a session token can extend the tokens of the user
+    let denied = extend("SECRET_TOKEN", "abc_").await.unwrap();
+    assert_eq!(denied.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // `_` is matched literally, it does not also select abcdTOKEN_2
+    let extended = extend("SESSION_TOKEN", "abc_").await.unwrap();
+    assert_eq!(extended.status(), reqwest::StatusCode::OK);
+
+    let expirations = sqlx::query_as::<_, (String, bool)>(
+        "SELECT token, expiration > now() + interval '29 days' FROM token
+        WHERE token LIKE 'abc%' ORDER BY token COLLATE \"C\"",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        expirations,
+        vec![
+            ("abc_TOKEN_1".to_string(), true),
+            ("abcdTOKEN_2".to_string(), false)
+        ]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_deploy_reuses_lock_of_same_imports(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+    let path = "f/system/lock_reuse";
+    let content = "import os\n\ndef main():\n    return os.name\n";
+
+    let first = client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script(path, content, None),
+        )
+        .await
+        .unwrap();
+    assert_eq!(first.headers()["x-windmill-lock"], "regenerated");
+
+    let mut completed = listen_for_completed_jobs(&db).await;
+    in_test_worker(
+        &db,
+        async move {
+            completed.next().await; // lock of the first deployment
+        },
+        port,
+    )
+    .await;
+
+    let first_hash = query!("SELECT hash FROM script WHERE path = $1", path)
+        .fetch_one(&db)
+        .await
+        .unwrap()
+        .hash;
+    let second = client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script(
+                path,
+                &format!("{content}\n# only the body changed\n"),
+                Some(ScriptHash(first_hash).to_string()),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(second.headers()["x-windmill-lock"], "reused");
+
+    let dependency_jobs = sqlx::query_scalar::<_, i64>(
+        "SELECT (SELECT COUNT(*) FROM queue WHERE job_kind = 'dependencies' AND script_path = $1)
+            + (SELECT COUNT(*) FROM completed_job WHERE job_kind = 'dependencies' AND script_path = $1)",
+    )
+    .bind(path)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(dependency_jobs, 1);
+
+    let locks = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT lock FROM script WHERE path = $1 ORDER BY created_at",
+    )
+    .bind(path)
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(locks.len(), 2);
+    assert!(locks[1].is_some());
+    assert_eq!(locks[0], locks[1]);
+
+    let second_hash = sqlx::query_scalar::<_, i64>(
+        "SELECT hash FROM script WHERE path = $1 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(path)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    let third = client
+        .create_script(
+            "test-workspace",
+            None,
+            &new_python_script(
+                path,
+                &format!("# requirements:\n# requests==2.31.0\n{content}"),
+                Some(ScriptHash(second_hash).to_string()),
+            ),
+        )
+        .await
+        .unwrap();
+    assert_eq!(third.headers()["x-windmill-lock"], "regenerated");
+
+    server.close().await.unwrap();
+}
+
+async fn run_deployed_relative_imports(
+    db: &Pool<Postgres>,
+    script_content: String,
+    language: ScriptLang,
+) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = windmill_api_client::create_client(
+        &format!("http://localhost:{port}"),
+        "SECRET_TOKEN".to_string(),
+    );
+
+    client
+        .create_script(
+            "test-workspace",
+            None,
+            &NewScript {
+                language: NewScriptLanguage::from_str(language.as_str()).unwrap(),
+                content: script_content,
+                path: "f/system/test_import".to_string(),
+                concurrent_limit: None,
+                concurrency_time_window_s: None,
+                cache_ttl: None,
+                dedicated_worker: None,
+                description: "".to_string(),
+                draft_only: None,
+                envs: vec![],
+                is_template: None,
+                kind: None,
+                parent_hash: None,
+                lock: None,
+                summary: "".to_string(),
+                tag: None,
+                schema: std::collections::HashMap::new(),
+                ws_error_handler_muted: Some(false),
+                priority: None,
+                delete_after_use: None,
+                timeout: None,
+                restart_unless_cancelled: None,
+                deployment_message: None,
+                concurrency_key: None,
+                visible_to_runner_only: None,
+                no_main_func: None,
+                codebase: None,
+                has_preprocessor: None,
+                on_behalf_of_email: None,
+                strict_args: None,
+                retry_on_failure: None,
+                redact_args: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let mut completed = listen_for_completed_jobs(&db).await;
+    let db2 = db.clone();
+    in_test_worker(
+        &db,
+        async move {
+            completed.next().await; // deployed script
+
+            let script = query!(
+                "SELECT hash FROM script WHERE path = $1",
+                "f/system/test_import".to_string()
+            )
+            .fetch_one(&db2)
+            .await
+            .unwrap();
+
+            let job = RunJob::from(JobPayload::ScriptHash {
+                path: "f/system/test_import".to_string(),
+                hash: ScriptHash(script.hash),
+                custom_concurrency_key: None,
+                concurrent_limit: None,
+                concurrency_time_window_s: None,
+                cache_ttl: None,
+                dedicated_worker: None,
+                language,
+                priority: None,
+                apply_preprocessor: false,
+            })
+            .push(&db2)
+            .await;
+
+            completed.next().await; // completed job
+
+            let result = completed_job(job, &db2).await.json_result().unwrap();
+
+            assert_eq!(
+                result,
+                serde_json::json!([
+                    "f/system/same_folder_script",
+                    "f/system/same_folder_script",
+                    "f/system_relative/different_folder_script",
+                    "f/system_relative/different_folder_script"
+                ])
+            );
+        },
+        port,
+    )
+    .await;
+}
+
+async fn run_preview_relative_imports(
+    db: &Pool<Postgres>,
+    script_content: String,
+    language: ScriptLang,
+) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let mut completed = listen_for_completed_jobs(&db).await;
+    let db2 = db.clone();
+    in_test_worker(
+        &db,
+        async move {
+            let job = RunJob::from(JobPayload::Code(RawCode {
+                hash: None,
+                content: script_content,
+                path: Some("f/system/test_import".to_string()),
+                language,
+                lock: None,
+                custom_concurrency_key: None,
+                concurrent_limit: None,
+                concurrency_time_window_s: None,
+                cache_ttl: None,
+                dedicated_worker: None,
+            }))
+            .push(&db2)
+            .await;
+
+            completed.next().await; // completed job
+
+            let result = completed_job(job, &db2).await.json_result().unwrap();
+
+            assert_eq!(
+                result,
+                serde_json::json!([
+                    "f/system/same_folder_script",
+                    "f/system/same_folder_script",
+                    "f/system_relative/different_folder_script",
+                    "f/system_relative/different_folder_script"
+                ])
+            );
+        },
+        port,
+    )
+    .await;
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_resource_type_versions_and_rollback(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/resources/type");
+
+    let schema_v1 = json!({ "type": "object", "properties": { "host": { "type": "string" } } });
+    let schema_v2 = json!({ "type": "object", "properties": { "url": { "type": "string" } } });
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "name": "smtp_server", "schema": schema_v1, "description": "" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{base}/update/smtp_server"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "schema": schema_v2 }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let list_versions = || async {
+        client
+            .get(format!("{base}/versions/smtp_server"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|version| version["id"].as_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let versions = list_versions().await;
+    assert_eq!(versions.len(), 2);
+    let first = versions[1];
+
+    let at_first = client
+        .get(format!("{base}/get/v/{first}/smtp_server"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(at_first["schema"], schema_v1);
+    assert_eq!(at_first["created_by"], "test-user");
+
+    client
+        .post(format!("{base}/rollback/v/{first}/smtp_server"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let schema = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+        "SELECT schema FROM resource_type WHERE name = 'smtp_server' AND workspace_id = 'test-workspace'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(schema, Some(schema_v1));
+    // the rollback is recorded as a new version on top of the history
+    let versions = list_versions().await;
+    assert_eq!(versions.len(), 3);
+    assert!(versions[0] > versions[1]);
+
+    let missing = client
+        .post(format!("{base}/rollback/v/{}/smtp_server", versions[0] + 1))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_paused_schedule_catches_up_according_to_its_policy(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES ('test-workspace', 'test-user', 'echo hourly', '{}', '', '', 'f/system/hourly_script', 545454, 'bash', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/hourly",
+            "schedule": "0 0 * * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/hourly_script",
+            "is_flow": false,
+            "args": {},
+            "enabled": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // 3 hourly occurrences fall between now and the end of the pause
+    let paused_until = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        "SELECT date_trunc('hour', now()) + interval '3 hours 30 minutes'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    let next_occurrence = paused_until + chrono::Duration::try_minutes(30).unwrap();
+
+    let pause_with = |catchup_policy: &'static str| {
+        let client = client.clone();
+        let base = base.clone();
+        let db = db.clone();
+        async move {
+            client
+                .post(format!("{base}/pause/f/system/hourly"))
+                .bearer_auth("SECRET_TOKEN")
+                .json(&json!({ "paused_until": paused_until, "catchup_policy": catchup_policy }))
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap();
+            sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+                "SELECT scheduled_for FROM queue WHERE schedule_path = 'f/system/hourly'
+                ORDER BY scheduled_for",
+            )
+            .fetch_all(&db)
+            .await
+            .unwrap()
+        }
+    };
+
+    assert_eq!(
+        pause_with("run_all").await,
+        vec![paused_until, paused_until, paused_until, next_occurrence]
+    );
+    assert_eq!(
+        pause_with("run_once").await,
+        vec![paused_until, next_occurrence]
+    );
+    assert_eq!(pause_with("skip").await, vec![next_occurrence]);
+
+    let schedule = client
+        .get(format!("{base}/get/f/system/hourly"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(schedule["catchup_policy"], "skip");
+    assert_eq!(
+        serde_json::from_value::<chrono::DateTime<chrono::Utc>>(schedule["next_run"].clone())
+            .unwrap(),
+        next_occurrence
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_script_dependents_are_resolved_transitively(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    for (path, hash, content) in [
+        (
+            "f/system/direct_caller",
+            676761_i64,
+            "wmill.run_script_by_path(path=\"f/system/failing_script\")",
+        ),
+        (
+            "f/system/lookalike_caller",
+            676762,
+            "wmill.run_script_by_path(path=\"f/system/failing_script_v2\")",
+        ),
+        (
+            "f/system/flow_caller",
+            676763,
+            "await wmill.runFlowAsync('f/system/failing_flow', {})",
+        ),
+    ] {
+        sqlx::query(
+            "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+            VALUES ('test-workspace', 'test-user', $1, '{}', $2, '', $2, $3, 'bun', '')",
+        )
+        .bind(content)
+        .bind(path)
+        .bind(hash)
+        .execute(&db)
+        .await
+        .unwrap();
+    }
+
+    let dependents = |depth: u8| async move {
+        reqwest::Client::new()
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/scripts/dependents/f/system/failing_script?depth={depth}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|dependent| {
+                (
+                    dependent["kind"].as_str().unwrap().to_string(),
+                    dependent["path"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let direct = vec![
+        ("flow".to_string(), "f/system/failing_flow".to_string()),
+        ("script".to_string(), "f/system/direct_caller".to_string()),
+    ];
+    assert_eq!(dependents(1).await, direct);
+    let mut transitive = direct;
+    transitive.push(("script".to_string(), "f/system/flow_caller".to_string()));
+    assert_eq!(dependents(2).await, transitive);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_archive_script_path_cancels_its_queued_jobs(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let run = |path: &'static str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/p/{path}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({}))
+            .send()
+    };
+    let mut archived_jobs = vec![];
+    for _ in 0..2 {
+        let job_id = run("f/system/failing_script")
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        archived_jobs.push(Uuid::parse_str(&job_id).unwrap());
+    }
+    let other_job = run("f/system/schedule_error_handler")
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let other_job = Uuid::parse_str(&other_job).unwrap();
+
+    let archived = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/scripts/archive_path/f/system/failing_script"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        archived,
+        json!({ "archived": true, "cancelled_job_count": 2 })
+    );
+
+    let script_archived = sqlx::query_scalar::<_, bool>(
+        "SELECT archived FROM script WHERE path = 'f/system/failing_script' AND workspace_id = 'test-workspace'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(script_archived);
+    let mut canceled = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM completed_job WHERE canceled AND canceled_by = 'test-user'",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    canceled.sort();
+    archived_jobs.sort();
+    assert_eq!(canceled, archived_jobs);
+    let still_queued = sqlx::query_scalar::<_, Uuid>("SELECT id FROM queue")
+        .fetch_all(&db)
+        .await
+        .unwrap();
+    assert_eq!(still_queued, vec![other_job]);
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_bulk_enable_and_disable_schedules(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+
+    for path in ["f/system/daily_a", "f/system/daily_b"] {
+        client
+            .post(format!("{base}/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "schedule": "0 0 0 * * *",
+                "timezone": "UTC",
+                "script_path": "f/system/failing_script",
+                "is_flow": false,
+                "args": { "fail": false },
+                "enabled": false,
+            }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let bulk = |action: &'static str, paths: Vec<String>| {
+        client
+            .post(format!("{base}/{action}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&paths)
+            .send()
+    };
+    let queued_schedules = || async {
+        sqlx::query_scalar::<_, String>(
+            "SELECT schedule_path FROM queue ORDER BY schedule_path COLLATE \"C\"",
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap()
+    };
+
+    let enabled = bulk(
+        "bulk_enable",
+        [
+            "f/system/daily_a",
+            "f/system/daily_b",
+            "f/system/missing",
+            "f/system/daily_a",
+        ]
+        .map(String::from)
+        .to_vec(),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(
+        enabled,
+        json!({ "enabled_count": 2, "not_found_paths": ["f/system/missing"] })
+    );
+    assert_eq!(
+        queued_schedules().await,
+        vec!["f/system/daily_a", "f/system/daily_b"]
+    );
+
+    bulk("bulk_disable", vec!["f/system/daily_a".to_string()])
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(queued_schedules().await, vec!["f/system/daily_b"]);
+    let enabled =
+        sqlx::query_scalar::<_, bool>("SELECT enabled FROM schedule ORDER BY path COLLATE \"C\"")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+    assert_eq!(enabled, vec![false, true]);
+
+    let too_many = bulk(
+        "bulk_disable",
+        (0..101).map(|i| format!("f/system/daily_{i}")).collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(too_many.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_audit_export_streams_the_matching_logs_oldest_first(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // more matching logs than fit in one export chunk
+    sqlx::query(
+        "INSERT INTO audit (workspace_id, username, operation, action_kind, resource, timestamp)
+        SELECT 'test-workspace', 'test-user', 'jobs.run', 'execute', 'f/job_' || i,
+            '2024-01-01T00:00:00Z'::timestamptz + i * interval '1 second'
+        FROM generate_series(1, 1001) i",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO audit (workspace_id, username, operation, action_kind, resource, timestamp)
+        VALUES
+            ('test-workspace', 'test-user', 'scripts.create', 'create', 'f/other_operation', '2024-01-02T00:00:00Z'),
+            ('test-workspace', 'alice', 'jobs.run', 'execute', 'f/other_user', '2024-01-02T00:00:00Z'),
+            ('test-workspace', 'test-user', 'jobs.run', 'execute', 'f/too_late', '2024-03-01T00:00:00Z')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let export = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/audit/export?operation_prefix=jobs.&username=test-user&before=2024-02-01T00:00:00Z"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(export.headers()["content-type"], "application/x-ndjson");
+    let resources = export
+        .text()
+        .await
+        .unwrap()
+        .lines()
+        .map(|line| {
+            serde_json::from_str::<serde_json::Value>(line).unwrap()["resource"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        resources,
+        (1..=1001).map(|i| format!("f/job_{i}")).collect::<Vec<_>>()
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_schedule_next_runs_preview(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/noon",
+            "schedule": "0 0 12 * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/failing_script",
+            "is_flow": false,
+            "args": {},
+            "enabled": false,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let next_runs = |path: &'static str, query: Vec<(&'static str, &'static str)>| {
+        client
+            .get(format!("{base}/next_runs/{path}"))
+            .bearer_auth("SECRET_TOKEN")
+            .query(&query)
+            .send()
+    };
+    let runs_of = |response: reqwest::Response| async move {
+        response
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<chrono::DateTime<chrono::Utc>>>()
+            .await
+            .unwrap()
+    };
+
+    let runs = runs_of(
+        next_runs("f/system/noon", vec![("count", "3")])
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(runs.len(), 3);
+    assert!(runs[0] > chrono::Utc::now());
+    assert!(runs
+        .iter()
+        .all(|run| run.format("%H:%M:%S").to_string() == "12:00:00"));
+    assert!(runs
+        .windows(2)
+        .all(|w| w[1] - w[0] == chrono::Duration::try_days(1).unwrap()));
+
+    let runs = runs_of(
+        next_runs("f/system/noon", vec![("count", "1000")])
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(runs.len(), 100);
+
+    // an ad-hoc cron can be previewed for a schedule that is not saved yet
+    let runs = runs_of(
+        next_runs(
+            "f/system/not_saved_yet",
+            vec![("count", "2"), ("cron", "0 0 * * * *"), ("timezone", "UTC")],
+        )
+        .await
+        .unwrap(),
+    )
+    .await;
+    assert_eq!(runs[1] - runs[0], chrono::Duration::try_hours(1).unwrap());
+
+    assert_eq!(
+        next_runs("f/system/not_saved_yet", vec![])
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[cfg(feature = "enterprise")]
+#[sqlx::test(fixtures("base"))]
+async fn test_rerun_failed_iterations_of_a_flow(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "loop",
+            "value": {
+                "type": "forloopflow",
+                "iterator": { "type": "javascript", "expr": "flow_input.items" },
+                "skip_failures": true,
+                "modules": [{
+                    "id": "even_fails",
+                    "value": {
+                        "input_transforms": {
+                            "n": { "type": "javascript", "expr": "flow_input.iter.value" },
+                        },
+                        "type": "rawscript",
+                        "language": "python3",
+                        "content": "def main(n):\n    if n % 2 == 0:\n        raise Exception(n)\n    return n",
+                    },
+                }],
+            },
+        }],
+    }))
+    .unwrap();
+    let completed = RunJob::from(JobPayload::RawFlow {
+        value: flow,
+        path: Some("f/system/loop_flow".to_string()),
+        restarted_from: None,
+    })
+    .arg("items", json!([1, 2, 3, 4]))
+    .run_until_complete(&db, port)
+    .await;
+
+    let rerun = reqwest::Client::new()
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/jobs/flow/rerun_failed_iterations/{}",
+            completed.id
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(rerun["step_id"], "loop");
+    assert_eq!(rerun["iterations"], json!([1, 3]));
+
+    // the loop of the new run only iterates over the inputs of the failed iterations
+    let rerun_id = Uuid::parse_str(rerun["job_id"].as_str().unwrap()).unwrap();
+    let iterator = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT COALESCE(queue.raw_flow, job.raw_flow)->'modules'->0->'value'->'iterator'
+        FROM queue LEFT JOIN job ON job.id = queue.id WHERE queue.id = $1",
+    )
+    .bind(rerun_id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(iterator, json!({ "type": "static", "value": [2, 4] }));
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_flow_test_data_can_be_saved_run_and_deleted(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/flows/test_data");
+
+    let create = |path: &'static str, name: &'static str, args: serde_json::Value| {
+        client
+            .post(format!("{base}/create/{path}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "name": name, "args": args }))
+            .send()
+    };
+    for (name, args) in [
+        ("passing", json!({ "fail": true })),
+        ("failing", json!({ "fail": true })),
+        // saving a payload under an existing name replaces it
+        ("passing", json!({ "fail": false })),
+    ] {
+        create("f/system/failing_flow", name, args)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    assert_eq!(
+        create("f/system/missing_flow", "passing", json!({}))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+
+    let list = || async {
+        client
+            .get(format!("{base}/list/f/system/failing_flow"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|test_data| (test_data["name"].clone(), test_data["args"].clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        list().await,
+        vec![
+            (json!("failing"), json!({ "fail": true })),
+            (json!("passing"), json!({ "fail": false })),
+        ]
+    );
+
+    let job_id = client
+        .get(format!(
+            "{base}/list/f/system/failing_flow?run=true&name=passing"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let (script_path, args) = sqlx::query_as::<_, (Option<String>, Option<serde_json::Value>)>(
+        "SELECT script_path, args FROM queue WHERE id = $1",
+    )
+    .bind(Uuid::parse_str(&job_id).unwrap())
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(script_path.as_deref(), Some("f/system/failing_flow"));
+    assert_eq!(args, Some(json!({ "fail": false })));
+
+    let delete = || {
+        client
+            .delete(format!("{base}/delete/failing/f/system/failing_flow"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+    delete().await.unwrap().error_for_status().unwrap();
+    assert_eq!(
+        list().await,
+        vec![(json!("passing"), json!({ "fail": false }))]
+    );
+    assert_eq!(
+        delete().await.unwrap().status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_replay_captures_into_script_and_flow_runs(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let mut capture_ids = vec![];
+    for (payload, age) in [
+        (json!({ "fail": false }), "2 minutes"),
+        (json!("not an object"), "1 minute"),
+        (json!({ "fail": true }), "0 minutes"),
+    ] {
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO capture (workspace_id, path, is_flow, trigger_kind, payload, created_by, created_at)
+            VALUES ('test-workspace', 'f/system/failing_script', false, 'webhook', $1, 'test-user', now() - $2::interval)
+            RETURNING id",
+        )
+        .bind(payload)
+        .bind(age)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        capture_ids.push(id);
+    }
+
+    let replay = |id: i64, query: &'static str, target: serde_json::Value| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/capture/replay/{id}?{query}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&target)
+            .send()
+    };
+    let queued_job = |id: Uuid| {
+        sqlx::query_as::<_, (Option<String>, Option<serde_json::Value>)>(
+            "SELECT script_path, args FROM queue WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_one(&db)
+    };
+
+    // a capture of a script can be replayed into a flow
+    let job_id = replay(
+        capture_ids[0],
+        "",
+        json!({ "runnable_kind": "flow", "path": "f/system/failing_flow" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    let job_id = Uuid::parse_str(&job_id).unwrap();
+    assert_eq!(
+        queued_job(job_id).await.unwrap(),
+        (
+            Some("f/system/failing_flow".to_string()),
+            Some(json!({ "fail": false }))
+        )
+    );
+    let replay_job_ids =
+        sqlx::query_scalar::<_, Vec<Uuid>>("SELECT replay_job_ids FROM capture WHERE id = $1")
+            .bind(capture_ids[0])
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert_eq!(replay_job_ids, vec![job_id]);
+
+    let script_target = json!({ "runnable_kind": "script", "path": "f/system/failing_script" });
+    assert_eq!(
+        replay(capture_ids[1], "", script_target.clone())
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+
+    // in bulk mode, the captures that cannot be replayed are reported without failing the others
+    let results = replay(capture_ids[0], "bulk=true&count=3", script_target)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    assert_eq!(
+        results
+            .iter()
+            .map(|result| result["capture_id"].as_i64().unwrap())
+            .collect::<Vec<_>>(),
+        vec![capture_ids[2], capture_ids[1], capture_ids[0]]
+    );
+    assert!(results[1]["job_id"].is_null());
+    assert!(results[1]["error"]
+        .as_str()
+        .unwrap()
+        .contains("not a JSON object"));
+    let newest_job = Uuid::parse_str(results[0]["job_id"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        queued_job(newest_job).await.unwrap(),
+        (
+            Some("f/system/failing_script".to_string()),
+            Some(json!({ "fail": true }))
+        )
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_list_workers_serving_the_workspace(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let running_job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "sleep 1000".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .push(&db)
+    .await;
+    sqlx::query("UPDATE queue SET running = true WHERE id = $1")
+        .bind(running_job)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    sqlx::query(
+        "INSERT INTO worker_ping
+            (worker_instance, worker, ip, worker_group, wm_version, current_job_id, current_job_workspace_id, ping_at)
+        VALUES
+            ('instance', 'wk-running', 'ip', 'default', 'test', $1, 'test-workspace', now()),
+            ('instance', 'wk-idle', 'ip', 'default', 'test', NULL, 'test-workspace', now() - interval '1 minute'),
+            ('instance', 'wk-other-workspace', 'ip', 'default', 'test', NULL, 'other-workspace', now()),
+            ('instance', 'wk-stale', 'ip', 'default', 'test', NULL, 'test-workspace', now() - interval '10 minutes')",
+    )
+    .bind(running_job)
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let workers = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{port}/api/w/test-workspace/workers"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|worker| {
+            (
+                worker["worker"].clone(),
+                worker["current_job_count"].clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        workers,
+        vec![
+            (json!("wk-running"), json!(1)),
+            (json!("wk-idle"), json!(0))
+        ]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_list_children_of_a_flow_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [
+            {
+                "id": "a",
+                "value": {
+                    "type": "rawscript",
+                    "content": "echo a",
+                    "language": "bash",
+                    "input_transforms": {},
+                },
+            },
+            {
+                "id": "b",
+                "value": {
+                    "type": "rawscript",
+                    "content": "echo b",
+                    "language": "bash",
+                    "input_transforms": {},
+                },
+            },
+        ],
+    }))
+    .unwrap();
+    let flow_job =
+        RunJob::from(JobPayload::RawFlow { value: flow, path: None, restarted_from: None })
+            .run_until_complete(&db, port)
+            .await;
+    assert!(flow_job.success);
+
+    // a child that has not started yet is listed last
+    let queued_child = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content: "echo queued".to_string(),
+        path: None,
+        lock: None,
+        language: ScriptLang::Bash,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .push(&db)
+    .await;
+    sqlx::query("UPDATE queue SET parent_job = $1 WHERE id = $2")
+        .bind(flow_job.id)
+        .bind(queued_child)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let children = |query: &'static str| async move {
+        reqwest::Client::new()
+            .get(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/completed/{}/children?{query}",
+                flow_job.id
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+    };
+    let all = children("").await;
+    assert_eq!(all.len(), 3);
+    assert!(all[..2].iter().all(|child| child["success"] == true));
+    assert_eq!(all[2]["id"], json!(queued_child));
+    assert!(all[2]["success"].is_null());
+    assert!(all.iter().all(|child| child.get("args").is_none()));
+
+    let second_page = children("page=2&per_page=2").await;
+    assert_eq!(second_page, all[2..].to_vec());
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_flow_runs_and_schedules_pinned_to_a_flow_version(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let pinned: i64 = 1443253234253452;
+
+    let latest = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO flow_version (workspace_id, path, schema, value, created_by)
+        SELECT workspace_id, path, schema, value, 'test-user' FROM flow_version WHERE id = $1
+        RETURNING id",
+    )
+    .bind(pinned)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    sqlx::query("UPDATE flow SET versions = array_append(versions, $1) WHERE path = 'f/system/failing_flow'")
+        .bind(latest)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let run = |query: String| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/f/f/system/failing_flow?{query}"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "fail": false }))
+            .send()
+    };
+    let version_of_job = |job_id: String| {
+        sqlx::query_scalar::<_, Option<i64>>("SELECT script_hash FROM queue WHERE id = $1")
+            .bind(Uuid::parse_str(&job_id).unwrap())
+            .fetch_one(&db)
+    };
+
+    let job_id = run(String::new())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(version_of_job(job_id).await.unwrap(), Some(latest));
+    let job_id = run(format!("version={pinned}"))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(version_of_job(job_id).await.unwrap(), Some(pinned));
+    assert_eq!(
+        run(format!("version={}", latest + 1))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+
+    let create_schedule = |path: &'static str, pinned_version_id: i64| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/schedules/create"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "schedule": "0 0 0 * * *",
+                "timezone": "UTC",
+                "script_path": "f/system/failing_flow",
+                "is_flow": true,
+                "args": { "fail": false },
+                "enabled": true,
+                "pinned_version_id": pinned_version_id,
+            }))
+            .send()
+    };
+    create_schedule("f/system/pinned", pinned)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let scheduled_version = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT script_hash FROM queue WHERE schedule_path = 'f/system/pinned'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(scheduled_version, Some(pinned));
+    assert_eq!(
+        create_schedule("f/system/pinned_to_missing", latest + 1)
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_starred_inputs_are_listed_first_and_only_starred_by_their_creator(
+    db: Pool<Postgres>,
+) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let http = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/inputs");
+    let runnable = "runnable_id=u/test-user/script&runnable_type=ScriptPath";
+
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token (token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let mut ids = vec![];
+    for (name, args) in [("older", json!({ "a": 1 })), ("newer", json!({ "a": 2 }))] {
+        let id = http
+            .post(format!("{base}/create?{runnable}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "name": name, "args": args }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<String>()
+            .await
+            .unwrap();
+        http.post(format!("{base}/update"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "id": id, "name": name, "is_public": true }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        ids.push(id);
+    }
+    sqlx::query("UPDATE input SET last_used_at = now() - interval '1 hour' WHERE name = 'older'")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let star = |token: &'static str, id: String| {
+        http.post(format!("{base}/star/{id}"))
+            .bearer_auth(token)
+            .send()
+    };
+    assert_eq!(
+        star("ALICE_TOKEN", ids[0].clone()).await.unwrap().status(),
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    star("SECRET_TOKEN", ids[0].clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let list = |query: &'static str| {
+        let http = http.clone();
+        let base = base.clone();
+        async move {
+            http.get(format!("{base}/list?{runnable}{query}"))
+                .bearer_auth("ALICE_TOKEN")
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap()
+                .json::<Vec<serde_json::Value>>()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|input| (input["name"].clone(), input["is_starred"].clone()))
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(
+        list("").await,
+        vec![
+            (json!("older"), json!(true)),
+            (json!("newer"), json!(false))
+        ]
+    );
+    assert_eq!(
+        list("&is_starred=true").await,
+        vec![(json!("older"), json!(true))]
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_patch_resource_merges_the_top_level_keys(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/resources");
+
+    sqlx::query(
+        "INSERT INTO resource_type (workspace_id, name, schema, description)
+        VALUES ('test-workspace', 'smtp_server', $1, '')",
+    )
+    .bind(json!({
+        "type": "object",
+        "properties": { "host": { "type": "string" }, "port": { "type": "integer" } },
+        "required": ["host"]
+    }))
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by) VALUES
+            ('test-workspace', 'u/test-user/smtp', '{\"host\": \"smtp.windmill.dev\", \"port\": 25, \"tls\": true}', 'smtp_server', 'test-user'),
+            ('test-workspace', 'u/test-user/plain', '\"not an object\"', 'smtp_server', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let patch = |path: &'static str, patch: serde_json::Value| {
+        client
+            .patch(format!("{base}/update/{path}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&patch)
+            .send()
+    };
+    let value_of = |path: &'static str| {
+        sqlx::query_scalar::<_, serde_json::Value>(
+            "SELECT value FROM resource WHERE path = $1 AND workspace_id = 'test-workspace'",
+        )
+        .bind(path)
+        .fetch_one(&db)
+    };
+
+    let patched = patch(
+        "u/test-user/smtp",
+        json!({ "port": 587, "tls": null, "user": "windmill" }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    let expected = json!({ "host": "smtp.windmill.dev", "port": 587, "user": "windmill" });
+    assert_eq!(patched, expected);
+    assert_eq!(value_of("u/test-user/smtp").await.unwrap(), expected);
+
+    // the patched value must still match the resource type schema
+    assert_eq!(
+        patch("u/test-user/smtp", json!({ "host": null }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(value_of("u/test-user/smtp").await.unwrap(), expected);
+
+    assert_eq!(
+        patch("u/test-user/plain", json!({ "host": "smtp.windmill.dev" }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        patch("u/test-user/missing", json!({}))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_labels_of_completed_jobs_can_be_edited_after_the_fact(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/jobs/completed");
+
+    let bash = |content: &str| {
+        RunJob::from(JobPayload::Code(RawCode {
+            hash: None,
+            content: content.to_string(),
+            path: None,
+            lock: None,
+            language: ScriptLang::Bash,
+            custom_concurrency_key: None,
+            concurrent_limit: None,
+            concurrency_time_window_s: None,
+            cache_ttl: None,
+            dedicated_worker: None,
+        }))
+    };
+    let first = bash("echo '{\"n\": 1}' > result.json")
+        .run_until_complete(&db, port)
+        .await;
+    let second = bash("echo '{\"n\": 2}' > result.json")
+        .run_until_complete(&db, port)
+        .await;
+    let not_an_object = bash("echo plain").run_until_complete(&db, port).await;
+
+    let labels = client
+        .post(format!("{base}/labels/{}", first.id))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "add": ["b", "a"] }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<String>>()
+        .await
+        .unwrap();
+    assert_eq!(labels, vec!["a", "b"]);
+
+    let labeled = client
+        .post(format!("{base}/labels"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "job_ids": [first.id, second.id], "add": ["c"], "remove": ["a"] }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap();
+    let mut labeled = labeled
+        .into_iter()
+        .map(|job| (job["id"].clone(), job["labels"].clone()))
+        .collect::<Vec<_>>();
+    labeled.sort_by_key(|(id, _)| id.to_string());
+    let mut expected = vec![
+        (json!(first.id), json!(["b", "c"])),
+        (json!(second.id), json!(["c"])),
+    ];
+    expected.sort_by_key(|(id, _)| id.to_string());
+    assert_eq!(labeled, expected);
+
+    // the labels are the ones the label filter of the completed jobs list matches on
+    let mut listed = client
+        .get(format!("{base}/list?label=c"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|job| job["id"].clone())
+        .collect::<Vec<_>>();
+    listed.sort_by_key(|id| id.to_string());
+    assert_eq!(
+        listed,
+        expected.into_iter().map(|(id, _)| id).collect::<Vec<_>>()
+    );
+
+    for (id, body) in [
+        (not_an_object.id, json!({ "add": ["c"] })),
+        (first.id, json!({ "add": [], "remove": [] })),
+        (first.id, json!({ "add": [" "] })),
+    ] {
+        assert_eq!(
+            client
+                .post(format!("{base}/labels/{id}"))
+                .bearer_auth("SECRET_TOKEN")
+                .json(&body)
+                .send()
+                .await
+                .unwrap()
+                .status(),
+            reqwest::StatusCode::BAD_REQUEST
+        );
+    }
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_secret_rotation_report_lists_the_stalest_secrets_first(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/variables");
+
+    for (path, is_secret) in [
+        ("u/test-user/recent", true),
+        ("u/test-user/stale", true),
+        ("u/test-user/untracked", true),
+        ("u/test-user/plain", false),
+    ] {
+        client
+            .post(format!("{base}/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "value": "v1",
+                "is_secret": is_secret,
+                "description": "",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    // secrets created before rotations were tracked have no last rotation
+    sqlx::query(
+        "UPDATE variable SET last_rotated_at = CASE path
+            WHEN 'u/test-user/stale' THEN now() - interval '40 days'
+            ELSE NULL END
+        WHERE workspace_id = 'test-workspace' AND path IN ('u/test-user/stale', 'u/test-user/untracked')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let report = |query: &'static str| {
+        let client = client.clone();
+        let base = base.clone();
+        async move {
+            client
+                .get(format!("{base}/rotation_report{query}"))
+                .bearer_auth("SECRET_TOKEN")
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap()
+                .json::<Vec<serde_json::Value>>()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| (s["path"].clone(), s["days_since_rotation"].clone()))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        report("").await,
+        vec![
+            (json!("u/test-user/untracked"), json!(null)),
+            (json!("u/test-user/stale"), json!(40)),
+            (json!("u/test-user/recent"), json!(0)),
+        ]
+    );
+    assert_eq!(
+        report("?older_than_days=30").await,
+        vec![
+            (json!("u/test-user/untracked"), json!(null)),
+            (json!("u/test-user/stale"), json!(40)),
+        ]
+    );
+
+    // updating the value of a secret rotates it, making it a secret no longer clears its rotation
+    client
+        .post(format!("{base}/update/u/test-user/stale"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "value": "v2" }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{base}/update/u/test-user/untracked"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "value": "v2", "is_secret": false }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        sqlx::query_scalar::<_, Option<chrono::DateTime<chrono::Utc>>>(
+            "SELECT last_rotated_at FROM variable
+            WHERE workspace_id = 'test-workspace' AND path = 'u/test-user/untracked'",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap(),
+        None
+    );
+    assert_eq!(report("?older_than_days=30").await, vec![]);
+    assert_eq!(
+        report("").await,
+        vec![
+            (json!("u/test-user/recent"), json!(0)),
+            (json!("u/test-user/stale"), json!(0)),
+        ]
+    );
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        client
+            .get(format!("{base}/rotation_report"))
+            .bearer_auth("ALICE_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_clone_workspace_copies_its_items_into_another_workspace(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let api = format!("http://localhost:{port}/api/w");
+
+    sqlx::query(
+        "INSERT INTO workspace (id, name, owner) VALUES ('clone-target', 'clone-target', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO workspace_key (workspace_id, kind, key) VALUES ('clone-target', 'cloud', 'other-key')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO workspace_settings (workspace_id) VALUES ('clone-target')")
+        .execute(&db)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('clone-target', 'test@windmill.dev', 'test-user', true, 'Admin')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by) VALUES
+            ('test-workspace', 'u/test-user/db', '{\"host\": \"source\"}', 'postgresql', 'test-user'),
+            ('clone-target', 'u/test-user/db', '{\"host\": \"target\"}', 'postgresql', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    client
+        .post(format!("{api}/test-workspace/variables/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "u/test-user/secret",
+            "value": "s3cr3t",
+            "is_secret": true,
+            "description": "",
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{api}/test-workspace/schedules/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/nightly",
+            "schedule": "0 0 0 * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/failing_script",
+            "is_flow": false,
+            "args": {},
+            "enabled": false,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let clone_to = |target: &'static str, body: serde_json::Value| {
+        client
+            .post(format!("{api}/test-workspace/workspaces/clone_to/{target}"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&body)
+            .send()
+    };
+
+    // a schedule is not cloned before what it runs exists in the target workspace
+    let report = clone_to("clone-target", json!({ "include": ["schedules"] }))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        report,
+        json!({
+            "schedules": {
+                "cloned": 0,
+                "overwritten": 0,
+                "skipped": [{
+                    "path": "f/system/nightly",
+                    "reason": "script f/system/failing_script does not exist in the target workspace",
+                }],
+            },
+        })
+    );
+
+    let report = clone_to(
+        "clone-target",
+        json!({ "include": ["scripts", "flows", "schedules", "resources", "variables"] }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(
+        report,
+        json!({
+            "scripts": { "cloned": 3, "overwritten": 0, "skipped": [] },
+            "flows": { "cloned": 1, "overwritten": 0, "skipped": [] },
+            "resources": {
+                "cloned": 0,
+                "overwritten": 0,
+                "skipped": [{
+                    "path": "u/test-user/db",
+                    "reason": "already exists in the target workspace",
+                }],
+            },
+            "variables": { "cloned": 1, "overwritten": 0, "skipped": [] },
+            "schedules": { "cloned": 1, "overwritten": 0, "skipped": [] },
+        })
+    );
+
+    // secrets are re-encrypted with the key of the target workspace
+    let secret = client
+        .get(format!(
+            "{api}/clone-target/variables/get_value/u/test-user/secret"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<String>()
+        .await
+        .unwrap();
+    assert_eq!(secret, "s3cr3t");
+    let distinct_ciphertexts = sqlx::query_scalar::<_, i64>(
+        "SELECT count(DISTINCT value) FROM variable WHERE path = 'u/test-user/secret'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(distinct_ciphertexts, 2);
+
+    // the cloned flow gets a version of its own in the target workspace
+    let versions = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM flow_version WHERE workspace_id = 'clone-target' AND path = 'f/system/failing_flow'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(versions, 1);
+
+    let report = clone_to(
+        "clone-target",
+        json!({ "include": ["resources"], "overwrite": true }),
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap()
+    .json::<serde_json::Value>()
+    .await
+    .unwrap();
+    assert_eq!(
+        report,
+        json!({ "resources": { "cloned": 1, "overwritten": 1, "skipped": [] } })
+    );
+    let host = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT value->'host' FROM resource WHERE workspace_id = 'clone-target' AND path = 'u/test-user/db'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(host, json!("source"));
+
+    assert_eq!(
+        clone_to("test-workspace", json!({ "include": ["scripts"] }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        clone_to("missing-workspace", json!({ "include": ["scripts"] }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_purge_workspace_deletes_its_data_in_batches(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    sqlx::query("INSERT INTO workspace (id, name, owner) VALUES ('doomed', 'doomed', 'test-user')")
+        .execute(&db)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO workspace_settings (workspace_id) VALUES ('doomed')")
+        .execute(&db)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('doomed', 'test@windmill.dev', 'test-user', true, 'Admin')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable (workspace_id, path, value, is_secret, description) VALUES
+            ('doomed', 'u/test-user/var', 'value', false, ''),
+            ('test-workspace', 'u/test-user/var', 'value', false, '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    // more audit logs than fit in two batches
+    sqlx::query(
+        "INSERT INTO audit (workspace_id, username, operation, action_kind, resource)
+        SELECT w, 'test-user', 'jobs.run', 'execute', 'f/job_' || i
+        FROM generate_series(1, 2001) i, unnest(ARRAY['doomed', 'test-workspace']) w",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let report = client
+        .delete(format!(
+            "http://localhost:{port}/api/w/doomed/workspaces/purge"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let deleted_counts = &report["deleted_counts"];
+    assert_eq!(deleted_counts["audit"], json!(2001));
+    assert_eq!(deleted_counts["variable"], json!(1));
+    assert_eq!(deleted_counts["usr"], json!(1));
+    assert_eq!(deleted_counts["completed_job"], json!(0));
+    assert_eq!(deleted_counts["workspace"], json!(1));
+
+    let remaining = sqlx::query_as::<_, (String, i64)>(
+        "SELECT workspace_id, count(*) FROM (
+            SELECT workspace_id FROM audit
+            UNION ALL SELECT workspace_id FROM variable
+            UNION ALL SELECT id FROM workspace
+        ) w WHERE workspace_id IN ('doomed', 'test-workspace')
+        GROUP BY workspace_id",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(remaining, vec![("test-workspace".to_string(), 2003)]);
+
+    assert_eq!(
+        client
+            .delete(format!(
+                "http://localhost:{port}/api/w/admins/workspaces/purge"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_batch_invite_reports_what_happened_to_each_email(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let url = format!("http://localhost:{port}/api/w/test-workspace/users/batch_invite");
+
+    let result = client
+        .post(&url)
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "emails": [
+                "Bob@Example.com",
+                " bob@example.com",
+                "not-an-email",
+                "test@windmill.dev",
+                "carol@example.com",
+            ],
+            "is_admin": false,
+            "operator": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!({
+            "invited": ["bob@example.com", "carol@example.com"],
+            "already_members": ["test@windmill.dev"],
+            "invalid_emails": ["not-an-email"],
+            "missing_instance_users": [],
+        })
+    );
+
+    let invites = sqlx::query_as::<_, (String, bool, bool)>(
+        "SELECT email, is_admin, operator FROM workspace_invite
+        WHERE workspace_id = 'test-workspace' ORDER BY email",
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        invites,
+        vec![
+            ("bob@example.com".to_string(), false, true),
+            ("carol@example.com".to_string(), false, true),
+        ]
+    );
+
+    let too_many = (0..101)
+        .map(|i| format!("user{i}@example.com"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        client
+            .post(&url)
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "emails": too_many, "is_admin": false, "operator": false }))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        client
+            .post(&url)
+            .bearer_auth("ALICE_TOKEN")
+            .json(&json!({ "emails": ["dave@example.com"], "is_admin": true, "operator": false }))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+}
+
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_deploy_webhook_is_configured_tested_and_notified(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace");
+
+    let (events_tx, mut events) =
+        tokio::sync::mpsc::unbounded_channel::<(Option<String>, serde_json::Value)>();
+    let webhook = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook_addr = webhook.local_addr().unwrap();
+    let app = axum::Router::new().route(
+        "/",
+        axum::routing::post(
+            move |headers: axum::http::HeaderMap,
+                  axum::Json(event): axum::Json<serde_json::Value>| async move {
+                let signature = headers
+                    .get("x-windmill-signature")
+                    .map(|s| s.to_str().unwrap().to_string());
+                let _ = events_tx.send((signature, event));
+            },
+        ),
+    );
+    tokio::spawn(async move { axum::serve(webhook, app).await.unwrap() });
+
+    let edit = |body: serde_json::Value| {
+        client
+            .post(format!("{base}/workspaces/edit_deploy_webhook"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&body)
+            .send()
+    };
+    let test_webhook = || {
+        client
+            .post(format!("{base}/workspaces/test_deploy_webhook"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+    };
+    async fn next_event(
+        events: &mut tokio::sync::mpsc::UnboundedReceiver<(Option<String>, serde_json::Value)>,
+    ) -> (Option<String>, serde_json::Value) {
+        tokio::time::timeout(std::time::Duration::from_secs(10), events.recv())
+            .await
+            .expect("deploy webhook was not called")
+            .unwrap()
+    }
+
+    assert_eq!(
+        edit(json!({ "deploy_webhook_url": "ftp://example.com" }))
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    edit(json!({
+        "deploy_webhook_url": format!("http://{webhook_addr}/"),
+        "deploy_webhook_secret": "shh",
+    }))
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    test_webhook().await.unwrap().error_for_status().unwrap();
+    let (signature, event) = next_event(&mut events).await;
+    assert!(signature.is_some_and(|s| s.starts_with("sha256=") && s.len() == 7 + 64));
+    assert_eq!(
+        (
+            &event["kind"],
+            &event["action"],
+            &event["path"],
+            &event["test"]
+        ),
+        (
+            &json!("script"),
+            &json!("deploy"),
+            &json!("u/test-user/deploy_webhook_test"),
+            &json!(true)
+        )
+    );
+
+    // deployments are notified without the test flag
+    client
+        .post(format!("{base}/scripts/archive/p/f/system/failing_script"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let (signature, event) = next_event(&mut events).await;
+    assert!(signature.is_some());
+    assert_eq!(
+        (
+            &event["kind"],
+            &event["action"],
+            &event["path"],
+            &event["username"]
+        ),
+        (
+            &json!("script"),
+            &json!("archive"),
+            &json!("f/system/failing_script"),
+            &json!("test-user")
+        )
+    );
+    assert!(event.get("test").is_none());
+
+    // removing the secret stops signing the events
+    edit(json!({
+        "deploy_webhook_url": format!("http://{webhook_addr}/"),
+        "deploy_webhook_secret": "",
+    }))
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+    test_webhook().await.unwrap().error_for_status().unwrap();
+    let (signature, _) = next_event(&mut events).await;
+    assert_eq!(signature, None);
+
+    edit(json!({ "deploy_webhook_url": null }))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        test_webhook().await.unwrap().status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_version_check_uses_the_configured_update_server(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let current = windmill_common::utils::GIT_VERSION
+        .split('-')
+        .next()
+        .unwrap()
+        .to_string();
+    let fetches = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let update_server = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let update_server_addr = update_server.local_addr().unwrap();
+    let app = axum::Router::new()
+        .route(
+            "/outdated",
+            axum::routing::get({
+                let fetches = fetches.clone();
+                move || async move {
+                    fetches.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    axum::Json(json!({
+                        "tag_name": "v0.0.1",
+                        "html_url": "https://example.com/releases/v0.0.1",
+                    }))
+                }
+            }),
+        )
+        .route(
+            "/current",
+            axum::routing::get(move || {
+                let current = current.clone();
+                async move { axum::Json(json!({ "tag_name": current })) }
+            }),
+        );
+    tokio::spawn(async move { axum::serve(update_server, app).await.unwrap() });
+
+    let version_check = || async {
+        client
+            .get(format!("http://localhost:{port}/api/version_check"))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap()
+    };
+    let uptodate = || async {
+        client
+            .get(format!("http://localhost:{port}/api/uptodate"))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    };
+
+    *windmill_api::version_check::VERSION_CHECK_URL.write().await =
+        Some(format!("http://{update_server_addr}/outdated"));
+    assert_eq!(
+        version_check().await,
+        json!({
+            "current": windmill_common::utils::GIT_VERSION,
+            "latest": "v0.0.1",
+            "up_to_date": false,
+            "release_notes_url": "https://example.com/releases/v0.0.1",
+        })
+    );
+    assert_eq!(
+        uptodate().await,
+        format!("Update: {} -> v0.0.1", windmill_common::utils::GIT_VERSION)
+    );
+    // the latest release is cached for the configured url
+    assert_eq!(fetches.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    *windmill_api::version_check::VERSION_CHECK_URL.write().await =
+        Some(format!("http://{update_server_addr}/current"));
+    let check = version_check().await;
+    assert_eq!(check["up_to_date"], json!(true));
+    assert_eq!(check["release_notes_url"], json!(null));
+    assert_eq!(uptodate().await, "yes");
+
+    *windmill_api::version_check::VERSION_CHECK_URL.write().await = None;
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_ai_completion_is_streamed_from_the_provider(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by)
+        VALUES ('test-workspace', 'u/test-user/openai', '{\"api_key\": \"sk-test\"}', 'openai', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "UPDATE workspace_settings
+        SET ai_resource = '{\"path\": \"u/test-user/openai\", \"provider\": \"openai\"}'
+        WHERE workspace_id = 'test-workspace'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    const EVENTS: &str = "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+        data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n\
+        data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":30,\"total_tokens\":42}}\n\n\
+        data: [DONE]\n\n";
+    let (requests_tx, mut requests) =
+        tokio::sync::mpsc::unbounded_channel::<(Option<String>, serde_json::Value)>();
+    let provider = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let provider_addr = provider.local_addr().unwrap();
+    let app = axum::Router::new().route(
+        "/v1/chat/completions",
+        axum::routing::post(
+            move |headers: axum::http::HeaderMap,
+                  axum::Json(body): axum::Json<serde_json::Value>| async move {
+                let authorization = headers
+                    .get("authorization")
+                    .map(|a| a.to_str().unwrap().to_string());
+                let _ = requests_tx.send((authorization, body));
+                ([("content-type", "text/event-stream")], EVENTS)
+            },
+        ),
+    );
+    tokio::spawn(async move { axum::serve(provider, app).await.unwrap() });
+    *windmill_api::ai::AI_COMPLETION_URL.write().await =
+        Some(format!("http://{provider_addr}/v1/"));
+
+    let response = client
+        .post(format!(
+            "http://localhost:{port}/api/w/test-workspace/ai/completion"
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({ "prompt": "Say hello", "model": "gpt-4o-mini", "max_tokens": 64 }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    assert_eq!(response.text().await.unwrap(), EVENTS);
+
+    let (authorization, body) = requests.recv().await.unwrap();
+    assert_eq!(authorization.as_deref(), Some("Bearer sk-test"));
+    assert_eq!(
+        body,
+        json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 64,
+            "stream": true,
+            "stream_options": { "include_usage": true },
+            "messages": [{ "role": "user", "content": "Say hello" }],
+        })
+    );
+
+    *windmill_api::ai::AI_COMPLETION_URL.write().await = None;
+}
+
+/// The embeddings db is global and indexing replaces the items of the workspaces missing from the
+/// indexed database, so the tests indexing workspace items do not run concurrently
+#[cfg(feature = "embedding")]
+static EMBEDDINGS_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The api server of the tests does not load the embedding model
+#[cfg(feature = "embedding")]
+async fn load_embedding_model() {
+    use windmill_api::embeddings::{ModelInstance, MODEL_INSTANCE};
+
+    if MODEL_INSTANCE.read().await.is_none() {
+        *MODEL_INSTANCE.write().await = Some(Arc::new(ModelInstance::new().await.unwrap()));
+    }
+}
+
+#[cfg(feature = "embedding")]
+#[sqlx::test(fixtures("base"))]
+async fn test_vector_search_ranks_the_workspace_items_visible_to_the_user(db: Pool<Postgres>) {
+    use windmill_api::embeddings::EMBEDDINGS_DB;
+
+    let _lock = EMBEDDINGS_LOCK.lock().await;
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/embeddings");
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES
+            ('test-workspace', 'test-user', '', NULL, 'Send an email through an SMTP server', '', 'u/test-user/send_email', 8001, 'deno', ''),
+            ('test-workspace', 'test-user', '', NULL, 'Resize a PNG image', '', 'u/test-user/resize_image', 8002, 'deno', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO flow (workspace_id, summary, description, path, versions, schema, value, edited_by)
+        VALUES ('test-workspace', 'Post a message to a Slack channel', '', 'u/test-user/notify_slack', '{}', NULL, '{\"modules\": []}', 'test-user')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    // the workspace items are indexed when the embeddings db is created
+    load_embedding_model().await;
+    windmill_api::embeddings::update_embeddings_db(&db).await;
+    assert!(EMBEDDINGS_DB.read().await.is_some());
+
+    let search = |token: &'static str, query: serde_json::Value| {
+        let client = client.clone();
+        let base = base.clone();
+        async move {
+            client
+                .post(format!("{base}/search"))
+                .bearer_auth(token)
+                .json(&query)
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap()
+                .json::<Vec<serde_json::Value>>()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|r| {
+                    (
+                        r["path"].clone(),
+                        r["kind"].clone(),
+                        r["description"].clone(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        search(
+            "SECRET_TOKEN",
+            json!({ "query": "send an email", "limit": 1 })
+        )
+        .await,
+        vec![(
+            json!("u/test-user/send_email"),
+            json!("script"),
+            json!("Send an email through an SMTP server")
+        )]
+    );
+    assert_eq!(
+        search(
+            "SECRET_TOKEN",
+            json!({ "query": "send an email", "kind": "flow" })
+        )
+        .await,
+        vec![(
+            json!("u/test-user/notify_slack"),
+            json!("flow"),
+            json!("Post a message to a Slack channel")
+        )]
+    );
+    assert_eq!(
+        search(
+            "SECRET_TOKEN",
+            json!({ "query": "send an email", "limit": 0 })
+        )
+        .await,
+        vec![]
+    );
+
+    // the items of other users are not returned to those who cannot see them
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        search("ALICE_TOKEN", json!({ "query": "send an email" })).await,
+        vec![]
+    );
+}
+
+#[cfg(feature = "embedding")]
+#[sqlx::test(fixtures("base"))]
+async fn test_embeddings_refresh_reindexes_the_workspace_items(db: Pool<Postgres>) {
+    let _lock = EMBEDDINGS_LOCK.lock().await;
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/embeddings");
+
+    load_embedding_model().await;
+    windmill_api::embeddings::update_embeddings_db(&db).await;
+
+    sqlx::query(
+        "INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock)
+        VALUES
+            ('test-workspace', 'test-user', '', NULL, 'Send an email through an SMTP server', '', 'u/test-user/send_email', 8101, 'deno', ''),
+            ('test-workspace', 'test-user', '', NULL, 'Resize a PNG image', '', 'u/test-user/resize_image', 8102, 'deno', '')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let refresh = || async {
+        let started = client
+            .post(format!("{base}/refresh"))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(started["status"], json!("running"));
+        assert!(started["started_at"].is_string());
+
+        tokio::time::timeout(std::time::Duration::from_secs(120), async {
+            loop {
+                let status = client
+                    .get(format!("{base}/refresh_status"))
+                    .bearer_auth("SECRET_TOKEN")
+                    .send()
+                    .await
+                    .unwrap()
+                    .json::<serde_json::Value>()
+                    .await
+                    .unwrap();
+                if status["status"] != json!("running") {
+                    return status;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            }
+        })
+        .await
+        .expect("the embeddings refresh did not complete")
+    };
+    let search = || async {
+        client
+            .post(format!("{base}/search"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({ "query": "send an email" }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<serde_json::Value>>()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r["path"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let status = refresh().await;
+    assert_eq!(
+        (&status["status"], &status["processed"], &status["total"]),
+        (&json!("completed"), &json!(2), &json!(2))
+    );
+    assert_eq!(
+        search().await,
+        vec!["u/test-user/send_email", "u/test-user/resize_image"]
+    );
+
+    // archived scripts are dropped from the index by the next refresh
+    sqlx::query("UPDATE script SET archived = true WHERE hash = 8101")
+        .execute(&db)
+        .await
+        .unwrap();
+    let status = refresh().await;
+    assert_eq!(
+        (&status["status"], &status["processed"], &status["total"]),
+        (&json!("completed"), &json!(1), &json!(1))
+    );
+    assert_eq!(search().await, vec!["u/test-user/resize_image"]);
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        client
+            .post(format!("{base}/refresh"))
+            .bearer_auth("ALICE_TOKEN")
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+}
+
+#[cfg(feature = "http_trigger")]
+#[sqlx::test(fixtures("base"))]
+async fn test_http_trigger_hmac_secret_must_be_readable_by_its_owner(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let api = format!("http://localhost:{port}/api/w/test-workspace");
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'alice@windmill.dev', 'alice', false, 'Developer')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin) VALUES
+            ('ALICE_TOKEN', 'alice@windmill.dev', 'alice token', false)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    for (path, is_secret) in [
+        ("u/test-user/signing", true),
+        ("u/test-user/plain", false),
+        ("u/alice/signing", true),
+    ] {
+        client
+            .post(format!("{api}/variables/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": path,
+                "value": "key",
+                "is_secret": is_secret,
+                "description": "",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let trigger = |path: &str, secret_path: &str| {
+        json!({
+            "path": path,
+            "route_path": path.replace('/', "_"),
+            "script_path": "f/system/hello",
+            "is_flow": false,
+            "is_async": true,
+            "requires_auth": false,
+            "http_method": "post",
+            "hmac_verification": {
+                "header_name": "X-Signature",
+                "algorithm": "sha256",
+                "secret_path": secret_path,
+            },
+        })
+    };
+    let create = |body: serde_json::Value| {
+        client
+            .post(format!("{api}/http_triggers/create"))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&body)
+            .send()
+    };
+
+    let res = create(trigger("u/test-user/plain_hook", "u/test-user/plain"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+    let res = create(trigger("u/test-user/missing_hook", "u/test-user/missing"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+    let res = create(trigger("u/alice/hook", "u/test-user/signing"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::CREATED);
+    sqlx::query(
+        "UPDATE http_trigger SET extra_perms = '{\"u/alice\": true}'
+        WHERE workspace_id = 'test-workspace' AND path = 'u/alice/hook'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    // alice owns the trigger once she saves it but cannot read the secret of test-user
+    let update = |secret_path: &str| {
+        client
+            .post(format!("{api}/http_triggers/update/u/alice/hook"))
+            .bearer_auth("ALICE_TOKEN")
+            .json(&trigger("u/alice/hook", secret_path))
+            .send()
+    };
+    let res = update("u/test-user/signing").await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+    let res = update("u/alice/signing").await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+    let secret_path = sqlx::query_scalar::<_, String>(
+        "SELECT hmac_verification->>'secret_path' FROM http_trigger
+        WHERE workspace_id = 'test-workspace' AND path = 'u/alice/hook'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(secret_path, "u/alice/signing");
 }
 
 #[sqlx::test(fixtures("base", "relative_bun"))]
@@ -3743,6 +10387,129 @@ async fn test_result_format(db: Pool<Postgres>) {
     assert_eq!(result.get(), correct_result);
 }
 
+#[cfg(all(feature = "enterprise", feature = "kafka"))]
+#[sqlx::test(fixtures("base", "schedule"))]
+async fn test_kafka_trigger_dead_letters_failed_message(db: Pool<Postgres>) {
+    use rdkafka::{
+        consumer::{Consumer, StreamConsumer},
+        message::{Header, Headers, Message, OwnedHeaders},
+        mocking::MockCluster,
+        producer::{FutureProducer, FutureRecord},
+        ClientConfig,
+    };
+    use windmill_api::kafka_triggers_dlq::{DEAD_LETTER_ERROR_HEADER, DEAD_LETTER_JOB_ID_HEADER};
+
+    initialize_tracing().await;
+    let cluster = MockCluster::new(1).unwrap();
+    cluster.create_topic("orders", 1, 1).unwrap();
+    cluster.create_topic("orders-dlq", 1, 1).unwrap();
+    let brokers = cluster.bootstrap_servers();
+
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by)
+        VALUES ('test-workspace', 'u/test-user/kafka', $1, 'kafka', 'test-user')",
+    )
+    .bind(json!({ "brokers": [brokers], "security": { "label": "PLAINTEXT" } }))
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+
+    let create = |dead_letter_topic: &str| {
+        client
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/kafka_triggers/create"
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .json(&json!({
+                "path": "u/test-user/orders",
+                "kafka_resource_path": "u/test-user/kafka",
+                "group_id": "windmill",
+                "topics": ["orders"],
+                "script_path": "f/system/failing_script",
+                "is_flow": false,
+                "dead_letter_topic": dead_letter_topic,
+                "max_in_flight": 2,
+            }))
+            .send()
+    };
+    assert_eq!(create("orders").await.unwrap().status(), 400);
+    assert_eq!(create("orders-dlq").await.unwrap().status(), 201);
+
+    let (dead_letter_topic, max_in_flight) = sqlx::query_as::<_, (Option<String>, Option<i32>)>(
+        "SELECT dead_letter_topic, max_in_flight FROM kafka_trigger WHERE path = 'u/test-user/orders'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(dead_letter_topic.as_deref(), Some("orders-dlq"));
+    assert_eq!(max_in_flight, Some(2));
+
+    let producer: FutureProducer = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .create()
+        .unwrap();
+    let dead_letters: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .set("group.id", "dead-letters")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .unwrap();
+    dead_letters.subscribe(&["orders-dlq"]).unwrap();
+
+    let dead_letter = in_test_worker(
+        &db,
+        async {
+            // the trigger only consumes the messages produced once it has joined the group
+            loop {
+                producer
+                    .send(
+                        FutureRecord::to("orders")
+                            .key("order-1")
+                            .payload("{\"id\": 1}")
+                            .headers(
+                                OwnedHeaders::new()
+                                    .insert(Header { key: "source", value: Some("test") }),
+                            ),
+                        Duration::from_secs(5),
+                    )
+                    .await
+                    .unwrap();
+                if let Ok(msg) = timeout(Duration::from_secs(3), dead_letters.recv()).await {
+                    break msg.unwrap().detach();
+                }
+            }
+        },
+        port,
+    )
+    .await;
+
+    assert_eq!(dead_letter.payload(), Some("{\"id\": 1}".as_bytes()));
+    assert_eq!(dead_letter.key(), Some("order-1".as_bytes()));
+    let header = |key: &str| {
+        dead_letter
+            .headers()
+            .and_then(|headers| headers.iter().find(|h| h.key == key))
+            .and_then(|h| h.value)
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    };
+    assert_eq!(header("source").as_deref(), Some("test"));
+    assert!(header(DEAD_LETTER_ERROR_HEADER).unwrap().contains("Failed"));
+
+    let job_id = Uuid::parse_str(&header(DEAD_LETTER_JOB_ID_HEADER).unwrap()).unwrap();
+    let success = sqlx::query_scalar::<_, bool>(
+        "SELECT success FROM completed_job WHERE id = $1 AND script_path = 'f/system/failing_script'",
+    )
+    .bind(job_id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(!success);
+}
+
 async fn test_for_versions<F: Future<Output = ()>>(
     version_flags: impl Iterator<Item = Arc<RwLock<bool>>>,
     test: impl Fn() -> F,
@@ -4082,6 +10849,7 @@ mod job_payload {
                 path: "f/system/hello_with_nodes_flow".to_string(),
                 dedicated_worker: None,
                 apply_preprocessor: true,
+                version: None,
             })
             .run_until_complete(&db, port)
             .await
@@ -4124,6 +10892,7 @@ mod job_payload {
                 path: "f/system/hello_with_nodes_flow".to_string(),
                 dedicated_worker: None,
                 apply_preprocessor: true,
+                version: None,
             })
             .run_until_complete(&db, port)
             .await
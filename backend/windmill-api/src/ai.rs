@@ -1,5 +1,6 @@
 use crate::{
     db::{ApiAuthed, DB},
+    users::check_scopes,
     variables::get_variable_or_self,
 };
 
@@ -7,30 +8,40 @@ use anthropic::AnthropicCache;
 use axum::{
     body::Bytes,
     extract::{Path, Query},
-    response::IntoResponse,
-    routing::post,
-    Extension, Router,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
 };
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::StreamExt;
 use lazy_static::lazy_static;
 use mistral::MistralCache;
 use openai::OpenaiCache;
 use quick_cache::sync::Cache;
 use reqwest::{Client, RequestBuilder};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::FromRow;
 use windmill_audit::audit_ee::audit_log;
 use windmill_audit::ActionKind;
-use windmill_common::error::{to_anyhow, Result};
+use windmill_common::error::{to_anyhow, JsonResult, Result};
+use windmill_common::utils::require_admin;
 
 use windmill_common::error::Error;
 
 use serde_json::value::{RawValue, Value};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::RwLock;
 
 lazy_static::lazy_static! {
     static ref HTTP_CLIENT: Client = reqwest::ClientBuilder::new()
         .timeout(std::time::Duration::from_secs(60 * 5))
         .user_agent("windmill/beta")
         .build().unwrap();
+
+    /// OpenAI-compatible base url the completions are sent to instead of the provider of the
+    /// workspace ai resource, whose key is still used
+    pub static ref AI_COMPLETION_URL: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
 }
 
 trait AiRequest {
@@ -91,6 +102,10 @@ mod openai {
         ) -> Self {
             Self { api_key, organization_id, azure_base_path, user }
         }
+
+        pub fn api_key(&self) -> &str {
+            &self.api_key
+        }
     }
 
     const BASE_URL: &str = "https://api.openai.com/v1";
@@ -322,6 +337,16 @@ pub enum KeyCache {
     Mistral(MistralCache),
 }
 
+impl KeyCache {
+    fn api_key(&self) -> &str {
+        match self {
+            KeyCache::Openai(cached) => cached.api_key(),
+            KeyCache::Anthropic(cached) => &cached.api_key,
+            KeyCache::Mistral(cached) => &cached.api_key,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct AiCache {
     pub path: String,
@@ -351,6 +376,13 @@ struct ProxyQueryParams {
     no_cache: Option<bool>,
 }
 
+#[derive(Deserialize, Debug)]
+struct CompletionRequest {
+    prompt: String,
+    model: String,
+    max_tokens: u32,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct AiResource {
     pub path: String,
@@ -373,29 +405,23 @@ where
 }
 
 pub fn workspaced_service() -> Router {
-    let router = Router::new().route("/proxy/*ai", post(proxy));
+    let router = Router::new()
+        .route("/proxy/*ai", post(proxy))
+        .route("/completion", post(stream_completion))
+        .route("/usage", get(get_usage));
 
     router
 }
 
-async fn proxy(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Path((w_id, ai_path)): Path<(String, String)>,
-    Query(query_params): Query<ProxyQueryParams>,
-    body: Bytes,
-) -> impl IntoResponse {
-    let workspace_cache = AI_KEY_CACHE.get(&w_id);
-    let ai_cache = match workspace_cache {
-        Some(cache) if !cache.is_expired() && !query_params.no_cache.unwrap_or(false) => {
-            cache.cached_key
-        }
+async fn get_ai_cache(db: &DB, w_id: &str, no_cache: bool) -> Result<KeyCache> {
+    match AI_KEY_CACHE.get(w_id) {
+        Some(cache) if !cache.is_expired() && !no_cache => Ok(cache.cached_key),
         _ => {
             let ai_resource = sqlx::query_scalar!(
                 "SELECT ai_resource FROM workspace_settings WHERE workspace_id = $1",
-                &w_id
+                w_id
             )
-            .fetch_one(&db)
+            .fetch_one(db)
             .await?;
 
             if ai_resource.is_none() {
@@ -411,9 +437,9 @@ async fn proxy(
                 FROM resource
                 WHERE path = $1 AND workspace_id = $2",
                 &ai_resource_path,
-                &w_id
+                w_id
             )
-            .fetch_optional(&db)
+            .fetch_optional(db)
             .await?
             .ok_or_else(|| {
                 Error::InternalErr(format!(
@@ -431,21 +457,44 @@ async fn proxy(
             let resource = resource.unwrap();
 
             let ai_cache = match ai_resource.provider.as_str() {
-                "openai" => openai::get_cached_value(&db, &w_id, resource).await,
-                "anthropic" => anthropic::get_cached_value(&db, &w_id, resource).await,
-                "mistral" => mistral::get_cached_value(&db, &w_id, resource).await,
+                "openai" => openai::get_cached_value(db, w_id, resource).await,
+                "anthropic" => anthropic::get_cached_value(db, w_id, resource).await,
+                "mistral" => mistral::get_cached_value(db, w_id, resource).await,
                 provider => {
                     return Err(Error::BadRequest(format!("{} is not supported", provider)))
                 }
             };
             let ai_cache = ai_cache?;
             AI_KEY_CACHE.insert(
-                w_id.clone(),
+                w_id.to_string(),
                 AiCache::new(ai_resource_path, ai_cache.clone()),
             );
-            ai_cache
+            Ok(ai_cache)
         }
-    };
+    }
+}
+
+async fn proxy(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, ai_path)): Path<(String, String)>,
+    Query(query_params): Query<ProxyQueryParams>,
+    body: Bytes,
+) -> Result<Response> {
+    if let Some((spent, budget)) = exceeded_daily_budget(&db, &w_id).await? {
+        return Ok(budget_exceeded_response(&w_id, spent, budget));
+    }
+
+    #[derive(Deserialize)]
+    struct RequestModel {
+        model: Option<String>,
+    }
+    let model = serde_json::from_slice::<RequestModel>(&body)
+        .ok()
+        .and_then(|request| request.model)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let ai_cache = get_ai_cache(&db, &w_id, query_params.no_cache.unwrap_or(false)).await?;
     let (path, request) = match ai_cache {
         KeyCache::Openai(cached) => ("openai_path", cached.prepare_request(&ai_path, body)),
         KeyCache::Anthropic(cached) => ("anthropic_path", cached.prepare_request(&ai_path, body)),
@@ -475,6 +524,362 @@ async fn proxy(
 
     let status_code = response.status();
     let headers = response.headers().clone();
-    let stream = response.bytes_stream();
-    Ok((status_code, headers, axum::body::Body::from_stream(stream)))
+    let event_stream = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+    let body = forward_logging_usage(response, event_stream, db, w_id, model, authed.username);
+    Ok((status_code, headers, body).into_response())
+}
+
+fn budget_exceeded_response(w_id: &str, spent: f64, budget: f64) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        format!(
+            "Daily AI budget of {budget} USD exceeded for workspace {w_id} \
+            ({spent:.2} USD spent today)"
+        ),
+    )
+        .into_response()
+}
+
+/// Forwards the body of the provider response and logs the usage it reports. The response is read
+/// to its end from a separate task so that the usage is logged even when the client goes away
+/// before the last chunk
+fn forward_logging_usage(
+    response: reqwest::Response,
+    event_stream: bool,
+    db: DB,
+    w_id: String,
+    model: String,
+    username: String,
+) -> axum::body::Body {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<reqwest::Result<Bytes>>(16);
+    tokio::spawn(async move {
+        let mut scanner = UsageScanner::new(event_stream);
+        let mut chunks = response.bytes_stream();
+        let mut client_connected = true;
+        while let Some(chunk) = chunks.next().await {
+            if let Ok(chunk) = &chunk {
+                scanner.feed(chunk);
+            }
+            if client_connected && tx.send(chunk).await.is_err() {
+                client_connected = false;
+            }
+        }
+        if let Some(usage) = scanner.finish() {
+            if let Err(e) = log_usage(&db, &w_id, &model, &username, usage).await {
+                tracing::error!("Could not log the ai usage of workspace {w_id}: {e:#}");
+            }
+        }
+    });
+
+    axum::body::Body::from_stream(async_stream::stream! {
+        while let Some(chunk) = rx.recv().await {
+            yield chunk;
+        }
+    })
+}
+
+/// Sends the prompt as an OpenAI-compatible chat completion and streams the server-sent events
+/// back as they arrive
+async fn stream_completion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(completion): Json<CompletionRequest>,
+) -> Result<Response> {
+    check_scopes(&authed, || "ai:use".to_string())?;
+
+    if let Some((spent, budget)) = exceeded_daily_budget(&db, &w_id).await? {
+        return Ok(budget_exceeded_response(&w_id, spent, budget));
+    }
+
+    let ai_cache = get_ai_cache(&db, &w_id, false).await?;
+    let completion_url = AI_COMPLETION_URL.read().await.clone();
+
+    let mut body = serde_json::json!({
+        "model": completion.model,
+        "max_tokens": completion.max_tokens,
+        "stream": true,
+        "messages": [{ "role": "user", "content": completion.prompt }],
+    });
+    // mistral always sends the usage in the last chunk and rejects unknown fields
+    if completion_url.is_some() || !matches!(ai_cache, KeyCache::Mistral(_)) {
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
+    let body: Bytes = serde_json::to_vec(&body).map_err(to_anyhow)?.into();
+
+    let request = if let Some(base_url) = completion_url {
+        HTTP_CLIENT
+            .post(format!(
+                "{}/chat/completions",
+                base_url.trim_end_matches('/')
+            ))
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", ai_cache.api_key()))
+            .body(body)
+    } else {
+        match ai_cache {
+            KeyCache::Openai(cached) => cached.prepare_request("chat/completions", body)?,
+            KeyCache::Anthropic(cached) => cached.prepare_request("v1/chat/completions", body)?,
+            KeyCache::Mistral(cached) => cached.prepare_request("v1/chat/completions", body)?,
+        }
+    };
+
+    let response = request
+        .header("accept", "text/event-stream")
+        .send()
+        .await
+        .map_err(to_anyhow)?;
+
+    let mut tx = db.begin().await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "ai.completion",
+        ActionKind::Execute,
+        &w_id,
+        Some(&authed.email),
+        Some([("model", &completion.model[..])].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    if response.error_for_status_ref().is_err() {
+        let err_msg = response.text().await.unwrap_or("".to_string());
+        return Err(Error::AiError(err_msg));
+    }
+
+    let status_code = response.status();
+    let body = forward_logging_usage(response, true, db, w_id, completion.model, authed.username);
+
+    Ok((
+        status_code,
+        [(header::CONTENT_TYPE, "text/event-stream")],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletionUsage {
+    /// `input_tokens` and `output_tokens` in the responses of the anthropic messages api
+    #[serde(alias = "input_tokens")]
+    prompt_tokens: i64,
+    #[serde(alias = "output_tokens")]
+    completion_tokens: i64,
+    total_tokens: Option<i64>,
+    /// reported by some OpenAI-compatible gateways
+    cost: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct CompletionChunk {
+    usage: Option<CompletionUsage>,
+}
+
+/// Picks the token usage out of the server-sent events of a completion as they are streamed, or
+/// out of the whole body of a response that is not an event stream
+struct UsageScanner {
+    event_stream: bool,
+    pending: Vec<u8>,
+    usage: Option<CompletionUsage>,
+}
+
+impl UsageScanner {
+    fn new(event_stream: bool) -> Self {
+        Self { event_stream, pending: vec![], usage: None }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        if !self.event_stream {
+            return;
+        }
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line = self.pending.drain(..=end).collect::<Vec<u8>>();
+            let Some(data) = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| line.strip_prefix("data:"))
+            else {
+                continue;
+            };
+            if let Ok(CompletionChunk { usage: Some(usage) }) = serde_json::from_str(data.trim()) {
+                self.usage = Some(usage);
+            }
+        }
+    }
+
+    fn finish(self) -> Option<CompletionUsage> {
+        if self.event_stream {
+            self.usage
+        } else {
+            serde_json::from_slice::<CompletionChunk>(&self.pending)
+                .ok()
+                .and_then(|body| body.usage)
+        }
+    }
+}
+
+/// USD per million prompt and completion tokens of the models whose cost is not reported by the
+/// provider, the first matching prefix is used
+const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("o3-mini", 1.1, 4.4),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("mistral-large", 2.0, 6.0),
+    ("codestral", 0.3, 0.9),
+];
+
+fn usage_cost_usd(model: &str, usage: &CompletionUsage) -> f64 {
+    usage.cost.unwrap_or_else(|| {
+        MODEL_PRICES
+            .iter()
+            .find(|(prefix, _, _)| model.starts_with(prefix))
+            .map(|(_, prompt_price, completion_price)| {
+                (usage.prompt_tokens as f64 * prompt_price
+                    + usage.completion_tokens as f64 * completion_price)
+                    / 1_000_000.0
+            })
+            .unwrap_or(0.0)
+    })
+}
+
+async fn log_usage(
+    db: &DB,
+    w_id: &str,
+    model: &str,
+    created_by: &str,
+    usage: CompletionUsage,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO ai_usage_log
+            (workspace_id, model, prompt_tokens, completion_tokens, total_tokens, cost_usd, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(w_id)
+    .bind(model)
+    .bind(usage.prompt_tokens)
+    .bind(usage.completion_tokens)
+    .bind(
+        usage
+            .total_tokens
+            .unwrap_or(usage.prompt_tokens + usage.completion_tokens),
+    )
+    .bind(usage_cost_usd(model, &usage))
+    .bind(created_by)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The cost of the completions since midnight (UTC) and the daily budget of the workspace if it
+/// is exceeded
+async fn exceeded_daily_budget(db: &DB, w_id: &str) -> Result<Option<(f64, f64)>> {
+    let (spent, budget) = sqlx::query_as::<_, (f64, Option<f64>)>(
+        "SELECT
+            (SELECT COALESCE(SUM(cost_usd), 0) FROM ai_usage_log
+            WHERE workspace_id = $1
+                AND created_at >= date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'),
+            (SELECT ai_daily_budget_usd FROM workspace_settings WHERE workspace_id = $1)",
+    )
+    .bind(w_id)
+    .fetch_one(db)
+    .await?;
+    Ok(budget
+        .filter(|budget| spent >= *budget)
+        .map(|budget| (spent, budget)))
+}
+
+#[derive(Deserialize)]
+struct UsageQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    model: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+struct ModelDailyUsage {
+    day: NaiveDate,
+    model: String,
+    total_tokens: i64,
+    total_cost_usd: f64,
+    request_count: i64,
+}
+
+/// Tokens and cost of the completions per model and day (UTC)
+async fn get_usage(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<UsageQuery>,
+) -> JsonResult<Vec<ModelDailyUsage>> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let usage = sqlx::query_as::<_, ModelDailyUsage>(
+        "SELECT (created_at AT TIME ZONE 'UTC')::date AS day, model,
+            SUM(total_tokens)::bigint AS total_tokens, SUM(cost_usd) AS total_cost_usd,
+            COUNT(*) AS request_count
+        FROM ai_usage_log
+        WHERE workspace_id = $1
+            AND ($2::timestamptz IS NULL OR created_at >= $2)
+            AND ($3::timestamptz IS NULL OR created_at <= $3)
+            AND ($4::text IS NULL OR model = $4)
+        GROUP BY day, model
+        ORDER BY day DESC, model",
+    )
+    .bind(&w_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.model)
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_read_from_split_events() {
+        let mut scanner = UsageScanner::new(true);
+        scanner.feed(b"data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}],\"usage\":null}\n\n");
+        scanner.feed(b"data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,");
+        assert!(scanner.usage.is_none());
+        scanner.feed(b"\"completion_tokens\":30,\"total_tokens\":42}}\n\ndata: [DONE]\n\n");
+
+        let usage = scanner.finish().unwrap();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens
+            ),
+            (12, 30, Some(42))
+        );
+        assert_eq!(usage_cost_usd("gpt-4o-mini-2024-07-18", &usage), 0.0000198);
+        assert_eq!(usage_cost_usd("my-local-model", &usage), 0.0);
+    }
+
+    #[test]
+    fn usage_is_read_from_whole_bodies() {
+        let mut scanner = UsageScanner::new(false);
+        scanner.feed(b"{\"content\":[{\"type\":\"text\",\"text\":\"hi\"}],");
+        scanner.feed(b"\"usage\":{\"input_tokens\":7,\"output_tokens\":3}}");
+
+        let usage = scanner.finish().unwrap();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens
+            ),
+            (7, 3, None)
+        );
+    }
 }
@@ -13,7 +13,7 @@ use crate::{
     resources::get_resource_value_interpolated_internal,
     users::{require_owner_of_path, OptAuthed},
     utils::WithStarredInfoQuery,
-    webhook_util::{WebhookMessage, WebhookShared},
+    webhook_util::{DeployAction, DeployEvent, DeployKind, WebhookMessage, WebhookShared},
     HTTP_CLIENT,
 };
 #[cfg(feature = "parquet")]
@@ -853,6 +853,14 @@ async fn create_app(
 
     new_tx.commit().await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::App,
+        DeployAction::Deploy,
+        &w_id,
+        &app.path,
+        Some(v_id.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::CreateApp { workspace: w_id, path: app.path.clone() },
@@ -963,6 +971,14 @@ async fn delete_app(
         ))
     })?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::App,
+        DeployAction::Delete,
+        &w_id,
+        path,
+        None,
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone().clone(),
         WebhookMessage::DeleteApp { workspace: w_id, path: path.to_owned() },
@@ -1169,6 +1185,14 @@ async fn update_app(
     tracing::info!("Pushed app dependency job {}", dependency_job_uuid);
     new_tx.commit().await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::App,
+        DeployAction::Deploy,
+        &w_id,
+        &npath,
+        Some(v_id.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::UpdateApp {
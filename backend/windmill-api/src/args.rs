@@ -10,10 +10,12 @@ use axum::{
 use bytes::Bytes;
 use http::{header::CONTENT_TYPE, request::Parts, StatusCode};
 #[cfg(feature = "parquet")]
-use object_store::{Attribute, Attributes};
+use object_store::{Attribute, Attributes, ObjectStore, PutMultipartOpts, WriteMultipart};
 use serde::Deserialize;
 use serde_json::value::RawValue;
-use sqlx::types::JsonRawValue;
+use sqlx::types::{JsonRawValue, Uuid};
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
 #[cfg(feature = "parquet")]
 use windmill_common::s3_helpers::build_object_store_client;
 use windmill_common::{error::Error, worker::to_raw_value, DB};
@@ -21,7 +23,7 @@ use windmill_queue::PushArgsOwned;
 
 use crate::db::ApiAuthed;
 #[cfg(feature = "parquet")]
-use crate::job_helpers_ee::{get_random_file_name, upload_file_internal};
+use crate::{job_helpers_ee::get_random_file_name, users::fetch_api_authed};
 
 #[derive(Default)]
 pub struct WebhookArgs {
@@ -30,13 +32,49 @@ pub struct WebhookArgs {
     pub wrap_body: Option<bool>,
 }
 
+lazy_static::lazy_static! {
+    /// Max size of a single file of a multipart/form-data body, the whole body being limited by
+    /// REQUEST_SIZE_LIMIT
+    static ref MULTIPART_FILE_SIZE_LIMIT: Option<usize> = std::env::var("MULTIPART_FILE_SIZE_LIMIT")
+        .ok()
+        .and_then(|x| x.parse().ok());
+}
+
 impl WebhookArgs {
-    #[cfg(not(feature = "parquet"))]
     pub async fn to_push_args_owned(
+        self,
+        authed: &ApiAuthed,
+        db: &DB,
+        w_id: &str,
+    ) -> Result<PushArgsOwned, Error> {
+        self.into_push_args(authed, db, w_id, None).await
+    }
+
+    /// Same as `to_push_args_owned`, the files of a multipart/form-data body being uploaded under
+    /// `uploads/<job_id>/<field_name>`. The job id has to be known before the job is pushed, so it
+    /// is generated if not set, and a job id that is already used is rejected.
+    pub async fn to_push_args_owned_for_job(
+        self,
+        authed: &ApiAuthed,
+        db: &DB,
+        w_id: &str,
+        job_id: &mut Option<Uuid>,
+    ) -> Result<PushArgsOwned, Error> {
+        let upload_job_id = if self.multipart.is_some() {
+            Some(*job_id.get_or_insert_with(|| ulid::Ulid::new().into()))
+        } else {
+            None
+        };
+        self.into_push_args(authed, db, w_id, upload_job_id).await
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    async fn into_push_args(
         self,
         _authed: &ApiAuthed,
         _db: &DB,
         _w_id: &str,
+        _job_id: Option<Uuid>,
     ) -> Result<PushArgsOwned, Error> {
         if self.multipart.is_some() {
             return Err(Error::BadRequest(format!(
@@ -48,103 +86,361 @@ impl WebhookArgs {
     }
 
     #[cfg(feature = "parquet")]
-    pub async fn to_push_args_owned(
-        mut self,
+    async fn into_push_args(
+        self,
         authed: &ApiAuthed,
         db: &DB,
         w_id: &str,
+        job_id: Option<Uuid>,
     ) -> Result<PushArgsOwned, Error> {
-        use futures::TryStreamExt;
-
-        if let Some(mut multipart) = self.multipart {
-            {
-                let (_, s3_resource) =
-                    get_workspace_s3_resource(authed, db, None, "", w_id, None).await?;
-
-                if let Some(s3_resource) = s3_resource {
-                    let s3_client = build_object_store_client(&s3_resource).await?;
-
-                    let mut body = HashMap::new();
-                    let mut files = HashMap::new();
-
-                    while let Some(field) = multipart.next_field().await.map_err(|e| {
-                        Error::BadRequest(format!(
-                            "Error reading multipart field: {}",
-                            e.body_text()
-                        ))
-                    })? {
-                        if let Some(name) = field.name().map(|x| x.to_string()) {
-                            if let Some(content_type) = field.content_type() {
-                                let ext = field
-                                    .file_name()
-                                    .map(|x| x.split('.').last())
-                                    .flatten()
-                                    .map(|x| x.to_string());
-
-                                let file_key = get_random_file_name(ext);
-
-                                let options = Attributes::from_iter(vec![
-                                    (Attribute::ContentType, content_type.to_string()),
-                                    (
-                                        Attribute::ContentDisposition,
-                                        if let Some(filename) = field.file_name() {
-                                            format!("inline; filename=\"{}\"", filename)
-                                        } else {
-                                            "inline".to_string()
-                                        },
-                                    ),
-                                ])
-                                .into();
-
-                                let bytes_stream = field.into_stream().map_err(|err| {
-                                    std::io::Error::new(std::io::ErrorKind::Other, err)
-                                });
-
-                                upload_file_internal(
-                                    s3_client.clone(),
-                                    &file_key,
-                                    bytes_stream,
-                                    options,
-                                )
-                                .await?;
-
-                                files.entry(name).or_insert(vec![]).push(serde_json::json!({
-                                    "s3": &file_key
-                                }));
-                            } else {
-                                body.insert(
-                                    name,
-                                    to_raw_value(&field.text().await.unwrap_or_default()),
-                                );
-                            }
-                        }
-                    }
+        if self.multipart.is_none() {
+            return Ok(self.args);
+        }
+
+        let (_, s3_resource) = get_workspace_s3_resource(authed, db, None, "", w_id, None).await?;
+        let Some(s3_resource) = s3_resource else {
+            return Err(Error::BadRequest(format!(
+                "You need to connect your workspace to an S3 bucket to use multipart/form-data"
+            )));
+        };
+        let s3_client = build_object_store_client(&s3_resource).await?;
+
+        let request_size_limit = *crate::REQUEST_SIZE_LIMIT.read().await;
+        let file_size_limit = MULTIPART_FILE_SIZE_LIMIT
+            .map(|x| x.min(request_size_limit))
+            .unwrap_or(request_size_limit);
+
+        self.upload_multipart(
+            db,
+            MultipartUpload {
+                s3_client,
+                w_id,
+                job_id,
+                username: &authed.username,
+                email: &authed.email,
+                file_size_limit,
+                request_size_limit,
+            },
+        )
+        .await
+    }
 
-                    for (k, v) in files {
-                        body.insert(k, to_raw_value(&v));
-                    }
+    /// Uploads the files of the multipart/form-data body to `upload.s3_client` and returns the
+    /// args with the other fields and the keys of the files
+    #[cfg(feature = "parquet")]
+    pub async fn upload_multipart(
+        mut self,
+        db: &DB,
+        upload: MultipartUpload<'_>,
+    ) -> Result<PushArgsOwned, Error> {
+        use futures::{StreamExt, TryStreamExt};
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-                    if self.wrap_body.unwrap_or(false) {
-                        self.args
-                            .args
-                            .insert("body".to_string(), to_raw_value(&body));
-                    } else {
-                        self.args.args.extend(body);
-                    }
+        let Some(mut multipart) = self.multipart else {
+            return Ok(self.args);
+        };
+        let MultipartUpload {
+            s3_client,
+            w_id,
+            job_id,
+            username,
+            email,
+            file_size_limit,
+            request_size_limit,
+        } = upload;
+
+        if let Some(job_id) = job_id {
+            let used = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS (SELECT 1 FROM job WHERE id = $1) \
+                    OR EXISTS (SELECT 1 FROM job_upload WHERE job_id = $1)",
+            )
+            .bind(job_id)
+            .fetch_one(db)
+            .await?;
+            if used {
+                return Err(Error::Conflict(format!(
+                    "Job id {job_id} is already used, files can only be uploaded for a new job"
+                )));
+            }
+        }
 
-                    return Ok(self.args);
+        let total_size = Arc::new(AtomicUsize::new(0));
+
+        let mut body = HashMap::new();
+        let mut files = HashMap::new();
+
+        while let Some(field) = multipart.next_field().await.map_err(|e| {
+            Error::BadRequest(format!("Error reading multipart field: {}", e.body_text()))
+        })? {
+            if let Some(name) = field.name().map(|x| x.to_string()) {
+                if let Some(content_type) = field.content_type() {
+                    let content_type = content_type.to_string();
+                    let ext = field
+                        .file_name()
+                        .map(|x| x.split('.').last())
+                        .flatten()
+                        .map(|x| x.to_string());
+
+                    let file_key = if let Some(job_id) = job_id {
+                        let nb_files = files.get(&name).map(Vec::len).unwrap_or(0);
+                        let file_key = upload_file_key(job_id, &name, nb_files);
+                        // claiming the key before uploading guarantees that a request reusing the
+                        // job id of another one cannot overwrite its files
+                        let claimed = sqlx::query_scalar::<_, String>(
+                            "INSERT INTO job_upload \
+                                (workspace_id, job_id, file_key, created_by, email) \
+                            VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING \
+                            RETURNING file_key",
+                        )
+                        .bind(w_id)
+                        .bind(job_id)
+                        .bind(&file_key)
+                        .bind(username)
+                        .bind(email)
+                        .fetch_optional(db)
+                        .await?;
+                        if claimed.is_none() {
+                            return Err(Error::Conflict(format!(
+                                "{file_key} was already uploaded by another request"
+                            )));
+                        }
+                        file_key
+                    } else {
+                        get_random_file_name(ext)
+                    };
+
+                    let options = Attributes::from_iter(vec![
+                        (Attribute::ContentType, content_type.clone()),
+                        (
+                            Attribute::ContentDisposition,
+                            if let Some(filename) = field.file_name() {
+                                format!("inline; filename=\"{}\"", filename)
+                            } else {
+                                "inline".to_string()
+                            },
+                        ),
+                    ])
+                    .into();
+
+                    let file_size = Arc::new(AtomicUsize::new(0));
+                    let (file_counter, total_counter) = (file_size.clone(), total_size.clone());
+                    let field_name = name.clone();
+                    let bytes_stream = field
+                        .into_stream()
+                        .map_err(|e| {
+                            Error::BadRequest(format!(
+                                "Error reading multipart field: {}",
+                                e.body_text()
+                            ))
+                        })
+                        .map(move |chunk| {
+                            let chunk = chunk?;
+                            let len = chunk.len();
+                            let limit = if file_counter.fetch_add(len, Ordering::Relaxed) + len
+                                > file_size_limit
+                            {
+                                file_size_limit
+                            } else if total_counter.fetch_add(len, Ordering::Relaxed) + len
+                                > request_size_limit
+                            {
+                                request_size_limit
+                            } else {
+                                return Ok(chunk);
+                            };
+                            Err(Error::BadRequest(format!(
+                                "file {field_name} exceeds {limit} bytes"
+                            )))
+                        });
+
+                    upload_stream(&s3_client, &file_key, bytes_stream, options).await?;
+
+                    let file = if job_id.is_some() {
+                        serde_json::json!({
+                            "s3": &file_key,
+                            "size": file_size.load(Ordering::Relaxed),
+                            "content_type": content_type,
+                        })
+                    } else {
+                        serde_json::json!({
+                            "s3": &file_key
+                        })
+                    };
+                    files.entry(name).or_insert(vec![]).push(file);
+                } else {
+                    body.insert(name, to_raw_value(&field.text().await.unwrap_or_default()));
                 }
             }
+        }
 
-            return Err(Error::BadRequest(format!(
-                "You need to connect your workspace to an S3 bucket to use multipart/form-data"
-            )));
+        for (k, mut v) in files {
+            // a single file uploaded for a job is passed as is rather than as a list
+            if job_id.is_some() && v.len() == 1 {
+                body.insert(k, to_raw_value(&v.remove(0)));
+            } else {
+                body.insert(k, to_raw_value(&v));
+            }
+        }
+
+        if self.wrap_body.unwrap_or(false) {
+            self.args
+                .args
+                .insert("body".to_string(), to_raw_value(&body));
+        } else {
+            self.args.args.extend(body);
         }
 
         Ok(self.args)
     }
 }
 
+/// Where the files of a multipart/form-data body are uploaded to and the limits of their size
+#[cfg(feature = "parquet")]
+pub struct MultipartUpload<'a> {
+    pub s3_client: Arc<dyn ObjectStore>,
+    pub w_id: &'a str,
+    /// the files are uploaded under `uploads/<job_id>/` and recorded in `job_upload` if set
+    pub job_id: Option<Uuid>,
+    /// uploader of the files, the object store of the workspace is loaded as them to delete the
+    /// expired uploads
+    pub username: &'a str,
+    pub email: &'a str,
+    pub file_size_limit: usize,
+    pub request_size_limit: usize,
+}
+
+/// Streams `stream` to `file_key` as a multipart upload, aborted if the stream fails
+#[cfg(feature = "parquet")]
+async fn upload_stream(
+    s3_client: &Arc<dyn ObjectStore>,
+    file_key: &str,
+    mut stream: impl futures::Stream<Item = Result<Bytes, Error>> + Unpin,
+    options: PutMultipartOpts,
+) -> Result<(), Error> {
+    use futures::StreamExt;
+
+    let path = object_store::path::Path::from(file_key);
+    let upload = s3_client
+        .put_multipart_opts(&path, options)
+        .await
+        .map_err(|e| Error::InternalErr(format!("Failed to upload {file_key}: {e}")))?;
+    let mut writer = WriteMultipart::new(upload);
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => writer.write(&chunk),
+            Err(e) => {
+                if let Err(abort_err) = writer.abort().await {
+                    tracing::error!("Failed to abort the upload of {file_key}: {abort_err}");
+                }
+                return Err(e);
+            }
+        }
+    }
+    writer
+        .finish()
+        .await
+        .map_err(|e| Error::InternalErr(format!("Failed to upload {file_key}: {e}")))?;
+    Ok(())
+}
+
+/// Key of the `index`-th file of the multipart field `field_name` in the args of `job_id`
+#[cfg(feature = "parquet")]
+fn upload_file_key(job_id: Uuid, field_name: &str, index: usize) -> String {
+    let field_name = field_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if index == 0 {
+        format!("uploads/{job_id}/{field_name}")
+    } else {
+        format!("uploads/{job_id}/{field_name}_{index}")
+    }
+}
+
+/// Uploads are kept as long as their job, so the ones whose job was deleted by the retention
+/// policy, or that never got a job because it failed to be pushed, are deleted from the object
+/// store by the monitor. The object store of a workspace is loaded as the user who uploaded the
+/// files.
+#[cfg(feature = "parquet")]
+pub async fn delete_expired_uploads(db: &DB) {
+    let uploaders = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT DISTINCT workspace_id, created_by, email FROM job_upload \
+        WHERE created_at < now() - interval '1 day' AND created_by IS NOT NULL \
+            AND NOT EXISTS (SELECT 1 FROM job WHERE job.id = job_upload.job_id) \
+        LIMIT 10",
+    )
+    .fetch_all(db)
+    .await;
+
+    match uploaders {
+        Ok(uploaders) => {
+            for (w_id, username, email) in uploaders {
+                if let Err(e) = delete_expired_uploads_of(db, &w_id, username, email).await {
+                    tracing::error!("Error deleting expired uploads of workspace {w_id}: {e:#}");
+                }
+            }
+        }
+        Err(e) => tracing::error!("Error listing expired uploads: {e:#}"),
+    }
+}
+
+#[cfg(feature = "parquet")]
+async fn delete_expired_uploads_of(
+    db: &DB,
+    w_id: &str,
+    username: String,
+    email: String,
+) -> Result<(), Error> {
+    let authed = fetch_api_authed(username.clone(), email, w_id, db, None).await?;
+    let (_, s3_resource) = get_workspace_s3_resource(&authed, db, None, "", w_id, None).await?;
+    let s3_client = match s3_resource {
+        Some(s3_resource) => Some(build_object_store_client(&s3_resource).await?),
+        // the files cannot be reached anymore once the workspace is disconnected from its bucket
+        None => None,
+    };
+    delete_expired_uploads_from(db, w_id, &username, s3_client).await
+}
+
+/// Deletes a batch of the expired uploads of `username` in a workspace from `s3_client`
+#[cfg(feature = "parquet")]
+pub async fn delete_expired_uploads_from(
+    db: &DB,
+    w_id: &str,
+    username: &str,
+    s3_client: Option<Arc<dyn ObjectStore>>,
+) -> Result<(), Error> {
+    let expired = sqlx::query_scalar::<_, String>(
+        "DELETE FROM job_upload WHERE (job_id, file_key) IN ( \
+            SELECT job_id, file_key FROM job_upload \
+            WHERE workspace_id = $1 AND created_by = $2 \
+                AND created_at < now() - interval '1 day' \
+                AND NOT EXISTS (SELECT 1 FROM job WHERE job.id = job_upload.job_id) \
+            LIMIT 100 FOR UPDATE SKIP LOCKED \
+        ) RETURNING file_key",
+    )
+    .bind(w_id)
+    .bind(username)
+    .fetch_all(db)
+    .await?;
+
+    if let Some(s3_client) = s3_client {
+        for file_key in expired {
+            let path = object_store::path::Path::from(file_key.as_str());
+            if let Err(e) = s3_client.delete(&path).await {
+                tracing::error!(
+                    "Failed to delete expired upload {file_key} of workspace {w_id}: {e}"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 #[derive(Deserialize)]
 pub struct RequestQuery {
     pub raw: Option<bool>,
@@ -464,6 +760,72 @@ impl PushArgsOwnedExt for PushArgsOwned {
     }
 }
 
+/// Keys the body of a request is wrapped in (`wrap_body`, non object or cloudevents payloads) or
+/// stored as (`raw`), they are not part of the schema of the runnable
+const WRAP_BODY_KEYS: [&str; 3] = ["body", "raw_string", "WEBHOOK__METADATA__"];
+
+/// Checks the args of a job against the top level properties of the script or flow schema.
+/// Keys prefixed by `wm_` or `_` (e.g. `_ENTRYPOINT_OVERRIDE`) are reserved and always accepted,
+/// as are the [`WRAP_BODY_KEYS`].
+/// The `extra` args (headers, query params, raw body, ...) can satisfy required properties
+/// but are never reported as unknown.
+pub fn validate_args_against_schema(
+    schema: &serde_json::Value,
+    args: &PushArgsOwned,
+) -> Result<(), Error> {
+    let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) else {
+        return Ok(());
+    };
+    let is_reserved =
+        |key: &str| key.starts_with("wm_") || key.starts_with('_') || WRAP_BODY_KEYS.contains(&key);
+    let is_present = |key: &str| {
+        args.args.contains_key(key) || args.extra.as_ref().is_some_and(|e| e.contains_key(key))
+    };
+
+    let missing = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|required| {
+            required
+                .iter()
+                .filter_map(|key| key.as_str())
+                .filter(|key| !is_present(key))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut unknown = if schema.get("additionalProperties") == Some(&serde_json::Value::Bool(true))
+    {
+        vec![]
+    } else {
+        args.args
+            .keys()
+            .map(|key| key.as_str())
+            .filter(|key| !is_reserved(key) && !properties.contains_key(*key))
+            .collect::<Vec<_>>()
+    };
+    unknown.sort();
+
+    let mut errors = vec![];
+    if !missing.is_empty() {
+        errors.push(format!(
+            "missing required properties: {}",
+            missing.join(", ")
+        ));
+    }
+    if !unknown.is_empty() {
+        errors.push(format!("unknown properties: {}", unknown.join(", ")));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::BadRequest(format!(
+            "args do not match the schema, {}",
+            errors.join("; ")
+        )))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -524,4 +886,49 @@ mod tests {
             "1.5"
         );
     }
+
+    #[test]
+    fn test_validate_args_against_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "email": { "type": "string" }, "count": { "type": "integer" } },
+            "required": ["email"]
+        });
+        let push_args = |args: serde_json::Value, extra: serde_json::Value| PushArgsOwned {
+            args: serde_json::from_value(args).unwrap(),
+            extra: Some(serde_json::from_value(extra).unwrap()),
+        };
+
+        let valid = push_args(
+            serde_json::json!({ "email": "a@b.c", "wm_trigger": {}, "_ENTRYPOINT_OVERRIDE": "main" }),
+            serde_json::json!({ "include_header": "x" }),
+        );
+        assert!(validate_args_against_schema(&schema, &valid).is_ok());
+
+        let from_extra = push_args(
+            serde_json::json!({}),
+            serde_json::json!({ "email": "a@b.c" }),
+        );
+        assert!(validate_args_against_schema(&schema, &from_extra).is_ok());
+
+        let wrapped = push_args(
+            serde_json::json!({ "email": "a@b.c", "body": [1, 2], "WEBHOOK__METADATA__": {} }),
+            serde_json::json!({ "raw_string": "[1, 2]" }),
+        );
+        assert!(validate_args_against_schema(&schema, &wrapped).is_ok());
+
+        let invalid = push_args(
+            serde_json::json!({ "emial": "a@b.c" }),
+            serde_json::json!({}),
+        );
+        match validate_args_against_schema(&schema, &invalid) {
+            Err(Error::BadRequest(msg)) => {
+                assert!(msg.contains("missing required properties: email"));
+                assert!(msg.contains("unknown properties: emial"));
+            }
+            _ => panic!("expected a bad request"),
+        }
+
+        assert!(validate_args_against_schema(&serde_json::Value::Null, &invalid).is_ok());
+    }
 }
@@ -6,20 +6,37 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
+use std::collections::HashMap;
+
 use axum::{
+    body::Body,
     extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
     routing::get,
     Extension, Json, Router,
 };
-use windmill_audit::{AuditLog, ListAuditLogQuery};
-use windmill_common::{db::UserDB, error::JsonResult, utils::Pagination};
+use serde::{Deserialize, Serialize};
+use windmill_audit::{
+    audit_ee::audit_log, ActionKind, AuditCursor, AuditLog, AuditLogPage, ListAuditLogQuery,
+};
+use windmill_common::{
+    db::UserDB,
+    error::{JsonResult, Result},
+    utils::{paginate, require_admin, Pagination},
+    DB,
+};
 
 use crate::db::ApiAuthed;
 
+/// Number of audit logs fetched per query while streaming an export
+const AUDIT_EXPORT_CHUNK_SIZE: i64 = 1000;
+
 pub fn workspaced_service() -> Router {
     Router::new()
         .route("/list", get(list_audit))
         .route("/get/:id", get(get_audit))
+        .route("/export", get(export_audit))
 }
 
 async fn get_audit(
@@ -31,14 +48,146 @@ async fn get_audit(
     let audit = windmill_audit::audit_ee::get_audit(tx, id, &w_id).await?;
     Ok(Json(audit))
 }
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ListAuditResponse {
+    Offset(Vec<AuditLog>),
+    Cursor(AuditLogPage),
+}
+
 async fn list_audit(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path(w_id): Path<String>,
     Query(pagination): Query<Pagination>,
     Query(lq): Query<ListAuditLogQuery>,
-) -> JsonResult<Vec<AuditLog>> {
+) -> JsonResult<ListAuditResponse> {
+    if lq.cursor.is_none() {
+        let tx = user_db.begin(&authed).await?;
+        let rows = windmill_audit::audit_ee::list_audit(tx, w_id, pagination, lq).await?;
+        return Ok(Json(ListAuditResponse::Offset(rows)));
+    }
+
+    // keyset pagination: the cursor replaces the page offset
+    let (per_page, _) = paginate(Pagination { per_page: pagination.per_page, page: None });
     let tx = user_db.begin(&authed).await?;
-    let rows = windmill_audit::audit_ee::list_audit(tx, w_id, pagination, lq).await?;
-    Ok(Json(rows))
+    let items = windmill_audit::audit_ee::list_audit(tx, w_id, pagination, lq).await?;
+    let next_cursor = if items.len() >= per_page {
+        items.last().map(|log| AuditCursor::from_log(log).encode())
+    } else {
+        None
+    };
+    Ok(Json(ListAuditResponse::Cursor(AuditLogPage {
+        items,
+        next_cursor,
+    })))
+}
+
+#[derive(Deserialize)]
+struct ExportAuditLogQuery {
+    after: Option<chrono::DateTime<chrono::Utc>>,
+    before: Option<chrono::DateTime<chrono::Utc>>,
+    username: Option<String>,
+    /// only export the audit logs whose operation starts with this prefix, e.g. `jobs.`
+    operation_prefix: Option<String>,
+}
+
+async fn fetch_audit_export_chunk(
+    db: &DB,
+    w_id: &str,
+    q: &ExportAuditLogQuery,
+    cursor: Option<&AuditCursor>,
+) -> Result<Vec<AuditLog>> {
+    let logs = sqlx::query_as::<_, AuditLog>(
+        "SELECT workspace_id, id, timestamp, username, operation, action_kind, resource, parameters
+        FROM audit
+        WHERE workspace_id = $1
+            AND ($2::timestamptz IS NULL OR timestamp >= $2)
+            AND ($3::timestamptz IS NULL OR timestamp < $3)
+            AND ($4::text IS NULL OR username = $4)
+            AND ($5::text IS NULL OR starts_with(operation, $5))
+            AND ($6::timestamptz IS NULL OR (timestamp, id) > ($6, $7))
+        ORDER BY timestamp, id
+        LIMIT $8",
+    )
+    .bind(w_id)
+    .bind(q.after)
+    .bind(q.before)
+    .bind(q.username.as_deref())
+    .bind(q.operation_prefix.as_deref())
+    .bind(cursor.map(|c| c.timestamp))
+    .bind(cursor.map(|c| c.id))
+    .bind(AUDIT_EXPORT_CHUNK_SIZE)
+    .fetch_all(db)
+    .await?;
+    Ok(logs)
+}
+
+/// Streams the matching audit logs as NDJSON, oldest first. Rows are fetched by chunks using
+/// keyset pagination so that exporting a large audit table does not load it in memory.
+async fn export_audit(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(q): Query<ExportAuditLogQuery>,
+) -> Result<impl IntoResponse> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let after = q.after.map(|t| t.to_rfc3339());
+    let before = q.before.map(|t| t.to_rfc3339());
+    let mut parameters: HashMap<&str, &str> = HashMap::new();
+    if let Some(after) = after.as_deref() {
+        parameters.insert("after", after);
+    }
+    if let Some(before) = before.as_deref() {
+        parameters.insert("before", before);
+    }
+    if let Some(username) = q.username.as_deref() {
+        parameters.insert("username", username);
+    }
+    if let Some(operation_prefix) = q.operation_prefix.as_deref() {
+        parameters.insert("operation_prefix", operation_prefix);
+    }
+    audit_log(
+        &db,
+        &authed,
+        "audit.export",
+        ActionKind::Execute,
+        &w_id,
+        None,
+        Some(parameters),
+    )
+    .await?;
+
+    let stream = async_stream::stream! {
+        let mut cursor: Option<AuditCursor> = None;
+        loop {
+            let logs = match fetch_audit_export_chunk(&db, &w_id, &q, cursor.as_ref()).await {
+                Ok(logs) => logs,
+                Err(e) => {
+                    tracing::error!("Error exporting audit logs of {w_id}: {e:#}");
+                    yield Err(e);
+                    break;
+                }
+            };
+            let mut ndjson = Vec::new();
+            for log in logs.iter() {
+                if let Err(e) = serde_json::to_writer(&mut ndjson, log) {
+                    yield Err(e.into());
+                    return;
+                }
+                ndjson.push(b'\n');
+            }
+            yield Ok(bytes::Bytes::from(ndjson));
+            if (logs.len() as i64) < AUDIT_EXPORT_CHUNK_SIZE {
+                break;
+            }
+            cursor = logs.last().map(AuditCursor::from_log);
+        }
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream),
+    ))
 }
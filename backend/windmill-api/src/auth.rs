@@ -389,6 +389,36 @@ pub struct Tokened {
     pub token: String,
 }
 
+/// The job a job token was issued for: job tokens are JWTs carrying the id of the job, or tokens
+/// stored with it
+pub async fn job_id_of_token(db: &DB, token: &str) -> Option<uuid::Uuid> {
+    let Some(jwt) = token.strip_prefix("jwt_") else {
+        return sqlx::query_scalar::<_, Option<uuid::Uuid>>(
+            "SELECT job FROM token WHERE token = $1",
+        )
+        .bind(token)
+        .fetch_optional(db)
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+    };
+    let jwt_secret = JWT_SECRET.read().await;
+    if jwt_secret.is_empty() {
+        return None;
+    }
+    jsonwebtoken::decode::<JWTAuthClaims>(
+        jwt,
+        &jsonwebtoken::DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
+    )
+    .ok()?
+    .claims
+    .job_id?
+    .parse()
+    .ok()
+}
+
 pub struct OptTokened {
     #[allow(dead_code)]
     pub token: Option<String>,
@@ -538,8 +568,9 @@ where
                             scopes
                                 .iter()
                                 .any(|s| s.starts_with("jobs:") || s.starts_with("run:"))
-                        }) && (path_vec.len() < 3
-                            || (path_vec[4] != "jobs" && path_vec[4] != "jobs_u"))
+                        }) && original_uri.path() != "/api/users/me/scopes"
+                            && (path_vec.len() < 3
+                                || (path_vec[4] != "jobs" && path_vec[4] != "jobs_u"))
                         {
                             BRUTE_FORCE_COUNTER.increment().await;
                             return Err((
@@ -8,6 +8,7 @@
 
 use axum::{
     extract::{Extension, Path, Query},
+    response::IntoResponse,
     routing::{delete, get, head, post},
     Json, Router,
 };
@@ -19,14 +20,12 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use sqlx::types::Json as SqlxJson;
-#[cfg(feature = "http_trigger")]
 use std::collections::HashMap;
 use std::fmt;
-#[cfg(feature = "http_trigger")]
-use windmill_common::error::Error;
+use uuid::Uuid;
 use windmill_common::{
     db::UserDB,
-    error::{JsonResult, Result},
+    error::{Error, JsonResult, Result},
     utils::{not_found_if_none, paginate, Pagination, StripPath},
     worker::{to_raw_value, CLOUD_HOSTED},
 };
@@ -41,10 +40,14 @@ use crate::nats_triggers_ee::NatsTriggerConfigConnection;
 use crate::{
     args::WebhookArgs,
     db::{ApiAuthed, DB},
+    jobs::{run_flow_by_path_inner, run_script_by_path_inner, RunJobQuery},
     users::fetch_api_authed,
 };
 
 const KEEP_LAST: i64 = 20;
+const MAX_REPLAY_COUNT: i64 = 100;
+/// number of payloads kept in the capture log of each trigger path
+const CAPTURE_LOG_KEEP_LAST: i64 = 100;
 
 pub fn workspaced_service() -> Router {
     Router::new()
@@ -55,6 +58,12 @@ pub fn workspaced_service() -> Router {
         )
         .route("/get_configs/:runnable_kind/*path", get(get_configs))
         .route("/list/:runnable_kind/*path", get(list_captures))
+        .route("/replay/:id", post(replay_capture))
+        .route(
+            "/history/*trigger_path",
+            get(list_capture_log).delete(clear_capture_log),
+        )
+        .route("/history_entry/:id", get(get_capture_log_entry))
         .route("/:id", delete(delete_capture))
         .route("/:id", get(get_capture))
 }
@@ -253,6 +262,8 @@ struct Capture {
     trigger_kind: TriggerKind,
     payload: SqlxJson<Box<serde_json::value::RawValue>>,
     trigger_extra: Option<SqlxJson<Box<serde_json::value::RawValue>>>,
+    /// jobs started by replaying this capture
+    replay_job_ids: Vec<Uuid>,
 }
 
 #[derive(Deserialize)]
@@ -281,7 +292,7 @@ async fn list_captures(
 
     let captures = sqlx::query_as!(
         Capture,
-        r#"SELECT id, created_at, trigger_kind as "trigger_kind: _", CASE WHEN pg_column_size(payload) < 40000 THEN payload ELSE '"WINDMILL_TOO_BIG"'::jsonb END as "payload!: _", trigger_extra as "trigger_extra: _"
+        r#"SELECT id, created_at, trigger_kind as "trigger_kind: _", CASE WHEN pg_column_size(payload) < 40000 THEN payload ELSE '"WINDMILL_TOO_BIG"'::jsonb END as "payload!: _", trigger_extra as "trigger_extra: _", replay_job_ids
         FROM capture
         WHERE workspace_id = $1
             AND path = $2 AND is_flow = $3
@@ -312,12 +323,13 @@ async fn get_capture(
     let mut tx = user_db.begin(&authed).await?;
     let capture = sqlx::query_as!(
         Capture,
-        r#"SELECT id, created_at, trigger_kind as "trigger_kind: _", payload as "payload!: _", trigger_extra as "trigger_extra: _" FROM capture WHERE id = $1 AND workspace_id = $2"#,
+        r#"SELECT id, created_at, trigger_kind as "trigger_kind: _", payload as "payload!: _", trigger_extra as "trigger_extra: _", replay_job_ids
+        FROM capture WHERE id = $1 AND workspace_id = $2"#,
         id,
         &w_id,
     )
     .fetch_one(&mut *tx)
-        .await?;
+    .await?;
     tx.commit().await?;
     Ok(Json(capture))
 }
@@ -335,6 +347,247 @@ async fn delete_capture(
     Ok(())
 }
 
+#[derive(Serialize)]
+struct CaptureLogEntry {
+    id: i64,
+    trigger_path: String,
+    trigger_kind: TriggerKind,
+    payload: SqlxJson<Box<RawValue>>,
+    received_at: chrono::DateTime<chrono::Utc>,
+}
+
+async fn list_capture_log(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, trigger_path)): Path<(String, StripPath)>,
+    Query(pagination): Query<Pagination>,
+) -> JsonResult<Vec<CaptureLogEntry>> {
+    let (per_page, offset) = paginate(Pagination {
+        per_page: pagination.per_page.or(Some(CAPTURE_LOG_KEEP_LAST as usize)),
+        ..pagination
+    });
+
+    let mut tx = user_db.begin(&authed).await?;
+    let entries = sqlx::query_as!(
+        CaptureLogEntry,
+        r#"SELECT id, trigger_path, trigger_kind as "trigger_kind: _", CASE WHEN pg_column_size(payload) < 40000 THEN payload ELSE '"WINDMILL_TOO_BIG"'::jsonb END as "payload!: _", received_at
+        FROM capture_log
+        WHERE workspace_id = $1 AND trigger_path = $2
+        ORDER BY received_at DESC, id DESC
+        OFFSET $3
+        LIMIT $4"#,
+        &w_id,
+        trigger_path.to_path(),
+        offset as i64,
+        per_page as i64,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(entries))
+}
+
+async fn get_capture_log_entry(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> JsonResult<CaptureLogEntry> {
+    let mut tx = user_db.begin(&authed).await?;
+    let entry = sqlx::query_as!(
+        CaptureLogEntry,
+        r#"SELECT id, trigger_path, trigger_kind as "trigger_kind: _", payload as "payload: _", received_at
+        FROM capture_log WHERE id = $1 AND workspace_id = $2"#,
+        id,
+        &w_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let entry = not_found_if_none(entry, "Capture log entry", id.to_string())?;
+    Ok(Json(entry))
+}
+
+async fn clear_capture_log(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, trigger_path)): Path<(String, StripPath)>,
+) -> Result<String> {
+    let trigger_path = trigger_path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM capture_log WHERE workspace_id = $1 AND trigger_path = $2",
+        &w_id,
+        trigger_path
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    tx.commit().await?;
+
+    Ok(format!(
+        "Cleared {deleted} captures from the log of {trigger_path}"
+    ))
+}
+
+#[derive(Deserialize)]
+struct ReplayCaptureTarget {
+    runnable_kind: RunnableKind,
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct ReplayCaptureQuery {
+    /// replay the last `count` captures of the runnable the capture belongs to
+    bulk: Option<bool>,
+    count: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ReplayCaptureResult {
+    capture_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Args of a replayed run, only payloads that are JSON objects can be used as args
+fn replay_args(capture_id: i64, payload: &RawValue) -> Result<PushArgsOwned> {
+    let args = serde_json::from_str::<HashMap<String, Box<RawValue>>>(payload.get()).map_err(
+        |_| {
+            Error::BadRequest(format!(
+                "Payload of capture {capture_id} is not a JSON object and cannot be replayed as args"
+            ))
+        },
+    )?;
+    Ok(PushArgsOwned { extra: None, args })
+}
+
+async fn replay_capture(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, i64)>,
+    Query(query): Query<ReplayCaptureQuery>,
+    Query(run_query): Query<RunJobQuery>,
+    Json(target): Json<ReplayCaptureTarget>,
+) -> Result<axum::response::Response> {
+    run_query.reject_dedup_window()?;
+    let bulk = query.bulk.unwrap_or(false);
+
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let captures = if bulk {
+        sqlx::query_as::<_, (i64, SqlxJson<Box<RawValue>>)>(
+            "SELECT c.id, c.payload FROM capture c
+            JOIN capture src ON src.workspace_id = c.workspace_id AND src.path = c.path
+                AND src.is_flow = c.is_flow
+            WHERE src.id = $1 AND src.workspace_id = $2
+            ORDER BY c.created_at DESC
+            LIMIT $3",
+        )
+        .bind(id)
+        .bind(&w_id)
+        .bind(query.count.unwrap_or(10).clamp(1, MAX_REPLAY_COUNT))
+        .fetch_all(&mut *tx)
+        .await?
+    } else {
+        sqlx::query_as::<_, (i64, SqlxJson<Box<RawValue>>)>(
+            "SELECT id, payload FROM capture WHERE id = $1 AND workspace_id = $2",
+        )
+        .bind(id)
+        .bind(&w_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .into_iter()
+        .collect()
+    };
+    tx.commit().await?;
+
+    if captures.is_empty() {
+        return Err(Error::NotFound(format!("Capture {id} not found")));
+    }
+
+    let mut results = vec![];
+    for (capture_id, payload) in captures {
+        let args = match replay_args(capture_id, &payload.0) {
+            Ok(args) => args,
+            Err(e) if bulk => {
+                results.push(ReplayCaptureResult {
+                    capture_id,
+                    job_id: None,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let path = StripPath(target.path.clone());
+        let pushed = match target.runnable_kind {
+            RunnableKind::Script => {
+                run_script_by_path_inner(
+                    authed.clone(),
+                    db.clone(),
+                    user_db.clone(),
+                    w_id.clone(),
+                    path,
+                    run_query.clone(),
+                    args,
+                    None,
+                )
+                .await
+            }
+            RunnableKind::Flow => {
+                run_flow_by_path_inner(
+                    authed.clone(),
+                    db.clone(),
+                    user_db.clone(),
+                    w_id.clone(),
+                    path,
+                    run_query.clone(),
+                    args,
+                    None,
+                )
+                .await
+            }
+        };
+        let job_id = pushed.and_then(|(_, uuid)| {
+            Uuid::parse_str(&uuid).map_err(|e| Error::InternalErr(e.to_string()))
+        });
+
+        match job_id {
+            Ok(job_id) => {
+                sqlx::query!(
+                    "UPDATE capture SET replay_job_ids = array_append(replay_job_ids, $1)
+                    WHERE id = $2 AND workspace_id = $3",
+                    job_id,
+                    capture_id,
+                    &w_id,
+                )
+                .execute(&db)
+                .await?;
+                results.push(ReplayCaptureResult { capture_id, job_id: Some(job_id), error: None });
+            }
+            Err(e) if bulk => {
+                results.push(ReplayCaptureResult {
+                    capture_id,
+                    job_id: None,
+                    error: Some(e.to_string()),
+                });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if bulk {
+        Ok(Json(results).into_response())
+    } else {
+        let job_id = results.pop().and_then(|r| r.job_id).unwrap_or_default();
+        Ok((StatusCode::CREATED, job_id.to_string()).into_response())
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct ActiveCaptureOwner {
     owner: String,
@@ -456,6 +709,11 @@ pub async fn insert_capture_payload(
     trigger_extra: Option<Box<RawValue>>,
     owner: &str,
 ) -> Result<()> {
+    let payload = SqlxJson(to_raw_value(&PushArgs {
+        args: &payload.args,
+        extra: payload.extra,
+    }));
+
     sqlx::query!(
         "INSERT INTO capture (workspace_id, path, is_flow, trigger_kind, payload, trigger_extra, created_by)
         VALUES ($1, $2, $3, $4, $5, $6, $7)",
@@ -463,10 +721,7 @@ pub async fn insert_capture_payload(
         path,
         is_flow,
         trigger_kind as &TriggerKind,
-        SqlxJson(to_raw_value(&PushArgs {
-            args: &payload.args,
-            extra: payload.extra
-        })) as SqlxJson<Box<RawValue>>,
+        payload.clone() as SqlxJson<Box<RawValue>>,
         trigger_extra.map(SqlxJson) as Option<SqlxJson<Box<RawValue>>>,
         owner,
     )
@@ -474,7 +729,43 @@ pub async fn insert_capture_payload(
     .await?;
 
     clear_captures_history(db, &w_id).await?;
+    insert_capture_log(db, w_id, path, trigger_kind, &payload).await?;
+
+    Ok(())
+}
 
+/// Keeps the last `CAPTURE_LOG_KEEP_LAST` payloads received for the trigger path, they are not
+/// pruned with the captures
+async fn insert_capture_log(
+    db: &DB,
+    w_id: &str,
+    trigger_path: &str,
+    trigger_kind: &TriggerKind,
+    payload: &SqlxJson<Box<RawValue>>,
+) -> Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "INSERT INTO capture_log (workspace_id, trigger_path, trigger_kind, payload)
+        VALUES ($1, $2, $3, $4)",
+        w_id,
+        trigger_path,
+        trigger_kind as &TriggerKind,
+        payload as &SqlxJson<Box<RawValue>>,
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM capture_log WHERE workspace_id = $1 AND trigger_path = $2 AND id NOT IN (
+            SELECT id FROM capture_log WHERE workspace_id = $1 AND trigger_path = $2
+            ORDER BY received_at DESC, id DESC LIMIT $3
+        )",
+        w_id,
+        trigger_path,
+        CAPTURE_LOG_KEEP_LAST,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
     Ok(())
 }
 
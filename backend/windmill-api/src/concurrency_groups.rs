@@ -11,9 +11,11 @@ use serde::Deserialize;
 
 use axum::Router;
 
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sql_builder::bind::Bind;
 use sql_builder::SqlBuilder;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 use windmill_common::db::UserDB;
 use windmill_common::error::Error::{InternalErr, PermissionDenied};
@@ -28,7 +30,9 @@ pub fn global_service() -> Router {
 }
 
 pub fn workspaced_service() -> Router {
-    Router::new().route("/list_jobs", get(get_concurrent_intervals))
+    Router::new()
+        .route("/list_jobs", get(get_concurrent_intervals))
+        .route("/:key/history", get(get_concurrency_group_history))
 }
 
 #[derive(Serialize)]
@@ -349,3 +353,165 @@ async fn get_concurrency_key(
         .await?;
     Ok(Json(key))
 }
+
+const CONCURRENCY_HISTORY_DAYS: i64 = 7;
+const MIN_CONCURRENCY_HISTORY_BUCKET_SECS: i64 = 60;
+
+#[derive(Deserialize)]
+struct ConcurrencyHistoryQuery {
+    /// size of the buckets in seconds, defaults to one hour
+    bucket_secs: Option<i64>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ConcurrencyHistoryBucket {
+    bucket_ts: DateTime<Utc>,
+    /// number of jobs that started in the bucket
+    total_jobs: i64,
+    /// null if no job started in the bucket
+    avg_duration_ms: Option<f64>,
+    /// highest number of jobs running at once during the bucket
+    max_concurrent_at_peak: i64,
+}
+
+async fn get_concurrency_group_history(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, key)): Path<(String, String)>,
+    Query(hq): Query<ConcurrencyHistoryQuery>,
+) -> JsonResult<Vec<ConcurrencyHistoryBucket>> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let bucket_secs = hq
+        .bucket_secs
+        .unwrap_or(3600)
+        .max(MIN_CONCURRENCY_HISTORY_BUCKET_SECS);
+
+    let jobs = sqlx::query_as::<_, (DateTime<Utc>, i64)>(
+        "SELECT completed_job.started_at, completed_job.duration_ms FROM completed_job \
+        JOIN concurrency_key ON concurrency_key.job_id = completed_job.id \
+        WHERE concurrency_key.key = $1 AND completed_job.workspace_id = $2 \
+        AND completed_job.started_at > now() - make_interval(days => $3) \
+        ORDER BY completed_job.started_at",
+    )
+    .bind(&key)
+    .bind(&w_id)
+    .bind(CONCURRENCY_HISTORY_DAYS as i32)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(bucket_concurrency_history(&jobs, bucket_secs)))
+}
+
+#[derive(Default)]
+struct BucketAcc {
+    total_jobs: i64,
+    total_duration_ms: i64,
+    max_concurrent: i64,
+}
+
+/// Buckets the `(started_at, duration_ms)` intervals of the jobs by start time. The peak
+/// concurrency of each bucket is computed with a sweep line over the interval bounds, jobs
+/// running across bucket boundaries count towards every bucket they overlap.
+fn bucket_concurrency_history(
+    jobs: &[(DateTime<Utc>, i64)],
+    bucket_secs: i64,
+) -> Vec<ConcurrencyHistoryBucket> {
+    let bucket_ms = bucket_secs * 1000;
+    let bucket_of = |ts_ms: i64| ts_ms.div_euclid(bucket_ms);
+
+    let mut buckets: BTreeMap<i64, BucketAcc> = BTreeMap::new();
+    let mut events = Vec::with_capacity(jobs.len() * 2);
+    for (started_at, duration_ms) in jobs {
+        let start = started_at.timestamp_millis();
+        let acc = buckets.entry(bucket_of(start)).or_default();
+        acc.total_jobs += 1;
+        acc.total_duration_ms += duration_ms;
+        events.push((start, 1));
+        events.push((start + (*duration_ms).max(0), -1));
+    }
+    // at equal timestamps, ends are processed before starts so that back to back jobs do not
+    // count as concurrent
+    events.sort_unstable();
+
+    let mut current = 0i64;
+    let mut last_bucket: Option<i64> = None;
+    for (ts, delta) in events {
+        let bucket = bucket_of(ts);
+        if let Some(last_bucket) = last_bucket {
+            // jobs still running when the sweep crosses into the next buckets
+            let crossed_until = if ts > bucket * bucket_ms {
+                bucket
+            } else {
+                bucket - 1
+            };
+            if current > 0 {
+                for b in (last_bucket + 1)..=crossed_until {
+                    let acc = buckets.entry(b).or_default();
+                    acc.max_concurrent = acc.max_concurrent.max(current);
+                }
+            }
+        }
+        current += delta;
+        let acc = buckets.entry(bucket).or_default();
+        acc.max_concurrent = acc.max_concurrent.max(current);
+        last_bucket = Some(bucket);
+    }
+
+    buckets
+        .into_iter()
+        .filter(|(_, acc)| acc.total_jobs > 0 || acc.max_concurrent > 0)
+        .filter_map(|(b, acc)| {
+            Some(ConcurrencyHistoryBucket {
+                bucket_ts: DateTime::from_timestamp_millis(b * bucket_ms)?,
+                total_jobs: acc.total_jobs,
+                avg_duration_ms: (acc.total_jobs > 0)
+                    .then(|| acc.total_duration_ms as f64 / acc.total_jobs as f64),
+                max_concurrent_at_peak: acc.max_concurrent,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::bucket_concurrency_history;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn concurrency_history_peaks() {
+        let jobs = vec![
+            // overlapping in the first bucket
+            (at(0), 30_000),
+            (at(10), 10_000),
+            (at(15), 10_000),
+            // back to back, never concurrent
+            (at(120), 10_000),
+            (at(130), 10_000),
+            // spans the whole bucket starting at 240s
+            (at(200), 100_000),
+        ];
+        let history = bucket_concurrency_history(&jobs, 60);
+        let summary = history
+            .iter()
+            .map(|b| {
+                (
+                    b.bucket_ts.timestamp(),
+                    b.total_jobs,
+                    b.max_concurrent_at_peak,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![(0, 3, 3), (120, 2, 1), (180, 1, 1), (240, 0, 1)]
+        );
+        assert_eq!(history[0].avg_duration_ms, Some(50_000.0 / 3.0));
+        assert_eq!(history[3].avg_duration_ms, None);
+    }
+}
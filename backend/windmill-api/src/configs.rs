@@ -28,6 +28,7 @@ pub fn global_service() -> Router {
         .route("/list_worker_groups", get(list_worker_groups))
         .route("/update/:name", post(update_config).delete(delete_config))
         .route("/get/:name", get(get_config))
+        .route("/drain/:worker_group", post(drain_worker_group))
         .route("/list", get(list_configs))
         .route(
             "/list_autoscaling_events/:worker_group",
@@ -181,6 +182,51 @@ async fn delete_config(
     Ok(format!("Deleted config {name}"))
 }
 
+#[derive(Deserialize)]
+struct DrainWorkerGroup {
+    drain: bool,
+}
+
+/// Sets the `drain` flag of the config of a worker group. Its workers, notified of the config
+/// change, stop pulling jobs and exit once idle. Unsetting it lets the workers still alive resume.
+async fn drain_worker_group(
+    Path(worker_group): Path<String>,
+    Extension(db): Extension<DB>,
+    authed: ApiAuthed,
+    Json(DrainWorkerGroup { drain }): Json<DrainWorkerGroup>,
+) -> error::Result<String> {
+    require_super_admin(&db, &authed.email).await?;
+
+    let name = format!("worker__{worker_group}");
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        "INSERT INTO config (name, config) VALUES ($1, jsonb_build_object('drain', $2::boolean))
+        ON CONFLICT (name) DO UPDATE SET config = coalesce(config.config, '{}'::jsonb) || jsonb_build_object('drain', $2::boolean)",
+    )
+    .bind(&name)
+    .bind(drain)
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "worker_config.drain",
+        ActionKind::Update,
+        "global",
+        Some(&name),
+        Some([("drain", if drain { "true" } else { "false" })].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    if drain {
+        Ok(format!("Worker group {worker_group} draining"))
+    } else {
+        Ok(format!("Worker group {worker_group} not draining anymore"))
+    }
+}
+
 #[derive(Serialize, Deserialize, FromRow)]
 struct AutoscalingEvent {
     id: i64,
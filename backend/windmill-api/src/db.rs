@@ -489,6 +489,13 @@ async fn fix_job_completed_index(db: &DB) -> Result<(), Error> {
         ).execute(db).await?;
     });
 
+    run_windmill_migration!("completed_job_archive_file_index", &db, {
+        tracing::info!("Special migration to add index concurrently on the archive files of jobs");
+        sqlx::query(
+            "CREATE INDEX CONCURRENTLY IF NOT EXISTS ix_completed_job_archive_file ON completed_job (archive_file) WHERE archive_file IS NOT NULL"
+        ).execute(db).await?;
+    });
+
     Ok(())
 }
 
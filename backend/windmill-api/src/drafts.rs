@@ -11,22 +11,33 @@ use crate::{
     users::{maybe_refresh_folders, require_owner_of_path},
 };
 
+use std::sync::Arc;
+
 use axum::{
     extract::{Extension, Path},
-    routing::{delete, post},
+    routing::{delete, get, post},
     Json, Router,
 };
 use hyper::StatusCode;
+use lazy_static::lazy_static;
+use quick_cache::sync::Cache;
 use serde::{Deserialize, Serialize};
-use windmill_common::{db::UserDB, error::Result, utils::StripPath};
+use serde_json::{Map, Value};
+use similar::TextDiff;
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    utils::{not_found_if_none, StripPath},
+};
 
 pub fn workspaced_service() -> Router {
     Router::new()
         .route("/create", post(create_draft))
         .route("/delete/:kind/*path", delete(delete_draft))
+        .route("/diff/:kind/*path", get(diff_draft))
 }
 
-#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
 #[sqlx(type_name = "DRAFT_TYPE", rename_all = "lowercase")]
 #[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
 pub enum DraftType {
@@ -114,6 +125,316 @@ async fn delete_draft(
     Ok(format!("deleted draft"))
 }
 
+const SCRIPT_METADATA_FIELDS: [&str; 5] = ["summary", "description", "schema", "tag", "language"];
+const FLOW_METADATA_FIELDS: [&str; 3] = ["summary", "description", "schema"];
+const APP_METADATA_FIELDS: [&str; 2] = ["summary", "policy"];
+
+lazy_static! {
+    // script hashes, flow versions and app versions are immutable so the deployed side
+    // can be cached by (workspace, hash/version id) and only the latest id has to be queried
+    static ref DEPLOYED_CACHE: Cache<(String, DraftType, i64), Arc<Value>> = Cache::new(1000);
+}
+
+#[derive(Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub deployed: Option<Value>,
+    pub draft: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub struct ModuleDiff {
+    pub id: String,
+    pub deployed: Value,
+    pub draft: Value,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DraftDiff {
+    Script {
+        deployed_hash: String,
+        content_diff: String,
+        metadata: Vec<FieldDiff>,
+    },
+    Flow {
+        deployed_version: i64,
+        added_modules: Vec<String>,
+        removed_modules: Vec<String>,
+        changed_modules: Vec<ModuleDiff>,
+        metadata: Vec<FieldDiff>,
+    },
+    App {
+        deployed_version: i64,
+        value: Vec<FieldDiff>,
+        metadata: Vec<FieldDiff>,
+    },
+}
+
+async fn diff_draft(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, kind, path)): Path<(String, DraftType, StripPath)>,
+) -> JsonResult<DraftDiff> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+
+    let draft_o = sqlx::query_scalar::<_, sqlx::types::Json<Value>>(
+        "SELECT value::jsonb FROM draft WHERE path = $1 AND typ = $2 AND workspace_id = $3",
+    )
+    .bind(path)
+    .bind(kind.clone())
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let draft = not_found_if_none(draft_o, "Draft", path)?.0;
+
+    let diff = match kind {
+        DraftType::Script => {
+            let hash_o = sqlx::query_scalar::<_, i64>(
+                "SELECT hash FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false \
+                 AND draft_only IS NOT TRUE ORDER BY created_at DESC LIMIT 1",
+            )
+            .bind(path)
+            .bind(&w_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let hash = not_found_if_none(hash_o, "Deployed script", path)?;
+            let key = (w_id.clone(), DraftType::Script, hash);
+            let deployed = match DEPLOYED_CACHE.get(&key) {
+                Some(deployed) => deployed,
+                None => {
+                    let fetched = sqlx::query_scalar::<_, sqlx::types::Json<Value>>(
+                        "SELECT jsonb_build_object('content', content, 'summary', summary, \
+                     'description', description, 'schema', schema::jsonb, 'tag', tag, \
+                     'language', language) FROM script WHERE hash = $1 AND workspace_id = $2",
+                    )
+                    .bind(hash)
+                    .bind(&w_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                    cache_deployed(key, fetched)?
+                }
+            };
+
+            let deployed_content = deployed
+                .get("content")
+                .and_then(|c| c.as_str())
+                .unwrap_or("");
+            let draft_content = draft.get("content").and_then(|c| c.as_str()).unwrap_or("");
+            let content_diff = TextDiff::from_lines(deployed_content, draft_content)
+                .unified_diff()
+                .context_radius(3)
+                .header("deployed", "draft")
+                .to_string();
+
+            DraftDiff::Script {
+                deployed_hash: windmill_common::scripts::ScriptHash(hash).to_string(),
+                content_diff,
+                metadata: diff_fields(&deployed, &draft, &SCRIPT_METADATA_FIELDS),
+            }
+        }
+        DraftType::Flow => {
+            let version_o = sqlx::query_scalar::<_, Option<i64>>(
+                "SELECT versions[array_upper(versions, 1)] FROM flow \
+                 WHERE path = $1 AND workspace_id = $2 AND archived = false \
+                 AND draft_only IS NOT TRUE",
+            )
+            .bind(path)
+            .bind(&w_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .flatten();
+            let version = not_found_if_none(version_o, "Deployed flow", path)?;
+            let key = (w_id.clone(), DraftType::Flow, version);
+            let deployed = match DEPLOYED_CACHE.get(&key) {
+                Some(deployed) => deployed,
+                None => {
+                    let fetched = sqlx::query_scalar::<_, sqlx::types::Json<Value>>(
+                        "SELECT jsonb_build_object('summary', flow.summary, \
+                     'description', flow.description, 'schema', flow_version.schema::jsonb, \
+                     'value', flow_version.value) FROM flow_version JOIN flow \
+                     ON flow.workspace_id = flow_version.workspace_id \
+                     AND flow.path = flow_version.path \
+                     WHERE flow_version.id = $1 AND flow_version.workspace_id = $2",
+                    )
+                    .bind(version)
+                    .bind(&w_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                    cache_deployed(key, fetched)?
+                }
+            };
+
+            let mut deployed_modules = Map::new();
+            collect_flow_modules(deployed.get("value"), &mut deployed_modules);
+            let mut draft_modules = Map::new();
+            collect_flow_modules(draft.get("value"), &mut draft_modules);
+
+            let added_modules = draft_modules
+                .keys()
+                .filter(|id| !deployed_modules.contains_key(*id))
+                .cloned()
+                .collect();
+            let removed_modules = deployed_modules
+                .keys()
+                .filter(|id| !draft_modules.contains_key(*id))
+                .cloned()
+                .collect();
+            let changed_modules = deployed_modules
+                .iter()
+                .filter_map(|(id, deployed)| match draft_modules.get(id) {
+                    Some(draft) if draft != deployed => Some(ModuleDiff {
+                        id: id.clone(),
+                        deployed: deployed.clone(),
+                        draft: draft.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect();
+
+            DraftDiff::Flow {
+                deployed_version: version,
+                added_modules,
+                removed_modules,
+                changed_modules,
+                metadata: diff_fields(&deployed, &draft, &FLOW_METADATA_FIELDS),
+            }
+        }
+        DraftType::App => {
+            let version_o = sqlx::query_scalar::<_, Option<i64>>(
+                "SELECT versions[array_upper(versions, 1)] FROM app \
+                 WHERE path = $1 AND workspace_id = $2",
+            )
+            .bind(path)
+            .bind(&w_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .flatten();
+            let version = not_found_if_none(version_o, "Deployed app", path)?;
+            let key = (w_id.clone(), DraftType::App, version);
+            let deployed = match DEPLOYED_CACHE.get(&key) {
+                Some(deployed) => deployed,
+                None => {
+                    let fetched = sqlx::query_scalar::<_, sqlx::types::Json<Value>>(
+                        "SELECT jsonb_build_object('summary', app.summary, \
+                         'policy', app.policy::jsonb, 'value', app_version.value::jsonb) \
+                         FROM app_version JOIN app ON app.id = app_version.app_id \
+                         WHERE app_version.id = $1 AND app.workspace_id = $2",
+                    )
+                    .bind(version)
+                    .bind(&w_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                    cache_deployed(key, fetched)?
+                }
+            };
+
+            let empty = Map::new();
+            let deployed_value = deployed
+                .get("value")
+                .and_then(|v| v.as_object())
+                .unwrap_or(&empty);
+            let draft_value = draft
+                .get("value")
+                .and_then(|v| v.as_object())
+                .unwrap_or(&empty);
+            let mut keys = deployed_value
+                .keys()
+                .chain(draft_value.keys())
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            let value = keys
+                .into_iter()
+                .filter_map(|k| field_diff(k, deployed_value.get(k), draft_value.get(k)))
+                .collect();
+
+            DraftDiff::App {
+                deployed_version: version,
+                value,
+                metadata: diff_fields(&deployed, &draft, &APP_METADATA_FIELDS),
+            }
+        }
+    };
+    tx.commit().await?;
+
+    Ok(Json(diff))
+}
+
+fn cache_deployed(
+    key: (String, DraftType, i64),
+    fetched: Option<sqlx::types::Json<Value>>,
+) -> Result<Arc<Value>> {
+    let deployed =
+        fetched.ok_or_else(|| Error::NotFound(format!("deployed version {} not found", key.2)))?;
+    let deployed = Arc::new(deployed.0);
+    DEPLOYED_CACHE.insert(key, deployed.clone());
+    Ok(deployed)
+}
+
+fn field_diff(field: &str, deployed: Option<&Value>, draft: Option<&Value>) -> Option<FieldDiff> {
+    let deployed = deployed.filter(|v| !v.is_null());
+    let draft = draft.filter(|v| !v.is_null());
+    if deployed == draft {
+        return None;
+    }
+    Some(FieldDiff { field: field.to_string(), deployed: deployed.cloned(), draft: draft.cloned() })
+}
+
+fn diff_fields(deployed: &Value, draft: &Value, fields: &[&str]) -> Vec<FieldDiff> {
+    fields
+        .iter()
+        .filter_map(|f| field_diff(f, deployed.get(*f), draft.get(*f)))
+        .collect()
+}
+
+/// collects every module of a flow value by id, including the ones nested in loops and
+/// branches. Nested modules are stripped from their parent so that a change in a child
+/// is only reported on the child itself.
+fn collect_flow_modules(flow_value: Option<&Value>, acc: &mut Map<String, Value>) {
+    let Some(flow_value) = flow_value else {
+        return;
+    };
+    collect_modules_array(flow_value.get("modules"), acc);
+    for special in ["failure_module", "preprocessor_module"] {
+        if let Some(module) = flow_value.get(special).filter(|m| m.is_object()) {
+            collect_module(module, acc);
+        }
+    }
+}
+
+fn collect_modules_array(modules: Option<&Value>, acc: &mut Map<String, Value>) {
+    if let Some(modules) = modules.and_then(|m| m.as_array()) {
+        for module in modules {
+            collect_module(module, acc);
+        }
+    }
+}
+
+fn collect_module(module: &Value, acc: &mut Map<String, Value>) {
+    let Some(id) = module.get("id").and_then(|id| id.as_str()) else {
+        return;
+    };
+    let mut stripped = module.clone();
+    if let Some(value) = stripped.get_mut("value").and_then(|v| v.as_object_mut()) {
+        if let Some(children) = value.remove("modules") {
+            collect_modules_array(Some(&children), acc);
+        }
+        if let Some(default) = value.remove("default") {
+            collect_modules_array(Some(&default), acc);
+        }
+        if let Some(branches) = value.get_mut("branches").and_then(|b| b.as_array_mut()) {
+            for branch in branches.iter_mut().filter_map(|b| b.as_object_mut()) {
+                if let Some(children) = branch.remove("modules") {
+                    collect_modules_array(Some(&children), acc);
+                }
+            }
+        }
+    }
+    acc.insert(id.to_string(), stripped);
+}
+
 // async fn get_draft(
 //     authed: ApiAuthed,
 //     Extension(user_db): Extension<UserDB>,
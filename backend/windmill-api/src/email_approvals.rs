@@ -0,0 +1,266 @@
+use axum::{
+    extract::{Path, Query},
+    Extension,
+};
+use hyper::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::types::Uuid;
+
+use crate::db::{ApiAuthed, DB};
+use crate::jobs::{get_resume_urls_internal, QueryApprover, ResumeUrls};
+use crate::users::VALID_EMAIL;
+
+use windmill_common::{
+    email_ee::send_email,
+    error::{Error, Result},
+    worker::SMTP_CONFIG,
+};
+use windmill_queue::append_logs;
+
+const SMTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const ARGS_PREVIEW_MAX_LEN: usize = 2000;
+/// flow argument holding the recipients when they are not passed to the endpoint
+const APPROVERS_EMAILS_ARG: &str = "approvers_emails";
+
+#[derive(Deserialize)]
+pub struct QueryEmailApproval {
+    message: Option<String>,
+    flow_step_id: String,
+    /// comma separated recipients, defaults to the `approvers_emails` argument of the flow
+    approvers_emails: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SuspendedFlow {
+    script_path: Option<String>,
+    created_by: String,
+    args: Option<sqlx::types::Json<Value>>,
+}
+
+pub async fn request_email_approval(
+    _authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Query(approver): Query<QueryApprover>,
+    Query(query): Query<QueryEmailApproval>,
+) -> Result<StatusCode> {
+    let flow = sqlx::query_as::<_, SuspendedFlow>(
+        "SELECT flow.script_path, flow.created_by, flow.args
+        FROM queue step JOIN queue flow ON flow.id = step.parent_job
+        WHERE step.id = $1 AND step.workspace_id = $2",
+    )
+    .bind(job_id)
+    .bind(&w_id)
+    .fetch_optional(&db)
+    .await?
+    .ok_or_else(|| {
+        Error::BadRequest(
+            "Email approvals can only be requested by a step of a running flow".to_string(),
+        )
+    })?;
+
+    let sent = send_approval_email(&db, &w_id, job_id, approver, &query, &flow).await;
+
+    // the step waits for the approval, surface the outcome in its logs
+    let logs = match &sent {
+        Ok(recipients) => format!("\nApproval email sent to {}\n", recipients.join(", ")),
+        Err(e) => format!("\nFailed to send the approval email: {e}\n"),
+    };
+    append_logs(&job_id, &w_id, logs, &db).await;
+
+    sent.map(|_| StatusCode::OK)
+}
+
+async fn send_approval_email(
+    db: &DB,
+    w_id: &str,
+    job_id: Uuid,
+    approver: QueryApprover,
+    query: &QueryEmailApproval,
+    flow: &SuspendedFlow,
+) -> Result<Vec<String>> {
+    require_email_delivery()?;
+
+    let flow_args = flow.args.as_ref().map(|a| &a.0);
+    let recipients = approval_recipients(query.approvers_emails.as_deref(), flow_args)?;
+
+    let smtp = SMTP_CONFIG.read().await.clone().ok_or_else(|| {
+        Error::BadConfig(
+            "SMTP must be configured in the instance settings to send approval emails".to_string(),
+        )
+    })?;
+
+    let resume_id = rand::random::<u32>();
+    let urls = get_resume_urls_internal(
+        Extension(db.clone()),
+        Path((w_id.to_string(), job_id, resume_id)),
+        Query(approver),
+    )
+    .await?
+    .0;
+
+    let flow_path = flow.script_path.as_deref().unwrap_or("flow preview");
+    let content = render_approval_email(
+        flow_path,
+        &query.flow_step_id,
+        &flow.created_by,
+        query.message.as_deref(),
+        flow_args,
+        &urls,
+    );
+
+    send_email(
+        &format!("Approval requested for {flow_path}"),
+        &content,
+        recipients.clone(),
+        smtp,
+        Some(SMTP_TIMEOUT),
+    )
+    .await?;
+
+    Ok(recipients)
+}
+
+/// The open source `send_email` does not send anything, so the step would report an email that the
+/// approvers never receive
+fn require_email_delivery() -> Result<()> {
+    #[cfg(not(feature = "enterprise"))]
+    return Err(Error::BadRequest(
+        "Approval emails are only available in the enterprise version".to_string(),
+    ));
+
+    #[cfg(feature = "enterprise")]
+    Ok(())
+}
+
+fn approval_recipients(query: Option<&str>, flow_args: Option<&Value>) -> Result<Vec<String>> {
+    let mut recipients: Vec<String> =
+        match (query, flow_args.and_then(|a| a.get(APPROVERS_EMAILS_ARG))) {
+            (Some(emails), _) => emails.split(',').map(|e| e.trim().to_string()).collect(),
+            (None, Some(Value::Array(emails))) => emails
+                .iter()
+                .filter_map(|e| e.as_str().map(|e| e.trim().to_string()))
+                .collect(),
+            (None, Some(Value::String(emails))) => {
+                emails.split(',').map(|e| e.trim().to_string()).collect()
+            }
+            _ => vec![],
+        };
+    recipients.retain(|e| !e.is_empty());
+    recipients.sort();
+    recipients.dedup();
+
+    if recipients.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "No recipient for the approval email, pass approvers_emails or set the {APPROVERS_EMAILS_ARG} argument of the flow"
+        )));
+    }
+    let invalid = recipients
+        .iter()
+        .filter(|e| !VALID_EMAIL.is_match(e))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !invalid.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "Invalid approver emails: {}",
+            invalid.join(", ")
+        )));
+    }
+    Ok(recipients)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn args_preview(args: Option<&Value>) -> String {
+    let mut args = args.cloned().unwrap_or(Value::Null);
+    if let Value::Object(args) = &mut args {
+        args.remove(APPROVERS_EMAILS_ARG);
+    }
+    let mut preview = serde_json::to_string_pretty(&args).unwrap_or_default();
+    if preview.len() > ARGS_PREVIEW_MAX_LEN {
+        let end = (0..=ARGS_PREVIEW_MAX_LEN)
+            .rev()
+            .find(|i| preview.is_char_boundary(*i))
+            .unwrap_or(0);
+        preview.truncate(end);
+        preview.push_str("\n...");
+    }
+    preview
+}
+
+fn render_approval_email(
+    flow_path: &str,
+    flow_step_id: &str,
+    requester: &str,
+    message: Option<&str>,
+    args: Option<&Value>,
+    urls: &ResumeUrls,
+) -> String {
+    let message = message
+        .map(|m| format!("<p>{}</p>", escape_html(m)))
+        .unwrap_or_default();
+    format!(
+        r#"<p>A flow has been suspended and is waiting for approval.</p>
+<table>
+<tr><td><b>Flow</b></td><td>{flow_path}</td></tr>
+<tr><td><b>Step</b></td><td>{flow_step_id}</td></tr>
+<tr><td><b>Requested by</b></td><td>{requester}</td></tr>
+</table>
+{message}
+<p><b>Arguments</b></p>
+<pre>{args}</pre>
+<p><a href="{resume}">Approve</a> | <a href="{cancel}">Reject</a> | <a href="{page}">Open the approval page</a></p>"#,
+        flow_path = escape_html(flow_path),
+        flow_step_id = escape_html(flow_step_id),
+        requester = escape_html(requester),
+        args = escape_html(&args_preview(args)),
+        resume = escape_html(&urls.resume),
+        cancel = escape_html(&urls.cancel),
+        page = escape_html(&urls.approvalPage),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approval_recipients_from_query_or_flow_args() {
+        let args =
+            serde_json::json!({ "approvers_emails": ["b@acme.com", "a@acme.com", "b@acme.com"] });
+        assert_eq!(
+            approval_recipients(None, Some(&args)).unwrap(),
+            vec!["a@acme.com", "b@acme.com"]
+        );
+        assert_eq!(
+            approval_recipients(Some("c@acme.com, d@acme.com"), Some(&args)).unwrap(),
+            vec!["c@acme.com", "d@acme.com"]
+        );
+        assert!(approval_recipients(None, None).is_err());
+        assert!(approval_recipients(Some("not an email"), None).is_err());
+    }
+
+    #[test]
+    fn approval_email_escapes_flow_content() {
+        let urls = ResumeUrls {
+            approvalPage: "https://wm/approve/1".to_string(),
+            cancel: "https://wm/cancel/1".to_string(),
+            resume: "https://wm/resume/1?approver=a&b".to_string(),
+        };
+        let args = serde_json::json!({ "name": "<script>", "approvers_emails": "a@acme.com" });
+        let html =
+            render_approval_email("f/team/flow", "a", "alice", Some("ok?"), Some(&args), &urls);
+
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("approvers_emails"));
+        assert!(html.contains(r#"href="https://wm/resume/1?approver=a&amp;b""#));
+        assert!(html.contains("<p>ok?</p>"));
+    }
+}
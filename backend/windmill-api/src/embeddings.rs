@@ -12,11 +12,11 @@ use axum::Router;
 #[cfg(feature = "embedding")]
 use axum::{
     extract::{Path, Query},
-    Json,
+    Extension, Json,
 };
 
 #[cfg(feature = "embedding")]
-use axum::routing::get;
+use axum::routing::{get, post};
 #[cfg(feature = "embedding")]
 use candle_core::{Device, Tensor};
 #[cfg(feature = "embedding")]
@@ -47,12 +47,20 @@ use windmill_common::utils::http_get_from_hub;
 use windmill_common::error::JsonResult;
 
 #[cfg(feature = "embedding")]
-use crate::{resources::ResourceType, HTTP_CLIENT};
+use crate::{
+    db::{ApiAuthed, DB},
+    resources::ResourceType,
+    HTTP_CLIENT,
+};
+#[cfg(feature = "embedding")]
+use windmill_common::{db::UserDB, utils::require_admin};
 
 #[cfg(feature = "embedding")]
 lazy_static::lazy_static! {
     pub static ref EMBEDDINGS_DB: Arc<RwLock<Option<EmbeddingsDb>>> = Arc::new(RwLock::new(None));
     pub static ref MODEL_INSTANCE: Arc<RwLock<Option<Arc<ModelInstance>>>> = Arc::new(RwLock::new(None));
+    /// progress of the last embeddings refresh of each workspace
+    static ref REFRESH_STATUS: Arc<RwLock<HashMap<String, Arc<RwLock<RefreshStatus>>>>> = Arc::new(RwLock::new(HashMap::new()));
     pub static ref HUB_EMBEDDINGS_PULLING_INTERVAL_SECS: u64 = std::env::var("HUB_EMBEDDINGS_PULLING_INTERVAL_SECS").ok().map(|x| x.parse::<u64>().ok()).flatten().unwrap_or(3600 * 24);
 }
 
@@ -130,6 +138,224 @@ async fn query_resource_types(
     }
 }
 
+#[cfg(feature = "embedding")]
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceItemKind {
+    Script,
+    Flow,
+}
+
+#[cfg(feature = "embedding")]
+impl WorkspaceItemKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceItemKind::Script => "script",
+            WorkspaceItemKind::Flow => "flow",
+        }
+    }
+}
+
+#[cfg(feature = "embedding")]
+fn default_search_limit() -> u8 {
+    10
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Deserialize)]
+struct VectorSearchQuery {
+    query: String,
+    #[serde(default = "default_search_limit")]
+    limit: u8,
+    kind: Option<WorkspaceItemKind>,
+    min_score: Option<f32>,
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Serialize)]
+pub struct VectorSearchResult {
+    path: String,
+    kind: WorkspaceItemKind,
+    score: f32,
+    description: String,
+}
+
+#[cfg(feature = "embedding")]
+async fn vector_search(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(query): Json<VectorSearchQuery>,
+) -> JsonResult<Vec<VectorSearchResult>> {
+    let limit = query.limit as usize;
+    if limit == 0 {
+        return Ok(Json(vec![]));
+    }
+
+    // candidates are over-fetched as the items the user cannot see are filtered out afterwards
+    let candidates = {
+        let embeddings_db = EMBEDDINGS_DB.read().await;
+        let Some(embeddings_db) = embeddings_db.as_ref() else {
+            return Err(windmill_common::error::Error::InternalErr(
+                "Embeddings db not initialized".to_string(),
+            ));
+        };
+        embeddings_db
+            .query_workspace_items(&w_id, &query.query, limit * 4, query.kind, query.min_score)
+            .await?
+    };
+
+    let (script_paths, flow_paths): (Vec<_>, Vec<_>) = candidates
+        .iter()
+        .partition(|r| r.kind == WorkspaceItemKind::Script);
+    let script_paths: Vec<String> = script_paths.into_iter().map(|r| r.path.clone()).collect();
+    let flow_paths: Vec<String> = flow_paths.into_iter().map(|r| r.path.clone()).collect();
+
+    let mut tx = user_db.begin(&authed).await?;
+    let visible_scripts = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT path FROM script WHERE workspace_id = $1 AND path = ANY($2) AND archived = false",
+    )
+    .bind(&w_id)
+    .bind(&script_paths)
+    .fetch_all(&mut *tx)
+    .await?;
+    let visible_flows = sqlx::query_scalar::<_, String>(
+        "SELECT path FROM flow WHERE workspace_id = $1 AND path = ANY($2) AND archived = false",
+    )
+    .bind(&w_id)
+    .bind(&flow_paths)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let results = candidates
+        .into_iter()
+        .filter(|r| match r.kind {
+            WorkspaceItemKind::Script => visible_scripts.contains(&r.path),
+            WorkspaceItemKind::Flow => visible_flows.contains(&r.path),
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(results))
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum RefreshState {
+    Idle,
+    Running,
+    Completed,
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Serialize, Clone, Debug)]
+pub struct RefreshStatus {
+    status: RefreshState,
+    processed: usize,
+    total: usize,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// set when the last refresh failed, the status is then back to idle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[cfg(feature = "embedding")]
+impl Default for RefreshStatus {
+    fn default() -> Self {
+        Self { status: RefreshState::Idle, processed: 0, total: 0, started_at: None, error: None }
+    }
+}
+
+#[cfg(feature = "embedding")]
+async fn refresh_embeddings(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<RefreshStatus> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let model_instance = MODEL_INSTANCE.read().await.clone().ok_or_else(|| {
+        windmill_common::error::Error::InternalErr("Embedding model not initialized".to_string())
+    })?;
+
+    let status = REFRESH_STATUS
+        .write()
+        .await
+        .entry(w_id.clone())
+        .or_default()
+        .clone();
+    let started = {
+        let mut status = status.write().await;
+        if status.status == RefreshState::Running {
+            return Err(windmill_common::error::Error::Conflict(format!(
+                "An embeddings refresh is already running for workspace {w_id}"
+            )));
+        }
+        *status = RefreshStatus {
+            status: RefreshState::Running,
+            started_at: Some(chrono::Utc::now()),
+            ..Default::default()
+        };
+        status.clone()
+    };
+
+    tokio::spawn(async move {
+        let r = refresh_workspace_embeddings(&db, &w_id, model_instance, &status).await;
+        let mut status = status.write().await;
+        match r {
+            Ok(()) => status.status = RefreshState::Completed,
+            Err(e) => {
+                tracing::error!("Failed to refresh embeddings of workspace {w_id}: {e:#}");
+                status.status = RefreshState::Idle;
+                status.error = Some(e.to_string());
+            }
+        }
+    });
+
+    Ok(Json(started))
+}
+
+#[cfg(feature = "embedding")]
+async fn refresh_workspace_embeddings(
+    db: &DB,
+    w_id: &str,
+    model_instance: Arc<ModelInstance>,
+    status: &RwLock<RefreshStatus>,
+) -> Result<()> {
+    let items = list_workspace_items(db, Some(w_id)).await?;
+    status.write().await.total = items.len();
+
+    let mut embeddings = Vec::with_capacity(items.len());
+    for item in items {
+        if let Some(embedding) = item.try_embed(model_instance.clone()).await {
+            embeddings.push(embedding);
+        }
+        status.write().await.processed += 1;
+    }
+
+    EMBEDDINGS_DB
+        .write()
+        .await
+        .as_mut()
+        .ok_or(Error::msg("Embeddings db not initialized"))?
+        .upsert_workspace_items(w_id, embeddings)
+}
+
+#[cfg(feature = "embedding")]
+async fn get_refresh_status(
+    _authed: ApiAuthed,
+    Path(w_id): Path<String>,
+) -> JsonResult<RefreshStatus> {
+    let status = REFRESH_STATUS.read().await.get(&w_id).cloned();
+    let status = match status {
+        Some(status) => status.read().await.clone(),
+        None => RefreshStatus::default(),
+    };
+    Ok(Json(status))
+}
+
 #[cfg(feature = "embedding")]
 #[derive(Deserialize, Debug, Clone)]
 struct HubScript {
@@ -273,17 +499,27 @@ pub struct EmbeddingsDb {
 
 #[cfg(feature = "embedding")]
 impl EmbeddingsDb {
-    pub async fn new(pg_db: &Pool<Postgres>, model_instance: Arc<ModelInstance>) -> Result<Self> {
+    /// Loads the hub scripts and the resource types, the workspace items indexed so far are carried
+    /// over as they are indexed separately by `index_workspace_items`
+    pub async fn new(
+        pg_db: &Pool<Postgres>,
+        model_instance: Arc<ModelInstance>,
+        workspace_items: Vec<Embedding>,
+    ) -> Result<Self> {
         let db = Db::new();
 
         let mut embeddings_db = Self { db, model_instance: model_instance.clone() };
 
-        embeddings_db.fill_db(pg_db).await?;
+        embeddings_db.fill_db(pg_db, workspace_items).await?;
 
         Ok(embeddings_db)
     }
 
-    async fn fill_db(&mut self, pg_db: &Pool<Postgres>) -> Result<()> {
+    async fn fill_db(
+        &mut self,
+        pg_db: &Pool<Postgres>,
+        workspace_items: Vec<Embedding>,
+    ) -> Result<()> {
         if self.db.get_collection("scripts").is_some() {
             self.db.delete_collection("scripts")?;
         }
@@ -298,6 +534,17 @@ impl EmbeddingsDb {
         self.db
             .create_collection("resource_types".to_string(), 384, Distance::Cosine)?;
 
+        if self.db.get_collection("workspace_items").is_some() {
+            self.db.delete_collection("workspace_items")?;
+        }
+
+        self.db
+            .create_collection("workspace_items".to_string(), 384, Distance::Cosine)?;
+        for embedding in workspace_items {
+            self.db
+                .insert_into_collection("workspace_items", embedding)?;
+        }
+
         let hub_base_url = HUB_BASE_URL.read().await.clone();
 
         let response = match hub_base_url.as_str() {
@@ -420,14 +667,26 @@ impl EmbeddingsDb {
             let vector = if let Some(hub_rt) = hub_rt {
                 hub_rt.embedding.clone()
             } else {
-                self.model_instance
+                let embedding = self
+                    .model_instance
                     .clone()
                     .create_embedding(&format!(
                         "{};{}",
                         rt.name,
                         rt.description.unwrap_or_default()
                     ))
-                    .await?
+                    .await;
+                match embedding {
+                    Ok(embedding) => embedding,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Could not embed resource type {} of workspace {}: {e:#}",
+                            rt.name,
+                            rt.workspace_id
+                        );
+                        continue;
+                    }
+                }
             };
 
             let embedding = Embedding {
@@ -443,6 +702,52 @@ impl EmbeddingsDb {
         Ok(())
     }
 
+    fn workspace_item_embeddings(&self) -> Vec<Embedding> {
+        self.db
+            .get_collection("workspace_items")
+            .map(|collection| collection.embeddings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces all the indexed scripts and flows of a workspace
+    fn upsert_workspace_items(
+        &mut self,
+        workspace: &str,
+        embeddings: Vec<Embedding>,
+    ) -> Result<()> {
+        let retained = self
+            .db
+            .get_collection("workspace_items")
+            .map(|collection| {
+                collection
+                    .embeddings
+                    .iter()
+                    .filter(|embedding| {
+                        embedding
+                            .metadata
+                            .as_ref()
+                            .and_then(|metadata| metadata.get("workspace"))
+                            .is_some_and(|w| w != workspace)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if self.db.get_collection("workspace_items").is_some() {
+            self.db.delete_collection("workspace_items")?;
+        }
+        self.db
+            .create_collection("workspace_items".to_string(), 384, Distance::Cosine)?;
+
+        for embedding in retained.into_iter().chain(embeddings) {
+            self.db
+                .insert_into_collection("workspace_items", embedding)?;
+        }
+
+        Ok(())
+    }
+
     pub async fn query_hub_scripts(
         &self,
         query: &str,
@@ -584,6 +889,231 @@ impl EmbeddingsDb {
 
         results
     }
+
+    pub async fn query_workspace_items(
+        &self,
+        workspace: &str,
+        query: &str,
+        limit: usize,
+        kind: Option<WorkspaceItemKind>,
+        min_score: Option<f32>,
+    ) -> Result<Vec<VectorSearchResult>> {
+        let model_instance = self.model_instance.clone();
+        let query_embedding = model_instance.create_embedding(query).await?;
+
+        let Some(collection) = self.db.get_collection("workspace_items") else {
+            return Ok(vec![]);
+        };
+
+        let filter = |embedding: &Embedding| {
+            embedding.metadata.as_ref().is_some_and(|metadata| {
+                metadata.get("workspace").map(|x| x.as_str()) == Some(workspace)
+                    && kind.map_or(true, |kind| {
+                        metadata.get("kind").map(|x| x.as_str()) == Some(kind.as_str())
+                    })
+            })
+        };
+
+        let results = collection.get_similarity(&query_embedding, limit, Some(&filter), min_score);
+
+        results
+            .iter()
+            .map(|r| {
+                let metadata = r
+                    .embedding
+                    .metadata
+                    .as_ref()
+                    .ok_or(Error::msg("no metadata"))?;
+                Ok(VectorSearchResult {
+                    path: metadata
+                        .get("path")
+                        .ok_or(Error::msg("no path"))?
+                        .to_owned(),
+                    kind: match metadata.get("kind").map(|x| x.as_str()) {
+                        Some("flow") => WorkspaceItemKind::Flow,
+                        _ => WorkspaceItemKind::Script,
+                    },
+                    description: metadata.get("description").cloned().unwrap_or_default(),
+                    score: r.score,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "embedding")]
+struct WorkspaceItem {
+    workspace_id: String,
+    path: String,
+    kind: WorkspaceItemKind,
+    summary: String,
+    description: String,
+}
+
+/// Latest version of the scripts and flows of a workspace, or of all workspaces
+#[cfg(feature = "embedding")]
+async fn list_workspace_items(
+    pg_db: &Pool<Postgres>,
+    workspace: Option<&str>,
+) -> Result<Vec<WorkspaceItem>> {
+    let scripts = sqlx::query_as::<_, (String, String, String, String)>(
+        "SELECT DISTINCT ON (workspace_id, path) workspace_id, path, summary, description
+        FROM script WHERE archived = false AND deleted = false AND ($1::text IS NULL OR workspace_id = $1)
+        ORDER BY workspace_id, path, created_at DESC",
+    )
+    .bind(workspace)
+    .fetch_all(pg_db)
+    .await?;
+    let flows = sqlx::query_as::<_, (String, String, String, String)>(
+        "SELECT workspace_id, path, summary, description FROM flow
+        WHERE archived = false AND ($1::text IS NULL OR workspace_id = $1)",
+    )
+    .bind(workspace)
+    .fetch_all(pg_db)
+    .await?;
+
+    let items = scripts
+        .into_iter()
+        .map(|item| (WorkspaceItemKind::Script, item))
+        .chain(
+            flows
+                .into_iter()
+                .map(|item| (WorkspaceItemKind::Flow, item)),
+        )
+        .map(
+            |(kind, (workspace_id, path, summary, description))| WorkspaceItem {
+                workspace_id,
+                path,
+                kind,
+                summary,
+                description,
+            },
+        )
+        .collect();
+
+    Ok(items)
+}
+
+#[cfg(feature = "embedding")]
+impl WorkspaceItem {
+    fn id(&self) -> String {
+        format!("{}_{}_{}", self.workspace_id, self.kind.as_str(), self.path)
+    }
+
+    fn text(&self) -> String {
+        format!("{};{};{}", self.path, self.summary, self.description)
+    }
+
+    /// Identifies the embedded text, an item whose hash did not change keeps its embedding
+    fn text_hash(&self) -> String {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.text().hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+
+    async fn embed(self, model_instance: Arc<ModelInstance>) -> Result<Embedding> {
+        let vector = model_instance.create_embedding(&self.text()).await?;
+
+        let mut hm = HashMap::new();
+        hm.insert("workspace".to_string(), self.workspace_id.clone());
+        hm.insert("path".to_string(), self.path.clone());
+        hm.insert("kind".to_string(), self.kind.as_str().to_string());
+        hm.insert("text_hash".to_string(), self.text_hash());
+        hm.insert(
+            "description".to_string(),
+            if self.description.is_empty() {
+                self.summary
+            } else {
+                self.description
+            },
+        );
+
+        Ok(Embedding { id: self.id(), vector, metadata: Some(hm) })
+    }
+
+    /// The embedding of the item, or `None` if it could not be created so that one item does not
+    /// prevent the others from being indexed
+    async fn try_embed(self, model_instance: Arc<ModelInstance>) -> Option<Embedding> {
+        let (workspace_id, path) = (self.workspace_id.clone(), self.path.clone());
+        match self.embed(model_instance).await {
+            Ok(embedding) => Some(embedding),
+            Err(e) => {
+                tracing::warn!("Could not embed {path} of workspace {workspace_id}: {e:#}");
+                None
+            }
+        }
+    }
+}
+
+/// Indexes the latest version of the scripts and flows of every workspace, separately from the hub
+/// data. Items whose path, summary and description did not change keep their embedding, and each
+/// workspace is replaced on its own so that a failure only affects the items it concerns
+#[cfg(feature = "embedding")]
+pub async fn index_workspace_items(db: &Pool<Postgres>, model_instance: Arc<ModelInstance>) {
+    let items = match list_workspace_items(db, None).await {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::error!("Could not list the workspace items to index: {e:#}");
+            return;
+        }
+    };
+
+    let mut indexed: HashMap<String, HashMap<String, Embedding>> = HashMap::new();
+    if let Some(embeddings_db) = EMBEDDINGS_DB.read().await.as_ref() {
+        for embedding in embeddings_db.workspace_item_embeddings() {
+            let workspace = embedding
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get("workspace"))
+                .cloned()
+                .unwrap_or_default();
+            indexed
+                .entry(workspace)
+                .or_default()
+                .insert(embedding.id.clone(), embedding);
+        }
+    }
+
+    let mut items_by_workspace: HashMap<String, Vec<WorkspaceItem>> = HashMap::new();
+    for item in items {
+        items_by_workspace
+            .entry(item.workspace_id.clone())
+            .or_default()
+            .push(item);
+    }
+    // workspaces without items anymore are emptied
+    for workspace in indexed.keys() {
+        items_by_workspace.entry(workspace.clone()).or_default();
+    }
+
+    for (workspace, items) in items_by_workspace {
+        let mut previous = indexed.remove(&workspace).unwrap_or_default();
+        let mut embeddings = Vec::with_capacity(items.len());
+        for item in items {
+            let unchanged = previous.remove(&item.id()).filter(|embedding| {
+                embedding
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.get("text_hash"))
+                    == Some(&item.text_hash())
+            });
+            let embedding = match unchanged {
+                Some(embedding) => Some(embedding),
+                None => item.try_embed(model_instance.clone()).await,
+            };
+            embeddings.extend(embedding);
+        }
+
+        let r = match EMBEDDINGS_DB.write().await.as_mut() {
+            Some(embeddings_db) => embeddings_db.upsert_workspace_items(&workspace, embeddings),
+            None => return,
+        };
+        if let Err(e) = r {
+            tracing::error!("Could not index the items of workspace {workspace}: {e:#}");
+        }
+    }
 }
 
 #[cfg(feature = "embedding")]
@@ -625,15 +1155,25 @@ pub fn load_embeddings_db(db: &Pool<Postgres>) -> () {
 
 #[cfg(feature = "embedding")]
 pub async fn update_embeddings_db(db: &Pool<Postgres>) -> () {
-    if let Some(model_instance) = MODEL_INSTANCE.read().await.as_ref() {
+    let model_instance = MODEL_INSTANCE.read().await.clone();
+    if let Some(model_instance) = model_instance {
         tracing::info!("Creating embeddings DB...");
-        let new_embeddings_db = EmbeddingsDb::new(&db, model_instance.clone()).await;
+        let workspace_items = EMBEDDINGS_DB
+            .read()
+            .await
+            .as_ref()
+            .map(|embeddings_db| embeddings_db.workspace_item_embeddings())
+            .unwrap_or_default();
+        let new_embeddings_db =
+            EmbeddingsDb::new(&db, model_instance.clone(), workspace_items).await;
         if let Err(e) = new_embeddings_db.as_ref() {
             tracing::error!("Failed to create embeddings db: {}", e);
         } else {
             let mut embeddings_db = EMBEDDINGS_DB.write().await;
             *embeddings_db = new_embeddings_db.ok();
+            drop(embeddings_db);
             tracing::info!("Created embeddings DB");
+            index_workspace_items(db, model_instance).await;
         }
     } else {
         tracing::error!("Could not update embeddings DB, model instance not initialized");
@@ -642,7 +1182,11 @@ pub async fn update_embeddings_db(db: &Pool<Postgres>) -> () {
 
 #[cfg(feature = "embedding")]
 pub fn workspaced_service() -> Router {
-    Router::new().route("/query_resource_types", get(query_resource_types))
+    Router::new()
+        .route("/query_resource_types", get(query_resource_types))
+        .route("/search", post(vector_search))
+        .route("/refresh", post(refresh_embeddings))
+        .route("/refresh_status", get(get_refresh_status))
 }
 
 #[cfg(feature = "embedding")]
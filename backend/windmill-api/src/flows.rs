@@ -15,9 +15,10 @@ use crate::triggers::{
 use crate::utils::WithStarredInfoQuery;
 use crate::{
     db::DB,
+    path_rename::{rename_path, RenamePath, RenameQuery, RenameResult, RenamedKind},
     schedule::clear_schedule,
     users::{maybe_refresh_folders, require_owner_of_path},
-    webhook_util::{WebhookMessage, WebhookShared},
+    webhook_util::{DeployAction, DeployEvent, DeployKind, WebhookMessage, WebhookShared},
     HTTP_CLIENT,
 };
 use axum::response::IntoResponse;
@@ -55,6 +56,7 @@ pub fn workspaced_service() -> Router {
         .route("/create", post(create_flow))
         .route("/update/*path", post(update_flow))
         .route("/archive/*path", post(archive_flow_by_path))
+        .route("/rename/*path", post(rename_flow))
         .route("/delete/*path", delete(delete_flow_by_path))
         .route("/get_triggers_count/*path", get(get_triggers_count))
         .route("/list_tokens/*path", get(list_tokens))
@@ -73,6 +75,12 @@ pub fn workspaced_service() -> Router {
             "/toggle_workspace_error_handler/*path",
             post(toggle_workspace_error_handler),
         )
+        .route("/test_data/create/*path", post(create_flow_test_data))
+        .route("/test_data/list/*path", get(list_flow_test_data))
+        .route(
+            "/test_data/delete/:name/*path",
+            delete(delete_flow_test_data),
+        )
 }
 
 pub fn global_service() -> Router {
@@ -398,6 +406,15 @@ async fn create_flow(
         w_id
     ).execute(&mut *tx).await?;
 
+    sqlx::query!(
+        "UPDATE flow SET strict_args = $1 WHERE path = $2 AND workspace_id = $3",
+        nf.strict_args.unwrap_or(false),
+        &nf.path,
+        &w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     sqlx::query!(
         "DELETE FROM draft WHERE path = $1 AND workspace_id = $2 AND typ = 'flow'",
         nf.path,
@@ -468,6 +485,14 @@ async fn create_flow(
     .await?;
 
     new_tx.commit().await?;
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Flow,
+        DeployAction::Deploy,
+        &w_id,
+        &nf.path,
+        Some(version.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::CreateFlow { workspace: w_id.clone(), path: nf.path.clone() },
@@ -511,10 +536,38 @@ pub async fn require_is_writer(authed: &ApiAuthed, path: &str, w_id: &str, db: D
     .await;
 }
 
+/// Renames a flow path, the caller must be a writer of both the current and the new path
+async fn rename_flow(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<RenameQuery>,
+    Json(rename): Json<RenamePath>,
+) -> JsonResult<RenameResult> {
+    let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
+    require_is_writer(&authed, &rename.new_path, &w_id, db.clone()).await?;
+
+    let result = rename_path(
+        &authed,
+        &db,
+        &w_id,
+        RenamedKind::Flow,
+        path,
+        &rename.new_path,
+        query.dry_run.unwrap_or(false),
+    )
+    .await?;
+    result.send_deploy_events(&webhook, &authed, &w_id, RenamedKind::Flow);
+    Ok(Json(result))
+}
+
 #[derive(Serialize)]
 pub struct FlowVersion {
     pub id: i64,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_by: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deployment_msg: Option<String>,
 }
@@ -529,9 +582,9 @@ async fn get_flow_history(
 
     let flows = sqlx::query_as!(
         FlowVersion,
-        "SELECT flow_version.id, flow_version.created_at, deployment_metadata.deployment_msg FROM flow_version 
+        "SELECT flow_version.id, flow_version.created_at, flow_version.created_by, deployment_metadata.deployment_msg FROM flow_version
         LEFT JOIN deployment_metadata ON flow_version.id = deployment_metadata.flow_version
-        WHERE flow_version.path = $1 AND flow_version.workspace_id = $2 
+        WHERE flow_version.path = $1 AND flow_version.workspace_id = $2
         ORDER BY flow_version.created_at DESC",
         path,
         w_id
@@ -553,9 +606,9 @@ async fn get_latest_version(
 
     let version = sqlx::query_as!(
         FlowVersion,
-        "SELECT flow_version.id, flow_version.created_at, deployment_metadata.deployment_msg FROM flow_version 
+        "SELECT flow_version.id, flow_version.created_at, flow_version.created_by, deployment_metadata.deployment_msg FROM flow_version
         LEFT JOIN deployment_metadata ON flow_version.id = deployment_metadata.flow_version
-        WHERE flow_version.path = $1 AND flow_version.workspace_id = $2 
+        WHERE flow_version.path = $1 AND flow_version.workspace_id = $2
         ORDER BY flow_version.created_at DESC",
         path,
         w_id
@@ -576,7 +629,7 @@ async fn get_flow_version(
     let mut tx = user_db.begin(&authed).await?;
 
     let flow = sqlx::query_as::<_, Flow>(
-        "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by
+        "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow.strict_args, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by
         FROM flow
         LEFT JOIN flow_version ON flow_version.path = flow.path AND flow_version.workspace_id = flow.workspace_id
         WHERE flow.path = $1 AND flow.workspace_id = $2 AND flow_version.id = $3",
@@ -791,6 +844,15 @@ async fn update_flow(
         version, nf.path, w_id
     ).execute(&mut *tx).await?;
 
+    sqlx::query!(
+        "UPDATE flow SET strict_args = $1 WHERE path = $2 AND workspace_id = $3",
+        nf.strict_args.unwrap_or(false),
+        &nf.path,
+        &w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     if is_new_path {
         check_schedule_conflict(&mut tx, &w_id, &nf.path).await?;
 
@@ -852,6 +914,14 @@ async fn update_flow(
     )
     .await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Flow,
+        DeployAction::Deploy,
+        &w_id,
+        &nf.path,
+        Some(version.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::UpdateFlow {
@@ -957,7 +1027,7 @@ async fn get_flow_by_path(
 
     let flow_o = if query.with_starred_info.unwrap_or(false) {
         sqlx::query_as::<_, FlowWithStarred>(
-            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by, favorite.path IS NOT NULL as starred
+            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow.strict_args, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by, favorite.path IS NOT NULL as starred
             FROM flow
             LEFT JOIN favorite
             ON favorite.favorite_kind = 'flow' 
@@ -974,7 +1044,7 @@ async fn get_flow_by_path(
             .await?
     } else {
         sqlx::query_as::<_, FlowWithStarred>(
-            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by, NULL as starred
+            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow.strict_args, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by, NULL as starred
             FROM flow
             LEFT JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
             WHERE flow.path = $1 AND flow.workspace_id = $2"
@@ -1119,6 +1189,19 @@ async fn archive_flow_by_path(
     )
     .await?;
 
+    let action = if archived.archived.unwrap_or(true) {
+        DeployAction::Archive
+    } else {
+        DeployAction::Unarchive
+    };
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Flow,
+        action,
+        &w_id,
+        path,
+        None,
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::ArchiveFlow { workspace: w_id, path: path.to_owned() },
@@ -1153,6 +1236,14 @@ async fn delete_flow_by_path(
     .execute(&mut *tx)
     .await?;
 
+    sqlx::query!(
+        "DELETE FROM flow_test_data WHERE flow_path = $1 AND workspace_id = $2",
+        path,
+        &w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
     sqlx::query!(
         "DELETE FROM capture_config WHERE path = $1 AND workspace_id = $2 AND is_flow IS TRUE",
         path,
@@ -1209,6 +1300,14 @@ async fn delete_flow_by_path(
         ))
     })?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Flow,
+        DeployAction::Delete,
+        &w_id,
+        path,
+        None,
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::DeleteFlow { workspace: w_id, path: path.to_owned() },
@@ -1217,6 +1316,184 @@ async fn delete_flow_by_path(
     Ok(format!("Flow {path} deleted"))
 }
 
+type TestDataArgs = HashMap<String, Box<serde_json::value::RawValue>>;
+
+#[derive(Deserialize)]
+struct NewFlowTestData {
+    name: String,
+    args: TestDataArgs,
+}
+
+#[derive(Serialize)]
+struct FlowTestData {
+    name: String,
+    args: sqlx::types::Json<TestDataArgs>,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct FlowTestDataQuery {
+    run: Option<bool>,
+    name: Option<String>,
+}
+
+async fn require_flow_readable(
+    authed: &ApiAuthed,
+    user_db: UserDB,
+    w_id: &str,
+    path: &str,
+) -> Result<()> {
+    let mut tx = user_db.begin(authed).await?;
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+        path,
+        w_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .unwrap_or(false);
+    tx.commit().await?;
+    if !exists {
+        return Err(Error::NotFound(format!("Flow {path} not found")));
+    }
+    Ok(())
+}
+
+async fn create_flow_test_data(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(nt): Json<NewFlowTestData>,
+) -> Result<(StatusCode, String)> {
+    let path = path.to_path();
+    require_owner_of_path(&authed, path)?;
+    require_flow_readable(&authed, user_db, &w_id, path).await?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "INSERT INTO flow_test_data (workspace_id, flow_path, name, args, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (workspace_id, flow_path, name)
+        DO UPDATE SET args = EXCLUDED.args, created_by = EXCLUDED.created_by, created_at = now()",
+        &w_id,
+        path,
+        &nt.name,
+        sqlx::types::Json(&nt.args) as _,
+        &authed.username
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "flows.test_data.create",
+        ActionKind::Create,
+        &w_id,
+        Some(path),
+        Some([("name", nt.name.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        format!("Test data {} of flow {path} saved", nt.name),
+    ))
+}
+
+async fn list_flow_test_data(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<FlowTestDataQuery>,
+    Query(run_query): Query<crate::jobs::RunJobQuery>,
+) -> Result<axum::response::Response> {
+    let flow_path = path.to_path();
+    require_flow_readable(&authed, user_db.clone(), &w_id, flow_path).await?;
+
+    if query.run.unwrap_or(false) {
+        run_query.reject_dedup_window()?;
+        let name = query.name.ok_or_else(|| {
+            Error::BadRequest("name of the test data to run the flow with is required".to_string())
+        })?;
+        let args = sqlx::query_scalar!(
+            r#"SELECT args as "args: sqlx::types::Json<TestDataArgs>" FROM flow_test_data
+            WHERE workspace_id = $1 AND flow_path = $2 AND name = $3"#,
+            &w_id,
+            flow_path,
+            &name
+        )
+        .fetch_optional(&db)
+        .await?;
+        let args = not_found_if_none(args, "Flow test data", &name)?;
+
+        let res = crate::jobs::run_flow_by_path_inner(
+            authed,
+            db,
+            user_db,
+            w_id,
+            path,
+            run_query,
+            windmill_queue::PushArgsOwned { extra: None, args: args.0 },
+            None,
+        )
+        .await?;
+        return Ok(res.into_response());
+    }
+
+    let test_data = sqlx::query_as!(
+        FlowTestData,
+        r#"SELECT name, args as "args: _", created_by, created_at FROM flow_test_data
+        WHERE workspace_id = $1 AND flow_path = $2
+        ORDER BY name"#,
+        &w_id,
+        flow_path
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(test_data).into_response())
+}
+
+async fn delete_flow_test_data(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, name, path)): Path<(String, String, StripPath)>,
+) -> Result<String> {
+    let path = path.to_path();
+    require_owner_of_path(&authed, path)?;
+
+    let mut tx = db.begin().await?;
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM flow_test_data WHERE workspace_id = $1 AND flow_path = $2 AND name = $3
+        RETURNING 1",
+        &w_id,
+        path,
+        &name
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Flow test data", &name)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "flows.test_data.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        Some([("name", name.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Test data {name} of flow {path} deleted"))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1260,6 +1537,7 @@ mod tests {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1291,6 +1569,7 @@ mod tests {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1320,6 +1599,7 @@ mod tests {
                     cache_ttl: None,
                     mock: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -1348,6 +1628,7 @@ mod tests {
                 cache_ttl: None,
                 mock: None,
                 timeout: None,
+                step_timeout_ms: None,
                 priority: None,
                 delete_after_use: None,
                 continue_on_error: None,
@@ -6,13 +6,17 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::db::ApiAuthed;
 
 use crate::{
     auth::AuthCache,
     db::DB,
+    path_rename::TRIGGER_TABLES,
     users::Tokened,
     webhook_util::{WebhookMessage, WebhookShared},
 };
@@ -22,12 +26,13 @@ use axum::{
     Json, Router,
 };
 use lazy_static::lazy_static;
+use quick_cache::sync::Cache;
 use regex::Regex;
 use windmill_audit::audit_ee::audit_log;
 use windmill_audit::ActionKind;
 use windmill_common::{
     db::UserDB,
-    error::{self, to_anyhow, JsonResult, Result},
+    error::{self, to_anyhow, Error, JsonResult, Result},
     users::username_to_permissioned_as,
     utils::{not_found_if_none, paginate, Pagination},
 };
@@ -44,10 +49,12 @@ pub fn workspaced_service() -> Router {
         .route("/get/:name", get(get_folder))
         .route("/update/:name", post(update_folder))
         .route("/getusage/:name", get(get_folder_usage))
+        .route("/:name/stats", get(get_folder_stats))
         .route("/delete/:name", delete(delete_folder))
         .route("/addowner/:name", post(add_owner))
         .route("/removeowner/:name", post(remove_owner))
         .route("/is_owner/*path", get(is_owner_api))
+        .route("/bulk_move", post(bulk_move_folder))
 }
 
 #[derive(FromRow, Serialize, Deserialize, Clone)]
@@ -516,6 +523,107 @@ async fn get_folder_usage(
     }))
 }
 
+const FOLDER_STATS_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Copy)]
+struct FolderStats {
+    scripts: i64,
+    flows: i64,
+    schedules: i64,
+    resources: i64,
+    variables: i64,
+    total_job_runs_last_30d: i64,
+    #[serde(skip)]
+    fetched_at: Instant,
+}
+
+lazy_static! {
+    static ref FOLDER_STATS: Cache<(String, String), FolderStats> = Cache::new(1000);
+}
+
+async fn count_in_folder(db: &DB, sql: &str, w_id: &str, name: &str) -> Result<i64> {
+    Ok(sqlx::query_scalar::<_, i64>(sql)
+        .bind(w_id)
+        .bind(name)
+        .fetch_one(db)
+        .await?)
+}
+
+/// Counts of what is inside a folder visible to the user, computed for the whole folder and cached
+/// for a minute
+async fn get_folder_stats(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, name)): Path<(String, String)>,
+) -> JsonResult<FolderStats> {
+    let mut tx = user_db.begin(&authed).await?;
+    not_found_if_none(get_folderopt(&mut tx, &w_id, &name).await?, "Folder", &name)?;
+    tx.commit().await?;
+
+    let key = (w_id.clone(), name.clone());
+    if let Some(stats) = FOLDER_STATS.get(&key) {
+        if stats.fetched_at.elapsed() < FOLDER_STATS_TTL {
+            return Ok(Json(stats));
+        }
+    }
+
+    let (scripts, flows, schedules, resources, variables, total_job_runs_last_30d) = tokio::try_join!(
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM script WHERE workspace_id = $1 AND starts_with(path, 'f/' || $2 || '/')
+            AND archived = false AND deleted = false",
+            &w_id,
+            &name,
+        ),
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM flow WHERE workspace_id = $1 AND starts_with(path, 'f/' || $2 || '/')
+            AND archived = false",
+            &w_id,
+            &name,
+        ),
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM schedule WHERE workspace_id = $1 AND starts_with(path, 'f/' || $2 || '/')",
+            &w_id,
+            &name,
+        ),
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM resource WHERE workspace_id = $1 AND starts_with(path, 'f/' || $2 || '/')",
+            &w_id,
+            &name,
+        ),
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM variable WHERE workspace_id = $1 AND starts_with(path, 'f/' || $2 || '/')",
+            &w_id,
+            &name,
+        ),
+        count_in_folder(
+            &db,
+            "SELECT COUNT(*) FROM completed_job WHERE workspace_id = $1
+            AND starts_with(script_path, 'f/' || $2 || '/') AND is_flow_step = false
+            AND created_at > now() - interval '30 days'",
+            &w_id,
+            &name,
+        ),
+    )?;
+
+    let stats = FolderStats {
+        scripts,
+        flows,
+        schedules,
+        resources,
+        variables,
+        total_job_runs_last_30d,
+        fetched_at: Instant::now(),
+    };
+    FOLDER_STATS.insert(key, stats);
+    Ok(Json(stats))
+}
+
 async fn delete_folder(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -677,3 +785,386 @@ async fn remove_owner(
 
     Ok(format!("Removed {} to folder {}", owner, name))
 }
+
+#[derive(Deserialize)]
+struct BulkMoveFolder {
+    source_prefix: String,
+    target_prefix: String,
+}
+
+#[derive(Serialize)]
+struct BulkMovedItems {
+    scripts: u64,
+    flows: u64,
+    schedules: u64,
+    resources: u64,
+    variables: u64,
+    /// triggers that run a moved script or flow
+    triggers: u64,
+    /// flows whose modules reference a moved script or flow
+    referencing_flows: u64,
+}
+
+lazy_static! {
+    static ref VALID_FOLDER_PREFIX: Regex = Regex::new(r#"^f/[a-zA-Z_0-9]+(/[\w-]+)*$"#).unwrap();
+}
+
+/// Filters the items whose `column` is the source prefix ($2) or is below it
+fn in_source(column: &str) -> String {
+    format!("({column} = $2 OR starts_with({column}, $2 || '/'))")
+}
+
+/// `column` of an item of the source once moved under the target prefix ($3)
+fn moved(column: &str) -> String {
+    format!("$3 || substr({column}, char_length($2) + 1)")
+}
+
+/// The paths moved from the source ($2) to the target ($3)
+#[derive(Clone, Copy)]
+pub(crate) enum MovedPaths {
+    /// the source path alone, as when renaming a single item
+    Exact,
+    /// the source prefix and the paths below it
+    Prefix,
+}
+
+impl MovedPaths {
+    fn filter(self, column: &str) -> String {
+        match self {
+            MovedPaths::Exact => format!("{column} = $2"),
+            MovedPaths::Prefix => in_source(column),
+        }
+    }
+
+    fn moved(self, column: &str) -> String {
+        match self {
+            MovedPaths::Exact => "$3".to_string(),
+            MovedPaths::Prefix => moved(column),
+        }
+    }
+}
+
+/// Returns the folder of a `f/<folder>[/<subpath>]` prefix
+fn folder_of_prefix(prefix: &str) -> Result<&str> {
+    if !VALID_FOLDER_PREFIX.is_match(prefix) {
+        return Err(Error::BadRequest(format!(
+            "{prefix} is not a folder prefix, expected f/<folder>[/<subpath>]"
+        )));
+    }
+    Ok(prefix.split('/').nth(1).unwrap_or_default())
+}
+
+fn is_nested(prefix: &str, other: &str) -> bool {
+    prefix == other || prefix.starts_with(&format!("{other}/"))
+}
+
+/// `path` once moved under the target prefix, if it is the source prefix or below it
+fn moved_path(path: &str, source: &str, target: &str) -> Option<String> {
+    is_nested(path, source).then(|| format!("{target}{}", &path[source.len()..]))
+}
+
+/// Moves the `path` of the flow modules (`{ "type": "script" | "flow", "path": ... }`) of `value`
+/// that are below the source prefix, returns whether any was moved
+fn move_flow_module_refs(value: &mut serde_json::Value, source: &str, target: &str) -> bool {
+    match value {
+        serde_json::Value::Object(o) => {
+            let mut moved = false;
+            if matches!(
+                o.get("type").and_then(serde_json::Value::as_str),
+                Some("script" | "flow")
+            ) {
+                if let Some(path) = o
+                    .get("path")
+                    .and_then(serde_json::Value::as_str)
+                    .and_then(|path| moved_path(path, source, target))
+                {
+                    o.insert("path".to_string(), serde_json::Value::String(path));
+                    moved = true;
+                }
+            }
+            for v in o.values_mut() {
+                moved |= move_flow_module_refs(v, source, target);
+            }
+            moved
+        }
+        serde_json::Value::Array(a) => a.iter_mut().fold(false, |moved, v| {
+            move_flow_module_refs(v, source, target) | moved
+        }),
+        _ => false,
+    }
+}
+
+/// Paths outside of the source that some moved item of `table` would be renamed to
+async fn moved_path_collisions(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    w_id: &str,
+    source: &str,
+    target: &str,
+) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar::<_, String>(&format!(
+        "SELECT DISTINCT path FROM {table} WHERE workspace_id = $1 AND NOT {in_source}
+        AND path IN (SELECT {moved} FROM {table} WHERE workspace_id = $1 AND {in_source})",
+        in_source = in_source("path"),
+        moved = moved("path"),
+    ))
+    .bind(w_id)
+    .bind(source)
+    .bind(target)
+    .fetch_all(&mut **tx)
+    .await?)
+}
+
+async fn move_paths(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    column: &str,
+    w_id: &str,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    move_matching_paths(tx, table, column, w_id, MovedPaths::Prefix, source, target).await
+}
+
+async fn move_matching_paths(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    column: &str,
+    w_id: &str,
+    paths: MovedPaths,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    Ok(sqlx::query(&format!(
+        "UPDATE {table} SET {column} = {moved} WHERE workspace_id = $1 AND {filter}",
+        moved = paths.moved(column),
+        filter = paths.filter(column),
+    ))
+    .bind(w_id)
+    .bind(source)
+    .bind(target)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected())
+}
+
+/// Flows are copied to their new path before their versions and nodes are moved, the foreign keys
+/// on the flow path do not cascade updates
+pub(crate) async fn move_flow_paths(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    paths: MovedPaths,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    sqlx::query(&format!(
+        "INSERT INTO flow
+            (workspace_id, path, summary, description, archived, extra_perms, dependency_job, draft_only, tag, ws_error_handler_muted, dedicated_worker, timeout, visible_to_runner_only, on_behalf_of_email, concurrency_key, versions, value, schema, edited_by, edited_at, strict_args)
+        SELECT workspace_id, {moved}, summary, description, archived, extra_perms, dependency_job, draft_only, tag, ws_error_handler_muted, dedicated_worker, timeout, visible_to_runner_only, on_behalf_of_email, concurrency_key, versions, value, schema, edited_by, edited_at, strict_args
+            FROM flow
+            WHERE workspace_id = $1 AND {filter}",
+        moved = paths.moved("path"),
+        filter = paths.filter("path"),
+    ))
+    .bind(w_id)
+    .bind(source)
+    .bind(target)
+    .execute(&mut **tx)
+    .await?;
+
+    move_matching_paths(tx, "flow_version", "path", w_id, paths, source, target).await?;
+    move_matching_paths(tx, "flow_node", "path", w_id, paths, source, target).await?;
+
+    Ok(sqlx::query(&format!(
+        "DELETE FROM flow WHERE workspace_id = $1 AND {}",
+        paths.filter("path")
+    ))
+    .bind(w_id)
+    .bind(source)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected())
+}
+
+/// Schedules keep pointing to the moved scripts and flows, and their pending jobs to the moved
+/// schedules
+async fn move_schedule_paths(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    let schedules = move_paths(tx, "schedule", "path", w_id, source, target).await?;
+    move_paths(tx, "schedule", "script_path", w_id, source, target).await?;
+
+    sqlx::query(&format!(
+        "UPDATE queue SET schedule_path = {} WHERE workspace_id = $1 AND running = false AND {}",
+        moved("schedule_path"),
+        in_source("schedule_path"),
+    ))
+    .bind(w_id)
+    .bind(source)
+    .bind(target)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(schedules)
+}
+
+/// Triggers keep running the moved scripts and flows
+async fn move_trigger_script_paths(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    let mut triggers = 0;
+    for table in TRIGGER_TABLES {
+        triggers += move_paths(tx, table, "script_path", w_id, source, target).await?;
+    }
+    Ok(triggers)
+}
+
+/// Deploys a new version of the flows of the workspace whose latest version references a moved
+/// script or flow. Former versions are left untouched as they are cached by id.
+async fn move_flow_refs(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    source: &str,
+    target: &str,
+) -> Result<u64> {
+    let flows = sqlx::query_as::<_, (String, i64, serde_json::Value)>(
+        "SELECT flow.path, flow_version.id, flow_version.value
+        FROM flow
+        JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+        WHERE flow.workspace_id = $1 AND strpos(flow_version.value::text, $2) > 0",
+    )
+    .bind(w_id)
+    .bind(source)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut rewritten = 0;
+    for (path, version_id, mut value) in flows {
+        if !move_flow_module_refs(&mut value, source, target) {
+            continue;
+        }
+        let version = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO flow_version (workspace_id, path, value, schema, created_by)
+            SELECT workspace_id, path, $1, schema, $2 FROM flow_version WHERE id = $3
+            RETURNING id",
+        )
+        .bind(&value)
+        .bind(&authed.username)
+        .bind(version_id)
+        .fetch_one(&mut **tx)
+        .await?;
+        sqlx::query(
+            "UPDATE flow SET value = $1, versions = array_append(versions, $2), edited_by = $3, edited_at = now()
+            WHERE path = $4 AND workspace_id = $5",
+        )
+        .bind(&value)
+        .bind(version)
+        .bind(&authed.username)
+        .bind(&path)
+        .bind(w_id)
+        .execute(&mut **tx)
+        .await?;
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
+
+/// Moves the scripts, flows, schedules, resources and variables under `source_prefix` to
+/// `target_prefix` along with the triggers and flow modules referencing the moved scripts and
+/// flows, in a single transaction
+async fn bulk_move_folder(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(BulkMoveFolder { source_prefix, target_prefix }): Json<BulkMoveFolder>,
+) -> JsonResult<BulkMovedItems> {
+    let source = source_prefix.trim_end_matches('/');
+    let target = target_prefix.trim_end_matches('/');
+    let source_folder = folder_of_prefix(source)?;
+    let target_folder = folder_of_prefix(target)?;
+    if is_nested(source, target) || is_nested(target, source) {
+        return Err(Error::BadRequest(format!(
+            "Cannot move {source} to {target}, one is contained in the other"
+        )));
+    }
+    require_is_owner(&authed, source_folder)?;
+    require_is_owner(&authed, target_folder)?;
+
+    let mut tx = db.begin().await?;
+    for folder in [source_folder, target_folder] {
+        not_found_if_none(
+            get_folderopt(&mut tx, &w_id, folder).await?,
+            "Folder",
+            folder,
+        )?;
+    }
+
+    let tables = [
+        ("script", "scripts"),
+        ("flow", "flows"),
+        ("schedule", "schedule"),
+        ("resource", "resources"),
+        ("variable", "variables"),
+    ];
+
+    let mut collisions = vec![];
+    for (table, _) in tables {
+        for path in moved_path_collisions(&mut tx, table, &w_id, source, target).await? {
+            collisions.push(format!("{table} {path}"));
+        }
+    }
+    if !collisions.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "Cannot move {source} to {target}, these items already exist: {}",
+            collisions.join(", ")
+        )));
+    }
+
+    let items = BulkMovedItems {
+        scripts: move_paths(&mut tx, "script", "path", &w_id, source, target).await?,
+        flows: move_flow_paths(&mut tx, &w_id, MovedPaths::Prefix, source, target).await?,
+        schedules: move_schedule_paths(&mut tx, &w_id, source, target).await?,
+        resources: move_paths(&mut tx, "resource", "path", &w_id, source, target).await?,
+        variables: move_paths(&mut tx, "variable", "path", &w_id, source, target).await?,
+        triggers: move_trigger_script_paths(&mut tx, &w_id, source, target).await?,
+        referencing_flows: move_flow_refs(&mut tx, &authed, &w_id, source, target).await?,
+    };
+
+    let counts = [
+        items.scripts,
+        items.flows,
+        items.schedules,
+        items.resources,
+        items.variables,
+    ];
+    for ((_, audit_prefix), count) in tables.into_iter().zip(counts) {
+        if count == 0 {
+            continue;
+        }
+        let count = count.to_string();
+        audit_log(
+            &mut *tx,
+            &authed,
+            &format!("{audit_prefix}.bulk_move"),
+            ActionKind::Update,
+            &w_id,
+            Some(source),
+            Some([("target_prefix", target), ("count", count.as_str())].into()),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    for folder in [source_folder, target_folder] {
+        FOLDER_STATS.remove(&(w_id.clone(), folder.to_string()));
+    }
+
+    Ok(Json(items))
+}
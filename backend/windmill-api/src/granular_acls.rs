@@ -6,9 +6,12 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use crate::{db::DB, users::require_owner_of_path};
+use crate::{
+    db::DB,
+    users::{fetch_api_authed_from_permissioned_as, require_owner_of_path},
+};
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     routing::{get, post},
     Json, Router,
 };
@@ -39,11 +42,17 @@ const KINDS: [&str; 13] = [
     "nats_trigger",
 ];
 
+/// kinds whose row level security is driven by the path, the folder and the extra_perms
+const EFFECTIVE_KINDS: [&str; 7] = [
+    "script", "flow", "app", "resource", "variable", "schedule", "raw_app",
+];
+
 pub fn workspaced_service() -> Router {
     Router::new()
         .route("/get/*path", get(get_granular_acls))
         .route("/add/*path", post(add_granular_acl))
         .route("/remove/*path", post(remove_granular_acl))
+        .route("/effective", get(get_effective_permission))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,7 +61,7 @@ pub struct GranularAcl {
     pub write: Option<bool>,
 }
 
-async fn add_granular_acl(
+pub(crate) async fn add_granular_acl(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
@@ -292,7 +301,7 @@ async fn remove_granular_acl(
     Ok("Successfully removed granular acl".to_string())
 }
 
-async fn get_granular_acls(
+pub(crate) async fn get_granular_acls(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, path)): Path<(String, StripPath)>,
@@ -322,3 +331,184 @@ async fn get_granular_acls(
 
     Ok(Json(obj))
 }
+
+#[derive(Deserialize)]
+pub struct EffectivePermissionQuery {
+    pub kind: String,
+    pub path: String,
+    pub username: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionLevel {
+    None,
+    Viewer,
+    Writer,
+    Owner,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GrantSource {
+    /// the user is an admin of the workspace
+    Admin,
+    /// the item is in the home of the user or of one of its groups
+    Path,
+    /// the extra_perms of the item itself
+    Item,
+    /// the extra_perms or owners of the folder of the item
+    Folder,
+}
+
+#[derive(Serialize)]
+pub struct EffectiveGrant {
+    pub source: GrantSource,
+    /// `u/<username>` or `g/<group>` the grant was given to
+    pub grantee: String,
+    pub permission: PermissionLevel,
+}
+
+#[derive(Serialize)]
+pub struct EffectivePermission {
+    pub permission: PermissionLevel,
+    pub grants: Vec<EffectiveGrant>,
+}
+
+fn extra_perms_grants(
+    extra_perms: &serde_json::Value,
+    owners: &[String],
+    grantees: &[String],
+    source: GrantSource,
+) -> Vec<EffectiveGrant> {
+    grantees
+        .iter()
+        .filter_map(|grantee| {
+            let write = extra_perms.get(grantee)?.as_bool().unwrap_or(false);
+            let permission = if owners.contains(grantee) {
+                PermissionLevel::Owner
+            } else if write {
+                PermissionLevel::Writer
+            } else {
+                PermissionLevel::Viewer
+            };
+            Some(EffectiveGrant { source, grantee: grantee.clone(), permission })
+        })
+        .collect()
+}
+
+async fn get_effective_permission(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(EffectivePermissionQuery { kind, path, username }): Query<EffectivePermissionQuery>,
+) -> JsonResult<EffectivePermission> {
+    if !EFFECTIVE_KINDS.contains(&kind.as_str()) {
+        return Err(Error::BadRequest(format!(
+            "Invalid kind {kind}, expected one of {}",
+            EFFECTIVE_KINDS.join(", ")
+        )));
+    }
+    if !authed.is_admin && authed.username != username {
+        return Err(Error::NotAuthorized(
+            "Only admins can query the effective permissions of another user".to_string(),
+        ));
+    }
+
+    let email = sqlx::query_scalar::<_, String>(
+        "SELECT email FROM usr WHERE username = $1 AND workspace_id = $2 AND disabled = false",
+    )
+    .bind(&username)
+    .bind(&w_id)
+    .fetch_optional(&db)
+    .await?;
+    let email = not_found_if_none(email, "User", &username)?;
+
+    let user =
+        fetch_api_authed_from_permissioned_as(format!("u/{username}"), email, &w_id, &db, None)
+            .await?;
+
+    // the item is read with the row level security of the user, so that a missing item and an
+    // item hidden from the user get the same answer
+    let mut tx = user_db.begin(&user).await?;
+    let extra_perms = sqlx::query_scalar::<_, serde_json::Value>(&format!(
+        "SELECT extra_perms FROM {kind} WHERE path = $1 AND workspace_id = $2 LIMIT 1"
+    ))
+    .bind(&path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    let Some(extra_perms) = extra_perms else {
+        return Ok(Json(EffectivePermission {
+            permission: PermissionLevel::None,
+            grants: vec![],
+        }));
+    };
+
+    let mut grantees = vec![format!("u/{username}")];
+    grantees.extend(user.groups.iter().map(|g| format!("g/{g}")));
+
+    let mut grants = vec![];
+    if user.is_admin {
+        grants.push(EffectiveGrant {
+            source: GrantSource::Admin,
+            grantee: grantees[0].clone(),
+            permission: PermissionLevel::Owner,
+        });
+    }
+    let mut splitted = path.split('/');
+    match (splitted.next(), splitted.next()) {
+        (Some("u"), Some(owner)) if owner == username => grants.push(EffectiveGrant {
+            source: GrantSource::Path,
+            grantee: grantees[0].clone(),
+            permission: PermissionLevel::Owner,
+        }),
+        (Some("g"), Some(group)) if user.groups.iter().any(|g| g == group) => {
+            grants.push(EffectiveGrant {
+                source: GrantSource::Path,
+                grantee: format!("g/{group}"),
+                permission: PermissionLevel::Writer,
+            })
+        }
+        (Some("f"), Some(folder)) => {
+            let folder = sqlx::query_as::<_, (serde_json::Value, Vec<String>)>(
+                "SELECT extra_perms, owners FROM folder WHERE name = $1 AND workspace_id = $2",
+            )
+            .bind(folder)
+            .bind(&w_id)
+            .fetch_optional(&db)
+            .await?;
+            if let Some((folder_perms, owners)) = folder {
+                grants.extend(extra_perms_grants(
+                    &folder_perms,
+                    &owners,
+                    &grantees,
+                    GrantSource::Folder,
+                ));
+            }
+        }
+        _ => (),
+    }
+    grants.extend(extra_perms_grants(
+        &extra_perms,
+        &[],
+        &grantees,
+        GrantSource::Item,
+    ));
+
+    // the grants are the ones the write policies of the row level security check
+    let permission = if require_owner_of_path(&user, &path).is_ok() {
+        PermissionLevel::Owner
+    } else if grants
+        .iter()
+        .any(|g| g.permission >= PermissionLevel::Writer)
+    {
+        PermissionLevel::Writer
+    } else {
+        PermissionLevel::Viewer
+    };
+
+    Ok(Json(EffectivePermission { permission, grants }))
+}
@@ -7,6 +7,11 @@
  */
 
 use crate::db::ApiAuthed;
+use crate::jobs::{
+    list_completed_jobs_query, ListCompletedQuery, ListableCompletedJob,
+    LISTABLE_COMPLETED_JOB_FIELDS,
+};
+use crate::users::{check_scopes, get_scope_tags};
 use crate::{db::DB, utils::require_super_admin};
 
 use axum::{
@@ -24,7 +29,9 @@ use windmill_common::{
 };
 use windmill_common::{db::UserDB, users::username_to_permissioned_as};
 
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use sql_builder::quote;
 use sqlx::{query_scalar, FromRow, Postgres, Transaction};
 use windmill_git_sync::handle_deployment_metadata;
 
@@ -34,10 +41,12 @@ pub fn workspaced_service() -> Router {
         .route("/listnames", get(list_group_names))
         .route("/create", post(create_group))
         .route("/get/:name", get(get_group))
+        .route("/:name/jobs", get(list_group_jobs))
         .route("/update/:name", post(update_group))
         .route("/delete/:name", delete(delete_group))
         .route("/adduser/:name", post(add_user))
         .route("/removeuser/:name", post(remove_user))
+        .route("/sync/:name", post(sync_group_members))
         .route("/is_owner", get(is_owner))
 }
 
@@ -50,8 +59,16 @@ pub fn global_service() -> Router {
         .route("/delete/:name", delete(delete_igroup))
         .route("/adduser/:name", post(add_user_igroup))
         .route("/removeuser/:name", post(remove_user_igroup))
+        .route("/sync/:name", post(sync_igroup_members))
         .route("/export", get(export_igroups))
         .route("/overwrite", post(overwrite_igroups))
+        .route(
+            "/mappings",
+            get(list_igroup_mappings).post(create_igroup_mapping),
+        )
+        .route("/mappings/update/:id", post(update_igroup_mapping))
+        .route("/mappings/delete/:id", delete(delete_igroup_mapping))
+        .route("/mappings/sync", post(sync_igroup_mappings_route))
 }
 
 #[derive(FromRow, Serialize, Deserialize)]
@@ -92,6 +109,59 @@ pub struct Email {
     pub email: String,
 }
 
+#[derive(Deserialize)]
+pub struct SyncMembers {
+    /// desired members of the group, usernames or emails (only emails for instance groups)
+    pub members: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GroupJobsQuery {
+    /// also return the email and name of the user who created each job
+    pub include_member_details: Option<bool>,
+}
+
+#[derive(FromRow, Serialize)]
+pub struct GroupJob {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub job: ListableCompletedJob,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by_email: Option<String>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncMembersQuery {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct SyncMembersResult {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+fn sync_members_plan(current: Vec<String>, mut desired: Vec<String>) -> SyncMembersResult {
+    desired.sort();
+    desired.dedup();
+    let added = desired
+        .iter()
+        .filter(|m| !current.contains(m))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut removed = current
+        .into_iter()
+        .filter(|m| !desired.contains(m))
+        .collect::<Vec<_>>();
+    removed.sort();
+    SyncMembersResult { unchanged: desired.len() - added.len(), added, removed }
+}
+
 async fn list_groups(
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
@@ -378,6 +448,7 @@ async fn delete_igroup(
     .await?;
 
     tx.commit().await?;
+    reconcile_igroup_mappings(&db, Some(&name)).await;
     Ok(format!("Deleted group {}", name))
 }
 
@@ -436,6 +507,85 @@ async fn get_group(
     }))
 }
 
+async fn list_group_jobs(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, name)): Path<(String, String)>,
+    Query(pagination): Query<Pagination>,
+    Query(lq): Query<ListCompletedQuery>,
+    Query(GroupJobsQuery { include_member_details }): Query<GroupJobsQuery>,
+) -> JsonResult<Vec<GroupJob>> {
+    check_scopes(&authed, || format!("jobs:listjobs"))?;
+    if !authed.is_admin && !authed.groups.contains(&name) {
+        return Err(Error::NotAuthorized(format!(
+            "Only members of group {name} and admins can list the jobs of its members"
+        )));
+    }
+
+    // the jobs are listed with the row level security of the caller, as in `list_completed_jobs`
+    let mut tx = user_db.begin(&authed).await?;
+
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM group_ WHERE name = $1 AND workspace_id = $2)",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if !exists {
+        return Err(Error::NotFound(format!("Group {name} not found")));
+    }
+
+    let members = sqlx::query_scalar::<_, String>(
+        "SELECT usr FROM usr_to_group WHERE group_ = $1 AND workspace_id = $2",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    if members.is_empty() {
+        tx.commit().await?;
+        return Ok(Json(vec![]));
+    }
+
+    let (per_page, offset) = paginate(pagination);
+    let mut sqlb = list_completed_jobs_query(
+        &w_id,
+        per_page,
+        offset,
+        &lq,
+        &LISTABLE_COMPLETED_JOB_FIELDS,
+        false,
+        get_scope_tags(&authed),
+    );
+    sqlb.and_where_in(
+        "permissioned_as",
+        &members
+            .iter()
+            .map(|m| quote(username_to_permissioned_as(m)))
+            .collect::<Vec<_>>(),
+    );
+
+    let sql = if include_member_details.unwrap_or(false) {
+        format!(
+            "SELECT jobs.*, usr.email AS created_by_email, password.name AS created_by_name
+            FROM {} LEFT JOIN usr ON usr.username = jobs.created_by AND usr.workspace_id = jobs.workspace_id
+            LEFT JOIN password ON password.email = usr.email
+            ORDER BY jobs.created_at {}",
+            sqlb.subquery_as("jobs")?,
+            if lq.order_desc.unwrap_or(true) { "DESC" } else { "ASC" }
+        )
+    } else {
+        sqlb.sql()?
+    };
+
+    let jobs = sqlx::query_as::<_, GroupJob>(&sql)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(jobs))
+}
+
 async fn delete_group(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -628,6 +778,7 @@ async fn add_user_igroup(
     )
     .await?;
     tx.commit().await?;
+    reconcile_igroup_mappings(&db, Some(&name)).await;
     Ok(format!("Added {} to igroup {}", email, name))
 }
 
@@ -697,6 +848,7 @@ async fn remove_user_igroup(
     )
     .await?;
     tx.commit().await?;
+    reconcile_igroup_mappings(&db, Some(&name)).await;
     Ok(format!("Added {} to igroup {}", email, name))
 }
 
@@ -752,6 +904,185 @@ async fn remove_user(
     Ok(format!("Removed {} to group {}", user_username, name))
 }
 
+async fn sync_group_members(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, name)): Path<(String, String)>,
+    Query(query): Query<SyncMembersQuery>,
+    Json(SyncMembers { members }): Json<SyncMembers>,
+) -> JsonResult<SyncMembersResult> {
+    let mut tx = user_db.begin(&authed).await?;
+    if !authed.is_admin {
+        require_is_owner(&name, &authed.username, &authed.groups, &w_id, &db).await?;
+    }
+
+    not_found_if_none(get_group_opt(&mut tx, &w_id, &name).await?, "Group", &name)?;
+
+    let resolved = sqlx::query_as::<_, (String, String)>(
+        "SELECT username, email FROM usr
+        WHERE workspace_id = $1 AND (username = ANY($2) OR email = ANY($2))",
+    )
+    .bind(&w_id)
+    .bind(&members)
+    .fetch_all(&mut *tx)
+    .await?;
+    let unknown = members
+        .iter()
+        .filter(|m| !resolved.iter().any(|(u, e)| u == *m || e == *m))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "Unknown users in workspace {w_id}: {}",
+            unknown.into_iter().join(", ")
+        )));
+    }
+
+    let current = sqlx::query_scalar::<_, String>(
+        "SELECT usr FROM usr_to_group WHERE group_ = $1 AND workspace_id = $2",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    let plan = sync_members_plan(current, resolved.into_iter().map(|(u, _)| u).collect());
+
+    if &name == "all" && !plan.removed.is_empty() {
+        return Err(Error::BadRequest(format!("Cannot delete users from all")));
+    }
+    if query.dry_run.unwrap_or(false) || (plan.added.is_empty() && plan.removed.is_empty()) {
+        return Ok(Json(plan));
+    }
+
+    for user_username in plan.added.iter() {
+        sqlx::query(
+            "INSERT INTO usr_to_group (workspace_id, usr, group_) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(&w_id)
+        .bind(user_username)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_log(
+            &mut *tx,
+            &authed,
+            "group.adduser",
+            ActionKind::Update,
+            &w_id,
+            Some(&name.to_string()),
+            Some([("user", user_username.as_str())].into()),
+        )
+        .await?;
+    }
+    for user_username in plan.removed.iter() {
+        sqlx::query(
+            "DELETE FROM usr_to_group WHERE usr = $1 AND group_ = $2 AND workspace_id = $3",
+        )
+        .bind(user_username)
+        .bind(&name)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_log(
+            &mut *tx,
+            &authed,
+            "group.removeuser",
+            ActionKind::Update,
+            &w_id,
+            Some(&name.to_string()),
+            Some([("user", user_username.as_str())].into()),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        windmill_git_sync::DeployedObject::Group { name: name.clone() },
+        Some(format!("Synced members of group '{}'", &name)),
+        true,
+    )
+    .await?;
+
+    Ok(Json(plan))
+}
+
+async fn sync_igroup_members(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(name): Path<String>,
+    Query(query): Query<SyncMembersQuery>,
+    Json(SyncMembers { members }): Json<SyncMembers>,
+) -> JsonResult<SyncMembersResult> {
+    require_super_admin(&db, &authed.email).await?;
+    let mut tx = db.begin().await?;
+
+    let group_opt = sqlx::query_scalar!("SELECT name FROM instance_group WHERE name = $1", name)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    not_found_if_none(group_opt, "IGroup", &name)?;
+
+    let current =
+        sqlx::query_scalar::<_, String>("SELECT email FROM email_to_igroup WHERE igroup = $1")
+            .bind(&name)
+            .fetch_all(&mut *tx)
+            .await?;
+    let plan = sync_members_plan(current, members);
+
+    if query.dry_run.unwrap_or(false) {
+        return Ok(Json(plan));
+    }
+
+    for email in plan.added.iter() {
+        sqlx::query(
+            "INSERT INTO email_to_igroup (email, igroup) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(email)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+
+        audit_log(
+            &mut *tx,
+            &authed,
+            "igroup.adduser",
+            ActionKind::Update,
+            "global",
+            Some(&name.to_string()),
+            Some([("email", email.as_str())].into()),
+        )
+        .await?;
+    }
+    for email in plan.removed.iter() {
+        sqlx::query("DELETE FROM email_to_igroup WHERE email = $1 AND igroup = $2")
+            .bind(email)
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?;
+
+        audit_log(
+            &mut *tx,
+            &authed,
+            "igroup.removeuser",
+            ActionKind::Update,
+            "global",
+            Some(&name.to_string()),
+            Some([("email", email.as_str())].into()),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    reconcile_igroup_mappings(&db, Some(&name)).await;
+
+    Ok(Json(plan))
+}
+
 #[cfg(feature = "enterprise")]
 #[derive(Serialize, Deserialize)]
 struct ExportedIGroup {
@@ -857,6 +1188,7 @@ async fn overwrite_igroups(
     .await?;
 
     tx.commit().await?;
+    reconcile_igroup_mappings(&db, None).await;
     Ok("Imported igroups".to_string())
 }
 
@@ -866,3 +1198,323 @@ async fn overwrite_igroups() -> JsonResult<String> {
         "This feature is only available in the enterprise version".to_string(),
     ))
 }
+
+#[derive(FromRow, Serialize)]
+struct IGroupMapping {
+    id: i64,
+    instance_group: String,
+    workspace_id: String,
+    workspace_group: String,
+    remove_on_unassign: bool,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct NewIGroupMapping {
+    instance_group: String,
+    workspace_id: String,
+    workspace_group: String,
+    #[serde(default)]
+    remove_on_unassign: bool,
+}
+
+#[derive(Deserialize)]
+struct EditIGroupMapping {
+    remove_on_unassign: bool,
+}
+
+#[derive(Serialize)]
+pub struct IGroupMappingSync {
+    pub id: i64,
+    pub instance_group: String,
+    pub workspace_id: String,
+    pub workspace_group: String,
+    pub added: i64,
+    pub removed: i64,
+}
+
+async fn list_igroup_mappings(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+) -> JsonResult<Vec<IGroupMapping>> {
+    require_super_admin(&db, &authed.email).await?;
+    let mappings = sqlx::query_as::<_, IGroupMapping>(
+        "SELECT * FROM igroup_mapping ORDER BY instance_group, workspace_id, workspace_group",
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(mappings))
+}
+
+async fn create_igroup_mapping(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Json(nm): Json<NewIGroupMapping>,
+) -> Result<String> {
+    require_super_admin(&db, &authed.email).await?;
+    let mut tx = db.begin().await?;
+
+    let igroup_opt =
+        sqlx::query_scalar::<_, String>("SELECT name FROM instance_group WHERE name = $1")
+            .bind(&nm.instance_group)
+            .fetch_optional(&mut *tx)
+            .await?;
+    not_found_if_none(igroup_opt, "IGroup", &nm.instance_group)?;
+    not_found_if_none(
+        get_group_opt(&mut tx, &nm.workspace_id, &nm.workspace_group).await?,
+        "Group",
+        &nm.workspace_group,
+    )?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO igroup_mapping
+            (instance_group, workspace_id, workspace_group, remove_on_unassign, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (instance_group, workspace_id, workspace_group) DO NOTHING
+        RETURNING id",
+    )
+    .bind(&nm.instance_group)
+    .bind(&nm.workspace_id)
+    .bind(&nm.workspace_group)
+    .bind(nm.remove_on_unassign)
+    .bind(&authed.email)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+        Error::BadRequest(format!(
+            "Instance group {} is already mapped to group {} of workspace {}",
+            nm.instance_group, nm.workspace_group, nm.workspace_id
+        ))
+    })?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "igroup.mapping.create",
+        ActionKind::Create,
+        "global",
+        Some(&nm.instance_group),
+        Some(
+            [
+                ("workspace_id", nm.workspace_id.as_str()),
+                ("workspace_group", nm.workspace_group.as_str()),
+            ]
+            .into(),
+        ),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!(
+        "Created mapping {id} of instance group {} to group {} of workspace {}",
+        nm.instance_group, nm.workspace_group, nm.workspace_id
+    ))
+}
+
+async fn update_igroup_mapping(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(id): Path<i64>,
+    Json(em): Json<EditIGroupMapping>,
+) -> Result<String> {
+    require_super_admin(&db, &authed.email).await?;
+    let mut tx = db.begin().await?;
+
+    let updated = sqlx::query_scalar::<_, String>(
+        "UPDATE igroup_mapping SET remove_on_unassign = $1 WHERE id = $2 RETURNING instance_group",
+    )
+    .bind(em.remove_on_unassign)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let instance_group = not_found_if_none(updated, "IGroup mapping", id.to_string())?;
+
+    let remove_on_unassign = em.remove_on_unassign.to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "igroup.mapping.update",
+        ActionKind::Update,
+        "global",
+        Some(&instance_group),
+        Some([("remove_on_unassign", remove_on_unassign.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Updated mapping {id}"))
+}
+
+/// Deleting a mapping keeps the members it added in the workspace group
+async fn delete_igroup_mapping(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(id): Path<i64>,
+) -> Result<String> {
+    require_super_admin(&db, &authed.email).await?;
+    let mut tx = db.begin().await?;
+
+    let deleted = sqlx::query_scalar::<_, String>(
+        "DELETE FROM igroup_mapping WHERE id = $1 RETURNING instance_group",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let instance_group = not_found_if_none(deleted, "IGroup mapping", id.to_string())?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "igroup.mapping.delete",
+        ActionKind::Delete,
+        "global",
+        Some(&instance_group),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Deleted mapping {id}"))
+}
+
+async fn sync_igroup_mappings_route(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+) -> JsonResult<Vec<IGroupMappingSync>> {
+    require_super_admin(&db, &authed.email).await?;
+    let synced = sync_igroup_mappings(&db, None).await?;
+
+    let mut tx = db.begin().await?;
+    let added = synced.iter().map(|s| s.added).sum::<i64>().to_string();
+    let removed = synced.iter().map(|s| s.removed).sum::<i64>().to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "igroup.mapping.sync",
+        ActionKind::Execute,
+        "global",
+        None,
+        Some([("added", added.as_str()), ("removed", removed.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(synced))
+}
+
+/// Adds the workspace users of the mapped instance groups (all of them if `instance_group` is
+/// None) to their workspace group and, for the mappings with `remove_on_unassign`, removes the
+/// members they added that left the instance group. Members added by hand are never removed.
+pub async fn sync_igroup_mappings(
+    db: &DB,
+    instance_group: Option<&str>,
+) -> Result<Vec<IGroupMappingSync>> {
+    let mut tx = db.begin().await?;
+    let mappings = sqlx::query_as::<_, IGroupMapping>(
+        "SELECT * FROM igroup_mapping WHERE $1::text IS NULL OR instance_group = $1
+        ORDER BY id FOR UPDATE",
+    )
+    .bind(instance_group)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut synced = vec![];
+    for mapping in mappings {
+        let desired = sqlx::query_scalar::<_, String>(
+            "SELECT usr.username FROM usr JOIN email_to_igroup ON email_to_igroup.email = usr.email
+            WHERE email_to_igroup.igroup = $1 AND usr.workspace_id = $2 AND NOT usr.disabled",
+        )
+        .bind(&mapping.instance_group)
+        .bind(&mapping.workspace_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let added = sqlx::query_scalar::<_, i64>(
+            "WITH added AS (
+                INSERT INTO usr_to_group (workspace_id, usr, group_)
+                SELECT $2, username, $3 FROM unnest($4::text[]) AS username
+                ON CONFLICT DO NOTHING
+                RETURNING usr
+            ), tracked AS (
+                INSERT INTO igroup_mapping_member (mapping_id, username)
+                SELECT $1, usr FROM added
+                ON CONFLICT DO NOTHING
+            )
+            SELECT COUNT(*) FROM added",
+        )
+        .bind(mapping.id)
+        .bind(&mapping.workspace_id)
+        .bind(&mapping.workspace_group)
+        .bind(&desired)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let removed = if mapping.remove_on_unassign {
+            sqlx::query_scalar::<_, i64>(
+                "WITH untracked AS (
+                    DELETE FROM igroup_mapping_member
+                    WHERE mapping_id = $1 AND NOT (username = ANY($4::text[]))
+                    RETURNING username
+                ), removed AS (
+                    DELETE FROM usr_to_group
+                    WHERE workspace_id = $2 AND group_ = $3
+                        AND usr IN (SELECT username FROM untracked)
+                    RETURNING usr
+                )
+                SELECT COUNT(*) FROM removed",
+            )
+            .bind(mapping.id)
+            .bind(&mapping.workspace_id)
+            .bind(&mapping.workspace_group)
+            .bind(&desired)
+            .fetch_one(&mut *tx)
+            .await?
+        } else {
+            0
+        };
+
+        synced.push(IGroupMappingSync {
+            id: mapping.id,
+            instance_group: mapping.instance_group,
+            workspace_id: mapping.workspace_id,
+            workspace_group: mapping.workspace_group,
+            added,
+            removed,
+        });
+    }
+    tx.commit().await?;
+
+    Ok(synced)
+}
+
+/// To call once the members of an instance group (or of all of them) changed, e.g. from the SCIM
+/// handlers, errors are only logged as the membership change is already committed
+pub async fn reconcile_igroup_mappings(db: &DB, instance_group: Option<&str>) {
+    if let Err(e) = sync_igroup_mappings(db, instance_group).await {
+        tracing::error!(
+            "Could not sync the mappings of instance group {}: {e:#}",
+            instance_group.unwrap_or("*")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_members_plan() {
+        let plan = sync_members_plan(
+            vec!["alice".to_string(), "bob".to_string()],
+            vec![
+                "carol".to_string(),
+                "alice".to_string(),
+                "carol".to_string(),
+            ],
+        );
+        assert_eq!(plan.added, vec!["carol".to_string()]);
+        assert_eq!(plan.removed, vec!["bob".to_string()]);
+        assert_eq!(plan.unchanged, 1);
+    }
+}
@@ -0,0 +1,216 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use std::{collections::BTreeMap, future::Future, time::Duration};
+
+use axum::{extract::Query, http::StatusCode, response::IntoResponse, Extension, Json};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use windmill_common::{utils::GIT_VERSION, DB};
+
+#[cfg(feature = "smtp")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the smtp listener has been started successfully
+#[cfg(feature = "smtp")]
+pub(crate) static SMTP_LISTENER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Hard budget of the whole health check, components that do not answer in time are reported
+/// as degraded
+const HEALTH_CHECK_BUDGET: Duration = Duration::from_secs(2);
+const WORKER_ALIVE_SECS: i32 = 60;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum HealthProbe {
+    /// only checks that the server process answers, never touches the database
+    Liveness,
+    /// checks the dependencies required to serve requests
+    Readiness,
+}
+
+#[derive(Deserialize)]
+struct HealthQuery {
+    probe: Option<HealthProbe>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+#[derive(Serialize)]
+struct ComponentHealth {
+    status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ComponentHealth {
+    fn ok() -> Self {
+        Self { status: HealthStatus::Ok, latency_ms: None, count: None, error: None }
+    }
+
+    fn degraded(error: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            latency_ms: None,
+            count: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: HealthStatus,
+    version: &'static str,
+    components: BTreeMap<&'static str, ComponentHealth>,
+}
+
+async fn within_budget<F>(deadline: Instant, f: F) -> ComponentHealth
+where
+    F: Future<Output = ComponentHealth>,
+{
+    tokio::time::timeout_at(deadline, f)
+        .await
+        .unwrap_or_else(|_| ComponentHealth::degraded("timed out"))
+}
+
+async fn check_db(db: &DB) -> ComponentHealth {
+    let start = Instant::now();
+    match sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(db).await {
+        Ok(_) => ComponentHealth {
+            latency_ms: Some(start.elapsed().as_millis()),
+            ..ComponentHealth::ok()
+        },
+        Err(e) => ComponentHealth::degraded(format!("{e:#}")),
+    }
+}
+
+async fn check_count(db: &DB, sql: &str) -> ComponentHealth {
+    match sqlx::query_scalar::<_, i64>(sql).fetch_one(db).await {
+        Ok(count) => ComponentHealth { count: Some(count), ..ComponentHealth::ok() },
+        Err(e) => ComponentHealth::degraded(format!("{e:#}")),
+    }
+}
+
+#[cfg(feature = "parquet")]
+async fn check_object_store() -> Option<ComponentHealth> {
+    let os = windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS
+        .read()
+        .await
+        .clone()?;
+    let start = Instant::now();
+    // a missing object still proves that the object store answered
+    let health = match os
+        .head(&object_store::path::Path::from("windmill_health_check"))
+        .await
+    {
+        Ok(_) | Err(object_store::Error::NotFound { .. }) => ComponentHealth {
+            latency_ms: Some(start.elapsed().as_millis()),
+            ..ComponentHealth::ok()
+        },
+        Err(e) => ComponentHealth::degraded(format!("{e:#}")),
+    };
+    Some(health)
+}
+
+#[cfg(not(feature = "parquet"))]
+async fn check_object_store() -> Option<ComponentHealth> {
+    None
+}
+
+fn smtp_health() -> Option<ComponentHealth> {
+    #[cfg(feature = "smtp")]
+    {
+        Some(if SMTP_LISTENER_STARTED.load(Ordering::Relaxed) {
+            ComponentHealth::ok()
+        } else {
+            ComponentHealth::degraded("smtp listener not started")
+        })
+    }
+
+    #[cfg(not(feature = "smtp"))]
+    {
+        None
+    }
+}
+
+pub async fn health(
+    Extension(db): Extension<DB>,
+    Query(query): Query<HealthQuery>,
+) -> impl IntoResponse {
+    let deadline = Instant::now() + HEALTH_CHECK_BUDGET;
+    let mut components = BTreeMap::new();
+
+    if query.probe != Some(HealthProbe::Liveness) {
+        let object_store = async {
+            tokio::time::timeout_at(deadline, check_object_store())
+                .await
+                .unwrap_or_else(|_| Some(ComponentHealth::degraded("timed out")))
+        };
+        let (database, object_store) =
+            tokio::join!(within_budget(deadline, check_db(&db)), object_store);
+        components.insert("database", database);
+        if let Some(object_store) = object_store {
+            components.insert("object_store", object_store);
+        }
+    }
+
+    if query.probe.is_none() {
+        let (queue, workers) = tokio::join!(
+            within_budget(
+                deadline,
+                check_count(
+                    &db,
+                    "SELECT COUNT(*) FROM queue WHERE running = false AND scheduled_for <= now()",
+                )
+            ),
+            within_budget(
+                deadline,
+                check_count(
+                    &db,
+                    &format!(
+                        "SELECT COUNT(*) FROM worker_ping WHERE ping_at > now() - interval '{WORKER_ALIVE_SECS} seconds'"
+                    ),
+                )
+            ),
+        );
+        components.insert("queue", queue);
+        components.insert("workers", workers);
+        if let Some(smtp) = smtp_health() {
+            components.insert("smtp", smtp);
+        }
+    }
+
+    let status = if components.values().all(|c| c.status == HealthStatus::Ok) {
+        HealthStatus::Ok
+    } else {
+        HealthStatus::Degraded
+    };
+
+    // only the readiness probe reports a failure to the orchestrator, a degraded full check is
+    // informational
+    let code = if status == HealthStatus::Degraded && query.probe == Some(HealthProbe::Readiness) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    (
+        code,
+        Json(Health { status, version: GIT_VERSION, components }),
+    )
+}
@@ -1,21 +1,23 @@
 #[cfg(feature = "parquet")]
 use crate::job_helpers_ee::get_workspace_s3_resource;
 use crate::{
-    args::WebhookArgs,
+    args::{build_extra, WebhookArgs},
     auth::{AuthCache, OptTokened},
     db::{ApiAuthed, DB},
     jobs::{
-        run_flow_by_path_inner, run_script_by_path_inner, run_wait_result_flow_by_path_internal,
-        run_wait_result_script_by_path_internal, RunJobQuery,
+        add_raw_string, run_flow_by_path_inner, run_script_by_path_inner,
+        run_wait_result_flow_by_path_internal, run_wait_result_script_by_path_internal,
+        RunJobQuery,
     },
     users::fetch_api_authed,
 };
 use axum::{
-    extract::{Path, Query},
+    extract::{FromRequest, Path, Query, Request},
     response::IntoResponse,
     routing::{delete, get, post},
     Extension, Json, Router,
 };
+use hmac::Mac;
 #[cfg(feature = "parquet")]
 use http::header::IF_NONE_MATCH;
 use http::{HeaderMap, StatusCode};
@@ -30,10 +32,13 @@ use windmill_common::s3_helpers::build_object_store_client;
 use windmill_common::{
     db::UserDB,
     error::{self, JsonResult},
+    oauth2::HmacSha256,
     s3_helpers::S3Object,
     utils::{not_found_if_none, paginate, require_admin, Pagination, StripPath},
+    variables::{build_crypt, decrypt, encrypt, get_secret_value_as_admin},
     worker::{to_raw_value, CLOUD_HOSTED},
 };
+use windmill_queue::PushArgsOwned;
 
 lazy_static::lazy_static! {
     static ref ROUTE_PATH_KEY_RE: regex::Regex = regex::Regex::new(r"/:\w+").unwrap();
@@ -58,7 +63,8 @@ pub fn routes_global_service() -> Router {
                 .delete(route_job)
                 .put(route_job)
                 .patch(route_job)
-                .head(|| async { "" }),
+                .head(|| async { "" })
+                .layer(axum::middleware::from_fn(crate::rate_limit::limit_runs)),
         )
         .layer(cors)
 }
@@ -99,6 +105,28 @@ impl TryFrom<&http::Method> for HttpMethod {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HmacAlgorithm {
+    Sha256,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HmacVerification {
+    /// header carrying the signature, e.g. `X-Hub-Signature-256`
+    header_name: String,
+    algorithm: HmacAlgorithm,
+    /// path of the (secret) variable holding the signing key
+    secret_path: String,
+}
+
+#[derive(Deserialize)]
+struct StaticAuthHeader {
+    name: String,
+    /// when omitted on update, the previously stored secret is kept
+    secret: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct NewTrigger {
     path: String,
@@ -109,6 +137,9 @@ struct NewTrigger {
     requires_auth: bool,
     http_method: HttpMethod,
     static_asset_config: Option<sqlx::types::Json<S3Object>>,
+    static_auth_header: Option<StaticAuthHeader>,
+    hmac_verification: Option<sqlx::types::Json<HmacVerification>>,
+    wrap_body: Option<bool>,
 }
 
 #[derive(FromRow, Serialize)]
@@ -127,6 +158,9 @@ struct Trigger {
     requires_auth: bool,
     http_method: HttpMethod,
     static_asset_config: Option<sqlx::types::Json<S3Object>>,
+    static_auth_header_name: Option<String>,
+    hmac_verification: Option<sqlx::types::Json<HmacVerification>>,
+    wrap_body: bool,
 }
 
 #[derive(Deserialize)]
@@ -139,6 +173,9 @@ struct EditTrigger {
     requires_auth: bool,
     http_method: HttpMethod,
     static_asset_config: Option<sqlx::types::Json<S3Object>>,
+    static_auth_header: Option<StaticAuthHeader>,
+    hmac_verification: Option<sqlx::types::Json<HmacVerification>>,
+    wrap_body: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -194,7 +231,7 @@ async fn get_trigger(
     let path = path.to_path();
     let trigger = sqlx::query_as!(
         Trigger,
-        r#"SELECT workspace_id, path, route_path, route_path_key, script_path, is_flow, http_method as "http_method: _", edited_by, email, edited_at, extra_perms, is_async, requires_auth, static_asset_config as "static_asset_config: _"
+        r#"SELECT workspace_id, path, route_path, route_path_key, script_path, is_flow, http_method as "http_method: _", edited_by, email, edited_at, extra_perms, is_async, requires_auth, static_asset_config as "static_asset_config: _", static_auth_header_name, hmac_verification as "hmac_verification: _", wrap_body
             FROM http_trigger
             WHERE workspace_id = $1 AND path = $2"#,
         w_id,
@@ -209,19 +246,78 @@ async fn get_trigger(
     Ok(Json(trigger))
 }
 
+async fn encrypt_static_auth_secret(
+    db: &DB,
+    w_id: &str,
+    static_auth_header: &Option<StaticAuthHeader>,
+) -> error::Result<Option<String>> {
+    match static_auth_header {
+        Some(StaticAuthHeader { secret: Some(secret), .. }) => {
+            let mc = build_crypt(db, w_id).await?;
+            Ok(Some(encrypt(&mc, secret)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The signing key is read as admin when a request comes in, so the secret variable has to be
+/// readable by the user saving the trigger, who becomes its owner
+async fn check_hmac_secret_path(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    w_id: &str,
+    hmac_verification: &Option<sqlx::types::Json<HmacVerification>>,
+) -> error::Result<()> {
+    let Some(sqlx::types::Json(hmac_verification)) = hmac_verification else {
+        return Ok(());
+    };
+    let is_secret = sqlx::query_scalar!(
+        "SELECT is_secret FROM variable WHERE workspace_id = $1 AND path = $2",
+        w_id,
+        &hmac_verification.secret_path
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    match is_secret {
+        Some(true) => Ok(()),
+        Some(false) => Err(error::Error::BadRequest(format!(
+            "hmac_verification secret_path {} is not a secret variable",
+            hmac_verification.secret_path
+        ))),
+        None => Err(error::Error::BadRequest(format!(
+            "hmac_verification secret_path {} is not a variable you can read",
+            hmac_verification.secret_path
+        ))),
+    }
+}
+
 async fn create_trigger(
     authed: ApiAuthed,
+    Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path(w_id): Path<String>,
     Json(ct): Json<NewTrigger>,
 ) -> error::Result<(StatusCode, String)> {
     require_admin(authed.is_admin, &authed.username)?;
 
+    if ct
+        .static_auth_header
+        .as_ref()
+        .is_some_and(|h| h.secret.is_none())
+    {
+        return Err(error::Error::BadRequest(
+            "static_auth_header requires a secret".to_string(),
+        ));
+    }
+
     let route_path_key = ROUTE_PATH_KEY_RE.replace_all(ct.route_path.as_str(), ":key");
+    let static_auth_header_secret =
+        encrypt_static_auth_secret(&db, &w_id, &ct.static_auth_header).await?;
 
     let mut tx = user_db.begin(&authed).await?;
+    check_hmac_secret_path(&mut tx, &w_id, &ct.hmac_verification).await?;
+
     sqlx::query!(
-        "INSERT INTO http_trigger (workspace_id, path, route_path, route_path_key, script_path, is_flow, is_async, requires_auth, http_method, static_asset_config, edited_by, email, edited_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, now())",
+        "INSERT INTO http_trigger (workspace_id, path, route_path, route_path_key, script_path, is_flow, is_async, requires_auth, http_method, static_asset_config, edited_by, email, edited_at, static_auth_header_name, static_auth_header_secret, hmac_verification, wrap_body) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, now(), $13, $14, $15, $16)",
         w_id,
         ct.path,
         ct.route_path,
@@ -233,7 +329,11 @@ async fn create_trigger(
         ct.http_method as _,
         ct.static_asset_config as _,
         &authed.username,
-        &authed.email
+        &authed.email,
+        ct.static_auth_header.as_ref().map(|h| h.name.as_str()),
+        static_auth_header_secret,
+        ct.hmac_verification as _,
+        ct.wrap_body.unwrap_or(false)
     )
     .execute(&mut *tx).await?;
 
@@ -255,12 +355,16 @@ async fn create_trigger(
 
 async fn update_trigger(
     authed: ApiAuthed,
+    Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, path)): Path<(String, StripPath)>,
     Json(ct): Json<EditTrigger>,
 ) -> error::Result<String> {
     let path = path.to_path();
+    let static_auth_header_secret =
+        encrypt_static_auth_secret(&db, &w_id, &ct.static_auth_header).await?;
     let mut tx = user_db.begin(&authed).await?;
+    check_hmac_secret_path(&mut tx, &w_id, &ct.hmac_verification).await?;
 
     if authed.is_admin {
         if ct.route_path.is_none() {
@@ -310,6 +414,22 @@ async fn update_trigger(
         .execute(&mut *tx).await?;
     }
 
+    sqlx::query!(
+        "UPDATE http_trigger
+            SET static_auth_header_name = $1,
+                static_auth_header_secret = CASE WHEN $1::VARCHAR IS NULL THEN NULL ELSE COALESCE($2, static_auth_header_secret) END,
+                hmac_verification = $3, wrap_body = $4
+            WHERE workspace_id = $5 AND path = $6",
+        ct.static_auth_header.as_ref().map(|h| h.name.as_str()),
+        static_auth_header_secret,
+        ct.hmac_verification as _,
+        ct.wrap_body.unwrap_or(false),
+        w_id,
+        ct.path,
+    )
+    .execute(&mut *tx)
+    .await?;
+
     audit_log(
         &mut *tx,
         &authed,
@@ -420,6 +540,10 @@ struct TriggerRoute {
     edited_by: String,
     email: String,
     static_asset_config: Option<sqlx::types::Json<S3Object>>,
+    static_auth_header_name: Option<String>,
+    static_auth_header_secret: Option<String>,
+    hmac_verification: Option<sqlx::types::Json<HmacVerification>>,
+    wrap_body: bool,
 }
 
 async fn get_http_route_trigger(
@@ -439,7 +563,7 @@ async fn get_http_route_trigger(
         let route_path = StripPath(splitted.collect::<Vec<_>>().join("/"));
         let triggers = sqlx::query_as!(
             TriggerRoute,
-            r#"SELECT path, script_path, is_flow, route_path, workspace_id, is_async, requires_auth, edited_by, email, static_asset_config as "static_asset_config: _" FROM http_trigger WHERE workspace_id = $1 AND http_method = $2"#,
+            r#"SELECT path, script_path, is_flow, route_path, workspace_id, is_async, requires_auth, edited_by, email, static_asset_config as "static_asset_config: _", static_auth_header_name, static_auth_header_secret, hmac_verification as "hmac_verification: _", wrap_body FROM http_trigger WHERE workspace_id = $1 AND http_method = $2"#,
             w_id,
             http_method as HttpMethod
         )
@@ -449,7 +573,7 @@ async fn get_http_route_trigger(
     } else {
         let triggers = sqlx::query_as!(
             TriggerRoute,
-            r#"SELECT path, script_path, is_flow, route_path, workspace_id, is_async, requires_auth, edited_by, email, static_asset_config as "static_asset_config: _" FROM http_trigger WHERE http_method = $1"#,
+            r#"SELECT path, script_path, is_flow, route_path, workspace_id, is_async, requires_auth, edited_by, email, static_asset_config as "static_asset_config: _", static_auth_header_name, static_auth_header_secret, hmac_verification as "hmac_verification: _", wrap_body FROM http_trigger WHERE http_method = $1"#,
             http_method as HttpMethod
         )
         .fetch_all(db)
@@ -528,6 +652,89 @@ async fn get_http_route_trigger(
     Ok((trigger, route_path.0, params, authed))
 }
 
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Checks a signature header such as `sha256=<hex>` (GitHub) or a bare hex/base64 digest
+fn verify_hmac_signature(
+    algorithm: HmacAlgorithm,
+    secret: &[u8],
+    body: &[u8],
+    signature: &str,
+) -> bool {
+    use base64::Engine;
+
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let Some(signature) = hex::decode(signature).ok().or_else(|| {
+        base64::engine::general_purpose::STANDARD
+            .decode(signature)
+            .ok()
+    }) else {
+        return false;
+    };
+
+    match algorithm {
+        HmacAlgorithm::Sha256 => {
+            let Ok(mut mac) = HmacSha256::new_from_slice(secret) else {
+                return false;
+            };
+            mac.update(body);
+            mac.verify_slice(&signature).is_ok()
+        }
+    }
+}
+
+async fn authenticate_trigger_request(
+    db: &DB,
+    trigger: &TriggerRoute,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> error::Result<()> {
+    if let (Some(header_name), Some(encrypted_secret)) = (
+        trigger.static_auth_header_name.as_ref(),
+        trigger.static_auth_header_secret.as_ref(),
+    ) {
+        let mc = build_crypt(db, &trigger.workspace_id).await?;
+        let secret = decrypt(&mc, encrypted_secret.to_owned())?;
+        let provided = headers
+            .get(header_name)
+            .map(|v| v.as_bytes())
+            .unwrap_or_default();
+        if !constant_time_eq(provided, secret.as_bytes()) {
+            return Err(error::Error::NotAuthorized(format!(
+                "Invalid or missing {header_name} header"
+            )));
+        }
+    }
+
+    if let Some(sqlx::types::Json(hmac_verification)) = trigger.hmac_verification.as_ref() {
+        let secret =
+            get_secret_value_as_admin(db, &trigger.workspace_id, &hmac_verification.secret_path)
+                .await?;
+        let valid = headers
+            .get(&hmac_verification.header_name)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|signature| {
+                verify_hmac_signature(
+                    hmac_verification.algorithm,
+                    secret.as_bytes(),
+                    body,
+                    signature,
+                )
+            });
+        if !valid {
+            return Err(error::Error::NotAuthorized(format!(
+                "Invalid or missing {} signature",
+                hmac_verification.header_name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn build_http_trigger_extra(
     route_path: &str,
     called_path: &str,
@@ -563,7 +770,7 @@ async fn route_job(
     Query(query): Query<HashMap<String, String>>,
     method: http::Method,
     headers: HeaderMap,
-    args: WebhookArgs,
+    request: Request,
 ) -> impl IntoResponse {
     let route_path = route_path.to_path();
     let (trigger, called_path, params, authed) = match get_http_route_trigger(
@@ -580,12 +787,48 @@ async fn route_job(
         Err(e) => return e.into_response(),
     };
 
-    let mut args = match args
-        .to_push_args_owned(&authed, &db, &trigger.workspace_id)
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, *crate::REQUEST_SIZE_LIMIT.read().await).await {
+        Ok(body) => body,
+        Err(e) => {
+            return error::Error::BadRequest(format!("Could not read request body: {e}"))
+                .into_response()
+        }
+    };
+
+    if let Err(e) = authenticate_trigger_request(&db, &trigger, &headers, &body).await {
+        return e.into_response();
+    }
+
+    let mut args = if trigger.wrap_body {
+        let raw_string = match String::from_utf8(body.to_vec()) {
+            Ok(raw_string) => raw_string,
+            Err(e) => {
+                return error::Error::BadRequest(format!("invalid utf8: {}", e)).into_response()
+            }
+        };
+        let args = add_raw_string(Some(raw_string), serde_json::Map::new())
+            .into_iter()
+            .map(|(k, v)| (k, to_raw_value(&v)))
+            .collect();
+        PushArgsOwned { extra: Some(build_extra(&headers, None)), args }
+    } else {
+        let args = match WebhookArgs::from_request(
+            Request::from_parts(parts, axum::body::Body::from(body)),
+            &(),
+        )
         .await
-    {
-        Ok(args) => args,
-        Err(e) => return e.into_response(),
+        {
+            Ok(args) => args,
+            Err(e) => return e,
+        };
+        match args
+            .to_push_args_owned(&authed, &db, &trigger.workspace_id)
+            .await
+        {
+            Ok(args) => args,
+            Err(e) => return e.into_response(),
+        }
     };
 
     #[cfg(not(feature = "parquet"))]
@@ -745,3 +988,58 @@ async fn route_job(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &[u8], body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn test_valid_hmac_signature() {
+        let body = br#"{"action":"opened"}"#;
+        let signature = sign(b"my_secret", body);
+
+        assert!(verify_hmac_signature(
+            HmacAlgorithm::Sha256,
+            b"my_secret",
+            body,
+            &signature
+        ));
+        assert!(verify_hmac_signature(
+            HmacAlgorithm::Sha256,
+            b"my_secret",
+            body,
+            &format!("sha256={signature}")
+        ));
+    }
+
+    #[test]
+    fn test_invalid_hmac_signature() {
+        let body = br#"{"action":"opened"}"#;
+        let signature = sign(b"other_secret", body);
+
+        assert!(!verify_hmac_signature(
+            HmacAlgorithm::Sha256,
+            b"my_secret",
+            body,
+            &signature
+        ));
+        assert!(!verify_hmac_signature(
+            HmacAlgorithm::Sha256,
+            b"my_secret",
+            br#"{"action":"closed"}"#,
+            &sign(b"my_secret", body)
+        ));
+        assert!(!verify_hmac_signature(
+            HmacAlgorithm::Sha256,
+            b"my_secret",
+            body,
+            "not a signature"
+        ));
+    }
+}
@@ -6,38 +6,48 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use crate::db::ApiAuthed;
+use crate::db::{ApiAuthed, DB};
 use axum::{
     extract::{Path, Query},
     routing::{get, post},
     Extension, Json, Router,
 };
 use chrono::{DateTime, Utc};
+use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{value::RawValue, Value};
 use sqlx::{types::Uuid, FromRow};
 use std::{
+    collections::HashMap,
     fmt::{Display, Formatter},
     vec,
 };
+use windmill_audit::{audit_ee::audit_log, ActionKind};
 use windmill_common::{
     db::UserDB,
-    error::JsonResult,
+    error::{Error, JsonResult, Result},
     jobs::JobKind,
     scripts::to_i64,
-    utils::{not_found_if_none, paginate, Pagination},
+    utils::{not_found_if_none, paginate, Pagination, StripPath},
 };
 pub fn workspaced_service() -> Router {
     Router::new()
+        .route("/", post(create_saved_input))
+        .route("/by_path/*path", get(list_saved_inputs_by_path))
+        .route(
+            "/:id",
+            get(get_saved_input)
+                .put(update_saved_input)
+                .delete(delete_saved_input),
+        )
         .route("/history", get(get_input_history))
         .route("/list", get(list_saved_inputs))
         .route("/create", post(create_input))
         .route("/update", post(update_input))
         .route("/delete/:id", post(delete_input))
-        .route(
-            "/:job_or_input_id/args",
-            get(get_args_from_history_or_saved_input),
-        )
+        .route("/star/:id", post(star_input))
+        .route("/unstar/:id", post(unstar_input))
+        .route("/:id/args", get(get_args_from_history_or_saved_input))
 }
 
 #[derive(Debug, sqlx::FromRow, Serialize, Deserialize)]
@@ -51,6 +61,10 @@ pub struct InputRow {
     pub created_at: DateTime<Utc>,
     pub created_by: String,
     pub is_public: bool,
+    pub is_starred: bool,
+    /// number of times these args were saved, summed over the collapsed duplicates
+    pub use_count: i64,
+    pub last_used_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type)]
@@ -104,6 +118,9 @@ pub struct Input {
     created_by: String,
     is_public: bool,
     success: bool,
+    is_starred: bool,
+    use_count: i64,
+    last_used_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -183,6 +200,9 @@ async fn get_input_history(
             created_by: row.created_by,
             is_public: true,
             success: row.success,
+            is_starred: false,
+            use_count: 1,
+            last_used_at: row.created_at,
         });
     }
 
@@ -239,22 +259,38 @@ async fn get_args_from_history_or_saved_input(
     Ok(Json(result))
 }
 
+#[derive(Deserialize)]
+struct ListSavedInputs {
+    is_starred: Option<bool>,
+}
+
+/// Lists the saved inputs of a runnable, inputs with the same name and args are collapsed into the
+/// most recently used one. Starred inputs come first.
 async fn list_saved_inputs(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path(w_id): Path<String>,
     Query(pagination): Query<Pagination>,
     Query(r): Query<RunnableParams>,
+    Query(l): Query<ListSavedInputs>,
 ) -> JsonResult<Vec<Input>> {
     let (per_page, offset) = paginate(pagination);
 
     let mut tx = user_db.begin(&authed).await?;
 
     let rows = sqlx::query_as::<_, InputRow>(
-        "select id, workspace_id, runnable_id, runnable_type, name, 'null'::jsonb as args, created_at, created_by, is_public from input \
-         where runnable_id = $1 and runnable_type = $2 and workspace_id = $3 \
-         and (is_public IS true OR created_by = $4) \
-         order by created_at desc limit $5 offset $6",
+        "select * from ( \
+            select distinct on (coalesce(args_hash, id::text), name) id, workspace_id, runnable_id, runnable_type, name, \
+                'null'::jsonb as args, created_at, created_by, is_public, \
+                bool_or(is_starred) over w as is_starred, sum(use_count) over w as use_count, \
+                max(coalesce(last_used_at, created_at)) over w as last_used_at from input \
+            where runnable_id = $1 and runnable_type = $2 and workspace_id = $3 \
+            and (is_public IS true OR created_by = $4) \
+            window w as (partition by coalesce(args_hash, id::text), name) \
+            order by coalesce(args_hash, id::text), name, input.is_starred desc, \
+                coalesce(input.last_used_at, input.created_at) desc \
+         ) i where ($7::bool IS NULL OR is_starred = $7) \
+         order by is_starred desc, last_used_at desc limit $5 offset $6",
     )
     .bind(&r.runnable_id)
     .bind(&r.runnable_type)
@@ -262,6 +298,7 @@ async fn list_saved_inputs(
     .bind(&authed.username)
     .bind(per_page as i32)
     .bind(offset as i32)
+    .bind(l.is_starred)
     .fetch_all(&mut *tx)
     .await?;
 
@@ -278,6 +315,9 @@ async fn list_saved_inputs(
             created_at: row.created_at,
             is_public: row.is_public,
             success: true,
+            is_starred: row.is_starred,
+            use_count: row.use_count,
+            last_used_at: row.last_used_at,
         })
     }
 
@@ -299,10 +339,32 @@ async fn create_input(
 ) -> JsonResult<String> {
     let mut tx = user_db.begin(&authed).await?;
 
+    // saving args that were already saved under the same name only bumps the existing input
+    let existing = sqlx::query_scalar::<_, Uuid>(
+        "UPDATE input SET use_count = use_count + 1, last_used_at = now() WHERE id = ( \
+            SELECT id FROM input WHERE workspace_id = $1 AND runnable_id = $2 AND runnable_type = $3 \
+            AND args_hash = md5($4::jsonb::text) AND created_by = $5 AND name = $6 \
+            ORDER BY last_used_at DESC NULLS LAST LIMIT 1 \
+        ) RETURNING id",
+    )
+    .bind(&w_id)
+    .bind(&r.runnable_id)
+    .bind(&r.runnable_type)
+    .bind(sqlx::types::Json(&input.args))
+    .bind(&authed.username)
+    .bind(&input.name)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(id) = existing {
+        tx.commit().await?;
+        return Ok(Json(id.to_string()));
+    }
+
     let id = Uuid::new_v4();
 
     sqlx::query(
-        "INSERT INTO input (id, workspace_id, runnable_id, runnable_type, name, args, created_by) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        "INSERT INTO input (id, workspace_id, runnable_id, runnable_type, name, args, created_by, args_hash) VALUES ($1, $2, $3, $4, $5, $6, $7, md5($6::jsonb::text))",
     )
     .bind(&id)
     .bind(&w_id)
@@ -364,3 +426,323 @@ async fn delete_input(
 
     Ok(Json(i_id.to_string()))
 }
+
+async fn star_input(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, i_id)): Path<(String, Uuid)>,
+) -> JsonResult<String> {
+    set_input_starred(authed, user_db, w_id, i_id, true).await
+}
+
+async fn unstar_input(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, i_id)): Path<(String, Uuid)>,
+) -> JsonResult<String> {
+    set_input_starred(authed, user_db, w_id, i_id, false).await
+}
+
+async fn set_input_starred(
+    authed: ApiAuthed,
+    user_db: UserDB,
+    w_id: String,
+    i_id: Uuid,
+    is_starred: bool,
+) -> JsonResult<String> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let created_by = sqlx::query_scalar::<_, String>(
+        "SELECT created_by FROM input WHERE id = $1 and workspace_id = $2 FOR UPDATE",
+    )
+    .bind(&i_id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let created_by = not_found_if_none(created_by, "Input", i_id.to_string())?;
+
+    // starring pins the input for everyone who can see it, so only its creator can do it
+    if created_by != authed.username && !authed.is_admin {
+        return Err(Error::NotAuthorized(format!(
+            "Only {created_by} or an admin can star input {i_id}"
+        )));
+    }
+
+    sqlx::query("UPDATE input SET is_starred = $1 WHERE id = $2 and workspace_id = $3")
+        .bind(is_starred)
+        .bind(&i_id)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(i_id.to_string()))
+}
+
+const INPUT_ARGS_HASH_BACKFILL_BATCH: i64 = 1000;
+
+/// Hashes the args of the inputs saved before `args_hash` existed, one batch per call so that
+/// each monitor tick only holds row locks on a bounded number of inputs.
+pub async fn backfill_input_args_hash(db: &DB) {
+    // jsonb text output is normalized (key order and whitespace), so equal args hash the same
+    let backfilled = sqlx::query(
+        "UPDATE input SET args_hash = md5(args::text), last_used_at = coalesce(last_used_at, created_at) \
+        WHERE id IN (SELECT id FROM input WHERE args_hash IS NULL LIMIT $1 FOR UPDATE SKIP LOCKED)",
+    )
+    .bind(INPUT_ARGS_HASH_BACKFILL_BATCH)
+    .execute(db)
+    .await;
+
+    match backfilled {
+        Ok(r) if r.rows_affected() > 0 => {
+            tracing::info!("backfilled the args hash of {} inputs", r.rows_affected())
+        }
+        Ok(_) => (),
+        Err(e) => tracing::error!("Error backfilling the args hash of inputs: {e:#}"),
+    }
+}
+
+type SavedInputArgs = HashMap<String, Box<RawValue>>;
+
+/// Named set of args saved for the script or flow at `path`
+#[derive(Serialize, FromRow)]
+struct SavedInput {
+    id: Uuid,
+    path: String,
+    name: String,
+    args: sqlx::types::Json<SavedInputArgs>,
+    created_by: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct NewSavedInput {
+    path: String,
+    name: String,
+    args: SavedInputArgs,
+}
+
+#[derive(Deserialize)]
+struct EditSavedInput {
+    name: Option<String>,
+    args: Option<SavedInputArgs>,
+}
+
+/// Saved inputs are only visible to, and editable by, the users who can read the script or flow
+/// they were saved for
+async fn require_path_readable(
+    authed: &ApiAuthed,
+    user_db: UserDB,
+    w_id: &str,
+    path: &str,
+) -> Result<()> {
+    let mut tx = user_db.begin(authed).await?;
+    let readable = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false) \
+        OR EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+    )
+    .bind(path)
+    .bind(w_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    if !readable {
+        return Err(Error::NotFound(format!("Script or flow {path} not found")));
+    }
+    Ok(())
+}
+
+async fn fetch_saved_input(
+    authed: &ApiAuthed,
+    db: &DB,
+    user_db: UserDB,
+    w_id: &str,
+    id: Uuid,
+) -> Result<SavedInput> {
+    let saved_input = sqlx::query_as::<_, SavedInput>(
+        "SELECT id, path, name, args, created_by, created_at FROM saved_input \
+        WHERE id = $1 AND workspace_id = $2",
+    )
+    .bind(id)
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?;
+    let saved_input = not_found_if_none(saved_input, "Saved input", id.to_string())?;
+    require_path_readable(authed, user_db, w_id, &saved_input.path)
+        .await
+        .map_err(|_| Error::NotFound(format!("Saved input {id} not found")))?;
+    Ok(saved_input)
+}
+
+/// Args of the saved input `id`, which must have been saved for the script or flow at `path`
+pub async fn get_saved_input_args(
+    authed: &ApiAuthed,
+    db: &DB,
+    user_db: UserDB,
+    w_id: &str,
+    id: Uuid,
+    path: &str,
+) -> Result<SavedInputArgs> {
+    let saved_input = fetch_saved_input(authed, db, user_db, w_id, id).await?;
+    if saved_input.path != path {
+        return Err(Error::BadRequest(format!(
+            "Saved input {id} was saved for {}, not {path}",
+            saved_input.path
+        )));
+    }
+    Ok(saved_input.args.0)
+}
+
+async fn create_saved_input(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(ns): Json<NewSavedInput>,
+) -> Result<(StatusCode, String)> {
+    require_path_readable(&authed, user_db, &w_id, &ns.path).await?;
+
+    let mut tx = db.begin().await?;
+    let id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO saved_input (id, workspace_id, path, name, args, created_by) \
+        VALUES ($1, $2, $3, $4, $5, $6) \
+        ON CONFLICT (workspace_id, path, name) DO NOTHING RETURNING id",
+    )
+    .bind(Uuid::new_v4())
+    .bind(&w_id)
+    .bind(&ns.path)
+    .bind(&ns.name)
+    .bind(sqlx::types::Json(&ns.args))
+    .bind(&authed.username)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| {
+        Error::BadRequest(format!(
+            "An input named {} is already saved for {}",
+            ns.name, ns.path
+        ))
+    })?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "inputs.saved.create",
+        ActionKind::Create,
+        &w_id,
+        Some(ns.path.as_str()),
+        Some([("id", id.to_string().as_str()), ("name", ns.name.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, id.to_string()))
+}
+
+async fn list_saved_inputs_by_path(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<Vec<SavedInput>> {
+    let path = path.to_path();
+    require_path_readable(&authed, user_db, &w_id, path).await?;
+
+    let saved_inputs = sqlx::query_as::<_, SavedInput>(
+        "SELECT id, path, name, args, created_by, created_at FROM saved_input \
+        WHERE workspace_id = $1 AND path = $2 ORDER BY name",
+    )
+    .bind(&w_id)
+    .bind(path)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(saved_inputs))
+}
+
+async fn get_saved_input(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> JsonResult<SavedInput> {
+    Ok(Json(
+        fetch_saved_input(&authed, &db, user_db, &w_id, id).await?,
+    ))
+}
+
+async fn update_saved_input(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+    Json(es): Json<EditSavedInput>,
+) -> Result<String> {
+    let saved_input = fetch_saved_input(&authed, &db, user_db, &w_id, id).await?;
+
+    let mut tx = db.begin().await?;
+    let updated = sqlx::query_scalar::<_, Uuid>(
+        "UPDATE saved_input SET name = coalesce($1, name), args = coalesce($2, args) \
+        WHERE id = $3 AND workspace_id = $4 \
+        AND NOT EXISTS(SELECT 1 FROM saved_input other WHERE other.workspace_id = $4 \
+            AND other.path = saved_input.path AND other.name = $1 AND other.id != $3) \
+        RETURNING id",
+    )
+    .bind(&es.name)
+    .bind(es.args.as_ref().map(sqlx::types::Json))
+    .bind(id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    if updated.is_none() {
+        return Err(Error::BadRequest(format!(
+            "An input named {} is already saved for {}",
+            es.name.unwrap_or_default(),
+            saved_input.path
+        )));
+    }
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "inputs.saved.update",
+        ActionKind::Update,
+        &w_id,
+        Some(saved_input.path.as_str()),
+        Some([("id", id.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Saved input {id} updated"))
+}
+
+async fn delete_saved_input(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> Result<String> {
+    let saved_input = fetch_saved_input(&authed, &db, user_db, &w_id, id).await?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM saved_input WHERE id = $1 AND workspace_id = $2")
+        .bind(id)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "inputs.saved.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(saved_input.path.as_str()),
+        Some([("id", id.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Saved input {id} deleted"))
+}
@@ -0,0 +1,557 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Archival of old completed jobs to the instance object store.
+//!
+//! Completed jobs older than the `job_archive_after_days` global setting are exported to parquet
+//! files partitioned by workspace and day of creation. Their row in `completed_job` is then
+//! stripped down to a stub flagged with `archived` that refers to its file, and the full record is
+//! read back from the object store when the job is fetched. The labels and the offloaded result
+//! of a job are kept on its stub so that they can still be listed, edited and deleted.
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+use windmill_common::error::{Error, Result};
+
+use crate::db::DB;
+
+#[cfg(feature = "parquet")]
+use {
+    bytes::Bytes,
+    datafusion::arrow::{
+        array::{Array, ArrayRef, BooleanArray, StringArray, TimestampMicrosecondArray},
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    datafusion::parquet::{
+        arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+        basic::{Compression, ZstdLevel},
+        file::properties::WriterProperties,
+    },
+    futures::TryStreamExt,
+    object_store::ObjectStore,
+    quick_cache::sync::Cache,
+    std::collections::BTreeMap,
+    windmill_common::{
+        error::to_anyhow,
+        global_settings::{load_value_from_global_settings, JOB_ARCHIVE_AFTER_DAYS_SETTING},
+        s3_helpers::OBJECT_STORE_CACHE_SETTINGS,
+    },
+};
+
+#[cfg(feature = "parquet")]
+const ARCHIVE_BATCH_SIZE: i64 = 1000;
+/// bounds the time spent archiving on each tick of the monitor
+#[cfg(feature = "parquet")]
+const MAX_ARCHIVE_BATCHES_PER_RUN: usize = 10;
+
+#[cfg(feature = "parquet")]
+lazy_static::lazy_static! {
+    /// recently read archived records, a job page fetches the job, its result and its logs
+    static ref ARCHIVED_JOBS: Cache<Uuid, Arc<String>> = Cache::new(100);
+}
+
+#[cfg(feature = "parquet")]
+fn workspace_archives(w_id: &str) -> String {
+    format!("archived_jobs/{w_id}")
+}
+
+#[cfg(feature = "parquet")]
+fn archive_partition(w_id: &str, day: chrono::NaiveDate) -> String {
+    format!("{}/{}", workspace_archives(w_id), day.format("%Y-%m-%d"))
+}
+
+/// Prefixes `sql` with a CTE shadowing `relation` by the archived record bound to `$param`, so
+/// that the queries on live jobs can run unchanged on an archived one
+pub(crate) fn with_archived_record(relation: &str, param: usize, sql: &str) -> String {
+    format!(
+        "WITH {relation} AS (SELECT * FROM jsonb_populate_record(NULL::{relation}, ${param}::jsonb)) {sql}"
+    )
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ArchivedJobLogs {
+    pub logs: Option<String>,
+    pub log_offset: Option<i32>,
+    pub log_file_index: Option<Vec<String>>,
+}
+
+#[cfg(feature = "parquet")]
+#[derive(sqlx::FromRow)]
+struct JobToArchive {
+    id: Uuid,
+    workspace_id: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    success: bool,
+    script_path: Option<String>,
+    job: String,
+}
+
+#[cfg(feature = "parquet")]
+fn archive_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("workspace_id", DataType::Utf8, false),
+        Field::new(
+            "created_at",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("success", DataType::Boolean, false),
+        Field::new("script_path", DataType::Utf8, true),
+        // the full `completed_job_view` record as JSON
+        Field::new("job", DataType::Utf8, false),
+    ]))
+}
+
+#[cfg(feature = "parquet")]
+fn write_archive(jobs: &[&JobToArchive]) -> Result<Vec<u8>> {
+    let schema = archive_schema();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            jobs.iter().map(|j| j.id.to_string()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            jobs.iter().map(|j| j.workspace_id.as_str()),
+        )),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(
+                jobs.iter().map(|j| j.created_at.timestamp_micros()),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(BooleanArray::from_iter(
+            jobs.iter().map(|j| Some(j.success)),
+        )),
+        Arc::new(StringArray::from_iter(
+            jobs.iter().map(|j| j.script_path.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            jobs.iter().map(|j| j.job.as_str()),
+        )),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns).map_err(to_anyhow)?;
+
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut buf = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buf, schema, Some(props)).map_err(to_anyhow)?;
+    writer.write(&batch).map_err(to_anyhow)?;
+    writer.close().map_err(to_anyhow)?;
+    Ok(buf)
+}
+
+#[cfg(feature = "parquet")]
+fn find_in_archive(file: Bytes, id: &str) -> Result<Option<String>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|builder| builder.build())
+        .map_err(to_anyhow)?;
+    for batch in reader {
+        let batch = batch.map_err(to_anyhow)?;
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| Error::InternalErr(format!("Archive has no {name} column")))
+        };
+        let (ids, jobs) = (column("id")?, column("job")?);
+        if let Some(i) = (0..ids.len()).find(|&i| ids.value(i) == id) {
+            return Ok(Some(jobs.value(i).to_string()));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "parquet")]
+async fn archive_batch(db: &DB, os: &Arc<dyn ObjectStore>, after_days: i32) -> Result<usize> {
+    let mut tx = db.begin().await?;
+
+    // jobs whose parent flow is still running may still be read by the flow
+    let jobs = sqlx::query_as::<_, JobToArchive>(
+        "SELECT v.id, v.workspace_id, v.created_at, v.success, v.script_path, to_jsonb(v)::text AS job
+        FROM completed_job_view v WHERE v.id = ANY(ARRAY(
+            SELECT c.id FROM completed_job c
+            WHERE c.archived = false AND c.created_at < now() - make_interval(days => $1)
+                AND NOT EXISTS (SELECT 1 FROM queue q WHERE q.id = c.parent_job)
+            ORDER BY c.created_at LIMIT $2
+            FOR UPDATE OF c SKIP LOCKED
+        ))",
+    )
+    .bind(after_days)
+    .bind(ARCHIVE_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if jobs.is_empty() {
+        return Ok(0);
+    }
+
+    let mut partitions: BTreeMap<(&str, chrono::NaiveDate), Vec<&JobToArchive>> = BTreeMap::new();
+    for job in jobs.iter() {
+        partitions
+            .entry((job.workspace_id.as_str(), job.created_at.date_naive()))
+            .or_default()
+            .push(job);
+    }
+
+    let (mut ids, mut files) = (
+        Vec::with_capacity(jobs.len()),
+        Vec::with_capacity(jobs.len()),
+    );
+    for ((w_id, day), jobs) in partitions {
+        let path = format!(
+            "{}/{}.parquet",
+            archive_partition(w_id, day),
+            Uuid::new_v4()
+        );
+        let file = write_archive(&jobs)?;
+        os.put(&object_store::path::Path::from(path.as_str()), file.into())
+            .await
+            .map_err(|e| {
+                Error::InternalErr(format!("Failed to put {path} to object store: {e}"))
+            })?;
+        for job in jobs {
+            ids.push(job.id);
+            files.push(path.clone());
+        }
+    }
+
+    // the labels and the offloaded result are kept so that the jobs can still be filtered by
+    // label and their offloaded result deleted with them
+    sqlx::query(
+        "UPDATE completed_job c SET archived = true, archive_file = f.file, args = NULL,
+            result = CASE WHEN jsonb_typeof(c.result) = 'object' THEN jsonb_strip_nulls(
+                jsonb_build_object(
+                    'wm_labels', c.result->'wm_labels',
+                    '__windmill_large_result', c.result->'__windmill_large_result'
+                )
+            ) END,
+            logs = NULL, flow_status = NULL, raw_code = NULL, raw_lock = NULL, raw_flow = NULL
+        FROM unnest($1::uuid[], $2::text[]) f(id, file)
+        WHERE c.id = f.id",
+    )
+    .bind(&ids)
+    .bind(&files)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM job_logs WHERE job_id = ANY($1)")
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(ids.len())
+}
+
+/// Archives the completed jobs older than the `job_archive_after_days` global setting. Called
+/// periodically by the monitor, does nothing if the setting is not set or no object store is
+/// configured.
+#[cfg(feature = "parquet")]
+pub async fn archive_completed_jobs(db: &DB) {
+    let after_days = match load_value_from_global_settings(db, JOB_ARCHIVE_AFTER_DAYS_SETTING).await
+    {
+        Ok(Some(value)) => match serde_json::from_value::<i32>(value) {
+            Ok(days) if days > 0 => days,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::error!("Invalid job archive setting: {e:#}");
+                return;
+            }
+        },
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Could not load job archive setting: {e:#}");
+            return;
+        }
+    };
+
+    let Some(os) = OBJECT_STORE_CACHE_SETTINGS.read().await.clone() else {
+        tracing::warn!("Completed jobs are not archived as no object store is configured");
+        return;
+    };
+
+    for _ in 0..MAX_ARCHIVE_BATCHES_PER_RUN {
+        match archive_batch(db, &os, after_days).await {
+            Ok(archived) => {
+                if archived > 0 {
+                    tracing::info!("archived {archived} completed jobs to the object store");
+                }
+                if (archived as i64) < ARCHIVE_BATCH_SIZE {
+                    break;
+                }
+            }
+            Err(e) => {
+                tracing::error!("Error archiving completed jobs: {e:#}");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+async fn object_store() -> Result<Arc<dyn ObjectStore>> {
+    OBJECT_STORE_CACHE_SETTINGS
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| {
+            Error::BadConfig(
+                "Object store is required to read archived jobs and is not configured".to_string(),
+            )
+        })
+}
+
+#[cfg(feature = "parquet")]
+async fn read_archived_job(id: Uuid, archive_file: &str) -> Result<String> {
+    let os = object_store().await?;
+    let bytes = os
+        .get(&object_store::path::Path::from(archive_file))
+        .await
+        .map_err(to_anyhow)?
+        .bytes()
+        .await
+        .map_err(to_anyhow)?;
+    find_in_archive(bytes, &id.to_string())?.ok_or_else(|| {
+        Error::NotFound(format!(
+            "Archived record of job {id} not found in {archive_file}"
+        ))
+    })
+}
+
+#[cfg(not(feature = "parquet"))]
+async fn read_archived_job(_id: Uuid, _archive_file: &str) -> Result<String> {
+    Err(Error::BadConfig(
+        "Reading archived jobs requires the object store support (parquet feature)".to_string(),
+    ))
+}
+
+/// The labels of an archived job are edited on its stub, they replace the ones of its archived
+/// record
+fn with_current_labels(record: &str, labels: Option<&serde_json::Value>) -> Result<String> {
+    let Some(labels) = labels else {
+        return Ok(record.to_string());
+    };
+    let mut record = serde_json::from_str::<serde_json::Value>(record)?;
+    if let Some(result) = record.get_mut("result").and_then(|r| r.as_object_mut()) {
+        result.insert("wm_labels".to_string(), labels.clone());
+    }
+    Ok(serde_json::to_string(&record)?)
+}
+
+/// Full `completed_job_view` record of a job as JSON if it is archived, read back from the
+/// object store
+pub(crate) async fn fetch_archived_job(
+    db: &DB,
+    w_id: &str,
+    id: Uuid,
+) -> Result<Option<Arc<String>>> {
+    let stub = sqlx::query_as::<_, (Option<String>, Option<sqlx::types::Json<serde_json::Value>>)>(
+        "SELECT archive_file, result->'wm_labels' FROM completed_job
+        WHERE id = $1 AND workspace_id = $2 AND archived",
+    )
+    .bind(id)
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?;
+
+    let Some((archive_file, labels)) = stub else {
+        return Ok(None);
+    };
+    let labels = labels.map(|labels| labels.0);
+
+    #[cfg(feature = "parquet")]
+    if let Some(job) = ARCHIVED_JOBS.get(&id) {
+        return Ok(Some(Arc::new(with_current_labels(&job, labels.as_ref())?)));
+    }
+
+    let archive_file = archive_file.ok_or_else(|| {
+        Error::InternalErr(format!(
+            "Archived job {id} does not refer to its archive file"
+        ))
+    })?;
+    let job = Arc::new(read_archived_job(id, &archive_file).await?);
+    #[cfg(feature = "parquet")]
+    ARCHIVED_JOBS.insert(id, job.clone());
+    Ok(Some(Arc::new(with_current_labels(&job, labels.as_ref())?)))
+}
+
+/// Deletes the archive files no job refers to anymore once the jobs archived in them were
+/// deleted, errors are only logged
+#[cfg(feature = "parquet")]
+pub async fn delete_unreferenced_archives(db: &DB, mut files: Vec<String>) {
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        return;
+    }
+
+    let unreferenced = sqlx::query_scalar::<_, String>(
+        "SELECT f FROM unnest($1::text[]) f
+        WHERE NOT EXISTS (SELECT 1 FROM completed_job WHERE archive_file = f)",
+    )
+    .bind(&files)
+    .fetch_all(db)
+    .await;
+    let unreferenced = match unreferenced {
+        Ok(unreferenced) => unreferenced,
+        Err(e) => {
+            tracing::error!("Could not find the archive files to delete: {e:#}");
+            return;
+        }
+    };
+
+    let os = match object_store().await {
+        Ok(os) => os,
+        Err(e) => {
+            tracing::warn!(
+                "Could not delete {} archive files: {e:#}",
+                unreferenced.len()
+            );
+            return;
+        }
+    };
+    for file in unreferenced {
+        if let Err(e) = os
+            .delete(&object_store::path::Path::from(file.as_str()))
+            .await
+        {
+            tracing::error!("Failed to delete archive file {file} from object store: {e}");
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+pub async fn delete_unreferenced_archives(_db: &DB, _files: Vec<String>) {}
+
+/// Deletes all the archive files of a purged workspace, errors are only logged
+#[cfg(feature = "parquet")]
+pub(crate) async fn delete_workspace_archives(w_id: &str) {
+    let Ok(os) = object_store().await else {
+        return;
+    };
+    let prefix = object_store::path::Path::from(workspace_archives(w_id));
+    let files = match os.list(Some(&prefix)).try_collect::<Vec<_>>().await {
+        Ok(files) => files,
+        Err(e) => {
+            tracing::error!("Could not list the archive files of workspace {w_id}: {e}");
+            return;
+        }
+    };
+    for file in files {
+        if let Err(e) = os.delete(&file.location).await {
+            tracing::error!(
+                "Failed to delete archive file {} from object store: {e}",
+                file.location
+            );
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+pub(crate) async fn delete_workspace_archives(_w_id: &str) {}
+
+/// Rehydrates the row of an archived job from its archived record. Returns false if the job is
+/// not archived.
+pub(crate) async fn unarchive_job(
+    tx: &mut Transaction<'_, Postgres>,
+    db: &DB,
+    w_id: &str,
+    id: Uuid,
+) -> Result<bool> {
+    let Some(job) = fetch_archived_job(db, w_id, id).await? else {
+        return Ok(false);
+    };
+
+    let restored = sqlx::query(
+        "UPDATE completed_job c SET archived = false, archive_file = NULL, args = a.args,
+            result = a.result,
+            logs = a.logs, flow_status = a.flow_status, raw_code = a.raw_code,
+            raw_lock = a.raw_lock, raw_flow = a.raw_flow
+        FROM jsonb_populate_record(NULL::completed_job, $3::jsonb) a
+        WHERE c.id = $1 AND c.workspace_id = $2 AND c.archived",
+    )
+    .bind(id)
+    .bind(w_id)
+    .bind(job.as_str())
+    .execute(&mut **tx)
+    .await?
+    .rows_affected()
+        > 0;
+
+    // the inline logs are restored on the job, only the references to the log files are left
+    sqlx::query(
+        "INSERT INTO job_logs (job_id, workspace_id, log_offset, log_file_index)
+        SELECT $1, $2, coalesce(a.log_offset, 0), a.log_file_index
+        FROM jsonb_populate_record(NULL::completed_job_view, $3::jsonb) a
+        WHERE $4 AND a.log_file_index IS NOT NULL
+        ON CONFLICT (job_id) DO NOTHING",
+    )
+    .bind(id)
+    .bind(w_id)
+    .bind(job.as_str())
+    .bind(restored)
+    .execute(&mut **tx)
+    .await?;
+
+    #[cfg(feature = "parquet")]
+    ARCHIVED_JOBS.remove(&id);
+
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archived_record_takes_the_labels_of_the_stub() {
+        let record = r#"{"id": "a", "result": {"x": 1, "wm_labels": ["old"]}}"#;
+        let labels = serde_json::json!(["new", "other"]);
+
+        let record = with_current_labels(record, Some(&labels)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&record).unwrap(),
+            serde_json::json!({"id": "a", "result": {"x": 1, "wm_labels": ["new", "other"]}})
+        );
+
+        let unlabeled = r#"{"id": "a", "result": [1, 2]}"#;
+        assert_eq!(
+            with_current_labels(unlabeled, Some(&labels)).unwrap(),
+            r#"{"id":"a","result":[1,2]}"#
+        );
+        assert_eq!(with_current_labels(unlabeled, None).unwrap(), unlabeled);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn archived_job_is_found_back_in_its_parquet_file() {
+        let jobs = (0..3)
+            .map(|i| JobToArchive {
+                id: Uuid::new_v4(),
+                workspace_id: "test-workspace".to_string(),
+                created_at: chrono::Utc::now(),
+                success: i % 2 == 0,
+                script_path: (i > 0).then(|| format!("f/test/script_{i}")),
+                job: format!("{{\"index\": {i}}}"),
+            })
+            .collect::<Vec<_>>();
+
+        let file = Bytes::from(write_archive(&jobs.iter().collect::<Vec<_>>()).unwrap());
+
+        let found = find_in_archive(file.clone(), &jobs[1].id.to_string()).unwrap();
+        assert_eq!(found.as_deref(), Some("{\"index\": 1}"));
+        assert_eq!(
+            find_in_archive(file, &Uuid::new_v4().to_string()).unwrap(),
+            None
+        );
+    }
+}
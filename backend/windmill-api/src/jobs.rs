@@ -39,10 +39,13 @@ use crate::db::ApiAuthed;
 use crate::users::get_scope_tags;
 use crate::utils::content_plain;
 use crate::{
-    args::{DecodeQueries, WebhookArgs},
+    args::{validate_args_against_schema, DecodeQueries, WebhookArgs},
+    auth::job_id_of_token,
     db::DB,
-    users::{check_scopes, require_owner_of_path, OptAuthed},
+    job_archive,
+    users::{check_scopes, require_owner_of_path, OptAuthed, Tokened},
     utils::require_super_admin,
+    workers::is_tag_served,
 };
 use anyhow::Context;
 use axum::{
@@ -68,9 +71,13 @@ use windmill_common::{
     cache,
     db::UserDB,
     error::{self, to_anyhow, Error},
-    flow_status::{Approval, FlowStatus, FlowStatusModule},
+    flow_status::{Approval, ApprovalConditions, FlowStatus, FlowStatusModule},
     flows::{add_virtual_items_if_necessary, resolve_maybe_value, FlowValue},
-    jobs::{script_path_to_payload, CompletedJob, JobKind, JobPayload, QueuedJob, RawCode},
+    jobs::{
+        get_redact_args, redact_args, redact_completed_job_args, script_path_to_payload,
+        CompletedJob, JobKind, JobPayload, QueuedJob, RawCode, MAX_JOB_CHAIN_DEPTH,
+    },
+    large_results::{delete_large_results, large_result_stream, read_large_result},
     oauth2::HmacSha256,
     scripts::{ScriptHash, ScriptLang},
     users::username_to_permissioned_as,
@@ -78,6 +85,7 @@ use windmill_common::{
         not_found_if_none, now_from_db, paginate, paginate_without_limits, require_admin,
         Pagination, StripPath,
     },
+    workspaces::WorkspaceJobLimits,
 };
 
 #[cfg(all(feature = "enterprise", feature = "parquet"))]
@@ -88,6 +96,7 @@ use windmill_common::{METRICS_DEBUG_ENABLED, METRICS_ENABLED};
 use windmill_common::{get_latest_deployed_hash_for_path, BASE_URL};
 use windmill_queue::{
     cancel_job, get_queued_job, get_result_and_success_by_id_from_flow, job_is_complete, push,
+    schedule::{get_schedule_opt, push_scheduled_job},
     PushArgs, PushArgsOwned, PushIsolationLevel,
 };
 
@@ -131,6 +140,9 @@ pub fn workspaced_service() -> Router {
     let ce_headers =
         ServiceBuilder::new().layer(axum::middleware::from_fn(add_webhook_allowed_origin));
 
+    let run_rate_limit =
+        ServiceBuilder::new().layer(axum::middleware::from_fn(crate::rate_limit::limit_runs));
+
     let api_list_jobs_query_duration = setup_list_jobs_debug_metrics();
 
     Router::new()
@@ -139,7 +151,8 @@ pub fn workspaced_service() -> Router {
             post(run_flow_by_path)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route(
             "/run/workflow_as_code/:job_id/:entrypoint",
@@ -156,12 +169,17 @@ pub fn workspaced_service() -> Router {
             "/restart/f/:job_id/from/:step_id/:branch_of_iteration_n",
             post(restart_flow).head(|| async { "" }).layer(cors.clone()),
         )
+        .route(
+            "/flow/rerun_failed_iterations/:job_id",
+            post(rerun_failed_iterations),
+        )
         .route(
             "/run/p/*script_path",
             post(run_script_by_path)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route(
             "/run_wait_result/p/*script_path",
@@ -169,14 +187,16 @@ pub fn workspaced_service() -> Router {
                 .get(run_wait_result_job_by_path_get)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route(
             "/run_wait_result/h/:hash",
             post(run_wait_result_script_by_hash)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route(
             "/run_wait_result/f/*script_path",
@@ -184,14 +204,16 @@ pub fn workspaced_service() -> Router {
                 .get(run_wait_result_flow_by_path_get)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route(
             "/run/h/:hash",
             post(run_job_by_hash)
                 .head(|| async { "" })
                 .layer(cors.clone())
-                .layer(ce_headers.clone()),
+                .layer(ce_headers.clone())
+                .layer(run_rate_limit.clone()),
         )
         .route("/run/preview", post(run_preview_script))
         .route(
@@ -206,10 +228,23 @@ pub fn workspaced_service() -> Router {
         )
         .route("/queue/list", get(list_queue_jobs))
         .route("/queue/count", get(count_queue_jobs))
+        .route("/queue/oldest", get(oldest_queued_jobs))
+        .route("/queue/list_suspended", get(list_suspended_flows))
         .route("/queue/list_filtered_uuids", get(list_filtered_uuids))
         .route("/queue/cancel_selection", post(cancel_selection))
+        .route("/cancel_by_schedule_path", post(cancel_by_schedule_path))
         .route("/completed/count", get(count_completed_jobs))
         .route("/completed/count_jobs", get(count_completed_jobs_detail))
+        .route(
+            "/completed/aggregated_duration",
+            get(aggregated_completed_jobs_duration),
+        )
+        .route("/completed/:id/children", get(list_job_children))
+        .route("/completed/diff_results", get(diff_completed_job_results))
+        .route(
+            "/completed/failed_last_per_script",
+            get(list_last_failed_jobs_per_script),
+        )
         .route(
             "/completed/list",
             get(list_completed_jobs).layer(cors.clone()),
@@ -230,6 +265,10 @@ pub fn workspaced_service() -> Router {
             "/completed/delete/:id",
             post(delete_completed_job).layer(cors.clone()),
         )
+        .route("/completed/unarchive/:id", post(unarchive_completed_job))
+        .route("/completed/:id/archive", post(archive_job_result))
+        .route("/completed/labels", post(update_completed_jobs_labels))
+        .route("/completed/labels/:id", post(update_completed_job_labels))
         .route(
             "/flow/resume/:id",
             post(resume_suspended_flow_as_owner).layer(cors.clone()),
@@ -238,12 +277,20 @@ pub fn workspaced_service() -> Router {
             "/job_signature/:job_id/:resume_id",
             get(create_job_signature).layer(cors.clone()),
         )
+        .route(
+            "/flow/user_states/:job_id",
+            get(list_flow_user_states).layer(cors.clone()),
+        )
         .route(
             "/flow/user_states/:job_id/:key",
             get(get_flow_user_state)
                 .post(set_flow_user_state)
                 .layer(cors.clone()),
         )
+        .route(
+            "/flow/user_states/:job_id/:key/update",
+            post(update_flow_user_state).layer(cors.clone()),
+        )
         .route(
             "/resume_urls/:job_id/:resume_id",
             get(get_resume_urls).layer(cors.clone()),
@@ -280,9 +327,11 @@ pub fn workspace_unauthed_service() -> Router {
         )
         .route("/get_root_job_id/:id", get(get_root_job))
         .route("/get/:id", get(get_job))
+        .route("/queue/estimate/:id", get(get_queue_estimate))
         .route("/get_logs/:id", get(get_job_logs))
         .route("/get_args/:id", get(get_args))
         .route("/get_flow_debug_info/:id", get(get_flow_job_debug_info))
+        .route("/get_tree/:id", get(get_job_tree))
         .route("/completed/get/:id", get(get_completed_job))
         .route("/completed/get_result/:id", get(get_completed_job_result))
         .route(
@@ -651,6 +700,9 @@ async fn get_job(
     Path((w_id, id)): Path<(String, Uuid)>,
     Query(GetJobQuery { no_logs }): Query<GetJobQuery>,
 ) -> error::Result<Response> {
+    if let Some(authed) = opt_authed.as_ref() {
+        check_scopes(authed, || format!("jobs:listjobs"))?;
+    }
     let tags = opt_authed
         .as_ref()
         .map(|authed| get_scope_tags(authed))
@@ -665,18 +717,317 @@ async fn get_job(
     }
     let mut job = get.fetch(&db, id, &w_id).await?;
     job.fetch_outstanding_wait_time(&db).await?;
+    job.fetch_retry_count(&db).await?;
+    job.fetch_follow_up_job_id(&db).await?;
+    job.fetch_queue_estimate(&db).await?;
 
     log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
 
     Ok(Json(job).into_response())
 }
 
+const QUEUE_THROUGHPUT_WINDOW_MINS: i32 = 10;
+const QUEUE_TAG_STATS_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EstimateConfidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct QueueEstimate {
+    /// 1 when the job is the next one to be pulled for its tag
+    pub position: i64,
+    pub jobs_ahead: i64,
+    /// jobs of the same tag started per minute over the last 10 minutes
+    pub recent_throughput_per_min: f64,
+    pub workers: i64,
+    pub estimated_start: Option<chrono::DateTime<chrono::Utc>>,
+    pub confidence: EstimateConfidence,
+}
+
+#[derive(Clone, Copy)]
+struct QueueTagStats {
+    started_in_window: i64,
+    workers: i64,
+    fetched_at: std::time::Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref QUEUE_TAG_STATS: Cache<String, QueueTagStats> = Cache::new(1000);
+}
+
+async fn queue_tag_stats(db: &DB, tag: &str) -> Result<QueueTagStats, sqlx::Error> {
+    if let Some(stats) = QUEUE_TAG_STATS.get(tag) {
+        if stats.fetched_at.elapsed() < QUEUE_TAG_STATS_TTL {
+            return Ok(stats);
+        }
+    }
+    let (started_in_window, workers) = tokio::try_join!(
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM completed_job
+            WHERE tag = $1 AND started_at > now() - make_interval(mins => $2)",
+        )
+        .bind(tag)
+        .bind(QUEUE_THROUGHPUT_WINDOW_MINS)
+        .fetch_one(db),
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM worker_ping
+            WHERE ping_at > now() - interval '60 seconds' AND $1 = ANY(custom_tags)",
+        )
+        .bind(tag)
+        .fetch_one(db),
+    )?;
+    let stats = QueueTagStats { started_in_window, workers, fetched_at: std::time::Instant::now() };
+    QUEUE_TAG_STATS.insert(tag.to_string(), stats);
+    Ok(stats)
+}
+
+/// Jobs are pulled by tag, highest priority first then by scheduled time, so the jobs ahead are
+/// counted across all workspaces and drained at the recent rate of the tag
+async fn estimate_queue_start(
+    db: &DB,
+    id: Uuid,
+    tag: &str,
+    priority: Option<i16>,
+    scheduled_for: chrono::DateTime<chrono::Utc>,
+) -> Result<QueueEstimate, sqlx::Error> {
+    let jobs_ahead = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM queue
+        WHERE running = false AND tag = $1 AND id != $2
+            AND scheduled_for <= GREATEST(now(), $4)
+            AND (
+                (priority IS NOT NULL AND ($3::smallint IS NULL OR priority > $3))
+                OR (priority IS NOT DISTINCT FROM $3 AND scheduled_for < $4)
+            )",
+    )
+    .bind(tag)
+    .bind(id)
+    .bind(priority)
+    .bind(scheduled_for)
+    .fetch_one(db)
+    .await?;
+    let stats = queue_tag_stats(db, tag).await?;
+    Ok(compute_queue_estimate(
+        jobs_ahead,
+        stats,
+        scheduled_for,
+        Utc::now(),
+    ))
+}
+
+fn compute_queue_estimate(
+    jobs_ahead: i64,
+    stats: QueueTagStats,
+    scheduled_for: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> QueueEstimate {
+    let recent_throughput_per_min =
+        stats.started_in_window as f64 / QUEUE_THROUGHPUT_WINDOW_MINS as f64;
+    let not_before = scheduled_for.max(now);
+
+    let (estimated_start, confidence) = if stats.workers == 0 {
+        // nothing is pulling this tag, the job only starts once a worker listens to it
+        (None, EstimateConfidence::Low)
+    } else if recent_throughput_per_min == 0.0 {
+        if jobs_ahead == 0 {
+            (Some(not_before), EstimateConfidence::Medium)
+        } else {
+            (None, EstimateConfidence::Low)
+        }
+    } else {
+        let wait_ms = (jobs_ahead as f64 / recent_throughput_per_min * 60_000.0) as i64;
+        let confidence = match stats.started_in_window {
+            n if n >= 50 => EstimateConfidence::High,
+            n if n >= 10 => EstimateConfidence::Medium,
+            _ => EstimateConfidence::Low,
+        };
+        (
+            Some(not_before.max(now + chrono::Duration::milliseconds(wait_ms))),
+            confidence,
+        )
+    };
+
+    QueueEstimate {
+        position: jobs_ahead + 1,
+        jobs_ahead,
+        recent_throughput_per_min,
+        workers: stats.workers,
+        estimated_start,
+        confidence,
+    }
+}
+
+async fn get_queue_estimate(
+    OptAuthed(opt_authed): OptAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> JsonResult<QueueEstimate> {
+    if let Some(authed) = opt_authed.as_ref() {
+        check_scopes(authed, || format!("jobs:listjobs"))?;
+    }
+    let job = sqlx::query_as::<_, (String, Option<i16>, chrono::DateTime<chrono::Utc>, bool)>(
+        "SELECT tag, priority, scheduled_for, running FROM queue WHERE id = $1 AND workspace_id = $2",
+    )
+    .bind(id)
+    .bind(&w_id)
+    .fetch_optional(&db)
+    .await?;
+    let (tag, priority, scheduled_for, running) =
+        not_found_if_none(job, "Queued job", id.to_string())?;
+    if running {
+        return Err(Error::BadRequest(format!("Job {id} is already running")));
+    }
+    Ok(Json(
+        estimate_queue_start(&db, id, &tag, priority, scheduled_for).await?,
+    ))
+}
+
+const JOB_TREE_DEFAULT_DEPTH: usize = 5;
+const JOB_TREE_MAX_NODES: usize = 500;
+
+#[derive(Deserialize)]
+struct GetJobTreeQuery {
+    depth: Option<usize>,
+    without_logs: Option<bool>,
+    without_args: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct JobTreeNode {
+    job: Job,
+    children: Vec<JobTreeNode>,
+}
+
+#[derive(Serialize)]
+struct JobTree {
+    #[serde(flatten)]
+    root: JobTreeNode,
+    /// more than JOB_TREE_MAX_NODES jobs were found, the remaining ones are not included
+    truncated: bool,
+}
+
+/// Direct children of a job: jobs having it as parent and, for queued flows, its leaf jobs
+async fn job_children_ids(db: &DB, w_id: &str, job: &Job) -> error::Result<Vec<Uuid>> {
+    let mut ids = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM (
+            SELECT id, created_at FROM queue WHERE workspace_id = $1 AND parent_job = $2
+            UNION ALL
+            SELECT id, created_at FROM completed_job WHERE workspace_id = $1 AND parent_job = $2
+        ) children ORDER BY created_at",
+    )
+    .bind(w_id)
+    .bind(job.id())
+    .fetch_all(db)
+    .await?;
+
+    if let Job::QueuedJob(job) = job {
+        let leaf_jobs: HashMap<String, JobResult> = job
+            .leaf_jobs
+            .clone()
+            .and_then(|x| serde_json::from_value(x).ok())
+            .unwrap_or_else(HashMap::new);
+        for leaf in leaf_jobs.into_values() {
+            let leaf_ids = match leaf {
+                JobResult::ListJob(jobs) => jobs,
+                JobResult::SingleJob(job) => vec![job],
+            };
+            for id in leaf_ids {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+    Ok(ids)
+}
+
+async fn get_job_tree(
+    OptAuthed(opt_authed): OptAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+    Query(query): Query<GetJobTreeQuery>,
+) -> error::JsonResult<JobTree> {
+    let tags = opt_authed
+        .as_ref()
+        .map(|authed| get_scope_tags(authed))
+        .flatten();
+
+    let mut get = GetQuery::new()
+        .with_auth(&opt_authed)
+        .with_in_tags(tags.as_ref());
+    if query.without_logs.unwrap_or(false) {
+        get = get.without_logs();
+    }
+    let without_args = query.without_args.unwrap_or(false);
+    let strip = |mut job: Job| {
+        if without_args {
+            match &mut job {
+                Job::QueuedJob(job) => job.args = None,
+                Job::CompletedJob(job) => job.args = None,
+            }
+        }
+        job
+    };
+
+    // jobs are fetched breadth first, a child always comes after its parent
+    let mut jobs = vec![strip(get.fetch(&db, id, &w_id).await?)];
+    let mut parents: Vec<Option<usize>> = vec![None];
+    let mut truncated = false;
+    let mut frontier = vec![0];
+    'levels: for _ in 0..query.depth.unwrap_or(JOB_TREE_DEFAULT_DEPTH) {
+        let mut next_frontier = vec![];
+        for parent in frontier {
+            for child_id in job_children_ids(&db, &w_id, &jobs[parent]).await? {
+                if jobs.len() >= JOB_TREE_MAX_NODES {
+                    truncated = true;
+                    break 'levels;
+                }
+                match get.fetch(&db, child_id, &w_id).await {
+                    Ok(child) => {
+                        jobs.push(strip(child));
+                        parents.push(Some(parent));
+                        next_frontier.push(jobs.len() - 1);
+                    }
+                    // filtered out by the scope tags
+                    Err(Error::NotFound(_)) => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if next_frontier.is_empty() {
+            break;
+        }
+        frontier = next_frontier;
+    }
+
+    let mut nodes = jobs
+        .into_iter()
+        .map(|job| Some(JobTreeNode { job, children: vec![] }))
+        .collect_vec();
+    for i in (1..nodes.len()).rev() {
+        let node = nodes[i].take().unwrap();
+        if let Some(parent) = parents[i].and_then(|p| nodes[p].as_mut()) {
+            parent.children.insert(0, node);
+        }
+    }
+    let root = nodes[0].take().unwrap();
+
+    log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
+
+    Ok(Json(JobTree { root, truncated }))
+}
+
 macro_rules! get_job_query {
     ("completed_job_view", $($opts:tt)*) => {
         get_job_query!(
             @impl "completed_job_view", ($($opts)*),
             "duration_ms, success, result, deleted, is_skipped, result->'wm_labels' as labels, \
-            CASE WHEN result is null or pg_column_size(result) < 90000 THEN result ELSE '\"WINDMILL_TOO_BIG\"'::jsonb END as result",
+            CASE WHEN result is null or pg_column_size(result) < 90000 THEN result ELSE '\"WINDMILL_TOO_BIG\"'::jsonb END as result, \
+            EXISTS(SELECT 1 FROM completed_job c WHERE c.id = completed_job_view.id AND c.archived) as archived",
         )
     };
     ("queue_view", $($opts:tt)*) => {
@@ -866,12 +1217,27 @@ impl<'a> GetQuery<'a> {
             with_code: self.with_code,
             with_flow: self.with_flow,
         );
-        let query = sqlx::query_as::<_, JobExtended<CompletedJob>>(query)
+        let mut cjob = sqlx::query_as::<_, JobExtended<CompletedJob>>(query)
             .bind(job_id)
             .bind(workspace_id)
-            .bind(self.with_in_tags);
+            .bind(self.with_in_tags)
+            .fetch_optional(db)
+            .await?;
 
-        let mut cjob = query.fetch_optional(db).await?;
+        if cjob.as_ref().is_some_and(|job| job.archived) {
+            if let Some(archived) =
+                job_archive::fetch_archived_job(db, workspace_id, job_id).await?
+            {
+                let query = job_archive::with_archived_record("completed_job_view", 4, query);
+                cjob = sqlx::query_as::<_, JobExtended<CompletedJob>>(&query)
+                    .bind(job_id)
+                    .bind(workspace_id)
+                    .bind(self.with_in_tags)
+                    .bind(archived.as_str())
+                    .fetch_optional(db)
+                    .await?;
+            }
+        }
 
         self.check_auth(cjob.as_ref().map(|job| job.created_by.as_str()))?;
         if let Some(job) = cjob.as_mut() {
@@ -1020,12 +1386,20 @@ async fn get_job_logs(
     .fetch_optional(&db)
     .await?;
 
-    if let Some(record) = record {
+    if let Some(mut record) = record {
         if opt_authed.is_none() && record.created_by != "anonymous" {
             return Err(Error::BadRequest(
                 "As a non logged in user, you can only see jobs ran by anonymous users".to_string(),
             ));
         }
+        if record.log_file_index.is_none() && record.logs.as_deref().map_or(true, str::is_empty) {
+            if let Some(archived) = job_archive::fetch_archived_job(&db, &w_id, id).await? {
+                let archived = serde_json::from_str::<job_archive::ArchivedJobLogs>(&archived)?;
+                record.logs = archived.logs;
+                record.log_offset = archived.log_offset.unwrap_or(0);
+                record.log_file_index = archived.log_file_index;
+            }
+        }
         let logs = record.logs.unwrap_or_default();
 
         log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
@@ -1143,7 +1517,16 @@ async fn get_args(
 
         log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
 
-        Ok(Json(record.args.map(|x| x.0).unwrap_or_default()))
+        // the args are only redacted in the job tables once it completes
+        let args = record.args.map(|x| x.0).unwrap_or_default();
+        let redact_paths = get_redact_args(&db, id, &w_id).await?;
+        if redact_paths.is_empty() {
+            return Ok(Json(args));
+        }
+        let mut args = serde_json::from_str::<HashMap<String, Box<RawValue>>>(args.get())
+            .map_err(|e| Error::InternalErr(format!("Invalid args of job {id}: {e:#}")))?;
+        redact_args(&mut args, &redact_paths);
+        Ok(Json(to_raw_value(&args)))
     }
 }
 
@@ -1192,6 +1575,10 @@ pub struct ListableCompletedJob {
     pub priority: Option<i16>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<serde_json::Value>,
+    /// archived jobs only keep the fields needed to list them, the rest is in the object store
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
 }
 
 #[derive(Deserialize, Clone, Default)]
@@ -1208,9 +1595,52 @@ pub struct RunJobQuery {
     pub timeout: Option<i32>,
     pub cache_ttl: Option<i32>,
     pub skip_preprocessor: Option<bool>,
+    /// validate the args against the schema before pushing, defaults to the strict_args setting
+    /// of the script or flow
+    pub strict_args: Option<bool>,
+    /// flow_version to run instead of the latest one, only for flows
+    pub version: Option<i64>,
+    /// script/ or flow/ prefixed path of a job pushed with the result of this one when it succeeds
+    pub on_success_path: Option<String>,
+    /// script/ or flow/ prefixed path of a job pushed with the error of this one when it fails
+    pub on_failure_path: Option<String>,
+    /// memory limit of the job process in MB, at most the max_mem_limit_mb of the workspace.
+    /// The steps of a flow inherit it
+    pub mem_limit_mb: Option<i32>,
+    /// comma separated args, or dot paths of nested fields, redacted from the job once it completes
+    pub redact_args: Option<String>,
+    /// runs the script with the args of this saved input instead of the request body
+    pub saved_input_id: Option<Uuid>,
+    /// returns the id of a job of the same runnable with the same args created in the last
+    /// `dedup_window_s` seconds instead of pushing a new one. Only supported by the run endpoints
+    /// that return the job id
+    pub dedup_window_s: Option<i32>,
+    /// job whose token the run was requested with, set by the run endpoints to find the depth of
+    /// the job in its chain
+    #[serde(skip)]
+    pub requesting_job_id: Option<Uuid>,
 }
 
 impl RunJobQuery {
+    /// Only looked up when the run has callbacks, it is only used to check the job chain depth
+    pub(crate) async fn set_requesting_job(&mut self, db: &DB, token: &str) {
+        if self.on_success_path.is_some() || self.on_failure_path.is_some() {
+            self.requesting_job_id = job_id_of_token(db, token).await;
+        }
+    }
+
+    /// The endpoints that wait for the result or run previews cannot return the id of an
+    /// identical job, they reject the parameter instead of ignoring it
+    pub(crate) fn reject_dedup_window(&self) -> error::Result<()> {
+        if self.dedup_window_s.is_some() {
+            return Err(Error::BadRequest(
+                "dedup_window_s is only supported by the run endpoints returning the job id"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     async fn get_scheduled_for<'c>(
         &self,
         db: &DB,
@@ -1480,15 +1910,19 @@ async fn list_queue_jobs(
     Ok(Json(jobs))
 }
 
-async fn cancel_jobs(
+/// Cancels the given jobs. Jobs belonging to a schedule are only cancelled if `schedule_path`
+/// is the path of that schedule.
+pub(crate) async fn cancel_jobs(
     jobs: Vec<Uuid>,
     db: &DB,
     username: &str,
     w_id: &str,
+    schedule_path: Option<&str>,
+    reason: Option<String>,
 ) -> error::JsonResult<Vec<Uuid>> {
     let mut uuids = vec![];
     let mut tx = db.begin().await?;
-    let trivial_jobs =  sqlx::query!("INSERT INTO completed_job AS cj
+    let trivial_jobs =  sqlx::query_scalar!("INSERT INTO completed_job AS cj
                    ( workspace_id
                    , id
                    , parent_job
@@ -1536,7 +1970,7 @@ async fn cancel_jobs(
                    , raw_lock
                    , true
                    , $1
-                   , canceled_reason
+                   , COALESCE($5, canceled_reason)
                    , job_kind
                    , schedule_path
                    , permissioned_as
@@ -1550,10 +1984,14 @@ async fn cancel_jobs(
                    , mem_peak
                    , tag
                    , priority FROM queue 
-        WHERE id = any($2) AND running = false AND parent_job IS NULL AND workspace_id = $3 AND schedule_path IS NULL FOR UPDATE SKIP LOCKED
-        ON CONFLICT (id) DO NOTHING RETURNING id", username, &jobs, w_id, serde_json::json!({"error": { "message": format!("Job canceled: cancel all by {username}"), "name": "Canceled", "reason": "cancel all", "canceler": username}}))
+        WHERE id = any($2) AND running = false AND parent_job IS NULL AND workspace_id = $3 AND schedule_path IS NOT DISTINCT FROM $6 FOR UPDATE SKIP LOCKED
+        ON CONFLICT (id) DO NOTHING RETURNING id", username, &jobs, w_id, serde_json::json!({"error": { "message": format!("Job canceled: {} by {username}", reason.as_deref().unwrap_or("cancel all")), "name": "Canceled", "reason": reason.as_deref().unwrap_or("cancel all"), "canceler": username}}), reason.as_deref(), schedule_path)
         .fetch_all(&mut *tx)
-        .await?.into_iter().map(|x| x.id).collect::<Vec<Uuid>>();
+        .await?;
+
+    for job_id in &trivial_jobs {
+        redact_completed_job_args(&mut tx, *job_id, w_id).await?;
+    }
 
     sqlx::query!(
         "DELETE FROM queue WHERE id = any($1) AND workspace_id = $2",
@@ -1574,11 +2012,12 @@ async fn cancel_jobs(
         if trivial_jobs.contains(&job_id) {
             continue;
         }
+        let reason = reason.clone();
         match tokio::time::timeout(tokio::time::Duration::from_secs(5), async move {
             let tx = db.begin().await?;
             let (tx, _) = windmill_queue::cancel_job(
                 username,
-                None,
+                reason,
                 job_id.clone(),
                 w_id,
                 tx,
@@ -1632,7 +2071,90 @@ async fn cancel_selection(
     .await?;
     tx.commit().await?;
 
-    cancel_jobs(jobs_to_cancel, &db, authed.username.as_str(), w_id.as_str()).await
+    cancel_jobs(
+        jobs_to_cancel,
+        &db,
+        authed.username.as_str(),
+        w_id.as_str(),
+        None,
+        None,
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct CancelBySchedulePath {
+    schedule_path: String,
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CancelBySchedulePathResponse {
+    cancelled_count: usize,
+    job_ids: Vec<Uuid>,
+}
+
+/// Cancels the runs of a schedule that are due or running, its upcoming tick stays queued
+async fn cancel_by_schedule_path(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(CancelBySchedulePath { schedule_path, reason }): Json<CancelBySchedulePath>,
+) -> error::JsonResult<CancelBySchedulePathResponse> {
+    require_owner_of_path(&authed, &schedule_path)?;
+
+    // the next tick of the schedule is left alone, only the runs that are due or running are
+    // canceled
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let jobs = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM queue WHERE workspace_id = $1 AND schedule_path = $2 AND parent_job IS NULL
+            AND (running = true OR scheduled_for <= now())",
+    )
+    .bind(&w_id)
+    .bind(&schedule_path)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let Json(job_ids) = cancel_jobs(
+        jobs,
+        &db,
+        &authed.username,
+        &w_id,
+        Some(&schedule_path),
+        reason,
+    )
+    .await?;
+    let cancelled_count = job_ids.len();
+
+    // scripts push the next tick of their schedule when they complete, which the runs canceled
+    // before they started never do
+    let mut tx = db.begin().await?;
+    if let Some(schedule) = get_schedule_opt(&mut *tx, &w_id, &schedule_path).await? {
+        if schedule.enabled {
+            tx = push_scheduled_job(&db, tx, &schedule, None).await?;
+        }
+    }
+    tx.commit().await?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "jobs.cancel_by_schedule_path",
+        ActionKind::Delete,
+        &w_id,
+        Some(&schedule_path),
+        Some([("cancelled_count", cancelled_count.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(CancelBySchedulePathResponse {
+        cancelled_count,
+        job_ids,
+    }))
 }
 
 async fn list_filtered_uuids(
@@ -1691,40 +2213,193 @@ async fn count_queue_jobs(
 }
 
 #[derive(Deserialize)]
-pub struct CountCompletedJobsQuery {
-    completed_after_s_ago: Option<i64>,
-    success: Option<bool>,
-    tags: Option<String>,
+pub struct OldestQueuedJobsQuery {
+    tag: Option<String>,
     all_workspaces: Option<bool>,
 }
 
-async fn count_completed_jobs_detail(
+#[derive(Serialize, FromRow)]
+struct OldestQueuedJob {
+    tag: String,
+    id: Uuid,
+    created_at: chrono::DateTime<chrono::Utc>,
+    scheduled_for: chrono::DateTime<chrono::Utc>,
+    age_s: f64,
+}
+
+/// Oldest job waiting to be picked up, per tag
+async fn oldest_queued_jobs(
+    authed: ApiAuthed,
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
-    Query(query): Query<CountCompletedJobsQuery>,
-) -> error::JsonResult<i64> {
-    let mut sqlb = SqlBuilder::select_from("completed_job");
-    sqlb.field("COUNT(*) as count");
+    Query(oq): Query<OldestQueuedJobsQuery>,
+) -> error::JsonResult<Vec<OldestQueuedJob>> {
+    let tags = get_scope_tags(&authed).map(|v| v.iter().map(|s| s.to_string()).collect_vec());
+    let jobs = sqlx::query_as::<_, OldestQueuedJob>(
+        "SELECT DISTINCT ON (tag) tag, id, created_at, scheduled_for,
+            EXTRACT(EPOCH FROM (now() - scheduled_for))::float8 as age_s
+        FROM queue
+        WHERE (workspace_id = $1 OR $2) AND running = false AND scheduled_for <= now()
+            AND ($3::text IS NULL OR tag = $3) AND ($4::text[] IS NULL OR tag = ANY($4))
+        ORDER BY tag, scheduled_for, created_at",
+    )
+    .bind(&w_id)
+    .bind(w_id == "admins" && oq.all_workspaces.unwrap_or(false))
+    .bind(oq.tag)
+    .bind(tags)
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(jobs))
+}
 
-    if !query.all_workspaces.unwrap_or(false) {
-        sqlb.and_where_eq("workspace_id", "?".bind(&w_id));
-    }
+#[derive(Deserialize)]
+struct ListSuspendedFlowsQuery {
+    /// `me` to only list the flows the caller can approve
+    approver_can_act: Option<String>,
+}
 
-    if let Some(after_s_ago) = query.completed_after_s_ago {
-        let after = Utc::now() - chrono::Duration::seconds(after_s_ago);
-        sqlb.and_where_gt(
-            "started_at + duration_ms / 1000 * interval '1 second'",
-            "?".bind(&after.to_rfc3339()),
-        );
-    }
+#[derive(FromRow)]
+struct SuspendedFlowRow {
+    id: Uuid,
+    script_path: Option<String>,
+    created_by: String,
+    email: String,
+    flow_status: Option<serde_json::Value>,
+    suspend: Option<i32>,
+    suspend_until: Option<chrono::DateTime<chrono::Utc>>,
+    suspended_at: Option<chrono::DateTime<chrono::Utc>>,
+    approvers: Vec<String>,
+}
 
-    if let Some(success) = query.success {
-        sqlb.and_where_eq("success", "?".bind(&success));
-    }
+#[derive(Serialize)]
+struct SuspendedFlow {
+    id: Uuid,
+    script_path: Option<String>,
+    created_by: String,
+    /// id of the step waiting for the approvals
+    step_id: Option<String>,
+    /// number of approvals the step requires
+    required_events: Option<u16>,
+    /// approvals still expected before the flow resumes
+    remaining_events: i32,
+    approvers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    approval_conditions: Option<ApprovalConditions>,
+    suspended_at: Option<chrono::DateTime<chrono::Utc>>,
+    suspend_until: Option<chrono::DateTime<chrono::Utc>>,
+}
 
-    if let Some(tags) = query.tags {
-        sqlb.and_where_in(
-            "tag",
+/// Flows waiting for approvals, the ones suspended for the longest first
+async fn list_suspended_flows(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<ListSuspendedFlowsQuery>,
+) -> error::JsonResult<Vec<SuspendedFlow>> {
+    let only_actionable = match query.approver_can_act.as_deref() {
+        None => false,
+        Some("me") => true,
+        Some(other) => {
+            return Err(Error::BadRequest(format!(
+                "approver_can_act only supports `me`, got {other}"
+            )))
+        }
+    };
+    let tags = get_scope_tags(&authed).map(|v| v.iter().map(|s| s.to_string()).collect_vec());
+
+    let mut tx = user_db.begin(&authed).await?;
+    let rows = sqlx::query_as::<_, SuspendedFlowRow>(
+        "SELECT q.id, q.script_path, q.created_by, q.email, q.flow_status, q.suspend, q.suspend_until,
+            step.started_at + step.duration_ms * interval '1 millisecond' as suspended_at,
+            ARRAY(SELECT coalesce(r.approver, 'anonymous') FROM resume_job r
+                WHERE r.flow = q.id AND r.job = step.id AND r.approved ORDER BY r.created_at) as approvers
+        FROM queue q
+        LEFT JOIN completed_job step
+            ON step.id = (q.flow_status->'modules'->((q.flow_status->>'step')::int)->>'job')::uuid
+        WHERE q.workspace_id = $1 AND (q.suspend > 0 OR q.suspend_until IS NOT NULL)
+            AND ($2::text[] IS NULL OR q.tag = ANY($2))
+        ORDER BY suspended_at ASC NULLS LAST, q.created_at ASC",
+    )
+    .bind(&w_id)
+    .bind(tags)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let mut flows = vec![];
+    for row in rows {
+        let flow_status = row
+            .flow_status
+            .and_then(|fs| serde_json::from_value::<FlowStatus>(fs).ok());
+        if only_actionable {
+            let can_act = flow_status.clone().map_or(true, |fs| {
+                conditionally_require_authed_user(Some(authed.clone()), fs, &row.email).is_ok()
+            });
+            if !can_act {
+                continue;
+            }
+        }
+        let waiting = flow_status
+            .as_ref()
+            .and_then(|fs| fs.modules.get(fs.step as usize));
+        let (step_id, required_events) = match waiting {
+            Some(FlowStatusModule::WaitingForEvents { id, count, .. }) => {
+                (Some(id.clone()), Some(*count))
+            }
+            _ => (None, None),
+        };
+        flows.push(SuspendedFlow {
+            id: row.id,
+            script_path: row.script_path,
+            created_by: row.created_by,
+            step_id,
+            required_events,
+            remaining_events: row.suspend.unwrap_or(0),
+            approvers: row.approvers,
+            approval_conditions: flow_status.and_then(|fs| fs.approval_conditions),
+            suspended_at: row.suspended_at,
+            suspend_until: row.suspend_until,
+        });
+    }
+
+    Ok(Json(flows))
+}
+
+#[derive(Deserialize)]
+pub struct CountCompletedJobsQuery {
+    completed_after_s_ago: Option<i64>,
+    success: Option<bool>,
+    tags: Option<String>,
+    all_workspaces: Option<bool>,
+}
+
+async fn count_completed_jobs_detail(
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<CountCompletedJobsQuery>,
+) -> error::JsonResult<i64> {
+    let mut sqlb = SqlBuilder::select_from("completed_job");
+    sqlb.field("COUNT(*) as count");
+
+    if !query.all_workspaces.unwrap_or(false) {
+        sqlb.and_where_eq("workspace_id", "?".bind(&w_id));
+    }
+
+    if let Some(after_s_ago) = query.completed_after_s_ago {
+        let after = Utc::now() - chrono::Duration::seconds(after_s_ago);
+        sqlb.and_where_gt(
+            "started_at + duration_ms / 1000 * interval '1 second'",
+            "?".bind(&after.to_rfc3339()),
+        );
+    }
+
+    if let Some(success) = query.success {
+        sqlb.and_where_eq("success", "?".bind(&success));
+    }
+
+    if let Some(tags) = query.tags {
+        sqlb.and_where_in(
+            "tag",
             &tags
                 .split(",")
                 .map(|t| format!("'{}'", t))
@@ -1738,6 +2413,138 @@ async fn count_completed_jobs_detail(
     Ok(Json(stats))
 }
 
+const MAX_DURATION_BUCKETS: i64 = 10_000;
+
+#[derive(Deserialize)]
+pub struct AggregatedDurationQuery {
+    script_path: Option<String>,
+    flow_path: Option<String>,
+    started_after: Option<chrono::DateTime<chrono::Utc>>,
+    started_before: Option<chrono::DateTime<chrono::Utc>>,
+    /// one of 1m, 5m, 1h, 1d, defaults to 1h
+    bucket_size: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct DurationBucket {
+    bucket_ts: chrono::DateTime<chrono::Utc>,
+    avg_duration_ms: Option<f64>,
+    p50: Option<f64>,
+    p95: Option<f64>,
+    p99: Option<f64>,
+    job_count: i64,
+}
+
+/// SQL expression truncating `started_at` to the bucket, and the bucket length in seconds
+fn duration_bucket(bucket_size: &str) -> error::Result<(&'static str, i64)> {
+    match bucket_size {
+        "1m" => Ok(("date_trunc('minute', started_at)", 60)),
+        "5m" => Ok((
+            "date_trunc('minute', started_at) - (EXTRACT(minute FROM started_at)::int % 5) * interval '1 minute'",
+            5 * 60,
+        )),
+        "1h" => Ok(("date_trunc('hour', started_at)", 60 * 60)),
+        "1d" => Ok(("date_trunc('day', started_at)", 24 * 60 * 60)),
+        _ => Err(error::Error::BadRequest(format!(
+            "invalid bucket_size {bucket_size}, must be one of 1m, 5m, 1h, 1d"
+        ))),
+    }
+}
+
+async fn aggregated_completed_jobs_duration(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<AggregatedDurationQuery>,
+) -> error::JsonResult<Vec<DurationBucket>> {
+    let (bucket, bucket_secs) = duration_bucket(query.bucket_size.as_deref().unwrap_or("1h"))?;
+    let started_before = query.started_before.unwrap_or_else(Utc::now);
+    let started_after = query
+        .started_after
+        .unwrap_or_else(|| started_before - chrono::Duration::days(1));
+    if started_after >= started_before {
+        return Err(error::Error::BadRequest(
+            "started_after must be before started_before".to_string(),
+        ));
+    }
+    if (started_before - started_after).num_seconds() / bucket_secs > MAX_DURATION_BUCKETS {
+        return Err(error::Error::BadRequest(format!(
+            "time range is too large for the bucket size, at most {MAX_DURATION_BUCKETS} buckets can be returned"
+        )));
+    }
+
+    let sql = format!(
+        "SELECT {bucket} AS bucket_ts,
+            avg(duration_ms)::float8 AS avg_duration_ms,
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY duration_ms) AS p50,
+            percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms) AS p95,
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY duration_ms) AS p99,
+            count(*) AS job_count
+        FROM completed_job
+        WHERE workspace_id = $1 AND started_at >= $2 AND started_at < $3
+            AND ($4::text IS NULL OR (script_path = $4 AND job_kind = 'script'))
+            AND ($5::text IS NULL OR (script_path = $5 AND job_kind = 'flow'))
+        GROUP BY bucket_ts
+        ORDER BY bucket_ts"
+    );
+    let mut tx = user_db.begin(&authed).await?;
+    let buckets = sqlx::query_as::<_, DurationBucket>(&sql)
+        .bind(&w_id)
+        .bind(started_after)
+        .bind(started_before)
+        .bind(&query.script_path)
+        .bind(&query.flow_path)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(buckets))
+}
+
+#[derive(Serialize, FromRow)]
+pub struct JobChild {
+    id: Uuid,
+    job_kind: JobKind,
+    script_path: Option<String>,
+    /// null while the child is still queued or running
+    success: Option<bool>,
+    duration_ms: Option<i64>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Lists the direct children of a flow job, without their args, logs nor results
+async fn list_job_children(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+    Query(pagination): Query<Pagination>,
+) -> error::JsonResult<Vec<JobChild>> {
+    check_scopes(&authed, || format!("jobs:listjobs"))?;
+    let (per_page, offset) = paginate(pagination);
+
+    let mut tx = user_db.begin(&authed).await?;
+    let children = sqlx::query_as::<_, JobChild>(
+        "SELECT * FROM (
+            SELECT id, job_kind, script_path, success, duration_ms, started_at
+            FROM completed_job WHERE parent_job = $1 AND workspace_id = $2
+            UNION ALL
+            SELECT id, job_kind, script_path, NULL, NULL, started_at
+            FROM queue WHERE parent_job = $1 AND workspace_id = $2
+        ) children
+        ORDER BY started_at ASC NULLS LAST, id
+        LIMIT $3 OFFSET $4",
+    )
+    .bind(id)
+    .bind(&w_id)
+    .bind(per_page as i64)
+    .bind(offset as i64)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(children))
+}
+
 async fn count_completed_jobs(
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
@@ -2330,33 +3137,203 @@ pub async fn get_flow_user_state(
     Ok(Json(r))
 }
 
+#[derive(Deserialize)]
+pub struct ListFlowUserStatesQuery {
+    include_values: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct FlowUserState {
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<serde_json::Value>,
+}
+
+pub async fn list_flow_user_states(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Query(query): Query<ListFlowUserStatesQuery>,
+) -> error::JsonResult<Vec<FlowUserState>> {
+    let mut tx = user_db.begin(&authed).await?;
+    let user_states = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+        "SELECT flow_status->'user_states' FROM queue
+        WHERE id = $1 AND workspace_id = $2 AND job_kind IN ('flow', 'flowpreview', 'flownode')",
+    )
+    .bind(job_id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| Error::NotFound("Flow job not found".to_string()))?;
+    tx.commit().await?;
+
+    let include_values = query.include_values.unwrap_or(false);
+    let user_states = match user_states {
+        Some(serde_json::Value::Object(user_states)) => user_states
+            .into_iter()
+            .map(|(key, value)| FlowUserState { key, value: include_values.then_some(value) })
+            .collect(),
+        _ => vec![],
+    };
+    Ok(Json(user_states))
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum FlowUserStateOp {
+    #[default]
+    Set,
+    Increment,
+    Append,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct FlowUserStateUpdate {
+    #[serde(default)]
+    value: serde_json::Value,
+    /// expected current value, `null` also matches an unset key
+    #[serde(default, deserialize_with = "deserialize_if_match")]
+    if_match: Option<serde_json::Value>,
+    #[serde(default)]
+    op: FlowUserStateOp,
+}
+
+/// An explicit `null` is a condition on an unset key rather than the absence of condition
+fn deserialize_if_match<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<serde_json::Value>, D::Error> {
+    serde_json::Value::deserialize(deserializer).map(Some)
+}
+
+impl FlowUserStateUpdate {
+    fn from_body(body: serde_json::Value) -> error::Result<Self> {
+        let update = serde_json::from_value::<Self>(body)
+            .map_err(|e| Error::BadRequest(format!("invalid flow user state update: {e}")))?;
+        if update.op == FlowUserStateOp::Increment && !update.value.is_number() {
+            return Err(Error::BadRequest(
+                "increment value must be a number".to_string(),
+            ));
+        }
+        Ok(update)
+    }
+}
+
+/// Overwrites the flow user state at `key` with the body
 pub async fn set_flow_user_state(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, job_id, key)): Path<(String, Uuid, String)>,
     Json(value): Json<serde_json::Value>,
 ) -> error::Result<String> {
+    let update = FlowUserStateUpdate { value, if_match: None, op: FlowUserStateOp::Set };
+    apply_flow_user_state_update(authed, user_db, w_id, job_id, key, update).await
+}
+
+/// Atomically updates the flow user state at `key`, conditionally on its current value with
+/// `if_match` or relatively to it with the `increment` and `append` ops
+pub async fn update_flow_user_state(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, job_id, key)): Path<(String, Uuid, String)>,
+    Json(body): Json<serde_json::Value>,
+) -> error::Result<String> {
+    let update = FlowUserStateUpdate::from_body(body)?;
+    apply_flow_user_state_update(authed, user_db, w_id, job_id, key, update).await
+}
+
+async fn apply_flow_user_state_update(
+    authed: ApiAuthed,
+    user_db: UserDB,
+    w_id: String,
+    job_id: Uuid,
+    key: String,
+    update: FlowUserStateUpdate,
+) -> error::Result<String> {
+    let current = "flow_status->'user_states'->$1";
+    let (new_value, expected_type) = match update.op {
+        FlowUserStateOp::Set => ("$2".to_string(), None),
+        FlowUserStateOp::Increment => (
+            format!(
+                "to_jsonb(CASE WHEN jsonb_typeof({current}) = 'number' THEN ({current})::numeric ELSE 0 END + ($2)::numeric)"
+            ),
+            Some("number"),
+        ),
+        FlowUserStateOp::Append => (
+            format!(
+                "CASE WHEN jsonb_typeof({current}) = 'array' THEN {current} ELSE '[]'::jsonb END || jsonb_build_array($2)"
+            ),
+            Some("array"),
+        ),
+    };
+    let mut conditions = vec![];
+    if let Some(expected_type) = expected_type {
+        conditions.push(format!(
+            "COALESCE(jsonb_typeof({current}), 'null') IN ('{expected_type}', 'null')"
+        ));
+    }
+    if update.if_match.is_some() {
+        conditions.push(format!("COALESCE({current}, 'null'::jsonb) = $5"));
+    }
+    let sql = format!(
+        "UPDATE queue SET flow_status = JSONB_SET(flow_status, ARRAY['user_states'], JSONB_SET(COALESCE(flow_status->'user_states', '{{}}'::jsonb), ARRAY[$1], {new_value}))
+        WHERE id = $3 AND workspace_id = $4 AND job_kind IN ('flow', 'flowpreview', 'flownode'){}
+        RETURNING 1",
+        conditions.iter().map(|c| format!(" AND {c}")).join("")
+    );
+
     let mut tx = user_db.begin(&authed).await?;
-    let r = sqlx::query_scalar!(
-        r#"
-        UPDATE queue SET flow_status = JSONB_SET(flow_status,  ARRAY['user_states'], JSONB_SET(COALESCE(flow_status->'user_states', '{}'::jsonb), ARRAY[$1], $2))
-        WHERE id = $3 AND workspace_id = $4 AND job_kind IN ('flow', 'flowpreview', 'flownode') RETURNING 1
-        "#,
-        key,
-        value,
-        job_id,
-        w_id
-    )
-    .fetch_optional(&mut *tx)
-    .await?
-    .flatten();
+    let mut q = sqlx::query_scalar::<_, i32>(&sql)
+        .bind(&key)
+        .bind(&update.value)
+        .bind(job_id)
+        .bind(&w_id);
+    if let Some(if_match) = update.if_match.as_ref() {
+        q = q.bind(if_match);
+    }
+    let r = q.fetch_optional(&mut *tx).await?;
+
     if r.is_none() {
-        return Err(Error::NotFound("Flow job not found".to_string()));
+        // the update is atomic, only look at the current state to explain why it did not apply
+        let current = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+            "SELECT flow_status->'user_states'->$1 FROM queue
+            WHERE id = $2 AND workspace_id = $3 AND job_kind IN ('flow', 'flowpreview', 'flownode')",
+        )
+        .bind(&key)
+        .bind(job_id)
+        .bind(&w_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        return Err(match (current, expected_type) {
+            (None, _) => Error::NotFound("Flow job not found".to_string()),
+            (Some(Some(current)), Some(expected_type))
+                if !current.is_null() && json_type_name(&current) != expected_type =>
+            {
+                Error::BadRequest(format!(
+                    "Flow user state {key} is not a {expected_type}, found: {current}"
+                ))
+            }
+            (Some(current), _) => Error::Conflict(format!(
+                "Flow user state {key} does not match if_match, current value: {}",
+                current.unwrap_or(serde_json::Value::Null)
+            )),
+        });
     }
     tx.commit().await?;
     Ok("Flow job state updated".to_string())
 }
 
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
 fn create_signature(
     key: String,
     job_id: Uuid,
@@ -2455,6 +3432,22 @@ pub struct JobExtended<T> {
     #[sqlx(skip)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub aggregate_wait_time_ms: Option<i64>,
+    /// attempt number of a retry of a failed script job
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_count: Option<i32>,
+    /// job pushed by the `on_success_path`/`on_failure_path` callback of a completed job
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_up_job_id: Option<Uuid>,
+    /// only for queued jobs that are not running yet
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_estimate: Option<QueueEstimate>,
+    /// only for completed jobs read back from the object store
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
 }
 
 impl<T> JobExtended<T> {
@@ -2470,6 +3463,10 @@ impl<T> JobExtended<T> {
             raw_flow: None,
             self_wait_time_ms,
             aggregate_wait_time_ms,
+            retry_count: None,
+            follow_up_job_id: None,
+            queue_estimate: None,
+            archived: false,
         }
     }
 }
@@ -2645,6 +3642,40 @@ impl Job {
         }
         Ok(())
     }
+
+    pub async fn fetch_retry_count(&mut self, db: &Pool<Postgres>) -> Result<(), sqlx::Error> {
+        let retry_count = get_retry_count(db, self.id()).await?;
+        match self {
+            Job::QueuedJob(job) => job.retry_count = retry_count,
+            Job::CompletedJob(job) => job.retry_count = retry_count,
+        }
+        Ok(())
+    }
+
+    pub async fn fetch_follow_up_job_id(&mut self, db: &DB) -> Result<(), sqlx::Error> {
+        if let Job::CompletedJob(job) = self {
+            job.follow_up_job_id = sqlx::query_scalar::<_, Option<Uuid>>(
+                "SELECT follow_up_job_id FROM job_chain WHERE job_id = $1",
+            )
+            .bind(job.id)
+            .fetch_optional(db)
+            .await?
+            .flatten();
+        }
+        Ok(())
+    }
+
+    pub async fn fetch_queue_estimate(&mut self, db: &DB) -> Result<(), sqlx::Error> {
+        if let Job::QueuedJob(job) = self {
+            if !job.running {
+                job.queue_estimate = Some(
+                    estimate_queue_start(db, job.id, &job.tag, job.priority, job.scheduled_for)
+                        .await?,
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(sqlx::FromRow)]
@@ -2846,6 +3877,10 @@ impl<'a> From<UnifiedJob> for Job {
                     flow_step_id: None,
                     cache_ttl: None,
                     priority: uj.priority,
+                    timeout_ms: None,
+                    mem_limit_mb: None,
+                    trace_context: None,
+                    redact_args: None,
                 },
             )),
             t => panic!("job type {} not valid", t),
@@ -2878,6 +3913,7 @@ struct Preview {
     tag: Option<String>,
     dedicated_worker: Option<bool>,
     lock: Option<String>,
+    mem_limit_mb: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -2955,6 +3991,7 @@ pub fn add_raw_string(
 }
 
 async fn check_tag_available_for_workspace(
+    db: &DB,
     w_id: &str,
     tag: &Option<String>,
     authed: &ApiAuthed,
@@ -2974,23 +4011,41 @@ async fn check_tag_available_for_workspace(
             }
         }
 
-        let custom_tags_per_w = CUSTOM_TAGS_PER_WORKSPACE.read().await;
-        if custom_tags_per_w.0.contains(&tag.to_string()) {
-            Ok(())
-        } else if custom_tags_per_w.1.contains_key(tag)
-            && custom_tags_per_w
-                .1
-                .get(tag)
-                .unwrap()
-                .contains(&w_id.to_string())
         {
-            Ok(())
-        } else {
-            return Err(error::Error::BadRequest(format!(
-                "Tag {tag} cannot be used on workspace {w_id}: (CUSTOM_TAGS: {:?})",
-                custom_tags_per_w
-            )));
+            let custom_tags_per_w = CUSTOM_TAGS_PER_WORKSPACE.read().await;
+            if !custom_tags_per_w.0.contains(&tag.to_string())
+                && !custom_tags_per_w
+                    .1
+                    .get(tag)
+                    .is_some_and(|workspaces| workspaces.contains(&w_id.to_string()))
+            {
+                return Err(error::Error::BadRequest(format!(
+                    "Tag {tag} cannot be used on workspace {w_id}: (CUSTOM_TAGS: {:?})",
+                    custom_tags_per_w
+                )));
+            }
+        }
+
+        if is_tag_served(tag).await == Some(false) {
+            let strict_tags = sqlx::query_scalar::<_, bool>(
+                "SELECT strict_tags FROM workspace_settings WHERE workspace_id = $1",
+            )
+            .bind(w_id)
+            .fetch_optional(db)
+            .await?
+            .unwrap_or(false);
+            if strict_tags {
+                return Err(Error::BadRequest(format!(
+                    "No live worker has pulled from tag {tag} in the last 5 minutes, the job would stay in the queue (strict tags are enabled on workspace {w_id})"
+                )));
+            }
+            tracing::warn!(
+                workspace_id = %w_id,
+                "Job pushed with tag {tag} that no live worker has pulled from in the last 5 minutes, it will stay in the queue until a worker serves it"
+            );
         }
+
+        Ok(())
     } else {
         Ok(())
     }
@@ -3010,57 +4065,451 @@ pub async fn check_license_key_valid() -> error::Result<()> {
     Ok(())
 }
 
-pub async fn run_flow_by_path(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, flow_path)): Path<(String, StripPath)>,
-    Query(run_query): Query<RunJobQuery>,
-    args: WebhookArgs,
-) -> error::Result<(StatusCode, String)> {
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
+enum DedupTarget<'a> {
+    ScriptPath(&'a str),
+    ScriptHash(i64),
+    FlowPath(&'a str),
+}
 
-    run_flow_by_path_inner(authed, db, user_db, w_id, flow_path, run_query, args, None).await
+enum Dedup {
+    /// an identical job was pushed within the window
+    Duplicate(Uuid),
+    /// no identical job, the new one must be pushed before the lock is released
+    Push(DedupLock),
 }
 
-pub async fn run_flow_by_path_inner(
-    authed: ApiAuthed,
-    db: DB,
-    user_db: UserDB,
-    w_id: String,
-    flow_path: StripPath,
-    run_query: RunJobQuery,
-    args: PushArgsOwned,
-    label_prefix: Option<String>,
-) -> error::Result<(StatusCode, String)> {
+/// Transaction holding the advisory lock of the runnable and args being deduplicated, so that
+/// identical concurrent requests are serialized and the later ones find the job pushed by the
+/// first one. The lock is released when the transaction ends.
+struct DedupLock(Option<Transaction<'static, Postgres>>);
+
+impl DedupLock {
+    async fn release(self) -> error::Result<()> {
+        if let Some(tx) = self.0 {
+            tx.commit().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Queued or completed job of `target` created in the last `dedup_window_s` seconds with the same
+/// args, wm_ prefixed extra args excepted. Only looked up when `dedup_window_s` is set.
+async fn find_duplicate_job(
+    authed: &ApiAuthed,
+    db: &DB,
+    user_db: &UserDB,
+    w_id: &str,
+    target: DedupTarget<'_>,
+    dedup_window_s: Option<i32>,
+    args: &PushArgsOwned,
+) -> error::Result<Dedup> {
+    let Some(dedup_window_s) = dedup_window_s else {
+        return Ok(Dedup::Push(DedupLock(None)));
+    };
+    let normalized_args = args
+        .args
+        .iter()
+        .chain(args.extra.iter().flatten())
+        .filter(|(k, _)| !k.starts_with("wm_"))
+        .map(|(k, v)| Ok((k.clone(), serde_json::from_str(v.get())?)))
+        .collect::<serde_json::Result<serde_json::Map<String, serde_json::Value>>>()
+        .map_err(|e| Error::BadRequest(format!("invalid args: {e}")))?;
+
+    let (filter, lock_target, path, hash) = match target {
+        DedupTarget::ScriptPath(path) => (
+            "script_path = $2 AND job_kind = 'script'",
+            format!("script/{path}"),
+            path,
+            None,
+        ),
+        DedupTarget::FlowPath(path) => (
+            "script_path = $2 AND job_kind = 'flow'",
+            format!("flow/{path}"),
+            path,
+            None,
+        ),
+        DedupTarget::ScriptHash(hash) => (
+            "script_hash = $3 AND job_kind = 'script'",
+            format!("hash/{hash}"),
+            "",
+            Some(hash),
+        ),
+    };
+
+    // the jsonb text representation has sorted keys, identical args get the same lock whatever
+    // the order of their keys
+    let mut lock = db.begin().await?;
+    sqlx::query(
+        "SELECT pg_advisory_xact_lock(hashtextextended('dedup:' || $1 || ':' || $2 || ':' || $3::jsonb::text, 0))",
+    )
+    .bind(w_id)
+    .bind(&lock_target)
+    .bind(sqlx::types::Json(&normalized_args))
+    .execute(&mut *lock)
+    .await?;
+
+    // the path or hash and the window are covered by indexes, args are only compared on the
+    // remaining rows
+    let sql = format!(
+        "SELECT id FROM (
+            SELECT id, args, created_at FROM queue
+            WHERE workspace_id = $1 AND {filter} AND created_at >= now() - make_interval(secs => $4)
+            UNION ALL
+            SELECT id, args, created_at FROM completed_job
+            WHERE workspace_id = $1 AND {filter} AND created_at >= now() - make_interval(secs => $4)
+        ) j
+        WHERE coalesce(args, '{{}}'::jsonb) @> $5
+            AND (SELECT coalesce(jsonb_object_agg(key, value), '{{}}'::jsonb) FROM jsonb_each(coalesce(j.args, '{{}}'::jsonb))
+                WHERE key NOT LIKE 'wm\\_%') = $5
+        ORDER BY created_at DESC
+        LIMIT 1"
+    );
+    let mut tx = user_db.clone().begin(authed).await?;
+    let job_id = sqlx::query_scalar::<_, Uuid>(&sql)
+        .bind(w_id)
+        .bind(path)
+        .bind(hash)
+        .bind(dedup_window_s as f64)
+        .bind(sqlx::types::Json(normalized_args))
+        .fetch_optional(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(match job_id {
+        Some(job_id) => Dedup::Duplicate(job_id),
+        None => Dedup::Push(DedupLock(Some(lock))),
+    })
+}
+
+fn deduplicated_response(job_id: Uuid) -> Response {
+    (
+        StatusCode::OK,
+        [("X-Windmill-Deduplicated", "true")],
+        job_id.to_string(),
+    )
+        .into_response()
+}
+
+enum StrictArgsTarget<'a> {
+    ScriptHash(i64),
+    /// path and version of the flow
+    FlowPath(&'a str, Option<i64>),
+}
+
+impl StrictArgsTarget<'_> {
+    /// for a script payload, hub scripts have no strict args
+    fn of_payload(job_payload: &JobPayload) -> Option<Self> {
+        match job_payload {
+            JobPayload::ScriptHash { hash, .. } => Some(Self::ScriptHash(hash.0)),
+            _ => None,
+        }
+    }
+
+    /// script hashes and flow versions are immutable, and so is their strict_args flag
+    fn cache_key(&self) -> Option<(bool, i64)> {
+        match self {
+            Self::ScriptHash(hash) => Some((false, *hash)),
+            Self::FlowPath(_, version) => version.map(|version| (true, version)),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STRICT_ARGS_FLAGS: Cache<(bool, i64), bool> = Cache::new(10000);
+}
+
+/// Rejects args not matching the schema of the runnable when strict args are requested or enabled
+/// on the script/flow. Must be called once the runnable has been fetched with the user permissions.
+/// The schema is only fetched when strict args are requested or the flag of the runnable is set
+/// (or not cached yet).
+async fn check_strict_args(
+    db: &DB,
+    w_id: &str,
+    target: Option<StrictArgsTarget<'_>>,
+    run_query: &RunJobQuery,
+    args: &PushArgsOwned,
+) -> error::Result<()> {
+    let Some(target) = target else {
+        return Ok(());
+    };
+    if run_query.strict_args == Some(false) {
+        return Ok(());
+    }
+    let cache_key = target.cache_key();
+    if run_query.strict_args.is_none()
+        && cache_key.is_some_and(|key| STRICT_ARGS_FLAGS.get(&key) == Some(false))
+    {
+        return Ok(());
+    }
+    let row = match target {
+        StrictArgsTarget::ScriptHash(hash) => {
+            sqlx::query_as::<_, (Option<serde_json::Value>, bool)>(
+                "SELECT schema, strict_args FROM script WHERE hash = $1 AND workspace_id = $2",
+            )
+            .bind(hash)
+            .bind(w_id)
+            .fetch_optional(db)
+            .await?
+        }
+        StrictArgsTarget::FlowPath(path, version) => {
+            sqlx::query_as::<_, (Option<serde_json::Value>, bool)>(
+                "SELECT COALESCE(flow_version.schema, flow.schema), flow.strict_args FROM flow
+                LEFT JOIN flow_version ON flow_version.id = $3
+                    AND flow_version.path = flow.path AND flow_version.workspace_id = flow.workspace_id
+                WHERE flow.path = $1 AND flow.workspace_id = $2",
+            )
+            .bind(path)
+            .bind(w_id)
+            .bind(version)
+            .fetch_optional(db)
+            .await?
+        }
+    };
+    if let (Some(key), Some((_, strict_args))) = (cache_key, row.as_ref()) {
+        STRICT_ARGS_FLAGS.insert(key, *strict_args);
+    }
+    match row {
+        Some((Some(schema), strict_args)) if run_query.strict_args.unwrap_or(strict_args) => {
+            validate_args_against_schema(&schema, args)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Validates the `on_success_path`/`on_failure_path` callbacks of a run, returns the depth of the
+/// job in its chain if it has any. A job run by a job of a chain (e.g. a follow-up, which gets its
+/// own `job_chain` row when pushed) is at the depth of that job.
+async fn check_job_chain(
+    authed: &ApiAuthed,
+    db: &DB,
+    user_db: &UserDB,
+    w_id: &str,
+    run_query: &RunJobQuery,
+) -> error::Result<Option<i32>> {
+    let paths = [&run_query.on_success_path, &run_query.on_failure_path];
+    if paths.iter().all(|p| p.is_none()) {
+        return Ok(None);
+    }
+
+    let depth = match run_query.requesting_job_id {
+        Some(job_id) => sqlx::query_scalar::<_, i32>(
+            "SELECT depth FROM job_chain WHERE job_id = $1 AND workspace_id = $2",
+        )
+        .bind(job_id)
+        .bind(w_id)
+        .fetch_optional(db)
+        .await?
+        .unwrap_or(0),
+        None => 0,
+    };
+    if depth >= MAX_JOB_CHAIN_DEPTH {
+        return Err(Error::BadRequest(format!(
+            "Job chains are limited to {MAX_JOB_CHAIN_DEPTH} follow-ups"
+        )));
+    }
+
+    let mut tx = user_db.clone().begin(authed).await?;
+    for path in paths.into_iter().flatten() {
+        check_scopes(authed, || format!("run:{path}"))?;
+        let exists = if let Some(script_path) = path.strip_prefix("script/") {
+            sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM script
+                WHERE path = $1 AND workspace_id = $2 AND archived = false AND deleted = false)",
+            )
+            .bind(script_path)
+            .bind(w_id)
+            .fetch_one(&mut *tx)
+            .await?
+        } else if let Some(flow_path) = path.strip_prefix("flow/") {
+            sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+            )
+            .bind(flow_path)
+            .bind(w_id)
+            .fetch_one(&mut *tx)
+            .await?
+        } else {
+            return Err(Error::BadRequest(format!(
+                "job chain path must start with script/ or flow/ (got {path})"
+            )));
+        };
+        if !exists {
+            return Err(Error::NotFound(format!(
+                "job chain target {path} not found in workspace {w_id}"
+            )));
+        }
+    }
+    tx.commit().await?;
+    Ok(Some(depth))
+}
+
+/// Stores the callbacks of a job, pushed by the queue once the job completes
+async fn insert_job_chain(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    job_id: Uuid,
+    run_query: &RunJobQuery,
+    depth: Option<i32>,
+) -> error::Result<()> {
+    let Some(depth) = depth else {
+        return Ok(());
+    };
+    sqlx::query(
+        "INSERT INTO job_chain (job_id, workspace_id, on_success_path, on_failure_path, depth)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(job_id)
+    .bind(w_id)
+    .bind(&run_query.on_success_path)
+    .bind(&run_query.on_failure_path)
+    .bind(depth)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Stores the memory limit of a job, enforced by the worker on the job process
+async fn set_job_mem_limit(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: Uuid,
+    mem_limit_mb: Option<i32>,
+) -> error::Result<()> {
+    if mem_limit_mb.is_some() {
+        sqlx::query("UPDATE queue SET mem_limit_mb = $1 WHERE id = $2")
+            .bind(mem_limit_mb)
+            .bind(job_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Stores the args to redact from a job once it completes, on top of the ones of its script
+async fn set_job_redact_args(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: Uuid,
+    redact_args: Option<&str>,
+) -> error::Result<()> {
+    let paths = redact_args
+        .map(|paths| {
+            paths
+                .split(',')
+                .map(|path| path.trim().to_string())
+                .filter(|path| !path.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !paths.is_empty() {
+        sqlx::query("UPDATE queue SET redact_args = $1 WHERE id = $2")
+            .bind(paths)
+            .bind(job_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Fails when the flow version a run is pinned to does not belong to the flow or no longer exists
+fn check_pinned_flow_version(
+    flow_path: &str,
+    pinned: Option<i64>,
+    found: Option<i64>,
+) -> error::Result<()> {
+    match (pinned, found) {
+        (Some(version), None) => Err(Error::NotFound(format!(
+            "version {version} of flow at path {flow_path} not found, it may have been deleted"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub async fn run_flow_by_path(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, flow_path)): Path<(String, StripPath)>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
+    args: WebhookArgs,
+) -> error::Result<Response> {
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
+
+    let lock = match find_duplicate_job(
+        &authed,
+        &db,
+        &user_db,
+        &w_id,
+        DedupTarget::FlowPath(flow_path.to_path()),
+        run_query.dedup_window_s,
+        &args,
+    )
+    .await?
+    {
+        Dedup::Duplicate(job_id) => return Ok(deduplicated_response(job_id)),
+        Dedup::Push(lock) => lock,
+    };
+    let response =
+        run_flow_by_path_inner(authed, db, user_db, w_id, flow_path, run_query, args, None).await?;
+    lock.release().await?;
+    Ok(response.into_response())
+}
+
+pub async fn run_flow_by_path_inner(
+    authed: ApiAuthed,
+    db: DB,
+    user_db: UserDB,
+    w_id: String,
+    flow_path: StripPath,
+    run_query: RunJobQuery,
+    args: PushArgsOwned,
+    label_prefix: Option<String>,
+) -> error::Result<(StatusCode, String)> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
     let flow_path = flow_path.to_path();
     check_scopes(&authed, || format!("run:flow/{flow_path}"))?;
 
     let mut tx = user_db.clone().begin(&authed).await?;
-    let (tag, dedicated_worker, has_preprocessor, on_behalf_of_email, edited_by) = sqlx::query!(
-        "SELECT tag, dedicated_worker, flow_version.value->>'preprocessor_module' IS NOT NULL as has_preprocessor, on_behalf_of_email, edited_by
+    let (tag, dedicated_worker, has_preprocessor, on_behalf_of_email, edited_by, version) = sqlx::query!(
+        r#"SELECT tag, dedicated_worker, flow_version.value->>'preprocessor_module' IS NOT NULL as has_preprocessor, on_behalf_of_email, edited_by, flow_version.id as "version?"
         FROM flow 
         LEFT JOIN flow_version
-            ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
-        WHERE flow.path = $1 and flow.workspace_id = $2",
+            ON flow_version.id = COALESCE($3, flow.versions[array_upper(flow.versions, 1)])
+            AND flow_version.path = flow.path AND flow_version.workspace_id = flow.workspace_id
+        WHERE flow.path = $1 and flow.workspace_id = $2"#,
         flow_path,
-        w_id
+        w_id,
+        run_query.version
     )
     .fetch_optional(&mut *tx)
     .await?
-    .map(|x| (x.tag, x.dedicated_worker, x.has_preprocessor, x.on_behalf_of_email, x.edited_by))
+    .map(|x| (x.tag, x.dedicated_worker, x.has_preprocessor, x.on_behalf_of_email, x.edited_by, x.version))
     .ok_or_else(|| {
         Error::NotFound(format!(
             "flow not found at path {flow_path} in workspace {w_id}"
         ))
     })?;
+    check_pinned_flow_version(flow_path, run_query.version, version)?;
     drop(tx);
+    check_strict_args(
+        &db,
+        &w_id,
+        Some(StrictArgsTarget::FlowPath(flow_path, version)),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
 
     let tag = run_query.tag.clone().or(tag);
 
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
 
     let (email, permissioned_as, push_authed, tx) =
@@ -3080,7 +4529,7 @@ pub async fn run_flow_by_path_inner(
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -3089,6 +4538,7 @@ pub async fn run_flow_by_path_inner(
             dedicated_worker,
             apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
                 && has_preprocessor.unwrap_or(false),
+            version: run_query.version,
         },
         PushArgs { args: &args.args, extra: args.extra },
         &label_prefix
@@ -3112,6 +4562,9 @@ pub async fn run_flow_by_path_inner(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
     tx.commit().await?;
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
@@ -3147,6 +4600,7 @@ pub async fn restart_flow(
     )>,
     Query(run_query): Query<RunJobQuery>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_dedup_window()?;
     check_license_key_valid().await?;
 
     let mut tx = user_db.clone().begin(&authed).await?;
@@ -3209,16 +4663,301 @@ pub async fn restart_flow(
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
 
+#[derive(Serialize)]
+pub struct RerunFailedIterationsResponse {
+    pub job_id: Uuid,
+    pub step_id: String,
+    pub iterations: Vec<usize>,
+}
+
+#[cfg(not(feature = "enterprise"))]
+pub async fn rerun_failed_iterations(
+    _authed: ApiAuthed,
+    Extension(_db): Extension<DB>,
+    Extension(_user_db): Extension<UserDB>,
+    Path((_w_id, _job_id)): Path<(String, Uuid)>,
+    Query(_run_query): Query<RunJobQuery>,
+) -> error::Result<(StatusCode, Json<RerunFailedIterationsResponse>)> {
+    return Err(Error::BadRequest(
+        "Restarting a flow is a feature only available in enterprise version".to_string(),
+    ));
+}
+
+/// Pushes a new run of a completed flow restarted at its first forloop/branchall module that
+/// had failed iterations (resp. branches), with the loop iterator restricted to the inputs of
+/// the failed iterations (resp. only the failed branches kept). The modules before it are
+/// carried over from the completed flow and its args are reused.
+#[cfg(feature = "enterprise")]
+pub async fn rerun_failed_iterations(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Query(run_query): Query<RunJobQuery>,
+) -> error::Result<(StatusCode, Json<RerunFailedIterationsResponse>)> {
+    use windmill_common::flows::{FlowModuleValue, InputTransform};
+
+    run_query.reject_dedup_window()?;
+    check_license_key_valid().await?;
+
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let completed_job = sqlx::query_as::<_, CompletedJob>(
+        "SELECT *, result->'wm_labels' as labels from completed_job WHERE id = $1 and workspace_id = $2",
+    )
+    .bind(job_id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .with_context(|| "Unable to find completed job with the given job UUID")?;
+    drop(tx);
+
+    if !matches!(completed_job.job_kind, JobKind::Flow | JobKind::FlowPreview) {
+        return Err(Error::BadRequest(format!("Job {job_id} is not a flow")));
+    }
+    let flow_path = completed_job
+        .script_path
+        .clone()
+        .with_context(|| "No flow path set for completed flow job")?;
+    check_scopes(&authed, || format!("run:flow/{flow_path}"))?;
+
+    let flow_status = completed_job
+        .flow_status
+        .as_ref()
+        .and_then(|v| serde_json::from_str::<FlowStatus>(v.get()).ok())
+        .ok_or_else(|| Error::InternalErr(format!("Unable to parse flow status of {job_id}")))?;
+
+    let flow_data = cache::job::fetch_flow(&db, completed_job.job_kind, completed_job.script_hash)
+        .or_else(|_| cache::job::fetch_preview_flow(&db, &job_id, completed_job.raw_flow.clone()))
+        .await?;
+    let mut flow_value = flow_data.value().clone();
+
+    // first forloop/branchall module with at least one failed iteration
+    let mut failed_module = None;
+    for module in flow_status.modules.iter() {
+        let Some(flow_jobs) = module.flow_jobs().filter(|jobs| !jobs.is_empty()) else {
+            continue;
+        };
+        let success = module.flow_jobs_success().unwrap_or_default();
+        // older flow status may not track the success of each iteration
+        let unknown = flow_jobs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| success.get(*i).copied().flatten().is_none())
+            .map(|(_, id)| *id)
+            .collect::<Vec<_>>();
+        let completed_success = if unknown.is_empty() {
+            HashMap::new()
+        } else {
+            sqlx::query_as::<_, (Uuid, bool)>(
+                "SELECT id, success FROM completed_job WHERE id = ANY($1) AND workspace_id = $2",
+            )
+            .bind(&unknown)
+            .bind(&w_id)
+            .fetch_all(&db)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+        };
+
+        let mut failed = vec![];
+        for (i, iteration_job) in flow_jobs.iter().enumerate() {
+            let iteration_success = success
+                .get(i)
+                .copied()
+                .flatten()
+                .or_else(|| completed_success.get(iteration_job).copied());
+            match iteration_success {
+                Some(true) => continue,
+                Some(false) => failed.push((i, *iteration_job)),
+                None => {
+                    return Err(Error::BadRequest(format!(
+                        "Iteration {i} of step {} (job {iteration_job}) no longer exists, \
+                         it was deleted after use or by the retention policy",
+                        module.id()
+                    )))
+                }
+            }
+        }
+        if !failed.is_empty() {
+            failed_module = Some((module.id(), failed));
+            break;
+        }
+    }
+    let Some((step_id, failed)) = failed_module else {
+        return Err(Error::BadRequest(format!(
+            "Flow {job_id} has no loop or branch-all step with failed iterations"
+        )));
+    };
+    let iterations = failed.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+
+    let module = flow_value
+        .modules
+        .iter_mut()
+        .find(|m| m.id == step_id)
+        .ok_or_else(|| {
+            Error::BadRequest(format!("Step {step_id} is not part of the flow anymore"))
+        })?;
+    let new_value = match module.get_value()? {
+        FlowModuleValue::ForloopFlow {
+            modules,
+            modules_node,
+            skip_failures,
+            parallel,
+            parallelism,
+            ..
+        } => {
+            let failed_ids = failed.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+            let failed_args =
+                sqlx::query_as::<_, (Uuid, Option<sqlx::types::Json<Box<RawValue>>>)>(
+                    "SELECT id, args FROM completed_job WHERE id = ANY($1) AND workspace_id = $2",
+                )
+                .bind(&failed_ids)
+                .bind(&w_id)
+                .fetch_all(&db)
+                .await?
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+
+            let mut inputs = Vec::with_capacity(failed.len());
+            for (i, iteration_job) in failed.iter() {
+                let args = failed_args
+                    .get(iteration_job)
+                    .and_then(|args| args.as_ref())
+                    .ok_or_else(|| {
+                        Error::BadRequest(format!(
+                            "Args of iteration {i} of step {step_id} (job {iteration_job}) were \
+                             deleted, its input cannot be recovered"
+                        ))
+                    })?;
+                let value = serde_json::from_str::<HashMap<String, serde_json::Value>>(args.get())
+                    .ok()
+                    .and_then(|mut args| args.remove("iter"))
+                    .and_then(|mut iter| iter.get_mut("value").map(serde_json::Value::take))
+                    .ok_or_else(|| {
+                        Error::BadRequest(format!(
+                            "Input of iteration {i} of step {step_id} cannot be recovered from \
+                             the args of job {iteration_job}"
+                        ))
+                    })?;
+                inputs.push(value);
+            }
+            FlowModuleValue::ForloopFlow {
+                iterator: InputTransform::Static { value: to_raw_value(&inputs) },
+                modules,
+                modules_node,
+                skip_failures,
+                parallel,
+                parallelism,
+            }
+        }
+        FlowModuleValue::BranchAll { branches, parallel } => FlowModuleValue::BranchAll {
+            branches: branches
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| iterations.contains(i))
+                .map(|(_, b)| b)
+                .collect(),
+            parallel,
+        },
+        _ => {
+            return Err(Error::BadRequest(format!(
+                "Step {step_id} is not a for-loop or branch-all step"
+            )))
+        }
+    };
+    module.value = to_raw_value(&new_value);
+
+    let ehm = HashMap::new();
+    let push_args = completed_job
+        .args
+        .as_ref()
+        .map(|json| PushArgs { args: &json.0, extra: None })
+        .unwrap_or_else(|| PushArgs::from(&ehm));
+
+    let scheduled_for = run_query.get_scheduled_for(&db).await?;
+
+    let tx = PushIsolationLevel::Isolated(user_db, authed.clone().into());
+
+    let (uuid, tx) = push(
+        &db,
+        tx,
+        &w_id,
+        JobPayload::RawFlow {
+            value: flow_value,
+            path: Some(flow_path),
+            restarted_from: Some(RestartedFrom {
+                flow_job_id: job_id,
+                step_id: step_id.clone(),
+                branch_or_iteration_n: None,
+            }),
+        },
+        push_args,
+        &authed.username,
+        &authed.email,
+        username_to_permissioned_as(&authed.username),
+        scheduled_for,
+        None,
+        run_query.parent_job,
+        run_query.root_job.or(run_query.parent_job),
+        run_query.job_id,
+        false,
+        false,
+        None,
+        !run_query.invisible_to_owner.unwrap_or(false),
+        Some(completed_job.tag),
+        None,
+        None,
+        completed_job.priority,
+        Some(&authed.clone().into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RerunFailedIterationsResponse { job_id: uuid, step_id, iterations }),
+    ))
+}
+
 pub async fn run_script_by_path(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
-    Query(run_query): Query<RunJobQuery>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
     args: WebhookArgs,
-) -> error::Result<(StatusCode, String)> {
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
-    run_script_by_path_inner(
+) -> error::Result<Response> {
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
+    if let Some(saved_input_id) = run_query.saved_input_id {
+        args.args = crate::inputs::get_saved_input_args(
+            &authed,
+            &db,
+            user_db.clone(),
+            &w_id,
+            saved_input_id,
+            script_path.to_path(),
+        )
+        .await?;
+    }
+    let lock = match find_duplicate_job(
+        &authed,
+        &db,
+        &user_db,
+        &w_id,
+        DedupTarget::ScriptPath(script_path.to_path()),
+        run_query.dedup_window_s,
+        &args,
+    )
+    .await?
+    {
+        Dedup::Duplicate(job_id) => return Ok(deduplicated_response(job_id)),
+        Dedup::Push(lock) => lock,
+    };
+    let response = run_script_by_path_inner(
         authed,
         db,
         user_db,
@@ -3228,7 +4967,9 @@ pub async fn run_script_by_path(
         args,
         None,
     )
-    .await
+    .await?;
+    lock.release().await?;
+    Ok(response.into_response())
 }
 
 pub async fn run_script_by_path_inner(
@@ -3252,10 +4993,22 @@ pub async fn run_script_by_path_inner(
     let (job_payload, tag, _delete_after_use, timeout, on_behalf_of) =
         script_path_to_payload(script_path, &mut *tx, &w_id, run_query.skip_preprocessor).await?;
     drop(tx);
+    check_strict_args(
+        &db,
+        &w_id,
+        StrictArgsTarget::of_payload(&job_payload),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
 
     let tag = run_query.tag.clone().or(tag);
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) =
         if let Some(on_behalf_of) = on_behalf_of.as_ref() {
@@ -3274,7 +5027,7 @@ pub async fn run_script_by_path_inner(
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -3301,6 +5054,9 @@ pub async fn run_script_by_path_inner(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
     tx.commit().await?;
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
@@ -3319,6 +5075,7 @@ pub async fn run_workflow_as_code(
     Query(wkflow_query): Query<WorkflowAsCodeQuery>,
     Json(task): Json<WorkflowTask>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_dedup_window()?;
     let mut i = 1;
 
     if *CLOUD_HOSTED {
@@ -3328,7 +5085,7 @@ pub async fn run_workflow_as_code(
 
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
-    check_tag_available_for_workspace(&w_id, &run_query.tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &run_query.tag, &authed).await?;
 
     if *CLOUD_HOSTED {
         tracing::info!("workflow_as_code_tracing id {i} ");
@@ -3588,13 +5345,17 @@ pub async fn run_wait_result_internal(
 
         if result.is_none() {
             let row = sqlx::query_as::<_, RawResultWithSuccess>(
-                "SELECT '' as created_by, result, language, flow_status, success FROM completed_job WHERE id = $1 AND workspace_id = $2",
+                "SELECT '' as created_by, result, language, flow_status, success, result->'__windmill_large_result'->>'s3' as large_result FROM completed_job WHERE id = $1 AND workspace_id = $2",
             )
             .bind(uuid)
             .bind(&w_id)
             .fetch_optional(db)
             .await?;
             if let Some(mut raw_result) = row {
+                if let Some(large_result) = raw_result.large_result.as_deref() {
+                    raw_result.result =
+                        Some(sqlx::types::Json(read_large_result(large_result).await?));
+                }
                 format_result(
                     raw_result.language.as_ref(),
                     raw_result.flow_status.as_ref(),
@@ -3865,6 +5626,7 @@ pub async fn run_wait_result_job_by_path_get(
     Query(run_query): Query<RunJobQuery>,
     DecodeQueries(queries): DecodeQueries,
 ) -> error::Result<Response> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -3897,7 +5659,7 @@ pub async fn run_wait_result_job_by_path_get(
     drop(tx);
 
     let tag = run_query.tag.clone().or(tag);
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) =
         if let Some(on_behalf_of) = on_behalf_authed.as_ref() {
@@ -3959,6 +5721,7 @@ pub async fn run_wait_result_flow_by_path_get(
     Query(run_query): Query<RunJobQuery>,
     DecodeQueries(queries): DecodeQueries,
 ) -> error::Result<Response> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -3994,13 +5757,18 @@ pub async fn run_wait_result_script_by_path(
     Extension(user_db): Extension<UserDB>,
     Extension(db): Extension<DB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
-    Query(run_query): Query<RunJobQuery>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
     args: WebhookArgs,
 ) -> error::Result<Response> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
 
     run_wait_result_script_by_path_internal(
         db,
@@ -4032,9 +5800,21 @@ pub async fn run_wait_result_script_by_path_internal(
     let mut tx = user_db.clone().begin(&authed).await?;
     let (job_payload, tag, delete_after_use, timeout, on_behalf_of) =
         script_path_to_payload(script_path, &mut *tx, &w_id, run_query.skip_preprocessor).await?;
+    check_strict_args(
+        &db,
+        &w_id,
+        StrictArgsTarget::of_payload(&job_payload),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
 
     let tag = run_query.tag.clone().or(tag);
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) =
         if let Some(on_behalf_of) = on_behalf_of.as_ref() {
@@ -4053,7 +5833,7 @@ pub async fn run_wait_result_script_by_path_internal(
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4080,6 +5860,9 @@ pub async fn run_wait_result_script_by_path_internal(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
     tx.commit().await?;
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
@@ -4094,13 +5877,18 @@ pub async fn run_wait_result_script_by_hash(
     Extension(user_db): Extension<UserDB>,
     Extension(db): Extension<DB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
-    Query(run_query): Query<RunJobQuery>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
     args: WebhookArgs,
 ) -> error::Result<Response> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
 
     check_queue_too_long(&db, run_query.queue_limit).await?;
 
@@ -4126,9 +5914,21 @@ pub async fn run_wait_result_script_by_hash(
         cache_ttl = Some(run_query_cache_ttl);
     }
     check_scopes(&authed, || format!("run:script/{path}"))?;
+    check_strict_args(
+        &db,
+        &w_id,
+        Some(StrictArgsTarget::ScriptHash(hash)),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
 
     let tag = run_query.tag.clone().or(tag);
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) = if let Some(email) = on_behalf_of_email.as_ref()
     {
@@ -4147,7 +5947,7 @@ pub async fn run_wait_result_script_by_hash(
         )
     };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4184,6 +5984,9 @@ pub async fn run_wait_result_script_by_hash(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
     tx.commit().await?;
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
@@ -4198,13 +6001,18 @@ pub async fn run_wait_result_flow_by_path(
     Extension(user_db): Extension<UserDB>,
     Extension(db): Extension<DB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
-    Query(run_query): Query<RunJobQuery>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
     args: WebhookArgs,
 ) -> error::Result<Response> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
 
     run_wait_result_flow_by_path_internal(
         db, run_query, flow_path, authed, user_db, args, w_id, None,
@@ -4230,26 +6038,42 @@ pub async fn run_wait_result_flow_by_path_internal(
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
 
     let mut tx = user_db.clone().begin(&authed).await?;
-    let (tag, dedicated_worker, early_return, has_preprocessor, on_behalf_of_email, edited_by) = sqlx::query!(
-        "SELECT tag, dedicated_worker, flow_version.value->>'early_return' as early_return, flow_version.value->>'preprocessor_module' IS NOT NULL as has_preprocessor, on_behalf_of_email, edited_by
+    let (tag, dedicated_worker, early_return, has_preprocessor, on_behalf_of_email, edited_by, version) = sqlx::query!(
+        r#"SELECT tag, dedicated_worker, flow_version.value->>'early_return' as early_return, flow_version.value->>'preprocessor_module' IS NOT NULL as has_preprocessor, on_behalf_of_email, edited_by, flow_version.id as "version?"
         FROM flow 
         LEFT JOIN flow_version
-            ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
-        WHERE flow.path = $1 and flow.workspace_id = $2",
+            ON flow_version.id = COALESCE($3, flow.versions[array_upper(flow.versions, 1)])
+            AND flow_version.path = flow.path AND flow_version.workspace_id = flow.workspace_id
+        WHERE flow.path = $1 and flow.workspace_id = $2"#,
         flow_path,
-        w_id
+        w_id,
+        run_query.version
     )
     .fetch_optional(&mut *tx)
     .await?
-    .map(|x| (x.tag, x.dedicated_worker, x.early_return, x.has_preprocessor, x.on_behalf_of_email, x.edited_by))
+    .map(|x| (x.tag, x.dedicated_worker, x.early_return, x.has_preprocessor, x.on_behalf_of_email, x.edited_by, x.version))
     .ok_or_else(|| {
         Error::NotFound(format!(
             "flow not found at path {flow_path} in workspace {w_id}"
         ))
     })?;
+    check_pinned_flow_version(flow_path, run_query.version, version)?;
+
+    check_strict_args(
+        &db,
+        &w_id,
+        Some(StrictArgsTarget::FlowPath(flow_path, version)),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
 
     let tag = run_query.tag.clone().or(tag);
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) =
         if let Some(on_behalf_of_email) = on_behalf_of_email.as_ref() {
@@ -4268,7 +6092,7 @@ pub async fn run_wait_result_flow_by_path_internal(
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4277,6 +6101,7 @@ pub async fn run_wait_result_flow_by_path_internal(
             dedicated_worker,
             apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
                 && has_preprocessor.unwrap_or(false),
+            version: run_query.version,
         },
         PushArgs { args: &args.args, extra: args.extra },
         &label_prefix
@@ -4300,6 +6125,9 @@ pub async fn run_wait_result_flow_by_path_internal(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
     tx.commit().await?;
 
     run_wait_result(&db, uuid, w_id, early_return, &authed.username).await
@@ -4313,6 +6141,7 @@ async fn run_preview_script(
     Query(run_query): Query<RunJobQuery>,
     Json(preview): Json<Preview>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_dedup_window()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4324,10 +6153,14 @@ async fn run_preview_script(
     }
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
     let tag = run_query.tag.clone().or(preview.tag.clone());
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
+    let mem_limit_mb = run_query.mem_limit_mb.or(preview.mem_limit_mb);
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(mem_limit_mb)?;
     let tx = PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into());
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4367,6 +6200,8 @@ async fn run_preview_script(
         Some(&authed.clone().into()),
     )
     .await?;
+    set_job_mem_limit(&mut tx, uuid, mem_limit_mb).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
     tx.commit().await?;
 
     Ok((StatusCode::CREATED, uuid.to_string()))
@@ -4383,6 +6218,7 @@ async fn run_bundle_preview_script(
 ) -> error::Result<(StatusCode, String)> {
     use windmill_common::scripts::PREVIEW_IS_TAR_CODEBASE_HASH;
 
+    run_query.reject_dedup_window()?;
     check_license_key_valid().await?;
 
     check_scopes(&authed, || format!("jobs:runscript"))?;
@@ -4406,7 +6242,7 @@ async fn run_bundle_preview_script(
 
             let scheduled_for = run_query.get_scheduled_for(&db).await?;
             let tag = run_query.tag.clone().or(preview.tag.clone());
-            check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+            check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
             let ltx = PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into());
 
             let args = preview.args.unwrap_or_default();
@@ -4925,6 +6761,7 @@ async fn run_preview_flow_job(
     Query(run_query): Query<RunJobQuery>,
     Json(raw_flow): Json<PreviewFlow>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_dedup_window()?;
     check_scopes(&authed, || format!("jobs:runflow"))?;
     if authed.is_operator {
         return Err(error::Error::NotAuthorized(
@@ -4933,10 +6770,13 @@ async fn run_preview_flow_job(
     }
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
     let tag = run_query.tag.clone().or(raw_flow.tag.clone());
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
     let tx = PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into());
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4965,6 +6805,8 @@ async fn run_preview_flow_job(
         Some(&authed.clone().into()),
     )
     .await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
     tx.commit().await?;
 
     Ok((StatusCode::CREATED, uuid.to_string()))
@@ -4975,11 +6817,29 @@ pub async fn run_job_by_hash(
     Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
-    Query(run_query): Query<RunJobQuery>,
+    Query(mut run_query): Query<RunJobQuery>,
+    Tokened { token }: Tokened,
     args: WebhookArgs,
-) -> error::Result<(StatusCode, String)> {
-    let args = args.to_push_args_owned(&authed, &db, &w_id).await?;
-    run_job_by_hash_inner(
+) -> error::Result<Response> {
+    let args = args
+        .to_push_args_owned_for_job(&authed, &db, &w_id, &mut run_query.job_id)
+        .await?;
+    run_query.set_requesting_job(&db, &token).await;
+    let lock = match find_duplicate_job(
+        &authed,
+        &db,
+        &user_db,
+        &w_id,
+        DedupTarget::ScriptHash(script_hash.0),
+        run_query.dedup_window_s,
+        &args,
+    )
+    .await?
+    {
+        Dedup::Duplicate(job_id) => return Ok(deduplicated_response(job_id)),
+        Dedup::Push(lock) => lock,
+    };
+    let response = run_job_by_hash_inner(
         authed,
         db,
         user_db,
@@ -4989,7 +6849,9 @@ pub async fn run_job_by_hash(
         args,
         None,
     )
-    .await
+    .await?;
+    lock.release().await?;
+    Ok(response.into_response())
 }
 
 pub async fn run_job_by_hash_inner(
@@ -5024,13 +6886,25 @@ pub async fn run_job_by_hash_inner(
     ) = get_path_tag_limits_cache_for_hash(user_db.clone().begin(&authed).await?, &w_id, hash)
         .await?;
     check_scopes(&authed, || format!("run:script/{path}"))?;
+    check_strict_args(
+        &db,
+        &w_id,
+        Some(StrictArgsTarget::ScriptHash(hash)),
+        &run_query,
+        &args,
+    )
+    .await?;
+    let chain_depth = check_job_chain(&authed, &db, &user_db, &w_id, &run_query).await?;
+    WorkspaceJobLimits::get(&w_id)
+        .await
+        .check_mem_limit(run_query.mem_limit_mb)?;
     if let Some(run_query_cache_ttl) = run_query.cache_ttl {
         cache_ttl = Some(run_query_cache_ttl);
     }
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
     let tag = run_query.tag.clone().or(tag);
 
-    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+    check_tag_available_for_workspace(&db, &w_id, &tag, &authed).await?;
 
     let (email, permissioned_as, push_authed, tx) = if let Some(email) = on_behalf_of_email.as_ref()
     {
@@ -5049,7 +6923,7 @@ pub async fn run_job_by_hash_inner(
         )
     };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -5088,6 +6962,9 @@ pub async fn run_job_by_hash_inner(
         push_authed.as_ref(),
     )
     .await?;
+    insert_job_chain(&mut tx, &w_id, uuid, &run_query, chain_depth).await?;
+    set_job_redact_args(&mut tx, uuid, run_query.redact_args.as_deref()).await?;
+    set_job_mem_limit(&mut tx, uuid, run_query.mem_limit_mb).await?;
     tx.commit().await?;
 
     Ok((StatusCode::CREATED, uuid.to_string()))
@@ -5109,6 +6986,15 @@ pub struct JobUpdate {
     pub mem_peak: Option<i32>,
     pub progress: Option<i32>,
     pub flow_status: Option<Box<serde_json::value::RawValue>>,
+    pub retry_count: Option<i32>,
+}
+
+/// Attempt number of the job if it is a retry of a failed script job
+async fn get_retry_count(db: &DB, job_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>("SELECT attempt FROM script_job_retry WHERE job_id = $1")
+        .bind(job_id)
+        .fetch_optional(db)
+        .await
 }
 
 async fn get_log_file(Path((_w_id, file_p)): Path<(String, String)>) -> error::Result<Response> {
@@ -5225,6 +7111,7 @@ async fn get_job_update(
             flow_status: record
                 .flow_status
                 .map(|x: sqlx::types::Json<Box<RawValue>>| x.0),
+            retry_count: get_retry_count(&db, job_id).await?,
         }))
     } else {
         let record = sqlx::query_as::<_, JobUpdateRow>(
@@ -5258,6 +7145,7 @@ async fn get_job_update(
                 flow_status: record
                     .flow_status
                     .map(|x: sqlx::types::Json<Box<RawValue>>| x.0),
+                retry_count: get_retry_count(&db, job_id).await?,
             }))
         } else {
             Err(error::Error::NotFound(format!("Job not found: {}", job_id)))
@@ -5428,6 +7316,41 @@ pub struct ListCompletedQuery {
     pub concurrency_key: Option<String>,
 }
 
+/// Fields of [`ListableCompletedJob`] selected from `completed_job`
+pub(crate) const LISTABLE_COMPLETED_JOB_FIELDS: [&str; 31] = [
+    "id",
+    "workspace_id",
+    "parent_job",
+    "created_by",
+    "created_at",
+    "started_at",
+    "duration_ms",
+    "success",
+    "script_hash",
+    "script_path",
+    "deleted",
+    "canceled",
+    "canceled_by",
+    "canceled_reason",
+    "job_kind",
+    "schedule_path",
+    "permissioned_as",
+    "null as raw_code",
+    "null as flow_status",
+    "null as raw_flow",
+    "is_flow_step",
+    "language",
+    "is_skipped",
+    "email",
+    "visible_to_owner",
+    "mem_peak",
+    "tag",
+    "priority",
+    "result->'wm_labels' as labels",
+    "archived",
+    "'CompletedJob' as type",
+];
+
 async fn list_completed_jobs(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -5444,38 +7367,7 @@ async fn list_completed_jobs(
         per_page,
         offset,
         &lq,
-        &[
-            "id",
-            "workspace_id",
-            "parent_job",
-            "created_by",
-            "created_at",
-            "started_at",
-            "duration_ms",
-            "success",
-            "script_hash",
-            "script_path",
-            "deleted",
-            "canceled",
-            "canceled_by",
-            "canceled_reason",
-            "job_kind",
-            "schedule_path",
-            "permissioned_as",
-            "null as raw_code",
-            "null as flow_status",
-            "null as raw_flow",
-            "is_flow_step",
-            "language",
-            "is_skipped",
-            "email",
-            "visible_to_owner",
-            "mem_peak",
-            "tag",
-            "priority",
-            "result->'wm_labels' as labels",
-            "'CompletedJob' as type",
-        ],
+        &LISTABLE_COMPLETED_JOB_FIELDS,
         false,
         get_scope_tags(&authed),
     )
@@ -5488,6 +7380,43 @@ async fn list_completed_jobs(
     Ok(Json(jobs))
 }
 
+#[derive(Deserialize)]
+struct LastFailedJobsQuery {
+    tag: Option<String>,
+    job_kind: Option<String>,
+}
+
+/// Most recent failed job of each script path
+async fn list_last_failed_jobs_per_script(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(q): Query<LastFailedJobsQuery>,
+) -> error::JsonResult<Vec<ListableCompletedJob>> {
+    check_scopes(&authed, || format!("jobs:listjobs"))?;
+
+    let tags = get_scope_tags(&authed);
+    let sql = format!(
+        "SELECT DISTINCT ON (script_path) {} FROM completed_job
+        WHERE workspace_id = $1 AND success = false AND script_path IS NOT NULL
+        AND ($2::text IS NULL OR tag = $2)
+        AND ($3::text IS NULL OR job_kind::text = $3)
+        AND ($4::text[] IS NULL OR tag = ANY($4))
+        ORDER BY script_path, started_at DESC",
+        LISTABLE_COMPLETED_JOB_FIELDS.join(", ")
+    );
+    let mut tx = user_db.begin(&authed).await?;
+    let jobs = sqlx::query_as::<_, ListableCompletedJob>(&sql)
+        .bind(&w_id)
+        .bind(&q.tag)
+        .bind(&q.job_kind)
+        .bind(tags.as_ref().map(|v| v.as_slice()))
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Json(jobs))
+}
+
 async fn get_completed_job<'a>(
     OptAuthed(opt_authed): OptAuthed,
     Extension(db): Extension<DB>,
@@ -5526,6 +7455,11 @@ pub struct RawResult {
     pub flow_status: Option<sqlx::types::Json<Box<RawValue>>>,
     pub language: Option<ScriptLang>,
     pub created_by: Option<String>,
+    #[sqlx(default)]
+    pub archived: bool,
+    /// object store path of the result if it was offloaded there
+    #[sqlx(default)]
+    pub large_result: Option<String>,
 }
 
 #[derive(FromRow)]
@@ -5535,6 +7469,108 @@ pub struct RawResultWithSuccess {
     pub language: Option<ScriptLang>,
     pub success: bool,
     pub created_by: String,
+    #[sqlx(default)]
+    pub large_result: Option<String>,
+}
+
+/// Splits a dot/bracket notation path like `a.b[0]["c.d"]` into the path array `{a,b,0,c.d}`
+/// given to the `#>` operator
+fn parse_json_path(json_path: &str) -> Vec<String> {
+    let mut path = vec![];
+    let mut rest = json_path;
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']').unwrap_or(inner.len());
+            path.push(
+                inner[..end]
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .to_string(),
+            );
+            rest = inner.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find(|c| c == '.' || c == '[').unwrap_or(rest.len());
+            if end > 0 {
+                path.push(rest[..end].to_string());
+            }
+            rest = &rest[end..];
+        }
+        rest = rest.strip_prefix('.').unwrap_or(rest);
+    }
+    path
+}
+
+/// Result of a completed job, read from its `archived` record instead of the table if given
+async fn fetch_raw_result(
+    db: &DB,
+    w_id: &str,
+    id: Uuid,
+    json_path: Option<&Vec<String>>,
+    tags: Option<&Vec<&str>>,
+    archived: Option<&str>,
+) -> error::Result<Option<RawResult>> {
+    let (sql, archived_param) = if json_path.is_some() {
+        (
+            "SELECT result #> $4 as result, flow_status, language, created_by, archived IS TRUE as archived, result->'__windmill_large_result'->>'s3' as large_result FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3))",
+            5,
+        )
+    } else {
+        (
+            "SELECT result, flow_status, language, created_by, archived IS TRUE as archived, result->'__windmill_large_result'->>'s3' as large_result FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3))",
+            4,
+        )
+    };
+    let sql = match archived {
+        Some(_) => job_archive::with_archived_record("completed_job", archived_param, sql),
+        None => sql.to_string(),
+    };
+
+    let mut query = sqlx::query_as::<_, RawResult>(&sql)
+        .bind(id)
+        .bind(w_id)
+        .bind(tags.map(|v| v.as_slice()));
+    if let Some(json_path) = json_path {
+        query = query.bind(json_path);
+    }
+    if let Some(archived) = archived {
+        query = query.bind(archived);
+    }
+    Ok(query.fetch_optional(db).await?)
+}
+
+/// Result offloaded to the object store, streamed back as is unless only the value at `json_path`
+/// is requested
+async fn large_result_response(
+    path: &str,
+    json_path: Option<&Vec<String>>,
+) -> error::Result<Response> {
+    let Some(json_path) = json_path else {
+        return Ok((
+            [(http::header::CONTENT_TYPE, "application/json")],
+            Body::from_stream(large_result_stream(path).await?),
+        )
+            .into_response());
+    };
+
+    let mut value = serde_json::from_str::<serde_json::Value>(read_large_result(path).await?.get())
+        .map_err(to_anyhow)?;
+    for key in json_path {
+        value = match value {
+            serde_json::Value::Object(mut o) => o.remove(key),
+            serde_json::Value::Array(mut a) => key
+                .parse::<usize>()
+                .ok()
+                .filter(|i| *i < a.len())
+                .map(|i| a.swap_remove(i)),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "No value at path {} in the result",
+                json_path.join(".")
+            ))
+        })?;
+    }
+    Ok(Json(value).into_response())
 }
 
 async fn get_completed_job_result(
@@ -5547,29 +7583,22 @@ async fn get_completed_job_result(
         .as_ref()
         .map(|authed| get_scope_tags(authed))
         .flatten();
-    let result_o = if let Some(json_path) = json_path {
-        sqlx::query_as::<_, RawResult>(
-            "SELECT result #> $3 as result, flow_status, language, created_by FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($4::text[] IS NULL OR tag = ANY($4))",
-        )
-        .bind(id)
-        .bind(&w_id)
-        .bind(
-            json_path
-                .split(".")
-                .map(|x| x.to_string())
-                .collect::<Vec<_>>(),
-        )
-        .bind(tags.as_ref().map(|v| v.as_slice()))
-        .fetch_optional(&db)
-        .await?
-    } else {
-        sqlx::query_as::<_, RawResult>("SELECT result, flow_status, language, created_by FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3))")
-            .bind(id)
-            .bind(&w_id)
-            .bind(tags.as_ref().map(|v| v.as_slice()))
-            .fetch_optional(&db)
-            .await?
-    };
+    let json_path = json_path.as_deref().map(parse_json_path);
+    let mut result_o =
+        fetch_raw_result(&db, &w_id, id, json_path.as_ref(), tags.as_ref(), None).await?;
+    if result_o.as_ref().is_some_and(|r| r.archived) {
+        if let Some(archived) = job_archive::fetch_archived_job(&db, &w_id, id).await? {
+            result_o = fetch_raw_result(
+                &db,
+                &w_id,
+                id,
+                json_path.as_ref(),
+                tags.as_ref(),
+                Some(archived.as_str()),
+            )
+            .await?;
+        }
+    }
 
     let mut raw_result = not_found_if_none(result_o, "Completed Job", id.to_string())?;
 
@@ -5611,6 +7640,19 @@ async fn get_completed_job_result(
         }
     }
 
+    if let Some(large_result) = raw_result.large_result.as_deref() {
+        log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
+        return large_result_response(large_result, json_path.as_ref()).await;
+    }
+
+    // `#>` returns NULL when nothing is at the path, and a json null when the value is null
+    if let Some(json_path) = json_path.as_ref().filter(|_| raw_result.result.is_none()) {
+        return Err(Error::NotFound(format!(
+            "No value at path {} in the result of job {id}",
+            json_path.join(".")
+        )));
+    }
+
     format_result(
         raw_result.language.as_ref(),
         raw_result.flow_status.as_ref(),
@@ -5622,6 +7664,229 @@ async fn get_completed_job_result(
     Ok(Json(raw_result.result).into_response())
 }
 
+#[derive(Deserialize)]
+struct DiffResultsQuery {
+    job_a: Uuid,
+    job_b: Uuid,
+    /// restrict the diff to the sub-tree at this dot separated path
+    json_path: Option<String>,
+}
+
+#[derive(FromRow)]
+struct DiffableResult {
+    id: Uuid,
+    result: Option<sqlx::types::Json<Box<RawValue>>>,
+    flow_status: Option<sqlx::types::Json<Box<RawValue>>>,
+    language: Option<ScriptLang>,
+    deleted: bool,
+    too_big: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ResultDiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ResultDiffEntry {
+    path: String,
+    kind: ResultDiffKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct ResultDiffError {
+    job_id: Uuid,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct ResultDiff {
+    job_a: Uuid,
+    job_b: Uuid,
+    /// jobs whose result cannot be diffed, the diff is empty if any
+    errors: Vec<ResultDiffError>,
+    diff: Vec<ResultDiffEntry>,
+}
+
+const MAX_DIFF_RESULT_SIZE: i32 = 90000;
+const MAX_DIFF_LEAF_LEN: usize = 1000;
+
+async fn diff_completed_job_results(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<DiffResultsQuery>,
+) -> error::JsonResult<ResultDiff> {
+    check_scopes(&authed, || format!("jobs:listjobs"))?;
+    let tags = get_scope_tags(&authed);
+    let json_path = query
+        .json_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.split(".").map(|x| x.to_string()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let rows = sqlx::query_as::<_, DiffableResult>(
+        "SELECT id, CASE WHEN result IS NULL OR pg_column_size(result) < $5 THEN result #> $3 END as result, \
+            flow_status, language, deleted, result IS NOT NULL AND pg_column_size(result) >= $5 as too_big \
+        FROM completed_job WHERE id = ANY($1) AND workspace_id = $2 AND ($4::text[] IS NULL OR tag = ANY($4))",
+    )
+    .bind(&[query.job_a, query.job_b][..])
+    .bind(&w_id)
+    .bind(&json_path)
+    .bind(tags.as_ref().map(|v| v.as_slice()))
+    .bind(MAX_DIFF_RESULT_SIZE)
+    .fetch_all(&db)
+    .await?;
+
+    let mut errors = vec![];
+    let mut results = vec![];
+    for job_id in [query.job_a, query.job_b] {
+        let row = not_found_if_none(
+            rows.iter().find(|r| r.id == job_id),
+            "Completed Job",
+            job_id.to_string(),
+        )?;
+        if row.deleted {
+            errors.push(ResultDiffError { job_id, error: "result was deleted".to_string() });
+            continue;
+        }
+        if row.too_big {
+            errors.push(ResultDiffError {
+                job_id,
+                error: format!("result is too big to be diffed (> {MAX_DIFF_RESULT_SIZE} bytes)"),
+            });
+            continue;
+        }
+        let mut result = row.result.clone();
+        format_result(
+            row.language.as_ref(),
+            row.flow_status.as_ref(),
+            result.as_mut(),
+        );
+        let value = result
+            .map(|r| serde_json::from_str::<serde_json::Value>(r.get()))
+            .transpose()
+            .map_err(|e| Error::InternalErr(format!("parsing result of job {job_id}: {e:#}")))?
+            .unwrap_or(serde_json::Value::Null);
+        results.push(value);
+    }
+
+    for job_id in [query.job_a, query.job_b] {
+        log_job_view(&db, Some(&authed), &w_id, &job_id).await?;
+    }
+
+    let mut diff = vec![];
+    if let [before, after] = &results[..] {
+        diff_json_values(
+            query.json_path.as_deref().unwrap_or(""),
+            before,
+            after,
+            &mut diff,
+        );
+    }
+
+    Ok(Json(ResultDiff {
+        job_a: query.job_a,
+        job_b: query.job_b,
+        errors,
+        diff,
+    }))
+}
+
+fn join_diff_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn truncate_diff_leaf(value: &serde_json::Value) -> serde_json::Value {
+    let s = value.to_string();
+    if s.len() > MAX_DIFF_LEAF_LEN {
+        let mut end = MAX_DIFF_LEAF_LEN;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        serde_json::Value::String(format!("{}...(truncated)", &s[..end]))
+    } else {
+        value.clone()
+    }
+}
+
+/// Structural diff of two json values, objects and arrays are compared key by key and index by
+/// index, any other difference is reported at the path where it happens
+fn diff_json_values(
+    path: &str,
+    before: &serde_json::Value,
+    after: &serde_json::Value,
+    diff: &mut Vec<ResultDiffEntry>,
+) {
+    use serde_json::Value;
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            for (k, bv) in b {
+                let p = join_diff_path(path, k);
+                match a.get(k) {
+                    Some(av) => diff_json_values(&p, bv, av, diff),
+                    None => diff.push(ResultDiffEntry {
+                        path: p,
+                        kind: ResultDiffKind::Removed,
+                        before: Some(truncate_diff_leaf(bv)),
+                        after: None,
+                    }),
+                }
+            }
+            for (k, av) in a {
+                if !b.contains_key(k) {
+                    diff.push(ResultDiffEntry {
+                        path: join_diff_path(path, k),
+                        kind: ResultDiffKind::Added,
+                        before: None,
+                        after: Some(truncate_diff_leaf(av)),
+                    });
+                }
+            }
+        }
+        (Value::Array(b), Value::Array(a)) => {
+            for i in 0..b.len().max(a.len()) {
+                let p = join_diff_path(path, &i.to_string());
+                match (b.get(i), a.get(i)) {
+                    (Some(bv), Some(av)) => diff_json_values(&p, bv, av, diff),
+                    (Some(bv), None) => diff.push(ResultDiffEntry {
+                        path: p,
+                        kind: ResultDiffKind::Removed,
+                        before: Some(truncate_diff_leaf(bv)),
+                        after: None,
+                    }),
+                    (None, Some(av)) => diff.push(ResultDiffEntry {
+                        path: p,
+                        kind: ResultDiffKind::Added,
+                        before: None,
+                        after: Some(truncate_diff_leaf(av)),
+                    }),
+                    (None, None) => (),
+                }
+            }
+        }
+        (b, a) if b != a => diff.push(ResultDiffEntry {
+            path: path.to_string(),
+            kind: ResultDiffKind::Changed,
+            before: Some(truncate_diff_leaf(b)),
+            after: Some(truncate_diff_leaf(a)),
+        }),
+        _ => (),
+    }
+}
+
 #[derive(Deserialize)]
 struct CountByTagQuery {
     horizon_secs: Option<i64>,
@@ -5671,24 +7936,29 @@ struct CompletedJobResult {
 #[derive(Deserialize)]
 struct GetCompletedJobQuery {
     get_started: Option<bool>,
+    /// only return the value at this dot/bracket notation path of the result, null if missing
+    json_path: Option<String>,
 }
 
 async fn get_completed_job_result_maybe(
     OptAuthed(opt_authed): OptAuthed,
     Extension(db): Extension<DB>,
     Path((w_id, id)): Path<(String, Uuid)>,
-    Query(GetCompletedJobQuery { get_started }): Query<GetCompletedJobQuery>,
+    Query(GetCompletedJobQuery { get_started, json_path }): Query<GetCompletedJobQuery>,
 ) -> error::Result<Response> {
     let tags = opt_authed
         .as_ref()
         .map(|authed| get_scope_tags(authed))
         .flatten();
+    let json_path = json_path.as_deref().map(parse_json_path);
+    // the projection is done in postgres so that only the value at the path is sent over
     let result_o = sqlx::query_as::<_, RawResultWithSuccess>(
-        "SELECT result, success, language, flow_status, created_by FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3))",
+        "SELECT CASE WHEN $4::text[] IS NULL THEN result ELSE result #> $4 END as result, success, language, flow_status, created_by FROM completed_job WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3))",
     )
     .bind(id)
     .bind(&w_id)
     .bind(tags.as_ref().map(|v| v.as_slice()))
+    .bind(json_path.as_ref())
     .fetch_optional(&db)
     .await?;
 
@@ -5751,6 +8021,16 @@ async fn delete_completed_job<'a>(
 
     require_admin(authed.is_admin, &authed.username)?;
     let tags = get_scope_tags(&authed);
+    // the offloaded result is removed from the object store once the job is deleted
+    let large_result = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT result->'__windmill_large_result'->>'s3' FROM completed_job
+        WHERE id = $1 AND workspace_id = $2",
+    )
+    .bind(id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten();
     let job_o = sqlx::query_as::<_, CompletedJob>(
         "UPDATE completed_job SET args = null, logs = '', result = null, deleted = true WHERE id = $1 AND workspace_id = $2 AND ($3::text[] IS NULL OR tag = ANY($3)) \
          RETURNING *, null as labels",
@@ -5779,9 +8059,386 @@ async fn delete_completed_job<'a>(
     .await?;
 
     tx.commit().await?;
+    delete_large_results(large_result.into_iter().collect()).await;
 
     let cj = format_completed_job_result(cj);
 
     let response = Json(cj).into_response();
     Ok(response)
 }
+
+async fn unarchive_completed_job(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> error::Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+    if !job_archive::unarchive_job(&mut tx, &db, &w_id, id).await? {
+        return Err(Error::NotFound(format!(
+            "Archived completed job {id} not found"
+        )));
+    }
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "jobs.unarchive",
+        ActionKind::Update,
+        &w_id,
+        Some(&id.to_string()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!("Completed job {id} unarchived"))
+}
+
+/// Anonymizes the result, args and logs of a completed job while keeping its metadata (duration,
+/// success, timestamps). Unlike `delete_completed_job`, the job is not flagged as deleted.
+async fn archive_job_result(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> error::Result<String> {
+    check_scopes(&authed, || format!("jobs:deletejob"))?;
+
+    let mut tx = db.begin().await?;
+
+    let job = sqlx::query_as::<_, (String, bool, Option<String>)>(
+        "SELECT created_by, archived, result->'__windmill_large_result'->>'s3' FROM completed_job
+        WHERE id = $1 AND workspace_id = $2 FOR UPDATE",
+    )
+    .bind(id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (created_by, archived, large_result) =
+        not_found_if_none(job, "Completed Job", id.to_string())?;
+
+    if !authed.is_admin && created_by != authed.username {
+        return Err(Error::PermissionDenied(format!(
+            "only the creator of job {id} or an admin can archive its result"
+        )));
+    }
+    if archived {
+        return Err(Error::BadRequest(format!(
+            "job {id} has been moved to the object store, unarchive it before archiving its result"
+        )));
+    }
+
+    sqlx::query(
+        "UPDATE completed_job SET result = '{\"archived\": true}'::jsonb, args = '{}'::jsonb, logs = '[archived]'
+        WHERE id = $1 AND workspace_id = $2",
+    )
+    .bind(id)
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM job_logs WHERE job_id = $1", id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "jobs.archive_result",
+        ActionKind::Update,
+        &w_id,
+        Some(&id.to_string()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+    delete_large_results(large_result.into_iter().collect()).await;
+
+    Ok(format!("Result of completed job {id} archived"))
+}
+
+const MAX_BULK_LABELED_JOBS: usize = 1000;
+
+#[derive(Deserialize)]
+struct EditLabels {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct BulkEditLabels {
+    job_ids: Vec<Uuid>,
+    #[serde(flatten)]
+    labels: EditLabels,
+}
+
+#[derive(Serialize, FromRow)]
+struct JobLabels {
+    id: Uuid,
+    labels: sqlx::types::Json<Vec<String>>,
+}
+
+async fn update_completed_job_labels(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+    Json(labels): Json<EditLabels>,
+) -> error::JsonResult<Vec<String>> {
+    let updated = edit_completed_jobs_labels(&authed, user_db, &w_id, vec![id], labels).await?;
+    Ok(Json(
+        updated
+            .into_iter()
+            .next()
+            .map(|j| j.labels.0)
+            .unwrap_or_default(),
+    ))
+}
+
+async fn update_completed_jobs_labels(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(BulkEditLabels { job_ids, labels }): Json<BulkEditLabels>,
+) -> error::JsonResult<Vec<JobLabels>> {
+    if job_ids.len() > MAX_BULK_LABELED_JOBS {
+        return Err(Error::BadRequest(format!(
+            "cannot edit the labels of more than {MAX_BULK_LABELED_JOBS} jobs at once"
+        )));
+    }
+    let updated = edit_completed_jobs_labels(&authed, user_db, &w_id, job_ids, labels).await?;
+    Ok(Json(updated))
+}
+
+/// Adds and removes labels from the `wm_labels` array of the results of completed jobs, which is
+/// what the label filter of the completed jobs list matches on. Only jobs whose result is an
+/// object can be labeled, and only by their creator, the owner of their path or an admin.
+async fn edit_completed_jobs_labels(
+    authed: &ApiAuthed,
+    user_db: UserDB,
+    w_id: &str,
+    job_ids: Vec<Uuid>,
+    EditLabels { add, remove }: EditLabels,
+) -> error::Result<Vec<JobLabels>> {
+    check_scopes(authed, || format!("jobs:labeljobs"))?;
+    if add.is_empty() && remove.is_empty() {
+        return Err(Error::BadRequest(
+            "at least one label to add or remove is required".to_string(),
+        ));
+    }
+    if add.iter().chain(remove.iter()).any(|l| l.trim().is_empty()) {
+        return Err(Error::BadRequest("labels cannot be empty".to_string()));
+    }
+
+    let mut tx = user_db.begin(authed).await?;
+
+    let jobs = sqlx::query_as::<_, (Uuid, String, Option<String>, bool)>(
+        "SELECT id, created_by, script_path, COALESCE(jsonb_typeof(result) = 'object', false) \
+        FROM completed_job WHERE id = ANY($1) AND workspace_id = $2 FOR UPDATE",
+    )
+    .bind(&job_ids)
+    .bind(w_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if let Some(missing) = job_ids.iter().find(|id| !jobs.iter().any(|j| j.0 == **id)) {
+        return Err(Error::NotFound(format!(
+            "Completed job {missing} not found"
+        )));
+    }
+    for (id, created_by, script_path, is_object) in &jobs {
+        let allowed = authed.is_admin
+            || created_by == &authed.username
+            || script_path
+                .as_deref()
+                .is_some_and(|p| require_owner_of_path(authed, p).is_ok());
+        if !allowed {
+            return Err(Error::PermissionDenied(format!(
+                "only the creator of job {id}, the owner of its path or an admin can edit its labels"
+            )));
+        }
+        if !is_object {
+            return Err(Error::BadRequest(format!(
+                "labels can only be set on jobs whose result is an object, which is not the case of job {id}"
+            )));
+        }
+    }
+
+    let updated = sqlx::query_as::<_, JobLabels>(
+        "UPDATE completed_job SET result = jsonb_set(result, '{wm_labels}', COALESCE(( \
+            SELECT jsonb_agg(l ORDER BY l) FROM ( \
+                SELECT jsonb_array_elements_text(CASE WHEN jsonb_typeof(result->'wm_labels') = 'array' \
+                    THEN result->'wm_labels' ELSE '[]'::jsonb END) \
+                UNION SELECT unnest($3::text[]) \
+            ) ls(l) WHERE NOT l = ANY($4::text[]) \
+        ), '[]'::jsonb)) \
+        WHERE id = ANY($1) AND workspace_id = $2 \
+        RETURNING id, result->'wm_labels' as labels",
+    )
+    .bind(&job_ids)
+    .bind(w_id)
+    .bind(&add)
+    .bind(&remove)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let add = add.join(",");
+    let remove = remove.join(",");
+    for job in &updated {
+        audit_log(
+            &mut *tx,
+            authed,
+            "jobs.edit_labels",
+            ActionKind::Update,
+            w_id,
+            Some(&job.id.to_string()),
+            Some([("add", add.as_str()), ("remove", remove.as_str())].into()),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        compute_queue_estimate, diff_json_values, parse_json_path, EstimateConfidence,
+        FlowUserStateOp, FlowUserStateUpdate, QueueTagStats, ResultDiffEntry, ResultDiffKind,
+        MAX_DIFF_LEAF_LEN,
+    };
+
+    fn diff(before: serde_json::Value, after: serde_json::Value) -> Vec<ResultDiffEntry> {
+        let mut diff = vec![];
+        diff_json_values("", &before, &after, &mut diff);
+        diff
+    }
+
+    #[test]
+    fn diff_results_nested_paths() {
+        let d = diff(
+            json!({"a": 1, "b": {"c": [1, 2, 3], "d": "x"}, "e": true}),
+            json!({"a": 2, "b": {"c": [1, 2], "d": "x"}, "f": null}),
+        );
+        assert_eq!(
+            d,
+            vec![
+                ResultDiffEntry {
+                    path: "a".to_string(),
+                    kind: ResultDiffKind::Changed,
+                    before: Some(json!(1)),
+                    after: Some(json!(2)),
+                },
+                ResultDiffEntry {
+                    path: "b.c.2".to_string(),
+                    kind: ResultDiffKind::Removed,
+                    before: Some(json!(3)),
+                    after: None,
+                },
+                ResultDiffEntry {
+                    path: "e".to_string(),
+                    kind: ResultDiffKind::Removed,
+                    before: Some(json!(true)),
+                    after: None,
+                },
+                ResultDiffEntry {
+                    path: "f".to_string(),
+                    kind: ResultDiffKind::Added,
+                    before: None,
+                    after: Some(json!(null)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_results_identical_and_truncated() {
+        assert!(diff(json!({"a": [1, {"b": 2}]}), json!({"a": [1, {"b": 2}]})).is_empty());
+
+        let d = diff(json!("short"), json!("x".repeat(2 * MAX_DIFF_LEAF_LEN)));
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].path, "");
+        let after = d[0].after.as_ref().unwrap().as_str().unwrap();
+        assert!(after.ends_with("...(truncated)"));
+        assert!(after.len() < MAX_DIFF_LEAF_LEN + 20);
+    }
+
+    #[test]
+    fn flow_user_state_update_from_body() {
+        let plain = FlowUserStateUpdate::from_body(json!({"value": 1})).unwrap();
+        assert_eq!(plain.value, json!(1));
+        assert_eq!(plain.op, FlowUserStateOp::Set);
+        assert_eq!(plain.if_match, None);
+
+        let cas = FlowUserStateUpdate::from_body(json!({"value": 2, "if_match": null})).unwrap();
+        assert_eq!(
+            cas,
+            FlowUserStateUpdate {
+                value: json!(2),
+                if_match: Some(json!(null)),
+                op: FlowUserStateOp::Set
+            }
+        );
+
+        let incr = FlowUserStateUpdate::from_body(json!({"value": 3, "op": "increment"})).unwrap();
+        assert_eq!(incr.op, FlowUserStateOp::Increment);
+        assert!(FlowUserStateUpdate::from_body(json!({"value": "a", "op": "increment"})).is_err());
+        assert!(FlowUserStateUpdate::from_body(json!({"op": "remove"})).is_err());
+        assert!(FlowUserStateUpdate::from_body(json!({"value": 1, "other": 2})).is_err());
+    }
+
+    #[test]
+    fn queue_estimate_from_tag_throughput() {
+        let now = chrono::Utc::now();
+        let stats = |started_in_window, workers| QueueTagStats {
+            started_in_window,
+            workers,
+            fetched_at: std::time::Instant::now(),
+        };
+
+        // 60 jobs in 10 minutes drain 6 jobs per minute
+        let e = compute_queue_estimate(12, stats(60, 2), now, now);
+        assert_eq!(e.position, 13);
+        assert_eq!(e.recent_throughput_per_min, 6.0);
+        assert_eq!(e.estimated_start, Some(now + chrono::Duration::minutes(2)));
+        assert_eq!(e.confidence, EstimateConfidence::High);
+
+        // a job scheduled later does not start before its scheduled time
+        let later = now + chrono::Duration::hours(1);
+        let e = compute_queue_estimate(12, stats(20, 2), later, now);
+        assert_eq!(e.estimated_start, Some(later));
+        assert_eq!(e.confidence, EstimateConfidence::Medium);
+
+        let e = compute_queue_estimate(0, stats(0, 1), now, now);
+        assert_eq!(e.estimated_start, Some(now));
+        assert_eq!(e.confidence, EstimateConfidence::Medium);
+
+        let e = compute_queue_estimate(3, stats(0, 1), now, now);
+        assert_eq!(e.estimated_start, None);
+        assert_eq!(e.confidence, EstimateConfidence::Low);
+
+        let e = compute_queue_estimate(0, stats(60, 0), now, now);
+        assert_eq!(e.estimated_start, None);
+        assert_eq!(e.confidence, EstimateConfidence::Low);
+    }
+
+    #[test]
+    fn json_path_dot_and_bracket_notation() {
+        assert_eq!(parse_json_path("a.b.c"), vec!["a", "b", "c"]);
+        assert_eq!(parse_json_path("rows[0].name"), vec!["rows", "0", "name"]);
+        assert_eq!(
+            parse_json_path(r#"a["b.c"]['d'][2]"#),
+            vec!["a", "b.c", "d", "2"]
+        );
+        assert_eq!(parse_json_path("[1]"), vec!["1"]);
+        assert!(parse_json_path("").is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+//! Delivery guarantees of the kafka triggers: the consumer of `kafka_triggers_ee` acquires an
+//! [`InFlightJobs`] slot before pulling a message, pushes the job and only commits the offset of
+//! the message once [`finalize_pushed_job`] returned, which produces the messages of failed jobs
+//! to the dead-letter topic of the trigger.
+
+use std::{sync::Arc, time::Duration};
+
+use rdkafka::{
+    message::{Header, Headers, Message, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    util::Timeout,
+};
+use serde_json::{value::RawValue, Value};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+use windmill_common::{
+    error::{Error, Result},
+    utils::report_critical_error,
+};
+
+use crate::db::DB;
+
+pub const DEAD_LETTER_JOB_ID_HEADER: &str = "windmill-job-id";
+pub const DEAD_LETTER_ERROR_HEADER: &str = "windmill-error";
+pub const DEAD_LETTER_FAILED_AT_HEADER: &str = "windmill-failed-at";
+
+const DEAD_LETTER_PRODUCE_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_OUTCOME_POLL_DELAY: Duration = Duration::from_secs(5);
+
+/// Bounds the number of pushed jobs whose outcome has not been handled yet. Without a
+/// `max_in_flight` the consumer is never paused.
+#[derive(Clone)]
+pub struct InFlightJobs(Option<Arc<Semaphore>>);
+
+impl InFlightJobs {
+    pub fn new(max_in_flight: Option<i32>) -> Self {
+        Self(
+            max_in_flight
+                .filter(|max| *max > 0)
+                .map(|max| Arc::new(Semaphore::new(max as usize))),
+        )
+    }
+
+    /// Waits until a slot is free. The slot is released when the returned permit is dropped,
+    /// which must happen once the outcome of the job has been finalized.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.0 {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JobOutcome {
+    Success,
+    Failure(String),
+}
+
+pub async fn wait_for_job_outcome(db: &DB, w_id: &str, job_id: Uuid) -> Result<JobOutcome> {
+    let mut delay = Duration::from_millis(100);
+    loop {
+        let completed = sqlx::query_as::<_, (bool, Option<sqlx::types::Json<Box<RawValue>>>)>(
+            "SELECT success, result FROM completed_job WHERE id = $1 AND workspace_id = $2",
+        )
+        .bind(job_id)
+        .bind(w_id)
+        .fetch_optional(db)
+        .await?;
+
+        if let Some((success, result)) = completed {
+            return Ok(if success {
+                JobOutcome::Success
+            } else {
+                JobOutcome::Failure(job_error_message(result.as_ref().map(|r| r.0.get())))
+            });
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = std::cmp::min(delay * 2, MAX_OUTCOME_POLL_DELAY);
+    }
+}
+
+fn job_error_message(result: Option<&str>) -> String {
+    let Some(result) = result else {
+        return "Job failed without a result".to_string();
+    };
+    match serde_json::from_str::<Value>(result) {
+        Ok(value) => match value.pointer("/error/message").and_then(Value::as_str) {
+            Some(message) => message.to_string(),
+            None => result.to_string(),
+        },
+        Err(_) => result.to_string(),
+    }
+}
+
+/// The headers of the original message followed by the job id, the error message and the
+/// failure time (RFC 3339)
+fn dead_letter_headers<M: Message>(
+    msg: &M,
+    job_id: Uuid,
+    error: &str,
+    failed_at: chrono::DateTime<chrono::Utc>,
+) -> OwnedHeaders {
+    let mut headers = OwnedHeaders::new();
+    if let Some(original) = msg.headers() {
+        for header in original.iter() {
+            headers = headers.insert(Header { key: header.key, value: header.value });
+        }
+    }
+    headers
+        .insert(Header { key: DEAD_LETTER_JOB_ID_HEADER, value: Some(&job_id.to_string()) })
+        .insert(Header { key: DEAD_LETTER_ERROR_HEADER, value: Some(error) })
+        .insert(Header { key: DEAD_LETTER_FAILED_AT_HEADER, value: Some(&failed_at.to_rfc3339()) })
+}
+
+async fn disable_with_error(db: &DB, w_id: &str, path: &str, error: &str) {
+    if let Err(e) = sqlx::query(
+        "UPDATE kafka_trigger SET enabled = FALSE, error = $1, server_id = NULL,
+            last_server_ping = NULL
+        WHERE workspace_id = $2 AND path = $3",
+    )
+    .bind(error)
+    .bind(w_id)
+    .bind(path)
+    .execute(db)
+    .await
+    {
+        tracing::error!("Could not disable kafka trigger {path} in {w_id}: {e:#}");
+    }
+    report_critical_error(
+        format!("Disabling kafka trigger {path} because of error: {error}"),
+        db.clone(),
+        Some(w_id),
+        None,
+    )
+    .await;
+}
+
+/// Waits for the job pushed for `msg` and, if it failed and `dead_letter` is set, produces the
+/// original message to its topic. The consumer must only commit the offset of `msg` once this
+/// returned `Ok`: if the message cannot be produced to the dead-letter topic, the trigger is
+/// disabled with the error so that the message is consumed again once it is re-enabled.
+pub async fn finalize_pushed_job<M: Message>(
+    db: &DB,
+    dead_letter: Option<(&FutureProducer, &str)>,
+    w_id: &str,
+    trigger_path: &str,
+    msg: &M,
+    job_id: Uuid,
+) -> Result<JobOutcome> {
+    let outcome = wait_for_job_outcome(db, w_id, job_id).await?;
+    let (JobOutcome::Failure(error), Some((producer, topic))) = (&outcome, dead_letter) else {
+        return Ok(outcome);
+    };
+
+    let mut record: FutureRecord<[u8], [u8]> = FutureRecord::to(topic)
+        .headers(dead_letter_headers(msg, job_id, error, chrono::Utc::now()));
+    if let Some(payload) = msg.payload() {
+        record = record.payload(payload);
+    }
+    if let Some(key) = msg.key() {
+        record = record.key(key);
+    }
+
+    if let Err((e, _)) = producer
+        .send(record, Timeout::After(DEAD_LETTER_PRODUCE_TIMEOUT))
+        .await
+    {
+        let error = format!(
+            "Could not produce the message of job {job_id} to dead-letter topic {topic}: {e}"
+        );
+        disable_with_error(db, w_id, trigger_path, &error).await;
+        return Err(Error::InternalErr(error));
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use rdkafka::{message::OwnedMessage, Timestamp};
+
+    use super::*;
+
+    #[test]
+    fn test_job_error_message() {
+        assert_eq!(
+            job_error_message(Some(r#"{"error": {"name": "Error", "message": "boom"}}"#)),
+            "boom"
+        );
+        assert_eq!(
+            job_error_message(Some(r#""not an object""#)),
+            r#""not an object""#
+        );
+        assert_eq!(job_error_message(None), "Job failed without a result");
+    }
+
+    #[test]
+    fn test_dead_letter_headers_keep_the_original_ones() {
+        let msg = OwnedMessage::new(
+            Some(b"payload".to_vec()),
+            Some(b"key".to_vec()),
+            "orders".to_string(),
+            Timestamp::NotAvailable,
+            0,
+            42,
+            Some(OwnedHeaders::new().insert(Header { key: "trace", value: Some("abc") })),
+        );
+        let job_id = Uuid::nil();
+        let failed_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let headers = dead_letter_headers(&msg, job_id, "boom", failed_at);
+        let headers = headers
+            .iter()
+            .map(|h| {
+                (
+                    h.key.to_string(),
+                    String::from_utf8(h.value.unwrap().to_vec()).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            vec![
+                ("trace".to_string(), "abc".to_string()),
+                (DEAD_LETTER_JOB_ID_HEADER.to_string(), job_id.to_string()),
+                (DEAD_LETTER_ERROR_HEADER.to_string(), "boom".to_string()),
+                (
+                    DEAD_LETTER_FAILED_AT_HEADER.to_string(),
+                    "2023-11-14T22:13:20+00:00".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_jobs_pause_at_the_limit() {
+        let in_flight = InFlightJobs::new(Some(1));
+        let permit = in_flight.acquire().await;
+        assert!(permit.is_some());
+        let blocked = tokio::time::timeout(Duration::from_millis(50), in_flight.acquire()).await;
+        assert!(blocked.is_err());
+        drop(permit);
+        assert!(in_flight.acquire().await.is_some());
+
+        assert!(InFlightJobs::new(None).acquire().await.is_none());
+    }
+}
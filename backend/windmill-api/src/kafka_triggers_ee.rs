@@ -1,20 +1,890 @@
-use crate::db::DB;
-use axum::Router;
+use std::{future::Future, time::Duration};
+
+use axum::{
+    extract::{Path, Query},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use futures::{stream::FuturesOrdered, StreamExt};
+use http::StatusCode;
+use rand::seq::SliceRandom;
+use rdkafka::{
+    config::ClientConfig,
+    consumer::{BaseConsumer, CommitMode, Consumer, StreamConsumer},
+    message::{Message, OwnedMessage},
+    producer::FutureProducer,
+    Offset, TopicPartitionList,
+};
 use serde::{Deserialize, Serialize};
+use sql_builder::{bind::Bind, SqlBuilder};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use tokio::sync::OwnedSemaphorePermit;
+use uuid::Uuid;
+use windmill_audit::{audit_ee::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{self, to_anyhow, JsonResult},
+    utils::{not_found_if_none, paginate, report_critical_error, Pagination, StripPath},
+    worker::{to_raw_value, CLOUD_HOSTED},
+    INSTANCE_NAME,
+};
+use windmill_queue::PushArgsOwned;
 
-#[derive(Serialize, Deserialize)]
-pub struct KafkaResourceSecurity {}
+use crate::{
+    db::{ApiAuthed, DB},
+    jobs::{run_flow_by_path_inner, run_script_by_path_inner, RunJobQuery},
+    kafka_triggers_dlq::{finalize_pushed_job, InFlightJobs},
+    resources::get_resource_value_interpolated_internal,
+    users::fetch_api_authed,
+};
 
 pub fn workspaced_service() -> Router {
     Router::new()
+        .route("/create", post(create_kafka_trigger))
+        .route("/list", get(list_kafka_triggers))
+        .route("/get/*path", get(get_kafka_trigger))
+        .route("/update/*path", post(update_kafka_trigger))
+        .route("/delete/*path", delete(delete_kafka_trigger))
+        .route("/exists/*path", get(exists_kafka_trigger))
+        .route("/setenabled/*path", post(set_enabled))
+        .route("/test", post(test_kafka_connection))
 }
 
-pub fn start_kafka_consumers(
-    _db: DB,
-    mut _killpill_rx: tokio::sync::broadcast::Receiver<()>,
-) -> () {
-    // implementation is not open source
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KafkaSaslCredentials {
+    mechanism: String,
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "label")]
+pub enum KafkaResourceSecurity {
+    #[serde(rename = "PLAINTEXT")]
+    Plaintext,
+    #[serde(rename = "SASL_PLAINTEXT")]
+    SaslPlaintext(KafkaSaslCredentials),
+    #[serde(rename = "SSL")]
+    Ssl,
+    #[serde(rename = "SASL_SSL")]
+    SaslSsl(KafkaSaslCredentials),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KafkaResource {
+    brokers: Vec<String>,
+    security: KafkaResourceSecurity,
+}
+
+impl KafkaResource {
+    fn client_config(&self) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", self.brokers.join(","));
+        let (protocol, sasl) = match &self.security {
+            KafkaResourceSecurity::Plaintext => ("PLAINTEXT", None),
+            KafkaResourceSecurity::SaslPlaintext(sasl) => ("SASL_PLAINTEXT", Some(sasl)),
+            KafkaResourceSecurity::Ssl => ("SSL", None),
+            KafkaResourceSecurity::SaslSsl(sasl) => ("SASL_SSL", Some(sasl)),
+        };
+        config.set("security.protocol", protocol);
+        if let Some(sasl) = sasl {
+            config
+                .set("sasl.mechanism", &sasl.mechanism)
+                .set("sasl.username", &sasl.username)
+                .set("sasl.password", &sasl.password);
+        }
+        config
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-pub enum KafkaTriggerConfigConnection {}
+#[serde(untagged)]
+pub enum KafkaTriggerConfigConnection {
+    Resource { kafka_resource_path: String },
+    Static(KafkaResource),
+}
+
+#[derive(Deserialize)]
+struct NewKafkaTrigger {
+    path: String,
+    kafka_resource_path: String,
+    group_id: String,
+    topics: Vec<String>,
+    script_path: String,
+    is_flow: bool,
+    enabled: Option<bool>,
+    dead_letter_topic: Option<String>,
+    max_in_flight: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct EditKafkaTrigger {
+    path: String,
+    kafka_resource_path: String,
+    group_id: String,
+    topics: Vec<String>,
+    script_path: String,
+    is_flow: bool,
+    dead_letter_topic: Option<String>,
+    max_in_flight: Option<i32>,
+}
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct KafkaTrigger {
+    workspace_id: String,
+    path: String,
+    kafka_resource_path: String,
+    group_id: String,
+    topics: Vec<String>,
+    script_path: String,
+    is_flow: bool,
+    edited_by: String,
+    email: String,
+    edited_at: chrono::DateTime<chrono::Utc>,
+    server_id: Option<String>,
+    last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
+    extra_perms: serde_json::Value,
+    error: Option<String>,
+    enabled: bool,
+    dead_letter_topic: Option<String>,
+    max_in_flight: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ListKafkaTriggerQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub path: Option<String>,
+    pub is_flow: Option<bool>,
+    pub path_start: Option<String>,
+}
+
+/// A dead-letter topic consumed by the trigger itself would push the failed messages again
+fn check_delivery_settings(
+    topics: &[String],
+    dead_letter_topic: Option<&str>,
+    max_in_flight: Option<i32>,
+) -> error::Result<()> {
+    if topics.is_empty() {
+        return Err(error::Error::BadRequest(
+            "at least one topic is required".to_string(),
+        ));
+    }
+    if let Some(topic) = dead_letter_topic {
+        if topic.trim().is_empty() {
+            return Err(error::Error::BadRequest(
+                "dead_letter_topic cannot be empty".to_string(),
+            ));
+        }
+        if topics.iter().any(|t| t == topic) {
+            return Err(error::Error::BadRequest(format!(
+                "dead_letter_topic {topic} cannot be one of the consumed topics"
+            )));
+        }
+    }
+    if max_in_flight.is_some_and(|max| max < 1) {
+        return Err(error::Error::BadRequest(
+            "max_in_flight must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn list_kafka_triggers(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(lst): Query<ListKafkaTriggerQuery>,
+) -> error::JsonResult<Vec<KafkaTrigger>> {
+    let mut tx = user_db.begin(&authed).await?;
+    let (per_page, offset) = paginate(Pagination { per_page: lst.per_page, page: lst.page });
+    let mut sqlb = SqlBuilder::select_from("kafka_trigger")
+        .field("*")
+        .order_by("edited_at", true)
+        .and_where("workspace_id = ?".bind(&w_id))
+        .offset(offset)
+        .limit(per_page)
+        .clone();
+    if let Some(path) = lst.path {
+        sqlb.and_where_eq("script_path", "?".bind(&path));
+    }
+    if let Some(is_flow) = lst.is_flow {
+        sqlb.and_where_eq("is_flow", "?".bind(&is_flow));
+    }
+    if let Some(path_start) = &lst.path_start {
+        sqlb.and_where_like_left("path", path_start);
+    }
+    let sql = sqlb
+        .sql()
+        .map_err(|e| error::Error::InternalErr(e.to_string()))?;
+    let rows = sqlx::query_as::<_, KafkaTrigger>(&sql)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(rows))
+}
+
+async fn get_kafka_trigger(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> error::JsonResult<KafkaTrigger> {
+    let mut tx = user_db.begin(&authed).await?;
+    let path = path.to_path();
+    let trigger = sqlx::query_as::<_, KafkaTrigger>(
+        "SELECT * FROM kafka_trigger WHERE workspace_id = $1 AND path = $2",
+    )
+    .bind(w_id)
+    .bind(path)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let trigger = not_found_if_none(trigger, "Trigger", path)?;
+
+    Ok(Json(trigger))
+}
+
+async fn create_kafka_trigger(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(ct): Json<NewKafkaTrigger>,
+) -> error::Result<(StatusCode, String)> {
+    if *CLOUD_HOSTED {
+        return Err(error::Error::BadRequest(
+            "Kafka triggers are not supported on multi-tenant cloud, use dedicated cloud or self-host".to_string(),
+        ));
+    }
+
+    check_delivery_settings(
+        &ct.topics,
+        ct.dead_letter_topic.as_deref(),
+        ct.max_in_flight,
+    )?;
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    sqlx::query(
+        "INSERT INTO kafka_trigger (workspace_id, path, kafka_resource_path, group_id, topics, script_path, is_flow, enabled, dead_letter_topic, max_in_flight, edited_by, email, edited_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, now())",
+    )
+    .bind(&w_id)
+    .bind(&ct.path)
+    .bind(ct.kafka_resource_path)
+    .bind(ct.group_id)
+    .bind(ct.topics)
+    .bind(ct.script_path)
+    .bind(ct.is_flow)
+    .bind(ct.enabled.unwrap_or(true))
+    .bind(ct.dead_letter_topic)
+    .bind(ct.max_in_flight)
+    .bind(&authed.username)
+    .bind(&authed.email)
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "kafka_triggers.create",
+        ActionKind::Create,
+        &w_id,
+        Some(ct.path.as_str()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, ct.path))
+}
+
+async fn update_kafka_trigger(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(ct): Json<EditKafkaTrigger>,
+) -> error::Result<String> {
+    let path = path.to_path();
+    check_delivery_settings(
+        &ct.topics,
+        ct.dead_letter_topic.as_deref(),
+        ct.max_in_flight,
+    )?;
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    // important to update server_id to NULL to stop the current consumer
+    let one_o = sqlx::query_scalar::<_, i32>(
+        "UPDATE kafka_trigger SET kafka_resource_path = $1, group_id = $2, topics = $3, script_path = $4, path = $5, is_flow = $6, dead_letter_topic = $7, max_in_flight = $8, edited_by = $9, email = $10, edited_at = now(), server_id = NULL, error = NULL
+        WHERE workspace_id = $11 AND path = $12 RETURNING 1",
+    )
+    .bind(ct.kafka_resource_path)
+    .bind(ct.group_id)
+    .bind(ct.topics)
+    .bind(ct.script_path)
+    .bind(&ct.path)
+    .bind(ct.is_flow)
+    .bind(ct.dead_letter_topic)
+    .bind(ct.max_in_flight)
+    .bind(&authed.username)
+    .bind(&authed.email)
+    .bind(&w_id)
+    .bind(path)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    not_found_if_none(one_o, "Kafka trigger", path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "kafka_triggers.update",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(path.to_string())
+}
+
+#[derive(Deserialize)]
+pub struct SetEnabled {
+    pub enabled: bool,
+}
+
+pub async fn set_enabled(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(payload): Json<SetEnabled>,
+) -> error::Result<String> {
+    let mut tx = user_db.begin(&authed).await?;
+    let path = path.to_path();
+
+    // important to set server_id, last_server_ping and error to NULL to stop the current consumer
+    let one_o = sqlx::query_scalar::<_, i32>(
+        "UPDATE kafka_trigger SET enabled = $1, email = $2, edited_by = $3, edited_at = now(), server_id = NULL, error = NULL
+        WHERE path = $4 AND workspace_id = $5 RETURNING 1",
+    )
+    .bind(payload.enabled)
+    .bind(&authed.email)
+    .bind(&authed.username)
+    .bind(path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    not_found_if_none(one_o, "Kafka trigger", path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "kafka_triggers.setenabled",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("enabled", payload.enabled.to_string().as_ref())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!(
+        "succesfully updated kafka trigger at path {} to status {}",
+        path, payload.enabled
+    ))
+}
+
+async fn delete_kafka_trigger(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> error::Result<String> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+    sqlx::query("DELETE FROM kafka_trigger WHERE workspace_id = $1 AND path = $2")
+        .bind(&w_id)
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "kafka_triggers.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!("Kafka trigger {path} deleted"))
+}
+
+async fn exists_kafka_trigger(
+    Extension(db): Extension<DB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<bool> {
+    let path = path.to_path();
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM kafka_trigger WHERE path = $1 AND workspace_id = $2)",
+    )
+    .bind(path)
+    .bind(&w_id)
+    .fetch_one(&db)
+    .await?;
+    Ok(Json(exists))
+}
+
+async fn get_kafka_resource(
+    authed: &ApiAuthed,
+    user_db: Option<UserDB>,
+    db: &DB,
+    w_id: &str,
+    path: &str,
+) -> error::Result<KafkaResource> {
+    let resource =
+        get_resource_value_interpolated_internal(authed, user_db, db, w_id, path, None, "").await?;
+    let resource = not_found_if_none(resource, "Kafka resource", path)?;
+    serde_json::from_value(resource)
+        .map_err(|e| error::Error::BadConfig(format!("Invalid kafka resource {path}: {e}")))
+}
+
+#[derive(Deserialize)]
+struct TestKafkaConnection {
+    connection: KafkaTriggerConfigConnection,
+}
+
+async fn test_kafka_connection(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(test): Json<TestKafkaConnection>,
+) -> error::Result<()> {
+    let resource = match test.connection {
+        KafkaTriggerConfigConnection::Resource { kafka_resource_path } => {
+            get_kafka_resource(&authed, Some(user_db), &db, &w_id, &kafka_resource_path).await?
+        }
+        KafkaTriggerConfigConnection::Static(resource) => resource,
+    };
+
+    let consumer: BaseConsumer = resource
+        .client_config()
+        .create()
+        .map_err(|e| error::Error::BadConfig(format!("Invalid kafka configuration: {e}")))?;
+    tokio::task::spawn_blocking(move || {
+        consumer
+            .fetch_metadata(None, Duration::from_secs(30))
+            .map(|_| ())
+    })
+    .await
+    .map_err(to_anyhow)?
+    .map_err(|e| error::Error::BadConfig(format!("Error connecting to kafka brokers: {e}")))
+}
+
+async fn listen_to_unlistened_kafka_triggers(
+    db: &DB,
+    killpill_rx: &tokio::sync::broadcast::Receiver<()>,
+) {
+    match sqlx::query_as::<_, KafkaTrigger>(
+        "SELECT * FROM kafka_trigger
+        WHERE enabled IS TRUE AND (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')",
+    )
+    .fetch_all(db)
+    .await
+    {
+        Ok(mut triggers) => {
+            triggers.shuffle(&mut rand::rng());
+            for trigger in triggers {
+                trigger
+                    .maybe_listen_to_kafka(db.clone(), killpill_rx.resubscribe())
+                    .await;
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error fetching kafka triggers: {:?}", err);
+        }
+    };
+}
+
+pub fn start_kafka_consumers(db: DB, mut killpill_rx: tokio::sync::broadcast::Receiver<()>) -> () {
+    tokio::spawn(async move {
+        listen_to_unlistened_kafka_triggers(&db, &killpill_rx).await;
+        loop {
+            tokio::select! {
+                biased;
+                _ = killpill_rx.recv() => {
+                    return;
+                }
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(15)) => {
+                    listen_to_unlistened_kafka_triggers(&db, &killpill_rx).await;
+                }
+            }
+        }
+    });
+}
+
+async fn loop_ping(db: &DB, trigger: &KafkaTrigger, error: Option<&str>) -> () {
+    loop {
+        if let None = trigger.update_ping(db, error).await {
+            return;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+}
+
+impl KafkaTrigger {
+    async fn maybe_listen_to_kafka(
+        self,
+        db: DB,
+        killpill_rx: tokio::sync::broadcast::Receiver<()>,
+    ) -> () {
+        let has_lock = sqlx::query_scalar::<_, bool>(
+            "UPDATE kafka_trigger SET server_id = $1, last_server_ping = now(), error = 'Connecting...'
+            WHERE enabled IS TRUE AND workspace_id = $2 AND path = $3 AND (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
+            RETURNING true",
+        )
+        .bind(&*INSTANCE_NAME)
+        .bind(&self.workspace_id)
+        .bind(&self.path)
+        .fetch_optional(&db)
+        .await;
+        match has_lock {
+            Ok(has_lock) => {
+                if has_lock.unwrap_or(false) {
+                    tokio::spawn(listen_to_kafka(self, db, killpill_rx));
+                } else {
+                    tracing::info!("Kafka trigger {} already being listened to", self.path);
+                }
+            }
+            Err(err) => {
+                tracing::error!(
+                    "Error acquiring lock for kafka trigger {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+    }
+
+    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
+        let updated = sqlx::query_scalar::<_, i32>(
+            "UPDATE kafka_trigger SET last_server_ping = now(), error = $1
+            WHERE workspace_id = $2 AND path = $3 AND server_id = $4 AND enabled IS TRUE RETURNING 1",
+        )
+        .bind(error)
+        .bind(&self.workspace_id)
+        .bind(&self.path)
+        .bind(&*INSTANCE_NAME)
+        .fetch_optional(db)
+        .await;
+        match updated {
+            Ok(updated) => {
+                if updated.is_none() {
+                    // allow faster restart of kafka trigger
+                    sqlx::query(
+                        "UPDATE kafka_trigger SET last_server_ping = NULL
+                        WHERE workspace_id = $1 AND path = $2 AND server_id IS NULL",
+                    )
+                    .bind(&self.workspace_id)
+                    .bind(&self.path)
+                    .execute(db)
+                    .await
+                    .ok();
+                    tracing::info!(
+                        "Kafka trigger {} changed, disabled, or deleted, stopping...",
+                        self.path
+                    );
+                    return None;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Error updating ping of kafka trigger {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+
+        Some(())
+    }
+
+    async fn disable_with_error(&self, db: &DB, error: String) -> () {
+        match sqlx::query(
+            "UPDATE kafka_trigger SET enabled = FALSE, error = $1, server_id = NULL, last_server_ping = NULL
+            WHERE workspace_id = $2 AND path = $3",
+        )
+        .bind(&error)
+        .bind(&self.workspace_id)
+        .bind(&self.path)
+        .execute(db)
+        .await
+        {
+            Ok(_) => {
+                report_critical_error(
+                    format!("Disabling kafka trigger {} because of error: {}", self.path, error),
+                    db.clone(),
+                    Some(&self.workspace_id),
+                    None,
+                )
+                .await;
+            }
+            Err(disable_err) => {
+                report_critical_error(
+                    format!(
+                        "Could not disable kafka trigger {} with err {}, disabling because of error {}",
+                        self.path, disable_err, error
+                    ),
+                    db.clone(),
+                    Some(&self.workspace_id),
+                    None,
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
+        fetch_api_authed(
+            self.edited_by.clone(),
+            self.email.clone(),
+            &self.workspace_id,
+            db,
+            Some(format!("kafka-{}", self.path)),
+        )
+        .await
+    }
+
+    /// The consumer of the topics of the trigger and, if it has a dead-letter topic, the producer
+    /// of the messages of the failed jobs
+    async fn connect(&self, db: &DB) -> error::Result<(StreamConsumer, Option<FutureProducer>)> {
+        let authed = self.fetch_authed(db).await?;
+        let resource = get_kafka_resource(
+            &authed,
+            None,
+            db,
+            &self.workspace_id,
+            &self.kafka_resource_path,
+        )
+        .await?;
+        let mut config = resource.client_config();
+
+        let producer = match self.dead_letter_topic {
+            Some(_) => Some(config.create::<FutureProducer>().map_err(to_anyhow)?),
+            None => None,
+        };
+
+        // offsets are committed once the job of the message is finalized
+        let consumer = config
+            .set("group.id", &self.group_id)
+            .set("enable.auto.commit", "false")
+            .create::<StreamConsumer>()
+            .map_err(to_anyhow)?;
+        let topics = self.topics.iter().map(String::as_str).collect::<Vec<_>>();
+        consumer.subscribe(&topics).map_err(to_anyhow)?;
+
+        Ok((consumer, producer))
+    }
+
+    async fn push_job(&self, db: &DB, msg: &OwnedMessage) -> error::Result<Uuid> {
+        let authed = self.fetch_authed(db).await?;
+        let payload = String::from_utf8_lossy(msg.payload().unwrap_or_default()).into_owned();
+        let args = PushArgsOwned {
+            args: HashMap::from([("msg".to_string(), to_raw_value(&payload))]),
+            extra: Some(HashMap::from([(
+                "wm_trigger".to_string(),
+                to_raw_value(&serde_json::json!({
+                    "kind": "kafka",
+                    "kafka": { "topic": msg.topic(), "group_id": self.group_id },
+                })),
+            )])),
+        };
+
+        let user_db = UserDB::new(db.clone());
+        let runnable_path = StripPath(self.script_path.clone());
+        let (_, job_id) = if self.is_flow {
+            run_flow_by_path_inner(
+                authed,
+                db.clone(),
+                user_db,
+                self.workspace_id.clone(),
+                runnable_path,
+                RunJobQuery::default(),
+                args,
+                None,
+            )
+            .await?
+        } else {
+            run_script_by_path_inner(
+                authed,
+                db.clone(),
+                user_db,
+                self.workspace_id.clone(),
+                runnable_path,
+                RunJobQuery::default(),
+                args,
+                None,
+            )
+            .await?
+        };
+        Uuid::parse_str(&job_id)
+            .map_err(to_anyhow)
+            .map_err(Into::into)
+    }
+
+    /// Pushes the job of `msg` and finalizes it, the in-flight slot is released once done. The
+    /// message is returned to be committed unless producing it to the dead-letter topic failed.
+    fn handle(
+        &self,
+        db: DB,
+        producer: Option<FutureProducer>,
+        msg: OwnedMessage,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> impl Future<Output = error::Result<OwnedMessage>> {
+        let trigger = self.clone();
+        async move {
+            let _permit = permit;
+            let job_id = match trigger.push_job(&db, &msg).await {
+                Ok(job_id) => job_id,
+                Err(err) => {
+                    report_critical_error(
+                        format!(
+                            "Failed to trigger job from kafka trigger {}: {:?}",
+                            trigger.path, err
+                        ),
+                        db.clone(),
+                        Some(&trigger.workspace_id),
+                        None,
+                    )
+                    .await;
+                    return Ok(msg);
+                }
+            };
+            let dead_letter = producer.as_ref().zip(trigger.dead_letter_topic.as_deref());
+            finalize_pushed_job(
+                &db,
+                dead_letter,
+                &trigger.workspace_id,
+                &trigger.path,
+                &msg,
+                job_id,
+            )
+            .await?;
+            Ok(msg)
+        }
+    }
+
+    fn commit(&self, consumer: &StreamConsumer, msg: &OwnedMessage) {
+        let mut tpl = TopicPartitionList::new();
+        let committed = tpl
+            .add_partition_offset(
+                msg.topic(),
+                msg.partition(),
+                Offset::Offset(msg.offset() + 1),
+            )
+            .and_then(|_| consumer.commit(&tpl, CommitMode::Async));
+        if let Err(err) = committed {
+            tracing::error!(
+                "Could not commit offset {} of topic {} for kafka trigger {}: {}",
+                msg.offset(),
+                msg.topic(),
+                self.path,
+                err
+            );
+        }
+    }
+
+    /// Offsets are committed in the order the messages were received, so that a message is never
+    /// committed before the ones preceding it are finalized. Stops without committing if a
+    /// message could not be produced to the dead-letter topic: the trigger is then disabled and
+    /// the message consumed again once it is re-enabled.
+    async fn consume(&self, db: &DB, consumer: &StreamConsumer, producer: Option<FutureProducer>) {
+        let in_flight = InFlightJobs::new(self.max_in_flight);
+        let mut pending = FuturesOrdered::new();
+        loop {
+            tokio::select! {
+                biased;
+                Some(finalized) = pending.next() => match finalized {
+                    Ok(msg) => self.commit(consumer, &msg),
+                    Err(err) => {
+                        tracing::error!("Stopping kafka trigger {}: {:?}", self.path, err);
+                        return;
+                    }
+                },
+                (permit, msg) = async { (in_flight.acquire().await, consumer.recv().await) } => match msg {
+                    Ok(msg) => {
+                        pending.push_back(self.handle(db.clone(), producer.clone(), msg.detach(), permit));
+                    }
+                    Err(err) => {
+                        tracing::error!("Error reading from kafka trigger {}: {}", self.path, err);
+                    }
+                },
+            }
+        }
+    }
+}
+
+async fn listen_to_kafka(
+    trigger: KafkaTrigger,
+    db: DB,
+    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
+) -> () {
+    let (consumer, producer) = tokio::select! {
+        biased;
+        _ = killpill_rx.recv() => {
+            return;
+        },
+        _ = loop_ping(&db, &trigger, Some("Connecting...")) => {
+            return;
+        },
+        connection = trigger.connect(&db) => match connection {
+            Ok(connection) => connection,
+            Err(err) => {
+                trigger.disable_with_error(&db, format!("Error connecting to kafka: {:?}", err)).await;
+                return;
+            }
+        },
+    };
+
+    tracing::info!(
+        "Listening to kafka topics {} for trigger {}",
+        trigger.topics.join(", "),
+        trigger.path
+    );
+
+    tokio::select! {
+        biased;
+        _ = killpill_rx.recv() => {},
+        _ = loop_ping(&db, &trigger, None) => {},
+        _ = trigger.consume(&db, &consumer, producer) => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_delivery_settings() {
+        let topics = vec!["orders".to_string()];
+        assert!(check_delivery_settings(&topics, Some("orders-dlq"), Some(10)).is_ok());
+        assert!(check_delivery_settings(&topics, None, None).is_ok());
+        assert!(check_delivery_settings(&topics, Some("orders"), None).is_err());
+        assert!(check_delivery_settings(&topics, Some(" "), None).is_err());
+        assert!(check_delivery_settings(&topics, None, Some(0)).is_err());
+        assert!(check_delivery_settings(&[], None, None).is_err());
+    }
+}
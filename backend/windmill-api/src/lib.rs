@@ -25,7 +25,6 @@ use crate::{
     webhook_util::WebhookShared,
 };
 
-use anyhow::Context;
 use argon2::Argon2;
 use axum::extract::DefaultBodyLimit;
 use axum::{middleware::from_extractor, routing::get, routing::post, Extension, Router};
@@ -41,6 +40,7 @@ use tokio::sync::RwLock;
 use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
 use tower_http::{
+    compression::{predicate::DefaultPredicate, CompressionLayer, Predicate},
     cors::{Any, CorsLayer},
     trace::TraceLayer,
 };
@@ -49,11 +49,12 @@ use windmill_common::worker::CLOUD_HOSTED;
 use windmill_common::{utils::GIT_VERSION, BASE_URL, INSTANCE_NAME};
 
 use crate::scim_ee::has_scim_token;
-use windmill_common::error::AppError;
+pub use crate::users::{IntrospectionClient, INTROSPECTION_CLIENT};
+pub use crate::workspaces::reload_custom_response_headers;
 
-mod ai;
+pub mod ai;
 mod apps;
-mod args;
+pub mod args;
 mod audit;
 mod auth;
 mod capture;
@@ -62,26 +63,31 @@ mod configs;
 mod db;
 mod drafts;
 pub mod ee;
+mod email_approvals;
 pub mod embeddings;
 mod favorite;
 mod flows;
 mod folders;
 mod granular_acls;
 mod groups;
+mod health;
 #[cfg(feature = "http_trigger")]
 mod http_triggers;
 mod indexer_ee;
-mod inputs;
+pub mod inputs;
 mod integration;
 #[cfg(feature = "postgres_trigger")]
 mod postgres_triggers;
 
 #[cfg(feature = "enterprise")]
 mod apps_ee;
+pub mod job_archive;
 #[cfg(feature = "parquet")]
 mod job_helpers_ee;
 pub mod job_metrics;
 pub mod jobs;
+#[cfg(feature = "kafka")]
+pub mod kafka_triggers_dlq;
 #[cfg(all(feature = "enterprise", feature = "kafka"))]
 mod kafka_triggers_ee;
 #[cfg(all(feature = "enterprise", feature = "nats"))]
@@ -89,6 +95,8 @@ mod nats_triggers_ee;
 #[cfg(feature = "oauth2")]
 pub mod oauth2_ee;
 mod oidc_ee;
+mod path_rename;
+pub mod rate_limit;
 mod raw_apps;
 mod resources;
 mod saml_ee;
@@ -110,13 +118,14 @@ mod users;
 mod users_ee;
 mod utils;
 mod variables;
+pub mod version_check;
 mod webhook_util;
 #[cfg(feature = "websocket")]
 mod websocket_triggers;
-mod workers;
+pub mod workers;
 mod workspaces;
 mod workspaces_ee;
-mod workspaces_export;
+pub mod workspaces_export;
 mod workspaces_extra;
 
 pub const DEFAULT_BODY_LIMIT: usize = 2097152 * 100; // 200MB
@@ -180,15 +189,69 @@ pub async fn add_webhook_allowed_origin(
     next.run(req).await
 }
 
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Id of a request, taken from its `X-Request-ID` header or generated, echoed in the response and
+/// recorded in the span of the request
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+pub async fn add_request_id(
+    mut req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .filter(|x| !x.is_empty() && x.len() <= 128)
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(req).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Makes the W3C trace context (`traceparent`/`tracestate` headers) of the request available to
+/// `windmill_queue::push`, which stores it with the jobs pushed by the request so that the worker
+/// running them can continue the trace of the caller
+#[cfg(feature = "otel")]
+pub async fn scope_trace_context(
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use opentelemetry::{propagation::TextMapPropagator, trace::TraceContextExt};
+
+    let propagator = opentelemetry_sdk::propagation::TraceContextPropagator::new();
+    let cx = propagator.extract(&opentelemetry_http::HeaderExtractor(req.headers()));
+    let trace_context = cx.span().span_context().is_valid().then(|| {
+        let mut trace_context = std::collections::HashMap::new();
+        propagator.inject_context(&cx, &mut trace_context);
+        trace_context
+    });
+    windmill_queue::PUSH_TRACE_CONTEXT
+        .scope(trace_context, next.run(req))
+        .await
+}
+
 #[cfg(not(feature = "tantivy"))]
 type IndexReader = ();
 
 #[cfg(not(feature = "tantivy"))]
 type ServiceLogIndexReader = ();
 
+#[cfg(not(feature = "tantivy"))]
+pub(crate) type ScriptIndexReader = ();
+
 #[cfg(feature = "tantivy")]
 type IndexReader = windmill_indexer::completed_runs_ee::IndexReader;
 #[cfg(feature = "tantivy")]
+pub(crate) type ScriptIndexReader = windmill_indexer::scripts_search::ScriptIndexReader;
+#[cfg(feature = "tantivy")]
 type ServiceLogIndexReader = windmill_indexer::service_logs_ee::ServiceLogIndexReader;
 
 pub async fn run_server(
@@ -213,17 +276,51 @@ pub async fn run_server(
     ));
     let argon2 = Arc::new(Argon2::default());
 
+    #[cfg(feature = "tantivy")]
+    let script_index_reader: Option<ScriptIndexReader> = if server_mode {
+        match windmill_indexer::scripts_search::init_index(&db).await {
+            Ok((reader, writer)) => {
+                let script_indexer_rx = rx.resubscribe();
+                let db = db.clone();
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        windmill_indexer::scripts_search::run_indexer(db, writer, script_indexer_rx)
+                            .await
+                    {
+                        tracing::error!("Script indexer stopped: {e:#}");
+                    }
+                });
+                Some(reader)
+            }
+            Err(e) => {
+                tracing::error!("Could not initialize the script search index: {e:#}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    #[cfg(not(feature = "tantivy"))]
+    let script_index_reader: Option<ScriptIndexReader> = None;
+
     let disable_response_logs = std::env::var("DISABLE_RESPONSE_LOGS")
         .ok()
         .map(|x| x == "true")
         .unwrap_or(false);
 
+    let enable_response_compression = std::env::var("ENABLE_RESPONSE_COMPRESSION")
+        .ok()
+        .map(|x| x == "true")
+        .unwrap_or(false);
+
     let middleware_stack = ServiceBuilder::new()
         .layer(Extension(db.clone()))
         .layer(Extension(user_db.clone()))
         .layer(Extension(auth_cache.clone()))
         .layer(Extension(job_index_reader))
         .layer(Extension(log_index_reader))
+        .layer(Extension(script_index_reader))
         // .layer(Extension(index_writer))
         .layer(CookieManagerLayer::new())
         .layer(Extension(WebhookShared::new(rx.resubscribe(), db.clone())))
@@ -252,6 +349,8 @@ pub async fn run_server(
             });
             if let Err(err) = smtp_server.start_listener_thread(addr).await {
                 tracing::error!("Error starting SMTP server: {err:#}");
+            } else {
+                health::SMTP_LISTENER_STARTED.store(true, std::sync::atomic::Ordering::Relaxed);
             }
         }
     }
@@ -364,6 +463,7 @@ pub async fn run_server(
                         )
                         .nest("/variables", variables::workspaced_service())
                         .nest("/workspaces", workspaces::workspaced_service())
+                        .nest("/workers", workers::workspaced_service())
                         .nest("/oidc", oidc_ee::workspaced_service())
                         .nest("/http_triggers", {
                             #[cfg(feature = "http_trigger")]
@@ -393,7 +493,10 @@ pub async fn run_server(
 
                             #[cfg(not(feature = "postgres_trigger"))]
                             Router::new()
-                        }),
+                        })
+                        .route_layer(axum::middleware::from_fn(
+                            workspaces::add_custom_response_headers,
+                        )),
                 )
                 .nest("/workspaces", workspaces::global_service())
                 .nest(
@@ -468,6 +571,10 @@ pub async fn run_server(
                     "/w/:workspace_id/jobs/slack_approval/:job_id",
                     get(slack_approvals::request_slack_approval),
                 )
+                .route(
+                    "/w/:workspace_id/jobs/email_approval/:job_id",
+                    get(email_approvals::request_email_approval),
+                )
                 .nest(
                     "/w/:workspace_id/resources_u",
                     resources::public_service().layer(cors.clone()),
@@ -502,10 +609,13 @@ pub async fn run_server(
                             Router::new()
                         }
                     }
-                    .layer(from_extractor::<OptAuthed>()),
+                    .layer(from_extractor::<OptAuthed>())
+                    .layer(axum::middleware::map_response(skip_compression)),
                 )
                 .route("/version", get(git_v))
-                .route("/uptodate", get(is_up_to_date))
+                .route("/health", get(health::health))
+                .route("/uptodate", get(version_check::is_up_to_date))
+                .route("/version_check", get(version_check::version_check))
                 .route("/ee_license", get(ee_license))
                 .route("/openapi.yaml", get(openapi))
                 .route("/openapi.json", get(openapi_json)),
@@ -513,6 +623,18 @@ pub async fn run_server(
         .fallback(static_assets::static_handler)
         .layer(middleware_stack);
 
+    // clients opt out per request with `Accept-Encoding: identity`
+    let app = if enable_response_compression {
+        app.layer(
+            CompressionLayer::new()
+                .gzip(true)
+                .zstd(true)
+                .compress_when(DefaultPredicate::new().and(not_skipped_compression)),
+        )
+    } else {
+        app
+    };
+
     let app = if disable_response_logs {
         app
     } else {
@@ -525,6 +647,12 @@ pub async fn run_server(
         )
     };
 
+    #[cfg(feature = "otel")]
+    let app = app.layer(axum::middleware::from_fn(scope_trace_context));
+
+    // outermost so that the id is set before the span of the request is made
+    let app = app.layer(axum::middleware::from_fn(add_request_id));
+
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     let port = listener.local_addr().map(|x| x.port()).unwrap_or(8000);
     let ip = listener
@@ -554,33 +682,6 @@ pub async fn run_server(
     Ok(())
 }
 
-async fn is_up_to_date() -> Result<String, AppError> {
-    let error_reading_version = || anyhow::anyhow!("Error reading latest released version");
-    let version = HTTP_CLIENT
-        .get("https://api.github.com/repos/windmill-labs/windmill/releases/latest")
-        .timeout(Duration::from_secs(10))
-        .send()
-        .await
-        .context("Impossible to reach api.github")?
-        .json::<serde_json::Value>()
-        .await?
-        .get("tag_name")
-        .ok_or_else(error_reading_version)?
-        .as_str()
-        .ok_or_else(error_reading_version)?
-        .to_string();
-    let release = GIT_VERSION
-        .split('-')
-        .next()
-        .ok_or_else(error_reading_version)?
-        .to_string();
-    if version == release {
-        Ok("yes".to_string())
-    } else {
-        Ok(format!("Update: {GIT_VERSION} -> {version}"))
-    }
-}
-
 #[cfg(feature = "enterprise")]
 async fn git_v() -> String {
     format!("EE {GIT_VERSION}")
@@ -607,6 +708,25 @@ async fn ee_license() -> String {
     }
 }
 
+/// Responses left uncompressed by the compression layer, e.g. those of the http triggers that may
+/// already return compressed or binary data
+#[derive(Clone)]
+struct SkipCompression;
+
+async fn skip_compression(mut response: axum::response::Response) -> axum::response::Response {
+    response.extensions_mut().insert(SkipCompression);
+    response
+}
+
+fn not_skipped_compression(
+    _: http::StatusCode,
+    _: http::Version,
+    _: &http::HeaderMap,
+    extensions: &http::Extensions,
+) -> bool {
+    extensions.get::<SkipCompression>().is_none()
+}
+
 async fn openapi() -> &'static str {
     include_str!("../openapi-deref.yaml")
 }
@@ -8,10 +8,478 @@
 
 use axum::Router;
 
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+use {
+    crate::{db::ApiAuthed, users::Tokened},
+    axum::{
+        extract::{Extension, Path},
+        routing::{get, post},
+        Json,
+    },
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine},
+    jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation},
+    rsa::{
+        pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, LineEnding},
+        traits::PublicKeyParts,
+        RsaPrivateKey,
+    },
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::sync::Arc,
+    tokio::sync::RwLock,
+    uuid::Uuid,
+    windmill_audit::{audit_ee::audit_log, ActionKind},
+    windmill_common::{
+        auth::{JWTAuthClaims, JWT_SECRET},
+        error::{Error, JsonResult, Result},
+        global_settings::{load_value_from_global_settings, OIDC_SIGNING_KEY_SETTING},
+        utils::not_found_if_none,
+        workspaces::WorkspaceOidcSettings,
+        BASE_URL, DB,
+    },
+};
+
 pub fn global_service() -> Router {
+    #[cfg(all(feature = "enterprise", feature = "oidc"))]
+    {
+        Router::new().route("/jwks", get(get_jwks)).route(
+            "/.well-known/openid-configuration",
+            get(get_openid_configuration),
+        )
+    }
+
+    #[cfg(not(all(feature = "enterprise", feature = "oidc")))]
     Router::new()
 }
 
 pub fn workspaced_service() -> Router {
+    #[cfg(all(feature = "enterprise", feature = "oidc"))]
+    {
+        // jobs fetch their token with a GET request, kept for compatibility
+        Router::new().route("/token/:audience", post(get_oidc_token).get(get_oidc_token))
+    }
+
+    #[cfg(not(all(feature = "enterprise", feature = "oidc")))]
     Router::new()
 }
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+lazy_static::lazy_static! {
+    static ref OIDC_SIGNING_KEY: RwLock<Option<Arc<OidcSigningKey>>> = RwLock::new(None);
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+const OIDC_SIGNING_KEY_BITS: usize = 2048;
+
+/// Instance wide RSA key the OIDC tokens are signed with, its public part is served by the jwks endpoint
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+struct OidcSigningKey {
+    kid: String,
+    encoding_key: EncodingKey,
+    n: String,
+    e: String,
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+impl OidcSigningKey {
+    fn generate_pem() -> Result<String> {
+        let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, OIDC_SIGNING_KEY_BITS)
+            .map_err(|e| Error::InternalErr(format!("generating oidc signing key: {e:#}")))?;
+        let pem = key
+            .to_pkcs1_pem(LineEnding::LF)
+            .map_err(|e| Error::InternalErr(format!("encoding oidc signing key: {e:#}")))?;
+        Ok(pem.to_string())
+    }
+
+    fn from_pem(pem: &str) -> Result<Self> {
+        let key = RsaPrivateKey::from_pkcs1_pem(pem)
+            .map_err(|e| Error::InternalErr(format!("parsing oidc signing key: {e:#}")))?;
+        let encoding_key = EncodingKey::from_rsa_pem(pem.as_bytes())
+            .map_err(|e| Error::InternalErr(format!("loading oidc signing key: {e:#}")))?;
+        let n = key.n().to_bytes_be();
+        let e = key.e().to_bytes_be();
+        Ok(Self {
+            kid: hex::encode(&Sha256::digest(&n)[..8]),
+            encoding_key,
+            n: URL_SAFE_NO_PAD.encode(n),
+            e: URL_SAFE_NO_PAD.encode(e),
+        })
+    }
+
+    fn jwk(&self) -> serde_json::Value {
+        serde_json::json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": self.kid,
+            "n": self.n,
+            "e": self.e,
+        })
+    }
+
+    fn sign(&self, claims: &OidcClaims) -> Result<String> {
+        let header = Header { kid: Some(self.kid.clone()), ..Header::new(Algorithm::RS256) };
+        jsonwebtoken::encode(&header, claims, &self.encoding_key)
+            .map_err(|e| Error::InternalErr(format!("signing oidc token: {e:#}")))
+    }
+}
+
+/// Loads the signing key from the global settings, generating it on first use
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn signing_key(db: &DB) -> Result<Arc<OidcSigningKey>> {
+    if let Some(key) = OIDC_SIGNING_KEY.read().await.as_ref() {
+        return Ok(key.clone());
+    }
+
+    let mut cached = OIDC_SIGNING_KEY.write().await;
+    if let Some(key) = cached.as_ref() {
+        return Ok(key.clone());
+    }
+
+    let pem = match load_signing_key_pem(db).await? {
+        Some(pem) => pem,
+        None => {
+            tracing::info!("No oidc signing key found, generating one");
+            let pem = tokio::task::spawn_blocking(OidcSigningKey::generate_pem)
+                .await
+                .map_err(|e| Error::InternalErr(format!("generating oidc signing key: {e:#}")))??;
+            // another server may be generating one at the same time, the first one saved wins
+            sqlx::query(
+                "INSERT INTO global_settings (name, value) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+            )
+            .bind(OIDC_SIGNING_KEY_SETTING)
+            .bind(serde_json::Value::String(pem))
+            .execute(db)
+            .await?;
+            not_found_if_none(
+                load_signing_key_pem(db).await?,
+                "Setting",
+                OIDC_SIGNING_KEY_SETTING,
+            )?
+        }
+    };
+
+    let key = Arc::new(OidcSigningKey::from_pem(&pem)?);
+    *cached = Some(key.clone());
+    Ok(key)
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn load_signing_key_pem(db: &DB) -> Result<Option<String>> {
+    Ok(
+        load_value_from_global_settings(db, OIDC_SIGNING_KEY_SETTING)
+            .await?
+            .and_then(|v| serde_json::from_value::<String>(v).ok()),
+    )
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn issuer() -> String {
+    format!("{}/api/oidc", BASE_URL.read().await)
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn get_jwks(Extension(db): Extension<DB>) -> JsonResult<serde_json::Value> {
+    let key = signing_key(&db).await?;
+    Ok(Json(serde_json::json!({ "keys": [key.jwk()] })))
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn get_openid_configuration() -> JsonResult<serde_json::Value> {
+    let issuer = issuer().await;
+    Ok(Json(serde_json::json!({
+        "issuer": issuer,
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["id_token"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "claims_supported": ["sub", "aud", "exp", "iat", "iss", "workspace", "email", "job_id", "script_path", "flow_path"],
+    })))
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+#[derive(sqlx::FromRow, Debug)]
+struct OidcJobContext {
+    job_id: Uuid,
+    script_path: Option<String>,
+    flow_path: Option<String>,
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct OidcClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    iat: i64,
+    nbf: i64,
+    exp: i64,
+    workspace: String,
+    email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flow_path: Option<String>,
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+impl OidcClaims {
+    fn new(
+        issuer: String,
+        audience: &str,
+        w_id: &str,
+        authed: &ApiAuthed,
+        job: Option<&OidcJobContext>,
+        ttl_secs: u32,
+        now: i64,
+    ) -> Self {
+        let script_path = job.and_then(|j| j.script_path.clone());
+        let flow_path = job.and_then(|j| j.flow_path.clone());
+        // the subject is what cloud providers trust policies match on, so it is the runnable when there is one
+        let sub = match (&flow_path, &script_path) {
+            (Some(flow_path), _) => format!("{w_id}:flow:{flow_path}"),
+            (None, Some(script_path)) => format!("{w_id}:script:{script_path}"),
+            (None, None) => format!("{w_id}:user:{}", authed.username),
+        };
+        Self {
+            iss: issuer,
+            sub,
+            aud: audience.to_string(),
+            iat: now,
+            nbf: now,
+            exp: now + ttl_secs as i64,
+            workspace: w_id.to_string(),
+            email: authed.email.clone(),
+            job_id: job.map(|j| j.job_id.to_string()),
+            script_path,
+            flow_path,
+        }
+    }
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+fn check_audience(settings: &WorkspaceOidcSettings, audience: &str) -> Result<()> {
+    if settings.is_audience_allowed(audience) {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(format!(
+            "audience {audience} is not in the allowed OIDC audiences of the workspace"
+        )))
+    }
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn workspace_oidc_settings(db: &DB, w_id: &str) -> Result<WorkspaceOidcSettings> {
+    let settings = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+        "SELECT oidc FROM workspace_settings WHERE workspace_id = $1",
+    )
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    Ok(settings
+        .map(serde_json::from_value)
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Returns the job a token was issued for, if any
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn job_id_of_token(db: &DB, token: &str) -> Result<Option<Uuid>> {
+    if let Some(jwt) = token.strip_prefix("jwt_") {
+        let jwt_secret = JWT_SECRET.read().await;
+        let claims = jsonwebtoken::decode::<JWTAuthClaims>(
+            jwt,
+            &DecodingKey::from_secret(jwt_secret.as_bytes()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|e| Error::NotAuthorized(format!("invalid job token: {e:#}")))?
+        .claims;
+        Ok(claims.job_id.and_then(|id| Uuid::parse_str(&id).ok()))
+    } else {
+        let job = sqlx::query_scalar::<_, Option<Uuid>>("SELECT job FROM token WHERE token = $1")
+            .bind(token)
+            .fetch_optional(db)
+            .await?
+            .flatten();
+        Ok(job)
+    }
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn job_context(db: &DB, w_id: &str, job_id: Uuid) -> Result<OidcJobContext> {
+    let job = sqlx::query_as::<_, OidcJobContext>(
+        "SELECT j.id AS job_id, j.script_path, f.script_path AS flow_path
+        FROM queue j
+        LEFT JOIN queue f ON f.id = COALESCE(j.root_job, j.parent_job) AND f.workspace_id = j.workspace_id
+        WHERE j.id = $1 AND j.workspace_id = $2",
+    )
+    .bind(job_id)
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?;
+    job.ok_or_else(|| {
+        Error::NotAuthorized(format!(
+            "job {job_id} is not running, OIDC tokens can only be issued to running jobs"
+        ))
+    })
+}
+
+#[cfg(all(feature = "enterprise", feature = "oidc"))]
+async fn get_oidc_token(
+    authed: ApiAuthed,
+    Tokened { token }: Tokened,
+    Extension(db): Extension<DB>,
+    Path((w_id, audience)): Path<(String, String)>,
+) -> JsonResult<String> {
+    let settings = workspace_oidc_settings(&db, &w_id).await?;
+    check_audience(&settings, &audience)?;
+
+    let job = match job_id_of_token(&db, &token).await? {
+        Some(job_id) => Some(job_context(&db, &w_id, job_id).await?),
+        None => None,
+    };
+
+    let claims = OidcClaims::new(
+        issuer().await,
+        &audience,
+        &w_id,
+        &authed,
+        job.as_ref(),
+        settings.token_ttl_secs(),
+        chrono::Utc::now().timestamp(),
+    );
+    let oidc_token = signing_key(&db).await?.sign(&claims)?;
+
+    let job_id = claims.job_id.unwrap_or_default();
+    audit_log(
+        &db,
+        &authed,
+        "oidc.token",
+        ActionKind::Execute,
+        &w_id,
+        Some(&audience),
+        Some([("job_id", job_id.as_str())].into()),
+    )
+    .await?;
+
+    Ok(Json(oidc_token))
+}
+
+#[cfg(all(test, feature = "enterprise", feature = "oidc"))]
+mod tests {
+    use super::*;
+
+    fn authed() -> ApiAuthed {
+        ApiAuthed {
+            email: "alice@windmill.dev".to_string(),
+            username: "alice".to_string(),
+            is_admin: false,
+            is_operator: false,
+            groups: vec![],
+            folders: vec![],
+            scopes: None,
+            username_override: None,
+        }
+    }
+
+    #[test]
+    fn test_disallowed_audience_is_rejected() {
+        let settings = WorkspaceOidcSettings {
+            allowed_audiences: vec!["sts.amazonaws.com".to_string()],
+            token_ttl_secs: None,
+        };
+        assert!(check_audience(&settings, "sts.amazonaws.com").is_ok());
+        assert!(matches!(
+            check_audience(&settings, "https://iam.googleapis.com"),
+            Err(Error::NotAuthorized(_))
+        ));
+        assert!(check_audience(&WorkspaceOidcSettings::default(), "sts.amazonaws.com").is_err());
+    }
+
+    #[test]
+    fn test_token_ttl_is_capped() {
+        let settings =
+            WorkspaceOidcSettings { allowed_audiences: vec![], token_ttl_secs: Some(86400) };
+        assert_eq!(settings.token_ttl_secs(), 3600);
+    }
+
+    #[test]
+    fn test_oidc_claims() {
+        let job = OidcJobContext {
+            job_id: Uuid::nil(),
+            script_path: Some("f/aws/deploy".to_string()),
+            flow_path: Some("f/aws/release".to_string()),
+        };
+        let claims = OidcClaims::new(
+            "https://windmill.example.com/api/oidc".to_string(),
+            "sts.amazonaws.com",
+            "demo",
+            &authed(),
+            Some(&job),
+            600,
+            1_700_000_000,
+        );
+        assert_eq!(
+            serde_json::to_value(&claims).unwrap(),
+            serde_json::json!({
+                "iss": "https://windmill.example.com/api/oidc",
+                "sub": "demo:flow:f/aws/release",
+                "aud": "sts.amazonaws.com",
+                "iat": 1_700_000_000,
+                "nbf": 1_700_000_000,
+                "exp": 1_700_000_600,
+                "workspace": "demo",
+                "email": "alice@windmill.dev",
+                "job_id": Uuid::nil().to_string(),
+                "script_path": "f/aws/deploy",
+                "flow_path": "f/aws/release",
+            })
+        );
+
+        let claims = OidcClaims::new(
+            "https://windmill.example.com/api/oidc".to_string(),
+            "sts.amazonaws.com",
+            "demo",
+            &authed(),
+            None,
+            600,
+            1_700_000_000,
+        );
+        let value = serde_json::to_value(&claims).unwrap();
+        assert_eq!(value["sub"], "demo:user:alice");
+        assert!(value.get("job_id").is_none());
+        assert!(value.get("script_path").is_none());
+    }
+
+    #[test]
+    fn test_token_verifies_with_jwk() {
+        let key = OidcSigningKey::from_pem(&OidcSigningKey::generate_pem().unwrap()).unwrap();
+        let claims = OidcClaims::new(
+            "https://windmill.example.com/api/oidc".to_string(),
+            "sts.amazonaws.com",
+            "demo",
+            &authed(),
+            None,
+            600,
+            chrono::Utc::now().timestamp(),
+        );
+        let token = key.sign(&claims).unwrap();
+
+        let jwk = key.jwk();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), jwk["kid"].as_str());
+
+        let decoding_key = DecodingKey::from_rsa_components(&key.n, &key.e).unwrap();
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&["sts.amazonaws.com"]);
+        let decoded =
+            jsonwebtoken::decode::<OidcClaims>(&token, &decoding_key, &validation).unwrap();
+        assert_eq!(decoded.claims, claims);
+
+        validation.set_audience(&["https://iam.googleapis.com"]);
+        assert!(jsonwebtoken::decode::<OidcClaims>(&token, &decoding_key, &validation).is_err());
+    }
+}
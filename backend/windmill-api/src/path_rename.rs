@@ -0,0 +1,660 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Renaming of the path of a script or a flow along with the flows, apps, schedules and triggers
+//! referencing it
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{Postgres, Transaction};
+use windmill_audit::{audit_ee::audit_log, ActionKind};
+use windmill_common::{
+    error::{Error, Result},
+    schedule::Schedule,
+    scripts::ScriptHash,
+};
+use windmill_queue::schedule::push_scheduled_job;
+
+use crate::{
+    db::{ApiAuthed, DB},
+    folders::{move_flow_paths, MovedPaths},
+    schedule::clear_schedule,
+    users::require_is_writer,
+    webhook_util::{DeployAction, DeployEvent, DeployKind, WebhookShared},
+};
+
+/// tables of the triggers, each runs either a script or a flow at `script_path`
+pub(crate) const TRIGGER_TABLES: [&str; 5] = [
+    "http_trigger",
+    "websocket_trigger",
+    "kafka_trigger",
+    "postgres_trigger",
+    "nats_trigger",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenamedKind {
+    Script,
+    Flow,
+}
+
+impl RenamedKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RenamedKind::Script => "script",
+            RenamedKind::Flow => "flow",
+        }
+    }
+
+    fn is_flow(&self) -> bool {
+        *self == RenamedKind::Flow
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RenamePath {
+    pub new_path: String,
+}
+
+#[derive(Deserialize)]
+pub struct RenameQuery {
+    /// only return the references that would be rewritten
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RewrittenReference {
+    /// flow, app, schedule or the table of the trigger
+    pub kind: String,
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct RenameResult {
+    pub old_path: String,
+    pub new_path: String,
+    pub dry_run: bool,
+    pub rewritten: Vec<RewrittenReference>,
+    /// hash of the script or version id of the flow at its new path
+    #[serde(skip)]
+    pub version: Option<String>,
+}
+
+impl RenameResult {
+    /// Notifies the deploy webhook of the workspace that the item was deleted from its former path
+    /// and deployed at the new one, along with the new versions of the flows and apps that were
+    /// rewritten
+    pub fn send_deploy_events(
+        &self,
+        webhook: &WebhookShared,
+        authed: &ApiAuthed,
+        w_id: &str,
+        kind: RenamedKind,
+    ) {
+        if self.dry_run {
+            return;
+        }
+        let deploy_kind = match kind {
+            RenamedKind::Script => DeployKind::Script,
+            RenamedKind::Flow => DeployKind::Flow,
+        };
+        webhook.send_deploy_event(DeployEvent::new(
+            deploy_kind,
+            DeployAction::Delete,
+            w_id,
+            &self.old_path,
+            None,
+            authed,
+        ));
+        webhook.send_deploy_event(DeployEvent::new(
+            deploy_kind,
+            DeployAction::Deploy,
+            w_id,
+            &self.new_path,
+            self.version.clone(),
+            authed,
+        ));
+        for reference in &self.rewritten {
+            let kind = match reference.kind.as_str() {
+                "flow" => DeployKind::Flow,
+                "app" => DeployKind::App,
+                _ => continue,
+            };
+            webhook.send_deploy_event(DeployEvent::new(
+                kind,
+                DeployAction::Deploy,
+                w_id,
+                &reference.path,
+                None,
+                authed,
+            ));
+        }
+    }
+}
+
+/// Sets the `path` of the objects of `value` matching `is_ref` to `new_path`, returns whether
+/// any was rewritten
+fn rewrite_refs(
+    value: &mut Value,
+    is_ref: &dyn Fn(&Map<String, Value>) -> bool,
+    new_path: &str,
+) -> bool {
+    match value {
+        Value::Object(o) => {
+            let mut rewritten = false;
+            if is_ref(o) {
+                o.insert("path".to_string(), Value::String(new_path.to_string()));
+                rewritten = true;
+            }
+            for v in o.values_mut() {
+                rewritten |= rewrite_refs(v, is_ref, new_path);
+            }
+            rewritten
+        }
+        Value::Array(a) => a.iter_mut().fold(false, |rewritten, v| {
+            rewrite_refs(v, is_ref, new_path) | rewritten
+        }),
+        _ => false,
+    }
+}
+
+/// Flow modules reference scripts and flows with `{ "type": "script" | "flow", "path": ... }`
+fn rewrite_flow_value(
+    value: &mut Value,
+    kind: RenamedKind,
+    old_path: &str,
+    new_path: &str,
+) -> bool {
+    let is_ref = |o: &Map<String, Value>| {
+        o.get("type").and_then(Value::as_str) == Some(kind.as_str())
+            && o.get("path").and_then(Value::as_str) == Some(old_path)
+    };
+    rewrite_refs(value, &is_ref, new_path)
+}
+
+/// App components reference scripts and flows with
+/// `{ "type": "runnableByPath", "runType": "script" | "flow", "path": ... }`
+fn rewrite_app_value(value: &mut Value, kind: RenamedKind, old_path: &str, new_path: &str) -> bool {
+    let is_ref = |o: &Map<String, Value>| {
+        o.get("type").and_then(Value::as_str) == Some("runnableByPath")
+            && o.get("runType").and_then(Value::as_str) == Some(kind.as_str())
+            && o.get("path").and_then(Value::as_str) == Some(old_path)
+    };
+    rewrite_refs(value, &is_ref, new_path)
+}
+
+/// The triggerables of an app policy are keyed by `<kind>/<path>` or `<component>:<kind>/<path>`
+fn rewrite_app_policy(
+    policy: &mut Value,
+    kind: RenamedKind,
+    old_path: &str,
+    new_path: &str,
+) -> bool {
+    let old_key = format!("{}/{old_path}", kind.as_str());
+    let new_key = format!("{}/{new_path}", kind.as_str());
+    let mut rewritten = false;
+    for field in ["triggerables", "triggerables_v2"] {
+        let Some(Value::Object(triggerables)) = policy.get_mut(field) else {
+            continue;
+        };
+        let renamed = triggerables
+            .keys()
+            .filter_map(|k| {
+                let new_k = if *k == old_key {
+                    new_key.clone()
+                } else {
+                    let (component, path) = k.split_once(':')?;
+                    if path != old_key {
+                        return None;
+                    }
+                    format!("{component}:{new_key}")
+                };
+                Some((k.clone(), new_k))
+            })
+            .collect::<Vec<_>>();
+        for (k, new_k) in renamed {
+            if let Some(v) = triggerables.remove(&k) {
+                triggerables.insert(new_k, v);
+                rewritten = true;
+            }
+        }
+    }
+    rewritten
+}
+
+async fn move_script(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    old_path: &str,
+    new_path: &str,
+) -> Result<()> {
+    // runs already queued keep working as they reference the script by hash
+    sqlx::query("UPDATE script SET path = $1 WHERE path = $2 AND workspace_id = $3")
+        .bind(new_path)
+        .bind(old_path)
+        .bind(w_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Deploys a new version of the flows whose latest version references the renamed path. Former
+/// versions are left untouched as they are cached by id.
+async fn rewrite_flows(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    kind: RenamedKind,
+    old_path: &str,
+    new_path: &str,
+) -> Result<Vec<RewrittenReference>> {
+    let flows = sqlx::query_as::<_, (String, i64, Value)>(
+        "SELECT flow.path, flow_version.id, flow_version.value
+        FROM flow
+        JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+        WHERE flow.workspace_id = $1
+        AND jsonb_path_exists(
+            flow_version.value,
+            '$.** ? (@.type == $kind && @.path == $path)',
+            jsonb_build_object('kind', $2::text, 'path', $3::text)
+        )
+        ORDER BY flow.path",
+    )
+    .bind(w_id)
+    .bind(kind.as_str())
+    .bind(old_path)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut rewritten = vec![];
+    for (path, version_id, mut value) in flows {
+        if !rewrite_flow_value(&mut value, kind, old_path, new_path) {
+            continue;
+        }
+        let version = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO flow_version (workspace_id, path, value, schema, created_by)
+            SELECT workspace_id, path, $1, schema, $2 FROM flow_version WHERE id = $3
+            RETURNING id",
+        )
+        .bind(&value)
+        .bind(&authed.username)
+        .bind(version_id)
+        .fetch_one(&mut **tx)
+        .await?;
+        sqlx::query(
+            "UPDATE flow SET value = $1, versions = array_append(versions, $2), edited_by = $3, edited_at = now()
+            WHERE path = $4 AND workspace_id = $5",
+        )
+        .bind(&value)
+        .bind(version)
+        .bind(&authed.username)
+        .bind(&path)
+        .bind(w_id)
+        .execute(&mut **tx)
+        .await?;
+        rewritten.push(RewrittenReference { kind: "flow".to_string(), path });
+    }
+    Ok(rewritten)
+}
+
+/// Deploys a new version of the apps whose latest version or policy references the renamed path
+async fn rewrite_apps(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    kind: RenamedKind,
+    old_path: &str,
+    new_path: &str,
+) -> Result<Vec<RewrittenReference>> {
+    let apps = sqlx::query_as::<_, (i64, String, Value, Value)>(
+        "SELECT app.id, app.path, app.policy, app_version.value::jsonb
+        FROM app
+        JOIN app_version ON app_version.id = app.versions[array_upper(app.versions, 1)]
+        WHERE app.workspace_id = $1
+        AND (jsonb_path_exists(
+            app_version.value::jsonb,
+            '$.** ? (@.type == \"runnableByPath\" && @.runType == $kind && @.path == $path)',
+            jsonb_build_object('kind', $2::text, 'path', $3::text)
+        ) OR strpos(app.policy::text, $4) > 0)
+        ORDER BY app.path",
+    )
+    .bind(w_id)
+    .bind(kind.as_str())
+    .bind(old_path)
+    .bind(format!("{}/{old_path}\"", kind.as_str()))
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut rewritten = vec![];
+    for (id, path, mut policy, mut value) in apps {
+        let value_rewritten = rewrite_app_value(&mut value, kind, old_path, new_path);
+        let policy_rewritten = rewrite_app_policy(&mut policy, kind, old_path, new_path);
+        if !value_rewritten && !policy_rewritten {
+            continue;
+        }
+        if value_rewritten {
+            let version = sqlx::query_scalar::<_, i64>(
+                "INSERT INTO app_version (app_id, value, created_by)
+                VALUES ($1, $2::text::json, $3) RETURNING id",
+            )
+            .bind(id)
+            //to preserve key orders
+            .bind(serde_json::to_string(&value).unwrap())
+            .bind(&authed.username)
+            .fetch_one(&mut **tx)
+            .await?;
+            sqlx::query(
+                "UPDATE app SET versions = array_append(versions, $1::bigint) WHERE id = $2",
+            )
+            .bind(version)
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+        }
+        sqlx::query("UPDATE app SET policy = $1 WHERE id = $2")
+            .bind(&policy)
+            .bind(id)
+            .execute(&mut **tx)
+            .await?;
+        rewritten.push(RewrittenReference { kind: "app".to_string(), path });
+    }
+    Ok(rewritten)
+}
+
+/// The user must be able to write every item referencing the renamed path, as they are rewritten
+/// on their behalf. The paths of those items are not reported otherwise, the dry run included.
+async fn require_writer_of_references(
+    authed: &ApiAuthed,
+    db: &DB,
+    w_id: &str,
+    old_path: &str,
+    rewritten: &[RewrittenReference],
+) -> Result<()> {
+    if authed.is_admin {
+        return Ok(());
+    }
+    for reference in rewritten {
+        // the kind of a reference is the table of the item
+        let query = format!(
+            "SELECT extra_perms FROM {} WHERE path = $1 AND workspace_id = $2",
+            reference.kind
+        );
+        let is_writer = require_is_writer(
+            authed,
+            &reference.path,
+            w_id,
+            db.clone(),
+            &query,
+            &reference.kind,
+        )
+        .await
+        .is_ok();
+        if !is_writer {
+            return Err(Error::NotAuthorized(format!(
+                "{old_path} is referenced by items {} cannot write, ask an admin to rename it",
+                authed.username
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Renames `old_path` to `new_path` and rewrites the references to it in a single transaction.
+/// The transaction is rolled back on a dry run, which reports what would have been rewritten.
+/// Callers must check that the user can write both paths.
+pub async fn rename_path(
+    authed: &ApiAuthed,
+    db: &DB,
+    w_id: &str,
+    kind: RenamedKind,
+    old_path: &str,
+    new_path: &str,
+    dry_run: bool,
+) -> Result<RenameResult> {
+    let segments = new_path.split('/').collect::<Vec<_>>();
+    if segments.len() < 3
+        || !matches!(segments[0], "u" | "f")
+        || segments.iter().any(|s| s.is_empty())
+    {
+        return Err(Error::BadRequest(format!(
+            "{new_path} is not a valid path, it must be of the form u/<user>/<name> or f/<folder>/<name>"
+        )));
+    }
+    if old_path == new_path {
+        return Err(Error::BadRequest(format!(
+            "{old_path} is already the path of this {}",
+            kind.as_str()
+        )));
+    }
+
+    let exists_query = match kind {
+        RenamedKind::Script => {
+            "SELECT EXISTS(SELECT 1 FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false)"
+        }
+        RenamedKind::Flow => "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+    };
+    let taken_query = match kind {
+        RenamedKind::Script => {
+            "SELECT EXISTS(SELECT 1 FROM script WHERE path = $1 AND workspace_id = $2)"
+        }
+        RenamedKind::Flow => exists_query,
+    };
+
+    let mut tx = db.begin().await?;
+
+    let exists = sqlx::query_scalar::<_, bool>(exists_query)
+        .bind(old_path)
+        .bind(w_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !exists {
+        return Err(Error::NotFound(format!(
+            "{} {old_path} not found",
+            kind.as_str()
+        )));
+    }
+    let taken = sqlx::query_scalar::<_, bool>(taken_query)
+        .bind(new_path)
+        .bind(w_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if taken {
+        return Err(Error::BadRequest(format!(
+            "A {} already exists at {new_path}",
+            kind.as_str()
+        )));
+    }
+
+    let version = match kind {
+        RenamedKind::Script => {
+            move_script(&mut tx, w_id, old_path, new_path).await?;
+            sqlx::query_scalar::<_, i64>(
+                "SELECT hash FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false
+                ORDER BY created_at DESC LIMIT 1",
+            )
+            .bind(new_path)
+            .bind(w_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|hash| ScriptHash(hash).to_string())
+        }
+        RenamedKind::Flow => {
+            move_flow_paths(&mut tx, w_id, MovedPaths::Exact, old_path, new_path).await?;
+            sqlx::query_scalar::<_, Option<i64>>(
+                "SELECT versions[array_upper(versions, 1)] FROM flow
+                WHERE path = $1 AND workspace_id = $2",
+            )
+            .bind(new_path)
+            .bind(w_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .flatten()
+            .map(|version| version.to_string())
+        }
+    };
+    for table in ["capture_config", "capture"] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET path = $1 WHERE path = $2 AND workspace_id = $3 AND is_flow = $4"
+        ))
+        .bind(new_path)
+        .bind(old_path)
+        .bind(w_id)
+        .bind(kind.is_flow())
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        "UPDATE draft SET path = $1 WHERE path = $2 AND workspace_id = $3 AND typ::text = $4",
+    )
+    .bind(new_path)
+    .bind(old_path)
+    .bind(w_id)
+    .bind(kind.as_str())
+    .execute(&mut *tx)
+    .await?;
+
+    let mut rewritten = rewrite_flows(&mut tx, authed, w_id, kind, old_path, new_path).await?;
+    rewritten.extend(rewrite_apps(&mut tx, authed, w_id, kind, old_path, new_path).await?);
+
+    let schedules = sqlx::query_as::<_, Schedule>(
+        "UPDATE schedule SET script_path = $1
+        WHERE script_path = $2 AND workspace_id = $3 AND is_flow = $4 RETURNING *",
+    )
+    .bind(new_path)
+    .bind(old_path)
+    .bind(w_id)
+    .bind(kind.is_flow())
+    .fetch_all(&mut *tx)
+    .await?;
+    for schedule in schedules {
+        clear_schedule(&mut tx, &schedule.path, w_id).await?;
+        if schedule.enabled {
+            tx = push_scheduled_job(db, tx, &schedule, None).await?;
+        }
+        rewritten.push(RewrittenReference { kind: "schedule".to_string(), path: schedule.path });
+    }
+
+    for table in TRIGGER_TABLES {
+        let triggers = sqlx::query_scalar::<_, String>(&format!(
+            "UPDATE {table} SET script_path = $1
+            WHERE script_path = $2 AND workspace_id = $3 AND is_flow = $4 RETURNING path"
+        ))
+        .bind(new_path)
+        .bind(old_path)
+        .bind(w_id)
+        .bind(kind.is_flow())
+        .fetch_all(&mut *tx)
+        .await?;
+        rewritten.extend(
+            triggers
+                .into_iter()
+                .map(|path| RewrittenReference { kind: table.to_string(), path }),
+        );
+    }
+
+    require_writer_of_references(authed, db, w_id, old_path, &rewritten).await?;
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        audit_log(
+            &mut *tx,
+            authed,
+            &format!("{}s.rename", kind.as_str()),
+            ActionKind::Update,
+            w_id,
+            Some(new_path),
+            Some([("old_path", old_path)].into()),
+        )
+        .await?;
+        tx.commit().await?;
+    }
+
+    Ok(RenameResult {
+        old_path: old_path.to_string(),
+        new_path: new_path.to_string(),
+        dry_run,
+        rewritten,
+        version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rewrites_nested_flow_and_app_references() {
+        let mut flow = json!({
+            "modules": [
+                { "id": "a", "value": { "type": "script", "path": "f/a/old" } },
+                { "id": "b", "value": { "type": "forloopflow", "modules": [
+                    { "id": "c", "value": { "type": "script", "path": "f/a/old", "hash": "123" } },
+                    { "id": "d", "value": { "type": "flow", "path": "f/a/old" } },
+                ] } },
+            ],
+        });
+        assert!(rewrite_flow_value(
+            &mut flow,
+            RenamedKind::Script,
+            "f/a/old",
+            "f/a/new"
+        ));
+        assert_eq!(flow["modules"][0]["value"]["path"], json!("f/a/new"));
+        assert_eq!(
+            flow["modules"][1]["value"]["modules"][0]["value"]["path"],
+            json!("f/a/new")
+        );
+        assert_eq!(
+            flow["modules"][1]["value"]["modules"][1]["value"]["path"],
+            json!("f/a/old")
+        );
+        assert!(!rewrite_flow_value(
+            &mut flow,
+            RenamedKind::Script,
+            "f/a/old",
+            "f/a/new"
+        ));
+
+        let mut app = json!({ "grid": [{ "data": { "componentInput": { "runnable": {
+            "type": "runnableByPath", "runType": "script", "path": "f/a/old"
+        } } } }] });
+        assert!(rewrite_app_value(
+            &mut app,
+            RenamedKind::Script,
+            "f/a/old",
+            "f/a/new"
+        ));
+        assert_eq!(
+            app["grid"][0]["data"]["componentInput"]["runnable"]["path"],
+            json!("f/a/new")
+        );
+
+        let mut policy = json!({ "triggerables_v2": {
+            "a:script/f/a/old": {}, "script/f/a/old": {}, "b:script/f/a/older": {}
+        } });
+        assert!(rewrite_app_policy(
+            &mut policy,
+            RenamedKind::Script,
+            "f/a/old",
+            "f/a/new"
+        ));
+        let mut keys = policy["triggerables_v2"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec!["a:script/f/a/new", "b:script/f/a/older", "script/f/a/new"]
+        );
+    }
+}
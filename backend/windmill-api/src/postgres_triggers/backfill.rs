@@ -0,0 +1,404 @@
+use std::{collections::HashMap, future::Future};
+
+use pg_escape::quote_identifier;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{types::Json as SqlxJson, Connection};
+use windmill_common::{
+    error::{Error, Result},
+    worker::to_raw_value,
+};
+
+use crate::db::DB;
+
+use super::{
+    handler::{get_raw_postgres_connection, Database, PostgresTrigger},
+    run_job,
+};
+
+const DEFAULT_BACKFILL_BATCH_SIZE: i32 = 500;
+
+/// Progress of the initial snapshot of the tables of a postgres trigger, persisted after every
+/// batch so that a restarted server resumes the backfill instead of starting over
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackfillCheckpoint {
+    /// WAL position at which the snapshot was taken, the replication stream starts from there
+    pub snapshot_lsn: String,
+    /// primary key of the last row processed, by `schema.table`
+    #[serde(default)]
+    pub last_pk: HashMap<String, Value>,
+    #[serde(default)]
+    pub done_tables: Vec<String>,
+    #[serde(default)]
+    pub completed: bool,
+}
+
+impl PostgresTrigger {
+    pub fn backfill_pending(&self) -> bool {
+        self.backfill
+            && !self
+                .backfill_checkpoint
+                .as_ref()
+                .is_some_and(|checkpoint| checkpoint.completed)
+    }
+}
+
+/// An LSN is interpolated in the START_REPLICATION command, make sure it is one
+pub fn is_valid_lsn(lsn: &str) -> bool {
+    lsn.split_once('/').is_some_and(|(high, low)| {
+        !high.is_empty()
+            && !low.is_empty()
+            && high.chars().all(|c| c.is_ascii_hexdigit())
+            && low.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// The condition is interpolated in the backfill query, it must be a single expression
+pub fn validate_backfill_where(backfill_where: &str) -> Result<()> {
+    let mut depth = 0i32;
+    let mut quote = None;
+    let mut chars = backfill_where.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ';') => {
+                return Err(Error::BadRequest(
+                    "backfill_where cannot contain ;".to_string(),
+                ))
+            }
+            (None, '-') if chars.peek() == Some(&'-') => {
+                return Err(Error::BadRequest(
+                    "backfill_where cannot contain comments".to_string(),
+                ))
+            }
+            (None, '/') if chars.peek() == Some(&'*') => {
+                return Err(Error::BadRequest(
+                    "backfill_where cannot contain comments".to_string(),
+                ))
+            }
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth < 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 || quote.is_some() {
+        return Err(Error::BadRequest(
+            "backfill_where has unbalanced parentheses or quotes".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn save_checkpoint(
+    db: &DB,
+    postgres_trigger: &PostgresTrigger,
+    checkpoint: &BackfillCheckpoint,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE postgres_trigger SET backfill_checkpoint = $1 WHERE workspace_id = $2 AND path = $3",
+    )
+    .bind(SqlxJson(checkpoint))
+    .bind(&postgres_trigger.workspace_id)
+    .bind(&postgres_trigger.path)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Pages through every table of the publication ordered by primary key and pushes one job per
+/// existing row, with the same args as a replication event but a `backfill` transaction type.
+///
+/// All pages are read from a single repeatable read snapshot whose LSN is returned: starting the
+/// replication stream at that LSN skips the transactions already visible in the snapshot, so rows
+/// are neither missed nor processed twice. A resumed backfill reads from a new snapshot though, so
+/// the rows changed since the initial one can be pushed both by the backfill and the replication.
+pub async fn run_backfill(
+    postgres_trigger: &PostgresTrigger,
+    database: &Database,
+    db: &DB,
+) -> Result<String> {
+    let mut connection = get_raw_postgres_connection(database).await?;
+    let mut tx = connection.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let current_lsn = sqlx::query_scalar::<_, String>("SELECT pg_current_wal_lsn()::text")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let mut checkpoint = match postgres_trigger.backfill_checkpoint.as_ref() {
+        Some(checkpoint) => checkpoint.0.clone(),
+        None => BackfillCheckpoint {
+            snapshot_lsn: current_lsn,
+            last_pk: HashMap::new(),
+            done_tables: vec![],
+            completed: false,
+        },
+    };
+    if checkpoint.completed {
+        return Ok(checkpoint.snapshot_lsn);
+    }
+    save_checkpoint(db, postgres_trigger, &checkpoint).await?;
+
+    let batch_size = postgres_trigger
+        .backfill_batch_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BACKFILL_BATCH_SIZE);
+    let where_clause = postgres_trigger
+        .backfill_where
+        .as_deref()
+        .filter(|where_clause| !where_clause.trim().is_empty())
+        .unwrap_or("TRUE");
+    validate_backfill_where(where_clause)?;
+
+    let tables = sqlx::query_as::<_, (String, String)>(
+        "SELECT schemaname::text, tablename::text FROM pg_publication_tables WHERE pubname = $1 ORDER BY schemaname, tablename",
+    )
+    .bind(&postgres_trigger.publication_name)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for (schema_name, table_name) in tables {
+        let table_key = format!("{}.{}", schema_name, table_name);
+        if checkpoint.done_tables.contains(&table_key) {
+            continue;
+        }
+        let qualified_name = format!(
+            "{}.{}",
+            quote_identifier(&schema_name),
+            quote_identifier(&table_name)
+        );
+
+        let pk_columns = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT
+                a.attname::text
+            FROM
+                pg_index i
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+            WHERE
+                i.indrelid = $1::regclass
+                AND i.indisprimary
+            ORDER BY
+                array_position(i.indkey::int2[], a.attnum)
+            "#,
+        )
+        .bind(&qualified_name)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        if pk_columns.is_empty() {
+            return Err(Error::BadRequest(format!(
+                "Table {} has no primary key and cannot be backfilled",
+                table_key
+            )));
+        }
+
+        let query = backfill_query(&qualified_name, &pk_columns, where_clause);
+
+        loop {
+            let rows = sqlx::query_scalar::<_, Value>(&query)
+                .bind(checkpoint.last_pk.get(&table_key))
+                .bind(batch_size as i64)
+                .fetch_all(&mut *tx)
+                .await?;
+
+            let pushed = push_rows(&rows, &pk_columns, &table_key, &mut checkpoint, |row| {
+                let database_info = HashMap::from([
+                    ("schema_name".to_string(), to_raw_value(&schema_name)),
+                    ("table_name".to_string(), to_raw_value(&table_name)),
+                    ("transaction_type".to_string(), to_raw_value(&"backfill")),
+                    ("row".to_string(), to_raw_value(row)),
+                ]);
+                let extra = Some(HashMap::from([(
+                    "wm_trigger".to_string(),
+                    to_raw_value(&serde_json::json!({"kind": "postgres", "backfill": true})),
+                )]));
+                run_job(Some(database_info), extra, db, postgres_trigger)
+            })
+            .await;
+            if let Err(err) = pushed {
+                // the checkpoint stops at the last pushed row, the failed one is retried on resume
+                save_checkpoint(db, postgres_trigger, &checkpoint).await?;
+                return Err(Error::InternalErr(format!(
+                    "Could not push backfill job for table {}: {:#}",
+                    table_key, err
+                )));
+            }
+
+            if rows.len() < batch_size as usize {
+                checkpoint.last_pk.remove(&table_key);
+                checkpoint.done_tables.push(table_key.clone());
+                save_checkpoint(db, postgres_trigger, &checkpoint).await?;
+                break;
+            }
+            save_checkpoint(db, postgres_trigger, &checkpoint).await?;
+        }
+
+        tracing::info!(
+            "Postgres trigger {} backfilled table {}",
+            postgres_trigger.path,
+            table_key
+        );
+    }
+
+    tx.commit().await?;
+
+    checkpoint.completed = true;
+    save_checkpoint(db, postgres_trigger, &checkpoint).await?;
+
+    Ok(checkpoint.snapshot_lsn)
+}
+
+/// Query reading a batch of `$2` rows of a table matching `where_clause` after the primary key
+/// `$1`, ordered by primary key
+fn backfill_query(qualified_name: &str, pk_columns: &[String], where_clause: &str) -> String {
+    let columns_of = |alias: &str| {
+        pk_columns
+            .iter()
+            .map(|column| format!("{}.{}", alias, quote_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "SELECT to_jsonb(t) FROM {qualified_name} t \
+        WHERE ({where_clause}) \
+        AND ($1::jsonb IS NULL OR ({t_pk}) > (SELECT {r_pk} FROM jsonb_populate_record(NULL::{qualified_name}, $1) r)) \
+        ORDER BY {t_pk} LIMIT $2",
+        t_pk = columns_of("t"),
+        r_pk = columns_of("r"),
+    )
+}
+
+/// Pushes a job for each row in order and advances the checkpoint of the table after each
+/// successful push, stopping at the first failure
+async fn push_rows<F, Fut>(
+    rows: &[Value],
+    pk_columns: &[String],
+    table_key: &str,
+    checkpoint: &mut BackfillCheckpoint,
+    mut push: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&Value) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    for row in rows {
+        push(row).await?;
+        let pk = pk_columns
+            .iter()
+            .map(|column| {
+                (
+                    column.clone(),
+                    row.get(column).cloned().unwrap_or(Value::Null),
+                )
+            })
+            .collect::<Map<String, Value>>();
+        checkpoint
+            .last_pk
+            .insert(table_key.to_string(), Value::Object(pk));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_lsn() {
+        assert!(is_valid_lsn("0/16B3748"));
+        assert!(is_valid_lsn("1A/FF"));
+        assert!(!is_valid_lsn("0/0; DROP TABLE x"));
+        assert!(!is_valid_lsn("16B3748"));
+        assert!(!is_valid_lsn("/16B3748"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_push_does_not_advance_checkpoint() {
+        let rows = (1..=4)
+            .map(|id| serde_json::json!({ "id": id, "name": format!("row {id}") }))
+            .collect::<Vec<_>>();
+        let pk_columns = vec!["id".to_string()];
+        let mut checkpoint = BackfillCheckpoint {
+            snapshot_lsn: "0/16B3748".to_string(),
+            last_pk: HashMap::new(),
+            done_tables: vec![],
+            completed: false,
+        };
+
+        let mut pushed = vec![];
+        let result = push_rows(&rows, &pk_columns, "public.t", &mut checkpoint, |row| {
+            let id = row["id"].as_i64().unwrap();
+            if id != 3 {
+                pushed.push(id);
+            }
+            async move {
+                if id == 3 {
+                    Err(anyhow::anyhow!("queue unavailable"))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(pushed, vec![1, 2]);
+        // resuming starts right after the last pushed row, so the failed one is retried
+        assert_eq!(
+            checkpoint.last_pk["public.t"],
+            serde_json::json!({ "id": 2 })
+        );
+
+        let mut resumed = vec![];
+        let remaining = rows
+            .iter()
+            .filter(|row| row["id"].as_i64() > checkpoint.last_pk["public.t"]["id"].as_i64())
+            .cloned()
+            .collect::<Vec<_>>();
+        push_rows(
+            &remaining,
+            &pk_columns,
+            "public.t",
+            &mut checkpoint,
+            |row| {
+                resumed.push(row["id"].as_i64().unwrap());
+                async { Ok(()) }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(resumed, vec![3, 4]);
+        assert_eq!(
+            checkpoint.last_pk["public.t"],
+            serde_json::json!({ "id": 4 })
+        );
+    }
+
+    #[test]
+    fn test_backfill_query_applies_where_clause() {
+        let query = backfill_query(r#""public"."t""#, &["id".to_string()], "status = 'active'");
+        assert!(query.contains("WHERE (status = 'active') AND ($1::jsonb IS NULL"));
+        assert!(query.contains("ORDER BY t.\"id\" LIMIT $2"));
+    }
+
+    #[test]
+    fn test_validate_backfill_where() {
+        assert!(validate_backfill_where("status = 'active' AND (id > 10 OR id < 5)").is_ok());
+        assert!(validate_backfill_where("name = 'a;b -- (c'").is_ok());
+        assert!(validate_backfill_where("TRUE; DELETE FROM t").is_err());
+        assert!(validate_backfill_where("TRUE -- comment").is_err());
+        assert!(validate_backfill_where("TRUE /* comment */").is_err());
+        assert!(validate_backfill_where("TRUE) OR (TRUE").is_err());
+        assert!(validate_backfill_where("(TRUE").is_err());
+        assert!(validate_backfill_where("name = 'a").is_err());
+    }
+}
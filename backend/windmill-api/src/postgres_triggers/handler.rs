@@ -25,6 +25,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 use sql_builder::{bind::Bind, SqlBuilder};
 use sqlx::{
     postgres::{types::Oid, PgConnectOptions, PgSslMode},
+    types::Json as SqlxJson,
     Connection, FromRow, PgConnection, QueryBuilder,
 };
 use windmill_audit::{audit_ee::audit_log, ActionKind};
@@ -36,7 +37,10 @@ use windmill_common::{
     worker::CLOUD_HOSTED,
 };
 
-use super::get_database_resource;
+use super::{
+    backfill::{validate_backfill_where, BackfillCheckpoint},
+    get_database_resource,
+};
 use lazy_static::lazy_static;
 
 #[derive(FromRow, Serialize, Deserialize, Debug)]
@@ -97,6 +101,10 @@ pub struct EditPostgresTrigger {
     is_flow: bool,
     postgres_resource_path: String,
     publication: Option<PublicationData>,
+    #[serde(default)]
+    backfill: bool,
+    backfill_where: Option<String>,
+    backfill_batch_size: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -110,6 +118,10 @@ pub struct NewPostgresTrigger {
     replication_slot_name: Option<String>,
     publication_name: Option<String>,
     publication: Option<PublicationData>,
+    #[serde(default)]
+    backfill: bool,
+    backfill_where: Option<String>,
+    backfill_batch_size: Option<i32>,
 }
 
 pub async fn get_database_connection(
@@ -229,6 +241,10 @@ pub struct PostgresTrigger {
     pub publication_name: String,
     pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
     pub enabled: bool,
+    pub backfill: bool,
+    pub backfill_where: Option<String>,
+    pub backfill_batch_size: Option<i32>,
+    pub backfill_checkpoint: Option<SqlxJson<BackfillCheckpoint>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -267,6 +283,9 @@ pub async fn create_postgres_trigger(
         publication_name,
         replication_slot_name,
         publication,
+        backfill,
+        backfill_where,
+        backfill_batch_size,
     } = new_postgres_trigger;
 
     if publication_name.is_none() && publication.is_none() {
@@ -275,6 +294,10 @@ pub async fn create_postgres_trigger(
         ));
     }
 
+    if let Some(backfill_where) = backfill_where.as_deref() {
+        validate_backfill_where(backfill_where)?;
+    }
+
     let create_slot = replication_slot_name.is_none();
     let create_publication = publication_name.is_none();
 
@@ -342,7 +365,10 @@ pub async fn create_postgres_trigger(
             email, 
             enabled, 
             postgres_resource_path, 
-            edited_by
+            edited_by,
+            backfill,
+            backfill_where,
+            backfill_batch_size
         ) 
         VALUES (
             $1, 
@@ -354,7 +380,10 @@ pub async fn create_postgres_trigger(
             $7, 
             $8, 
             $9, 
-            $10
+            $10,
+            $11,
+            $12,
+            $13
         )"#,
         pub_name,
         slot_name,
@@ -365,7 +394,10 @@ pub async fn create_postgres_trigger(
         &authed.email,
         enabled,
         postgres_resource_path,
-        &authed.username
+        &authed.username,
+        backfill,
+        backfill_where,
+        backfill_batch_size
     )
     .execute(&mut *tx)
     .await?;
@@ -411,6 +443,10 @@ pub async fn list_postgres_triggers(
             "postgres_resource_path",
             "replication_slot_name",
             "publication_name",
+            "backfill",
+            "backfill_where",
+            "backfill_batch_size",
+            "backfill_checkpoint",
         ])
         .order_by("edited_at", true)
         .and_where("workspace_id = ?".bind(&w_id))
@@ -1046,7 +1082,11 @@ pub async fn get_postgres_trigger(
             enabled,
             replication_slot_name,
             publication_name,
-            postgres_resource_path
+            postgres_resource_path,
+            backfill,
+            backfill_where,
+            backfill_batch_size,
+            backfill_checkpoint as "backfill_checkpoint: _"
         FROM 
             postgres_trigger
         WHERE 
@@ -1081,8 +1121,15 @@ pub async fn update_postgres_trigger(
         is_flow,
         postgres_resource_path,
         publication,
+        backfill,
+        backfill_where,
+        backfill_batch_size,
     } = postgres_trigger;
 
+    if let Some(backfill_where) = backfill_where.as_deref() {
+        validate_backfill_where(backfill_where)?;
+    }
+
     if let Some(publication) = publication {
         let mut connection = get_database_connection(
             authed.clone(),
@@ -1096,6 +1143,8 @@ pub async fn update_postgres_trigger(
     }
     let mut tx = user_db.begin(&authed).await?;
 
+    // the backfill checkpoint is only kept while backfill stays enabled, turning it off and on
+    // again starts a new snapshot
     sqlx::query!(
         r#"
             UPDATE postgres_trigger 
@@ -1108,12 +1157,16 @@ pub async fn update_postgres_trigger(
                 postgres_resource_path = $6, 
                 replication_slot_name = $7,
                 publication_name = $8,
+                backfill = $9,
+                backfill_where = $10,
+                backfill_batch_size = $11,
+                backfill_checkpoint = CASE WHEN $9 THEN backfill_checkpoint ELSE NULL END,
                 edited_at = now(), 
                 error = NULL,
                 server_id = NULL
             WHERE 
-                workspace_id = $9 AND 
-                path = $10
+                workspace_id = $12 AND 
+                path = $13
             "#,
         script_path,
         path,
@@ -1123,6 +1176,9 @@ pub async fn update_postgres_trigger(
         postgres_resource_path,
         replication_slot_name,
         publication_name,
+        backfill,
+        backfill_where,
+        backfill_batch_size,
         w_id,
         workspace_path,
     )
@@ -21,6 +21,7 @@ use handler::{
 use windmill_common::{db::UserDB, error::Error, utils::StripPath};
 use windmill_queue::PushArgsOwned;
 
+mod backfill;
 mod bool;
 mod converter;
 mod handler;
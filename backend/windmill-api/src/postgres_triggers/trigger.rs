@@ -3,6 +3,7 @@ use std::{collections::HashMap, pin::Pin};
 use crate::{
     db::DB,
     postgres_triggers::{
+        backfill::{is_valid_lsn, run_backfill},
         get_database_resource,
         relation::RelationConverter,
         replication_message::{
@@ -120,15 +121,20 @@ impl PostgresSimpleClient {
         &self,
         publication_name: &str,
         logical_replication_slot_name: &str,
+        start_lsn: Option<&str>,
     ) -> Result<(CopyBothDuplex<Bytes>, LogicalReplicationSettings), Error> {
         let options = format!(
             r#"("proto_version" '2', "publication_names" {})"#,
             quote_literal(publication_name),
         );
 
+        // transactions committed before the start LSN are skipped by the server
+        let start_lsn = start_lsn.filter(|lsn| is_valid_lsn(lsn)).unwrap_or("0/0");
+
         let query = format!(
-            r#"START_REPLICATION SLOT {} LOGICAL 0/0 {}"#,
+            r#"START_REPLICATION SLOT {} LOGICAL {} {}"#,
             quote_identifier(logical_replication_slot_name),
+            start_lsn,
             options
         );
 
@@ -284,12 +290,19 @@ async fn listen_to_transactions(
         )
         .await?;
 
+        let start_lsn = if postgres_trigger.backfill {
+            Some(run_backfill(postgres_trigger, &database, &db).await?)
+        } else {
+            None
+        };
+
         let client = PostgresSimpleClient::new(&database).await?;
 
         let (logical_replication_stream, logical_replication_settings) = client
             .get_logical_replication_stream(
                 &postgres_trigger.publication_name,
                 &postgres_trigger.replication_slot_name,
+                start_lsn.as_deref(),
             )
             .await?;
 
@@ -300,7 +313,7 @@ async fn listen_to_transactions(
         _ = killpill_rx.recv() => {
             return;
         }
-        _ = loop_ping(&db, postgres_trigger, Some("Connecting...")) => {
+        _ = loop_ping(&db, postgres_trigger, Some(if postgres_trigger.backfill_pending() { "Backfilling existing rows..." } else { "Connecting..." })) => {
             return;
         }
         result = start_logical_replication_streaming => {
@@ -498,7 +511,11 @@ async fn listen_to_unlistened_database_events(
                 extra_perms,
                 error,
                 enabled,
-                postgres_resource_path
+                postgres_resource_path,
+                backfill,
+                backfill_where,
+                backfill_batch_size,
+                backfill_checkpoint as "backfill_checkpoint: _"
             FROM
                 postgres_trigger
             WHERE
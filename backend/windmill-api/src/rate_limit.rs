@@ -0,0 +1,224 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use http::{HeaderName, HeaderValue, Method, StatusCode};
+use quick_cache::sync::Cache;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{auth::OptTokened, db::DB};
+
+const TOKEN_LIMIT_CACHE_TTL: Duration = Duration::from_secs(60);
+const MAX_TRACKED_TOKENS: usize = 10_000;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct RunRateLimit {
+    /// 0 disables the limit
+    pub per_minute: u32,
+    /// number of runs that can be made at once, defaults to `per_minute`
+    pub burst: Option<u32>,
+}
+
+impl RunRateLimit {
+    fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.per_minute).max(1)
+    }
+}
+
+#[derive(Clone)]
+struct TokenRunRateLimit {
+    is_job_token: bool,
+    limit: Option<RunRateLimit>,
+    fetched_at: Instant,
+}
+
+lazy_static::lazy_static! {
+    /// Instance wide limit of the run endpoints, reloaded from the `run_rate_limit` global setting
+    pub static ref RUN_RATE_LIMIT: Arc<RwLock<Option<RunRateLimit>>> = Arc::new(RwLock::new(None));
+
+    static ref TOKEN_LIMITS: Cache<String, TokenRunRateLimit> = Cache::new(1000);
+
+    /// theoretical arrival time of the next run of each token, limits are per server process
+    static ref NEXT_RUN_AT: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Generic cell rate algorithm: a run is allowed if it does not come more than `burst` emission
+/// intervals before the theoretical arrival time `tat`. Returns the next `tat` and the number of
+/// runs left at once, or how long to wait before the next allowed run.
+fn gcra(
+    tat: Option<Instant>,
+    now: Instant,
+    limit: RunRateLimit,
+) -> std::result::Result<(Instant, u32), Duration> {
+    let interval = Duration::from_secs(60) / limit.per_minute.max(1);
+    let tolerance = interval * limit.burst();
+    let next_tat = tat.map_or(now, |tat| tat.max(now)) + interval;
+    if next_tat > now + tolerance {
+        Err(next_tat - tolerance - now)
+    } else {
+        let remaining = (now + tolerance - next_tat).as_nanos() / interval.as_nanos();
+        Ok((next_tat, remaining as u32))
+    }
+}
+
+/// Job tokens are used by the workers and the scripts they run, and are never limited
+fn is_job_jwt(token: &str) -> bool {
+    token.starts_with("jwt_") && !token.starts_with("jwt_ext_")
+}
+
+async fn token_run_rate_limit(db: &DB, token: &str) -> TokenRunRateLimit {
+    if let Some(cached) = TOKEN_LIMITS.get(token) {
+        if cached.fetched_at.elapsed() < TOKEN_LIMIT_CACHE_TTL {
+            return cached;
+        }
+    }
+
+    let row = sqlx::query_as::<_, (bool, Option<i32>, Option<i32>)>(
+        "SELECT job IS NOT NULL, run_rate_limit_per_minute, run_rate_limit_burst \
+        FROM token WHERE token = $1",
+    )
+    .bind(token)
+    .fetch_optional(db)
+    .await;
+
+    let token_limit = match row {
+        Ok(row) => {
+            let (is_job_token, per_minute, burst) = row.unwrap_or((false, None, None));
+            TokenRunRateLimit {
+                is_job_token,
+                limit: per_minute.map(|per_minute| RunRateLimit {
+                    per_minute: per_minute.max(0) as u32,
+                    burst: burst.map(|b| b.max(1) as u32),
+                }),
+                fetched_at: Instant::now(),
+            }
+        }
+        Err(e) => {
+            tracing::error!("Error fetching the run rate limit of a token: {e:#}");
+            // not cached so that it is fetched again on the next run
+            return TokenRunRateLimit {
+                is_job_token: false,
+                limit: None,
+                fetched_at: Instant::now(),
+            };
+        }
+    };
+    TOKEN_LIMITS.insert(token.to_string(), token_limit.clone());
+    token_limit
+}
+
+fn with_rate_limit_headers(
+    mut response: Response,
+    limit: RunRateLimit,
+    remaining: u32,
+) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(limit.per_minute),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(remaining),
+    );
+    response
+}
+
+/// Limits the runs made with the same token to the limit set on the token at its creation, or to
+/// the instance wide `run_rate_limit` setting
+pub async fn limit_runs(
+    Extension(db): Extension<DB>,
+    OptTokened { token }: OptTokened,
+    req: Request,
+    next: Next,
+) -> Response {
+    let token = match token {
+        Some(token) if req.method() != Method::HEAD && !is_job_jwt(&token) => token,
+        _ => return next.run(req).await,
+    };
+
+    let token_limit = token_run_rate_limit(&db, &token).await;
+    if token_limit.is_job_token {
+        return next.run(req).await;
+    }
+    let limit = match token_limit.limit.or(*RUN_RATE_LIMIT.read().await) {
+        Some(limit) if limit.per_minute > 0 => limit,
+        _ => return next.run(req).await,
+    };
+
+    let allowed = {
+        let mut next_run_at = NEXT_RUN_AT.lock().unwrap();
+        let now = Instant::now();
+        if next_run_at.len() > MAX_TRACKED_TOKENS {
+            next_run_at.retain(|_, tat| *tat > now);
+        }
+        let allowed = gcra(next_run_at.get(&token).copied(), now, limit);
+        if let Ok((tat, _)) = allowed {
+            next_run_at.insert(token, tat);
+        }
+        allowed
+    };
+
+    match allowed {
+        Ok((_, remaining)) => with_rate_limit_headers(next.run(req).await, limit, remaining),
+        Err(retry_after) => {
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Rate limit of {} runs per minute exceeded for this token",
+                    limit.per_minute
+                ),
+            )
+                .into_response();
+            response.headers_mut().insert(
+                http::header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+            with_rate_limit_headers(response, limit, 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gcra_allows_burst_then_refills_at_rate() {
+        let limit = RunRateLimit { per_minute: 60, burst: Some(3) };
+        let start = Instant::now();
+
+        let mut tat = None;
+        for expected_remaining in [2, 1, 0] {
+            let (next_tat, remaining) = gcra(tat, start, limit).unwrap();
+            assert_eq!(remaining, expected_remaining);
+            tat = Some(next_tat);
+        }
+
+        let retry_after = gcra(tat, start, limit).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        let (_, remaining) = gcra(tat, start + Duration::from_secs(1), limit).unwrap();
+        assert_eq!(remaining, 0);
+
+        let (_, remaining) = gcra(tat, start + Duration::from_secs(10), limit).unwrap();
+        assert_eq!(remaining, 2);
+    }
+}
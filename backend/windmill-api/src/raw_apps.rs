@@ -13,6 +13,7 @@ use crate::{
 use axum::{
     body::Body,
     extract::{Extension, Json, Path, Query},
+    http::HeaderMap,
     response::Response,
     routing::{delete, get, post},
     Router,
@@ -21,14 +22,14 @@ use hyper::{header, StatusCode};
 use serde::{Deserialize, Serialize};
 use sql_builder::{bind::Bind, SqlBuilder};
 use sqlx::FromRow;
-use std::str;
+use std::{io::Write, str};
 use windmill_audit::audit_ee::audit_log;
 use windmill_audit::ActionKind;
 use windmill_common::{
     apps::ListAppQuery,
     db::UserDB,
-    error::{Error, JsonResult, Result},
-    utils::{not_found_if_none, paginate, Pagination, StripPath},
+    error::{to_anyhow, Error, JsonResult, Result},
+    utils::{calculate_hash, not_found_if_none, paginate, Pagination, StripPath},
 };
 
 pub fn workspaced_service() -> Router {
@@ -57,6 +58,8 @@ pub struct CreateApp {
     pub path: String,
     pub summary: String,
     pub value: String,
+    /// max-age of the Cache-Control header the bundle is served with, no-cache if unset or 0
+    pub cache_max_age: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -64,6 +67,50 @@ pub struct EditApp {
     pub path: Option<String>,
     pub summary: Option<String>,
     pub value: Option<String>,
+    pub cache_max_age: Option<i32>,
+}
+
+struct RawAppData {
+    data: String,
+    data_hash: Option<String>,
+    data_gzip: Option<Vec<u8>>,
+    cache_max_age: Option<i32>,
+}
+
+/// Content hash used as ETag and gzip-compressed copy of a bundle, computed once at upload so
+/// that it is not recompressed on each load
+fn hash_and_compress(data: &str) -> Result<(String, Vec<u8>)> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data.as_bytes()).map_err(to_anyhow)?;
+    let gzip = encoder.finish().map_err(to_anyhow)?;
+    Ok((calculate_hash(data), gzip))
+}
+
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|encoding| {
+            let mut parts = encoding.split(';').map(|p| p.trim());
+            parts.next() == Some("gzip")
+                && !parts.any(|p| {
+                    p.strip_prefix("q=")
+                        .and_then(|q| q.trim().parse::<f32>().ok())
+                        == Some(0.0)
+                })
+        })
+}
+
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
 }
 
 async fn list_apps(
@@ -123,14 +170,17 @@ async fn list_apps(
 
 async fn get_data(
     authed: ApiAuthed,
+    Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, _version, path)): Path<(String, u16, StripPath)>,
+    headers: HeaderMap,
 ) -> Result<Response> {
     let path = path.to_path();
     let mut tx = user_db.begin(&authed).await?;
 
-    let app_o = sqlx::query_scalar!(
-        "SELECT data FROM raw_app
+    let app_o = sqlx::query_as!(
+        RawAppData,
+        "SELECT data, data_hash, data_gzip, cache_max_age FROM raw_app
         WHERE path = $1 AND workspace_id = $2",
         path.to_owned(),
         &w_id
@@ -140,9 +190,54 @@ async fn get_data(
     tx.commit().await?;
 
     let app = not_found_if_none(app_o, "App", path)?;
-    let res = Response::builder().header(header::CONTENT_TYPE, "text/javascript");
 
-    Ok(res.body(Body::from(app)).unwrap())
+    let (data_hash, data_gzip) = match (app.data_hash, app.data_gzip) {
+        (Some(data_hash), data_gzip) => (data_hash, data_gzip),
+        // apps uploaded before the hash was stored, backfilled on their first load
+        (None, _) => {
+            let (data_hash, data_gzip) = hash_and_compress(&app.data)?;
+            if let Err(e) = sqlx::query!(
+                "UPDATE raw_app SET data_hash = $1, data_gzip = $2
+                WHERE path = $3 AND workspace_id = $4 AND data_hash IS NULL",
+                &data_hash,
+                &data_gzip,
+                path,
+                &w_id
+            )
+            .execute(&db)
+            .await
+            {
+                tracing::error!("Could not backfill the hash of raw app {path}: {e:#}");
+            }
+            (data_hash, Some(data_gzip))
+        }
+    };
+
+    let etag = format!("\"{data_hash}\"");
+    let cache_control = match app.cache_max_age {
+        Some(max_age) if max_age > 0 => format!("private, max-age={max_age}"),
+        _ => "no-cache".to_string(),
+    };
+    let res = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::VARY, "Accept-Encoding");
+
+    if matches_etag(&headers, &etag) {
+        return Ok(res
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    let res = res.header(header::CONTENT_TYPE, "text/javascript");
+    match data_gzip {
+        Some(data_gzip) if accepts_gzip(&headers) => Ok(res
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::from(data_gzip))
+            .unwrap()),
+        _ => Ok(res.body(Body::from(app.data)).unwrap()),
+    }
 }
 
 async fn create_app(
@@ -173,14 +268,18 @@ async fn create_app(
         )));
     }
 
+    let (data_hash, data_gzip) = hash_and_compress(&app.value)?;
     sqlx::query!(
         "INSERT INTO raw_app
-            (workspace_id, path, summary, extra_perms, data)
-            VALUES ($1, $2, $3, '{}', $4)",
+            (workspace_id, path, summary, extra_perms, data, data_hash, data_gzip, cache_max_age)
+            VALUES ($1, $2, $3, '{}', $4, $5, $6, $7)",
         w_id,
         app.path,
         app.summary,
         app.value,
+        data_hash,
+        data_gzip,
+        app.cache_max_age,
     )
     .execute(&mut *tx)
     .await?;
@@ -289,6 +388,13 @@ async fn update_app(
     if let Some(value) = &app.value {
         sqlb.set_str("data", value);
         sqlb.set("version", "version + 1");
+        // recomputed below
+        sqlb.set("data_hash", "NULL");
+        sqlb.set("data_gzip", "NULL");
+    }
+
+    if let Some(cache_max_age) = app.cache_max_age {
+        sqlb.set("cache_max_age", cache_max_age);
     }
 
     sqlb.returning("path");
@@ -298,6 +404,19 @@ async fn update_app(
     not_found_if_none(npath_o, "Raw App", path)?;
 
     let npath = app.path.clone().unwrap_or_else(|| path.to_owned());
+
+    if let Some(value) = &app.value {
+        let (data_hash, data_gzip) = hash_and_compress(value)?;
+        sqlx::query!(
+            "UPDATE raw_app SET data_hash = $1, data_gzip = $2 WHERE path = $3 AND workspace_id = $4",
+            data_hash,
+            data_gzip,
+            npath,
+            w_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
     audit_log(
         &mut *tx,
         &authed,
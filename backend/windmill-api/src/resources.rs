@@ -6,9 +6,10 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use crate::{
+    auth::job_id_of_token,
     db::{ApiAuthed, DB},
     users::{maybe_refresh_folders, require_owner_of_path, Tokened},
     webhook_util::{WebhookMessage, WebhookShared},
@@ -21,6 +22,7 @@ use axum::{
     Json, Router,
 };
 use hyper::{header, StatusCode};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::{value::RawValue, Value};
 use sql_builder::{bind::Bind, quote, SqlBuilder};
@@ -32,7 +34,7 @@ use windmill_common::{
     db::UserDB,
     error::{Error, JsonResult, Result},
     jobs::QueuedJob,
-    utils::{not_found_if_none, paginate, require_admin, Pagination, StripPath},
+    utils::{not_found_if_none, paginate, rd_string, require_admin, Pagination, StripPath},
     variables,
 };
 
@@ -41,6 +43,7 @@ pub fn workspaced_service() -> Router {
         .route("/list", get(list_resources))
         .route("/list_search", get(list_search_resources))
         .route("/list_names/:type", get(list_names))
+        .route("/by_type/:type_name", get(list_resources_by_type))
         .route("/get/*path", get(get_resource))
         .route("/exists/*path", get(exists_resource))
         .route("/get_value/*path", get(get_resource_value))
@@ -48,16 +51,31 @@ pub fn workspaced_service() -> Router {
             "/get_value_interpolated/*path",
             get(get_resource_value_interpolated),
         )
-        .route("/update/*path", post(update_resource))
+        .route("/update/*path", post(update_resource).patch(patch_resource))
         .route("/update_value/*path", post(update_resource_value))
         .route("/delete/*path", delete(delete_resource))
+        .route(
+            "/lock/*path",
+            post(acquire_resource_lock).delete(release_resource_lock),
+        )
+        .route("/usage/*path", get(get_resource_usage))
         .route("/create", post(create_resource))
+        .route("/validate/:type_name", post(validate_resource))
         .route("/type/list", get(list_resource_types))
         .route("/type/listnames", get(list_resource_types_names))
         .route("/type/get/:name", get(get_resource_type))
         .route("/type/exists/:name", get(exists_resource_type))
         .route("/type/update/:name", post(update_resource_type))
         .route("/type/delete/:name", delete(delete_resource_type))
+        .route("/type/versions/:name", get(list_resource_type_versions))
+        .route(
+            "/type/get/v/:version/:name",
+            get(get_resource_type_at_version),
+        )
+        .route(
+            "/type/rollback/v/:version/:name",
+            post(rollback_resource_type),
+        )
         .route(
             "/file_resource_type_to_file_ext_map",
             get(file_resource_ext_to_resource_type),
@@ -94,6 +112,23 @@ pub struct EditResourceType {
     pub description: Option<String>,
 }
 
+#[derive(FromRow, Serialize, Deserialize)]
+pub struct ResourceTypeVersion {
+    pub id: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_by: String,
+}
+
+#[derive(FromRow, Serialize, Deserialize)]
+pub struct ResourceTypeAtVersion {
+    pub id: i64,
+    pub workspace_id: String,
+    pub resource_type_name: String,
+    pub schema: Option<serde_json::Value>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_by: String,
+}
+
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct Resource {
     pub workspace_id: String,
@@ -124,6 +159,17 @@ pub struct ListableResource {
     pub account: Option<i32>,
 }
 
+#[derive(Deserialize)]
+pub struct ValidateResource {
+    pub value: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct ResourceValidation {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
 #[derive(Deserialize)]
 pub struct CreateResource {
     pub path: String,
@@ -170,6 +216,45 @@ async fn list_names(
     Ok(Json(rows))
 }
 
+#[derive(Deserialize)]
+pub struct ListResourcesByTypeQuery {
+    /// only list the resources whose path starts with this prefix
+    search: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ResourceOfType {
+    path: String,
+    description: Option<String>,
+    created_by: Option<String>,
+    edited_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+async fn list_resources_by_type(
+    authed: ApiAuthed,
+    Path((w_id, type_name)): Path<(String, String)>,
+    Extension(user_db): Extension<UserDB>,
+    Query(pagination): Query<Pagination>,
+    Query(lq): Query<ListResourcesByTypeQuery>,
+) -> JsonResult<Vec<ResourceOfType>> {
+    let (per_page, offset) = paginate(pagination);
+    let mut tx = user_db.begin(&authed).await?;
+    let rows = sqlx::query_as::<_, ResourceOfType>(
+        "SELECT path, description, created_by, edited_at FROM resource \
+        WHERE resource_type = $1 AND workspace_id = $2 AND ($3::text IS NULL OR starts_with(path, $3)) \
+        ORDER BY path LIMIT $4 OFFSET $5",
+    )
+    .bind(&type_name)
+    .bind(&w_id)
+    .bind(lq.search.as_deref().filter(|s| !s.is_empty()))
+    .bind(per_page as i64)
+    .bind(offset as i64)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Json(rows))
+}
+
 #[derive(Serialize, FromRow)]
 pub struct SearchResource {
     path: String,
@@ -660,6 +745,7 @@ async fn create_resource(
     }
 
     let res_value = resource.value.unwrap_or_default();
+    check_resource_value(&mut tx, &w_id, &resource.resource_type, &res_value).await?;
     let raw_json = sqlx::types::Json(res_value.as_ref());
 
     sqlx::query!(
@@ -710,23 +796,189 @@ async fn create_resource(
     ))
 }
 
+/// Max number of referencing items returned per kind, the total count is still reported
+const RESOURCE_USAGE_MAX_PER_KIND: i64 = 50;
+
+/// Queries listing the path of the items of each kind along with the text in which the
+/// references to a resource are searched, $1 is the workspace
+const RESOURCE_USAGE_QUERIES: [(&str, &str); 7] = [
+    (
+        "script",
+        "SELECT DISTINCT ON (path) path, content AS haystack FROM script
+        WHERE workspace_id = $1 AND archived = false AND deleted = false
+        ORDER BY path, created_at DESC",
+    ),
+    (
+        "flow",
+        "SELECT DISTINCT ON (path) path, value::text AS haystack FROM flow_version
+        WHERE workspace_id = $1 AND path IN (
+            SELECT path FROM flow WHERE workspace_id = $1 AND archived = false
+        )
+        ORDER BY path, created_at DESC",
+    ),
+    (
+        "schedule",
+        "SELECT path, args::text AS haystack FROM schedule WHERE workspace_id = $1",
+    ),
+    (
+        "websocket_trigger",
+        "SELECT path, concat_ws(' ', url, url_runnable_args::text,
+            array_to_string(initial_messages, ' ')) AS haystack
+        FROM websocket_trigger WHERE workspace_id = $1",
+    ),
+    (
+        "kafka_trigger",
+        "SELECT path, kafka_resource_path AS haystack FROM kafka_trigger WHERE workspace_id = $1",
+    ),
+    (
+        "nats_trigger",
+        "SELECT path, nats_resource_path AS haystack FROM nats_trigger WHERE workspace_id = $1",
+    ),
+    (
+        "postgres_trigger",
+        "SELECT path, postgres_resource_path AS haystack FROM postgres_trigger
+        WHERE workspace_id = $1",
+    ),
+];
+
+#[derive(Serialize)]
+struct ResourceUsageKind {
+    paths: Vec<String>,
+    total: i64,
+}
+
+/// LIKE pattern pre-filtering the texts containing `path`
+fn resource_usage_like_pattern(path: &str) -> String {
+    format!(
+        "%{}%",
+        path.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+/// Regex matching `path` only when it is not part of a longer path, so that `u/user/db` does
+/// not match `u/user/db_2` nor `u/user/db/nested`
+fn resource_usage_regex(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    format!("(^|[^[:alnum:]_/-]){escaped}($|[^[:alnum:]_/-])")
+}
+
+/// Items referencing a resource through `$res:<path>` or its bare path, grouped by kind. Only
+/// the items visible to the connection are returned.
+async fn resource_usage(
+    conn: &mut sqlx::PgConnection,
+    w_id: &str,
+    path: &str,
+) -> Result<HashMap<&'static str, ResourceUsageKind>> {
+    let pattern = resource_usage_like_pattern(path);
+    let regex = resource_usage_regex(path);
+    let mut usage = HashMap::new();
+    for (kind, query) in RESOURCE_USAGE_QUERIES {
+        let rows = sqlx::query_as::<_, (String, i64)>(&format!(
+            "SELECT path, count(*) OVER () FROM ({query}) u
+            WHERE haystack LIKE $2 AND haystack ~ $3
+            ORDER BY path LIMIT $4"
+        ))
+        .bind(w_id)
+        .bind(&pattern)
+        .bind(&regex)
+        .bind(RESOURCE_USAGE_MAX_PER_KIND)
+        .fetch_all(&mut *conn)
+        .await?;
+        if let Some((_, total)) = rows.first() {
+            let total = *total;
+            usage.insert(
+                kind,
+                ResourceUsageKind {
+                    paths: rows.into_iter().map(|(path, _)| path).collect(),
+                    total,
+                },
+            );
+        }
+    }
+    Ok(usage)
+}
+
+async fn get_resource_usage(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<HashMap<&'static str, ResourceUsageKind>> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM resource WHERE path = $1 AND workspace_id = $2)",
+        path,
+        w_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .unwrap_or(false);
+    if !exists {
+        return Err(Error::NotFound(format!("Resource {path} not found")));
+    }
+
+    let usage = resource_usage(&mut *tx, &w_id, path).await?;
+    tx.commit().await?;
+    Ok(Json(usage))
+}
+
+#[derive(Deserialize)]
+struct DeleteResourceQuery {
+    force: Option<bool>,
+}
+
 async fn delete_resource(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Extension(webhook): Extension<WebhookShared>,
     Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<DeleteResourceQuery>,
 ) -> Result<String> {
     let path = path.to_path();
+
     let mut tx = user_db.begin(&authed).await?;
 
-    sqlx::query!(
+    let deleted = sqlx::query!(
         "DELETE FROM resource WHERE path = $1 AND workspace_id = $2",
         path,
         w_id
     )
     .execute(&mut *tx)
-    .await?;
+    .await?
+    .rows_affected();
+
+    // the references are looked up in the transaction of the user so that the conflict only
+    // names the items the user can see
+    if deleted > 0 && !query.force.unwrap_or(false) {
+        let usage = resource_usage(&mut *tx, &w_id, path).await?;
+        if !usage.is_empty() {
+            let references = usage
+                .iter()
+                .sorted_by_key(|(kind, _)| **kind)
+                .map(|(kind, usage)| {
+                    let more = usage.total - usage.paths.len() as i64;
+                    if more > 0 {
+                        format!("{kind}: {} and {more} more", usage.paths.join(", "))
+                    } else {
+                        format!("{kind}: {}", usage.paths.join(", "))
+                    }
+                })
+                .join("; ");
+            return Err(Error::Conflict(format!(
+                "resource {path} is still referenced ({references}), use force=true to delete it anyway"
+            )));
+        }
+    }
+
     sqlx::query!(
         "DELETE FROM variable WHERE path = $1 AND workspace_id = $2",
         path,
@@ -771,6 +1023,7 @@ async fn update_resource(
     Extension(user_db): Extension<UserDB>,
     Extension(webhook): Extension<WebhookShared>,
     Path((w_id, path)): Path<(String, StripPath)>,
+    Query(lock): Query<LockTokenQuery>,
     Json(ns): Json<EditResource>,
 ) -> Result<String> {
     use sql_builder::prelude::*;
@@ -784,7 +1037,7 @@ async fn update_resource(
     if let Some(npath) = &ns.path {
         sqlb.set_str("path", npath);
     }
-    if let Some(nvalue) = ns.value {
+    if let Some(nvalue) = &ns.value {
         sqlb.set_str("value", nvalue.to_string());
     }
     if let Some(ndesc) = ns.description {
@@ -797,6 +1050,20 @@ async fn update_resource(
     let authed = maybe_refresh_folders(path, &w_id, authed, &db).await;
 
     let mut tx = user_db.begin(&authed).await?;
+    check_resource_lock(&mut tx, &db, &w_id, path, lock.lock_token.as_deref()).await?;
+
+    if let Some(nvalue) = &ns.value {
+        let resource_type = sqlx::query_scalar::<_, String>(
+            "SELECT resource_type FROM resource WHERE path = $1 AND workspace_id = $2",
+        )
+        .bind(path)
+        .bind(&w_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(resource_type) = resource_type {
+            check_resource_value(&mut tx, &w_id, &resource_type, nvalue).await?;
+        }
+    }
 
     if let Some(npath) = ns.path {
         if npath != path {
@@ -866,10 +1133,27 @@ async fn update_resource_value(
     Extension(user_db): Extension<UserDB>,
     Extension(webhook): Extension<WebhookShared>,
     Path((w_id, path)): Path<(String, StripPath)>,
+    Query(lock): Query<LockTokenQuery>,
     Json(nv): Json<UpdateResource>,
 ) -> Result<String> {
     let path = path.to_path();
     let mut tx = user_db.begin(&authed).await?;
+    check_resource_lock(&mut tx, &db, &w_id, path, lock.lock_token.as_deref()).await?;
+
+    if let Some(nvalue) = &nv.value {
+        let resource_type = sqlx::query_scalar::<_, String>(
+            "SELECT resource_type FROM resource WHERE path = $1 AND workspace_id = $2",
+        )
+        .bind(path)
+        .bind(&w_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(resource_type) = resource_type {
+            let raw_value = serde_json::value::to_raw_value(nvalue)
+                .map_err(|e| Error::InternalErr(format!("serializing resource value: {e}")))?;
+            check_resource_value(&mut tx, &w_id, &resource_type, &raw_value).await?;
+        }
+    }
 
     sqlx::query!(
         "UPDATE resource SET value = $1, edited_at = now() WHERE path = $2 AND workspace_id = $3",
@@ -914,6 +1198,256 @@ async fn update_resource_value(
     Ok(format!("value of resource {} updated", path))
 }
 
+/// Applies a JSON merge patch on the top level keys of the resource value, keys set to null in
+/// the patch are removed
+async fn patch_resource(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(lock): Query<LockTokenQuery>,
+    Json(patch): Json<serde_json::Map<String, Value>>,
+) -> JsonResult<Value> {
+    let path = path.to_path();
+    let authed = maybe_refresh_folders(path, &w_id, authed, &db).await;
+    let mut tx = user_db.begin(&authed).await?;
+    check_resource_lock(&mut tx, &db, &w_id, path, lock.lock_token.as_deref()).await?;
+
+    let current = sqlx::query_as::<_, (Option<Value>, String)>(
+        "SELECT value, resource_type FROM resource WHERE path = $1 AND workspace_id = $2 FOR UPDATE",
+    )
+    .bind(path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let (old_value, resource_type) = not_found_if_none(current, "Resource", path)?;
+    let old_value = match old_value {
+        None | Some(Value::Null) => serde_json::Map::new(),
+        Some(Value::Object(m)) => m,
+        Some(_) => {
+            return Err(Error::BadRequest(format!(
+                "Resource {path} does not have an object value and cannot be patched"
+            )))
+        }
+    };
+
+    let new_value = sqlx::query_scalar::<_, Value>(
+        "UPDATE resource SET value = jsonb_strip_nulls(COALESCE(value, '{}'::jsonb) || $1), edited_at = now() \
+        WHERE path = $2 AND workspace_id = $3 RETURNING value",
+    )
+    .bind(sqlx::types::Json(&patch))
+    .bind(path)
+    .bind(&w_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let raw_new_value = serde_json::value::to_raw_value(&new_value)
+        .map_err(|e| Error::InternalErr(format!("serializing patched resource value: {e}")))?;
+    check_resource_value(&mut tx, &w_id, &resource_type, &raw_new_value).await?;
+
+    let empty = serde_json::Map::new();
+    let new_map = new_value.as_object().unwrap_or(&empty);
+    let changed_keys = old_value
+        .keys()
+        .chain(new_map.keys())
+        .unique()
+        .filter(|k| old_value.get(*k) != new_map.get(*k))
+        .join(",");
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "resources.update",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("changed_keys", changed_keys.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::Resource { path: path.to_string(), parent_path: Some(path.to_string()) },
+        None,
+        true,
+    )
+    .await?;
+
+    webhook.send_message(
+        w_id.clone(),
+        WebhookMessage::UpdateResource {
+            workspace: w_id,
+            old_path: path.to_owned(),
+            new_path: path.to_owned(),
+        },
+    );
+
+    Ok(Json(new_value))
+}
+
+/// Longest a lock acquisition can wait for the current holder to release it
+const MAX_LOCK_WAIT: Duration = Duration::from_secs(60);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Deserialize)]
+struct LockTokenQuery {
+    lock_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AcquireResourceLock {
+    ttl_s: i32,
+    /// wait for the current holder to release the lock or for it to expire instead of failing
+    /// right away
+    wait_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ResourceLock {
+    lock_token: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Rejects the update of a resource locked by someone else than the holder of `lock_token`. The
+/// resource is first locked for update through the transaction of the user, so that the lock is
+/// only disclosed to the users allowed to update the resource
+async fn check_resource_lock(
+    tx: &mut Transaction<'_, Postgres>,
+    db: &DB,
+    w_id: &str,
+    path: &str,
+    lock_token: Option<&str>,
+) -> Result<()> {
+    let writable = sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM resource WHERE path = $1 AND workspace_id = $2 FOR UPDATE",
+    )
+    .bind(path)
+    .bind(w_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    not_found_if_none(writable, "Resource", path)?;
+
+    let lock = sqlx::query_as::<_, (String, String)>(
+        "SELECT lock_token, acquired_by FROM resource_lock
+        WHERE workspace_id = $1 AND path = $2 AND expires_at > now()",
+    )
+    .bind(w_id)
+    .bind(path)
+    .fetch_optional(db)
+    .await?;
+    match lock {
+        Some((held_token, acquired_by)) if lock_token != Some(held_token.as_str()) => {
+            Err(Error::Conflict(format!(
+                "Resource {path} is locked by {acquired_by}, its lock_token is required to update it"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Advisory lock on a resource path, e.g. for scripts reading and writing their state
+async fn acquire_resource_lock(
+    authed: ApiAuthed,
+    Tokened { token }: Tokened,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(req): Json<AcquireResourceLock>,
+) -> JsonResult<ResourceLock> {
+    let path = path.to_path();
+    if req.ttl_s <= 0 {
+        return Err(Error::BadRequest("ttl_s must be positive".to_string()));
+    }
+
+    let holder_job_id = job_id_of_token(&db, &token).await;
+    let authed = maybe_refresh_folders(path, &w_id, authed, &db).await;
+    let lock_token = rd_string(32);
+    let deadline = std::time::Instant::now()
+        + Duration::from_millis(req.wait_ms.unwrap_or(0)).min(MAX_LOCK_WAIT);
+
+    loop {
+        let mut tx = user_db.clone().begin(&authed).await?;
+        let expires_at = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+            "INSERT INTO resource_lock
+                (workspace_id, path, lock_token, holder_job_id, acquired_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, now() + $6 * interval '1 second')
+            ON CONFLICT (workspace_id, path) DO UPDATE SET
+                lock_token = EXCLUDED.lock_token, holder_job_id = EXCLUDED.holder_job_id,
+                acquired_by = EXCLUDED.acquired_by, expires_at = EXCLUDED.expires_at
+            WHERE resource_lock.expires_at <= now()
+            RETURNING expires_at",
+        )
+        .bind(&w_id)
+        .bind(path)
+        .bind(&lock_token)
+        .bind(holder_job_id)
+        .bind(&authed.username)
+        .bind(req.ttl_s)
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        if let Some(expires_at) = expires_at {
+            return Ok(Json(ResourceLock { lock_token, expires_at }));
+        }
+
+        let now = std::time::Instant::now();
+        if now >= deadline {
+            let holder = sqlx::query_as::<_, (String, Option<Uuid>)>(
+                "SELECT acquired_by, holder_job_id FROM resource_lock
+                WHERE workspace_id = $1 AND path = $2",
+            )
+            .bind(&w_id)
+            .bind(path)
+            .fetch_optional(&db)
+            .await?;
+            let holder = match holder {
+                Some((acquired_by, Some(job_id))) => format!("{acquired_by} (job {job_id})"),
+                Some((acquired_by, None)) => acquired_by,
+                None => "another caller".to_string(),
+            };
+            return Err(Error::Conflict(format!(
+                "Resource {path} is already locked by {holder}"
+            )));
+        }
+        tokio::time::sleep(LOCK_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+async fn release_resource_lock(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(lock): Query<LockTokenQuery>,
+) -> Result<String> {
+    let path = path.to_path();
+    let lock_token = lock
+        .lock_token
+        .ok_or_else(|| Error::BadRequest("lock_token is required".to_string()))?;
+    let authed = maybe_refresh_folders(path, &w_id, authed, &db).await;
+
+    let mut tx = user_db.begin(&authed).await?;
+    let released = sqlx::query_scalar::<_, String>(
+        "DELETE FROM resource_lock WHERE workspace_id = $1 AND path = $2 AND lock_token = $3
+        RETURNING path",
+    )
+    .bind(&w_id)
+    .bind(path)
+    .bind(&lock_token)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    not_found_if_none(released, "Resource lock", path)?;
+
+    Ok(format!("lock of resource {path} released"))
+}
+
 async fn file_resource_ext_to_resource_type(
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
@@ -1037,6 +1571,8 @@ async fn create_resource_type(
     .execute(&mut *tx)
     .await?;
 
+    insert_resource_type_version(&mut tx, &w_id, &resource_type.name, &authed.username).await?;
+
     handle_deployment_metadata(
         &authed.email,
         &authed.username,
@@ -1074,6 +1610,98 @@ async fn create_resource_type(
     ))
 }
 
+async fn get_resource_type_schema<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    w_id: &str,
+    name: &str,
+) -> Result<Option<Option<serde_json::Value>>> {
+    let schema = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+        "SELECT schema from resource_type WHERE name = $1 AND (workspace_id = $2 OR workspace_id = 'admins')
+        ORDER BY workspace_id = $2 DESC LIMIT 1",
+    )
+    .bind(name)
+    .bind(w_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(schema)
+}
+
+fn is_empty_schema(schema: &Option<serde_json::Value>) -> bool {
+    match schema {
+        None | Some(Value::Null) => true,
+        Some(Value::Object(m)) => m.is_empty(),
+        _ => false,
+    }
+}
+
+fn validate_against_schema(schema: &Value, value: &Value) -> Vec<String> {
+    let compiled = match jsonschema::JSONSchema::compile(schema) {
+        Ok(compiled) => compiled,
+        Err(e) => return vec![format!("invalid resource type schema: {e}")],
+    };
+    match compiled.validate(value) {
+        Ok(()) => vec![],
+        Err(errors) => errors
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{path}: {e}")
+                }
+            })
+            .collect(),
+    }
+}
+
+async fn check_resource_value<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    w_id: &str,
+    resource_type: &str,
+    value: &RawValue,
+) -> Result<()> {
+    let schema = get_resource_type_schema(tx, w_id, resource_type)
+        .await?
+        .flatten();
+    if is_empty_schema(&schema) {
+        return Ok(());
+    }
+    let value = serde_json::from_str::<Value>(value.get())
+        .map_err(|e| Error::BadRequest(format!("Invalid resource value: {e}")))?;
+    let errors = validate_against_schema(schema.as_ref().unwrap(), &value);
+    if !errors.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "Resource value does not match the schema of resource type {}: {}",
+            resource_type,
+            errors.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+async fn validate_resource(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, type_name)): Path<(String, String)>,
+    Json(ValidateResource { value }): Json<ValidateResource>,
+) -> JsonResult<ResourceValidation> {
+    let mut tx = user_db.begin(&authed).await?;
+    let schema = get_resource_type_schema(&mut tx, &w_id, &type_name).await?;
+    tx.commit().await?;
+
+    let schema = not_found_if_none(schema, "ResourceType", &type_name)?;
+    let errors = if is_empty_schema(&schema) {
+        vec![]
+    } else {
+        validate_against_schema(schema.as_ref().unwrap(), &value)
+    };
+
+    Ok(Json(ResourceValidation {
+        valid: errors.is_empty(),
+        errors,
+    }))
+}
+
 async fn check_rt_path_conflict<'c>(
     tx: &mut Transaction<'c, Postgres>,
     w_id: &str,
@@ -1169,6 +1797,7 @@ async fn update_resource_type(
     let mut tx = user_db.begin(&authed).await?;
 
     sqlx::query(&sql).execute(&mut *tx).await?;
+    insert_resource_type_version(&mut tx, &w_id, &name, &authed.username).await?;
     audit_log(
         &mut *tx,
         &authed,
@@ -1199,3 +1828,138 @@ async fn update_resource_type(
 
     Ok(format!("resource_type {} updated", name))
 }
+
+async fn insert_resource_type_version<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    w_id: &str,
+    name: &str,
+    created_by: &str,
+) -> Result<i64> {
+    let version = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO resource_type_version (workspace_id, resource_type_name, schema, created_by)
+            SELECT workspace_id, name, schema, $3 FROM resource_type WHERE name = $1 AND workspace_id = $2
+            RETURNING id",
+    )
+    .bind(name)
+    .bind(w_id)
+    .bind(created_by)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    not_found_if_none(version, "ResourceType", name)
+}
+
+async fn list_resource_type_versions(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, name)): Path<(String, String)>,
+) -> JsonResult<Vec<ResourceTypeVersion>> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let versions = sqlx::query_as::<_, ResourceTypeVersion>(
+        "SELECT id, created_at, created_by FROM resource_type_version
+        WHERE resource_type_name = $1 AND workspace_id = $2
+        ORDER BY created_at DESC, id DESC",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(versions))
+}
+
+async fn get_resource_type_at_version(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, version, name)): Path<(String, i64, String)>,
+) -> JsonResult<ResourceTypeAtVersion> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let resource_type_o = sqlx::query_as::<_, ResourceTypeAtVersion>(
+        "SELECT id, workspace_id, resource_type_name, schema, created_at, created_by FROM resource_type_version
+        WHERE resource_type_name = $1 AND workspace_id = $2 AND id = $3",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .bind(version)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let resource_type = not_found_if_none(
+        resource_type_o,
+        "ResourceType version",
+        format!("{version} for {name}"),
+    )?;
+    Ok(Json(resource_type))
+}
+
+async fn rollback_resource_type(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Path((w_id, version, name)): Path<(String, i64, String)>,
+) -> Result<String> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let updated = sqlx::query(
+        "UPDATE resource_type SET schema = resource_type_version.schema, edited_at = now()
+        FROM resource_type_version
+        WHERE resource_type.name = $1 AND resource_type.workspace_id = $2
+            AND resource_type_version.id = $3
+            AND resource_type_version.resource_type_name = resource_type.name
+            AND resource_type_version.workspace_id = resource_type.workspace_id",
+    )
+    .bind(&name)
+    .bind(&w_id)
+    .bind(version)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(Error::NotFound(format!(
+            "ResourceType version {version} for {name} not found"
+        )));
+    }
+
+    insert_resource_type_version(&mut tx, &w_id, &name, &authed.username).await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "resource_types.rollback",
+        ActionKind::Update,
+        &w_id,
+        Some(&name),
+        Some([("version", version.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::ResourceType { path: name.clone() },
+        Some(format!(
+            "Resource Type '{}' rolled back to version {}",
+            name, version
+        )),
+        true,
+    )
+    .await?;
+
+    webhook.send_message(
+        w_id.clone(),
+        WebhookMessage::UpdateResourceType { name: name.clone() },
+    );
+
+    Ok(format!(
+        "resource_type {} rolled back to version {}",
+        name, version
+    ))
+}
@@ -8,8 +8,9 @@
 
 use crate::{
     db::{ApiAuthed, DB},
+    granular_acls::GranularAcl,
     settings::{delete_global_setting, set_global_setting_internal},
-    users::maybe_refresh_folders,
+    users::{maybe_refresh_folders, require_owner_of_path},
     utils::require_super_admin,
 };
 use axum::{
@@ -18,6 +19,7 @@ use axum::{
     Json, Router,
 };
 use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sql_builder::{prelude::Bind, SqlBuilder};
 use sqlx::{Postgres, Transaction};
@@ -27,11 +29,14 @@ use windmill_audit::ActionKind;
 use windmill_common::{
     db::UserDB,
     error::{Error, JsonResult, Result},
-    schedule::Schedule,
-    utils::{not_found_if_none, paginate, Pagination, ScheduleType, StripPath},
+    schedule::{CatchupPolicy, Schedule, ScheduleConflictPolicy},
+    utils::{not_found_if_none, paginate, require_admin, Pagination, ScheduleType, StripPath},
 };
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
-use windmill_queue::schedule::push_scheduled_job;
+use windmill_queue::schedule::{
+    last_schedule_skip_reason, next_run_of_schedule, push_scheduled_job,
+    schedule_dependency_status, ScheduleDependencyStatus,
+};
 
 pub fn workspaced_service() -> Router {
     Router::new()
@@ -39,10 +44,18 @@ pub fn workspaced_service() -> Router {
         .route("/list_with_jobs", get(list_schedule_with_jobs))
         .route("/get/*path", get(get_schedule))
         .route("/exists/*path", get(exists_schedule))
+        .route("/dependency_status/*path", get(get_dependency_status))
+        .route("/next_runs/*path", get(preview_schedule_runs))
+        .route("/acls/*path", get(get_schedule_acls).post(add_schedule_acl))
         .route("/create", post(create_schedule))
         .route("/update/*path", post(edit_schedule))
         .route("/delete/*path", delete(delete_schedule))
         .route("/setenabled/*path", post(set_enabled))
+        .route("/bulk_enable", post(bulk_enable))
+        .route("/bulk_disable", post(bulk_disable))
+        .route("/pause/*path", post(pause_schedule))
+        .route("/pause_prefix", post(pause_prefix))
+        .route("/resume_prefix", post(resume_prefix))
         .route("/setdefaulthandler", post(set_default_error_handler))
     // .route("/catchup/*path", post(do_catchup).get(list_catchup))
 }
@@ -76,6 +89,13 @@ pub struct NewSchedule {
     pub tag: Option<String>,
     pub paused_until: Option<DateTime<Utc>>,
     pub cron_version: Option<String>,
+    pub catchup_policy: Option<CatchupPolicy>,
+    pub depends_on_schedule: Option<String>,
+    pub dependency_lookback_secs: Option<i32>,
+    pub jitter_seconds: Option<u32>,
+    pub max_concurrent_runs: Option<i32>,
+    pub on_conflict: Option<ScheduleConflictPolicy>,
+    pub pinned_version_id: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -155,18 +175,33 @@ async fn create_schedule(
 
     // Check schedule for error
     ScheduleType::from_str(&ns.schedule, ns.cron_version.as_deref())?;
+    check_dependency(&db, &w_id, &ns.path, ns.depends_on_schedule.as_deref()).await?;
+    let jitter_seconds = check_jitter(ns.jitter_seconds)?;
+    let max_concurrent_runs =
+        check_max_concurrent_runs(ns.max_concurrent_runs, ns.no_flow_overlap)?;
 
     check_path_conflict(&mut tx, &w_id, &ns.path).await?;
     check_flow_conflict(&mut tx, &w_id, &ns.path, ns.is_flow, &ns.script_path).await?;
+    check_pinned_version(
+        &mut tx,
+        &w_id,
+        ns.is_flow,
+        &ns.script_path,
+        ns.pinned_version_id,
+    )
+    .await?;
 
     let schedule = sqlx::query_as::<_, Schedule>(
         "INSERT INTO schedule (workspace_id, path, schedule, timezone, edited_by, script_path, \
             is_flow, args, enabled, email, on_failure, on_failure_times, on_failure_exact, \
             on_failure_extra_args, on_recovery, on_recovery_times, on_recovery_extra_args, \
             on_success, on_success_extra_args, \
-            ws_error_handler_muted, retry, summary, no_flow_overlap, tag, paused_until, cron_version \
+            ws_error_handler_muted, retry, summary, no_flow_overlap, tag, paused_until, cron_version, catchup_policy, \
+            depends_on_schedule, dependency_lookback_secs, jitter_seconds, \
+            max_concurrent_runs, on_conflict, pinned_version_id \
         ) VALUES ( \
-            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26 \
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, \
+            $31, $32, $33 \
         ) RETURNING *")
         .bind(&w_id)
         .bind(&ns.path)
@@ -194,6 +229,13 @@ async fn create_schedule(
         .bind(&ns.tag)
         .bind(&ns.paused_until)
         .bind(&ns.cron_version.unwrap_or("v2".to_string()))
+        .bind(&ns.catchup_policy.unwrap_or_default())
+        .bind(&ns.depends_on_schedule)
+        .bind(&ns.dependency_lookback_secs)
+        .bind(jitter_seconds)
+        .bind(max_concurrent_runs)
+        .bind(&ns.on_conflict.unwrap_or_default())
+        .bind(&ns.pinned_version_id)
     .fetch_one(&mut *tx)
     .await
     .map_err(|e| Error::InternalErr(format!("inserting schedule in {w_id}: {e:#}")))?;
@@ -246,10 +288,27 @@ async fn edit_schedule(
     let path = path.to_path();
 
     let authed = maybe_refresh_folders(&path, &w_id, authed, &db).await;
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     // Check schedule for error
     ScheduleType::from_str(&es.schedule, es.cron_version.as_deref())?;
+    check_dependency(&db, &w_id, path, es.depends_on_schedule.as_deref()).await?;
+    let jitter_seconds = check_jitter(es.jitter_seconds)?;
+    let max_concurrent_runs =
+        check_max_concurrent_runs(es.max_concurrent_runs, es.no_flow_overlap)?;
+
+    if es.pinned_version_id.is_some() {
+        let (is_flow, script_path) = sqlx::query_as::<_, (bool, String)>(
+            "SELECT is_flow, script_path FROM schedule WHERE path = $1 AND workspace_id = $2",
+        )
+        .bind(path)
+        .bind(&w_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Schedule {path} not found")))?;
+        check_pinned_version(&mut tx, &w_id, is_flow, &script_path, es.pinned_version_id).await?;
+    }
 
     clear_schedule(&mut tx, path, &w_id).await?;
     let schedule = sqlx::query_as::<_, Schedule>(
@@ -257,7 +316,10 @@ async fn edit_schedule(
             on_failure_exact = $6, on_failure_extra_args = $7, on_recovery = $8, on_recovery_times = $9, \
             on_recovery_extra_args = $10, on_success = $11, on_success_extra_args = $12, \
             ws_error_handler_muted = $13, retry = $14, summary = $15, \
-            no_flow_overlap = $16, tag = $17, paused_until = $18, cron_version = COALESCE($21, cron_version) \
+            no_flow_overlap = $16, tag = $17, paused_until = $18, cron_version = COALESCE($21, cron_version), \
+            catchup_policy = COALESCE($22, catchup_policy), depends_on_schedule = $23, \
+            dependency_lookback_secs = $24, jitter_seconds = $25, \
+            max_concurrent_runs = $26, on_conflict = COALESCE($27, on_conflict), pinned_version_id = $28 \
         WHERE path = $19 AND workspace_id = $20 RETURNING *")
         .bind(&es.schedule)
         .bind(&es.timezone)
@@ -280,6 +342,13 @@ async fn edit_schedule(
         .bind(&path)
         .bind(&w_id)
         .bind(&es.cron_version)
+        .bind(&es.catchup_policy)
+        .bind(&es.depends_on_schedule)
+        .bind(&es.dependency_lookback_secs)
+        .bind(jitter_seconds)
+        .bind(max_concurrent_runs)
+        .bind(&es.on_conflict)
+        .bind(&es.pinned_version_id)
     .fetch_one(&mut *tx)
     .await
     .map_err(|e| Error::InternalErr(format!("updating schedule in {w_id}: {e:#}")))?;
@@ -329,16 +398,30 @@ pub struct ListScheduleQuery {
     pub path_start: Option<String>,
 }
 
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ScheduleWQueuedNextRun {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    /// when the queued next run of the schedule is scheduled for, jitter and delay included
+    pub next_run: Option<DateTime<Utc>>,
+}
+
 async fn list_schedule(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path(w_id): Path<String>,
     Query(lsq): Query<ListScheduleQuery>,
-) -> JsonResult<Vec<Schedule>> {
+) -> JsonResult<Vec<ScheduleWQueuedNextRun>> {
     let mut tx = user_db.begin(&authed).await?;
     let (per_page, offset) = paginate(Pagination { per_page: lsq.per_page, page: lsq.page });
     let mut sqlb = SqlBuilder::select_from("schedule")
-        .field("*")
+        .field("schedule.*")
+        .field(
+            "(SELECT min(scheduled_for) FROM queue WHERE queue.workspace_id = schedule.workspace_id \
+            AND queue.schedule_path = schedule.path AND queue.parent_job IS NULL \
+            AND queue.running = false) AS next_run",
+        )
         .order_by("edited_at", true)
         .and_where("workspace_id = ?".bind(&w_id))
         .offset(offset)
@@ -357,14 +440,14 @@ async fn list_schedule(
         sqlb.and_where_like_left("path", path_start);
     }
     let sql = sqlb.sql().map_err(|e| Error::InternalErr(e.to_string()))?;
-    let rows = sqlx::query_as::<_, Schedule>(&sql)
+    let rows = sqlx::query_as::<_, ScheduleWQueuedNextRun>(&sql)
         .fetch_all(&mut *tx)
         .await?;
     tx.commit().await?;
     Ok(Json(rows))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
 pub struct ScheduleWJobs {
     pub workspace_id: String,
     pub path: String,
@@ -396,6 +479,13 @@ pub struct ScheduleWJobs {
     pub tag: Option<String>,
     pub paused_until: Option<DateTime<Utc>>,
     pub cron_version: Option<String>,
+    pub catchup_policy: CatchupPolicy,
+    pub depends_on_schedule: Option<String>,
+    pub dependency_lookback_secs: Option<i32>,
+    pub jitter_seconds: Option<i32>,
+    pub max_concurrent_runs: Option<i32>,
+    pub on_conflict: ScheduleConflictPolicy,
+    pub pinned_version_id: Option<i64>,
 }
 
 async fn list_schedule_with_jobs(
@@ -407,9 +497,17 @@ async fn list_schedule_with_jobs(
     let mut tx = user_db.begin(&authed).await?;
     let (per_page, offset) = paginate(pagination);
     let rows = sqlx::query_as!(ScheduleWJobs,
-        "SELECT schedule.*, t.jobs FROM schedule, LATERAL ( SELECT ARRAY (SELECT json_build_object('id', id, 'success', success, 'duration_ms', duration_ms) FROM completed_job WHERE
+        r#"SELECT schedule.workspace_id, schedule.path, schedule.edited_by, schedule.edited_at, schedule.schedule, schedule.timezone,
+        schedule.enabled, schedule.script_path, schedule.is_flow, schedule.args, schedule.extra_perms, schedule.email, schedule.error,
+        schedule.on_failure, schedule.on_failure_times, schedule.on_failure_exact, schedule.on_failure_extra_args, schedule.on_recovery,
+        schedule.on_recovery_times, schedule.on_recovery_extra_args, schedule.on_success, schedule.on_success_extra_args,
+        schedule.ws_error_handler_muted, schedule.retry, t.jobs, schedule.summary, schedule.no_flow_overlap, schedule.tag,
+        schedule.paused_until, schedule.cron_version, schedule.catchup_policy as "catchup_policy: _", schedule.depends_on_schedule,
+        schedule.dependency_lookback_secs, schedule.jitter_seconds, schedule.max_concurrent_runs,
+        schedule.on_conflict as "on_conflict: _", schedule.pinned_version_id
+        FROM schedule, LATERAL ( SELECT ARRAY (SELECT json_build_object('id', id, 'success', success, 'duration_ms', duration_ms) FROM completed_job WHERE
         completed_job.schedule_path = schedule.path AND completed_job.workspace_id = $1 AND parent_job IS NULL AND is_skipped = False ORDER BY started_at DESC LIMIT 20) AS jobs ) t
-        WHERE schedule.workspace_id = $1 ORDER BY schedule.edited_at desc LIMIT $2 OFFSET $3",
+        WHERE schedule.workspace_id = $1 ORDER BY schedule.edited_at desc LIMIT $2 OFFSET $3"#,
         w_id,
         per_page as i64,
         offset as i64
@@ -430,18 +528,197 @@ async fn list_schedule_with_jobs(
 //       ) AS tag_array
 //    ) t;
 
+#[derive(Serialize)]
+pub struct ScheduleWNextRun {
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    pub next_run: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_skip_reason: Option<String>,
+}
+
 async fn get_schedule(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, path)): Path<(String, StripPath)>,
-) -> JsonResult<Schedule> {
+) -> JsonResult<ScheduleWNextRun> {
     let path = path.to_path();
     let mut tx = user_db.begin(&authed).await?;
 
     let schedule_o = windmill_queue::schedule::get_schedule_opt(&mut *tx, &w_id, path).await?;
     let schedule = not_found_if_none(schedule_o, "Schedule", path)?;
+    let last_skip_reason = last_schedule_skip_reason(&mut *tx, &w_id, path).await?;
     tx.commit().await?;
-    Ok(Json(schedule))
+
+    let next_run = next_run_of_schedule(&schedule, Utc::now())?;
+    Ok(Json(ScheduleWNextRun {
+        schedule,
+        next_run,
+        last_skip_reason,
+    }))
+}
+
+async fn get_dependency_status(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<ScheduleDependencyStatus> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+    let schedule_o = windmill_queue::schedule::get_schedule_opt(&mut *tx, &w_id, path).await?;
+    not_found_if_none(schedule_o, "Schedule", path)?;
+    tx.commit().await?;
+
+    let status = schedule_dependency_status(&mut *db.acquire().await?, &w_id, path).await?;
+    Ok(Json(status))
+}
+
+pub async fn require_is_writer(authed: &ApiAuthed, path: &str, w_id: &str, db: DB) -> Result<()> {
+    return crate::users::require_is_writer(
+        authed,
+        path,
+        w_id,
+        db,
+        "SELECT extra_perms FROM schedule WHERE path = $1 AND workspace_id = $2",
+        "schedule",
+    )
+    .await;
+}
+
+async fn get_schedule_acls(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<serde_json::Value> {
+    let path = path.to_path();
+    crate::granular_acls::get_granular_acls(
+        authed,
+        Extension(user_db),
+        Path((w_id, StripPath(format!("schedule/{path}")))),
+    )
+    .await
+}
+
+async fn add_schedule_acl(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(acl): Json<GranularAcl>,
+) -> Result<String> {
+    let path = path.to_path();
+    crate::granular_acls::add_granular_acl(
+        authed,
+        Extension(db),
+        Extension(user_db),
+        Path((w_id, StripPath(format!("schedule/{path}")))),
+        Json(acl),
+    )
+    .await
+}
+
+const MAX_SCHEDULE_DEPENDENCY_DEPTH: i32 = 100;
+
+/// Rejects a dependency on a schedule that directly or transitively depends on `path`, whose runs
+/// would all be skipped
+async fn check_dependency(
+    db: &DB,
+    w_id: &str,
+    path: &str,
+    depends_on_schedule: Option<&str>,
+) -> Result<()> {
+    let Some(depends_on_schedule) = depends_on_schedule else {
+        return Ok(());
+    };
+    if depends_on_schedule == path {
+        return Err(Error::BadRequest(format!(
+            "Schedule {path} cannot depend on itself"
+        )));
+    }
+
+    let cycle = sqlx::query_scalar::<_, bool>(
+        "WITH RECURSIVE upstream (path, depth) AS (
+            SELECT $2::varchar, 0
+            UNION ALL
+            SELECT s.depends_on_schedule, u.depth + 1
+            FROM schedule s JOIN upstream u ON s.path = u.path
+            WHERE s.workspace_id = $1 AND s.depends_on_schedule IS NOT NULL AND u.depth < $4
+        )
+        SELECT EXISTS (SELECT 1 FROM upstream WHERE path = $3)",
+    )
+    .bind(w_id)
+    .bind(depends_on_schedule)
+    .bind(path)
+    .bind(MAX_SCHEDULE_DEPENDENCY_DEPTH)
+    .fetch_one(db)
+    .await?;
+    if cycle {
+        return Err(Error::BadRequest(format!(
+            "Schedule {path} cannot depend on {depends_on_schedule} which depends on {path}"
+        )));
+    }
+    Ok(())
+}
+
+const MAX_SCHEDULE_JITTER_SECONDS: u32 = 60 * 60;
+
+fn check_jitter(jitter_seconds: Option<u32>) -> Result<Option<i32>> {
+    match jitter_seconds {
+        Some(jitter_seconds) if jitter_seconds > MAX_SCHEDULE_JITTER_SECONDS => {
+            Err(Error::BadRequest(format!(
+                "jitter_seconds cannot exceed {MAX_SCHEDULE_JITTER_SECONDS}s"
+            )))
+        }
+        _ => Ok(jitter_seconds.map(|j| j as i32)),
+    }
+}
+
+/// `no_flow_overlap` is a shorthand for a single concurrent run, the only limit enforced
+fn check_max_concurrent_runs(
+    max_concurrent_runs: Option<i32>,
+    no_flow_overlap: Option<bool>,
+) -> Result<Option<i32>> {
+    if max_concurrent_runs.is_some_and(|m| m < 1) {
+        return Err(Error::BadRequest(
+            "max_concurrent_runs must be at least 1".to_string(),
+        ));
+    }
+    if no_flow_overlap == Some(true) {
+        return Ok(Some(1));
+    }
+    Ok(max_concurrent_runs)
+}
+
+async fn check_pinned_version<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    w_id: &str,
+    is_flow: bool,
+    script_path: &str,
+    pinned_version_id: Option<i64>,
+) -> Result<()> {
+    let Some(version) = pinned_version_id else {
+        return Ok(());
+    };
+    if !is_flow {
+        return Err(Error::BadRequest(
+            "pinned_version_id is only supported for flow schedules".to_string(),
+        ));
+    }
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM flow_version WHERE id = $1 AND path = $2 AND workspace_id = $3)",
+    )
+    .bind(version)
+    .bind(script_path)
+    .bind(w_id)
+    .fetch_one(&mut **tx)
+    .await?;
+    if !exists {
+        return Err(Error::NotFound(format!(
+            "version {version} of flow at path {script_path} not found"
+        )));
+    }
+    Ok(())
 }
 
 async fn exists_schedule(
@@ -474,6 +751,55 @@ pub async fn preview_schedule(
     Ok(Json(upcoming))
 }
 
+const MAX_NEXT_RUNS_COUNT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct NextRunsQuery {
+    pub count: Option<usize>,
+    pub cron: Option<String>,
+    pub timezone: Option<String>,
+    pub cron_version: Option<String>,
+}
+
+pub async fn preview_schedule_runs(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<NextRunsQuery>,
+) -> JsonResult<Vec<DateTime<Utc>>> {
+    let path = path.to_path();
+    let count = query.count.unwrap_or(5).min(MAX_NEXT_RUNS_COUNT);
+
+    let mut tx = user_db.begin(&authed).await?;
+    let schedule_o = windmill_queue::schedule::get_schedule_opt(&mut *tx, &w_id, path).await?;
+    tx.commit().await?;
+
+    // an ad-hoc cron expression can be previewed before the schedule is saved
+    let (cron, timezone, cron_version) = match (query.cron, schedule_o) {
+        (Some(cron), schedule_o) => (
+            cron,
+            query
+                .timezone
+                .or_else(|| schedule_o.as_ref().map(|s| s.timezone.clone()))
+                .unwrap_or_else(|| "UTC".to_string()),
+            query
+                .cron_version
+                .or_else(|| schedule_o.and_then(|s| s.cron_version)),
+        ),
+        (None, Some(schedule)) => (
+            schedule.schedule,
+            query.timezone.unwrap_or(schedule.timezone),
+            query.cron_version.or(schedule.cron_version),
+        ),
+        (None, None) => return Err(Error::NotFound(format!("Schedule {path} not found"))),
+    };
+
+    let schedule = ScheduleType::from_str(&cron, cron_version.as_deref())?;
+    let tz = chrono_tz::Tz::from_str(&timezone).map_err(|e| Error::BadRequest(e.to_string()))?;
+
+    Ok(Json(schedule.upcoming(tz, count)?))
+}
+
 pub async fn set_enabled(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -481,10 +807,11 @@ pub async fn set_enabled(
     Path((w_id, path)): Path<(String, StripPath)>,
     Json(payload): Json<SetEnabled>,
 ) -> Result<String> {
-    let mut tx = user_db.begin(&authed).await?;
     let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
+    let mut tx = user_db.begin(&authed).await?;
     let schedule_o = sqlx::query_as::<_, Schedule>(
-        "UPDATE schedule SET enabled = $1, email = $2 WHERE path = $3 AND workspace_id = $4 RETURNING *")
+        "UPDATE schedule SET enabled = $1, email = $2, paused_by_bulk_operation = false WHERE path = $3 AND workspace_id = $4 RETURNING *")
         .bind(&payload.enabled)
         .bind(&authed.email)
         .bind(&path)
@@ -529,6 +856,318 @@ pub async fn set_enabled(
     ))
 }
 
+const BULK_SET_ENABLED_MAX_PATHS: usize = 100;
+
+#[derive(Serialize)]
+pub struct BulkSetEnabledResponse {
+    pub enabled_count: usize,
+    pub not_found_paths: Vec<String>,
+}
+
+async fn bulk_enable(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(paths): Json<Vec<String>>,
+) -> JsonResult<BulkSetEnabledResponse> {
+    bulk_set_enabled(authed, db, user_db, w_id, paths, true).await
+}
+
+async fn bulk_disable(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(paths): Json<Vec<String>>,
+) -> JsonResult<BulkSetEnabledResponse> {
+    bulk_set_enabled(authed, db, user_db, w_id, paths, false).await
+}
+
+async fn bulk_set_enabled(
+    authed: ApiAuthed,
+    db: DB,
+    user_db: UserDB,
+    w_id: String,
+    paths: Vec<String>,
+    enabled: bool,
+) -> JsonResult<BulkSetEnabledResponse> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let paths = paths.into_iter().unique().collect_vec();
+    if paths.len() > BULK_SET_ENABLED_MAX_PATHS {
+        return Err(Error::BadRequest(format!(
+            "Cannot update more than {BULK_SET_ENABLED_MAX_PATHS} schedules at once"
+        )));
+    }
+
+    let mut tx = user_db.begin(&authed).await?;
+    let schedules = sqlx::query_as::<_, Schedule>(
+        "UPDATE schedule SET enabled = $1, email = $2, paused_by_bulk_operation = false \
+        WHERE path = ANY($3) AND workspace_id = $4 RETURNING *",
+    )
+    .bind(enabled)
+    .bind(&authed.email)
+    .bind(&paths)
+    .bind(&w_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for schedule in schedules.iter() {
+        // same as set_enabled: drop the pending tick and push a new one when enabling
+        clear_schedule(&mut tx, &schedule.path, &w_id).await?;
+
+        audit_log(
+            &mut *tx,
+            &authed,
+            "schedule.setenabled",
+            ActionKind::Update,
+            &w_id,
+            Some(&schedule.path),
+            Some([("enabled", enabled.to_string().as_ref()), ("bulk", "true")].into()),
+        )
+        .await?;
+
+        if enabled {
+            tx = push_scheduled_job(&db, tx, schedule, None).await?;
+        }
+    }
+    tx.commit().await?;
+
+    for schedule in schedules.iter() {
+        handle_deployment_metadata(
+            &authed.email,
+            &authed.username,
+            &db,
+            &w_id,
+            DeployedObject::Schedule { path: schedule.path.clone() },
+            None,
+            true,
+        )
+        .await?;
+    }
+
+    let not_found_paths = paths
+        .into_iter()
+        .filter(|path| !schedules.iter().any(|schedule| &schedule.path == path))
+        .collect();
+    Ok(Json(BulkSetEnabledResponse {
+        enabled_count: schedules.len(),
+        not_found_paths,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct PathPrefix {
+    pub path_prefix: String,
+}
+
+#[derive(Serialize)]
+pub struct BulkPrefixResponse {
+    /// number of schedules disabled by the pause or re-enabled by the resume
+    pub updated_count: usize,
+    /// number of schedules under the prefix left as they were
+    pub unchanged_count: i64,
+}
+
+/// Non admins can only target the schedules of a folder or user they own, so the prefix must
+/// contain the whole `f/<folder>/` or `u/<user>/` segment.
+fn require_owner_of_prefix(authed: &ApiAuthed, path_prefix: &str) -> Result<()> {
+    if authed.is_admin {
+        return Ok(());
+    }
+    match path_prefix.splitn(3, '/').collect_vec().as_slice() {
+        [_, owner, _] if !owner.is_empty() => require_owner_of_path(authed, path_prefix),
+        _ => require_admin(authed.is_admin, &authed.username),
+    }
+}
+
+async fn pause_prefix(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(PathPrefix { path_prefix }): Json<PathPrefix>,
+) -> JsonResult<BulkPrefixResponse> {
+    require_owner_of_prefix(&authed, &path_prefix)?;
+    let mut tx = user_db.begin(&authed).await?;
+    let paths = sqlx::query_scalar::<_, String>(
+        "UPDATE schedule SET enabled = false, paused_by_bulk_operation = true \
+        WHERE workspace_id = $1 AND starts_with(path, $2) AND enabled RETURNING path",
+    )
+    .bind(&w_id)
+    .bind(&path_prefix)
+    .fetch_all(&mut *tx)
+    .await?;
+    let unchanged_count = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM schedule WHERE workspace_id = $1 AND starts_with(path, $2)",
+    )
+    .bind(&w_id)
+    .bind(&path_prefix)
+    .fetch_one(&mut *tx)
+    .await?
+        - paths.len() as i64;
+
+    for path in paths.iter() {
+        clear_schedule(&mut tx, path, &w_id).await?;
+    }
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "schedule.pause_prefix",
+        ActionKind::Update,
+        &w_id,
+        Some(&path_prefix),
+        Some([("paths", paths.join(",").as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    for path in paths.iter() {
+        handle_deployment_metadata(
+            &authed.email,
+            &authed.username,
+            &db,
+            &w_id,
+            DeployedObject::Schedule { path: path.clone() },
+            None,
+            true,
+        )
+        .await?;
+    }
+
+    Ok(Json(BulkPrefixResponse {
+        updated_count: paths.len(),
+        unchanged_count,
+    }))
+}
+
+async fn resume_prefix(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(PathPrefix { path_prefix }): Json<PathPrefix>,
+) -> JsonResult<BulkPrefixResponse> {
+    require_owner_of_prefix(&authed, &path_prefix)?;
+    let mut tx = user_db.begin(&authed).await?;
+    // only the schedules disabled by a pause are re-enabled, not the ones that already were
+    let schedules = sqlx::query_as::<_, Schedule>(
+        "UPDATE schedule SET enabled = true, paused_by_bulk_operation = false \
+        WHERE workspace_id = $1 AND starts_with(path, $2) AND paused_by_bulk_operation RETURNING *",
+    )
+    .bind(&w_id)
+    .bind(&path_prefix)
+    .fetch_all(&mut *tx)
+    .await?;
+    let unchanged_count = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM schedule WHERE workspace_id = $1 AND starts_with(path, $2)",
+    )
+    .bind(&w_id)
+    .bind(&path_prefix)
+    .fetch_one(&mut *tx)
+    .await?
+        - schedules.len() as i64;
+
+    for schedule in schedules.iter() {
+        clear_schedule(&mut tx, &schedule.path, &w_id).await?;
+        tx = push_scheduled_job(&db, tx, schedule, None).await?;
+    }
+
+    let paths = schedules.iter().map(|s| s.path.as_str()).join(",");
+    audit_log(
+        &mut *tx,
+        &authed,
+        "schedule.resume_prefix",
+        ActionKind::Update,
+        &w_id,
+        Some(&path_prefix),
+        Some([("paths", paths.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    for schedule in schedules.iter() {
+        handle_deployment_metadata(
+            &authed.email,
+            &authed.username,
+            &db,
+            &w_id,
+            DeployedObject::Schedule { path: schedule.path.clone() },
+            None,
+            true,
+        )
+        .await?;
+    }
+
+    Ok(Json(BulkPrefixResponse {
+        updated_count: schedules.len(),
+        unchanged_count,
+    }))
+}
+
+pub async fn pause_schedule(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(payload): Json<PauseSchedule>,
+) -> Result<String> {
+    let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
+    let mut tx = user_db.begin(&authed).await?;
+    let schedule_o = sqlx::query_as::<_, Schedule>(
+        "UPDATE schedule SET paused_until = $1, catchup_policy = COALESCE($2, catchup_policy) \
+        WHERE path = $3 AND workspace_id = $4 RETURNING *",
+    )
+    .bind(&payload.paused_until)
+    .bind(&payload.catchup_policy)
+    .bind(&path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let schedule = not_found_if_none(schedule_o, "Schedule", path)?;
+
+    clear_schedule(&mut tx, path, &w_id).await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::Schedule { path: path.to_string() },
+        None,
+        true,
+    )
+    .await?;
+
+    let paused_until = payload
+        .paused_until
+        .map(|x| x.to_rfc3339())
+        .unwrap_or_else(|| "null".to_string());
+    audit_log(
+        &mut *tx,
+        &authed,
+        "schedule.pause",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("paused_until", paused_until.as_str())].into()),
+    )
+    .await?;
+
+    if schedule.enabled {
+        tx = push_scheduled_job(&db, tx, &schedule, None).await?;
+    }
+    tx.commit().await?;
+
+    Ok(format!(
+        "succesfully paused schedule at path {} until {}",
+        path, paused_until
+    ))
+}
+
 // pub async fn do_catchup(
 //     authed: ApiAuthed,
 //     Extension(db): Extension<DB>,
@@ -582,8 +1221,9 @@ async fn delete_schedule(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, path)): Path<(String, StripPath)>,
 ) -> Result<String> {
-    let mut tx = user_db.begin(&authed).await?;
     let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
+    let mut tx = user_db.begin(&authed).await?;
 
     clear_schedule(&mut tx, path, &w_id).await?;
     let exists = sqlx::query_scalar!(
@@ -829,6 +1469,13 @@ pub struct EditSchedule {
     pub tag: Option<String>,
     pub paused_until: Option<DateTime<Utc>>,
     pub cron_version: Option<String>,
+    pub catchup_policy: Option<CatchupPolicy>,
+    pub depends_on_schedule: Option<String>,
+    pub dependency_lookback_secs: Option<i32>,
+    pub jitter_seconds: Option<u32>,
+    pub max_concurrent_runs: Option<i32>,
+    pub on_conflict: Option<ScheduleConflictPolicy>,
+    pub pinned_version_id: Option<i64>,
 }
 
 pub async fn clear_schedule<'c>(
@@ -852,6 +1499,12 @@ pub struct SetEnabled {
     pub enabled: bool,
 }
 
+#[derive(Deserialize)]
+pub struct PauseSchedule {
+    pub paused_until: Option<DateTime<Utc>>,
+    pub catchup_policy: Option<CatchupPolicy>,
+}
+
 // #[derive(Deserialize)]
 // pub struct Catchup {
 //     pub from: DateTime<Utc>,
@@ -9,13 +9,15 @@
 use crate::{
     auth::AuthCache,
     db::{ApiAuthed, DB},
+    jobs::cancel_jobs,
+    path_rename::{rename_path, RenamePath, RenameQuery, RenameResult, RenamedKind},
     schedule::clear_schedule,
     triggers::{
         get_triggers_count_internal, list_tokens_internal, TriggersCount, TruncatedTokenWithEmail,
     },
     users::{maybe_refresh_folders, require_owner_of_path},
     utils::WithStarredInfoQuery,
-    webhook_util::{WebhookMessage, WebhookShared},
+    webhook_util::{DeployAction, DeployEvent, DeployKind, WebhookMessage, WebhookShared},
     HTTP_CLIENT,
 };
 #[cfg(all(feature = "enterprise", feature = "parquet"))]
@@ -32,13 +34,15 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
 use sql_builder::prelude::*;
 use sqlx::{FromRow, Postgres, Transaction};
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     hash::{Hash, Hasher},
     sync::Arc,
 };
+use uuid::Uuid;
 use windmill_audit::audit_ee::audit_log;
 use windmill_audit::ActionKind;
 
@@ -46,9 +50,10 @@ use windmill_audit::ActionKind;
 use windmill_common::error::to_anyhow;
 
 use windmill_common::{
+    cache,
     db::UserDB,
     error::{Error, JsonResult, Result},
-    jobs::JobPayload,
+    jobs::{JobKind, JobPayload},
     schedule::Schedule,
     scripts::{
         to_i64, HubScript, ListScriptQuery, ListableScript, NewScript, Schema, Script, ScriptHash,
@@ -134,12 +139,17 @@ pub fn workspaced_service() -> Router {
     Router::new()
         .route("/list", get(list_scripts))
         .route("/list_search", get(list_search_scripts))
+        .route("/search", get(search_scripts))
         .route("/create", post(create_script))
         .route("/create_snapshot", post(create_snapshot_script))
+        .route("/create_from_job/:job_id", post(create_script_from_job))
         .route("/archive/p/*path", post(archive_script_by_path))
+        .route("/archive_path/*path", post(archive_and_cancel))
+        .route("/rename/*path", post(rename_script))
         .route("/get/draft/*path", get(get_script_by_path_w_draft))
         .route("/get/p/*path", get(get_script_by_path))
         .route("/get_triggers_count/*path", get(get_triggers_count))
+        .route("/dependents/*path", get(list_script_dependents))
         .route("/list_tokens/*path", get(list_tokens))
         .route("/raw/p/*path", get(raw_script_by_path))
         .route("/raw_unpinned/p/*path", get(raw_script_by_path_unpinned))
@@ -194,6 +204,71 @@ async fn list_search_scripts(
     Ok(Json(rows))
 }
 
+#[derive(Deserialize)]
+struct SearchScriptsQuery {
+    q: String,
+    language: Option<String>,
+    limit: Option<usize>,
+}
+
+const SEARCH_SCRIPTS_DEFAULT_LIMIT: usize = 20;
+const SEARCH_SCRIPTS_MAX_LIMIT: usize = 100;
+
+#[cfg(feature = "tantivy")]
+async fn search_scripts(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(user_db): Extension<UserDB>,
+    Extension(script_index_reader): Extension<Option<crate::ScriptIndexReader>>,
+    Query(sq): Query<SearchScriptsQuery>,
+) -> JsonResult<Vec<windmill_indexer::scripts_search::ScriptSearchResult>> {
+    let index_reader = script_index_reader.ok_or_else(|| {
+        Error::BadConfig("The script search index is not available on this server".to_string())
+    })?;
+    if sq.q.trim().is_empty() {
+        return Err(Error::BadRequest(
+            "Search query cannot be empty".to_string(),
+        ));
+    }
+    let limit = sq
+        .limit
+        .unwrap_or(SEARCH_SCRIPTS_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_SCRIPTS_MAX_LIMIT);
+
+    // the index is not permission aware: over-fetch, then keep the scripts visible to the user
+    let hits = index_reader.search(&w_id, &sq.q, sq.language.as_deref(), limit * 4)?;
+    let paths = hits.iter().map(|hit| hit.path.clone()).collect_vec();
+    let mut tx = user_db.begin(&authed).await?;
+    let visible = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT path FROM script WHERE workspace_id = $1 AND path = ANY($2) AND archived = false",
+    )
+    .bind(&w_id)
+    .bind(&paths)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .collect::<HashSet<_>>();
+    tx.commit().await?;
+
+    Ok(Json(
+        hits.into_iter()
+            .filter(|hit| visible.contains(&hit.path))
+            .take(limit)
+            .collect(),
+    ))
+}
+
+#[cfg(not(feature = "tantivy"))]
+async fn search_scripts(
+    _authed: ApiAuthed,
+    Path(_w_id): Path<String>,
+    Query(_sq): Query<SearchScriptsQuery>,
+) -> JsonResult<Vec<serde_json::Value>> {
+    Err(Error::BadConfig(
+        "Full text search of scripts requires the tantivy feature".to_string(),
+    ))
+}
+
 async fn list_scripts(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -358,6 +433,89 @@ fn hash_script(ns: &NewScript) -> i64 {
     dh.finish() as i64
 }
 
+/// A comment made of a single word, which is the shape of annotations (`# py311`, `//nobundling`)
+/// and of the requirements listed after `# requirements:`, unlike prose comments
+fn is_single_word_comment(line: &str, prefix: &str) -> bool {
+    line.strip_prefix(prefix).is_some_and(|rest| {
+        let rest = rest.strip_prefix(' ').unwrap_or(rest).trim_end();
+        !rest.is_empty() && !rest.contains(char::is_whitespace)
+    })
+}
+
+/// The part of a script its lockfile is generated from: imports and the comments that can hold
+/// requirements or version pins. `None` when the lock cannot be derived from the script alone,
+/// e.g. when it imports other workspace scripts whose dependencies end up in its lock.
+fn lock_import_block(language: &ScriptLang, content: &str) -> Option<String> {
+    let lines = content.lines().map(str::trim);
+    let block: Vec<&str> = match language {
+        ScriptLang::Python3 => {
+            let block = lines
+                .filter(|l| {
+                    l.starts_with("import ")
+                        || l.starts_with("from ")
+                        || is_single_word_comment(l, "#")
+                })
+                .collect_vec();
+            let relative = ["from .", "from f.", "from u.", "import f.", "import u."];
+            if block
+                .iter()
+                .any(|l| relative.iter().any(|r| l.starts_with(r)))
+            {
+                return None;
+            }
+            block
+        }
+        ScriptLang::Bun | ScriptLang::Bunnative | ScriptLang::Deno => {
+            let block = lines
+                .filter(|l| {
+                    if l.starts_with("//") {
+                        is_single_word_comment(l, "//")
+                    } else {
+                        l.starts_with("from ")
+                            || l.contains("import")
+                            || l.contains("require(")
+                            || l.contains(" from ")
+                    }
+                })
+                .collect_vec();
+            let relative = ["\"./", "'./", "\"../", "'../", "\"/", "'/"];
+            if block.iter().any(|l| relative.iter().any(|r| l.contains(r))) {
+                return None;
+            }
+            block
+        }
+        ScriptLang::Go => {
+            let mut in_import_block = false;
+            lines
+                .filter(|l| {
+                    if in_import_block {
+                        in_import_block = !l.starts_with(')');
+                        true
+                    } else if l.starts_with("import (") {
+                        in_import_block = true;
+                        true
+                    } else {
+                        l.starts_with("import ") || l.starts_with("//require ")
+                    }
+                })
+                .collect_vec()
+        }
+        ScriptLang::Rust => lines.filter(|l| l.starts_with("//!")).collect_vec(),
+        _ => return None,
+    };
+    Some(block.join("\n"))
+}
+
+fn lock_import_hash(language: &ScriptLang, content: &str) -> Option<i64> {
+    let block = lock_import_block(language, content)?;
+    let digest = Sha256::new()
+        .chain_update(language.as_str())
+        .chain_update("\n")
+        .chain_update(block)
+        .finalize();
+    Some(i64::from_be_bytes(digest[..8].try_into().unwrap()))
+}
+
 #[cfg(not(all(feature = "enterprise", feature = "parquet")))]
 async fn create_snapshot_script() -> Result<(StatusCode, String)> {
     Err(Error::BadRequest("Upgrade to EE to use bundle".to_string()))
@@ -383,13 +541,14 @@ async fn create_snapshot_script(
             let ns: NewScript = Some(serde_json::from_slice(&data).map_err(to_anyhow)?).unwrap();
             let is_tar = ns.codebase.as_ref().is_some_and(|x| x.ends_with(".tar"));
 
-            let (new_hash, ntx) = create_script_internal(
+            let (new_hash, ntx, _) = create_script_internal(
                 ns,
                 w_id.clone(),
                 authed.clone(),
                 db.clone(),
                 user_db.clone(),
                 webhook.clone(),
+                false,
             )
             .await?;
             let nh = new_hash.to_string();
@@ -436,17 +595,179 @@ async fn create_snapshot_script(
     return Ok((StatusCode::CREATED, format!("{}", script_hash.unwrap())));
 }
 
+#[derive(Deserialize)]
+struct CreateScriptQuery {
+    /// always regenerate the lock, even if the imports did not change
+    force_deps: Option<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LockStatus {
+    /// the lock was provided by the client or the language does not need one
+    Provided,
+    /// the lock of a previous deployment with the same imports was copied
+    Reused,
+    /// a dependency job was pushed to generate the lock
+    Regenerated,
+}
+
+impl LockStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LockStatus::Provided => "provided",
+            LockStatus::Reused => "reused",
+            LockStatus::Regenerated => "regenerated",
+        }
+    }
+}
+
 async fn create_script(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Extension(webhook): Extension<WebhookShared>,
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
+    Query(query): Query<CreateScriptQuery>,
     Json(ns): Json<NewScript>,
-) -> Result<(StatusCode, String)> {
-    let (hash, tx) = create_script_internal(ns, w_id, authed, db, user_db, webhook).await?;
+) -> Result<(StatusCode, [(&'static str, &'static str); 1], String)> {
+    let (hash, tx, lock_status) = create_script_internal(
+        ns,
+        w_id,
+        authed,
+        db,
+        user_db,
+        webhook,
+        query.force_deps.unwrap_or(false),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok((
+        StatusCode::CREATED,
+        [("x-windmill-lock", lock_status.as_str())],
+        format!("{}", hash),
+    ))
+}
+
+#[derive(Deserialize)]
+struct NewScriptFromJob {
+    path: String,
+    summary: Option<String>,
+    description: Option<String>,
+    schema: Option<Schema>,
+    deployment_message: Option<String>,
+}
+
+#[derive(FromRow)]
+struct PreviewJob {
+    job_kind: JobKind,
+    language: Option<ScriptLang>,
+    tag: String,
+    raw_code: Option<String>,
+    raw_lock: Option<String>,
+}
+
+/// tags that are set on a preview when none is given
+fn is_default_tag(tag: &str, language: &ScriptLang, w_id: &str) -> bool {
+    let tag_lang = if language == &ScriptLang::Bunnative {
+        ScriptLang::Nativets.as_str()
+    } else {
+        language.as_str()
+    };
+    tag == tag_lang || tag == format!("{tag_lang}-{w_id}")
+}
+
+async fn create_script_from_job(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Extension(db): Extension<DB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Query(query): Query<CreateScriptQuery>,
+    Json(nj): Json<NewScriptFromJob>,
+) -> Result<(StatusCode, [(&'static str, &'static str); 1], String)> {
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let job = sqlx::query_as::<_, PreviewJob>(
+        "SELECT job_kind, language, tag, raw_code, raw_lock FROM queue \
+            WHERE id = $1 AND workspace_id = $2 \
+        UNION ALL \
+        SELECT job_kind, language, tag, raw_code, raw_lock FROM completed_job \
+            WHERE id = $1 AND workspace_id = $2 \
+        LIMIT 1",
+    )
+    .bind(job_id)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
     tx.commit().await?;
-    Ok((StatusCode::CREATED, format!("{}", hash)))
+    let job = not_found_if_none(job, "Job", job_id.to_string())?;
+
+    if job.job_kind != JobKind::Preview {
+        return Err(Error::BadRequest(format!(
+            "Job {job_id} is not a script preview, only previews can be deployed as a script"
+        )));
+    }
+    let language = job.language.ok_or_else(|| {
+        Error::BadRequest(format!("Job {job_id} has no language to deploy it with"))
+    })?;
+    let code = cache::job::fetch_preview_script(&db, &job_id, job.raw_lock, job.raw_code).await?;
+    if code.code.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "The code of job {job_id} has been purged and can no longer be deployed"
+        )));
+    }
+    let tag = Some(job.tag).filter(|tag| !is_default_tag(tag, &language, &w_id));
+
+    let ns = NewScript {
+        path: nj.path,
+        parent_hash: None,
+        summary: nj.summary.unwrap_or_default(),
+        description: nj.description.unwrap_or_default(),
+        content: code.code.clone(),
+        schema: nj.schema,
+        is_template: None,
+        lock: code.lock.clone(),
+        language,
+        kind: None,
+        tag,
+        draft_only: None,
+        envs: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+        ws_error_handler_muted: None,
+        priority: None,
+        timeout: None,
+        delete_after_use: None,
+        restart_unless_cancelled: None,
+        deployment_message: nj.deployment_message,
+        concurrency_key: None,
+        visible_to_runner_only: None,
+        no_main_func: None,
+        codebase: None,
+        has_preprocessor: None,
+        on_behalf_of_email: None,
+        strict_args: None,
+        retry_on_failure: None,
+        redact_args: None,
+    };
+
+    let (hash, tx, lock_status) = create_script_internal(
+        ns,
+        w_id,
+        authed,
+        db,
+        user_db,
+        webhook,
+        query.force_deps.unwrap_or(false),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok((
+        StatusCode::CREATED,
+        [("x-windmill-lock", lock_status.as_str())],
+        format!("{}", hash),
+    ))
 }
 
 async fn create_script_internal<'c>(
@@ -456,7 +777,8 @@ async fn create_script_internal<'c>(
     db: sqlx::Pool<Postgres>,
     user_db: UserDB,
     webhook: WebhookShared,
-) -> Result<(ScriptHash, Transaction<'c, Postgres>)> {
+    force_deps: bool,
+) -> Result<(ScriptHash, Transaction<'c, Postgres>, LockStatus)> {
     let codebase = ns.codebase.as_ref();
     #[cfg(not(feature = "enterprise"))]
     if ns.ws_error_handler_muted.is_some_and(|val| val) {
@@ -607,7 +929,6 @@ async fn create_script_internal<'c>(
             .and_then(|e| if e.is_empty() { None } else { Some(e) })
     };
 
-    let needs_lock_gen = lock.is_none() && codebase.is_none();
     let envs = ns.envs.as_ref().map(|x| x.as_slice());
     let envs = if ns.envs.is_none() || ns.envs.as_ref().unwrap().is_empty() {
         None
@@ -625,6 +946,28 @@ async fn create_script_internal<'c>(
     } else {
         ns.language.clone()
     };
+
+    let lock_import_hash = if codebase.is_none() {
+        lock_import_hash(&lang, &ns.content)
+    } else {
+        None
+    };
+    let reused_lock = match (&lock, lock_import_hash) {
+        (None, Some(lock_import_hash)) if !force_deps => {
+            find_reusable_lock(&mut tx, &w_id, &ns.path, lock_import_hash).await?
+        }
+        _ => None,
+    };
+    let lock_status = if reused_lock.is_some() {
+        LockStatus::Reused
+    } else if lock.is_none() && codebase.is_none() {
+        LockStatus::Regenerated
+    } else {
+        LockStatus::Provided
+    };
+    let lock = lock.or(reused_lock);
+    let needs_lock_gen = lock_status == LockStatus::Regenerated;
+
     sqlx::query!(
         "INSERT INTO script (workspace_id, hash, path, parent_hashes, summary, description, \
          content, created_by, schema, is_template, extra_perms, lock, language, kind, tag, \
@@ -671,6 +1014,46 @@ async fn create_script_internal<'c>(
     )
     .execute(&mut *tx)
     .await?;
+
+    if let Some(lock_import_hash) = lock_import_hash {
+        sqlx::query(
+            "UPDATE script SET lock_import_hash = $1 WHERE hash = $2 AND workspace_id = $3",
+        )
+        .bind(lock_import_hash)
+        .bind(&hash.0)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    if ns.strict_args.unwrap_or(false) {
+        sqlx::query("UPDATE script SET strict_args = true WHERE hash = $1 AND workspace_id = $2")
+            .bind(&hash.0)
+            .bind(&w_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if let Some(redact_args) = ns.redact_args.filter(|paths| !paths.is_empty()) {
+        sqlx::query("UPDATE script SET redact_args = $1 WHERE hash = $2 AND workspace_id = $3")
+            .bind(redact_args)
+            .bind(&hash.0)
+            .bind(&w_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if let Some(retry_on_failure) = ns.retry_on_failure {
+        sqlx::query(
+            "UPDATE script SET retry_on_failure = $1 WHERE hash = $2 AND workspace_id = $3",
+        )
+        .bind(sqlx::types::Json(retry_on_failure))
+        .bind(&hash.0)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
     let p_path_opt = parent_hashes_and_perms.as_ref().map(|x| x.p_path.clone());
     if let Some(ref p_path) = p_path_opt {
         sqlx::query!(
@@ -746,6 +1129,14 @@ async fn create_script_internal<'c>(
             Some([("hash", hash.to_string().as_str())].into()),
         )
         .await?;
+        webhook.send_deploy_event(DeployEvent::new(
+            DeployKind::Script,
+            DeployAction::Deploy,
+            &w_id,
+            &ns.path,
+            Some(hash.to_string()),
+            &authed,
+        ));
         webhook.send_message(
             w_id.clone(),
             WebhookMessage::UpdateScript {
@@ -771,6 +1162,14 @@ async fn create_script_internal<'c>(
             ),
         )
         .await?;
+        webhook.send_deploy_event(DeployEvent::new(
+            DeployKind::Script,
+            DeployAction::Deploy,
+            &w_id,
+            &ns.path,
+            Some(hash.to_string()),
+            &authed,
+        ));
         webhook.send_message(
             w_id.clone(),
             WebhookMessage::CreateScript {
@@ -830,7 +1229,7 @@ async fn create_script_internal<'c>(
             Some(&authed.clone().into()),
         )
         .await?;
-        Ok((hash, new_tx))
+        Ok((hash, new_tx, lock_status))
     } else {
         handle_deployment_metadata(
             &authed.email,
@@ -846,10 +1245,38 @@ async fn create_script_internal<'c>(
             false,
         )
         .await?;
-        Ok((hash, tx))
+        Ok((hash, tx, lock_status))
     }
 }
 
+/// Lock of the latest successfully locked deployment with the same imports, of the same path
+/// unless the workspace reuses locks across paths
+async fn find_reusable_lock(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    path: &str,
+    lock_import_hash: i64,
+) -> Result<Option<String>> {
+    let lock = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT lock FROM script
+        WHERE workspace_id = $1 AND lock_import_hash = $2
+            AND deleted = false AND lock IS NOT NULL AND lock_error_logs IS NULL
+            AND (path = $3 OR COALESCE(
+                (SELECT reuse_lock_across_paths FROM workspace_settings WHERE workspace_id = $1),
+                false
+            ))
+        ORDER BY path = $3 DESC, created_at DESC
+        LIMIT 1",
+    )
+    .bind(w_id)
+    .bind(lock_import_hash)
+    .bind(path)
+    .fetch_optional(&mut **tx)
+    .await?
+    .flatten();
+    Ok(lock)
+}
+
 pub async fn get_hub_script_by_path(
     Path(path): Path<StripPath>,
     Extension(db): Extension<DB>,
@@ -927,6 +1354,126 @@ async fn get_triggers_count(
     get_triggers_count_internal(&db, &w_id, &path, false).await
 }
 
+#[derive(Deserialize)]
+struct ScriptDependentsQuery {
+    /// levels of transitive dependents to resolve, 1 (direct dependents only) to 3
+    depth: Option<u8>,
+}
+
+#[derive(Serialize, FromRow, Clone, PartialEq, Eq, Hash)]
+struct ScriptDependent {
+    kind: String,
+    path: String,
+    name: String,
+}
+
+const MAX_DEPENDENTS_DEPTH: u8 = 3;
+
+/// Escapes a path to be matched literally by a postgres regex
+fn escape_pg_regex(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c.to_string()
+            } else {
+                format!("\\{c}")
+            }
+        })
+        .collect()
+}
+
+/// Regex matching calls to a runnable from the code of a script, e.g. `wmill.run_script("path")`,
+/// `wmill.runScriptAsync('path')` or `wmill.run_flow_async(path="path")`
+fn runnable_call_regex(kind: &str, path: &str) -> String {
+    let call = if kind == "flow" {
+        "(run_flow|runFlow)"
+    } else {
+        "(run_script|runScript)"
+    };
+    format!(
+        "{call}[A-Za-z_]*\\(\\s*(path\\s*=\\s*)?[\"'`]{}[\"'`]",
+        escape_pg_regex(path)
+    )
+}
+
+async fn find_direct_dependents(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    kind: &str,
+    path: &str,
+) -> Result<Vec<ScriptDependent>> {
+    let mut dependents = sqlx::query_as::<_, ScriptDependent>(
+        "SELECT 'flow' as kind, flow.path, flow.summary as name
+        FROM flow
+        JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+        WHERE flow.workspace_id = $1 AND flow.archived = false
+        AND jsonb_path_exists(
+            flow_version.value,
+            '$.** ? (@.type == $kind && @.path == $path)',
+            jsonb_build_object('kind', $2::text, 'path', $3::text)
+        )
+        ORDER BY flow.path",
+    )
+    .bind(w_id)
+    .bind(kind)
+    .bind(path)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let scripts = sqlx::query_as::<_, ScriptDependent>(
+        "SELECT 'script' as kind, path, summary as name FROM (
+            SELECT DISTINCT ON (path) path, summary, content FROM script
+            WHERE workspace_id = $1 AND archived = false
+            ORDER BY path, created_at DESC
+        ) s
+        WHERE path <> $2 AND content ~ $3
+        ORDER BY path",
+    )
+    .bind(w_id)
+    .bind(path)
+    .bind(runnable_call_regex(kind, path))
+    .fetch_all(&mut **tx)
+    .await?;
+
+    dependents.extend(scripts);
+    Ok(dependents)
+}
+
+async fn list_script_dependents(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<ScriptDependentsQuery>,
+) -> JsonResult<Vec<ScriptDependent>> {
+    let path = path.to_path();
+    let depth = query.depth.unwrap_or(1).clamp(1, MAX_DEPENDENTS_DEPTH);
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    let mut seen: HashSet<(String, String)> =
+        HashSet::from([("script".to_string(), path.to_string())]);
+    let mut dependents = vec![];
+    let mut frontier = vec![("script".to_string(), path.to_string())];
+    for _ in 0..depth {
+        let mut next_frontier = vec![];
+        for (kind, path) in frontier {
+            for dependent in find_direct_dependents(&mut tx, &w_id, &kind, &path).await? {
+                if seen.insert((dependent.kind.clone(), dependent.path.clone())) {
+                    next_frontier.push((dependent.kind.clone(), dependent.path.clone()));
+                    dependents.push(dependent);
+                }
+            }
+        }
+        if next_frontier.is_empty() {
+            break;
+        }
+        frontier = next_frontier;
+    }
+
+    tx.commit().await?;
+    Ok(Json(dependents))
+}
+
 async fn get_script_by_path_w_draft(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -1318,6 +1865,33 @@ pub async fn require_is_writer(authed: &ApiAuthed, path: &str, w_id: &str, db: D
     .await;
 }
 
+/// Renames a script path, the caller must be a writer of both the current and the new path
+async fn rename_script(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(query): Query<RenameQuery>,
+    Json(rename): Json<RenamePath>,
+) -> JsonResult<RenameResult> {
+    let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db.clone()).await?;
+    require_is_writer(&authed, &rename.new_path, &w_id, db.clone()).await?;
+
+    let result = rename_path(
+        &authed,
+        &db,
+        &w_id,
+        RenamedKind::Script,
+        path,
+        &rename.new_path,
+        query.dry_run.unwrap_or(false),
+    )
+    .await?;
+    result.send_deploy_events(&webhook, &authed, &w_id, RenamedKind::Script);
+    Ok(Json(result))
+}
+
 async fn archive_script_by_path(
     authed: ApiAuthed,
     Extension(webhook): Extension<WebhookShared>,
@@ -1365,6 +1939,14 @@ async fn archive_script_by_path(
     )
     .await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Script,
+        DeployAction::Archive,
+        &w_id,
+        path,
+        Some(ScriptHash(hash).to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::DeleteScript { workspace: w_id, hash: hash.to_string() },
@@ -1373,6 +1955,99 @@ async fn archive_script_by_path(
     Ok(())
 }
 
+#[derive(Serialize)]
+struct ArchiveAndCancel {
+    archived: bool,
+    cancelled_job_count: usize,
+}
+
+/// Archives a script and cancels its queued and running jobs
+async fn archive_and_cancel(
+    authed: ApiAuthed,
+    Extension(webhook): Extension<WebhookShared>,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<ArchiveAndCancel> {
+    let path = path.to_path();
+    require_owner_of_path(&authed, path)?;
+
+    let mut tx = db.begin().await?;
+    let hash = sqlx::query_scalar::<_, i64>(
+        "UPDATE script SET archived = true WHERE path = $1 AND workspace_id = $2 RETURNING hash",
+    )
+    .bind(path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| Error::InternalErr(format!("archiving script in {w_id}: {e:#}")))?;
+    let hash = not_found_if_none(hash, "Script", path)?;
+
+    let jobs = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM queue WHERE workspace_id = $1 AND script_path = $2 AND job_kind = 'script'",
+    )
+    .bind(&w_id)
+    .bind(path)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let Json(cancelled) = cancel_jobs(jobs, &db, &authed.username, &w_id, None, None).await?;
+    let cancelled_ids = cancelled.iter().map(|id| id.to_string()).join(",");
+
+    let mut tx = user_db.begin(&authed).await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "scripts.archive_and_cancel",
+        ActionKind::Delete,
+        &w_id,
+        Some(&ScriptHash(hash).to_string()),
+        Some(
+            [
+                ("workspace", w_id.as_str()),
+                ("cancelled_jobs", cancelled_ids.as_str()),
+            ]
+            .into(),
+        ),
+    )
+    .await?;
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::Script {
+            hash: ScriptHash(0), // dummy hash as it will not get inserted in db
+            path: path.to_string(),
+            parent_path: Some(path.to_string()),
+        },
+        Some(format!("Script '{}' archived", path)),
+        true,
+    )
+    .await?;
+
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Script,
+        DeployAction::Archive,
+        &w_id,
+        path,
+        Some(ScriptHash(hash).to_string()),
+        &authed,
+    ));
+    webhook.send_message(
+        w_id.clone(),
+        WebhookMessage::DeleteScript { workspace: w_id, hash: hash.to_string() },
+    );
+
+    Ok(Json(ArchiveAndCancel {
+        archived: true,
+        cancelled_job_count: cancelled.len(),
+    }))
+}
+
 async fn archive_script_by_hash(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -1401,6 +2076,14 @@ async fn archive_script_by_hash(
     .await?;
     tx.commit().await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Script,
+        DeployAction::Archive,
+        &w_id,
+        &script.path,
+        Some(hash.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::DeleteScript { workspace: w_id, hash: hash.to_string() },
@@ -1441,6 +2124,14 @@ async fn delete_script_by_hash(
     .await?;
     tx.commit().await?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Script,
+        DeployAction::Delete,
+        &w_id,
+        &script.path,
+        Some(hash.to_string()),
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::DeleteScript { workspace: w_id, hash: hash.to_string() },
@@ -1564,6 +2255,14 @@ async fn delete_script_by_path(
         ))
     })?;
 
+    webhook.send_deploy_event(DeployEvent::new(
+        DeployKind::Script,
+        DeployAction::Delete,
+        &w_id,
+        path,
+        None,
+        &authed,
+    ));
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::DeleteScriptPath { workspace: w_id, path: path.to_string() },
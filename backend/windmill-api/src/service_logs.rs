@@ -6,7 +6,7 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use crate::utils::{content_plain, require_devops_role};
+use crate::utils::{content_plain, require_devops_role, require_super_admin};
 use axum::{body::Body, extract::Query, response::Response, routing::get, Extension, Json, Router};
 use serde::Serialize;
 
@@ -21,6 +21,7 @@ pub fn global_service() -> Router {
     Router::new()
         .route("/list_files", get(list_files))
         .route("/get_log_file/*path", get(get_log_file))
+        .route("/query", get(query_logs))
 }
 use axum::extract::Path;
 
@@ -136,3 +137,206 @@ async fn get_log_file(
         Err(Error::NotFound(format!("File {path} not found")))
     }
 }
+
+const QUERY_LOGS_DEFAULT_RANGE_MINUTES: i64 = 60;
+const QUERY_LOGS_MAX_FILES: i64 = 240;
+const QUERY_LOGS_MAX_SCANNED_BYTES: usize = 200 * 1024 * 1024;
+const QUERY_LOGS_DEFAULT_MAX_RESULTS: usize = 1000;
+const QUERY_LOGS_MAX_RESULTS: usize = 10_000;
+
+lazy_static::lazy_static! {
+    static ref ANSI_ESCAPE_RE: regex::Regex = regex::Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogSeverity {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl std::str::FromStr for LogSeverity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" => Ok(LogSeverity::Trace),
+            "debug" => Ok(LogSeverity::Debug),
+            "info" => Ok(LogSeverity::Info),
+            "warn" | "warning" => Ok(LogSeverity::Warn),
+            "error" => Ok(LogSeverity::Error),
+            _ => Err(Error::BadRequest(format!("Unknown log severity: {s}"))),
+        }
+    }
+}
+
+/// Severity of a line written by the json or the compact tracing layer
+fn line_severity(line: &str, json_fmt: bool) -> Option<LogSeverity> {
+    if json_fmt {
+        serde_json::from_str::<serde_json::Value>(line)
+            .ok()?
+            .get("level")?
+            .as_str()?
+            .parse()
+            .ok()
+    } else {
+        // compact format: `<timestamp> <LEVEL> <target>: <message>`
+        line.split_whitespace()
+            .take(3)
+            .find_map(|token| token.parse().ok())
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct QueryLogsQuery {
+    hostname: Option<String>,
+    mode: Option<String>,
+    min_severity: Option<String>,
+    before: Option<chrono::DateTime<chrono::Utc>>,
+    after: Option<chrono::DateTime<chrono::Utc>>,
+    /// substring to look for, or a regex if `regex` is true
+    pattern: Option<String>,
+    regex: Option<bool>,
+    max_results: Option<usize>,
+}
+
+async fn read_log_file(hostname: &str, file_path: &str) -> Option<bytes::Bytes> {
+    use windmill_common::tracing_init::TMP_WINDMILL_LOGS_SERVICE;
+
+    let local_path = std::path::Path::new(TMP_WINDMILL_LOGS_SERVICE)
+        .join(hostname)
+        .join(file_path);
+    if let Ok(bytes) = tokio::fs::read(&local_path).await {
+        return Some(bytes.into());
+    }
+
+    #[cfg(feature = "parquet")]
+    {
+        let s3_client = windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS
+            .read()
+            .await
+            .clone();
+        if let Some(s3_client) = s3_client {
+            let path = format!(
+                "{}{hostname}/{file_path}",
+                windmill_common::tracing_init::LOGS_SERVICE
+            );
+            match s3_client.get(&object_store::path::Path::from(path)).await {
+                Ok(file) => return file.bytes().await.ok(),
+                Err(e) => tracing::warn!("Could not fetch log file {hostname}/{file_path}: {e}"),
+            }
+        }
+    }
+
+    None
+}
+
+/// Scans the service log files whose minute overlaps the time range (the last hour by default)
+/// and returns the matching lines. Works without the indexer: the number of files and bytes read
+/// by a single request is capped.
+async fn query_logs(
+    ApiAuthed { email, .. }: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Query(lq): Query<QueryLogsQuery>,
+) -> windmill_common::error::Result<Response> {
+    require_super_admin(&db, &email).await?;
+
+    let min_severity = lq
+        .min_severity
+        .as_deref()
+        .map(|s| s.parse::<LogSeverity>())
+        .transpose()?;
+    let matcher = match lq.pattern.as_deref().filter(|p| !p.is_empty()) {
+        Some(pattern) if lq.regex.unwrap_or(false) => Some(
+            regex::Regex::new(pattern)
+                .map_err(|e| Error::BadRequest(format!("Invalid regex: {e}")))?,
+        ),
+        Some(pattern) => Some(regex::Regex::new(&regex::escape(pattern)).unwrap()),
+        None => None,
+    };
+    let max_results = lq
+        .max_results
+        .unwrap_or(QUERY_LOGS_DEFAULT_MAX_RESULTS)
+        .clamp(1, QUERY_LOGS_MAX_RESULTS);
+    let before = lq.before.unwrap_or_else(chrono::Utc::now);
+    let after = lq
+        .after
+        .unwrap_or_else(|| before - chrono::Duration::minutes(QUERY_LOGS_DEFAULT_RANGE_MINUTES));
+    if after > before {
+        return Err(Error::BadRequest("after must be before before".to_string()));
+    }
+
+    // a file holds the logs of the minute starting at log_ts
+    let files = sqlx::query_as::<_, (String, String, bool)>(
+        "SELECT hostname, file_path, COALESCE(json_fmt, false) FROM log_file
+        WHERE log_ts <= $1 AND log_ts > $2 - interval '1 minute'
+            AND ($3::text IS NULL OR hostname = $3)
+            AND ($4::text IS NULL OR mode::text = $4)
+        ORDER BY log_ts, hostname
+        LIMIT $5",
+    )
+    .bind(before.naive_utc())
+    .bind(after.naive_utc())
+    .bind(&lq.hostname)
+    .bind(&lq.mode)
+    .bind(QUERY_LOGS_MAX_FILES)
+    .fetch_all(&db)
+    .await?;
+
+    let mut scanned_bytes = 0;
+    let mut lines = vec![];
+    'files: for (hostname, file_path, json_fmt) in files {
+        if scanned_bytes >= QUERY_LOGS_MAX_SCANNED_BYTES {
+            tracing::warn!("Service logs query stopped after scanning {scanned_bytes} bytes");
+            break;
+        }
+        let Some(bytes) = read_log_file(&hostname, &file_path).await else {
+            continue;
+        };
+        scanned_bytes += bytes.len();
+        for line in String::from_utf8_lossy(&bytes).lines() {
+            let line = ANSI_ESCAPE_RE.replace_all(line, "");
+            if min_severity.is_some_and(|min| {
+                line_severity(&line, json_fmt).map_or(true, |severity| severity < min)
+            }) {
+                continue;
+            }
+            if matcher.as_ref().is_some_and(|m| !m.is_match(&line)) {
+                continue;
+            }
+            lines.push(line.into_owned());
+            if lines.len() >= max_results {
+                break 'files;
+            }
+        }
+    }
+
+    Ok(content_plain(Body::from(lines.join("\n"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_severity() {
+        assert_eq!(
+            line_severity(
+                "2025-02-07T10:00:00.123456Z  WARN windmill_api: something odd",
+                false
+            ),
+            Some(LogSeverity::Warn)
+        );
+        assert_eq!(
+            line_severity(
+                r#"{"timestamp":"2025-02-07T10:00:00Z","level":"ERROR","message":"x"}"#,
+                true
+            ),
+            Some(LogSeverity::Error)
+        );
+        assert_eq!(line_severity("no level here", false), None);
+        assert!(LogSeverity::Info < LogSeverity::Warn);
+    }
+}
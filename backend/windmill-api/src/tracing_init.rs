@@ -11,6 +11,8 @@ use hyper::Response;
 use tower_http::trace::{MakeSpan, OnFailure, OnResponse};
 use uuid::Uuid;
 
+use crate::RequestId;
+
 lazy_static::lazy_static! {
     static ref LOG_REQUESTS: bool = std::env::var("LOG_REQUESTS")
     .ok()
@@ -63,6 +65,11 @@ impl<B> MakeSpan<B> for MyMakeSpan {
             .get(TRACING_HEADER.as_str())
             .and_then(|x| x.to_str().map(|x| x.to_string()).ok())
             .unwrap_or(Uuid::new_v4().to_string());
+        let request_id = request
+            .extensions()
+            .get::<RequestId>()
+            .map(|x| x.0.as_str())
+            .unwrap_or_default();
         tracing::info_span!(
             "request",
             method = %request.method(),
@@ -70,6 +77,7 @@ impl<B> MakeSpan<B> for MyMakeSpan {
             username = field::Empty,
             workspace_id = field::Empty,
             traceId = tracing_id,
+            requestId = request_id,
             email = field::Empty,
         )
     }
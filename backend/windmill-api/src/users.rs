@@ -18,19 +18,26 @@ pub use crate::auth::Tokened;
 use crate::utils::{
     generate_instance_wide_unique_username, get_instance_username_or_create_pending,
 };
+use crate::workspaces::{invite_user_internal, NewWorkspaceInvite};
 use crate::{
     db::DB, utils::require_super_admin, webhook_util::WebhookShared, COOKIE_DOMAIN, IS_SECURE,
 };
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use axum::{
     async_trait,
-    extract::{Extension, FromRequestParts, Path, Query},
+    extract::{Extension, FromRequestParts, Path, Query, Request},
     http::request::Parts,
+    middleware::Next,
     response::{IntoResponse, Response},
     routing::{delete, get, post},
-    Json, Router,
+    Form, Json, Router,
 };
-use hyper::{header::LOCATION, StatusCode};
+use base64::Engine;
+use hyper::{
+    header::{AUTHORIZATION, LOCATION, WWW_AUTHENTICATE},
+    StatusCode,
+};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -38,17 +45,17 @@ use sqlx::FromRow;
 use time::OffsetDateTime;
 use tower_cookies::{Cookie, Cookies};
 use tracing::Instrument;
-use windmill_audit::audit_ee::{audit_log, AuditAuthor};
+use windmill_audit::audit_ee::{audit_log, AuditAuthor, AuditAuthorable};
 use windmill_audit::ActionKind;
 use windmill_common::auth::fetch_authed_from_permissioned_as;
 use windmill_common::global_settings::AUTOMATE_USERNAME_CREATION_SETTING;
-use windmill_common::oauth2::InstanceEvent;
+use windmill_common::oauth2::{InstanceEvent, REQUIRE_PREEXISTING_USER_FOR_OAUTH};
 use windmill_common::users::COOKIE_NAME;
 use windmill_common::users::{truncate_token, username_to_permissioned_as};
 use windmill_common::utils::paginate;
 use windmill_common::worker::CLOUD_HOSTED;
 use windmill_common::{
-    auth::{get_folders_for_user, get_groups_for_user},
+    auth::{get_folders_for_user, get_groups_for_user, JWTAuthClaims, JWT_SECRET},
     db::UserDB,
     error::{self, Error, JsonResult, Result},
     utils::{not_found_if_none, rd_string, require_admin, Pagination, StripPath},
@@ -72,6 +79,7 @@ pub fn workspaced_service() -> Router {
         .route("/whoami", get(whoami))
         .route("/leave", post(leave_workspace))
         .route("/username_to_email/:username", get(username_to_email))
+        .route("/batch_invite", post(batch_invite))
 }
 
 pub fn global_service() -> Router {
@@ -79,6 +87,7 @@ pub fn global_service() -> Router {
         .route("/exists/:email", get(exists_email))
         .route("/email", get(get_email))
         .route("/whoami", get(global_whoami))
+        .route("/me/scopes", get(get_my_scopes))
         .route("/list_invites", get(list_invites))
         .route("/decline_invite", post(decline_invite))
         .route("/accept_invite", post(accept_invite))
@@ -93,6 +102,7 @@ pub fn global_service() -> Router {
         .route("/rename/:user", post(rename_user))
         .route("/tokens/create", post(create_token))
         .route("/tokens/delete/:token_prefix", delete(delete_token))
+        .route("/tokens/:token_prefix/extend", post(extend_token))
         .route("/tokens/list", get(list_tokens))
         .route("/tokens/impersonate", post(impersonate))
         .route("/usage", get(get_usage))
@@ -118,6 +128,10 @@ pub fn make_unauthed_service() -> Router {
         .route("/login", post(login))
         .route("/logout", post(logout).get(logout))
         .route("/is_first_time_setup", get(is_first_time_setup))
+        .route(
+            "/introspect",
+            post(introspect).route_layer(axum::middleware::from_fn(has_introspection_client)),
+        )
 }
 
 pub async fn maybe_refresh_folders(
@@ -157,11 +171,51 @@ where
     if authed.scopes.as_ref().is_some_and(|scopes| {
         scopes
             .iter()
-            .any(|s| s.starts_with("jobs:") || s.starts_with("run:"))
+            .any(|s| s.starts_with("jobs:") || s.starts_with("run:") || s.starts_with("ai:"))
     }) {
         let req = &required();
-        if !authed.scopes.as_ref().unwrap().contains(req) {
-            return Err(Error::BadRequest(format!("missing required scope: {req}")));
+        if !authed
+            .scopes
+            .as_ref()
+            .unwrap()
+            .iter()
+            .any(|s| scope_matches(s, req))
+        {
+            return Err(Error::NotAuthorized(format!(
+                "missing required scope: {req}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// a scope matches the required one either exactly or, if it ends with `*`,
+/// when the required scope starts with everything before the `*`
+/// (e.g. `run:script/f/alerts/*` matches `run:script/f/alerts/notify`)
+fn scope_matches(scope: &str, required: &str) -> bool {
+    match scope.strip_suffix('*') {
+        Some(prefix) => required.starts_with(prefix),
+        None => scope == required,
+    }
+}
+
+fn validate_scopes(scopes: &[String]) -> error::Result<()> {
+    for scope in scopes {
+        if scope.trim().is_empty() {
+            return Err(Error::BadRequest("scopes cannot be empty".to_string()));
+        }
+        if scope.trim_end_matches('*').contains('*') || scope.ends_with("**") {
+            return Err(Error::BadRequest(format!(
+                "invalid scope {scope}: `*` is only allowed as the last character"
+            )));
+        }
+        if (scope.starts_with("run:script/") || scope.starts_with("run:flow/"))
+            && scope.ends_with('*')
+            && !scope.ends_with("/*")
+        {
+            return Err(Error::BadRequest(format!(
+                "invalid scope {scope}: path globs must end with `/*`"
+            )));
         }
     }
     Ok(())
@@ -357,6 +411,9 @@ pub struct NewToken {
     pub impersonate_email: Option<String>,
     pub scopes: Option<Vec<String>>,
     pub workspace_id: Option<String>,
+    /// overrides the instance wide run rate limit for this token, 0 disables it
+    pub run_rate_limit_per_minute: Option<i32>,
+    pub run_rate_limit_burst: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -642,6 +699,154 @@ async fn logout(
     }
 }
 
+lazy_static::lazy_static! {
+    /// Credentials of the resource servers allowed to introspect tokens, reloaded from the
+    /// `introspection_client` global setting
+    pub static ref INTROSPECTION_CLIENT: Arc<tokio::sync::RwLock<Option<IntrospectionClient>>> =
+        Arc::new(tokio::sync::RwLock::new(None));
+}
+
+#[derive(Deserialize, Clone)]
+pub struct IntrospectionClient {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Compares in a time that does not depend on the position of the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Only lets through the requests authenticated with the introspection client credentials, sent
+/// with HTTP basic authentication as recommended by RFC 7662
+pub async fn has_introspection_client(request: Request, next: Next) -> Response {
+    let credentials = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Basic "))
+        .and_then(|c| {
+            base64::engine::general_purpose::STANDARD
+                .decode(c.trim())
+                .ok()
+        })
+        .and_then(|c| String::from_utf8(c).ok());
+
+    let authorized = match (credentials, INTROSPECTION_CLIENT.read().await.as_ref()) {
+        (Some(credentials), Some(client)) => {
+            credentials.split_once(':').is_some_and(|(id, secret)| {
+                id == client.client_id
+                    && constant_time_eq(secret.as_bytes(), client.client_secret.as_bytes())
+            })
+        }
+        _ => false,
+    };
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, "Basic")],
+            "Invalid introspection client credentials",
+        )
+            .into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct IntrospectionRequest {
+    token: String,
+}
+
+#[derive(Serialize, Default)]
+struct TokenIntrospection {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+async fn introspect_jwt(jwt: &str) -> Option<TokenIntrospection> {
+    let jwt_secret = JWT_SECRET.read().await;
+    if jwt_secret.is_empty() {
+        return None;
+    }
+    let claims = jsonwebtoken::decode::<JWTAuthClaims>(
+        jwt,
+        &jsonwebtoken::DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
+    )
+    .ok()?
+    .claims;
+    Some(TokenIntrospection {
+        active: true,
+        username: Some(claims.username),
+        email: Some(claims.email),
+        exp: Some(claims.exp as i64),
+        scope: claims.scopes.map(|s| s.join(" ")),
+    })
+}
+
+async fn introspect_token(db: &DB, token: &str) -> Result<Option<TokenIntrospection>> {
+    let token = sqlx::query_as::<
+        _,
+        (
+            Option<String>,
+            Option<String>,
+            Option<chrono::DateTime<chrono::Utc>>,
+            Option<Vec<String>>,
+            Option<String>,
+        ),
+    >(
+        "SELECT token.email, token.owner, token.expiration, token.scopes, password.username
+        FROM token LEFT JOIN password ON password.email = token.email
+        WHERE token.token = $1 AND (token.expiration > now() OR token.expiration IS NULL)",
+    )
+    .bind(token)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(token.map(|(email, owner, expiration, scopes, username)| {
+        // tokens owned by a workspace user carry its username, instance tokens fall back to the
+        // instance username then to the email like when authenticating
+        let username = owner
+            .as_deref()
+            .and_then(|o| o.strip_prefix("u/"))
+            .map(str::to_string)
+            .or(username)
+            .or_else(|| email.clone());
+        TokenIntrospection {
+            active: true,
+            username,
+            email,
+            exp: expiration.map(|e| e.timestamp()),
+            scope: scopes.map(|s| s.join(" ")),
+        }
+    }))
+}
+
+/// RFC 7662 introspection of the bearer tokens of the instance, invalid and expired tokens are
+/// only reported as inactive
+async fn introspect(
+    Extension(db): Extension<DB>,
+    Form(IntrospectionRequest { token }): Form<IntrospectionRequest>,
+) -> JsonResult<TokenIntrospection> {
+    let introspection = if token.starts_with("jwt_ext_") {
+        None
+    } else if let Some(jwt) = token.strip_prefix("jwt_") {
+        introspect_jwt(jwt).await
+    } else {
+        introspect_token(&db, &token).await?
+    };
+    Ok(Json(introspection.unwrap_or_default()))
+}
+
 async fn whoami(
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
@@ -711,6 +916,11 @@ async fn global_whoami(
     }
 }
 
+/// Scopes of the token used for the request, `*` when the token is not restricted
+async fn get_my_scopes(ApiAuthed { scopes, .. }: ApiAuthed) -> JsonResult<Vec<String>> {
+    Ok(Json(scopes.unwrap_or_else(|| vec!["*".to_string()])))
+}
+
 async fn exists_email(Extension(db): Extension<DB>, Path(email): Path<String>) -> JsonResult<bool> {
     let exists = sqlx::query_scalar!(
         "SELECT EXISTS(SELECT 1 FROM password WHERE email = $1)",
@@ -1017,6 +1227,94 @@ async fn decline_invite(
 
 lazy_static! {
     pub static ref VALID_USERNAME: Regex = Regex::new(r#"^[a-zA-Z][a-zA-Z_0-9]*$"#).unwrap();
+    pub static ref VALID_EMAIL: Regex = Regex::new(r#"^[^@\s]+@[^@\s]+\.[^@\s]+$"#).unwrap();
+}
+
+const MAX_BATCH_INVITES: usize = 100;
+
+#[derive(Deserialize)]
+struct BatchInvite {
+    emails: Vec<String>,
+    is_admin: bool,
+    operator: bool,
+}
+
+#[derive(Serialize, Default)]
+struct BatchInviteResult {
+    invited: Vec<String>,
+    already_members: Vec<String>,
+    invalid_emails: Vec<String>,
+    /// not invited because they have no account on the instance while oauth logins require one
+    missing_instance_users: Vec<String>,
+}
+
+async fn batch_invite(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(webhook): Extension<WebhookShared>,
+    Path(w_id): Path<String>,
+    Json(bi): Json<BatchInvite>,
+) -> JsonResult<BatchInviteResult> {
+    require_admin(authed.is_admin, &authed.username)?;
+    if bi.emails.len() > MAX_BATCH_INVITES {
+        return Err(Error::BadRequest(format!(
+            "at most {MAX_BATCH_INVITES} emails can be invited at once"
+        )));
+    }
+
+    let mut result = BatchInviteResult::default();
+    let mut emails = vec![];
+    for email in bi.emails {
+        let email = email.trim().to_lowercase();
+        if !VALID_EMAIL.is_match(&email) {
+            result.invalid_emails.push(email);
+        } else if !emails.contains(&email) {
+            emails.push(email);
+        }
+    }
+
+    let instance_users: Vec<String> =
+        if REQUIRE_PREEXISTING_USER_FOR_OAUTH.load(std::sync::atomic::Ordering::Relaxed) {
+            sqlx::query_scalar("SELECT email FROM password WHERE email = ANY($1)")
+                .bind(&emails)
+                .fetch_all(&db)
+                .await?
+        } else {
+            emails.clone()
+        };
+
+    let is_admin = bi.is_admin.to_string();
+    let operator = bi.operator.to_string();
+    for email in emails {
+        if !instance_users.contains(&email) {
+            result.missing_instance_users.push(email);
+            continue;
+        }
+        let nu = NewWorkspaceInvite { email, is_admin: bi.is_admin, operator: bi.operator };
+        if !invite_user_internal(&db, &webhook, &w_id, &nu).await? {
+            result.already_members.push(nu.email);
+            continue;
+        }
+        audit_log(
+            &db,
+            &authed,
+            "users.batch_invite",
+            ActionKind::Create,
+            &w_id,
+            Some(&nu.email),
+            Some(
+                [
+                    ("is_admin", is_admin.as_str()),
+                    ("operator", operator.as_str()),
+                ]
+                .into(),
+            ),
+        )
+        .await?;
+        result.invited.push(nu.email);
+    }
+
+    Ok(Json(result))
 }
 
 async fn accept_invite(
@@ -1315,6 +1613,17 @@ async fn update_workspace_user(
 
     tx.commit().await?;
 
+    if eu.disabled == Some(true) {
+        cancel_running_jobs_of_user(
+            &db,
+            &user_email,
+            Some(&w_id),
+            &authed,
+            "user disabled in the workspace",
+        )
+        .await?;
+    }
+
     handle_deployment_metadata(
         &authed.email,
         &authed.username,
@@ -1388,6 +1697,7 @@ async fn delete_user(
     Extension(db): Extension<DB>,
 ) -> Result<String> {
     require_super_admin(&db, &authed.email).await?;
+    cancel_running_jobs_of_user(&db, &email_to_delete, None, &authed, "user deleted").await?;
     let mut tx = db.begin().await?;
 
     sqlx::query!("DELETE FROM password WHERE email = $1", &email_to_delete)
@@ -1431,6 +1741,72 @@ async fn delete_user(
     Ok(format!("email {} deleted", &email_to_delete))
 }
 
+/// Cancels the running jobs of a user deprovisioned through SCIM, in every workspace they are a
+/// member of, so that they do not keep using compute and concurrency slots. Must be called once the
+/// user is deactivated and before their workspace memberships are removed. Returns the number of
+/// cancelled jobs.
+pub async fn cancel_running_jobs_of_deprovisioned_user(db: &DB, email: &str) -> Result<usize> {
+    let author = AuditAuthor {
+        email: "scim".to_string(),
+        username: "scim".to_string(),
+        username_override: None,
+    };
+    cancel_running_jobs_of_user(db, email, None, &author, "user deprovisioned through SCIM").await
+}
+
+/// Cancels the running jobs created by `email`, only in `w_id` if set, on behalf of `author`.
+/// Must be called before the workspace memberships of the user are removed.
+async fn cancel_running_jobs_of_user(
+    db: &DB,
+    email: &str,
+    w_id: Option<&str>,
+    author: &impl AuditAuthorable,
+    reason: &str,
+) -> Result<usize> {
+    let jobs = sqlx::query_as::<_, (String, uuid::Uuid)>(
+        "SELECT queue.workspace_id, queue.id FROM queue
+        JOIN usr ON usr.workspace_id = queue.workspace_id AND usr.username = queue.created_by
+        WHERE usr.email = $1 AND queue.running = true
+            AND ($2::varchar IS NULL OR queue.workspace_id = $2)",
+    )
+    .bind(email)
+    .bind(w_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut cancelled_count = 0;
+    for (w_id, jobs) in jobs.into_iter().into_group_map() {
+        let Json(cancelled) = crate::jobs::cancel_jobs(
+            jobs,
+            db,
+            author.username(),
+            &w_id,
+            None,
+            Some(reason.to_string()),
+        )
+        .await?;
+        cancelled_count += cancelled.len();
+
+        let cancelled = cancelled.len().to_string();
+        let mut tx = db.begin().await?;
+        audit_log(
+            &mut *tx,
+            author,
+            "users.cancel_jobs",
+            ActionKind::Delete,
+            &w_id,
+            Some(email),
+            Some([("cancelled", cancelled.as_str()), ("reason", reason)].into()),
+        )
+        .await?;
+        tx.commit().await?;
+    }
+    if cancelled_count > 0 {
+        tracing::info!("Cancelled {cancelled_count} running jobs of {email}: {reason}");
+    }
+    Ok(cancelled_count)
+}
+
 lazy_static::lazy_static! {
     pub static ref NEW_USER_WEBHOOK: Option<String> = std::env::var("NEW_USER_WEBHOOK").ok();
 
@@ -1464,6 +1840,14 @@ async fn delete_workspace_user(
     .await?;
 
     let email_to_delete = not_found_if_none(email_to_delete_o, "User", &username_to_delete)?;
+    cancel_running_jobs_of_user(
+        &db,
+        &email_to_delete,
+        Some(&w_id),
+        &authed,
+        "user removed from the workspace",
+    )
+    .await?;
 
     sqlx::query_scalar!(
         "DELETE FROM usr WHERE email = $1 AND workspace_id = $2",
@@ -1731,6 +2115,16 @@ async fn create_token(
     authed: ApiAuthed,
     Json(new_token): Json<NewToken>,
 ) -> Result<(StatusCode, String)> {
+    if let Some(scopes) = new_token.scopes.as_ref() {
+        validate_scopes(scopes)?;
+    }
+    if new_token.run_rate_limit_per_minute.is_some_and(|x| x < 0)
+        || new_token.run_rate_limit_burst.is_some_and(|x| x < 1)
+    {
+        return Err(Error::BadRequest(
+            "run_rate_limit_per_minute cannot be negative and run_rate_limit_burst must be at least 1".to_string(),
+        ));
+    }
     let token = rd_string(32);
     let mut tx = db.begin().await?;
 
@@ -1743,8 +2137,9 @@ async fn create_token(
     .unwrap_or(false);
     sqlx::query!(
         "INSERT INTO token
-            (token, email, label, expiration, super_admin, scopes, workspace_id)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            (token, email, label, expiration, super_admin, scopes, workspace_id,
+            run_rate_limit_per_minute, run_rate_limit_burst)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         token,
         authed.email,
         new_token.label,
@@ -1752,6 +2147,8 @@ async fn create_token(
         is_super_admin,
         new_token.scopes.as_ref().map(|x| x.as_slice()),
         new_token.workspace_id,
+        new_token.run_rate_limit_per_minute,
+        new_token.run_rate_limit_burst,
     )
     .execute(&mut *tx)
     .await?;
@@ -1902,6 +2299,107 @@ async fn delete_token(
     ))
 }
 
+const MAX_TOKEN_EXTENSION_DAYS: u32 = 365;
+
+#[derive(Deserialize)]
+struct ExtendToken {
+    extend_by_days: u32,
+}
+
+async fn extend_token(
+    Extension(db): Extension<DB>,
+    authed: ApiAuthed,
+    Tokened { token: caller_token }: Tokened,
+    Path(token_prefix): Path<String>,
+    Json(et): Json<ExtendToken>,
+) -> JsonResult<chrono::DateTime<chrono::Utc>> {
+    check_scopes(&authed, || "users:extend_token".to_string())?;
+    if et.extend_by_days == 0 || et.extend_by_days > MAX_TOKEN_EXTENSION_DAYS {
+        return Err(Error::BadRequest(format!(
+            "extend_by_days must be between 1 and {MAX_TOKEN_EXTENSION_DAYS}"
+        )));
+    }
+    let mut tx = db.begin().await?;
+
+    // scoped tokens and the tokens of jobs or triggers cannot extend the tokens of the user
+    let is_session = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM token
+               WHERE token = $1 AND label = 'session' AND scopes IS NULL)",
+    )
+    .bind(&caller_token)
+    .fetch_one(&mut *tx)
+    .await?;
+    if !is_session {
+        return Err(Error::NotAuthorized(
+            "tokens can only be extended with an unscoped session token".to_string(),
+        ));
+    }
+
+    // the prefix is matched literally, LIKE wildcards it contains are escaped
+    let pattern = format!(
+        "{}%",
+        token_prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let tokens: Vec<(String, Option<chrono::DateTime<chrono::Utc>>)> = sqlx::query_as(
+        "SELECT token, expiration FROM token
+               WHERE email = $1
+                 AND token LIKE $2
+                 FOR UPDATE",
+    )
+    .bind(&authed.email)
+    .bind(&pattern)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let token = match tokens.as_slice() {
+        [] => {
+            return Err(Error::NotFound(format!(
+                "no token with prefix {token_prefix}"
+            )))
+        }
+        [(_, None)] => {
+            return Err(Error::BadRequest(format!(
+                "token with prefix {token_prefix} never expires and cannot be extended"
+            )))
+        }
+        [(token, Some(_))] => token,
+        _ => {
+            return Err(Error::BadRequest(format!(
+                "{} tokens have the prefix {token_prefix}, use a longer prefix",
+                tokens.len()
+            )))
+        }
+    };
+
+    let expiration: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
+        "UPDATE token SET expiration = now() + make_interval(days => $2)
+               WHERE token = $1
+           RETURNING expiration",
+    )
+    .bind(token)
+    .bind(et.extend_by_days as i32)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let extend_by_days = et.extend_by_days.to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "users.token.extend",
+        ActionKind::Update,
+        &"global",
+        Some(&token[0..10]),
+        Some([("extend_by_days", extend_by_days.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(expiration))
+}
+
 async fn leave_workspace(
     Extension(db): Extension<DB>,
     Path(w_id): Path<String>,
@@ -2740,3 +3238,67 @@ async fn update_username_in_workpsace<'c>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoped_authed(scopes: &[&str]) -> ApiAuthed {
+        ApiAuthed {
+            email: "test@windmill.dev".to_string(),
+            username: "test".to_string(),
+            is_admin: false,
+            is_operator: false,
+            groups: vec![],
+            folders: vec![],
+            scopes: Some(scopes.iter().map(|s| s.to_string()).collect()),
+            username_override: None,
+        }
+    }
+
+    #[test]
+    fn check_scopes_glob_suffix() {
+        let authed = scoped_authed(&["run:script/f/alerts/*"]);
+
+        assert!(check_scopes(&authed, || "run:script/f/alerts/notify".to_string()).is_ok());
+        assert!(check_scopes(&authed, || "run:script/f/alerts/sub/page".to_string()).is_ok());
+        assert!(matches!(
+            check_scopes(&authed, || "run:script/f/billing/charge".to_string()),
+            Err(Error::NotAuthorized(_))
+        ));
+        assert!(matches!(
+            check_scopes(&authed, || "run:flow/f/alerts/notify".to_string()),
+            Err(Error::NotAuthorized(_))
+        ));
+        assert!(matches!(
+            check_scopes(&authed, || "jobs:listjobs".to_string()),
+            Err(Error::NotAuthorized(_))
+        ));
+    }
+
+    #[test]
+    fn check_scopes_exact_and_unscoped() {
+        let authed = scoped_authed(&["run:flow/f/alerts/main", "jobs:listjobs"]);
+        assert!(check_scopes(&authed, || "run:flow/f/alerts/main".to_string()).is_ok());
+        assert!(check_scopes(&authed, || "jobs:listjobs".to_string()).is_ok());
+        assert!(check_scopes(&authed, || "run:flow/f/alerts/main2".to_string()).is_err());
+
+        assert!(check_scopes(&authed, || "ai:use".to_string()).is_err());
+
+        let ai_only = scoped_authed(&["ai:use"]);
+        assert!(check_scopes(&ai_only, || "ai:use".to_string()).is_ok());
+        assert!(check_scopes(&ai_only, || "jobs:listjobs".to_string()).is_err());
+
+        let unscoped = ApiAuthed { scopes: None, ..scoped_authed(&[]) };
+        assert!(check_scopes(&unscoped, || "run:script/u/any".to_string()).is_ok());
+    }
+
+    #[test]
+    fn validate_scopes_globs() {
+        assert!(validate_scopes(&["run:script/f/alerts/*".to_string()]).is_ok());
+        assert!(validate_scopes(&["jobs:listjobs".to_string()]).is_ok());
+        assert!(validate_scopes(&["run:script/f/*/notify".to_string()]).is_err());
+        assert!(validate_scopes(&["run:script/f/alerts*".to_string()]).is_err());
+        assert!(validate_scopes(&["run:script/f/alerts/**".to_string()]).is_err());
+    }
+}
@@ -24,7 +24,7 @@ use windmill_audit::ActionKind;
 use windmill_common::{
     db::UserDB,
     error::{Error, JsonResult, Result},
-    utils::{not_found_if_none, paginate, Pagination, StripPath},
+    utils::{not_found_if_none, paginate, require_admin, Pagination, StripPath},
     variables::{
         build_crypt, get_reserved_variables, ContextualVariable, CreateVariable, ListableVariable,
     },
@@ -32,7 +32,7 @@ use windmill_common::{
 
 use lazy_static::lazy_static;
 use windmill_common::variables::{decrypt, encrypt};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, Transaction};
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
 
@@ -51,6 +51,7 @@ pub fn workspaced_service() -> Router {
         .route("/delete/*path", delete(delete_variable))
         .route("/create", post(create_variable))
         .route("/encrypt", post(encrypt_value))
+        .route("/rotation_report", get(get_rotation_report))
 }
 
 async fn list_contextual_variables(
@@ -327,8 +328,8 @@ async fn create_variable(
 
     sqlx::query!(
         "INSERT INTO variable
-            (workspace_id, path, value, is_secret, description, account, is_oauth, expires_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            (workspace_id, path, value, is_secret, description, account, is_oauth, expires_at, last_rotated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, CASE WHEN $4 THEN now() END)",
         &w_id,
         variable.path,
         value,
@@ -499,6 +500,9 @@ async fn update_variable(
             nvalue
         };
         sqlb.set_str("value", &value);
+        if is_secret {
+            sqlb.set("last_rotated_at", "now()");
+        }
     }
 
     if let Some(desc) = ns.description {
@@ -520,6 +524,9 @@ async fn update_variable(
             ));
         }
         sqlb.set_str("is_secret", nbool);
+        if !nbool {
+            sqlb.set("last_rotated_at", "NULL");
+        }
     }
     sqlb.returning("path");
     let mut tx: Transaction<'_, Postgres> = user_db.begin(&authed).await?;
@@ -599,6 +606,44 @@ async fn update_variable(
     Ok(format!("variable {} updated (npath: {:?})", path, npath))
 }
 
+#[derive(Deserialize)]
+struct RotationReportQuery {
+    older_than_days: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SecretRotation {
+    path: String,
+    last_rotated_at: Option<chrono::DateTime<chrono::Utc>>,
+    days_since_rotation: Option<i64>,
+}
+
+/// Secret variables that were never rotated since rotation tracking exists have no
+/// `last_rotated_at` and come first, as they are the stalest ones
+async fn get_rotation_report(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(RotationReportQuery { older_than_days }): Query<RotationReportQuery>,
+) -> JsonResult<Vec<SecretRotation>> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let rows = sqlx::query_as!(
+        SecretRotation,
+        "SELECT path, last_rotated_at, EXTRACT(DAY FROM now() - last_rotated_at)::bigint AS days_since_rotation
+        FROM variable
+        WHERE workspace_id = $1 AND is_secret IS TRUE
+            AND ($2::int IS NULL OR last_rotated_at IS NULL OR last_rotated_at < now() - make_interval(days => $2::int))
+        ORDER BY last_rotated_at ASC NULLS FIRST, path",
+        &w_id,
+        older_than_days.map(|d| d.min(i32::MAX as u32) as i32)
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(rows))
+}
+
 fn replace_path(v: serde_json::Value, path: &str, npath: &str) -> Value {
     match v {
         Value::Object(v) => Value::Object(
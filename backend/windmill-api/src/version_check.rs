@@ -0,0 +1,108 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Comparison of the running version with the latest release, published on GitHub or on the
+//! update server set by the `version_check_url` global setting for air-gapped instances.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use windmill_common::{error::AppError, utils::GIT_VERSION};
+
+use crate::HTTP_CLIENT;
+
+const DEFAULT_VERSION_CHECK_URL: &str =
+    "https://api.github.com/repos/windmill-labs/windmill/releases/latest";
+/// the GitHub API is rate limited per ip
+const LATEST_RELEASE_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+lazy_static::lazy_static! {
+    /// replaces the GitHub releases API, must answer with the `tag_name` and optional `html_url`
+    /// of the latest release
+    pub static ref VERSION_CHECK_URL: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
+
+    static ref LATEST_RELEASE: RwLock<Option<(CachedRelease, Instant)>> = RwLock::new(None);
+}
+
+#[derive(Deserialize, Clone)]
+struct LatestRelease {
+    tag_name: String,
+    html_url: Option<String>,
+}
+
+struct CachedRelease {
+    /// version check url the release was fetched from
+    url: String,
+    release: LatestRelease,
+}
+
+#[derive(Serialize)]
+pub struct VersionCheck {
+    current: String,
+    latest: String,
+    up_to_date: bool,
+    release_notes_url: Option<String>,
+}
+
+async fn latest_release() -> anyhow::Result<LatestRelease> {
+    let url = VERSION_CHECK_URL
+        .read()
+        .await
+        .clone()
+        .unwrap_or_else(|| DEFAULT_VERSION_CHECK_URL.to_string());
+    if let Some((cached, fetched_at)) = LATEST_RELEASE.read().await.as_ref() {
+        if cached.url == url && fetched_at.elapsed() < LATEST_RELEASE_CACHE_TTL {
+            return Ok(cached.release.clone());
+        }
+    }
+
+    let release = HTTP_CLIENT
+        .get(&url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .with_context(|| format!("Impossible to reach {url}"))?
+        .error_for_status()?
+        .json::<LatestRelease>()
+        .await
+        .context("Error reading latest released version")?;
+    *LATEST_RELEASE.write().await = Some((
+        CachedRelease { url, release: release.clone() },
+        Instant::now(),
+    ));
+    Ok(release)
+}
+
+fn current_release() -> &'static str {
+    GIT_VERSION.split('-').next().unwrap_or(GIT_VERSION)
+}
+
+pub async fn is_up_to_date() -> Result<String, AppError> {
+    let version = latest_release().await?.tag_name;
+    if version == current_release() {
+        Ok("yes".to_string())
+    } else {
+        Ok(format!("Update: {GIT_VERSION} -> {version}"))
+    }
+}
+
+pub async fn version_check() -> Result<Json<VersionCheck>, AppError> {
+    let release = latest_release().await?;
+    Ok(Json(VersionCheck {
+        current: GIT_VERSION.to_string(),
+        up_to_date: release.tag_name == current_release(),
+        latest: release.tag_name,
+        release_notes_url: release.html_url,
+    }))
+}
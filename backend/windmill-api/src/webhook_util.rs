@@ -7,8 +7,9 @@ use tokio::{select, sync::mpsc};
 #[cfg(feature = "prometheus")]
 use windmill_common::METRICS_ENABLED;
 
-use crate::db::DB;
-use windmill_common::oauth2::InstanceEvent;
+use crate::db::{ApiAuthed, DB};
+use hmac::Mac;
+use windmill_common::oauth2::{HmacSha256, InstanceEvent};
 
 #[cfg(feature = "prometheus")]
 lazy_static::lazy_static! {
@@ -27,9 +28,145 @@ lazy_static::lazy_static! {
 
 }
 
+const DEPLOY_WEBHOOK_ATTEMPTS: u32 = 3;
+const DEPLOY_WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// HMAC-SHA256 of the body with the deploy webhook secret, as `sha256=<hex>`
+const DEPLOY_WEBHOOK_SIGNATURE_HEADER: &str = "X-Windmill-Signature";
+
 pub enum WebhookPayload {
     WorkspaceEvent(String, WebhookMessage),
     InstanceEvent(InstanceEvent),
+    DeployEvent(DeployEvent),
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployKind {
+    Script,
+    Flow,
+    App,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployAction {
+    Deploy,
+    Archive,
+    Unarchive,
+    Delete,
+}
+
+/// Sent to the `deploy_webhook_url` of the workspace when a script, flow or app is deployed,
+/// archived or deleted
+#[derive(Serialize, Debug)]
+pub struct DeployEvent {
+    pub kind: DeployKind,
+    pub action: DeployAction,
+    pub workspace: String,
+    pub path: String,
+    /// hash of scripts, version id of flows and apps
+    pub version: Option<String>,
+    pub username: String,
+    pub email: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// sample event sent to verify the configuration of the webhook
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub test: bool,
+}
+
+impl DeployEvent {
+    pub fn new(
+        kind: DeployKind,
+        action: DeployAction,
+        workspace: &str,
+        path: &str,
+        version: Option<String>,
+        authed: &ApiAuthed,
+    ) -> Self {
+        Self {
+            kind,
+            action,
+            workspace: workspace.to_string(),
+            path: path.to_string(),
+            version,
+            username: authed.username.clone(),
+            email: authed.email.clone(),
+            timestamp: chrono::Utc::now(),
+            test: false,
+        }
+    }
+}
+
+fn sign_deploy_event(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub async fn send_deploy_event(
+    client: &reqwest::Client,
+    url: &str,
+    secret: Option<&str>,
+    event: &DeployEvent,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+        request = request.header(
+            DEPLOY_WEBHOOK_SIGNATURE_HEADER,
+            sign_deploy_event(secret, &body),
+        );
+    }
+    request
+        .body(body)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Delivery happens outside of the webhook loop so that retries do not delay the other events
+async fn deliver_deploy_event(client: reqwest::Client, db: DB, event: DeployEvent) {
+    let settings = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+        "SELECT deploy_webhook_url, deploy_webhook_secret FROM workspace_settings WHERE workspace_id = $1",
+    )
+    .bind(&event.workspace)
+    .fetch_optional(&db)
+    .await;
+    let (url, secret) = match settings {
+        Ok(Some((Some(url), secret))) => (url, secret),
+        Ok(_) => return,
+        Err(e) => {
+            tracing::error!(
+                "Could not get the deploy webhook of workspace {}: {e:#}",
+                event.workspace
+            );
+            return;
+        }
+    };
+
+    for attempt in 1..=DEPLOY_WEBHOOK_ATTEMPTS {
+        match send_deploy_event(&client, &url, secret.as_deref(), &event).await {
+            Ok(()) => return,
+            Err(e) if attempt < DEPLOY_WEBHOOK_ATTEMPTS => {
+                tracing::warn!(
+                    "Deploy webhook of workspace {} failed (attempt {attempt}), retrying: {e}",
+                    event.workspace
+                );
+                tokio::time::sleep(DEPLOY_WEBHOOK_RETRY_DELAY * attempt).await;
+            }
+            Err(e) => tracing::error!(
+                "Deploy webhook of workspace {} failed after {attempt} attempts, {:?} {} not notified: {e}",
+                event.workspace,
+                event.kind,
+                event.path
+            ),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -113,6 +250,9 @@ impl WebhookShared {
                                 timer.map(|x| x.stop_and_record());
                             }
                         },
+                        Some(WebhookPayload::DeployEvent(event)) => {
+                            tokio::spawn(deliver_deploy_event(client.clone(), db.clone(), event));
+                        },
                         Some(WebhookPayload::InstanceEvent(event)) => {
                             #[cfg(feature = "prometheus")]
                             if METRICS_ENABLED.load(std::sync::atomic::Ordering::Relaxed) { Some(WEBHOOK_REQUEST_COUNT.start_timer()) } else { None };
@@ -137,6 +277,10 @@ impl WebhookShared {
         ));
     }
 
+    pub fn send_deploy_event(&self, event: DeployEvent) {
+        let _ = self.channel.send(WebhookPayload::DeployEvent(event));
+    }
+
     pub fn send_instance_event(&self, event: InstanceEvent) {
         if INSTANCE_EVENTS_WEBHOOK.is_none() {
             return;
@@ -4,6 +4,7 @@ use axum::{
     routing::{delete, get, post},
     Extension, Json, Router,
 };
+use base64::Engine;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use http::StatusCode;
 use itertools::Itertools;
@@ -64,6 +65,9 @@ struct NewWebsocketTrigger {
     initial_messages: Option<Vec<Box<RawValue>>>,
     url_runnable_args: Option<Box<RawValue>>,
     can_return_message: bool,
+    #[serde(default)]
+    binary_as_base64: bool,
+    batch: Option<WebsocketBatch>,
 }
 
 #[derive(Deserialize)]
@@ -86,6 +90,14 @@ enum InitialMessage {
     RunnableResult { path: String, args: Box<RawValue>, is_flow: bool },
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WebsocketBatch {
+    /// Push a job as soon as this many messages have been buffered
+    max_messages: usize,
+    /// Push a job with whatever is buffered once the oldest message has waited this long
+    max_wait_ms: u64,
+}
+
 #[derive(FromRow, Serialize, Clone)]
 pub struct WebsocketTrigger {
     workspace_id: String,
@@ -105,6 +117,12 @@ pub struct WebsocketTrigger {
     initial_messages: Option<Vec<SqlxJson<Box<RawValue>>>>,
     url_runnable_args: Option<SqlxJson<Box<RawValue>>>,
     can_return_message: bool,
+    binary_as_base64: bool,
+    batch: Option<SqlxJson<WebsocketBatch>>,
+    messages_received: i64,
+    jobs_pushed: i64,
+    last_push_error: Option<String>,
+    last_push_error_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -117,6 +135,9 @@ struct EditWebsocketTrigger {
     initial_messages: Option<Vec<Box<RawValue>>>,
     url_runnable_args: Option<Box<RawValue>>,
     can_return_message: bool,
+    #[serde(default)]
+    binary_as_base64: bool,
+    batch: Option<WebsocketBatch>,
 }
 
 #[derive(Deserialize)]
@@ -186,6 +207,17 @@ async fn get_websocket_trigger(
     Ok(Json(trigger))
 }
 
+fn check_batch(batch: Option<&WebsocketBatch>) -> error::Result<()> {
+    if let Some(batch) = batch {
+        if batch.max_messages == 0 {
+            return Err(error::Error::BadRequest(
+                "batch.max_messages must be at least 1".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 async fn create_websocket_trigger(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -198,6 +230,8 @@ async fn create_websocket_trigger(
         ));
     }
 
+    check_batch(ct.batch.as_ref())?;
+
     let mut tx = user_db.begin(&authed).await?;
 
     let filters = ct.filters.into_iter().map(SqlxJson).collect_vec();
@@ -208,7 +242,7 @@ async fn create_websocket_trigger(
         .map(SqlxJson)
         .collect_vec();
     sqlx::query_as::<_, WebsocketTrigger>(
-      "INSERT INTO websocket_trigger (workspace_id, path, url, script_path, is_flow, enabled, filters, initial_messages, url_runnable_args, edited_by, can_return_message, email, edited_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, now()) RETURNING *",
+      "INSERT INTO websocket_trigger (workspace_id, path, url, script_path, is_flow, enabled, filters, initial_messages, url_runnable_args, edited_by, can_return_message, email, binary_as_base64, batch, edited_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, now()) RETURNING *",
     )
     .bind(&w_id)
     .bind(&ct.path)
@@ -222,6 +256,8 @@ async fn create_websocket_trigger(
     .bind(&authed.username)
     .bind(ct.can_return_message)
     .bind(&authed.email)
+    .bind(ct.binary_as_base64)
+    .bind(ct.batch.map(SqlxJson))
     .fetch_one(&mut *tx).await?;
 
     audit_log(
@@ -247,6 +283,8 @@ async fn update_websocket_trigger(
     Json(ct): Json<EditWebsocketTrigger>,
 ) -> error::Result<String> {
     let path = path.to_path();
+    check_batch(ct.batch.as_ref())?;
+
     let mut tx = user_db.begin(&authed).await?;
 
     let filters = ct.filters.into_iter().map(SqlxJson).collect_vec();
@@ -259,8 +297,8 @@ async fn update_websocket_trigger(
 
     // important to update server_id to NULL to stop current websocket listener
     sqlx::query!(
-        "UPDATE websocket_trigger SET url = $1, script_path = $2, path = $3, is_flow = $4, filters = $5, initial_messages = $6, url_runnable_args = $7, edited_by = $8, email = $9, can_return_message = $10, edited_at = now(), server_id = NULL, error = NULL
-            WHERE workspace_id = $11 AND path = $12",
+        "UPDATE websocket_trigger SET url = $1, script_path = $2, path = $3, is_flow = $4, filters = $5, initial_messages = $6, url_runnable_args = $7, edited_by = $8, email = $9, can_return_message = $10, binary_as_base64 = $11, batch = $12, edited_at = now(), server_id = NULL, error = NULL
+            WHERE workspace_id = $13 AND path = $14",
         ct.url,
         ct.script_path,
         ct.path,
@@ -271,6 +309,8 @@ async fn update_websocket_trigger(
         &authed.username,
         &authed.email,
         ct.can_return_message,
+        ct.binary_as_base64,
+        ct.batch.map(SqlxJson) as Option<SqlxJson<WebsocketBatch>>,
         w_id,
         path,
     )
@@ -886,19 +926,46 @@ impl WebsocketTrigger {
         db: &DB,
         args: PushArgsOwned,
         return_message_channels: Option<ReturnMessageChannels>,
+        messages_received: i64,
     ) -> () {
-        if let Err(err) = run_job(db, self, args, return_message_channels).await {
-            report_critical_error(
-                format!(
+        let error = match run_job(db, self, args, return_message_channels).await {
+            Ok(()) => None,
+            Err(err) => {
+                let error = format!(
                     "Failed to trigger job from WebSocket {}: {:?}",
                     self.url, err
-                ),
-                db.clone(),
-                Some(&self.workspace_id),
-                None,
-            )
-            .await;
+                );
+                report_critical_error(error.clone(), db.clone(), Some(&self.workspace_id), None)
+                    .await;
+                Some(error)
+            }
         };
+        self.record_metrics(db, messages_received, error).await;
+    }
+
+    async fn record_metrics(&self, db: &DB, messages_received: i64, error: Option<String>) -> () {
+        if let Err(err) = sqlx::query!(
+            "UPDATE websocket_trigger SET
+                messages_received = messages_received + $1,
+                jobs_pushed = jobs_pushed + CASE WHEN $2::TEXT IS NULL THEN 1 ELSE 0 END,
+                last_push_error = COALESCE($2, last_push_error),
+                last_push_error_at = CASE WHEN $2::TEXT IS NULL THEN last_push_error_at ELSE now() END
+            WHERE workspace_id = $3 AND path = $4",
+            messages_received,
+            error,
+            self.workspace_id,
+            self.path,
+        )
+        .execute(db)
+        .await
+        {
+            tracing::error!(
+                "Could not update metrics of WebSocket trigger {} ({}): {:?}",
+                self.path,
+                self.workspace_id,
+                err
+            );
+        }
     }
 
     async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
@@ -1084,6 +1151,94 @@ impl WebsocketEnum {
             WebsocketEnum::Capture(capture) => capture.disable_with_error(db, error).await,
         }
     }
+
+    async fn handle(
+        &self,
+        db: &DB,
+        args: PushArgsOwned,
+        return_message_channels: Option<ReturnMessageChannels>,
+        messages_received: i64,
+    ) -> () {
+        match self {
+            WebsocketEnum::Trigger(ws_trigger) => {
+                ws_trigger
+                    .handle(db, args, return_message_channels, messages_received)
+                    .await
+            }
+            WebsocketEnum::Capture(capture) => capture.handle(db, args).await,
+        }
+    }
+
+    async fn handle_batch(
+        &self,
+        db: &DB,
+        messages: Vec<HashMap<String, Box<RawValue>>>,
+        extra: Option<HashMap<String, Box<RawValue>>>,
+        return_message_channels: Option<ReturnMessageChannels>,
+        messages_received: i64,
+    ) -> () {
+        tracing::debug!("Pushing a batch of {} WebSocket messages", messages.len());
+        let args = HashMap::from([("messages".to_string(), to_raw_value(&messages))]);
+        self.handle(
+            db,
+            PushArgsOwned { args, extra },
+            return_message_channels,
+            messages_received,
+        )
+        .await
+    }
+}
+
+/// Turns a WebSocket frame into job args: text frames that pass the filters become `msg`,
+/// binary frames become a base64 `raw_string` when the trigger opted in. Other frames are dropped.
+fn message_to_args(
+    msg: Message,
+    filters: &[Filter],
+    binary_as_base64: bool,
+    url: &str,
+) -> Option<HashMap<String, Box<RawValue>>> {
+    match msg {
+        Message::Text(text) => {
+            tracing::debug!("Received text message from WebSocket {}: {}", url, text);
+            for filter in filters {
+                match filter {
+                    Filter::JsonFilter(JsonFilter { key, value }) => {
+                        let mut deserializer = serde_json::Deserializer::from_str(text.as_str());
+                        let filter_match = match is_value_superset(&mut deserializer, key, &value) {
+                            Ok(filter_match) => filter_match,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "Error deserializing filter for WebSocket {}: {:?}",
+                                    url,
+                                    err
+                                );
+                                false
+                            }
+                        };
+                        if !filter_match {
+                            return None;
+                        }
+                    }
+                }
+            }
+            Some(HashMap::from([("msg".to_string(), to_raw_value(&text))]))
+        }
+        Message::Binary(data) if binary_as_base64 => {
+            tracing::debug!(
+                "Received binary message from WebSocket {}: {} bytes",
+                url,
+                data.len()
+            );
+            Some(HashMap::from([(
+                "raw_string".to_string(),
+                to_raw_value(&base64::engine::general_purpose::STANDARD.encode(&data)),
+            )]))
+        }
+        a @ _ => {
+            tracing::debug!("Received non text-message from WebSocket {}: {:?}", url, a);
+            None
+        }
+    }
 }
 
 struct ReturnMessageChannels {
@@ -1220,59 +1375,60 @@ async fn listen_to_websocket(
                         _ => (None, None)
                     };
 
+                    let (binary_as_base64, batch) = match &ws {
+                        WebsocketEnum::Trigger(ws_trigger) => (
+                            ws_trigger.binary_as_base64,
+                            ws_trigger.batch.as_ref().map(|batch| batch.0.clone()),
+                        ),
+                        WebsocketEnum::Capture(_) => (false, None),
+                    };
+                    let extra = Some(HashMap::from([(
+                        "wm_trigger".to_string(),
+                        to_raw_value(&serde_json::json!({"kind": "websocket", "websocket": { "url": url }})),
+                    )]));
+
+                    // messages waiting to be pushed as a single job when batching is enabled
+                    let mut buffer: Vec<HashMap<String, Box<RawValue>>> = vec![];
+                    // messages received (filtered out ones included) since the last job was pushed
+                    let mut received: i64 = 0;
+                    let mut flush_deadline: Option<tokio::time::Instant> = None;
+
                     tokio::select! {
                         biased;
                         _ = killpill_rx.recv() => {},
                         _ = loop_ping(&db, &ws, None) => {},
                         _ = async {
                             loop {
-                                if let Some(msg) = reader.next().await {
+                                let msg = tokio::select! {
+                                    msg = reader.next() => msg,
+                                    _ = tokio::time::sleep_until(flush_deadline.unwrap_or_else(tokio::time::Instant::now)), if flush_deadline.is_some() => {
+                                        flush_deadline = None;
+                                        ws.handle_batch(&db, std::mem::take(&mut buffer), extra.clone(), return_message_channels.clone(), std::mem::take(&mut received)).await;
+                                        continue;
+                                    }
+                                };
+                                if let Some(msg) = msg {
                                     match msg {
                                         Ok(msg) => {
-                                            match msg {
-                                                tokio_tungstenite::tungstenite::Message::Text(text) => {
-                                                    tracing::debug!("Received text message from WebSocket {}: {}", url, text);
-                                                    let mut should_handle = true;
-                                                    for filter in &filters {
-                                                        match filter {
-                                                            Filter::JsonFilter(JsonFilter { key, value }) => {
-                                                                let mut deserializer = serde_json::Deserializer::from_str(text.as_str());
-                                                                should_handle = match is_value_superset(&mut deserializer, key, &value) {
-                                                                    Ok(filter_match) => {
-                                                                        filter_match
-                                                                    },
-                                                                    Err(err) => {
-                                                                        tracing::warn!("Error deserializing filter for WebSocket {}: {:?}", url, err);
-                                                                        false
-                                                                    }
-                                                                };
-                                                            }
-                                                        }
-                                                        if !should_handle {
-                                                            break;
-                                                        }
-                                                    }
-                                                    if should_handle {
-
-                                                        let args = HashMap::from([("msg".to_string(), to_raw_value(&text))]);
-                                                        let extra = Some(HashMap::from([(
-                                                            "wm_trigger".to_string(),
-                                                            to_raw_value(&serde_json::json!({"kind": "websocket", "websocket": { "url": url }})),
-                                                        )]));
-
-                                                        let args = PushArgsOwned { args, extra };
-                                                        match &ws {
-                                                            WebsocketEnum::Trigger(ws_trigger) => {
-                                                                ws_trigger.handle(&db, args, return_message_channels.clone()).await;
-                                                            },
-                                                            WebsocketEnum::Capture(capture) => {
-                                                                capture.handle(&db, args).await;
-                                                            },
-                                                        }
+                                            if matches!(msg, Message::Text(_) | Message::Binary(_)) {
+                                                received += 1;
+                                            }
+                                            let Some(args) = message_to_args(msg, &filters, binary_as_base64, &url) else {
+                                                continue;
+                                            };
+                                            match &batch {
+                                                Some(batch) => {
+                                                    buffer.push(args);
+                                                    if buffer.len() >= batch.max_messages {
+                                                        flush_deadline = None;
+                                                        ws.handle_batch(&db, std::mem::take(&mut buffer), extra.clone(), return_message_channels.clone(), std::mem::take(&mut received)).await;
+                                                    } else if flush_deadline.is_none() {
+                                                        flush_deadline = Some(tokio::time::Instant::now() + tokio::time::Duration::from_millis(batch.max_wait_ms));
                                                     }
                                                 },
-                                                a @ _ => {
-                                                    tracing::debug!("Received non text-message from WebSocket {}: {:?}", url, a);
+                                                None => {
+                                                    let args = PushArgsOwned { args, extra: extra.clone() };
+                                                    ws.handle(&db, args, return_message_channels.clone(), std::mem::take(&mut received)).await;
                                                 }
                                             }
                                         },
@@ -1288,6 +1444,10 @@ async fn listen_to_websocket(
                             }
                         } => {}
                     }
+                    // push whatever is still buffered so that batched messages are not lost on shutdown or disconnect
+                    if !buffer.is_empty() {
+                        ws.handle_batch(&db, buffer, extra, return_message_channels.clone(), received).await;
+                    }
                     // make sure to stop return message handler
                     if let Some(message_sender_handle) = message_sender_handle {
                         message_sender_handle.abort();
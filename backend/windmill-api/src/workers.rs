@@ -6,18 +6,25 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
 use axum::{
-    extract::{Extension, Query},
+    extract::{Extension, Path, Query},
     routing::get,
     Json, Router,
 };
 
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use tokio::sync::RwLock;
 use uuid::Uuid;
 use windmill_common::{
+    auth::is_super_admin_email,
     db::UserDB,
-    error::JsonResult,
+    error::{Error, JsonResult, Result},
     utils::{paginate, Pagination},
     worker::{ALL_TAGS, CUSTOM_TAGS_PER_WORKSPACE, DEFAULT_TAGS, DEFAULT_TAGS_PER_WORKSPACE},
     DB,
@@ -37,6 +44,41 @@ pub fn global_service() -> Router {
         .route("/get_default_tags", get(get_default_tags))
         .route("/queue_metrics", get(get_queue_metrics))
         .route("/queue_counts", get(get_queue_counts))
+        .route("/list_active_jobs", get(list_active_jobs))
+        .route("/group_activity_summary", get(get_group_activity_summary))
+        .route("/tags", get(list_worker_tags))
+}
+
+lazy_static::lazy_static! {
+    /// number of live workers pulling from each tag, refreshed by the monitor loop and unknown
+    /// until its first run
+    static ref LIVE_WORKER_TAGS: Arc<RwLock<Option<HashMap<String, i64>>>> = Arc::new(RwLock::new(None));
+}
+
+pub async fn refresh_live_worker_tags(db: &DB) -> Result<()> {
+    let tags = sqlx::query_as::<_, (String, i64)>(&format!(
+        "SELECT tag, COUNT(*)
+        FROM worker_ping, unnest(custom_tags) AS tag
+        WHERE ping_at > now() - interval '{ALIVE_WORKER_PING_INTERVAL}'
+        GROUP BY tag"
+    ))
+    .fetch_all(db)
+    .await?;
+    *LIVE_WORKER_TAGS.write().await = Some(tags.into_iter().collect());
+    Ok(())
+}
+
+/// Whether a live worker pulls from `tag`, `None` if the registry is not loaded yet
+pub async fn is_tag_served(tag: &str) -> Option<bool> {
+    LIVE_WORKER_TAGS
+        .read()
+        .await
+        .as_ref()
+        .map(|tags| tags.contains_key(tag))
+}
+
+pub fn workspaced_service() -> Router {
+    Router::new().route("/", get(list_workers_for_workspace))
 }
 
 #[derive(FromRow, Serialize, Deserialize)]
@@ -68,6 +110,10 @@ struct WorkerPing {
     memory_usage: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     wm_memory_usage: Option<i64>,
+    /// the worker stopped pulling jobs and exits once idle
+    draining: bool,
+    /// the drain flag is set on the config of the worker group
+    group_draining: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -95,12 +141,13 @@ async fn list_worker_pings(
 
     let rows = sqlx::query_as!(
         WorkerPing,
-        "SELECT worker, worker_instance,  EXTRACT(EPOCH FROM (now() - ping_at))::integer as last_ping, started_at, ip, jobs_executed,
+        r#"SELECT worker, worker_instance,  EXTRACT(EPOCH FROM (now() - ping_at))::integer as last_ping, started_at, ip, jobs_executed,
         CASE WHEN $4 IS TRUE THEN current_job_id ELSE NULL END as last_job_id, CASE WHEN $4 IS TRUE THEN current_job_workspace_id ELSE NULL END as last_job_workspace_id, 
-        custom_tags, worker_group, wm_version, occupancy_rate, occupancy_rate_15s, occupancy_rate_5m, occupancy_rate_30m, memory, vcpus, memory_usage, wm_memory_usage
+        custom_tags, worker_group, wm_version, occupancy_rate, occupancy_rate_15s, occupancy_rate_5m, occupancy_rate_30m, memory, vcpus, memory_usage, wm_memory_usage,
+        draining, coalesce((SELECT (config->>'drain')::boolean FROM config WHERE name = 'worker__' || worker_group), false) as "group_draining!"
         FROM worker_ping
         WHERE ($1::integer IS NULL AND ping_at > now() - interval '5 minute') OR (ping_at > now() - ($1 || ' seconds')::interval)
-        ORDER BY ping_at desc LIMIT $2 OFFSET $3",
+        ORDER BY ping_at desc LIMIT $2 OFFSET $3"#,
         query.ping_since,
         per_page as i64,
         offset as i64,
@@ -112,6 +159,50 @@ async fn list_worker_pings(
     Ok(Json(rows))
 }
 
+#[derive(FromRow, Serialize)]
+struct WorkspaceWorker {
+    worker: String,
+    worker_group: String,
+    last_ping: Option<i32>,
+    custom_tags: Option<Vec<String>>,
+    /// jobs of the workspace the worker is currently running
+    current_job_count: i64,
+}
+
+/// Workers that are running, or last ran, a job of the workspace
+async fn list_workers_for_workspace(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<ListWorkerQuery>,
+) -> JsonResult<Vec<WorkspaceWorker>> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let (per_page, offset) = paginate(Pagination { page: query.page, per_page: query.per_page });
+
+    let rows = sqlx::query_as::<_, WorkspaceWorker>(
+        "SELECT wp.worker, wp.worker_group, EXTRACT(EPOCH FROM (now() - wp.ping_at))::integer AS last_ping,
+            wp.custom_tags, count(q.id) AS current_job_count
+        FROM worker_ping wp
+        LEFT JOIN queue q ON q.id = wp.current_job_id AND q.workspace_id = $1 AND q.running = true
+        WHERE (($2::integer IS NULL AND wp.ping_at > now() - interval '5 minute')
+                OR (wp.ping_at > now() - ($2 || ' seconds')::interval))
+            AND (wp.current_job_workspace_id = $1 OR q.id IS NOT NULL OR EXISTS (
+                SELECT 1 FROM completed_job cj WHERE cj.id = wp.current_job_id AND cj.workspace_id = $1
+            ))
+        GROUP BY wp.worker
+        ORDER BY wp.ping_at DESC LIMIT $3 OFFSET $4",
+    )
+    .bind(&w_id)
+    .bind(query.ping_since)
+    .bind(per_page as i64)
+    .bind(offset as i64)
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Json(rows))
+}
+
 #[derive(Serialize, Deserialize)]
 struct TagQuery {
     tag: String,
@@ -187,6 +278,34 @@ async fn get_default_tags() -> JsonResult<Vec<String>> {
     Ok(Json(DEFAULT_TAGS.clone()))
 }
 
+#[derive(Serialize)]
+struct WorkerTag {
+    tag: String,
+    live_workers: i64,
+}
+
+/// Known tags, default, custom or pulled by a live worker, with the number of live workers
+/// pulling from them
+async fn list_worker_tags(Extension(db): Extension<DB>) -> JsonResult<Vec<WorkerTag>> {
+    if LIVE_WORKER_TAGS.read().await.is_none() {
+        refresh_live_worker_tags(&db).await?;
+    }
+    let live_tags = LIVE_WORKER_TAGS.read().await.clone().unwrap_or_default();
+
+    let mut tags = DEFAULT_TAGS
+        .iter()
+        .chain(ALL_TAGS.read().await.iter())
+        .map(|tag| (tag.clone(), 0))
+        .collect::<BTreeMap<String, i64>>();
+    tags.extend(live_tags);
+
+    Ok(Json(
+        tags.into_iter()
+            .map(|(tag, live_workers)| WorkerTag { tag, live_workers })
+            .collect(),
+    ))
+}
+
 #[derive(Serialize)]
 struct QueueMetric {
     id: String,
@@ -226,3 +345,116 @@ async fn get_queue_counts(
     let queue_counts = windmill_common::queue::get_queue_counts(&db).await;
     Ok(Json(queue_counts))
 }
+
+/// Workers that pinged within this interval are considered alive
+const ALIVE_WORKER_PING_INTERVAL: &str = "5 minute";
+
+#[derive(Deserialize)]
+struct WorkerGroupQuery {
+    worker_group: Option<String>,
+}
+
+#[derive(FromRow, Serialize)]
+struct WorkerActiveJob {
+    worker: String,
+    worker_group: String,
+    ip: String,
+    /// seconds since the last ping of the worker
+    last_ping: Option<i32>,
+    job_id: Option<Uuid>,
+    workspace_id: Option<String>,
+    script_path: Option<String>,
+    language: Option<String>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    mem_peak: Option<i32>,
+}
+
+#[derive(FromRow, Serialize)]
+struct WorkerGroupActivity {
+    worker_group: String,
+    busy: i64,
+    idle: i64,
+}
+
+/// Superadmins see every worker. Admins of a workspace only see the workers currently running a
+/// job of one of the workspaces they administer. Returns whether the caller is a superadmin.
+async fn require_super_admin_or_any_workspace_admin(db: &DB, email: &str) -> Result<bool> {
+    if is_super_admin_email(db, email).await? {
+        return Ok(true);
+    }
+    let is_workspace_admin = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM usr WHERE email = $1 AND is_admin AND NOT disabled)",
+    )
+    .bind(email)
+    .fetch_one(db)
+    .await?;
+    if !is_workspace_admin {
+        return Err(Error::NotAuthorized(
+            "This endpoint requires the caller to be a super admin or a workspace admin".to_owned(),
+        ));
+    }
+    Ok(false)
+}
+
+async fn list_active_jobs(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Query(query): Query<WorkerGroupQuery>,
+) -> JsonResult<Vec<WorkerActiveJob>> {
+    let is_super_admin = require_super_admin_or_any_workspace_admin(&db, &authed.email).await?;
+
+    let rows = sqlx::query_as::<_, WorkerActiveJob>(&format!(
+        "SELECT wp.worker, wp.worker_group, wp.ip,
+            EXTRACT(EPOCH FROM (now() - wp.ping_at))::integer AS last_ping,
+            q.id AS job_id, q.workspace_id, q.script_path, q.language::text AS language,
+            q.started_at, q.mem_peak
+        FROM worker_ping wp
+        LEFT JOIN queue q ON q.id = wp.current_job_id
+            AND q.workspace_id = wp.current_job_workspace_id AND q.running = true
+        WHERE wp.ping_at > now() - interval '{ALIVE_WORKER_PING_INTERVAL}'
+            AND ($1::text IS NULL OR wp.worker_group = $1)
+            AND ($2 OR q.workspace_id IN (
+                SELECT workspace_id FROM usr WHERE email = $3 AND is_admin AND NOT disabled
+            ))
+        ORDER BY wp.worker_group, wp.worker"
+    ))
+    .bind(query.worker_group)
+    .bind(is_super_admin)
+    .bind(&authed.email)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+async fn get_group_activity_summary(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Query(query): Query<WorkerGroupQuery>,
+) -> JsonResult<Vec<WorkerGroupActivity>> {
+    let is_super_admin = require_super_admin_or_any_workspace_admin(&db, &authed.email).await?;
+
+    // for non super admins, the workers running a job of a workspace they do not administer are
+    // counted neither as busy nor as idle
+    let rows = sqlx::query_as::<_, WorkerGroupActivity>(&format!(
+        "SELECT wp.worker_group,
+            COUNT(q.id) FILTER (WHERE $2 OR q.workspace_id IN (
+                SELECT workspace_id FROM usr WHERE email = $3 AND is_admin AND NOT disabled
+            )) AS busy,
+            COUNT(*) - COUNT(q.id) AS idle
+        FROM worker_ping wp
+        LEFT JOIN queue q ON q.id = wp.current_job_id
+            AND q.workspace_id = wp.current_job_workspace_id AND q.running = true
+        WHERE wp.ping_at > now() - interval '{ALIVE_WORKER_PING_INTERVAL}'
+            AND ($1::text IS NULL OR wp.worker_group = $1)
+        GROUP BY wp.worker_group
+        ORDER BY wp.worker_group"
+    ))
+    .bind(query.worker_group)
+    .bind(is_super_admin)
+    .bind(&authed.email)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(rows))
+}
@@ -6,7 +6,7 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use crate::ai::{AiResource, AI_KEY_CACHE};
 use crate::db::ApiAuthed;
@@ -17,11 +17,14 @@ use crate::{
     db::DB,
     users::{WorkspaceInvite, VALID_USERNAME},
     utils::require_super_admin,
-    webhook_util::WebhookShared,
+    webhook_util::{send_deploy_event, DeployAction, DeployEvent, DeployKind, WebhookShared},
+    HTTP_CLIENT,
 };
 
 use axum::{
-    extract::{Extension, Path, Query},
+    extract::{Extension, Path, Query, RawPathParams, Request},
+    middleware::Next,
+    response::Response,
     routing::{delete, get, post},
     Json, Router,
 };
@@ -41,6 +44,9 @@ use windmill_common::worker::to_raw_value;
 use windmill_common::workspaces::WorkspaceDeploymentUISettings;
 #[cfg(feature = "enterprise")]
 use windmill_common::workspaces::WorkspaceGitSyncSettings;
+use windmill_common::workspaces::{
+    WorkspaceJobLimits, WorkspaceOidcSettings, OIDC_MAX_TOKEN_TTL_SECS,
+};
 use windmill_common::{
     error::{Error, JsonResult, Result},
     global_settings::AUTOMATE_USERNAME_CREATION_SETTING,
@@ -52,14 +58,20 @@ use windmill_git_sync::handle_deployment_metadata;
 #[cfg(feature = "enterprise")]
 use windmill_common::utils::require_admin_or_devops;
 
+use http::{HeaderMap, HeaderName, HeaderValue};
 use hyper::StatusCode;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Postgres, Transaction};
+use tokio::sync::RwLock;
 use windmill_common::oauth2::InstanceEvent;
 use windmill_common::utils::not_found_if_none;
 
 lazy_static::lazy_static! {
     static ref WORKSPACE_KEY_REGEXP: Regex = Regex::new("^[a-zA-Z0-9]{64}$").unwrap();
+
+    /// Headers added to the responses of the routes of a workspace, keyed by workspace id
+    pub static ref CUSTOM_RESPONSE_HEADERS: Arc<RwLock<HashMap<String, HeaderMap>>> =
+        Arc::new(RwLock::new(HashMap::new()));
 }
 
 pub fn workspaced_service() -> Router {
@@ -67,6 +79,7 @@ pub fn workspaced_service() -> Router {
         .route("/list_pending_invites", get(list_pending_invites))
         .route("/update", post(edit_workspace))
         .route("/archive", post(archive_workspace))
+        .route("/purge", delete(purge_workspace))
         .route("/invite_user", post(invite_user))
         .route("/add_user", post(add_user))
         .route("/delete_invite", post(delete_invite))
@@ -78,9 +91,15 @@ pub fn workspaced_service() -> Router {
             post(run_slack_message_test_job),
         )
         .route("/edit_webhook", post(edit_webhook))
+        .route("/edit_deploy_webhook", post(edit_deploy_webhook))
+        .route("/test_deploy_webhook", post(test_deploy_webhook))
         .route("/edit_auto_invite", post(edit_auto_invite))
         .route("/edit_deploy_to", post(edit_deploy_to))
         .route("/tarball", get(crate::workspaces_export::tarball_workspace))
+        .route(
+            "/trigger_audit_export",
+            post(crate::workspaces_export::trigger_audit_export),
+        )
         .route("/is_premium", get(is_premium))
         .route("/edit_copilot_config", post(edit_copilot_config))
         .route("/get_copilot_info", get(get_copilot_info))
@@ -106,10 +125,27 @@ pub fn workspaced_service() -> Router {
         .route("/get_workspace_name", get(get_workspace_name))
         .route("/change_workspace_name", post(change_workspace_name))
         .route("/change_workspace_color", post(change_workspace_color))
+        .route("/edit_schedule_jitter", post(edit_schedule_jitter))
+        .route("/edit_strict_tags", post(edit_strict_tags))
+        .route("/edit_ai_daily_budget", post(edit_ai_daily_budget))
+        .route(
+            "/edit_reuse_lock_across_paths",
+            post(edit_reuse_lock_across_paths),
+        )
+        .route("/edit_job_limits", post(edit_job_limits))
+        .route(
+            "/edit_custom_response_headers",
+            post(edit_custom_response_headers),
+        )
+        .route("/edit_oidc_config", post(edit_oidc_config))
         .route(
             "/change_workspace_id",
             post(crate::workspaces_extra::change_workspace_id),
         )
+        .route(
+            "/clone_to/:target_workspace_id",
+            post(crate::workspaces_extra::clone_workspace),
+        )
         .route("/usage", get(get_usage))
         .route("/used_triggers", get(get_used_triggers))
         .route("/critical_alerts", get(get_critical_alerts))
@@ -135,6 +171,7 @@ pub fn workspaced_service() -> Router {
 pub fn global_service() -> Router {
     Router::new()
         .route("/list_as_superadmin", get(list_workspaces_as_super_admin))
+        .route("/search", get(search_workspaces))
         .route("/list", get(list_workspaces))
         .route("/users", get(user_workspaces))
         .route("/create", post(create_workspace))
@@ -190,6 +227,18 @@ pub struct WorkspaceSettings {
     pub mute_critical_alerts: Option<bool>,
     pub color: Option<String>,
     pub operator_settings: Option<serde_json::Value>,
+    pub schedule_jitter_enabled: bool,
+    pub default_timeout_secs_max: Option<i32>,
+    pub default_cache_ttl: Option<i32>,
+    pub max_mem_limit_mb: Option<i32>,
+    pub oidc: Option<serde_json::Value>, // effectively: WorkspaceOidcSettings
+    pub reuse_lock_across_paths: bool,
+    pub deploy_webhook_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub deploy_webhook_secret: Option<String>,
+    pub custom_response_headers: Option<serde_json::Value>, // effectively: HashMap<String, String>
+    pub strict_tags: bool,
+    pub ai_daily_budget_usd: Option<f64>,
 }
 
 #[derive(FromRow, Serialize, Debug)]
@@ -243,6 +292,18 @@ struct EditWebhook {
     webhook: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct EditDeployWebhook {
+    deploy_webhook_url: Option<String>,
+    /// the current secret is kept when not set, an empty string removes it
+    deploy_webhook_secret: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EditCustomResponseHeaders {
+    custom_response_headers: Option<HashMap<String, String>>,
+}
+
 #[derive(Deserialize)]
 struct EditCopilotConfig {
     ai_resource: Option<serde_json::Value>,
@@ -648,6 +709,254 @@ async fn edit_webhook(
     Ok(format!("Edit webhook for workspace {}", &w_id))
 }
 
+async fn edit_deploy_webhook(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(ew): Json<EditDeployWebhook>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    if let Some(url) = &ew.deploy_webhook_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(Error::BadRequest(format!(
+                "deploy webhook url must start with http:// or https:// (got {url})"
+            )));
+        }
+    }
+
+    let mut tx = db.begin().await?;
+
+    // removing the url also removes the secret
+    sqlx::query(
+        "UPDATE workspace_settings SET deploy_webhook_url = $1,
+            deploy_webhook_secret = CASE
+                WHEN $1::text IS NULL THEN NULL
+                WHEN $2::text IS NULL THEN deploy_webhook_secret
+                ELSE NULLIF($2, '')
+            END
+        WHERE workspace_id = $3",
+    )
+    .bind(&ew.deploy_webhook_url)
+    .bind(&ew.deploy_webhook_secret)
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let url = format!("{:?}", ew.deploy_webhook_url);
+    let secret = match ew.deploy_webhook_secret.as_deref() {
+        None => "unchanged",
+        Some("") => "removed",
+        Some(_) => "updated",
+    };
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_deploy_webhook",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("deploy_webhook_url", url.as_str()), ("secret", secret)].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Edit deploy webhook for workspace {}", &w_id))
+}
+
+/// Headers that cannot be added to the responses: the hop-by-hop headers, the ones describing the
+/// body that only the handler knows, and cookies
+const FORBIDDEN_CUSTOM_RESPONSE_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-type",
+    "content-length",
+    "content-encoding",
+    "content-range",
+    "set-cookie",
+];
+
+fn to_header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_str(name)
+            .map_err(|e| Error::BadRequest(format!("invalid header name {name}: {e}")))?;
+        if FORBIDDEN_CUSTOM_RESPONSE_HEADERS.contains(&name.as_str()) {
+            return Err(Error::BadRequest(format!(
+                "header {name} cannot be set as a custom response header"
+            )));
+        }
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| Error::BadRequest(format!("invalid value for header {name}: {e}")))?;
+        header_map.insert(name, value);
+    }
+    Ok(header_map)
+}
+
+/// Reloads the custom response headers of a workspace, or of all of them. Called on the
+/// notification sent by the trigger on `workspace_settings` when they are edited.
+pub async fn reload_custom_response_headers(db: &DB, w_id: Option<&str>) -> Result<()> {
+    let rows = sqlx::query_as::<_, (String, sqlx::types::Json<HashMap<String, String>>)>(
+        "SELECT workspace_id, custom_response_headers FROM workspace_settings
+        WHERE ($1::text IS NULL OR workspace_id = $1) AND custom_response_headers IS NOT NULL",
+    )
+    .bind(w_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut headers = CUSTOM_RESPONSE_HEADERS.write().await;
+    match w_id {
+        Some(w_id) => {
+            headers.remove(w_id);
+        }
+        None => headers.clear(),
+    }
+    for (w_id, sqlx::types::Json(workspace_headers)) in rows {
+        match to_header_map(&workspace_headers) {
+            Ok(header_map) => {
+                headers.insert(w_id, header_map);
+            }
+            Err(e) => {
+                tracing::error!("Ignoring custom response headers of workspace {w_id}: {e:#}")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Middleware of the `/w/:workspace_id` routes setting the custom response headers of the
+/// workspace, unless the handler already set them
+pub async fn add_custom_response_headers(
+    params: RawPathParams,
+    req: Request,
+    next: Next,
+) -> Response {
+    let w_id = params
+        .iter()
+        .find(|(key, _)| *key == "workspace_id")
+        .map(|(_, value)| value.to_string());
+    let mut response = next.run(req).await;
+    if let Some(w_id) = w_id {
+        if let Some(headers) = CUSTOM_RESPONSE_HEADERS.read().await.get(&w_id) {
+            for (name, value) in headers {
+                response
+                    .headers_mut()
+                    .entry(name)
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+    response
+}
+
+async fn edit_custom_response_headers(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(eh): Json<EditCustomResponseHeaders>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let custom_response_headers = eh
+        .custom_response_headers
+        .filter(|headers| !headers.is_empty());
+    let header_map = custom_response_headers
+        .as_ref()
+        .map(to_header_map)
+        .transpose()?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE workspace_settings SET custom_response_headers = $1 WHERE workspace_id = $2",
+    )
+    .bind(custom_response_headers.as_ref().map(sqlx::types::Json))
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let names = header_map
+        .as_ref()
+        .map(|headers| {
+            headers
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_custom_response_headers",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("headers", names.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    // the other servers reload them on the notification of the trigger on workspace_settings
+    let mut custom_headers = CUSTOM_RESPONSE_HEADERS.write().await;
+    match header_map {
+        Some(header_map) => custom_headers.insert(w_id.clone(), header_map),
+        None => custom_headers.remove(&w_id),
+    };
+
+    Ok(format!(
+        "Edit custom response headers for workspace {}",
+        &w_id
+    ))
+}
+
+/// Sends a sample deploy event to the deploy webhook and fails if it is not delivered
+async fn test_deploy_webhook(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let settings = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+        "SELECT deploy_webhook_url, deploy_webhook_secret FROM workspace_settings WHERE workspace_id = $1",
+    )
+    .bind(&w_id)
+    .fetch_optional(&db)
+    .await?;
+    let Some((Some(url), secret)) = settings else {
+        return Err(Error::BadRequest(format!(
+            "No deploy webhook configured for workspace {w_id}"
+        )));
+    };
+
+    let event = DeployEvent {
+        test: true,
+        ..DeployEvent::new(
+            DeployKind::Script,
+            DeployAction::Deploy,
+            &w_id,
+            &format!("u/{}/deploy_webhook_test", authed.username),
+            None,
+            &authed,
+        )
+    };
+    send_deploy_event(&HTTP_CLIENT, &url, secret.as_deref(), &event)
+        .await
+        .map_err(|e| {
+            Error::BadRequest(format!("Could not deliver the test event to {url}: {e}"))
+        })?;
+
+    Ok(format!("Test event delivered to {url}"))
+}
+
 async fn edit_copilot_config(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -1362,6 +1671,54 @@ async fn list_workspaces_as_super_admin(
     Ok(Json(workspaces))
 }
 
+#[derive(Deserialize)]
+struct SearchWorkspacesQuery {
+    q: String,
+}
+
+#[derive(FromRow, Serialize)]
+struct WorkspaceSummary {
+    id: String,
+    name: String,
+    owner: String,
+    user_count: i64,
+    /// only known for workspaces created after creation dates started being recorded
+    created_at: Option<chrono::DateTime<Utc>>,
+}
+
+async fn search_workspaces(
+    Extension(db): Extension<DB>,
+    ApiAuthed { email, .. }: ApiAuthed,
+    Query(SearchWorkspacesQuery { q }): Query<SearchWorkspacesQuery>,
+    Query(pagination): Query<Pagination>,
+) -> JsonResult<Vec<WorkspaceSummary>> {
+    require_super_admin(&db, &email).await?;
+    let (per_page, offset) = paginate(pagination);
+
+    // the query is matched literally, LIKE wildcards it contains are escaped
+    let pattern = format!(
+        "%{}%",
+        q.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let workspaces = sqlx::query_as::<_, WorkspaceSummary>(
+        "SELECT workspace.id, workspace.name, workspace.owner, workspace.created_at,
+            (SELECT COUNT(*) FROM usr WHERE usr.workspace_id = workspace.id) AS user_count
+        FROM workspace
+        WHERE workspace.id ILIKE $1 OR workspace.name ILIKE $1 OR workspace.owner ILIKE $1
+        ORDER BY workspace.id
+        LIMIT $2 OFFSET $3",
+    )
+    .bind(pattern)
+    .bind(per_page as i64)
+    .bind(offset as i64)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(workspaces))
+}
+
 async fn user_workspaces(
     Extension(db): Extension<DB>,
     ApiAuthed { email, .. }: ApiAuthed,
@@ -1696,6 +2053,94 @@ async fn unarchive_workspace(
     Ok(format!("Unarchived workspace {}", &w_id))
 }
 
+const PURGE_BATCH_SIZE: i64 = 1000;
+
+/// Tables purged in batches before the rest of the workspace is deleted in a single transaction,
+/// in an order that respects their foreign keys
+const PURGED_TABLES: [&str; 16] = [
+    "queue",
+    "completed_job",
+    "job",
+    "job_logs",
+    "job_chain",
+    "job_upload",
+    "script_job_retry",
+    "audit",
+    "schedule",
+    "script",
+    "flow",
+    "variable",
+    "resource",
+    "usr_to_group",
+    "group_",
+    "usr",
+];
+
+#[derive(Serialize)]
+struct PurgeReport {
+    deleted_counts: HashMap<String, usize>,
+}
+
+/// Unlike `delete`, the large tables are emptied in batches of short transactions so that the
+/// purge of a big workspace does not hold locks for long. A purge that failed midway can be
+/// resumed by calling it again.
+async fn purge_workspace(
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    authed: ApiAuthed,
+) -> JsonResult<PurgeReport> {
+    if w_id == "starter" || w_id == "admins" {
+        return Err(Error::BadRequest(format!(
+            "{w_id} workspace cannot be purged"
+        )));
+    }
+    require_super_admin(&db, &authed.email).await?;
+
+    let mut deleted_counts = HashMap::new();
+    for table in PURGED_TABLES {
+        let mut deleted = 0;
+        loop {
+            let batch = sqlx::query(&format!(
+                "DELETE FROM {table} WHERE ctid = ANY(ARRAY(
+                    SELECT ctid FROM {table} WHERE workspace_id = $1 LIMIT $2
+                ))"
+            ))
+            .bind(&w_id)
+            .bind(PURGE_BATCH_SIZE)
+            .execute(&db)
+            .await?
+            .rows_affected();
+            deleted += batch as usize;
+            if batch < PURGE_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+        tracing::info!("Purged {deleted} rows of {table} from workspace {w_id}");
+        deleted_counts.insert(table.to_string(), deleted);
+    }
+
+    let mut tx = db.begin().await?;
+    let deleted = crate::workspaces_extra::delete_workspace_data(&mut tx, &w_id).await?;
+    deleted_counts.insert("workspace".to_string(), deleted as usize);
+
+    // the workspace audit logs are gone, the purge itself is recorded at the instance level
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.purge",
+        ActionKind::Delete,
+        "global",
+        Some(&w_id),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    crate::job_archive::delete_workspace_archives(&w_id).await;
+
+    Ok(Json(PurgeReport { deleted_counts }))
+}
+
 async fn invite_user(
     ApiAuthed { username, is_admin, .. }: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -1707,11 +2152,31 @@ async fn invite_user(
 
     nu.email = nu.email.to_lowercase();
 
+    if !invite_user_internal(&db, &webhook, &w_id, &nu).await? {
+        return Err(Error::BadRequest(format!(
+            "user with email {} already exists in workspace {}",
+            nu.email, w_id
+        )));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        format!("user with email {} invited", nu.email),
+    ))
+}
+
+/// Invites a lowercased email to the workspace, returns false if the user is already a member
+pub(crate) async fn invite_user_internal(
+    db: &DB,
+    webhook: &WebhookShared,
+    w_id: &str,
+    nu: &NewWorkspaceInvite,
+) -> Result<bool> {
     let mut tx = db.begin().await?;
 
     let already_in_workspace = sqlx::query_scalar!(
         "SELECT EXISTS (SELECT 1 FROM usr WHERE workspace_id = $1 AND email = $2)",
-        &w_id,
+        w_id,
         nu.email
     )
     .fetch_one(&mut *tx)
@@ -1719,10 +2184,7 @@ async fn invite_user(
     .unwrap_or(false);
 
     if already_in_workspace {
-        return Err(Error::BadRequest(format!(
-            "user with email {} already exists in workspace {}",
-            nu.email, w_id
-        )));
+        return Ok(false);
     }
 
     sqlx::query!(
@@ -1730,7 +2192,7 @@ async fn invite_user(
             (workspace_id, email, is_admin, operator)
             VALUES ($1, $2, $3, $4) ON CONFLICT (workspace_id, email)
             DO UPDATE SET is_admin = $3, operator = $4",
-        &w_id,
+        w_id,
         nu.email,
         nu.is_admin,
         nu.operator
@@ -1753,13 +2215,10 @@ If you do not have an account on {}, login with SSO or ask an admin to create an
 
     webhook.send_instance_event(InstanceEvent::UserInvitedWorkspace {
         email: nu.email.clone(),
-        workspace: w_id,
+        workspace: w_id.to_string(),
     });
 
-    Ok((
-        StatusCode::CREATED,
-        format!("user with email {} invited", nu.email),
-    ))
+    Ok(true)
 }
 
 async fn add_user(
@@ -2033,6 +2492,313 @@ async fn change_workspace_color(
     ))
 }
 
+#[derive(Deserialize)]
+struct EditScheduleJitter {
+    schedule_jitter_enabled: bool,
+}
+
+async fn edit_schedule_jitter(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(ej): Json<EditScheduleJitter>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE workspace_settings SET schedule_jitter_enabled = $1 WHERE workspace_id = $2",
+    )
+    .bind(ej.schedule_jitter_enabled)
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let enabled = ej.schedule_jitter_enabled.to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_schedule_jitter",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("schedule_jitter_enabled", enabled.as_str())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!(
+        "{} schedule jitter for workspace {}",
+        if ej.schedule_jitter_enabled {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        &w_id
+    ))
+}
+
+#[derive(Deserialize)]
+struct EditStrictTags {
+    strict_tags: bool,
+}
+
+async fn edit_strict_tags(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(es): Json<EditStrictTags>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE workspace_settings SET strict_tags = $1 WHERE workspace_id = $2")
+        .bind(es.strict_tags)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let enabled = es.strict_tags.to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_strict_tags",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("strict_tags", enabled.as_str())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!(
+        "{} strict tags for workspace {}",
+        if es.strict_tags {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        &w_id
+    ))
+}
+
+#[derive(Deserialize)]
+struct EditAiDailyBudget {
+    ai_daily_budget_usd: Option<f64>,
+}
+
+async fn edit_ai_daily_budget(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(eb): Json<EditAiDailyBudget>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    if eb.ai_daily_budget_usd.is_some_and(|budget| budget < 0.0) {
+        return Err(Error::BadRequest(
+            "ai daily budget cannot be negative".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE workspace_settings SET ai_daily_budget_usd = $1 WHERE workspace_id = $2")
+        .bind(eb.ai_daily_budget_usd)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let budget = eb
+        .ai_daily_budget_usd
+        .map(|budget| budget.to_string())
+        .unwrap_or_else(|| "none".to_string());
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_ai_daily_budget",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("ai_daily_budget_usd", budget.as_str())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!(
+        "updated ai daily budget of workspace {w_id} to {budget}"
+    ))
+}
+
+#[derive(Deserialize)]
+struct EditReuseLockAcrossPaths {
+    reuse_lock_across_paths: bool,
+}
+
+async fn edit_reuse_lock_across_paths(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(er): Json<EditReuseLockAcrossPaths>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE workspace_settings SET reuse_lock_across_paths = $1 WHERE workspace_id = $2",
+    )
+    .bind(er.reuse_lock_across_paths)
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let enabled = er.reuse_lock_across_paths.to_string();
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_reuse_lock_across_paths",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some([("reuse_lock_across_paths", enabled.as_str())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!(
+        "{} reusing locks across paths for workspace {}",
+        if er.reuse_lock_across_paths {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        &w_id
+    ))
+}
+
+async fn edit_job_limits(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(limits): Json<WorkspaceJobLimits>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    if limits.default_timeout_secs_max.is_some_and(|t| t <= 0)
+        || limits.default_cache_ttl.is_some_and(|t| t <= 0)
+        || limits.max_mem_limit_mb.is_some_and(|m| m <= 0)
+    {
+        return Err(Error::BadRequest(
+            "default_timeout_secs_max, default_cache_ttl and max_mem_limit_mb must be positive"
+                .to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+
+    // the workspace_settings trigger notifies servers and workers to reload their cached limits
+    sqlx::query(
+        "UPDATE workspace_settings SET default_timeout_secs_max = $1, default_cache_ttl = $2, \
+        max_mem_limit_mb = $3 WHERE workspace_id = $4",
+    )
+    .bind(limits.default_timeout_secs_max)
+    .bind(limits.default_cache_ttl)
+    .bind(limits.max_mem_limit_mb)
+    .bind(&w_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let timeout_max = format!("{:?}", limits.default_timeout_secs_max);
+    let cache_ttl = format!("{:?}", limits.default_cache_ttl);
+    let mem_limit_max = format!("{:?}", limits.max_mem_limit_mb);
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_job_limits",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some(
+            [
+                ("default_timeout_secs_max", timeout_max.as_str()),
+                ("default_cache_ttl", cache_ttl.as_str()),
+                ("max_mem_limit_mb", mem_limit_max.as_str()),
+            ]
+            .into(),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!("Edit job limits for workspace {}", &w_id))
+}
+
+async fn edit_oidc_config(
+    authed: ApiAuthed,
+    Path(w_id): Path<String>,
+    Extension(db): Extension<DB>,
+    Json(settings): Json<WorkspaceOidcSettings>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    if settings
+        .token_ttl_secs
+        .is_some_and(|t| t == 0 || t > OIDC_MAX_TOKEN_TTL_SECS)
+    {
+        return Err(Error::BadRequest(format!(
+            "token_ttl_secs must be between 1 and {OIDC_MAX_TOKEN_TTL_SECS}"
+        )));
+    }
+    if settings
+        .allowed_audiences
+        .iter()
+        .any(|a| a.trim().is_empty())
+    {
+        return Err(Error::BadRequest(
+            "allowed audiences cannot be empty".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE workspace_settings SET oidc = $1 WHERE workspace_id = $2")
+        .bind(serde_json::to_value(&settings)?)
+        .bind(&w_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let audiences = settings.allowed_audiences.join(",");
+    let ttl = format!("{:?}", settings.token_ttl_secs);
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_oidc_config",
+        ActionKind::Update,
+        &w_id,
+        None,
+        Some(
+            [
+                ("allowed_audiences", audiences.as_str()),
+                ("token_ttl_secs", ttl.as_str()),
+            ]
+            .into(),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(format!("Edit oidc config for workspace {}", &w_id))
+}
+
 async fn get_usage(Extension(db): Extension<DB>, Path(w_id): Path<String>) -> Result<String> {
     let usage = sqlx::query_scalar!(
         "
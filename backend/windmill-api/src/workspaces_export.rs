@@ -20,6 +20,7 @@ use crate::{
 use axum::{
     extract::{Extension, Path, Query},
     response::IntoResponse,
+    Json,
 };
 
 use http::HeaderName;
@@ -28,10 +29,11 @@ use itertools::Itertools;
 use windmill_common::variables::decrypt;
 use windmill_common::{
     db::UserDB,
-    error::{to_anyhow, Error, Result},
+    error::{to_anyhow, Error, JsonResult, Result},
     flows::Flow,
     schedule::Schedule,
     scripts::{Schema, Script, ScriptLang},
+    utils::require_admin,
     variables::{build_crypt, ExportableListableVariable},
 };
 
@@ -428,7 +430,7 @@ pub(crate) async fn tarball_workspace(
 
     {
         let flows = sqlx::query_as::<_, Flow>(
-            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by
+            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow.strict_args, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by
             FROM flow
             LEFT JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
             WHERE flow.workspace_id = $1 AND flow.archived = false",
@@ -676,3 +678,303 @@ pub(crate) async fn tarball_workspace(
     ];
     Ok((headers, body))
 }
+
+#[derive(Deserialize)]
+struct AuditExportSettings {
+    #[serde(default)]
+    enabled: bool,
+    /// hour (UTC) after which the audit logs of the previous day are exported
+    #[serde(default)]
+    hour_utc: u32,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq)]
+#[sqlx(type_name = "AUDIT_EXPORT_STATUS", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportStatus {
+    Running,
+    Success,
+    Failure,
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct AuditLogExport {
+    pub id: i64,
+    pub workspace_id: String,
+    pub export_date: chrono::NaiveDate,
+    pub status: AuditExportStatus,
+    pub object_path: Option<String>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub triggered_by: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// hash the chain of the export starts from, the one of the previous export of the workspace
+    pub prev_chain_hash: Option<String>,
+    /// hash of the last exported log
+    pub chain_hash: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TriggerAuditExport {
+    /// day to export, defaults to yesterday (UTC)
+    date: Option<chrono::NaiveDate>,
+}
+
+fn audit_export_path(w_id: &str, date: chrono::NaiveDate) -> String {
+    format!("audit/{w_id}/{}.ndjson.zst", date.format("%Y-%m-%d"))
+}
+
+fn yesterday_utc() -> chrono::NaiveDate {
+    (chrono::Utc::now() - chrono::Duration::days(1)).date_naive()
+}
+
+/// An export left in the running state for longer than this is considered abandoned (e.g. the
+/// server exporting it was restarted) and can be claimed again
+const AUDIT_EXPORT_STALE_AFTER: &str = "1 hour";
+
+/// Hash of an exported audit log line, chained to the hash of the line before it so that an
+/// edited, removed or reordered line breaks every hash after it
+fn audit_chain_hash(prev_hash: &str, line: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(line);
+    hex::encode(hasher.finalize())
+}
+
+/// Streams the audit logs of one day as zstd compressed NDJSON and uploads them to the
+/// instance object store, returning the object path, the number of exported logs and the hash of
+/// the last one.
+///
+/// Every line ends with a `hash` field: the [`audit_chain_hash`] of the hash of the previous line
+/// (`prev_chain_hash` for the first one) and of the line without that field.
+#[cfg(feature = "parquet")]
+async fn upload_audit_logs_of_day(
+    db: &DB,
+    w_id: &str,
+    date: chrono::NaiveDate,
+    prev_chain_hash: &str,
+) -> Result<(String, i64, String)> {
+    use futures::TryStreamExt;
+    use std::io::Write;
+
+    let os = windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| {
+            Error::BadConfig(
+                "Object store is required for audit log exports and is not configured".to_string(),
+            )
+        })?;
+
+    let from = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let to = from + chrono::Duration::days(1);
+    let mut logs = sqlx::query_as::<_, windmill_audit::AuditLog>(
+        "SELECT workspace_id, id, timestamp, username, operation, action_kind, resource, parameters
+        FROM audit WHERE workspace_id = $1 AND timestamp >= $2 AND timestamp < $3
+        ORDER BY timestamp, id",
+    )
+    .bind(w_id)
+    .bind(from)
+    .bind(to)
+    .fetch(db);
+
+    let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 0)?;
+    let mut count = 0;
+    let mut chain_hash = prev_chain_hash.to_string();
+    while let Some(log) = logs.try_next().await? {
+        let line = serde_json::to_string(&log)?;
+        chain_hash = audit_chain_hash(&chain_hash, line.as_bytes());
+        writeln!(
+            encoder,
+            "{},\"hash\":\"{chain_hash}\"}}",
+            &line[..line.len() - 1]
+        )?;
+        count += 1;
+    }
+    let compressed = encoder.finish()?;
+
+    let path = audit_export_path(w_id, date);
+    os.put(
+        &object_store::path::Path::from(path.as_str()),
+        compressed.into(),
+    )
+    .await
+    .map_err(|e| Error::InternalErr(format!("Failed to put {path} to object store: {e}")))?;
+
+    Ok((path, count, chain_hash))
+}
+
+#[cfg(not(feature = "parquet"))]
+async fn upload_audit_logs_of_day(
+    _db: &DB,
+    _w_id: &str,
+    _date: chrono::NaiveDate,
+    _prev_chain_hash: &str,
+) -> Result<(String, i64, String)> {
+    Err(Error::BadConfig(
+        "Audit log exports require the object store support (parquet feature)".to_string(),
+    ))
+}
+
+/// Exports the audit logs of a workspace for one day and records the result in
+/// `audit_log_exports`. Returns `None` if the export is already done or running, unless `force`
+/// is set in which case only a running export prevents a new one. Running exports older than
+/// [`AUDIT_EXPORT_STALE_AFTER`] are taken over.
+async fn run_audit_export(
+    db: &DB,
+    w_id: &str,
+    date: chrono::NaiveDate,
+    triggered_by: &str,
+    force: bool,
+) -> Result<Option<AuditLogExport>> {
+    let export_id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO audit_log_exports (workspace_id, export_date, status, triggered_by)
+        VALUES ($1, $2, 'running', $3)
+        ON CONFLICT (workspace_id, export_date) DO UPDATE SET
+            status = 'running', triggered_by = EXCLUDED.triggered_by, started_at = now(),
+            finished_at = NULL, error = NULL
+        WHERE audit_log_exports.status = 'failure'
+            OR ($4 AND audit_log_exports.status <> 'running')
+            OR (audit_log_exports.status = 'running'
+                AND audit_log_exports.started_at < now() - $5::interval)
+        RETURNING id",
+    )
+    .bind(w_id)
+    .bind(date)
+    .bind(triggered_by)
+    .bind(force)
+    .bind(AUDIT_EXPORT_STALE_AFTER)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(export_id) = export_id else {
+        return Ok(None);
+    };
+
+    // the chain continues from the last successful export of an earlier day of the workspace
+    let prev_chain_hash = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT chain_hash FROM audit_log_exports
+        WHERE workspace_id = $1 AND export_date < $2 AND status = 'success'
+        ORDER BY export_date DESC LIMIT 1",
+    )
+    .bind(w_id)
+    .bind(date)
+    .fetch_optional(db)
+    .await?
+    .flatten()
+    .unwrap_or_default();
+
+    let (status, object_path, row_count, chain_hash, error) =
+        match upload_audit_logs_of_day(db, w_id, date, &prev_chain_hash).await {
+            Ok((path, count, chain_hash)) => (
+                AuditExportStatus::Success,
+                Some(path),
+                Some(count),
+                Some(chain_hash),
+                None,
+            ),
+            Err(e) => {
+                tracing::error!("Failed to export audit logs of {w_id} for {date}: {e:#}");
+                (
+                    AuditExportStatus::Failure,
+                    None,
+                    None,
+                    None,
+                    Some(e.to_string()),
+                )
+            }
+        };
+
+    let export = sqlx::query_as::<_, AuditLogExport>(
+        "UPDATE audit_log_exports SET status = $1, object_path = $2, row_count = $3, error = $4,
+            prev_chain_hash = $5, chain_hash = $6, finished_at = now()
+        WHERE id = $7 RETURNING *",
+    )
+    .bind(status)
+    .bind(object_path)
+    .bind(row_count)
+    .bind(error)
+    .bind(prev_chain_hash)
+    .bind(chain_hash)
+    .bind(export_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Some(export))
+}
+
+/// Nightly export of the audit logs of every workspace, configured by the `audit_export` global
+/// setting. Called periodically by the monitor, each (workspace, day) is exported at most once
+/// successfully, failed exports are retried after an hour and stale running ones are taken over.
+pub async fn export_audit_logs_to_object_store(db: &DB) {
+    let settings = match windmill_common::global_settings::load_value_from_global_settings(
+        db,
+        windmill_common::global_settings::AUDIT_EXPORT_SETTING,
+    )
+    .await
+    {
+        Ok(Some(value)) => match serde_json::from_value::<AuditExportSettings>(value) {
+            Ok(settings) => settings,
+            Err(e) => {
+                tracing::error!("Invalid audit export setting: {e:#}");
+                return;
+            }
+        },
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Could not load audit export setting: {e:#}");
+            return;
+        }
+    };
+
+    if !settings.enabled || chrono::Timelike::hour(&chrono::Utc::now()) < settings.hour_utc {
+        return;
+    }
+
+    let date = yesterday_utc();
+    let workspaces = sqlx::query_scalar::<_, String>(
+        "SELECT id FROM workspace w WHERE deleted = false AND NOT EXISTS (
+            SELECT 1 FROM audit_log_exports e WHERE e.workspace_id = w.id AND e.export_date = $1
+            AND (e.status = 'success'
+                OR (e.status = 'failure' AND e.finished_at > now() - interval '1 hour')
+                OR (e.status = 'running' AND e.started_at > now() - $2::interval))
+        )",
+    )
+    .bind(date)
+    .bind(AUDIT_EXPORT_STALE_AFTER)
+    .fetch_all(db)
+    .await;
+
+    match workspaces {
+        Ok(workspaces) => {
+            for w_id in workspaces {
+                if let Err(e) = run_audit_export(db, &w_id, date, "scheduler", false).await {
+                    tracing::error!("Error exporting audit logs of {w_id} for {date}: {e:#}");
+                }
+            }
+        }
+        Err(e) => tracing::error!("Could not list workspaces to export audit logs of: {e:#}"),
+    }
+}
+
+pub(crate) async fn trigger_audit_export(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(TriggerAuditExport { date }): Json<TriggerAuditExport>,
+) -> JsonResult<AuditLogExport> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let date = date.unwrap_or_else(yesterday_utc);
+    let export = run_audit_export(&db, &w_id, date, &authed.username, true)
+        .await?
+        .ok_or_else(|| {
+            Error::BadRequest(format!("An audit log export for {date} is already running"))
+        })?;
+
+    Ok(Json(export))
+}
@@ -1,5 +1,8 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::db::ApiAuthed;
 
+use crate::schedule::clear_schedule;
 use crate::workspaces::CREATE_WORKSPACE_REQUIRE_SUPERADMIN;
 use crate::{db::DB, utils::require_super_admin};
 
@@ -14,11 +17,15 @@ use windmill_audit::ActionKind;
 use windmill_common::worker::CLOUD_HOSTED;
 
 use windmill_common::{
-    error::{Error, Result},
+    error::{Error, JsonResult, Result},
+    schedule::Schedule,
     utils::require_admin,
+    variables::{build_crypt, decrypt, encrypt},
 };
+use windmill_queue::schedule::push_scheduled_job;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
 
 #[derive(Deserialize)]
 pub(crate) struct ChangeWorkspaceId {
@@ -427,140 +434,603 @@ pub(crate) async fn delete_workspace(
     let mut tx = db.begin().await?;
     require_super_admin(&db, &authed.email).await?;
 
-    sqlx::query!("DELETE FROM dependency_map WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    delete_workspace_data(&mut tx, &w_id).await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(&authed.email),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Deleted workspace {}", &w_id))
+}
+
+/// Deletes the workspace and everything that references it, returns the number of deleted
+/// workspace rows
+pub(crate) async fn delete_workspace_data(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+) -> Result<u64> {
+    sqlx::query!("DELETE FROM dependency_map WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM queue WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM queue WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM capture WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM capture WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
     // capture_config has on delete cascade
 
-    sqlx::query!("DELETE FROM draft WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM draft WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM script WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM script WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM flow WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM flow WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM app WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM app WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM raw_app WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM raw_app WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM input WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM input WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM variable WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM variable WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query!("DELETE FROM resource WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM resource WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM schedule WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM schedule WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM completed_job WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM completed_job WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM job_stats WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM job_stats WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
     sqlx::query!(
         "DELETE FROM deployment_metadata WHERE workspace_id = $1",
-        &w_id
+        w_id
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
-    sqlx::query!("DELETE FROM usr WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM usr WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM resource_type WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM resource_type WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!(
-        "DELETE FROM workspace_invite WHERE workspace_id = $1",
-        &w_id
-    )
-    .execute(&mut *tx)
-    .await?;
+    sqlx::query!("DELETE FROM workspace_invite WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
+        .await?;
 
-    sqlx::query!("DELETE FROM usr_to_group WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM usr_to_group WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM group_ WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM group_ WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM folder WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM folder WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM account WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM account WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM workspace_key WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM workspace_key WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
     sqlx::query!(
         "DELETE FROM workspace_settings WHERE workspace_id = $1",
-        &w_id
+        w_id
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
-    sqlx::query!("DELETE FROM token WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM token WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
-    sqlx::query!("DELETE FROM http_trigger WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM http_trigger WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
     sqlx::query!(
         "DELETE FROM websocket_trigger WHERE workspace_id = $1",
-        &w_id
+        w_id
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
-    sqlx::query!("DELETE FROM kafka_trigger WHERE workspace_id = $1", &w_id)
-        .execute(&mut *tx)
+    sqlx::query!("DELETE FROM kafka_trigger WHERE workspace_id = $1", w_id)
+        .execute(&mut **tx)
         .await?;
 
     // NATS triggers have on delete cascade
 
-    sqlx::query!("DELETE FROM workspace WHERE id = $1", &w_id)
-        .execute(&mut *tx)
-        .await?;
+    let deleted = sqlx::query!("DELETE FROM workspace WHERE id = $1", w_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+    Ok(deleted)
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CloneCategory {
+    Scripts,
+    Flows,
+    Schedules,
+    Resources,
+    Variables,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CloneWorkspace {
+    include: Vec<CloneCategory>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SkippedPath {
+    path: String,
+    reason: String,
+}
+
+/// `cloned` counts all the items written to the target workspace, `overwritten` the ones among
+/// them that replaced an existing item
+#[derive(Serialize, Default)]
+pub(crate) struct CloneCategoryReport {
+    cloned: usize,
+    overwritten: usize,
+    skipped: Vec<SkippedPath>,
+}
+
+impl CloneCategoryReport {
+    fn skip(&mut self, path: String, reason: impl Into<String>) {
+        self.skipped
+            .push(SkippedPath { path, reason: reason.into() });
+    }
+}
+
+#[derive(Serialize, Default)]
+pub(crate) struct CloneReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scripts: Option<CloneCategoryReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flows: Option<CloneCategoryReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<CloneCategoryReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<CloneCategoryReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedules: Option<CloneCategoryReport>,
+}
+
+const ALREADY_EXISTS: &str = "already exists in the target workspace";
+
+/// Going through jsonb copies all the columns of the rows without having to list them,
+/// `overrides` being `'column', value` pairs replacing some of them
+fn copy_rows_sql(table: &str, overrides: &str, filter: &str) -> String {
+    format!(
+        "INSERT INTO {table} SELECT (jsonb_populate_record(NULL::{table}, \
+            to_jsonb(t) || jsonb_build_object({overrides}))).* \
+        FROM {table} t WHERE {filter}"
+    )
+}
+
+async fn paths_of(
+    tx: &mut Transaction<'static, Postgres>,
+    table: &str,
+    w_id: &str,
+) -> Result<HashSet<String>> {
+    let paths = sqlx::query_scalar::<_, String>(&format!(
+        "SELECT path FROM {table} WHERE workspace_id = $1"
+    ))
+    .bind(w_id)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(paths.into_iter().collect())
+}
+
+pub(crate) async fn clone_workspace(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, target_id)): Path<(String, String)>,
+    Json(req): Json<CloneWorkspace>,
+) -> JsonResult<CloneReport> {
+    require_super_admin(&db, &authed.email).await?;
+
+    if w_id == target_id {
+        return Err(Error::BadRequest(
+            "cannot clone a workspace into itself".to_string(),
+        ));
+    }
+    let target_exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM workspace WHERE id = $1 AND deleted = false)",
+    )
+    .bind(&target_id)
+    .fetch_one(&db)
+    .await?;
+    if !target_exists {
+        return Err(Error::NotFound(format!("Workspace {target_id} not found")));
+    }
+
+    let includes = |category| req.include.contains(&category);
+    let overwrite = req.overwrite;
+    let mut report = CloneReport::default();
+    let mut tx = db.begin().await?;
+
+    if includes(CloneCategory::Scripts) {
+        report.scripts = Some(clone_scripts(&mut tx, &w_id, &target_id, overwrite).await?);
+    }
+    if includes(CloneCategory::Flows) {
+        report.flows =
+            Some(clone_flows(&mut tx, &w_id, &target_id, &authed.username, overwrite).await?);
+    }
+    if includes(CloneCategory::Resources) {
+        report.resources = Some(clone_resources(&mut tx, &w_id, &target_id, overwrite).await?);
+    }
+    if includes(CloneCategory::Variables) {
+        report.variables = Some(clone_variables(&db, &mut tx, &w_id, &target_id, overwrite).await?);
+    }
+    // schedules come last as they are only cloned if what they run exists in the target workspace
+    if includes(CloneCategory::Schedules) {
+        let (ntx, schedules) = clone_schedules(&db, tx, &w_id, &target_id, overwrite).await?;
+        tx = ntx;
+        report.schedules = Some(schedules);
+    }
 
     audit_log(
         &mut *tx,
         &authed,
-        "workspaces.delete",
-        ActionKind::Delete,
-        &w_id,
-        Some(&authed.email),
-        None,
+        "workspace.clone_to",
+        ActionKind::Create,
+        &target_id,
+        Some(&w_id),
+        Some([("overwrite", if overwrite { "true" } else { "false" })].into()),
     )
     .await?;
     tx.commit().await?;
 
-    Ok(format!("Deleted workspace {}", &w_id))
+    Ok(Json(report))
+}
+
+/// Only the latest version of each script is cloned, an overwritten script keeping its history
+/// in the target workspace
+async fn clone_scripts(
+    tx: &mut Transaction<'static, Postgres>,
+    source: &str,
+    target: &str,
+    overwrite: bool,
+) -> Result<CloneCategoryReport> {
+    let latest_sql = "SELECT DISTINCT ON (path) path, hash FROM script \
+        WHERE workspace_id = $1 AND archived = false AND deleted = false \
+        ORDER BY path, created_at DESC";
+    let scripts = sqlx::query_as::<_, (String, i64)>(latest_sql)
+        .bind(source)
+        .fetch_all(&mut **tx)
+        .await?;
+    let existing: HashMap<String, i64> = sqlx::query_as::<_, (String, i64)>(latest_sql)
+        .bind(target)
+        .fetch_all(&mut **tx)
+        .await?
+        .into_iter()
+        .collect();
+
+    let mut report = CloneCategoryReport::default();
+    for (path, hash) in scripts {
+        let parent_hash = existing.get(&path).copied();
+        if parent_hash.is_some() {
+            if !overwrite {
+                report.skip(path, ALREADY_EXISTS);
+                continue;
+            }
+            sqlx::query(
+                "UPDATE script SET archived = true \
+                WHERE workspace_id = $1 AND path = $2 AND archived = false",
+            )
+            .bind(target)
+            .bind(&path)
+            .execute(&mut **tx)
+            .await?;
+            report.overwritten += 1;
+        }
+        // the same version can already be in the target workspace if it was cloned before
+        sqlx::query(&format!(
+            "{} ON CONFLICT (workspace_id, hash) DO UPDATE SET archived = false",
+            copy_rows_sql(
+                "script",
+                "'workspace_id', $1::text, 'parent_hashes', $2::bigint[]",
+                "t.workspace_id = $3 AND t.hash = $4",
+            )
+        ))
+        .bind(target)
+        .bind(parent_hash.map(|h| vec![h]))
+        .bind(source)
+        .bind(hash)
+        .execute(&mut **tx)
+        .await?;
+        report.cloned += 1;
+    }
+    Ok(report)
+}
+
+async fn clone_flows(
+    tx: &mut Transaction<'static, Postgres>,
+    source: &str,
+    target: &str,
+    username: &str,
+    overwrite: bool,
+) -> Result<CloneCategoryReport> {
+    let flows = sqlx::query_scalar::<_, String>(
+        "SELECT path FROM flow \
+        WHERE workspace_id = $1 AND archived = false AND draft_only IS NOT TRUE ORDER BY path",
+    )
+    .bind(source)
+    .fetch_all(&mut **tx)
+    .await?;
+    let existing = paths_of(tx, "flow", target).await?;
+
+    let mut report = CloneCategoryReport::default();
+    for path in flows {
+        if existing.contains(&path) {
+            if !overwrite {
+                report.skip(path, ALREADY_EXISTS);
+                continue;
+            }
+            // updated in place rather than replaced to keep the versions of the target flow
+            sqlx::query(
+                "UPDATE flow f SET summary = s.summary, description = s.description, value = s.value, \
+                    schema = s.schema, tag = s.tag, ws_error_handler_muted = s.ws_error_handler_muted, \
+                    dedicated_worker = s.dedicated_worker, timeout = s.timeout, \
+                    visible_to_runner_only = s.visible_to_runner_only, \
+                    on_behalf_of_email = s.on_behalf_of_email, concurrency_key = s.concurrency_key, \
+                    archived = false, draft_only = NULL, edited_by = $3, edited_at = now() \
+                FROM flow s \
+                WHERE f.workspace_id = $1 AND f.path = $4 AND s.workspace_id = $2 AND s.path = $4",
+            )
+            .bind(target)
+            .bind(source)
+            .bind(username)
+            .bind(&path)
+            .execute(&mut **tx)
+            .await?;
+            report.overwritten += 1;
+        } else {
+            sqlx::query(&copy_rows_sql(
+                "flow",
+                "'workspace_id', $1::text, 'versions', '{}'::bigint[], 'dependency_job', NULL::uuid",
+                "t.workspace_id = $2 AND t.path = $3",
+            ))
+            .bind(target)
+            .bind(source)
+            .bind(&path)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        let version = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO flow_version (workspace_id, path, value, schema, created_by) \
+            SELECT $1, path, value, schema, $3 FROM flow WHERE workspace_id = $2 AND path = $4 \
+            RETURNING id",
+        )
+        .bind(target)
+        .bind(source)
+        .bind(username)
+        .bind(&path)
+        .fetch_one(&mut **tx)
+        .await?;
+        sqlx::query(
+            "UPDATE flow SET versions = array_append(versions, $1) \
+            WHERE workspace_id = $2 AND path = $3",
+        )
+        .bind(version)
+        .bind(target)
+        .bind(&path)
+        .execute(&mut **tx)
+        .await?;
+        report.cloned += 1;
+    }
+    Ok(report)
+}
+
+async fn clone_resources(
+    tx: &mut Transaction<'static, Postgres>,
+    source: &str,
+    target: &str,
+    overwrite: bool,
+) -> Result<CloneCategoryReport> {
+    let resources =
+        sqlx::query_scalar::<_, String>("SELECT path FROM resource WHERE workspace_id = $1")
+            .bind(source)
+            .fetch_all(&mut **tx)
+            .await?;
+    let existing = paths_of(tx, "resource", target).await?;
+
+    let mut report = CloneCategoryReport::default();
+    for path in resources {
+        if existing.contains(&path) {
+            if !overwrite {
+                report.skip(path, ALREADY_EXISTS);
+                continue;
+            }
+            sqlx::query("DELETE FROM resource WHERE workspace_id = $1 AND path = $2")
+                .bind(target)
+                .bind(&path)
+                .execute(&mut **tx)
+                .await?;
+            report.overwritten += 1;
+        }
+        sqlx::query(&copy_rows_sql(
+            "resource",
+            "'workspace_id', $1::text",
+            "t.workspace_id = $2 AND t.path = $3",
+        ))
+        .bind(target)
+        .bind(source)
+        .bind(&path)
+        .execute(&mut **tx)
+        .await?;
+        report.cloned += 1;
+    }
+    Ok(report)
+}
+
+/// Secrets are re-encrypted with the key of the target workspace. Variables linked to an OAuth
+/// account are not cloned as the account belongs to the source workspace.
+async fn clone_variables(
+    db: &DB,
+    tx: &mut Transaction<'static, Postgres>,
+    source: &str,
+    target: &str,
+    overwrite: bool,
+) -> Result<CloneCategoryReport> {
+    let variables = sqlx::query_as::<_, (String, String, bool, Option<i32>)>(
+        "SELECT path, value, is_secret, account FROM variable WHERE workspace_id = $1",
+    )
+    .bind(source)
+    .fetch_all(&mut **tx)
+    .await?;
+    let existing = paths_of(tx, "variable", target).await?;
+    let source_mc = build_crypt(db, source).await?;
+    let target_mc = build_crypt(db, target).await?;
+
+    let mut report = CloneCategoryReport::default();
+    for (path, value, is_secret, account) in variables {
+        if account.is_some() {
+            report.skip(path, "linked to an OAuth account of the source workspace");
+            continue;
+        }
+        if existing.contains(&path) {
+            if !overwrite {
+                report.skip(path, ALREADY_EXISTS);
+                continue;
+            }
+            sqlx::query("DELETE FROM variable WHERE workspace_id = $1 AND path = $2")
+                .bind(target)
+                .bind(&path)
+                .execute(&mut **tx)
+                .await?;
+            report.overwritten += 1;
+        }
+        let value = if is_secret {
+            encrypt(&target_mc, &decrypt(&source_mc, value)?)
+        } else {
+            value
+        };
+        sqlx::query(&copy_rows_sql(
+            "variable",
+            "'workspace_id', $1::text, 'value', $4::text",
+            "t.workspace_id = $2 AND t.path = $3",
+        ))
+        .bind(target)
+        .bind(source)
+        .bind(&path)
+        .bind(value)
+        .execute(&mut **tx)
+        .await?;
+        report.cloned += 1;
+    }
+    Ok(report)
+}
+
+/// A schedule is only cloned if the script or flow it runs exists in the target workspace. Flow
+/// version ids are specific to a workspace, so cloned schedules run the latest version.
+async fn clone_schedules(
+    db: &DB,
+    mut tx: Transaction<'static, Postgres>,
+    source: &str,
+    target: &str,
+    overwrite: bool,
+) -> Result<(Transaction<'static, Postgres>, CloneCategoryReport)> {
+    let schedules = sqlx::query_as::<_, (String, String, bool)>(
+        "SELECT path, script_path, is_flow FROM schedule WHERE workspace_id = $1",
+    )
+    .bind(source)
+    .fetch_all(&mut *tx)
+    .await?;
+    let existing = paths_of(&mut tx, "schedule", target).await?;
+
+    let mut report = CloneCategoryReport::default();
+    for (path, script_path, is_flow) in schedules {
+        let exists = existing.contains(&path);
+        if exists && !overwrite {
+            report.skip(path, ALREADY_EXISTS);
+            continue;
+        }
+        let runnable_exists = sqlx::query_scalar::<_, bool>(if is_flow {
+            "SELECT EXISTS(SELECT 1 FROM flow \
+                WHERE workspace_id = $1 AND path = $2 AND archived = false)"
+        } else {
+            "SELECT EXISTS(SELECT 1 FROM script \
+                WHERE workspace_id = $1 AND path = $2 AND archived = false AND deleted = false)"
+        })
+        .bind(target)
+        .bind(&script_path)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !runnable_exists {
+            let kind = if is_flow { "flow" } else { "script" };
+            report.skip(
+                path,
+                format!("{kind} {script_path} does not exist in the target workspace"),
+            );
+            continue;
+        }
+        if exists {
+            clear_schedule(&mut tx, &path, target).await?;
+            sqlx::query("DELETE FROM schedule WHERE workspace_id = $1 AND path = $2")
+                .bind(target)
+                .bind(&path)
+                .execute(&mut *tx)
+                .await?;
+            report.overwritten += 1;
+        }
+        let schedule = sqlx::query_as::<_, Schedule>(&format!(
+            "{} RETURNING *",
+            copy_rows_sql(
+                "schedule",
+                "'workspace_id', $1::text, 'pinned_version_id', NULL::bigint",
+                "t.workspace_id = $2 AND t.path = $3",
+            )
+        ))
+        .bind(target)
+        .bind(source)
+        .bind(&path)
+        .fetch_one(&mut *tx)
+        .await?;
+        if schedule.enabled {
+            tx = push_scheduled_job(db, tx, &schedule, None).await?;
+        }
+        report.cloned += 1;
+    }
+    Ok((tx, report))
 }
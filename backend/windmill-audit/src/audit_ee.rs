@@ -9,10 +9,10 @@ use std::collections::HashMap;
 
 use windmill_common::{
     error::{Error, Result},
-    utils::Pagination,
+    utils::{paginate, Pagination},
 };
 
-use crate::{ActionKind, AuditLog, ListAuditLogQuery};
+use crate::{list_audit_query, ActionKind, AuditLog, ListAuditLogQuery};
 use sqlx::{Postgres, Transaction};
 
 #[derive(Clone)]
@@ -56,14 +56,25 @@ pub async fn audit_log<'c, E: sqlx::Executor<'c, Database = Postgres>>(
     Ok(())
 }
 
+/// Lists the audit logs of a workspace, most recent first. With a cursor the logs are keyset
+/// paginated and the page of `pagination` is ignored.
 pub async fn list_audit(
-    _tx: Transaction<'_, Postgres>,
-    _w_id: String,
-    _pagination: Pagination,
-    _lq: ListAuditLogQuery,
+    mut tx: Transaction<'_, Postgres>,
+    w_id: String,
+    pagination: Pagination,
+    lq: ListAuditLogQuery,
 ) -> Result<Vec<AuditLog>> {
-    // Implementation is not open source as Audit logs is a Windmill Enterprise Edition feature
-    return Ok(vec![]);
+    let (per_page, offset) = if lq.cursor.is_some() {
+        paginate(Pagination { per_page: pagination.per_page, page: None })
+    } else {
+        paginate(pagination)
+    };
+    let sql = list_audit_query(&w_id, per_page, offset, &lq)?.sql()?;
+    let rows = sqlx::query_as::<_, AuditLog>(&sql)
+        .fetch_all(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(rows)
 }
 
 pub async fn get_audit(tx: Transaction<'_, Postgres>, _id: i32, _w_id: &str) -> Result<AuditLog> {
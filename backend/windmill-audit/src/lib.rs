@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
+use sql_builder::{bind::Bind, quote, SqlBuilder};
 use sqlx::FromRow;
+use windmill_common::error::{Error, Result};
 
 pub mod audit_ee;
 
@@ -12,6 +14,33 @@ pub enum ActionKind {
     Execute,
 }
 
+impl ActionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionKind::Create => "create",
+            ActionKind::Update => "update",
+            ActionKind::Delete => "delete",
+            ActionKind::Execute => "execute",
+        }
+    }
+}
+
+impl std::str::FromStr for ActionKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "create" => Ok(ActionKind::Create),
+            "update" => Ok(ActionKind::Update),
+            "delete" => Ok(ActionKind::Delete),
+            "execute" => Ok(ActionKind::Execute),
+            _ => Err(Error::BadRequest(format!(
+                "Invalid action_kind: {s}, expected one of create, update, delete, execute"
+            ))),
+        }
+    }
+}
+
 #[derive(FromRow, Serialize, Deserialize)]
 pub struct AuditLog {
     pub workspace_id: String,
@@ -24,9 +53,11 @@ pub struct AuditLog {
     pub parameters: Option<serde_json::Value>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct ListAuditLogQuery {
     pub username: Option<String>,
+    /// matched against the email of the workspace user that performed the action
+    pub email: Option<String>,
     pub operation: Option<String>,
     pub operations: Option<String>,
     pub exclude_operations: Option<String>,
@@ -34,4 +65,196 @@ pub struct ListAuditLogQuery {
     pub resource: Option<String>,
     pub before: Option<chrono::DateTime<chrono::Utc>>,
     pub after: Option<chrono::DateTime<chrono::Utc>>,
+    /// opaque keyset cursor, an empty string requests the first page
+    pub cursor: Option<String>,
+}
+
+/// Position of the last audit log of a page, audit logs being listed by (timestamp, id) desc
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditCursor {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub id: i32,
+}
+
+impl AuditCursor {
+    pub fn from_log(log: &AuditLog) -> Self {
+        Self { timestamp: log.timestamp, id: log.id }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.timestamp.timestamp_micros(), self.id)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || Error::BadRequest(format!("Invalid audit cursor: {cursor}"));
+        let (micros, id) = cursor.split_once('_').ok_or_else(invalid)?;
+        let timestamp = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(chrono::DateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?;
+        let id = id.parse::<i32>().map_err(|_| invalid())?;
+        Ok(Self { timestamp, id })
+    }
+
+    /// Restricts the listing to the audit logs strictly after the cursor
+    pub fn add_condition(&self, sqlb: &mut SqlBuilder) {
+        sqlb.and_where(
+            "(timestamp, id) < (?, ?)"
+                .bind(&self.timestamp.to_rfc3339())
+                .bind(&self.id),
+        );
+    }
+}
+
+impl ListAuditLogQuery {
+    pub fn parsed_cursor(&self) -> Result<Option<AuditCursor>> {
+        match self.cursor.as_deref() {
+            None | Some("") => Ok(None),
+            Some(cursor) => AuditCursor::decode(cursor).map(Some),
+        }
+    }
+
+    pub fn parsed_action_kind(&self) -> Result<Option<ActionKind>> {
+        self.action_kind
+            .as_deref()
+            .map(str::parse::<ActionKind>)
+            .transpose()
+    }
+
+    /// Restricts the listing by action kind and by who performed the action
+    pub fn add_actor_filters(&self, sqlb: &mut SqlBuilder, w_id: &str) -> Result<()> {
+        if let Some(action_kind) = self.parsed_action_kind()? {
+            sqlb.and_where_eq("action_kind", "?".bind(&action_kind.as_str()));
+        }
+        if let Some(username) = &self.username {
+            sqlb.and_where_eq("username", "?".bind(username));
+        }
+        if let Some(email) = &self.email {
+            sqlb.and_where(
+                "username IN (SELECT username FROM usr WHERE workspace_id = ? AND email = ?)"
+                    .bind(&w_id)
+                    .bind(email),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Fields of [`AuditLog`] selected from `audit`
+pub const AUDIT_LOG_FIELDS: [&str; 8] = [
+    "workspace_id",
+    "id",
+    "timestamp",
+    "username",
+    "operation",
+    "action_kind",
+    "resource",
+    "parameters",
+];
+
+/// Lists the audit logs of a workspace matching `lq`, most recent first. When a cursor is given
+/// it replaces the offset.
+pub fn list_audit_query(
+    w_id: &str,
+    per_page: usize,
+    offset: usize,
+    lq: &ListAuditLogQuery,
+) -> Result<SqlBuilder> {
+    let mut sqlb = SqlBuilder::select_from("audit")
+        .fields(&AUDIT_LOG_FIELDS)
+        .order_by("timestamp", true)
+        .order_by("id", true)
+        .limit(per_page)
+        .clone();
+
+    sqlb.and_where_eq("workspace_id", "?".bind(&w_id));
+    match lq.parsed_cursor()? {
+        Some(cursor) => cursor.add_condition(&mut sqlb),
+        None => {
+            sqlb.offset(offset);
+        }
+    }
+    lq.add_actor_filters(&mut sqlb, w_id)?;
+
+    if let Some(operation) = &lq.operation {
+        sqlb.and_where_eq("operation", "?".bind(operation));
+    }
+    if let Some(operations) = &lq.operations {
+        sqlb.and_where_in(
+            "operation",
+            &operations.split(',').map(quote).collect::<Vec<_>>(),
+        );
+    }
+    if let Some(exclude_operations) = &lq.exclude_operations {
+        sqlb.and_where_not_in(
+            "operation",
+            &exclude_operations.split(',').map(quote).collect::<Vec<_>>(),
+        );
+    }
+    if let Some(resource) = &lq.resource {
+        sqlb.and_where_eq("resource", "?".bind(resource));
+    }
+    if let Some(before) = &lq.before {
+        sqlb.and_where_le("timestamp", "?".bind(&before.to_rfc3339()));
+    }
+    if let Some(after) = &lq.after {
+        sqlb.and_where_ge("timestamp", "?".bind(&after.to_rfc3339()));
+    }
+
+    Ok(sqlb)
+}
+
+#[derive(Serialize)]
+pub struct AuditLogPage {
+    pub items: Vec<AuditLog>,
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_query_applies_actor_filters_and_cursor() {
+        let cursor = AuditCursor {
+            timestamp: chrono::DateTime::from_timestamp_micros(1_700_000_000_000_000).unwrap(),
+            id: 42,
+        };
+        let lq = ListAuditLogQuery {
+            action_kind: Some("Delete".to_string()),
+            email: Some("a@b.c".to_string()),
+            cursor: Some(cursor.encode()),
+            ..Default::default()
+        };
+        let sql = list_audit_query("ws", 10, 20, &lq).unwrap().sql().unwrap();
+
+        assert!(sql.contains("action_kind = 'delete'"), "{sql}");
+        assert!(
+            sql.contains("workspace_id = 'ws' AND email = 'a@b.c'"),
+            "{sql}"
+        );
+        assert!(
+            sql.contains("(timestamp, id) < ('2023-11-14T22:13:20+00:00', 42)"),
+            "{sql}"
+        );
+        assert!(!sql.contains("OFFSET"), "{sql}");
+    }
+
+    #[test]
+    fn list_query_without_cursor_is_offset_paginated() {
+        let lq = ListAuditLogQuery {
+            operations: Some("jobs.run,users.login".to_string()),
+            ..Default::default()
+        };
+        let sql = list_audit_query("ws", 10, 20, &lq).unwrap().sql().unwrap();
+        assert!(
+            sql.contains("operation IN ('jobs.run', 'users.login')"),
+            "{sql}"
+        );
+        assert!(sql.contains("OFFSET 20"), "{sql}");
+
+        let lq = ListAuditLogQuery { action_kind: Some("drop".to_string()), ..Default::default() };
+        assert!(list_audit_query("ws", 10, 0, &lq).is_err());
+    }
 }
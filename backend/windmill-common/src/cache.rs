@@ -504,9 +504,22 @@ pub mod script {
         static ref CACHE: { ScriptHash => ScriptFull } in "script" <= 1000;
     }
 
+    #[cfg(not(feature = "scoped_cache"))]
+    lazy_static! {
+        /// `redact_args` of the scripts, set once when the script is created.
+        static ref REDACT_ARGS: Cache<ScriptHash, Arc<[String]>> = Cache::new(1000);
+    }
+
+    #[cfg(feature = "scoped_cache")]
+    lazy_static! {
+        /// `redact_args` of the scripts, set once when the script is created.
+        static ref REDACT_ARGS: Cache<(ThreadId, ScriptHash), Arc<[String]>> = Cache::new(1000);
+    }
+
     /// Clear the script cache.
     pub fn clear() {
         CACHE.clear();
+        REDACT_ARGS.clear();
     }
 
     /// Fetch the script referenced by `hash` from the cache.
@@ -563,6 +576,30 @@ pub mod script {
     pub fn invalidate(hash: ScriptHash) {
         let _ = CACHE.remove(&hash);
     }
+
+    /// Fetch the paths of the args to redact from the jobs of the script referenced by `hash` from
+    /// the cache, or from the database if not present.
+    pub async fn fetch_redact_args<'c>(
+        e: impl PgExecutor<'c>,
+        hash: ScriptHash,
+    ) -> error::Result<Arc<[String]>> {
+        let fetch = async move {
+            sqlx::query_scalar::<_, Option<Vec<String>>>(
+                "SELECT redact_args FROM script WHERE hash = $1 LIMIT 1",
+            )
+            .bind(hash.0)
+            .fetch_optional(e)
+            .await
+            .map(|paths| paths.flatten().unwrap_or_default().into())
+            .map_err(Into::into)
+        };
+        #[cfg(not(feature = "scoped_cache"))]
+        return REDACT_ARGS.get_or_insert_async(&hash, fetch).await;
+        #[cfg(feature = "scoped_cache")]
+        return REDACT_ARGS
+            .get_or_insert_async(&(std::thread::current().id(), hash), fetch)
+            .await;
+    }
 }
 
 pub mod app {
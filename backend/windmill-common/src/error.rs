@@ -40,6 +40,8 @@ pub enum Error {
     RequireAdmin(String),
     #[error("{0}")]
     ExecutionErr(String),
+    #[error("{0}")]
+    JobTimeout(String),
     #[error("IO error: {0}")]
     IoErr(#[from] io::Error),
     #[error("Sql error: {0}")]
@@ -48,6 +50,8 @@ pub enum Error {
     BadRequest(String),
     #[error("Quota exceeded: {0}")]
     QuotaExceeded(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
     #[error("Internal: {0}")]
     InternalErr(String),
     #[error("Internal: {0}: {1}")]
@@ -109,6 +113,7 @@ impl IntoResponse for Error {
             Self::NotFound(_) => axum::http::StatusCode::NOT_FOUND,
             Self::NotAuthorized(_) => axum::http::StatusCode::UNAUTHORIZED,
             Self::RequireAdmin(_) => axum::http::StatusCode::FORBIDDEN,
+            Self::Conflict(_) => axum::http::StatusCode::CONFLICT,
             Self::SqlErr(_) | Self::BadRequest(_) | Self::AiError(_) | Self::QuotaExceeded(_) => {
                 axum::http::StatusCode::BAD_REQUEST
             }
@@ -20,6 +20,9 @@ const HOURS: Duration = MINUTES.saturating_mul(60);
 pub const MAX_RETRY_ATTEMPTS: u32 = u32::MAX;
 pub const MAX_RETRY_INTERVAL: Duration = HOURS.saturating_mul(6);
 
+/// `reason` of a failed step that was killed for exceeding its `step_timeout_ms`
+pub const STEP_TIMEOUT_REASON: &str = "step_timeout";
+
 pub fn is_retry_default(v: &RetryStatus) -> bool {
     v.fail_count == 0 && v.failed_jobs.is_empty()
 }
@@ -129,6 +132,7 @@ struct UntaggedFlowStatusModule {
     approvers: Option<Vec<Approval>>,
     failed_retries: Option<Vec<Uuid>>,
     skipped: Option<bool>,
+    reason: Option<String>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -193,6 +197,8 @@ pub enum FlowStatusModule {
         branch_chosen: Option<BranchChosen>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         failed_retries: Vec<Uuid>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
     },
 }
 
@@ -270,6 +276,7 @@ impl<'de> Deserialize<'de> for FlowStatusModule {
                 flow_jobs_success: untagged.flow_jobs_success,
                 branch_chosen: untagged.branch_chosen,
                 failed_retries: untagged.failed_retries.unwrap_or_default(),
+                reason: untagged.reason,
             }),
             other => Err(serde::de::Error::unknown_variant(
                 other,
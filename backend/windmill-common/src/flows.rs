@@ -51,6 +51,8 @@ pub struct Flow {
     pub visible_to_runner_only: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub on_behalf_of_email: Option<String>,
+    #[serde(skip_serializing_if = "is_none_or_false")]
+    pub strict_args: Option<bool>,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -101,6 +103,7 @@ pub struct NewFlow {
     pub deployment_message: Option<String>,
     pub visible_to_runner_only: Option<bool>,
     pub on_behalf_of_email: Option<String>,
+    pub strict_args: Option<bool>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -272,6 +275,9 @@ pub struct FlowModule {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeout: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    // Per-step override of `timeout`, in milliseconds
+    pub step_timeout_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     // Priority at the flow step level
     pub priority: Option<i16>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -320,6 +326,15 @@ impl FlowModule {
         serde_json::from_str::<FlowModuleValue>(self.value.get()).map_err(crate::error::to_anyhow)
     }
 
+    /// Timeout in seconds of the job dispatched for this step. `step_timeout_ms` takes precedence
+    /// over `timeout` and is rounded up to the next second, the worker enforces the exact value
+    /// stored in the `timeout_ms` of the job.
+    pub fn step_timeout(&self) -> Option<i32> {
+        self.step_timeout_ms
+            .map(|ms| ms.div_ceil(1000).min(i32::MAX as u32) as i32)
+            .or(self.timeout)
+    }
+
     pub fn get_value_with_skip_failures(&self) -> anyhow::Result<FlowModuleValueWithSkipFailures> {
         serde_json::from_str::<FlowModuleValueWithSkipFailures>(self.value.get())
             .map_err(crate::error::to_anyhow)
@@ -702,6 +717,7 @@ pub fn add_virtual_items_if_necessary(modules: &mut Vec<FlowModule>) {
             suspend: None,
             cache_ttl: None,
             timeout: None,
+            step_timeout_ms: None,
             priority: None,
             delete_after_use: None,
             continue_on_error: None,
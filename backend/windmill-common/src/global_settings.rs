@@ -4,6 +4,7 @@ pub const DEFAULT_TAGS_WORKSPACES_SETTING: &str = "default_tags_workspaces";
 pub const BASE_URL_SETTING: &str = "base_url";
 pub const OAUTH_SETTING: &str = "oauths";
 pub const RETENTION_PERIOD_SECS_SETTING: &str = "retention_period_secs";
+pub const AUDIT_RETENTION_PERIOD_SECS_SETTING: &str = "audit_retention_period_secs";
 pub const MONITOR_LOGS_ON_OBJECT_STORE_SETTING: &str = "monitor_logs_on_s3";
 pub const JOB_DEFAULT_TIMEOUT_SECS_SETTING: &str = "job_default_timeout";
 pub const REQUEST_SIZE_LIMIT_SETTING: &str = "request_size_limit_mb";
@@ -37,15 +38,27 @@ pub const CRITICAL_ERROR_CHANNELS_SETTING: &str = "critical_error_channels";
 pub const CRITICAL_ALERT_MUTE_UI_SETTING: &str = "critical_alert_mute_ui";
 pub const DEV_INSTANCE_SETTING: &str = "dev_instance";
 pub const JWT_SECRET_SETTING: &str = "jwt_secret";
+pub const OIDC_SIGNING_KEY_SETTING: &str = "oidc_signing_key";
 pub const EMAIL_DOMAIN_SETTING: &str = "email_domain";
 pub const OTEL_SETTING: &str = "otel";
+pub const AUDIT_EXPORT_SETTING: &str = "audit_export";
+pub const RUN_RATE_LIMIT_SETTING: &str = "run_rate_limit";
+pub const INTROSPECTION_CLIENT_SETTING: &str = "introspection_client";
+pub const JOB_ARCHIVE_AFTER_DAYS_SETTING: &str = "job_archive_after_days";
+pub const LARGE_RESULT_THRESHOLD_KB_SETTING: &str = "large_result_threshold_kb";
+pub const VERSION_CHECK_URL_SETTING: &str = "version_check_url";
+pub const AI_COMPLETION_URL_SETTING: &str = "ai_completion_url";
 
-pub const ENV_SETTINGS: [&str; 55] = [
+pub const ENV_SETTINGS: [&str; 59] = [
     "DISABLE_NSJAIL",
     "MODE",
     "NUM_WORKERS",
     "METRICS_ADDR",
     "JSON_FMT",
+    "LOG_FORMAT",
+    "LARGE_RESULT_THRESHOLD_KB",
+    "VERSION_CHECK_URL",
+    "AI_COMPLETION_URL",
     "BASE_URL",
     "TIMEOUT",
     "ZOMBIE_JOB_TIMEOUT",
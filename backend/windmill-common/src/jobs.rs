@@ -13,8 +13,16 @@ pub const ENTRYPOINT_OVERRIDE: &str = "_ENTRYPOINT_OVERRIDE";
 
 pub const PREPROCESSOR_FAKE_ENTRYPOINT: &str = "__WM_PREPROCESSOR";
 
+/// Maximum position of a job in a chain of `on_success`/`on_failure` callbacks, stored on its
+/// `job_chain` row. The follow-up of a job at this depth is never pushed
+pub const MAX_JOB_CHAIN_DEPTH: i32 = 5;
+
+/// Value stored instead of the args listed in the `redact_args` of a run or of its script
+pub const REDACTED_ARG: &str = "***REDACTED***";
+
 use crate::{
     apps::AppScriptId,
+    cache,
     error::{self, to_anyhow, Error},
     flow_status::{FlowStatus, RestartedFrom},
     flows::{FlowNodeId, FlowValue, Retry},
@@ -116,6 +124,18 @@ pub struct QueuedJob {
     pub cache_ttl: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<i16>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub timeout_ms: Option<i32>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub mem_limit_mb: Option<i32>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub trace_context: Option<Json<HashMap<String, String>>>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub redact_args: Option<Vec<String>>,
 }
 
 impl QueuedJob {
@@ -323,6 +343,8 @@ pub enum JobPayload {
         path: String,
         dedicated_worker: Option<bool>,
         apply_preprocessor: bool,
+        /// flow_version to run instead of the latest one
+        version: Option<i64>,
     },
     RestartedFlow {
         completed_job_id: Uuid,
@@ -520,7 +542,7 @@ pub async fn get_payload_tag_from_prefixed_path(
             .map(|x| (x.tag, x.dedicated_worker))
             .unwrap_or_else(|| (None, None));
         (
-            JobPayload::Flow { path, dedicated_worker, apply_preprocessor: false },
+            JobPayload::Flow { path, dedicated_worker, apply_preprocessor: false, version: None },
             tag,
             None,
             None,
@@ -676,3 +698,225 @@ pub async fn get_logs_from_store(
     }
     return None;
 }
+
+/// Paths of the args to redact once the job completes: the ones of the run and, for script jobs,
+/// the ones of the script
+pub async fn get_redact_args<'c>(
+    e: impl sqlx::PgExecutor<'c>,
+    job_id: Uuid,
+    w_id: &str,
+) -> error::Result<Vec<String>> {
+    let paths = sqlx::query_scalar::<_, Vec<String>>(
+        "SELECT ARRAY(SELECT DISTINCT unnest(COALESCE(q.redact_args, '{}') || COALESCE(s.redact_args, '{}')))
+        FROM queue q LEFT JOIN script s
+            ON q.job_kind = 'script' AND s.hash = q.script_hash AND s.workspace_id = q.workspace_id
+        WHERE q.id = $1 AND q.workspace_id = $2",
+    )
+    .bind(job_id)
+    .bind(w_id)
+    .fetch_optional(e)
+    .await?;
+    Ok(paths.unwrap_or_default())
+}
+
+fn collect_strings(value: &serde_json::Value, strings: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => strings.push(s.clone()),
+        serde_json::Value::Array(values) => values.iter().for_each(|v| collect_strings(v, strings)),
+        serde_json::Value::Object(values) => {
+            values.values().for_each(|v| collect_strings(v, strings))
+        }
+        _ => (),
+    }
+}
+
+/// Replaces the args at the given top-level keys, or dot paths for nested fields, with
+/// [`REDACTED_ARG`]. Returns the string values that were redacted so that they can also be
+/// removed from the logs.
+pub fn redact_args(args: &mut HashMap<String, Box<RawValue>>, paths: &[String]) -> Vec<String> {
+    let mut redacted = vec![];
+    for path in paths {
+        let mut keys = path.split('.');
+        let Some(arg) = keys.next().and_then(|key| args.get_mut(key)) else {
+            continue;
+        };
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(arg.get()) else {
+            continue;
+        };
+        let mut target = Some(&mut value);
+        for key in keys {
+            target = target.and_then(|t| match t {
+                serde_json::Value::Object(o) => o.get_mut(key),
+                serde_json::Value::Array(a) => key.parse::<usize>().ok().and_then(|i| a.get_mut(i)),
+                _ => None,
+            });
+        }
+        if let Some(target) = target {
+            collect_strings(target, &mut redacted);
+            *target = serde_json::Value::String(REDACTED_ARG.to_string());
+            *arg = to_raw_value(&value);
+        }
+    }
+    redacted
+}
+
+fn replace_values(value: &mut serde_json::Value, values: &[String]) -> bool {
+    match value {
+        serde_json::Value::String(s) if values.contains(s) => {
+            *s = REDACTED_ARG.to_string();
+            true
+        }
+        serde_json::Value::Array(a) => a
+            .iter_mut()
+            .fold(false, |replaced, v| replace_values(v, values) | replaced),
+        serde_json::Value::Object(o) => o
+            .values_mut()
+            .fold(false, |replaced, v| replace_values(v, values) | replaced),
+        _ => false,
+    }
+}
+
+/// Replaces the string values of the args equal to one of `values` with [`REDACTED_ARG`]
+pub fn redact_arg_values(args: &mut HashMap<String, Box<RawValue>>, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    for arg in args.values_mut() {
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(arg.get()) else {
+            continue;
+        };
+        if replace_values(&mut value, values) {
+            *arg = to_raw_value(&value);
+        }
+    }
+}
+
+/// Paths of the args to redact once `job` completes, from its `redact_args` if they were pulled
+/// with it, in which case only the ones of its script are left to fetch (from the cache)
+async fn get_job_redact_args(
+    tx: &mut Transaction<'_, Postgres>,
+    job: &QueuedJob,
+) -> error::Result<Vec<String>> {
+    let Some(run_paths) = job.redact_args.as_ref() else {
+        return get_redact_args(&mut **tx, job.id, &job.workspace_id).await;
+    };
+    let mut paths = run_paths.clone();
+    if let Some(hash) = job.script_hash.filter(|_| job.job_kind == JobKind::Script) {
+        let script_paths = cache::script::fetch_redact_args(&mut **tx, hash).await?;
+        paths.extend(script_paths.iter().cloned());
+        paths.sort();
+        paths.dedup();
+    }
+    Ok(paths)
+}
+
+/// The args of a job redacted of its `redact_args`, along with the string values that were
+/// redacted, or `None` if there is nothing to redact. The args of a flow step are also redacted of
+/// the values redacted from the args of its flow, which the input transforms may pass on under
+/// other names. Must be called while the job and its flow are still in the queue.
+pub async fn redact_job_args(
+    tx: &mut Transaction<'_, Postgres>,
+    job: &QueuedJob,
+) -> error::Result<Option<(HashMap<String, Box<RawValue>>, Vec<String>)>> {
+    let paths = get_job_redact_args(tx, job).await?;
+    let Some(Json(args)) = job.args.as_ref().filter(|_| !paths.is_empty()) else {
+        return Ok(None);
+    };
+    let mut args = args.clone();
+    let mut redacted = redact_args(&mut args, &paths);
+
+    if let Some(parent_job) = job.parent_job {
+        // the parent of a step is a flow, which has no script to take `redact_args` from
+        let parent =
+            sqlx::query_as::<
+                _,
+                (
+                    Option<Json<HashMap<String, Box<RawValue>>>>,
+                    Option<Vec<String>>,
+                ),
+            >("SELECT args, redact_args FROM queue WHERE id = $1 AND workspace_id = $2")
+            .bind(parent_job)
+            .bind(&job.workspace_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+        if let Some((Some(Json(mut parent_args)), Some(parent_paths))) = parent {
+            let parent_redacted = redact_args(&mut parent_args, &parent_paths);
+            redact_arg_values(&mut args, &parent_redacted);
+            redacted.extend(parent_redacted);
+        }
+    }
+
+    Ok(Some((args, redacted)))
+}
+
+/// Redacts the args of a job moved to completed_job without going through `add_completed_job`,
+/// must be called before it is deleted from the queue
+pub async fn redact_completed_job_args(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: Uuid,
+    w_id: &str,
+) -> error::Result<()> {
+    let job =
+        sqlx::query_as::<_, QueuedJob>("SELECT * FROM queue WHERE id = $1 AND workspace_id = $2")
+            .bind(job_id)
+            .bind(w_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+    let Some(job) = job else {
+        return Ok(());
+    };
+    if let Some((args, _)) = redact_job_args(tx, &job).await? {
+        sqlx::query("UPDATE completed_job SET args = $1 WHERE id = $2 AND workspace_id = $3")
+            .bind(Json(args))
+            .bind(job_id)
+            .bind(w_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_arg_values() {
+        let mut args: HashMap<String, Box<RawValue>> = serde_json::from_str(
+            r#"{"id": "123-45-6789", "nested": {"ids": ["123-45-6789", "other"]}, "n": 3}"#,
+        )
+        .unwrap();
+        redact_arg_values(&mut args, &["123-45-6789".to_string()]);
+
+        assert_eq!(args["id"].get(), r#""***REDACTED***""#);
+        assert_eq!(
+            args["nested"].get(),
+            r#"{"ids":["***REDACTED***","other"]}"#
+        );
+        assert_eq!(args["n"].get(), "3");
+    }
+
+    #[test]
+    fn test_redact_args() {
+        let mut args: HashMap<String, Box<RawValue>> = serde_json::from_str(
+            r#"{"ssn": "123-45-6789", "user": {"name": "alice", "emails": ["a@b.c"]}, "n": 3}"#,
+        )
+        .unwrap();
+        let redacted = redact_args(
+            &mut args,
+            &[
+                "ssn".to_string(),
+                "user.emails".to_string(),
+                "missing.key".to_string(),
+            ],
+        );
+
+        assert_eq!(redacted, vec!["123-45-6789", "a@b.c"]);
+        assert_eq!(args["ssn"].get(), r#""***REDACTED***""#);
+        assert_eq!(
+            args["user"].get(),
+            r#"{"name":"alice","emails":"***REDACTED***"}"#
+        );
+        assert_eq!(args["n"].get(), "3");
+    }
+}
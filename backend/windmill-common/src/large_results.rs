@@ -0,0 +1,208 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Offloading of large job results to the instance object store.
+//!
+//! When the `large_result_threshold_kb` global setting is set and an object store is configured,
+//! the result of a top-level job larger than the threshold is written to
+//! `results/<w_id>/<job_id>.json` and the row only keeps a pointer to it:
+//! `{"__windmill_large_result": {"s3": path, "size": n}}`.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::{stream::BoxStream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+#[cfg(feature = "parquet")]
+use {
+    crate::{error::to_anyhow, s3_helpers::OBJECT_STORE_CACHE_SETTINGS, worker::to_raw_value},
+    futures::StreamExt,
+    object_store::{path::Path, ObjectStore},
+};
+
+pub const LARGE_RESULT_KEY: &str = "__windmill_large_result";
+
+lazy_static::lazy_static! {
+    /// results bigger than this many KB are offloaded to the object store, all results are kept
+    /// in the db if unset
+    pub static ref LARGE_RESULT_THRESHOLD_KB: Arc<RwLock<Option<usize>>> = Arc::new(RwLock::new(None));
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LargeResultRef {
+    pub s3: String,
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LargeResultPointer {
+    #[serde(rename = "__windmill_large_result")]
+    large_result: LargeResultRef,
+}
+
+pub fn large_result_path(w_id: &str, job_id: Uuid) -> String {
+    format!("results/{w_id}/{job_id}.json")
+}
+
+/// The location of the offloaded result if `result` is a pointer to one
+pub fn large_result_ref(result: &RawValue) -> Option<LargeResultRef> {
+    // regular results are not parsed
+    result
+        .get()
+        .trim_start()
+        .strip_prefix('{')?
+        .trim_start()
+        .starts_with(&format!("\"{LARGE_RESULT_KEY}\""))
+        .then(|| serde_json::from_str::<LargeResultPointer>(result.get()).ok())
+        .flatten()
+        .map(|pointer| pointer.large_result)
+}
+
+#[cfg(feature = "parquet")]
+async fn object_store() -> Result<Arc<dyn ObjectStore>> {
+    OBJECT_STORE_CACHE_SETTINGS
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| {
+            Error::BadConfig(
+                "Object store is required to read large results and is not configured".to_string(),
+            )
+        })
+}
+
+/// Writes `result` to the object store if it is over the threshold and returns the pointer to
+/// store in its place
+#[cfg(feature = "parquet")]
+pub async fn offload_large_result<T: Serialize>(
+    w_id: &str,
+    job_id: Uuid,
+    result: &T,
+) -> Result<Option<Box<RawValue>>> {
+    let Some(threshold_kb) = *LARGE_RESULT_THRESHOLD_KB.read().await else {
+        return Ok(None);
+    };
+    let Some(os) = OBJECT_STORE_CACHE_SETTINGS.read().await.clone() else {
+        return Ok(None);
+    };
+    let bytes = serde_json::to_vec(result).map_err(to_anyhow)?;
+    if bytes.len() <= threshold_kb * 1024 {
+        return Ok(None);
+    }
+
+    let path = large_result_path(w_id, job_id);
+    let size = bytes.len();
+    os.put(&Path::from(path.as_str()), bytes.into())
+        .await
+        .map_err(|e| Error::InternalErr(format!("Failed to put {path} to object store: {e}")))?;
+    Ok(Some(to_raw_value(&LargeResultPointer {
+        large_result: LargeResultRef { s3: path, size },
+    })))
+}
+
+#[cfg(not(feature = "parquet"))]
+pub async fn offload_large_result<T: Serialize>(
+    _w_id: &str,
+    _job_id: Uuid,
+    _result: &T,
+) -> Result<Option<Box<RawValue>>> {
+    Ok(None)
+}
+
+/// Streams an offloaded result back from the object store
+#[cfg(feature = "parquet")]
+pub async fn large_result_stream(path: &str) -> Result<BoxStream<'static, std::io::Result<Bytes>>> {
+    let stream = object_store()
+        .await?
+        .get(&Path::from(path))
+        .await
+        .map_err(|e| Error::InternalErr(format!("Failed to get {path} from object store: {e}")))?
+        .into_stream()
+        .map_err(std::io::Error::other);
+    Ok(stream.boxed())
+}
+
+#[cfg(not(feature = "parquet"))]
+pub async fn large_result_stream(
+    _path: &str,
+) -> Result<BoxStream<'static, std::io::Result<Bytes>>> {
+    Err(Error::BadConfig(
+        "Reading large results requires the object store support (parquet feature)".to_string(),
+    ))
+}
+
+/// Reads a whole offloaded result back from the object store
+pub async fn read_large_result(path: &str) -> Result<Box<RawValue>> {
+    let bytes = large_result_stream(path)
+        .await?
+        .try_fold(Vec::new(), |mut acc, chunk| async move {
+            acc.extend_from_slice(&chunk);
+            Ok(acc)
+        })
+        .await
+        .map_err(|e| Error::InternalErr(format!("Failed to read {path} from object store: {e}")))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| Error::InternalErr(format!("Large result {path} is not valid json: {e}")))
+}
+
+/// Deletes the offloaded results of deleted jobs, errors are only logged
+#[cfg(feature = "parquet")]
+pub async fn delete_large_results(paths: Vec<String>) {
+    if paths.is_empty() {
+        return;
+    }
+    let Some(os) = OBJECT_STORE_CACHE_SETTINGS.read().await.clone() else {
+        tracing::warn!(
+            "Could not delete {} large results as no object store is configured",
+            paths.len()
+        );
+        return;
+    };
+    for path in paths {
+        if let Err(e) = os.delete(&Path::from(path.as_str())).await {
+            tracing::error!("Failed to delete large result {path} from object store: {e}");
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+pub async fn delete_large_results(_paths: Vec<String>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_pointers_are_large_result_refs() {
+        let pointer = RawValue::from_string(
+            r#"{"__windmill_large_result": {"s3": "results/w/id.json", "size": 42}}"#.to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            large_result_ref(&pointer),
+            Some(LargeResultRef { s3: "results/w/id.json".to_string(), size: 42 })
+        );
+
+        for regular in [
+            r#"{"a": {"__windmill_large_result": {"s3": "x", "size": 1}}}"#,
+            r#"{"__windmill_large_result": {"s3": "x", "size": 1}, "other": 1}"#,
+            r#""__windmill_large_result""#,
+            r#"[1, 2]"#,
+        ] {
+            let value = RawValue::from_string(regular.to_string()).unwrap();
+            assert_eq!(large_result_ref(&value), None, "{regular}");
+        }
+    }
+}
@@ -34,6 +34,7 @@ pub mod job_metrics;
 #[cfg(feature = "parquet")]
 pub mod job_s3_helpers_ee;
 pub mod jobs;
+pub mod large_results;
 pub mod more_serde;
 pub mod oauth2;
 pub mod teams_ee;
@@ -108,6 +109,9 @@ lazy_static::lazy_static! {
 
     pub static ref JOB_RETENTION_SECS: Arc<RwLock<i64>> = Arc::new(RwLock::new(0));
 
+    /// 0 keeps the audit logs forever
+    pub static ref AUDIT_RETENTION_SECS: Arc<RwLock<i64>> = Arc::new(RwLock::new(0));
+
     pub static ref MONITOR_LOGS_ON_OBJECT_STORE: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
 
     pub static ref INSTANCE_NAME: String = rd_string(5);
@@ -12,6 +12,28 @@ use sqlx::FromRow;
 
 use crate::flows::Retry;
 
+/// What to do with the occurrences missed while a schedule was paused
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[sqlx(type_name = "SCHEDULE_CATCHUP_POLICY", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CatchupPolicy {
+    #[default]
+    Skip,
+    RunOnce,
+    RunAll,
+}
+
+/// What to do with an occurrence of a schedule that already has `max_concurrent_runs` runs
+/// queued or running when it is enqueued
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[sqlx(type_name = "SCHEDULE_CONFLICT_POLICY", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleConflictPolicy {
+    #[default]
+    Skip,
+    Delay,
+}
+
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
 pub struct Schedule {
     pub workspace_id: String,
@@ -58,6 +80,30 @@ pub struct Schedule {
     pub paused_until: Option<DateTime<chrono::Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cron_version: Option<String>,
+    #[serde(default)]
+    pub catchup_policy: CatchupPolicy,
+    /// path of a schedule whose last run must have succeeded for this schedule to run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on_schedule: Option<String>,
+    /// how recent the successful run of `depends_on_schedule` must be, unlimited if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependency_lookback_secs: Option<i32>,
+    /// upper bound of the random delay added to each run, only applied if the
+    /// `schedule_jitter_enabled` workspace setting is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter_seconds: Option<i32>,
+    /// maximum number of runs of the schedule queued or running at once, unlimited if unset.
+    /// `no_flow_overlap` sets it to 1. The skipped occurrences are recorded in `schedule_run_log`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_runs: Option<i32>,
+    #[serde(default)]
+    pub on_conflict: ScheduleConflictPolicy,
+    /// flow version to run instead of the latest one, only for flow schedules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_version_id: Option<i64>,
+    /// disabled by a pause of all the schedules under a path prefix, re-enabled by its resume
+    #[serde(default)]
+    pub paused_by_bulk_operation: bool,
 }
 
 impl Schedule {
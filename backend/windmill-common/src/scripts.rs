@@ -203,6 +203,42 @@ pub struct Script {
     pub has_preprocessor: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub on_behalf_of_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict_args: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_on_failure: Option<sqlx::types::Json<RetryPolicy>>,
+    /// args, or dot paths of nested fields, redacted from the jobs of the script once they complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redact_args: Option<Vec<String>>,
+}
+
+/// Retries of a failed job of the script when it is not run as a flow step
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u8,
+    pub backoff_ms: u32,
+    pub backoff_multiplier: f32,
+}
+
+impl RetryPolicy {
+    /// Delay before the next retry given the number of retries already done, `None` once all the
+    /// attempts have been used.
+    pub fn delay(&self, previous_attempts: u32) -> Option<std::time::Duration> {
+        if previous_attempts >= self.max_attempts as u32 {
+            return None;
+        }
+        let factor = (self.backoff_multiplier.max(1.0) as f64).powi(previous_attempts as i32);
+        let delay_ms = (self.backoff_ms as f64 * factor).min(u32::MAX as f64);
+        Some(std::time::Duration::from_millis(delay_ms as u64))
+    }
+}
+
+impl Hash for RetryPolicy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.max_attempts.hash(state);
+        self.backoff_ms.hash(state);
+        self.backoff_multiplier.to_bits().hash(state);
+    }
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -301,6 +337,9 @@ pub struct NewScript {
     pub codebase: Option<String>,
     pub has_preprocessor: Option<bool>,
     pub on_behalf_of_email: Option<String>,
+    pub strict_args: Option<bool>,
+    pub retry_on_failure: Option<RetryPolicy>,
+    pub redact_args: Option<Vec<String>>,
 }
 
 fn lock_deserialize<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
@@ -543,3 +582,18 @@ pub struct HubScript {
     pub schema: Box<serde_json::value::RawValue>,
     pub summary: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy { max_attempts: 3, backoff_ms: 1000, backoff_multiplier: 2.0 };
+        assert_eq!(policy.delay(0), Some(Duration::from_millis(1000)));
+        assert_eq!(policy.delay(1), Some(Duration::from_millis(2000)));
+        assert_eq!(policy.delay(2), Some(Duration::from_millis(4000)));
+        assert_eq!(policy.delay(3), None);
+    }
+}
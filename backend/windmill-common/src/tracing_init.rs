@@ -37,7 +37,10 @@ fn compact_layer<S>() -> Layer<S, format::DefaultFields, format::Format<format::
 }
 
 lazy_static::lazy_static! {
-    pub static ref JSON_FMT: bool = std::env::var("JSON_FMT").map(|x| x == "true").unwrap_or(false);
+    /// json logs with the timestamp, level, target, message and current span fields of each
+    /// event, set with `JSON_FMT=true` or `LOG_FORMAT=json`
+    pub static ref JSON_FMT: bool = std::env::var("JSON_FMT").is_ok_and(|x| x == "true")
+        || std::env::var("LOG_FORMAT").is_ok_and(|x| x.eq_ignore_ascii_case("json"));
 }
 
 pub const LOGS_SERVICE: &str = "logs/services/";
@@ -197,3 +200,17 @@ where
         }
     }
 }
+
+/// Sets the parent of a job span to the remote span the job was pushed from, from the W3C trace
+/// context (`traceparent`/`tracestate`) stored with the job
+#[cfg(feature = "otel")]
+pub fn set_span_parent_from_trace_context(
+    span: &tracing::Span,
+    trace_context: &std::collections::HashMap<String, String>,
+) {
+    use opentelemetry::propagation::TextMapPropagator;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let cx = opentelemetry_sdk::propagation::TraceContextPropagator::new().extract(trace_context);
+    span.set_parent(cx);
+}
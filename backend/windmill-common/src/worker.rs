@@ -21,6 +21,9 @@ use crate::{
     error, global_settings::CUSTOM_TAGS_SETTING, indexer::TantivyIndexerSettings, server::Smtp, DB,
 };
 
+pub const DEFAULT_CLOUD_TIMEOUT: u64 = 900;
+pub const DEFAULT_SELFHOSTED_TIMEOUT: u64 = 604800; // 7 days
+
 lazy_static::lazy_static! {
     pub static ref WORKER_GROUP: String = std::env::var("WORKER_GROUP").unwrap_or_else(|_| "default".to_string());
     pub static ref NO_LOGS: bool = std::env::var("NO_LOGS").ok().is_some_and(|x| x == "1" || x == "true");
@@ -66,6 +69,7 @@ lazy_static::lazy_static! {
         additional_python_paths: Default::default(),
         pip_local_dependencies: Default::default(),
         env_vars: Default::default(),
+        drain: Default::default(),
     }));
 
     pub static ref WORKER_PULL_QUERIES: Arc<RwLock<Vec<String>>> = Arc::new(RwLock::new(vec![]));
@@ -78,6 +82,12 @@ lazy_static::lazy_static! {
 
     pub static ref CLOUD_HOSTED: bool = std::env::var("CLOUD_HOSTED").is_ok();
 
+    pub static ref MAX_TIMEOUT: u64 = std::env::var("TIMEOUT")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or_else(|| if *CLOUD_HOSTED { DEFAULT_CLOUD_TIMEOUT } else { DEFAULT_SELFHOSTED_TIMEOUT });
+    pub static ref JOB_DEFAULT_TIMEOUT: Arc<RwLock<Option<i32>>> = Arc::new(RwLock::new(None));
+
     pub static ref CUSTOM_TAGS: Vec<String> = std::env::var("CUSTOM_TAGS")
         .ok()
         .map(|x| x.split(',').map(|x| x.to_string()).collect::<Vec<_>>()).unwrap_or_default();
@@ -103,6 +113,16 @@ lazy_static::lazy_static! {
     pub static ref DISABLE_FLOW_SCRIPT: bool = std::env::var("DISABLE_FLOW_SCRIPT").ok().is_some_and(|x| x == "1" || x == "true");
 }
 
+/// Timeout in seconds of the jobs without a custom timeout: the instance default job timeout, or
+/// the max timeout of the instance if it is not set or greater
+pub async fn default_job_timeout_secs() -> i32 {
+    let max_timeout = i32::try_from(*MAX_TIMEOUT).unwrap_or(i32::MAX);
+    match *JOB_DEFAULT_TIMEOUT.read().await {
+        Some(default_timeout) if default_timeout < max_timeout => default_timeout,
+        _ => max_timeout,
+    }
+}
+
 pub async fn make_suspended_pull_query(wc: &WorkerConfig) {
     if wc.worker_tags.len() == 0 {
         tracing::error!("Empty tags in worker tags, skipping");
@@ -128,7 +148,8 @@ pub async fn make_suspended_pull_query(wc: &WorkerConfig) {
             flow_status,  is_flow_step,  language,  suspend,  suspend_until,
             same_worker,  pre_run_error,  email,  visible_to_owner,  mem_peak,
             root_job,  leaf_jobs,  tag,  concurrent_limit,  concurrency_time_window_s,
-            timeout,  flow_step_id,  cache_ttl, priority,
+            timeout,  flow_step_id,  cache_ttl, priority, timeout_ms, mem_limit_mb, trace_context,
+            COALESCE(redact_args, '{{}}') AS redact_args,
             raw_code, raw_lock, raw_flow", wc.worker_tags.iter().map(|x| format!("'{x}'")).join(", "));
     let mut l = WORKER_SUSPENDED_PULL_QUERY.write().await;
     *l = query;
@@ -160,7 +181,8 @@ pub async fn make_pull_query(wc: &WorkerConfig) {
         flow_status,  is_flow_step,  language,  suspend,  suspend_until,
         same_worker,  pre_run_error,  email,  visible_to_owner,  mem_peak,
         root_job,  leaf_jobs,  tag,  concurrent_limit,  concurrency_time_window_s,
-        timeout,  flow_step_id,  cache_ttl, priority,
+        timeout,  flow_step_id,  cache_ttl, priority, timeout_ms, mem_limit_mb, trace_context,
+        COALESCE(redact_args, '{{}}') AS redact_args,
         raw_code, raw_lock, raw_flow", tags.tags.iter().map(|x| format!("'{x}'")).join(", "));
 
         queries.push(query);
@@ -867,6 +889,7 @@ pub async fn load_worker_config(
                 .map(|x| x.split(':').map(|x| x.to_string()).collect())
         }),
         env_vars: resolved_env_vars,
+        drain: config.drain.unwrap_or(false),
     })
 }
 
@@ -887,6 +910,8 @@ pub struct WorkerConfigOpt {
     pub pip_local_dependencies: Option<Vec<String>>,
     pub env_vars_static: Option<HashMap<String, String>>,
     pub env_vars_allowlist: Option<Vec<String>>,
+    /// set through /configs/drain, workers of the group stop pulling jobs and exit once idle
+    pub drain: Option<bool>,
 }
 
 impl Default for WorkerConfigOpt {
@@ -901,6 +926,7 @@ impl Default for WorkerConfigOpt {
             pip_local_dependencies: Default::default(),
             env_vars_static: Default::default(),
             env_vars_allowlist: Default::default(),
+            drain: Default::default(),
         }
     }
 }
@@ -915,6 +941,7 @@ pub struct WorkerConfig {
     pub additional_python_paths: Option<Vec<String>>,
     pub pip_local_dependencies: Option<Vec<String>>,
     pub env_vars: HashMap<String, String>,
+    pub drain: bool,
 }
 
 #[derive(PartialEq, Debug, Clone)]
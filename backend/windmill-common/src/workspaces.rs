@@ -1,4 +1,128 @@
+use std::{collections::HashMap, sync::Arc};
+
 use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    error::{Error, Result},
+    DB,
+};
+
+lazy_static::lazy_static! {
+    /// Job limits of the workspaces that override the instance defaults, reloaded on
+    /// `notify_workspace_job_limits_change`
+    pub static ref WORKSPACE_JOB_LIMITS: Arc<RwLock<HashMap<String, WorkspaceJobLimits>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, Copy, Debug, Default, PartialEq)]
+pub struct WorkspaceJobLimits {
+    pub default_timeout_secs_max: Option<i32>,
+    pub default_cache_ttl: Option<i32>,
+    pub max_mem_limit_mb: Option<i32>,
+}
+
+impl WorkspaceJobLimits {
+    pub async fn get(w_id: &str) -> Self {
+        WORKSPACE_JOB_LIMITS
+            .read()
+            .await
+            .get(w_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Clamps a timeout to the workspace max timeout, along with a warning if it was clamped. Jobs
+    /// without a timeout get the instance default timeout, clamped as well, once a max is set.
+    pub fn clamp_timeout(
+        &self,
+        timeout: Option<i32>,
+        instance_default_timeout: i32,
+    ) -> (Option<i32>, Option<String>) {
+        match (timeout, self.default_timeout_secs_max) {
+            (Some(timeout), Some(max)) if timeout > max => (
+                Some(max),
+                Some(format!(
+                    "WARNING: Custom job timeout of {timeout} seconds was greater than the workspace maximum timeout. It was clamped to {max} seconds\n"
+                )),
+            ),
+            (None, Some(max)) => (Some(instance_default_timeout.min(max)), None),
+            (timeout, _) => (timeout, None),
+        }
+    }
+
+    /// Checks the memory limit requested for a run against the workspace max memory limit
+    pub fn check_mem_limit(&self, mem_limit_mb: Option<i32>) -> Result<()> {
+        match (mem_limit_mb, self.max_mem_limit_mb) {
+            (Some(mem_limit_mb), _) if mem_limit_mb <= 0 => Err(Error::BadRequest(
+                "mem_limit_mb must be positive".to_string(),
+            )),
+            (Some(mem_limit_mb), Some(max)) if mem_limit_mb > max => Err(Error::BadRequest(
+                format!(
+                    "mem_limit_mb of {mem_limit_mb} MB is greater than the workspace maximum memory limit of {max} MB"
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn cache_ttl_or_default(&self, cache_ttl: Option<i32>) -> Option<i32> {
+        cache_ttl.or(self.default_cache_ttl)
+    }
+}
+
+/// Reloads the job limits of a workspace, or of all workspaces if `w_id` is None
+pub async fn reload_workspace_job_limits(db: &DB, w_id: Option<&str>) -> Result<()> {
+    let rows = sqlx::query_as::<_, (String, Option<i32>, Option<i32>, Option<i32>)>(
+        "SELECT workspace_id, default_timeout_secs_max, default_cache_ttl, max_mem_limit_mb
+        FROM workspace_settings
+        WHERE ($1::text IS NULL OR workspace_id = $1)
+            AND (default_timeout_secs_max IS NOT NULL OR default_cache_ttl IS NOT NULL
+                OR max_mem_limit_mb IS NOT NULL)",
+    )
+    .bind(w_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut limits = WORKSPACE_JOB_LIMITS.write().await;
+    match w_id {
+        Some(w_id) => {
+            limits.remove(w_id);
+        }
+        None => limits.clear(),
+    }
+    for (w_id, default_timeout_secs_max, default_cache_ttl, max_mem_limit_mb) in rows {
+        limits.insert(
+            w_id,
+            WorkspaceJobLimits { default_timeout_secs_max, default_cache_ttl, max_mem_limit_mb },
+        );
+    }
+    Ok(())
+}
+
+/// Upper bound of the lifetime of the OIDC tokens minted for a workspace
+pub const OIDC_MAX_TOKEN_TTL_SECS: u32 = 3600;
+pub const OIDC_DEFAULT_TOKEN_TTL_SECS: u32 = 900;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct WorkspaceOidcSettings {
+    /// Audiences the jobs of the workspace can request an OIDC token for
+    #[serde(default)]
+    pub allowed_audiences: Vec<String>,
+    pub token_ttl_secs: Option<u32>,
+}
+
+impl WorkspaceOidcSettings {
+    pub fn is_audience_allowed(&self, audience: &str) -> bool {
+        self.allowed_audiences.iter().any(|a| a == audience)
+    }
+
+    pub fn token_ttl_secs(&self) -> u32 {
+        self.token_ttl_secs
+            .unwrap_or(OIDC_DEFAULT_TOKEN_TTL_SECS)
+            .min(OIDC_MAX_TOKEN_TTL_SECS)
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct WorkspaceGitSyncSettings {
@@ -1,3 +1,4 @@
 pub mod completed_runs_ee;
 pub mod indexer_ee;
+pub mod scripts_search;
 pub mod service_logs_ee;
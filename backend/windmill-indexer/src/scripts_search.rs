@@ -0,0 +1,530 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, Pool, Postgres};
+use tantivy::{
+    collector::{DocSetCollector, TopDocs},
+    directory::MmapDirectory,
+    doc,
+    query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, TermQuery},
+    schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT},
+    snippet::SnippetGenerator,
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+use tokio::sync::Mutex;
+use windmill_common::{
+    error::{Error, Result},
+    worker::TMP_DIR,
+};
+
+/// Channel notified by the `notify_script_change` trigger on every script insert, archive or
+/// delete, with a `{"workspace_id", "path"}` payload
+pub const SCRIPT_CHANGE_CHANNEL: &str = "notify_script_change";
+
+const WRITER_NUM_THREADS: usize = 1;
+const WRITER_MEMORY_BUDGET: usize = 50_000_000;
+const SNIPPET_MAX_NUM_CHARS: usize = 200;
+const SYNC_BATCH_SIZE: usize = 500;
+
+#[derive(Clone, Copy)]
+struct ScriptFields {
+    key: Field,
+    hash: Field,
+    workspace_id: Field,
+    path: Field,
+    language: Field,
+    content: Field,
+}
+
+impl ScriptFields {
+    fn from_schema(schema: &Schema) -> Result<Self> {
+        let field = |name: &str| {
+            schema
+                .get_field(name)
+                .map_err(|e| Error::InternalErr(format!("Missing script index field {name}: {e}")))
+        };
+        Ok(Self {
+            key: field("key")?,
+            hash: field("hash")?,
+            workspace_id: field("workspace_id")?,
+            path: field("path")?,
+            language: field("language")?,
+            content: field("content")?,
+        })
+    }
+}
+
+fn build_schema() -> Schema {
+    let mut schema_builder = Schema::builder();
+    // `workspace_id/path`, used to replace the document of a script when a new version is deployed
+    schema_builder.add_text_field("key", STRING | STORED);
+    // hash of the indexed version, compared to the latest one to sync the index on startup
+    schema_builder.add_i64_field("hash", STORED);
+    schema_builder.add_text_field("workspace_id", STRING | STORED);
+    schema_builder.add_text_field("path", TEXT | STORED);
+    schema_builder.add_text_field("language", STRING | STORED);
+    schema_builder.add_text_field("content", TEXT | STORED);
+    schema_builder.build()
+}
+
+fn script_key(workspace_id: &str, path: &str) -> String {
+    format!("{workspace_id}/{path}")
+}
+
+#[derive(Clone)]
+pub struct ScriptIndexReader {
+    index: Index,
+    reader: IndexReader,
+    fields: ScriptFields,
+}
+
+#[derive(Clone)]
+pub struct ScriptIndexWriter {
+    writer: Arc<Mutex<IndexWriter>>,
+    reader: IndexReader,
+    fields: ScriptFields,
+}
+
+#[derive(Serialize)]
+pub struct ScriptSearchResult {
+    pub path: String,
+    pub language: String,
+    pub snippet: String,
+    pub score: f32,
+}
+
+#[derive(sqlx::FromRow)]
+struct IndexedScript {
+    workspace_id: String,
+    path: String,
+    hash: i64,
+    content: String,
+    language: String,
+}
+
+#[derive(sqlx::FromRow, Debug, PartialEq)]
+struct ScriptVersion {
+    workspace_id: String,
+    path: String,
+    hash: i64,
+}
+
+#[derive(Deserialize)]
+struct ScriptChange {
+    workspace_id: String,
+    path: String,
+}
+
+fn tantivy_err(e: impl std::fmt::Display) -> Error {
+    Error::InternalErr(format!("Script index error: {e}"))
+}
+
+/// Opens the script index persisted in `SCRIPT_INDEX_DIR` (defaults to
+/// `/tmp/windmill/script_index`) and brings it up to date with the scripts changed while the
+/// server was down. It is then kept up to date by `run_indexer`.
+pub async fn init_index(db: &Pool<Postgres>) -> Result<(ScriptIndexReader, ScriptIndexWriter)> {
+    let dir =
+        std::env::var("SCRIPT_INDEX_DIR").unwrap_or_else(|_| format!("{TMP_DIR}/script_index"));
+    let (index_reader, index_writer) = open_index(Path::new(&dir))?;
+    index_writer.sync(db).await?;
+    Ok((index_reader, index_writer))
+}
+
+fn open_index(dir: &Path) -> Result<(ScriptIndexReader, ScriptIndexWriter)> {
+    let schema = build_schema();
+    let fields = ScriptFields::from_schema(&schema)?;
+
+    std::fs::create_dir_all(dir)?;
+    let open = || Index::open_or_create(MmapDirectory::open(dir)?, schema.clone());
+    let index = match open() {
+        Ok(index) => index,
+        Err(e) => {
+            // e.g. the schema changed, the index is rebuilt from scratch by the sync
+            tracing::warn!(
+                "Could not open the script index in {}, recreating it: {e}",
+                dir.display()
+            );
+            std::fs::remove_dir_all(dir)?;
+            std::fs::create_dir_all(dir)?;
+            open().map_err(tantivy_err)?
+        }
+    };
+
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()
+        .map_err(tantivy_err)?;
+    let writer = index
+        .writer_with_num_threads(WRITER_NUM_THREADS, WRITER_MEMORY_BUDGET)
+        .map_err(tantivy_err)?;
+
+    let index_writer =
+        ScriptIndexWriter { writer: Arc::new(Mutex::new(writer)), reader: reader.clone(), fields };
+    Ok((ScriptIndexReader { index, reader, fields }, index_writer))
+}
+
+/// Returns the keys of the documents to remove from the index and the script versions to index,
+/// given the hashes currently indexed by key and the latest version of every script
+fn plan_sync(
+    indexed: &HashMap<String, i64>,
+    latest: Vec<ScriptVersion>,
+) -> (Vec<String>, Vec<ScriptVersion>) {
+    let mut stale = vec![];
+    let mut missing = vec![];
+    let mut latest_keys = HashSet::with_capacity(latest.len());
+    for version in latest {
+        let key = script_key(&version.workspace_id, &version.path);
+        match indexed.get(&key) {
+            Some(hash) if *hash == version.hash => {}
+            Some(_) => {
+                stale.push(key.clone());
+                missing.push(version);
+            }
+            None => missing.push(version),
+        }
+        latest_keys.insert(key);
+    }
+    stale.extend(
+        indexed
+            .keys()
+            .filter(|key| !latest_keys.contains(*key))
+            .cloned(),
+    );
+    (stale, missing)
+}
+
+impl ScriptIndexWriter {
+    fn add_script(&self, writer: &IndexWriter, script: IndexedScript) -> Result<()> {
+        let fields = self.fields;
+        writer
+            .add_document(doc!(
+                fields.key => script_key(&script.workspace_id, &script.path),
+                fields.hash => script.hash,
+                fields.workspace_id => script.workspace_id,
+                fields.path => script.path,
+                fields.language => script.language,
+                fields.content => script.content,
+            ))
+            .map_err(tantivy_err)?;
+        Ok(())
+    }
+
+    /// The hash of the indexed version of every script, by key
+    fn indexed_versions(&self) -> Result<HashMap<String, i64>> {
+        let fields = self.fields;
+        let searcher = self.reader.searcher();
+        let addresses = searcher
+            .search(&AllQuery, &DocSetCollector)
+            .map_err(tantivy_err)?;
+        let mut versions = HashMap::with_capacity(addresses.len());
+        for address in addresses {
+            let doc: TantivyDocument = searcher.doc(address).map_err(tantivy_err)?;
+            let key = doc.get_first(fields.key).and_then(|v| v.as_str());
+            let hash = doc.get_first(fields.hash).and_then(|v| v.as_i64());
+            if let (Some(key), Some(hash)) = (key, hash) {
+                versions.insert(key.to_string(), hash);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Only reindexes the scripts whose latest version differs from the indexed one and removes
+    /// the archived or deleted ones, instead of rebuilding the whole index
+    async fn sync(&self, db: &Pool<Postgres>) -> Result<()> {
+        let latest = sqlx::query_as::<_, ScriptVersion>(
+            "SELECT DISTINCT ON (workspace_id, path) workspace_id, path, hash
+            FROM script
+            WHERE archived = false AND deleted = false
+            ORDER BY workspace_id, path, created_at DESC",
+        )
+        .fetch_all(db)
+        .await?;
+
+        self.reader.reload().map_err(tantivy_err)?;
+        let (stale, missing) = plan_sync(&self.indexed_versions()?, latest);
+        if stale.is_empty() && missing.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let writer = self.writer.lock().await;
+            for key in stale.iter() {
+                writer.delete_term(Term::from_field_text(self.fields.key, key));
+            }
+        }
+        for batch in missing.chunks(SYNC_BATCH_SIZE) {
+            let (workspace_ids, hashes): (Vec<&str>, Vec<i64>) = batch
+                .iter()
+                .map(|v| (v.workspace_id.as_str(), v.hash))
+                .unzip();
+            let scripts = sqlx::query_as::<_, IndexedScript>(
+                "SELECT workspace_id, path, hash, content, language::text AS language
+                FROM script
+                WHERE (workspace_id, hash) IN (SELECT * FROM UNNEST($1::text[], $2::bigint[]))",
+            )
+            .bind(workspace_ids)
+            .bind(hashes)
+            .fetch_all(db)
+            .await?;
+            let writer = self.writer.lock().await;
+            for script in scripts {
+                self.add_script(&writer, script)?;
+            }
+        }
+        self.writer.lock().await.commit().map_err(tantivy_err)?;
+        tracing::info!(
+            "Synced the script index: {} scripts removed or outdated, {} indexed",
+            stale.len(),
+            missing.len()
+        );
+        Ok(())
+    }
+
+    /// Replaces the document of a script path by its latest non archived version, if any
+    async fn reindex_script(
+        &self,
+        db: &Pool<Postgres>,
+        workspace_id: &str,
+        path: &str,
+    ) -> Result<()> {
+        let script = sqlx::query_as::<_, IndexedScript>(
+            "SELECT workspace_id, path, hash, content, language::text AS language
+            FROM script
+            WHERE workspace_id = $1 AND path = $2 AND archived = false AND deleted = false
+            ORDER BY created_at DESC
+            LIMIT 1",
+        )
+        .bind(workspace_id)
+        .bind(path)
+        .fetch_optional(db)
+        .await?;
+
+        let mut writer = self.writer.lock().await;
+        writer.delete_term(Term::from_field_text(
+            self.fields.key,
+            &script_key(workspace_id, path),
+        ));
+        if let Some(script) = script {
+            self.add_script(&writer, script)?;
+        }
+        writer.commit().map_err(tantivy_err)?;
+        Ok(())
+    }
+}
+
+async fn listen_script_changes(db: &Pool<Postgres>) -> Result<PgListener> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen(SCRIPT_CHANGE_CHANNEL).await?;
+    Ok(listener)
+}
+
+/// Applies the script changes notified on `SCRIPT_CHANGE_CHANNEL` to the index. Notifications
+/// sent while the listener is disconnected are lost, so the index is synced on reconnection.
+pub async fn run_indexer(
+    db: Pool<Postgres>,
+    index_writer: ScriptIndexWriter,
+    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
+) -> Result<()> {
+    let mut listener: Option<PgListener> = None;
+    loop {
+        let Some(l) = listener.as_mut() else {
+            match listen_script_changes(&db).await {
+                Ok(l) => {
+                    listener = Some(l);
+                    if let Err(e) = index_writer.sync(&db).await {
+                        tracing::error!("Could not sync the script index: {e:#}");
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Could not listen to script changes, retrying in 5s: {e:#}");
+                    tokio::select! {
+                        _ = killpill_rx.recv() => return Ok(()),
+                        _ = tokio::time::sleep(std::time::Duration::from_secs(5)) => {}
+                    }
+                }
+            }
+            continue;
+        };
+
+        let mut reconnect = false;
+        tokio::select! {
+            _ = killpill_rx.recv() => {
+                tracing::info!("Received killpill, stopping the script indexer");
+                return Ok(());
+            },
+            notification = l.try_recv() => match notification {
+                Ok(Some(notification)) => {
+                    match serde_json::from_str::<ScriptChange>(notification.payload()) {
+                        Ok(change) => {
+                            if let Err(e) = index_writer
+                                .reindex_script(&db, &change.workspace_id, &change.path)
+                                .await
+                            {
+                                tracing::error!(
+                                    "Could not index script {} in {}: {e:#}",
+                                    change.path,
+                                    change.workspace_id
+                                );
+                            }
+                        }
+                        Err(e) => tracing::error!("Invalid script change notification: {e:#}"),
+                    }
+                }
+                Ok(None) => {
+                    tracing::warn!("Script change listener disconnected, reconnecting");
+                    reconnect = true;
+                }
+                Err(e) => {
+                    tracing::error!("Error receiving script change notification: {e:#}");
+                    reconnect = true;
+                }
+            },
+        }
+        if reconnect {
+            listener = None;
+        }
+    }
+}
+
+impl ScriptIndexReader {
+    pub fn search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        language: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<ScriptSearchResult>> {
+        let fields = self.fields;
+        let query_parser = QueryParser::for_index(&self.index, vec![fields.content, fields.path]);
+        let user_query = query_parser
+            .parse_query(query)
+            .map_err(|e| Error::BadRequest(format!("Invalid search query: {e}")))?;
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(fields.workspace_id, workspace_id),
+                    IndexRecordOption::Basic,
+                )),
+            ),
+            (Occur::Must, user_query.box_clone()),
+        ];
+        if let Some(language) = language {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(fields.language, language),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        let search_query = BooleanQuery::new(clauses);
+
+        let searcher = self.reader.searcher();
+        let top_docs = searcher
+            .search(&search_query, &TopDocs::with_limit(limit))
+            .map_err(tantivy_err)?;
+
+        let mut snippet_generator =
+            SnippetGenerator::create(&searcher, &*user_query, fields.content)
+                .map_err(tantivy_err)?;
+        snippet_generator.set_max_num_chars(SNIPPET_MAX_NUM_CHARS);
+
+        let mut results = Vec::with_capacity(top_docs.len());
+        for (score, doc_address) in top_docs {
+            let doc: TantivyDocument = searcher.doc(doc_address).map_err(tantivy_err)?;
+            let text = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            results.push(ScriptSearchResult {
+                path: text(fields.path),
+                language: text(fields.language),
+                snippet: snippet_generator
+                    .snippet_from_doc(&doc)
+                    .fragment()
+                    .to_string(),
+                score,
+            });
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(path: &str, hash: i64) -> ScriptVersion {
+        ScriptVersion { workspace_id: "w".to_string(), path: path.to_string(), hash }
+    }
+
+    #[test]
+    fn test_plan_sync_only_touches_changed_scripts() {
+        let indexed = HashMap::from([
+            ("w/f/unchanged".to_string(), 1),
+            ("w/f/edited".to_string(), 2),
+            ("w/f/archived".to_string(), 3),
+        ]);
+        let latest = vec![
+            version("f/unchanged", 1),
+            version("f/edited", 20),
+            version("f/new", 4),
+        ];
+
+        let (mut stale, missing) = plan_sync(&indexed, latest);
+        stale.sort();
+        assert_eq!(
+            stale,
+            vec!["w/f/archived".to_string(), "w/f/edited".to_string()]
+        );
+        assert_eq!(missing, vec![version("f/edited", 20), version("f/new", 4)]);
+    }
+
+    #[tokio::test]
+    async fn test_index_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let (_, index_writer) = open_index(dir.path()).unwrap();
+            let writer = index_writer.writer.lock().await;
+            index_writer
+                .add_script(
+                    &writer,
+                    IndexedScript {
+                        workspace_id: "w".to_string(),
+                        path: "f/billing/invoice".to_string(),
+                        hash: 42,
+                        content: "export function main() { return computeInvoiceTotal() }"
+                            .to_string(),
+                        language: "deno".to_string(),
+                    },
+                )
+                .unwrap();
+            drop(writer);
+            index_writer.writer.lock().await.commit().unwrap();
+        }
+
+        let (index_reader, index_writer) = open_index(dir.path()).unwrap();
+        assert_eq!(
+            index_writer.indexed_versions().unwrap(),
+            HashMap::from([("w/f/billing/invoice".to_string(), 42)])
+        );
+        let results = index_reader
+            .search("w", "computeInvoiceTotal", None, 10)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "f/billing/invoice");
+        assert!(index_reader
+            .search("other", "computeInvoiceTotal", None, 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -40,18 +40,22 @@ use windmill_common::{
         add_virtual_items_if_necessary, FlowModule, FlowModuleValue, FlowValue, InputTransform,
     },
     jobs::{
-        get_payload_tag_from_prefixed_path, CompletedJob, JobKind, JobPayload, QueuedJob, RawCode,
-        ENTRYPOINT_OVERRIDE, PREPROCESSOR_FAKE_ENTRYPOINT,
+        get_payload_tag_from_prefixed_path, redact_job_args, script_hash_to_tag_and_limits,
+        CompletedJob, JobKind, JobPayload, QueuedJob, RawCode, ENTRYPOINT_OVERRIDE,
+        MAX_JOB_CHAIN_DEPTH, PREPROCESSOR_FAKE_ENTRYPOINT, REDACTED_ARG,
     },
+    large_results::offload_large_result,
     schedule::Schedule,
-    scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang},
+    scripts::{get_full_hub_script_by_path, RetryPolicy, ScriptHash, ScriptLang},
     users::{SUPERADMIN_NOTIFICATION_EMAIL, SUPERADMIN_SECRET_EMAIL},
     utils::{not_found_if_none, report_critical_error, StripPath, WarnAfterExt},
     worker::{
-        to_raw_value, CLOUD_HOSTED, DEFAULT_TAGS_PER_WORKSPACE, DEFAULT_TAGS_WORKSPACES,
-        DISABLE_FLOW_SCRIPT, MIN_VERSION_IS_AT_LEAST_1_427, MIN_VERSION_IS_AT_LEAST_1_432,
-        MIN_VERSION_IS_AT_LEAST_1_440, NO_LOGS, WORKER_PULL_QUERIES, WORKER_SUSPENDED_PULL_QUERY,
+        default_job_timeout_secs, to_raw_value, CLOUD_HOSTED, DEFAULT_TAGS_PER_WORKSPACE,
+        DEFAULT_TAGS_WORKSPACES, DISABLE_FLOW_SCRIPT, MIN_VERSION_IS_AT_LEAST_1_427,
+        MIN_VERSION_IS_AT_LEAST_1_432, MIN_VERSION_IS_AT_LEAST_1_440, NO_LOGS, WORKER_PULL_QUERIES,
+        WORKER_SUSPENDED_PULL_QUERY,
     },
+    workspaces::WorkspaceJobLimits,
     DB, METRICS_ENABLED,
 };
 
@@ -64,7 +68,7 @@ use windmill_common::BASE_URL;
 #[cfg(feature = "cloud")]
 use windmill_common::users::SUPERADMIN_SYNC_EMAIL;
 
-use crate::schedule::{get_schedule_opt, push_scheduled_job};
+use crate::schedule::{get_schedule_opt, push_dependent_schedules, push_scheduled_job_after};
 
 #[cfg(feature = "prometheus")]
 lazy_static::lazy_static! {
@@ -512,6 +516,290 @@ lazy_static::lazy_static! {
     pub static ref GLOBAL_ERROR_HANDLER_PATH_IN_ADMINS_WORKSPACE: Option<String> = std::env::var("GLOBAL_ERROR_HANDLER_PATH_IN_ADMINS_WORKSPACE").ok();
 }
 
+#[derive(Default)]
+struct ScriptJobRetry {
+    /// the job is itself a retry of a failed script job
+    is_retry: bool,
+    /// a retry of the job was pushed, error handlers only run once all attempts have failed
+    pushed: bool,
+}
+
+/// Re-pushes a failed script job according to the `retry_on_failure` policy of its script.
+/// Every retry has the original job as parent and its attempt number is kept in `script_job_retry`.
+async fn retry_failed_script_job(db: &Pool<Postgres>, queued_job: &QueuedJob) -> ScriptJobRetry {
+    if queued_job.job_kind != JobKind::Script || queued_job.is_flow_step {
+        return ScriptJobRetry::default();
+    }
+    let (Some(hash), Some(path)) = (queued_job.script_hash, queued_job.script_path.as_ref()) else {
+        return ScriptJobRetry::default();
+    };
+
+    let previous = sqlx::query_as::<_, (Uuid, i32)>(
+        "SELECT original_job_id, attempt FROM script_job_retry WHERE job_id = $1",
+    )
+    .bind(queued_job.id)
+    .fetch_optional(db)
+    .await;
+    let (original_job_id, attempt) = match previous {
+        Ok(previous) => previous.unwrap_or((queued_job.id, 0)),
+        Err(e) => {
+            tracing::error!(
+                "Could not fetch retry status of job {}: {e:#}",
+                queued_job.id
+            );
+            return ScriptJobRetry::default();
+        }
+    };
+
+    let pushed =
+        match push_script_job_retry(db, queued_job, hash, path, original_job_id, attempt).await {
+            Ok(pushed) => pushed,
+            Err(e) => {
+                tracing::error!(
+                    "Could not push retry of failed job {}: {e:#}",
+                    queued_job.id
+                );
+                false
+            }
+        };
+    ScriptJobRetry { is_retry: attempt > 0, pushed }
+}
+
+async fn push_script_job_retry(
+    db: &Pool<Postgres>,
+    queued_job: &QueuedJob,
+    hash: ScriptHash,
+    path: &str,
+    original_job_id: Uuid,
+    attempt: i32,
+) -> Result<bool, Error> {
+    let w_id = &queued_job.workspace_id;
+    let policy = sqlx::query_scalar::<_, Option<Json<RetryPolicy>>>(
+        "SELECT retry_on_failure FROM script WHERE hash = $1 AND workspace_id = $2",
+    )
+    .bind(hash.0)
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    let Some(delay) = policy.and_then(|Json(policy)| policy.delay(attempt as u32)) else {
+        return Ok(false);
+    };
+    let scheduled_for = chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+
+    let mut tx = db.begin().await?;
+    let (
+        _tag,
+        custom_concurrency_key,
+        concurrent_limit,
+        concurrency_time_window_s,
+        cache_ttl,
+        language,
+        dedicated_worker,
+        priority,
+        _delete_after_use,
+        _timeout,
+        _on_behalf_of_email,
+        _created_by,
+    ) = script_hash_to_tag_and_limits(&hash, &mut tx, w_id).await?;
+
+    let args = queued_job
+        .args
+        .as_ref()
+        .map(|args| args.0.clone())
+        .unwrap_or_default();
+    let (retry_job_id, mut tx) = push(
+        db,
+        PushIsolationLevel::Transaction(tx),
+        w_id,
+        JobPayload::ScriptHash {
+            hash,
+            path: path.to_string(),
+            custom_concurrency_key,
+            concurrent_limit,
+            concurrency_time_window_s,
+            cache_ttl,
+            language,
+            dedicated_worker,
+            priority,
+            apply_preprocessor: false,
+        },
+        PushArgs::from(&args),
+        &queued_job.created_by,
+        &queued_job.email,
+        queued_job.permissioned_as.clone(),
+        Some(scheduled_for),
+        // the schedule is only advanced by the original job
+        None,
+        Some(original_job_id),
+        Some(queued_job.root_job.unwrap_or(original_job_id)),
+        None,
+        false,
+        false,
+        None,
+        queued_job.visible_to_owner,
+        Some(queued_job.tag.clone()),
+        queued_job.timeout,
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO script_job_retry (job_id, workspace_id, original_job_id, attempt)
+        VALUES ($1, $2, $3, $4)",
+    )
+    .bind(retry_job_id)
+    .bind(w_id)
+    .bind(original_job_id)
+    .bind(attempt + 1)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "Job {} of script {path} failed, pushed retry {} ({}) scheduled in {}ms",
+        queued_job.id,
+        attempt + 1,
+        retry_job_id,
+        delay.as_millis()
+    );
+    Ok(true)
+}
+
+#[derive(FromRow)]
+struct JobChain {
+    job_id: Uuid,
+    on_success_path: Option<String>,
+    on_failure_path: Option<String>,
+    depth: i32,
+}
+
+/// Pushes the `on_success` or `on_failure` callback the job was run with, as the same user and
+/// with the outcome of the job as args. Retries of a failed script job share the callbacks of the
+/// original job and the follow-up is recorded on its `job_chain` row. The follow-up gets its own
+/// row, one level deeper, without callbacks.
+async fn push_job_chain_follow_up<T: Serialize + Send + Sync>(
+    db: &Pool<Postgres>,
+    queued_job: &QueuedJob,
+    success: bool,
+    result: Json<&T>,
+) -> Result<Option<Uuid>, Error> {
+    let w_id = &queued_job.workspace_id;
+    let chain = sqlx::query_as::<_, JobChain>(
+        "SELECT job_id, on_success_path, on_failure_path, depth FROM job_chain
+        WHERE workspace_id = $2 AND follow_up_job_id IS NULL
+            AND job_id = COALESCE((SELECT original_job_id FROM script_job_retry WHERE job_id = $1), $1)",
+    )
+    .bind(queued_job.id)
+    .bind(w_id)
+    .fetch_optional(db)
+    .await?;
+    let Some(chain) = chain else {
+        return Ok(None);
+    };
+    let path = if success {
+        chain.on_success_path
+    } else {
+        chain.on_failure_path
+    };
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    if chain.depth >= MAX_JOB_CHAIN_DEPTH {
+        tracing::warn!(
+            "Job {} is at the maximum chain depth of {MAX_JOB_CHAIN_DEPTH}, not pushing its follow-up {path}",
+            queued_job.id
+        );
+        return Ok(None);
+    }
+
+    let (payload, tag, _on_behalf_of) = get_payload_tag_from_prefixed_path(&path, db, w_id).await?;
+
+    let mut args = HashMap::new();
+    args.insert("previous_result".to_string(), to_raw_value(&result.0));
+    args.insert("previous_job_id".to_string(), to_raw_value(&queued_job.id));
+    args.insert("previous_success".to_string(), to_raw_value(&success));
+
+    let (follow_up_job_id, mut tx) = push(
+        db,
+        PushIsolationLevel::IsolatedRoot(db.clone()),
+        w_id,
+        payload,
+        PushArgs::from(&args),
+        &queued_job.created_by,
+        &queued_job.email,
+        queued_job.permissioned_as.clone(),
+        None,
+        None,
+        None,
+        None,
+        None,
+        false,
+        false,
+        None,
+        queued_job.visible_to_owner,
+        tag,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    sqlx::query("UPDATE job_chain SET follow_up_job_id = $1 WHERE job_id = $2")
+        .bind(follow_up_job_id)
+        .bind(chain.job_id)
+        .execute(&mut *tx)
+        .await?;
+    // the jobs the follow-up runs with callbacks continue the chain
+    sqlx::query("INSERT INTO job_chain (job_id, workspace_id, depth) VALUES ($1, $2, $3)")
+        .bind(follow_up_job_id)
+        .bind(w_id)
+        .bind(chain.depth + 1)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "Job {} completed (success: {success}), pushed its follow-up {path} as job {follow_up_job_id}",
+        queued_job.id
+    );
+    Ok(Some(follow_up_job_id))
+}
+
+/// Redacted values shorter than this are kept in the logs, replacing them would garble unrelated
+/// lines
+const MIN_REDACTED_LOG_VALUE_LEN: usize = 4;
+
+async fn redact_job_logs(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: Uuid,
+    redacted_values: &[String],
+) -> Result<(), Error> {
+    for value in redacted_values
+        .iter()
+        .filter(|v| v.len() >= MIN_REDACTED_LOG_VALUE_LEN)
+    {
+        sqlx::query("UPDATE job_logs SET logs = replace(logs, $1, $2) WHERE job_id = $3")
+            .bind(value)
+            .bind(REDACTED_ARG)
+            .bind(job_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Result written to `completed_job`, replaced by a pointer if it was offloaded to the object store
+#[derive(Serialize)]
+#[serde(untagged)]
+enum StoredResult<'a, T> {
+    Inline(&'a T),
+    Offloaded(Box<RawValue>),
+}
+
 pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
     db: &Pool<Postgres>,
     queued_job: &QueuedJob,
@@ -533,6 +821,17 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
         ));
     }
 
+    // the results of flow steps are read back by their flow and always stay in the db
+    let offloaded_result = if queued_job.parent_job.is_none() && !queued_job.is_flow_step {
+        offload_large_result(&queued_job.workspace_id, queued_job.id, &result.0).await?
+    } else {
+        None
+    };
+    let stored_result = Json(match offloaded_result {
+        Some(pointer) => StoredResult::Offloaded(pointer),
+        None => StoredResult::Inline(result.0),
+    });
+
     let _job_id = queued_job.id;
     let (opt_uuid, _duration, _skip_downstream_error_handlers) = (|| async {
         let mut tx = db.begin().await?;
@@ -572,6 +871,20 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
         };
 
         let mem_peak = mem_peak.max(queued_job.mem_peak.unwrap_or(0));
+
+        let mut redacted_values = vec![];
+        let redacted_args = match redact_job_args(&mut tx, queued_job).await? {
+            Some((args, values)) => {
+                redacted_values = values;
+                Some(Json(args))
+            }
+            None => None,
+        };
+        let args = if redacted_args.is_some() {
+            &redacted_args
+        } else {
+            &queued_job.args
+        };
         // add_time!(bench, "add_completed_job query START");
 
         let _duration =  sqlx::query_scalar!(
@@ -619,8 +932,8 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
             success,
             queued_job.script_hash.map(|x| x.0),
             queued_job.script_path,
-            &queued_job.args as &Option<Json<HashMap<String, Box<RawValue>>>>,
-            result as Json<&T>,
+            args as &Option<Json<HashMap<String, Box<RawValue>>>>,
+            &stored_result as &Json<StoredResult<T>>,
             raw_code,
             raw_lock,
             canceled_by.is_some(),
@@ -645,6 +958,7 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
         .await
         .map_err(|e| Error::InternalErr(format!("Could not add completed job {job_id}: {e:#}")))?;
 
+        redact_job_logs(&mut tx, job_id, &redacted_values).await?;
 
         if !queued_job.is_flow_step {
             if _duration > 500
@@ -890,6 +1204,35 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
         return Ok(job_id);
     }
 
+    let script_retry = if !success && canceled_by.is_none() {
+        retry_failed_script_job(db, queued_job).await
+    } else {
+        ScriptJobRetry::default()
+    };
+
+    // the schedules depending on the schedule of the job wait for one of its runs to succeed
+    if success && queued_job.parent_job.is_none() {
+        if let Some(schedule_path) = queued_job.schedule_path.as_deref() {
+            if let Err(e) =
+                push_dependent_schedules(db, &queued_job.workspace_id, schedule_path).await
+            {
+                tracing::error!(
+                    "Could not push the schedules depending on schedule {schedule_path}: {e:#}"
+                );
+            }
+        }
+    }
+
+    // the callbacks of a failed job only run once all its retries have failed
+    if !queued_job.is_flow_step && canceled_by.is_none() && !script_retry.pushed {
+        if let Err(e) = push_job_chain_follow_up(db, queued_job, success, Json(result.0)).await {
+            tracing::error!(
+                "Could not push the follow-up of job {}: {e:#}",
+                queued_job.id
+            );
+        }
+    }
+
     #[cfg(feature = "cloud")]
     if *CLOUD_HOSTED && !queued_job.is_flow() && _duration > 1000 {
         let additional_usage = _duration / 1000;
@@ -980,7 +1323,8 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
             && (matches!(queued_job.job_kind, JobKind::Script)
                 || matches!(queued_job.job_kind, JobKind::Flow)
                     && !has_failure_module(db, queued_job).await)
-            && queued_job.parent_job.is_none()
+            && (queued_job.parent_job.is_none() || script_retry.is_retry)
+            && !script_retry.pushed
         {
             let result = serde_json::from_str(
                 &serde_json::to_string(result.0).unwrap_or_else(|_| "{}".to_string()),
@@ -1270,6 +1614,8 @@ pub async fn send_error_to_workspace_handler<'a, 'c, T: Serialize + Send + Sync>
     Ok(())
 }
 
+/// Pushes the next job of the schedule of `job`, `job` not counting towards the schedule's max
+/// concurrent runs
 pub async fn handle_maybe_scheduled_job<'c>(
     db: &Pool<Postgres>,
     job: &QueuedJob,
@@ -1287,7 +1633,7 @@ pub async fn handle_maybe_scheduled_job<'c>(
         let push_next_job_future = (|| {
             tokio::time::timeout(std::time::Duration::from_secs(5), async {
                 let mut tx = db.begin().await?;
-                tx = push_scheduled_job(db, tx, &schedule, None).await?;
+                tx = push_scheduled_job_after(db, tx, &schedule, None, Some(job.id)).await?;
                 tx.commit().await?;
                 Ok::<(), Error>(())
             })
@@ -2709,6 +3055,12 @@ impl<'c> Serialize for PushArgs<'c> {
     }
 }
 
+tokio::task_local! {
+    /// W3C trace context (`traceparent`/`tracestate`) of the api request pushing jobs, stored
+    /// with the jobs it pushes for the worker to continue the trace
+    pub static PUSH_TRACE_CONTEXT: Option<HashMap<String, String>>;
+}
+
 impl PushArgsOwned {
     pub fn empty() -> Self {
         PushArgsOwned { extra: None, args: HashMap::new() }
@@ -3299,6 +3651,7 @@ pub async fn push<'c, 'd>(
                     sleep: None,
                     cache_ttl: None,
                     timeout: None,
+                    step_timeout_ms: None,
                     priority: None,
                     delete_after_use: None,
                     continue_on_error: None,
@@ -3333,18 +3686,35 @@ pub async fn push<'c, 'd>(
                 priority,
             )
         }
-        JobPayload::Flow { path, dedicated_worker, apply_preprocessor } => {
+        JobPayload::Flow { path, dedicated_worker, apply_preprocessor, version } => {
             let mut ntx = tx.into_tx().await?;
-            // Fetch the latest version of the flow.
-            let version = sqlx::query_scalar!(
-                "SELECT flow.versions[array_upper(flow.versions, 1)] AS \"version!: i64\"
+            let version = if let Some(version) = version {
+                // The pinned version may have been deleted or pruned since it was chosen.
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT id FROM flow_version WHERE id = $1 AND path = $2 AND workspace_id = $3",
+                )
+                .bind(version)
+                .bind(&path)
+                .bind(&workspace_id)
+                .fetch_optional(&mut *ntx)
+                .await?
+                .ok_or_else(|| {
+                    Error::NotFound(format!(
+                        "version {version} of flow at path {path} not found, it may have been deleted"
+                    ))
+                })?
+            } else {
+                // Fetch the latest version of the flow.
+                sqlx::query_scalar!(
+                    "SELECT flow.versions[array_upper(flow.versions, 1)] AS \"version!: i64\"
                 FROM flow WHERE path = $1 AND workspace_id = $2",
-                &path,
-                &workspace_id
-            )
-            .fetch_optional(&mut *ntx)
-            .await?
-            .ok_or_else(|| Error::InternalErr(format!("not found flow at path {:?}", path)))?;
+                    &path,
+                    &workspace_id
+                )
+                .fetch_optional(&mut *ntx)
+                .await?
+                .ok_or_else(|| Error::InternalErr(format!("not found flow at path {:?}", path)))?
+            };
 
             // Do not use the lite version unless all workers are updated.
             let data = if *DISABLE_FLOW_SCRIPT
@@ -3532,6 +3902,16 @@ pub async fn push<'c, 'd>(
         ),
     };
 
+    let workspace_job_limits = WorkspaceJobLimits::get(workspace_id).await;
+    // scripts that do not set a cache ttl inherit the default of their workspace
+    let cache_ttl = if job_kind == JobKind::Script && !is_flow_step {
+        workspace_job_limits.cache_ttl_or_default(cache_ttl)
+    } else {
+        cache_ttl
+    };
+    let (custom_timeout, timeout_warning) =
+        workspace_job_limits.clamp_timeout(custom_timeout, default_job_timeout_secs().await);
+
     let final_priority: Option<i16>;
     #[cfg(not(feature = "enterprise"))]
     {
@@ -3725,8 +4105,8 @@ pub async fn push<'c, 'd>(
                 script_hash, script_path, raw_code, raw_lock, args, job_kind, schedule_path, raw_flow, \
                 flow_status, is_flow_step, language, started_at, same_worker, pre_run_error, email, \
                 visible_to_owner, root_job, tag, concurrent_limit, concurrency_time_window_s, timeout, \
-                flow_step_id, cache_ttl, priority, last_ping)
-            VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, now()), $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, CASE WHEN $3 THEN now() END, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, NULL) \
+                flow_step_id, cache_ttl, priority, last_ping, trace_context)
+            VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, now()), $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, CASE WHEN $3 THEN now() END, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, NULL, $31) \
          RETURNING id",
         workspace_id,
         job_id,
@@ -3758,6 +4138,7 @@ pub async fn push<'c, 'd>(
         flow_step_id,
         cache_ttl,
         final_priority,
+        PUSH_TRACE_CONTEXT.try_with(Clone::clone).ok().flatten().map(Json) as Option<Json<HashMap<String, String>>>,
     )
     .fetch_one(&mut *tx)
     .warn_after_seconds(1)
@@ -3765,6 +4146,19 @@ pub async fn push<'c, 'd>(
     .map_err(|e| Error::InternalErr(format!("Could not insert into queue {job_id} with tag {tag}, schedule_path {schedule_path:?}, script_path: {script_path:?}, email {email}, workspace_id {workspace_id}: {e:#}")))?;
 
     tracing::debug!("Pushed {job_id}");
+
+    if let Some(timeout_warning) = timeout_warning {
+        tracing::warn!("{timeout_warning}");
+        sqlx::query(
+            "INSERT INTO job_logs (logs, job_id, workspace_id) VALUES ($1, $2, $3) ON CONFLICT (job_id) DO UPDATE SET logs = concat(job_logs.logs, $1::text)",
+        )
+        .bind(&timeout_warning)
+        .bind(job_id)
+        .bind(workspace_id)
+        .execute(&mut *tx)
+        .warn_after_seconds(1)
+        .await?;
+    }
     // TODO: technically the job isn't queued yet, as the transaction can be rolled back. Should be solved when moving these metrics to the queue abstraction.
     #[cfg(feature = "prometheus")]
     if METRICS_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
@@ -9,6 +9,8 @@
 use crate::push;
 use crate::PushIsolationLevel;
 use anyhow::Context;
+use rand::Rng;
+use serde::Serialize;
 use sqlx::{query_scalar, PgExecutor, Postgres, Transaction};
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -16,7 +18,8 @@ use windmill_common::db::Authed;
 use windmill_common::ee::LICENSE_KEY_VALID;
 use windmill_common::flows::Retry;
 use windmill_common::jobs::JobPayload;
-use windmill_common::schedule::schedule_to_user;
+use windmill_common::schedule::{schedule_to_user, CatchupPolicy, ScheduleConflictPolicy};
+use windmill_common::worker::to_raw_value;
 use windmill_common::DB;
 use windmill_common::{
     error::{self, Result},
@@ -25,11 +28,179 @@ use windmill_common::{
     utils::{now_from_db, ScheduleType, StripPath},
 };
 
+lazy_static::lazy_static! {
+    pub static ref SCHEDULE_CATCHUP_MAX_RUNS: usize = std::env::var("SCHEDULE_CATCHUP_MAX_RUNS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(100);
+}
+
+#[cfg(feature = "prometheus")]
+lazy_static::lazy_static! {
+    static ref SCHEDULES_SKIPPED_DUE_TO_MAX_CONCURRENT_RUNS: prometheus::IntCounter = prometheus::register_int_counter!(
+        "schedules_skipped_due_to_max_concurrent_runs_total",
+        "Total number of schedule runs skipped because the schedule had reached its max concurrent runs."
+    )
+    .unwrap();
+}
+
+/// `skipped_reason` of the occurrences skipped because of `max_concurrent_runs`, they do not
+/// block the schedules depending on the skipping one
+pub const MAX_CONCURRENT_RUNS_REACHED: &str = "max_concurrent_runs_reached";
+
+/// Number of catch-up jobs to enqueue for the occurrences falling between `from` and the end of
+/// the pause, according to the schedule's catch-up policy
+fn catchup_runs_count(
+    sched: &ScheduleType,
+    policy: CatchupPolicy,
+    from: &chrono::DateTime<chrono_tz::Tz>,
+    paused_until: &chrono::DateTime<chrono_tz::Tz>,
+) -> usize {
+    let max = match policy {
+        CatchupPolicy::Skip => return 0,
+        CatchupPolicy::RunOnce => 1,
+        CatchupPolicy::RunAll => *SCHEDULE_CATCHUP_MAX_RUNS,
+    };
+    let mut missed = 0;
+    let mut current = sched.find_next(from);
+    while &current <= paused_until && missed < max {
+        missed += 1;
+        current = sched.find_next(&current);
+    }
+    missed
+}
+
+/// Effective next run of a schedule, taking into account its pause and catch-up policy
+pub fn next_run_of_schedule(
+    schedule: &Schedule,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    if !schedule.enabled {
+        return Ok(None);
+    }
+    let sched = ScheduleType::from_str(&schedule.schedule, schedule.cron_version.as_deref())?;
+    let tz = chrono_tz::Tz::from_str(&schedule.timezone)
+        .map_err(|e| error::Error::BadRequest(e.to_string()))?;
+
+    let next = match schedule.paused_until {
+        Some(paused_until) if paused_until > now => {
+            let paused_until = paused_until.with_timezone(&tz);
+            if catchup_runs_count(
+                &sched,
+                schedule.catchup_policy,
+                &now.with_timezone(&tz),
+                &paused_until,
+            ) > 0
+            {
+                paused_until
+            } else {
+                sched.find_next(&paused_until)
+            }
+        }
+        _ => sched.find_next(&now.with_timezone(&tz)),
+    };
+
+    Ok(Some(next.with_timezone(&chrono::Utc)))
+}
+
+/// Upper bound of the random delay to add to the next run of the schedule, 0 if the schedule has
+/// no jitter or if jitter is disabled in its workspace
+async fn schedule_max_jitter_seconds(
+    tx: &mut Transaction<'_, Postgres>,
+    schedule: &Schedule,
+) -> Result<i64> {
+    let Some(jitter_seconds) = schedule.jitter_seconds.filter(|j| *j > 0) else {
+        return Ok(0);
+    };
+    let jitter_enabled = sqlx::query_scalar::<_, bool>(
+        "SELECT schedule_jitter_enabled FROM workspace_settings WHERE workspace_id = $1",
+    )
+    .bind(&schedule.workspace_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .unwrap_or(true);
+    Ok(if jitter_enabled {
+        i64::from(jitter_seconds)
+    } else {
+        0
+    })
+}
+
+/// Whether a job of the schedule is already scheduled within `window_secs` after `from`
+async fn scheduled_job_exists(
+    tx: &mut Transaction<'_, Postgres>,
+    schedule: &Schedule,
+    from: chrono::DateTime<chrono::Utc>,
+    window_secs: i64,
+) -> Result<bool> {
+    let exists = query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM queue WHERE workspace_id = $1 AND schedule_path = $2
+            AND scheduled_for >= $3 AND scheduled_for <= $3 + $4 * interval '1 second')",
+    )
+    .bind(&schedule.workspace_id)
+    .bind(&schedule.path)
+    .bind(from)
+    .bind(window_secs)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(exists)
+}
+
+/// Conflict policy to apply to a run of the schedule if it already has `max_concurrent_runs`
+/// runs running, or due when `count_due` is set. When enqueueing, the due runs are counted and
+/// `current_job` is the run pushing the next occurrence, either because it started (flows) or
+/// because it completed but may still be visible in the queue (scripts). When a flow starts, only
+/// the running ones are and `current_job` is the flow itself. `current_job` is never counted.
+pub async fn schedule_run_conflict(
+    conn: &mut sqlx::PgConnection,
+    schedule: &Schedule,
+    current_job: Option<uuid::Uuid>,
+    count_due: bool,
+) -> Result<Option<ScheduleConflictPolicy>> {
+    let Some(max_concurrent_runs) = schedule.max_concurrent_runs else {
+        return Ok(None);
+    };
+    let active_runs = query_scalar::<_, i64>(
+        "SELECT count(*) FROM queue WHERE workspace_id = $1 AND schedule_path = $2
+            AND parent_job IS NULL AND (running = true OR ($4 AND scheduled_for <= now()))
+            AND ($3::uuid IS NULL OR id != $3)",
+    )
+    .bind(&schedule.workspace_id)
+    .bind(&schedule.path)
+    .bind(current_job)
+    .bind(count_due)
+    .fetch_one(conn)
+    .await?;
+    let conflict = active_runs >= i64::from(max_concurrent_runs);
+
+    #[cfg(feature = "prometheus")]
+    if conflict
+        && schedule.on_conflict == ScheduleConflictPolicy::Skip
+        && windmill_common::METRICS_ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+    {
+        SCHEDULES_SKIPPED_DUE_TO_MAX_CONCURRENT_RUNS.inc();
+    }
+
+    Ok(conflict.then_some(schedule.on_conflict))
+}
+
 pub async fn push_scheduled_job<'c>(
+    db: &DB,
+    tx: Transaction<'c, Postgres>,
+    schedule: &Schedule,
+    authed: Option<&Authed>,
+) -> Result<Transaction<'c, Postgres>> {
+    push_scheduled_job_after(db, tx, schedule, authed, None).await
+}
+
+/// Same as `push_scheduled_job`, `current_job` being the run of the schedule whose start or
+/// completion triggered the push, which does not count towards `max_concurrent_runs`
+pub async fn push_scheduled_job_after<'c>(
     db: &DB,
     mut tx: Transaction<'c, Postgres>,
     schedule: &Schedule,
     authed: Option<&Authed>,
+    current_job: Option<uuid::Uuid>,
 ) -> Result<Transaction<'c, Postgres>> {
     if !*LICENSE_KEY_VALID.read().await {
         return Err(error::Error::BadRequest(
@@ -43,10 +214,27 @@ pub async fn push_scheduled_job<'c>(
     let tz = chrono_tz::Tz::from_str(&schedule.timezone)
         .map_err(|e| error::Error::BadRequest(e.to_string()))?;
 
+    // concurrent pushes of the same schedule (e.g. a completing run and an edit) are serialized so
+    // that the checks below see the runs pushed by each other
+    sqlx::query("SELECT 1 FROM schedule WHERE workspace_id = $1 AND path = $2 FOR UPDATE")
+        .bind(&schedule.workspace_id)
+        .bind(&schedule.path)
+        .execute(&mut *tx)
+        .await?;
+
     let now = now_from_db(&mut *tx).await?;
 
-    let starting_from = match schedule.paused_until {
-        Some(paused_until) if paused_until > now => paused_until.with_timezone(&tz),
+    let (starting_from, catchup_runs) = match schedule.paused_until {
+        Some(paused_until) if paused_until > now => {
+            let paused_until = paused_until.with_timezone(&tz);
+            let catchup_runs = catchup_runs_count(
+                &sched,
+                schedule.catchup_policy,
+                &now.with_timezone(&tz),
+                &paused_until,
+            );
+            (paused_until, catchup_runs)
+        }
         paused_until_o => {
             if paused_until_o.is_some() {
                 sqlx::query!(
@@ -58,7 +246,7 @@ pub async fn push_scheduled_job<'c>(
                 .await
                 .context("Failed to clear paused_until for schedule")?;
             }
-            now.with_timezone(&tz)
+            (now.with_timezone(&tz), 0)
         }
     };
 
@@ -68,19 +256,31 @@ pub async fn push_scheduled_job<'c>(
     // println!("next event(UTC): {}", next.with_timezone(&chrono::Utc));
 
     // Scheduled events must be stored in the database in UTC
-    let next = next.with_timezone(&chrono::Utc);
+    let mut next = next.with_timezone(&chrono::Utc);
+    let following = sched
+        .find_next(&next.with_timezone(&tz))
+        .with_timezone(&chrono::Utc);
 
-    let already_exists: bool = query_scalar!(
-        "SELECT EXISTS (SELECT 1 FROM queue WHERE workspace_id = $1 AND schedule_path = $2 AND scheduled_for = $3)",
-        &schedule.workspace_id,
-        &schedule.path,
-        next
+    let max_jitter_seconds = schedule_max_jitter_seconds(&mut tx, schedule).await?;
+    // a delayed occurrence runs at the time of the following one, which is then skipped
+    let max_delay_seconds = if schedule.max_concurrent_runs.is_some()
+        && schedule.on_conflict == ScheduleConflictPolicy::Delay
+    {
+        (following - next).num_seconds()
+    } else {
+        0
+    };
+
+    // a jittered (or delayed) job of this occurrence is scheduled anywhere in
+    // [next, next + max jitter + max delay]
+    if scheduled_job_exists(
+        &mut tx,
+        schedule,
+        next,
+        max_jitter_seconds + max_delay_seconds,
     )
-    .fetch_one(&mut *tx)
     .await?
-    .unwrap_or(false);
-
-    if already_exists {
+    {
         tracing::info!(
             "Job for schedule {} at {} already exists",
             &schedule.path,
@@ -89,6 +289,57 @@ pub async fn push_scheduled_job<'c>(
         return Ok(tx);
     }
 
+    // the occurrence is not pushed while the schedule it depends on did not succeed, the
+    // next successful run of that schedule pushes it again
+    if schedule.depends_on_schedule.is_some() {
+        let status =
+            schedule_dependency_status(&mut *tx, &schedule.workspace_id, &schedule.path).await?;
+        if let Some(reason) = status.blocked_reason {
+            tracing::info!(
+                "Skipping occurrence at {} of schedule {}: {}",
+                next,
+                &schedule.path,
+                reason
+            );
+            record_skipped_schedule_run(
+                &mut *tx,
+                &schedule.workspace_id,
+                &schedule.path,
+                next,
+                None,
+                &reason,
+            )
+            .await?;
+            return Ok(tx);
+        }
+    }
+
+    let delay_seconds = match schedule_run_conflict(&mut *tx, schedule, current_job, true).await? {
+        Some(ScheduleConflictPolicy::Skip) => {
+            tracing::info!(
+                "Skipping occurrence at {} of schedule {} which has reached its max concurrent runs",
+                next,
+                &schedule.path
+            );
+            record_skipped_schedule_run(
+                &mut *tx,
+                &schedule.workspace_id,
+                &schedule.path,
+                next,
+                None,
+                MAX_CONCURRENT_RUNS_REACHED,
+            )
+            .await?;
+            next = following;
+            if scheduled_job_exists(&mut tx, schedule, next, max_jitter_seconds).await? {
+                return Ok(tx);
+            }
+            0
+        }
+        Some(ScheduleConflictPolicy::Delay) => max_delay_seconds,
+        None => 0,
+    };
+
     let mut args: HashMap<String, Box<serde_json::value::RawValue>> = HashMap::new();
 
     if let Some(args_v) = &schedule.args {
@@ -119,6 +370,7 @@ pub async fn push_scheduled_job<'c>(
                 path: schedule.script_path.clone(),
                 dedicated_worker,
                 apply_preprocessor: false,
+                version: schedule.pinned_version_id,
             },
             tag,
             None,
@@ -249,32 +501,55 @@ pub async fn push_scheduled_job<'c>(
         )
     };
 
-    let tx = PushIsolationLevel::Transaction(tx);
-    let (_, mut tx) = push(
-        &db,
-        tx,
-        &schedule.workspace_id,
-        payload,
-        crate::PushArgs { args: &args, extra: None },
-        &schedule_to_user(&schedule.path),
-        email,
-        permissioned_as,
-        Some(next),
-        Some(schedule.path.clone()),
-        None,
-        None,
-        None,
-        false,
-        false,
-        None,
-        true,
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed,
-    )
-    .await?;
+    let jitter_seconds = if max_jitter_seconds > 0 {
+        rand::rng().random_range(0..=max_jitter_seconds)
+    } else {
+        0
+    };
+
+    // catch-up jobs for the occurrences missed during the pause run as soon as the pause lifts
+    let scheduled_fors = std::iter::repeat((starting_from.with_timezone(&chrono::Utc), None))
+        .take(catchup_runs)
+        .chain(std::iter::once((
+            next + chrono::Duration::seconds(delay_seconds + jitter_seconds),
+            (max_jitter_seconds > 0).then_some(jitter_seconds),
+        )));
+
+    let mut tx = tx;
+    for (scheduled_for, jitter_seconds) in scheduled_fors {
+        let extra = jitter_seconds.map(|jitter_seconds| {
+            HashMap::from([(
+                "wm_schedule_jitter_seconds".to_string(),
+                to_raw_value(&jitter_seconds),
+            )])
+        });
+        let (_, new_tx) = push(
+            &db,
+            PushIsolationLevel::Transaction(tx),
+            &schedule.workspace_id,
+            payload.clone(),
+            crate::PushArgs { args: &args, extra },
+            &schedule_to_user(&schedule.path),
+            email,
+            permissioned_as.clone(),
+            Some(scheduled_for),
+            Some(schedule.path.clone()),
+            None,
+            None,
+            None,
+            false,
+            false,
+            None,
+            true,
+            tag.clone(),
+            timeout,
+            None,
+            None,
+            push_authed,
+        )
+        .await?;
+        tx = new_tx;
+    }
 
     if revert_to_windmill_user {
         sqlx::query!("SET LOCAL ROLE windmill_user")
@@ -285,6 +560,184 @@ pub async fn push_scheduled_job<'c>(
     Ok(tx) // TODO: Bubble up pushed UUID from here
 }
 
+#[derive(Serialize, Debug)]
+pub struct ScheduleDependencyStatus {
+    pub depends_on_schedule: Option<String>,
+    pub dependency_lookback_secs: Option<i32>,
+    pub last_job_id: Option<uuid::Uuid>,
+    pub last_job_success: Option<bool>,
+    pub last_job_completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// why the next run of the schedule would be skipped, `None` if it can run
+    pub blocked_reason: Option<String>,
+}
+
+/// Last run of a schedule, either a completed job or an occurrence skipped because the schedule
+/// it depends on did not succeed
+#[derive(sqlx::FromRow)]
+struct LastScheduleRun {
+    job_id: Option<uuid::Uuid>,
+    success: bool,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    skipped_reason: Option<String>,
+}
+
+/// Checks whether the last run of the schedule this schedule depends on succeeded within the
+/// lookback window. Occurrences of the upstream schedule that were themselves skipped because of
+/// its own dependency count as failures so that a failure propagates along a chain of dependent
+/// schedules.
+pub async fn schedule_dependency_status(
+    conn: &mut sqlx::PgConnection,
+    w_id: &str,
+    schedule_path: &str,
+) -> Result<ScheduleDependencyStatus> {
+    let dependency = sqlx::query_as::<_, (Option<String>, Option<i32>)>(
+        "SELECT depends_on_schedule, dependency_lookback_secs FROM schedule
+        WHERE workspace_id = $1 AND path = $2",
+    )
+    .bind(w_id)
+    .bind(schedule_path)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (depends_on_schedule, dependency_lookback_secs) = dependency.unwrap_or((None, None));
+    let mut status = ScheduleDependencyStatus {
+        depends_on_schedule,
+        dependency_lookback_secs,
+        last_job_id: None,
+        last_job_success: None,
+        last_job_completed_at: None,
+        blocked_reason: None,
+    };
+    let Some(depends_on_schedule) = status.depends_on_schedule.as_deref() else {
+        return Ok(status);
+    };
+
+    let last_run = sqlx::query_as::<_, LastScheduleRun>(
+        "SELECT * FROM (
+            (SELECT id AS job_id, success,
+                started_at + duration_ms * interval '1 millisecond' AS completed_at,
+                NULL::text AS skipped_reason
+            FROM completed_job
+            WHERE workspace_id = $1 AND schedule_path = $2 AND parent_job IS NULL
+            ORDER BY created_at DESC
+            LIMIT 1)
+            UNION ALL
+            (SELECT NULL::uuid, false, created_at, skipped_reason
+            FROM schedule_run_log
+            WHERE workspace_id = $1 AND schedule_path = $2 AND job_id IS NULL
+                AND skipped_reason IS NOT NULL AND skipped_reason != $3
+            ORDER BY created_at DESC
+            LIMIT 1)
+        ) last_runs
+        ORDER BY completed_at DESC NULLS LAST
+        LIMIT 1",
+    )
+    .bind(w_id)
+    .bind(depends_on_schedule)
+    .bind(MAX_CONCURRENT_RUNS_REACHED)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(last_run) = last_run else {
+        status.blocked_reason = Some(format!(
+            "upstream schedule {depends_on_schedule} has no completed run"
+        ));
+        return Ok(status);
+    };
+
+    status.last_job_id = last_run.job_id;
+    status.last_job_success = Some(last_run.success);
+    status.last_job_completed_at = last_run.completed_at;
+
+    if let Some(skipped_reason) = last_run.skipped_reason {
+        status.blocked_reason = Some(format!(
+            "last run of upstream schedule {depends_on_schedule} was skipped: {skipped_reason}"
+        ));
+    } else if !last_run.success {
+        status.blocked_reason = Some(format!(
+            "last run {} of upstream schedule {depends_on_schedule} did not succeed",
+            last_run.job_id.unwrap_or_default()
+        ));
+    } else if let Some(lookback_secs) = status.dependency_lookback_secs {
+        let window_start = chrono::Utc::now() - chrono::Duration::seconds(i64::from(lookback_secs));
+        if last_run.completed_at.is_some_and(|t| t < window_start) {
+            status.blocked_reason = Some(format!(
+                "last successful run {} of upstream schedule {depends_on_schedule} is older than {lookback_secs}s",
+                last_run.job_id.unwrap_or_default()
+            ));
+        }
+    }
+
+    Ok(status)
+}
+
+/// Records a skipped occurrence of a schedule, only once per occurrence. `job_id` is the run
+/// that was skipped when it started, occurrences skipped without being pushed have none
+pub async fn record_skipped_schedule_run(
+    conn: &mut sqlx::PgConnection,
+    w_id: &str,
+    schedule_path: &str,
+    scheduled_for: chrono::DateTime<chrono::Utc>,
+    job_id: Option<uuid::Uuid>,
+    skipped_reason: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO schedule_run_log (workspace_id, schedule_path, scheduled_for, job_id, skipped_reason)
+        SELECT $1, $2, $3, $4, $5
+        WHERE NOT EXISTS (SELECT 1 FROM schedule_run_log
+            WHERE workspace_id = $1 AND schedule_path = $2 AND scheduled_for = $3
+                AND skipped_reason IS NOT NULL)",
+    )
+    .bind(w_id)
+    .bind(schedule_path)
+    .bind(scheduled_for)
+    .bind(job_id)
+    .bind(skipped_reason)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Pushes the next run of the enabled schedules depending on `schedule_path` that have no run
+/// queued, their last occurrence having been skipped while waiting for a successful run of
+/// `schedule_path`
+pub async fn push_dependent_schedules(db: &DB, w_id: &str, schedule_path: &str) -> Result<()> {
+    let dependents = sqlx::query_as::<_, Schedule>(
+        "SELECT * FROM schedule s
+        WHERE workspace_id = $1 AND depends_on_schedule = $2 AND enabled = true
+            AND NOT EXISTS (SELECT 1 FROM queue
+                WHERE workspace_id = s.workspace_id AND schedule_path = s.path AND parent_job IS NULL)",
+    )
+    .bind(w_id)
+    .bind(schedule_path)
+    .fetch_all(db)
+    .await?;
+    for schedule in dependents {
+        let tx = db.begin().await?;
+        let tx = push_scheduled_job(db, tx, &schedule, None).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
+
+pub async fn last_schedule_skip_reason<'c>(
+    e: impl PgExecutor<'c>,
+    w_id: &str,
+    schedule_path: &str,
+) -> Result<Option<String>> {
+    let reason = sqlx::query_scalar::<_, String>(
+        "SELECT skipped_reason FROM schedule_run_log
+        WHERE workspace_id = $1 AND schedule_path = $2 AND skipped_reason IS NOT NULL
+        ORDER BY created_at DESC
+        LIMIT 1",
+    )
+    .bind(w_id)
+    .bind(schedule_path)
+    .fetch_optional(e)
+    .await?;
+    Ok(reason)
+}
+
 pub async fn get_schedule_opt<'c>(
     e: impl PgExecutor<'c>,
     w_id: &str,
@@ -490,6 +490,19 @@ pub async fn update_worker_ping_for_failed_init_script(
         tracing::error!("Error updating worker ping for failed init script: {e:?}");
     }
 }
+
+/// reported in the worker list while the worker group is draining
+pub async fn update_worker_ping_draining(db: &DB, worker_name: &str, draining: bool) {
+    if let Err(e) = sqlx::query("UPDATE worker_ping SET draining = $1 WHERE worker = $2")
+        .bind(draining)
+        .bind(worker_name)
+        .execute(db)
+        .await
+    {
+        tracing::error!("Error updating worker ping draining status: {e:?}");
+    }
+}
+
 pub struct OccupancyMetrics {
     pub running_job_started_at: Option<Instant>,
     pub total_duration_of_running_jobs: f32,
@@ -567,18 +580,89 @@ impl OccupancyMetrics {
     }
 }
 
+tokio::task_local! {
+    /// Memory limit in MB of each process started for the job being executed, set per run through
+    /// the `mem_limit_mb` run parameter or inherited from the flow of the job
+    pub static JOB_MEM_LIMIT_MB: Option<i32>;
+    /// Timeout in ms of the job being executed, set for the flow steps with a `step_timeout_ms`
+    pub static JOB_TIMEOUT_MS: Option<i32>;
+}
+
 pub async fn start_child_process(mut cmd: Command, executable: &str) -> Result<Child, Error> {
+    #[cfg(unix)]
+    if let Ok(Some(limit_mb)) = JOB_MEM_LIMIT_MB.try_with(|limit_mb| *limit_mb) {
+        limit_child_memory(&mut cmd, limit_mb);
+    }
     return cmd
         .spawn()
         .map_err(|err| tentatively_improve_error(Error::IoErr(err), executable));
 }
 
+/// Makes the kernel enforce the memory limit of the job with RLIMIT_DATA, which bounds the heap and
+/// the private writable mappings of the child (but not the address space that runtimes like V8
+/// reserve without using). nsjail only resets RLIMIT_AS, so jailed processes inherit it too.
+/// RLIMIT_DATA is a per process limit: the processes the child forks or spawns inherit it and each
+/// of them can use the full limit, so it does not bound the total memory of a job that runs
+/// several processes (e.g. python multiprocessing), which would take a cgroup.
+#[cfg(unix)]
+fn limit_child_memory(cmd: &mut Command, limit_mb: i32) {
+    use nix::sys::resource::{setrlimit, Resource};
+
+    let limit = (limit_mb as u64).saturating_mul(1024 * 1024);
+    // SAFETY: setrlimit is async-signal-safe, the closure neither allocates nor takes locks
+    unsafe {
+        cmd.pre_exec(move || {
+            setrlimit(Resource::RLIMIT_DATA, limit, limit).map_err(std::io::Error::from)
+        });
+    }
+}
+
+/// The error of a job that failed to allocate memory under its memory limit, with the limit in the
+/// message as the error of the runtime alone (e.g. a python `MemoryError`) does not tell why
+pub fn mem_limit_exceeded_error(error: &RawValue, limit_mb: i32) -> Option<Box<RawValue>> {
+    let mut error = serde_json::from_str::<Value>(error.get()).ok()?;
+    let name = error
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let message = error
+        .get("message")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let out_of_memory = name == "MemoryError"
+        || [
+            "out of memory",
+            "Cannot allocate memory",
+            "memory allocation",
+        ]
+        .iter()
+        .any(|marker| message.contains(marker));
+    if !out_of_memory {
+        return None;
+    }
+    let message = format!("memory limit exceeded ({limit_mb} MB)");
+    error
+        .as_object_mut()?
+        .insert("message".to_string(), Value::String(message));
+    Some(to_raw_value(&error))
+}
+
 pub async fn resolve_job_timeout(
     _db: &Pool<Postgres>,
     _w_id: &str,
     _job_id: Uuid,
     custom_timeout_secs: Option<i32>,
 ) -> (Duration, Option<String>, bool) {
+    // the timeout of flow steps with a `step_timeout_ms` is kept in ms, `custom_timeout_secs` is
+    // then only its rounded up value
+    let custom_timeout = match JOB_TIMEOUT_MS
+        .try_with(|timeout_ms| *timeout_ms)
+        .ok()
+        .flatten()
+    {
+        Some(timeout_ms) => Some(Duration::from_millis(timeout_ms as u64)),
+        None => custom_timeout_secs.map(|timeout_secs| Duration::from_secs(timeout_secs as u64)),
+    };
     let mut warn_msg: Option<String> = None;
     #[cfg(feature = "cloud")]
     let cloud_premium_workspace = *CLOUD_HOSTED
@@ -599,13 +683,10 @@ pub async fn resolve_job_timeout(
         *MAX_TIMEOUT_DURATION
     };
 
-    match custom_timeout_secs {
-        Some(timeout_secs)
-            if Duration::from_secs(timeout_secs as u64) < global_max_timeout_duration =>
-        {
-            (Duration::from_secs(timeout_secs as u64), warn_msg, true)
-        }
-        Some(timeout_secs) => {
+    match custom_timeout {
+        Some(timeout) if timeout < global_max_timeout_duration => (timeout, warn_msg, true),
+        Some(timeout) => {
+            let timeout_secs = timeout.as_secs_f64();
             warn_msg = Some(format!("WARNING: Custom job timeout of {timeout_secs} seconds was greater than the maximum timeout. It will be ignored and the max timeout will be used instead"));
             tracing::warn!(warn_msg);
             (global_max_timeout_duration, warn_msg, false)
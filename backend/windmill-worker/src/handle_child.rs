@@ -53,6 +53,8 @@ use crate::job_logger::{append_job_logs, append_with_limit, LARGE_LOG_THRESHOLD_
 use crate::job_logger_ee::process_streaming_log_lines;
 use crate::{MAX_RESULT_SIZE, MAX_WAIT_FOR_SIGINT, MAX_WAIT_FOR_SIGTERM};
 
+const JOB_SPECIFIC_TIMEOUT_MSG: &str = "timeout after exceeding job-specific duration limit";
+
 lazy_static::lazy_static! {
     pub static ref SLOW_LOGS: bool = std::env::var("SLOW_LOGS").ok().is_some_and(|x| x == "1" || x == "true");
 }
@@ -160,7 +162,7 @@ pub async fn handle_child(
             match self {
                 KillReason::TooManyLogs => f.write_str("too many logs (max size: 2MB)"),
                 KillReason::Timeout { is_job_specific } => f.write_str(if *is_job_specific {
-                    "timeout after exceeding job-specific duration limit"
+                    JOB_SPECIFIC_TIMEOUT_MSG
                 } else {
                     "timeout after exceeding instance-wide job duration limit"
                 }),
@@ -431,6 +433,9 @@ pub async fn handle_child(
             KillReason::AlreadyCompleted => {
                 Err(Error::AlreadyCompleted("Job already completed".to_string()))
             }
+            KillReason::Timeout { is_job_specific: true } => Err(Error::JobTimeout(format!(
+                "job process terminated due to {kill_reason:#?}"
+            ))),
             _ => Err(Error::ExecutionErr(format!(
                 "job process terminated due to {kill_reason:#?}"
             ))),
@@ -44,7 +44,7 @@ use windmill_queue::{add_completed_job, add_completed_job_error};
 
 use crate::{
     bash_executor::ANSI_ESCAPE_RE,
-    common::{read_result, save_in_cache},
+    common::{mem_limit_exceeded_error, read_result, save_in_cache},
     worker_flow::update_flow_status_after_job_completion,
     AuthedClient, JobCompleted, JobCompletedSender, SameWorkerSender, SendResult, INIT_SCRIPT_TAG,
 };
@@ -353,11 +353,21 @@ pub async fn process_result(
                 }
                 err @ _ => to_raw_value(&SerializedError {
                     message: format!("error during execution of the script:\n{}", err),
-                    name: "ExecutionErr".to_string(),
+                    name: match err {
+                        Error::JobTimeout(_) => JOB_TIMEOUT_ERROR_NAME,
+                        _ => "ExecutionErr",
+                    }
+                    .to_string(),
                     step_id: job.flow_step_id.clone(),
                     exit_code: None,
                 }),
             };
+            let error_value = match job.mem_limit_mb {
+                Some(limit_mb) => {
+                    mem_limit_exceeded_error(&error_value, limit_mb).unwrap_or(error_value)
+                }
+                None => error_value,
+            };
 
             send_job_completed(
                 job_completed_tx,
@@ -653,6 +663,17 @@ pub async fn handle_job_error(
     tracing::error!(job_id = %job.id, "error handling job: {err:?} {} {} {}", job.id, job.workspace_id, job.created_by);
 }
 
+/// Name of the error of the jobs killed for exceeding their own timeout
+pub const JOB_TIMEOUT_ERROR_NAME: &str = "JobTimeout";
+
+/// Whether `result` is the error, wrapped or not, of a job killed for exceeding its own timeout
+pub fn is_job_timeout_error(result: &RawValue) -> bool {
+    serde_json::from_str::<serde_json::Value>(result.get()).is_ok_and(|result| {
+        let error = result.get("error").unwrap_or(&result);
+        error.get("name").and_then(serde_json::Value::as_str) == Some(JOB_TIMEOUT_ERROR_NAME)
+    })
+}
+
 #[derive(Debug, Serialize)]
 pub struct SerializedError {
     pub message: String,
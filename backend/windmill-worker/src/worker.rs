@@ -58,7 +58,7 @@ use windmill_common::{
     scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang, PREVIEW_IS_CODEBASE_HASH},
     users::SUPERADMIN_SECRET_EMAIL,
     utils::StripPath,
-    worker::{update_ping, CLOUD_HOSTED, NO_LOGS, WORKER_CONFIG, WORKER_GROUP},
+    worker::{update_ping, CLOUD_HOSTED, MAX_TIMEOUT, NO_LOGS, WORKER_CONFIG, WORKER_GROUP},
     DB, IS_READY,
 };
 
@@ -94,7 +94,9 @@ use crate::{
     bun_executor::handle_bun_job,
     common::{
         build_args_map, cached_result_path, get_cached_resource_value_if_valid,
-        get_reserved_variables, update_worker_ping_for_failed_init_script, OccupancyMetrics,
+        get_reserved_variables, update_worker_ping_draining,
+        update_worker_ping_for_failed_init_script, OccupancyMetrics, JOB_MEM_LIMIT_MB,
+        JOB_TIMEOUT_MS,
     },
     csharp_executor::handle_csharp_job,
     deno_executor::handle_deno_job,
@@ -305,8 +307,9 @@ const NUM_SECS_READINGS: u64 = 60;
 const INCLUDE_DEPS_PY_SH_CONTENT: &str = include_str!("../nsjail/download_deps.py.sh");
 const INCLUDE_DEPS_PY_SH_CONTENT_FALLBACK: &str = include_str!("../nsjail/download_deps.py.pip.sh");
 
-pub const DEFAULT_CLOUD_TIMEOUT: u64 = 900;
-pub const DEFAULT_SELFHOSTED_TIMEOUT: u64 = 604800; // 7 days
+pub use windmill_common::worker::{
+    DEFAULT_CLOUD_TIMEOUT, DEFAULT_SELFHOSTED_TIMEOUT, JOB_DEFAULT_TIMEOUT,
+};
 pub const DEFAULT_SLEEP_QUEUE: u64 = 50;
 
 // only 1 native job so that we don't have to worry about concurrency issues on non dedicated native jobs workers
@@ -419,12 +422,6 @@ lazy_static::lazy_static! {
     pub static ref PIP_EXTRA_INDEX_URL: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
     pub static ref PIP_INDEX_URL: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
     pub static ref INSTANCE_PYTHON_VERSION: Arc<RwLock<Option<String>>> = Arc::new(RwLock::new(None));
-    pub static ref JOB_DEFAULT_TIMEOUT: Arc<RwLock<Option<i32>>> = Arc::new(RwLock::new(None));
-
-    static ref MAX_TIMEOUT: u64 = std::env::var("TIMEOUT")
-        .ok()
-        .and_then(|x| x.parse::<u64>().ok())
-        .unwrap_or_else(|| if *CLOUD_HOSTED { DEFAULT_CLOUD_TIMEOUT } else { DEFAULT_SELFHOSTED_TIMEOUT });
 
     pub static ref MAX_WAIT_FOR_SIGINT: u64 = std::env::var("MAX_WAIT_FOR_SIGINT")
         .ok()
@@ -1163,6 +1160,7 @@ pub async fn run_worker(
     let mut last_30jobs_suspended: Vec<bool> = vec![false; 30];
     let mut last_suspend_first = Instant::now();
     let mut killed_but_draining_same_worker_jobs = false;
+    let mut draining = false;
 
     let mut killpill_rx2 = killpill_rx.resubscribe();
     loop {
@@ -1367,7 +1365,34 @@ pub async fn run_worker(
                     tokio::time::sleep(Duration::from_millis(200)).await;
                     continue;
                 }
+            } else if WORKER_CONFIG.read().await.drain {
+                if !draining {
+                    tracing::info!(worker = %worker_name, hostname = %hostname, "worker group is draining, jobs are not pulled anymore");
+                    draining = true;
+                    update_worker_ping_draining(db, &worker_name, true).await;
+                }
+                // once idle, exit the same way as on a killpill so that the remaining flows are
+                // progressed before the worker exits
+                if same_worker_queue_size.load(Ordering::SeqCst) == 0
+                    && job_completed_tx.0.capacity() == job_completed_tx.0.max_capacity()
+                {
+                    tracing::info!(worker = %worker_name, hostname = %hostname, "worker is idle and its group is draining, exiting");
+                    killed_but_draining_same_worker_jobs = true;
+                    job_completed_tx
+                        .0
+                        .send(SendResult::Kill)
+                        .await
+                        .expect("send kill to job completed tx");
+                } else {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                continue;
             } else {
+                if draining {
+                    tracing::info!(worker = %worker_name, hostname = %hostname, "worker group is not draining anymore, resuming pulling jobs");
+                    draining = false;
+                    update_worker_ping_draining(db, &worker_name, false).await;
+                }
                 let pull_time = Instant::now();
                 let likelihood_of_suspend =
                     (1.0 + last_30jobs_suspended.iter().filter(|&&x| x).count() as f64) / 31.0;
@@ -1641,6 +1666,13 @@ pub async fn run_worker(
                     }
 
                     windmill_common::otel_ee::set_span_parent(&span, &rj);
+                    #[cfg(feature = "otel")]
+                    if let Some(Json(trace_context)) = arc_job.trace_context.as_ref() {
+                        windmill_common::tracing_init::set_span_parent_from_trace_context(
+                            &span,
+                            trace_context,
+                        );
+                    }
                     // span.context().span().add_event_with_timestamp("job created".to_string(), arc_job.created_at.into(), vec![]);
 
                     match handle_queued_job(
@@ -2189,23 +2221,30 @@ async fn handle_queued_job(
                     RawData::Script(data) => Some(data),
                     _ => None,
                 });
-                let r = handle_code_execution_job(
-                    job.as_ref(),
-                    preview_data,
-                    db,
-                    client,
-                    job_dir,
-                    worker_dir,
-                    &mut mem_peak,
-                    &mut canceled_by,
-                    base_internal_url,
-                    worker_name,
-                    &mut column_order,
-                    &mut new_args,
-                    occupancy_metrics,
-                    killpill_rx,
-                )
-                .await;
+                let r = JOB_TIMEOUT_MS
+                    .scope(
+                        job.timeout_ms,
+                        JOB_MEM_LIMIT_MB.scope(
+                            job.mem_limit_mb,
+                            handle_code_execution_job(
+                                job.as_ref(),
+                                preview_data,
+                                db,
+                                client,
+                                job_dir,
+                                worker_dir,
+                                &mut mem_peak,
+                                &mut canceled_by,
+                                base_internal_url,
+                                worker_name,
+                                &mut column_order,
+                                &mut new_args,
+                                occupancy_metrics,
+                                killpill_rx,
+                            ),
+                        ),
+                    )
+                    .await;
                 occupancy_metrics.total_duration_of_running_jobs +=
                     metric_timer.elapsed().as_secs_f32();
                 r
@@ -13,6 +13,7 @@ use std::time::Duration;
 
 use crate::common::{cached_result_path, save_in_cache};
 use crate::js_eval::{eval_timeout, IdContext};
+use crate::result_processor::is_job_timeout_error;
 use crate::{
     AuthedClient, PreviousResult, SameWorkerPayload, SameWorkerSender, SendResult, JOB_TOKEN,
     KEEP_JOB_DIR,
@@ -50,11 +51,14 @@ use windmill_common::{
     error::{self, to_anyhow, Error},
     flow_status::{
         Approval, BranchAllStatus, BranchChosen, FlowStatus, FlowStatusModule, RetryStatus,
-        MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL,
+        MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL, STEP_TIMEOUT_REASON,
     },
     flows::{FlowModule, FlowModuleValue, FlowValue, InputTransform, Retry, Suspend},
 };
-use windmill_queue::schedule::get_schedule_opt;
+use windmill_queue::schedule::{
+    get_schedule_opt, record_skipped_schedule_run, schedule_run_conflict,
+    MAX_CONCURRENT_RUNS_REACHED,
+};
 use windmill_queue::{
     add_completed_job, add_completed_job_error, append_logs, handle_maybe_scheduled_job,
     CanceledBy, PushArgs, PushIsolationLevel, WrappedError,
@@ -585,6 +589,7 @@ pub async fn update_flow_status_after_job_completion_internal(
                             flow_jobs_success: flow_jobs_success.clone(),
                             branch_chosen: None,
                             failed_retries: vec![],
+                            reason: None,
                         }
                     };
                     let r = sqlx::query_scalar!(
@@ -751,6 +756,15 @@ pub async fn update_flow_status_after_job_completion_internal(
                     } else {
                         false
                     };
+                    let reason = if current_module
+                        .as_ref()
+                        .is_some_and(|m| m.step_timeout_ms.is_some())
+                        && is_job_timeout_error(&result)
+                    {
+                        Some(STEP_TIMEOUT_REASON.to_string())
+                    } else {
+                        None
+                    };
                     (
                         inc,
                         Some(FlowStatusModule::Failure {
@@ -760,6 +774,7 @@ pub async fn update_flow_status_after_job_completion_internal(
                             flow_jobs_success,
                             branch_chosen,
                             failed_retries: old_status.retry.failed_jobs.clone(),
+                            reason,
                         }),
                     )
                 }
@@ -1676,48 +1691,47 @@ async fn push_next_flow_job(
 
     if matches!(step, Step::Step(0)) {
         if !flow_job.is_flow_step && flow_job.schedule_path.is_some() {
-            let no_flow_overlap = sqlx::query_scalar!(
-                "SELECT no_flow_overlap FROM schedule WHERE path = $1 AND workspace_id = $2",
-                flow_job.schedule_path.as_ref().unwrap(),
-                flow_job.workspace_id.as_str()
-            )
-            .fetch_one(db)
-            .await?;
-            if no_flow_overlap {
-                let overlapping = sqlx::query_scalar!(
-                    "SELECT id FROM queue WHERE schedule_path = $1 AND workspace_id = $2 AND id != $3 AND running = true",
-                    flow_job.schedule_path.as_ref().unwrap(),
-                    flow_job.workspace_id.as_str(),
-                    flow_job.id
-                ).fetch_all(db).await?;
-                if overlapping.len() > 0 {
-                    let overlapping_str = overlapping
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ");
-                    job_completed_tx
-                        .send(SendResult::UpdateFlow {
-                            flow: flow_job.id,
-                            success: true,
-                            result: serde_json::from_str(
-                                &format!("\"not allowed to overlap with {overlapping_str}, scheduling next iteration\""),
-                            )
-                            .unwrap(),
-                            stop_early_override: Some(true),
-                            w_id: flow_job.workspace_id.clone(),
-                            worker_dir: worker_dir.to_string(),
-                            token: client.token.clone(),
-                        })
-                        .await
-                        .map_err(|e| {
-                            Error::InternalErr(format!(
-                                "error sending update flow message to job completed channel: {e:#}"
-                            ))
-                        })?;
-
-                    return Ok(());
+            let schedule_path = flow_job.schedule_path.as_ref().unwrap();
+            let schedule = get_schedule_opt(db, &flow_job.workspace_id, schedule_path).await?;
+            // a run delayed at enqueue that still conflicts when it starts is skipped as well
+            let conflict = match schedule.as_ref() {
+                Some(schedule) => {
+                    let mut conn = db.acquire().await?;
+                    schedule_run_conflict(&mut conn, schedule, Some(flow_job.id), false).await?
                 }
+                None => None,
+            };
+            if conflict.is_some() {
+                record_skipped_schedule_run(
+                    &mut *db.acquire().await?,
+                    &flow_job.workspace_id,
+                    schedule_path,
+                    flow_job.scheduled_for,
+                    Some(flow_job.id),
+                    MAX_CONCURRENT_RUNS_REACHED,
+                )
+                .await?;
+                job_completed_tx
+                    .send(SendResult::UpdateFlow {
+                        flow: flow_job.id,
+                        success: true,
+                        result: serde_json::from_str(&format!(
+                            "\"schedule {schedule_path} has reached its max concurrent runs, skipping this run\""
+                        ))
+                        .unwrap(),
+                        stop_early_override: Some(true),
+                        w_id: flow_job.workspace_id.clone(),
+                        worker_dir: worker_dir.to_string(),
+                        token: client.token.clone(),
+                    })
+                    .await
+                    .map_err(|e| {
+                        Error::InternalErr(format!(
+                            "error sending update flow message to job completed channel: {e:#}"
+                        ))
+                    })?;
+
+                return Ok(());
             }
         }
         if let Some(skip_expr) = &flow.skip_expr {
@@ -1897,7 +1911,10 @@ async fn push_next_flow_job(
                 && suspend.continue_on_disapprove_timeout.unwrap_or(false);
 
             let audit_author = AuditAuthor {
-                username: flow_job.permissioned_as.trim_start_matches("u/").to_string(),
+                username: flow_job
+                    .permissioned_as
+                    .trim_start_matches("u/")
+                    .to_string(),
                 email: flow_job.email.clone(),
                 username_override: None,
             };
@@ -2616,6 +2633,37 @@ async fn push_next_flow_job(
 
         tracing::debug!(id = %flow_job.id, root_id = %job_root, "pushed next flow job: {uuid}");
 
+        // the payload only has a timeout if the step is a script, whose timeout in seconds is
+        // rounded up from `step_timeout_ms`
+        if let (Some(timeout_ms), Some(_)) = (module.step_timeout_ms, payload_tag.timeout) {
+            sqlx::query("UPDATE queue SET timeout_ms = $1 WHERE id = $2")
+                .bind(timeout_ms.min(i32::MAX as u32) as i32)
+                .bind(uuid)
+                .execute(&mut *inner_tx)
+                .await?;
+        }
+
+        // steps inherit the memory limit of their flow, which subflows pass down to theirs
+        sqlx::query(
+            "UPDATE queue SET mem_limit_mb = flow.mem_limit_mb FROM queue flow
+            WHERE queue.id = $1 AND flow.id = $2 AND flow.mem_limit_mb IS NOT NULL",
+        )
+        .bind(uuid)
+        .bind(flow_job.id)
+        .execute(&mut *inner_tx)
+        .await?;
+
+        // steps are also redacted of the args redacted from their flow, on top of the ones of
+        // their script
+        sqlx::query(
+            "UPDATE queue SET redact_args = flow.redact_args FROM queue flow
+            WHERE queue.id = $1 AND flow.id = $2 AND flow.redact_args IS NOT NULL",
+        )
+        .bind(uuid)
+        .bind(flow_job.id)
+        .execute(&mut *inner_tx)
+        .await?;
+
         if value_with_parallel.type_ == "forloopflow" {
             if let Some(p) = value_with_parallel.parallelism {
                 tracing::debug!(id = %flow_job.id, root_id = %job_root, "updating suspend for forloopflow job {uuid}");
@@ -3147,7 +3195,7 @@ async fn compute_next_flow_transform(
                 },
                 tag: tag.clone(),
                 delete_after_use,
-                timeout: module.timeout,
+                timeout: module.step_timeout(),
                 on_behalf_of: None,
             };
             Ok(NextFlowTransform::Continue(
@@ -3757,7 +3805,7 @@ async fn payload_from_simple_module(
             },
             tag,
             delete_after_use,
-            timeout: module.timeout,
+            timeout: module.step_timeout(),
             on_behalf_of: None,
         },
         _ => unreachable!("is simple flow"),
@@ -3791,7 +3839,7 @@ fn raw_script_to_payload(
         }),
         tag,
         delete_after_use,
-        timeout: module.timeout,
+        timeout: module.step_timeout(),
         on_behalf_of: None,
     }
 }
@@ -3815,7 +3863,8 @@ async fn flow_to_payload(
     } else {
         None
     };
-    let payload = JobPayload::Flow { path, dedicated_worker: None, apply_preprocessor: false };
+    let payload =
+        JobPayload::Flow { path, dedicated_worker: None, apply_preprocessor: false, version: None };
     Ok(JobPayloadWithTag { payload, tag: None, delete_after_use, timeout: None, on_behalf_of })
 }
 
@@ -3886,7 +3935,7 @@ async fn script_to_payload(
     // the module value overrides the value set at the script level. Defaults to false if both are unset.
     let final_delete_after_user =
         module.delete_after_use.unwrap_or(false) || delete_after_use.unwrap_or(false);
-    let flow_step_timeout = module.timeout.or(script_timeout);
+    let flow_step_timeout = module.step_timeout().or(script_timeout);
     Ok(JobPayloadWithTag {
         payload,
         tag,
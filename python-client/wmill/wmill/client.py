@@ -321,6 +321,7 @@ class Windmill:
         value: Any,
         path: str,
         resource_type: str,
+        lock_token: Optional[str] = None,
     ):
         # check if resource exists
         r = self.get(f"/w/{self.workspace}/resources/get/{path}", raise_for_status=False)
@@ -339,10 +340,41 @@ class Windmill:
             self.post(
                 f"/w/{self.workspace}/resources/update_value/{path}",
                 json={"value": value},
+                params={"lock_token": lock_token} if lock_token else None,
             )
 
-    def set_state(self, value: Any):
-        self.set_resource(value, path=self.state_path, resource_type="state")
+    def set_state(self, value: Any, lock_token: Optional[str] = None):
+        self.set_resource(value, path=self.state_path, resource_type="state", lock_token=lock_token)
+
+    def acquire_state_lock(
+        self,
+        ttl_s: int = 60,
+        wait_ms: int = 0,
+        path: Optional[str] = None,
+    ) -> str:
+        """
+        Acquire the advisory lock of the state (or of the resource at path) so that concurrent
+        runs do not overwrite each other's state. Pass the returned lock token to set_state and
+        release it with release_state_lock
+        """
+        path = path or self.state_path
+        return self.post(
+            f"/w/{self.workspace}/resources/lock/{path}",
+            json={"ttl_s": ttl_s, "wait_ms": wait_ms},
+        ).json()["lock_token"]
+
+    def release_state_lock(self, lock_token: str, path: Optional[str] = None) -> None:
+        path = path or self.state_path
+        resp = self.client.delete(
+            f"/w/{self.workspace}/resources/lock/{path}",
+            params={"lock_token": lock_token},
+        )
+        try:
+            resp.raise_for_status()
+        except httpx.HTTPStatusError as err:
+            error = f"{err.request.url}: {err.response.status_code}, {err.response.text}"
+            logger.error(error)
+            raise Exception(error)
 
     def set_progress(self, value: int, job_id: Optional[str] = None):
         workspace = get_workspace()
@@ -957,11 +989,28 @@ def set_resource(path: str, value: Any, resource_type: str = "any") -> None:
 
 
 @init_global_client
-def set_state(value: Any) -> None:
+def set_state(value: Any, lock_token: Optional[str] = None) -> None:
+    """
+    Set the state, lock_token is required while the state is locked
+    """
+    return _client.set_state(value, lock_token=lock_token)
+
+
+@init_global_client
+def acquire_state_lock(ttl_s: int = 60, wait_ms: int = 0, path: Optional[str] = None) -> str:
+    """
+    Acquire the advisory lock of the state (or of the resource at path), waiting up to wait_ms
+    for the current holder to release it. Returns the lock token
+    """
+    return _client.acquire_state_lock(ttl_s=ttl_s, wait_ms=wait_ms, path=path)
+
+
+@init_global_client
+def release_state_lock(lock_token: str, path: Optional[str] = None) -> None:
     """
-    Set the state
+    Release the advisory lock of the state (or of the resource at path)
     """
-    return _client.set_state(value)
+    return _client.release_state_lock(lock_token, path=path)
 
 
 @init_global_client
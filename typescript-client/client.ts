@@ -358,11 +358,13 @@ export function getStatePath(): string {
  * @param path path of the resource to set, default to state path
  * @param value new value of the resource to set
  * @param initializeToTypeIfNotExist if the resource does not exist, initialize it with this type
+ * @param lockToken token of the lock held on the resource, required while it is locked
  */
 export async function setResource(
   value: any,
   path?: string,
-  initializeToTypeIfNotExist?: string
+  initializeToTypeIfNotExist?: string,
+  lockToken?: string
 ): Promise<void> {
   path = path ?? getStatePath();
   const mockedApi = await getMockedApi();
@@ -375,6 +377,7 @@ export async function setResource(
     await ResourceService.updateResourceValue({
       workspace,
       path,
+      lockToken,
       requestBody: { value },
     });
   } else if (initializeToTypeIfNotExist) {
@@ -401,9 +404,47 @@ export async function setInternalState(state: any): Promise<void> {
 /**
  * Set the state
  * @param state state to set
+ * @param lockToken token returned by acquireStateLock, required while the state is locked
  */
-export async function setState(state: any): Promise<void> {
-  await setResource(state, undefined, "state");
+export async function setState(state: any, lockToken?: string): Promise<void> {
+  await setResource(state, undefined, "state", lockToken);
+}
+
+/**
+ * Acquire the advisory lock of the state so that concurrent runs do not overwrite each other's
+ * state. Pass the returned lock token to setState and release it with releaseStateLock
+ * @param ttlS seconds after which the lock expires if not released
+ * @param waitMs wait up to this many milliseconds for the current holder to release the lock
+ * @param path path of the resource to lock, default to state path
+ * @returns the lock token
+ */
+export async function acquireStateLock(
+  ttlS: number = 60,
+  waitMs: number = 0,
+  path?: string
+): Promise<string> {
+  const lock = await ResourceService.acquireResourceLock({
+    workspace: getWorkspace(),
+    path: path ?? getStatePath(),
+    requestBody: { ttl_s: ttlS, wait_ms: waitMs },
+  });
+  return lock.lock_token;
+}
+
+/**
+ * Release the advisory lock of the state
+ * @param lockToken token returned by acquireStateLock
+ * @param path path of the locked resource, default to state path
+ */
+export async function releaseStateLock(
+  lockToken: string,
+  path?: string
+): Promise<void> {
+  await ResourceService.releaseResourceLock({
+    workspace: getWorkspace(),
+    path: path ?? getStatePath(),
+    lockToken,
+  });
 }
 
 /**